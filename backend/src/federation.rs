@@ -9,7 +9,7 @@ use futures_cpupool::CpuPool;
 use serde::Serialize;
 use serde_derive::Deserialize;
 use serde_derive::Serialize as SerDerive;
-use serde_json::Value as JsonValue;
+use serde_json::{Map, Value as JsonValue};
 use sodiumoxide::crypto::sign::SecretKey;
 
 #[derive(Clone)]
@@ -32,6 +32,7 @@ pub struct FederationData {
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
 struct Event {
     room_id: String,       // Room identifier
     sender: String,        // The ID of the user who has sent this event
@@ -49,6 +50,8 @@ struct Event {
     pub event_id: String,        // The event ID
     hashes: JsonValue, // Content hashes of the PDU, following the algorithm specified in `Signing Events`
     signatures: JsonValue, // Signatures for the PDU, following the algorithm specified in `Signing Events`
+    #[serde(flatten)]
+    unknown_fields: Map<String, JsonValue>, // Fields not defined above, kept so they are sent to the client
 }
 
 #[derive(Clone, Deserialize)]
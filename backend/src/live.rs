@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use actix::fut;
+use actix::prelude::*;
+use actix_web::error::BlockingError;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use futures::Future;
+
+use crate::postgres::{get_events_since, get_stream_position, room_exists, Database, DbError};
+
+// The interval between two polls of the database for newly persisted events
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tails the events of a room in the database and pushes them to the client of a WebSocket as
+/// soon as they are persisted, so that the frontend doesn't have to ask for the descendants of
+/// its latest events periodically.
+///
+/// Synapse gives each event it persists a position in a stream (`events.stream_ordering`), so
+/// the new events are the ones after the last position seen, which is polled for.
+pub struct LiveTail {
+    room_id: String,
+    db: web::Data<Database>,
+    since: i64,    // The position in the stream of the last event pushed
+    polling: bool, // Whether a poll of the database is in progress
+}
+
+impl LiveTail {
+    pub fn new(room_id: String, db: web::Data<Database>, since: i64) -> Self {
+        LiveTail {
+            room_id,
+            db,
+            since,
+            polling: false,
+        }
+    }
+
+    // Polls the database for the events persisted since the last poll and pushes them, unless
+    // the previous poll is still in progress
+    fn poll(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.polling {
+            return;
+        }
+
+        self.polling = true;
+
+        let f = get_events_since(
+            &self.room_id,
+            self.since,
+            &self.db.cpu_pool,
+            &self.db.pg_pool,
+        )
+        .into_actor(self)
+        .then(|result, tail, ctx| {
+            tail.polling = false;
+
+            match result {
+                Ok((response_object, position)) => {
+                    tail.since = position;
+
+                    if !response_object.is_empty() {
+                        ctx.text(
+                            serde_json::to_string(&response_object)
+                                .expect("Failed to serialize the response object"),
+                        );
+                    }
+                }
+                Err(_) => {
+                    // The client falls back to polling the descendants of its latest events
+                    ctx.close(Some(ws::CloseReason {
+                        code: ws::CloseCode::Error,
+                        description: Some("Error with the database".to_string()),
+                    }));
+                    ctx.stop();
+                }
+            }
+
+            fut::ok(())
+        });
+
+        ctx.spawn(f);
+    }
+}
+
+impl Actor for LiveTail {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(POLL_INTERVAL, |tail, ctx| tail.poll(ctx));
+    }
+}
+
+impl StreamHandler<ws::Message, ws::ProtocolError> for LiveTail {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Close(_) => ctx.stop(),
+            _ => {}
+        }
+    }
+}
+
+// Handler for the `/visualisations/live/{roomId}` request, which upgrades the connection to a
+// WebSocket through which the events persisted from now on are pushed
+pub fn live(
+    (req, stream, path, db): (
+        HttpRequest,
+        web::Payload,
+        web::Path<String>,
+        web::Data<Database>,
+    ),
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let room_id = path.into_inner();
+    let queried_room_id = room_id.clone();
+    let pool = db.pg_pool.clone();
+
+    // The position the tail starts from, `None` if the room doesn't exist
+    let since = web::block(move || -> Result<Option<i64>, DbError> {
+        if !room_exists(&queried_room_id, &pool)? {
+            return Ok(None);
+        }
+
+        Ok(Some(get_stream_position(&queried_room_id, &pool)?))
+    });
+
+    Box::new(since.then(move |result| match result {
+        Ok(Some(since)) => ws::start(LiveTail::new(room_id, db, since), &req, stream),
+        Ok(None) => Ok(HttpResponse::NotFound().body("This room doesn't exist")),
+        Err(BlockingError::Error(err)) => Err(err.into()),
+        Err(BlockingError::Canceled) => {
+            Ok(HttpResponse::InternalServerError().body("Error with the database"))
+        }
+    }))
+}
@@ -1,9 +1,13 @@
+extern crate actix;
 extern crate actix_web;
+extern crate actix_web_actors;
 extern crate awc;
 extern crate base64;
+extern crate env_logger;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate indolentjson;
+extern crate log;
 extern crate openssl;
 extern crate percent_encoding;
 extern crate r2d2_postgres;
@@ -12,7 +16,9 @@ extern crate serde_derive;
 extern crate serde_json;
 extern crate sodiumoxide;
 
+pub mod dsn;
 pub mod federation;
+pub mod live;
 pub mod postgres;
 
 use std::env::args;
@@ -25,9 +31,10 @@ use std::sync::{Arc, Mutex};
 use actix_web::{guard, web, App, HttpRequest, HttpResponse, HttpServer};
 use futures_cpupool::CpuPool;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
-use r2d2_postgres::{PostgresConnectionManager, TlsMode};
+use r2d2_postgres::PostgresConnectionManager;
 use sodiumoxide::crypto::sign::ed25519::{keypair_from_seed, SecretKey, Seed};
 
+use crate::dsn::{keyring_dsn, store_dsn, DatabaseConfig};
 use crate::federation::ancestors as federation_ancestors;
 use crate::federation::deepest as federation_deepest;
 use crate::federation::descendants as federation_descendants;
@@ -36,20 +43,49 @@ use crate::federation::serv_cert;
 use crate::federation::state as federation_state;
 use crate::federation::stop as federation_stop;
 use crate::federation::FederationData;
+use crate::live::live as pg_live;
 use crate::postgres::ancestors as pg_ancestors;
 use crate::postgres::deepest as pg_deepest;
 use crate::postgres::descendants as pg_descendants;
+use crate::postgres::event as pg_event;
+use crate::postgres::health as pg_health;
 use crate::postgres::state as pg_state;
 use crate::postgres::Database;
 
 fn main() -> std::io::Result<()> {
+    env_logger::init();
+
     let args: Vec<String> = args().collect();
 
-    if args.len() < 2 || (args[1] != "postgres" && args[1] != "federation") {
-        eprintln!("Usage: cargo run --release [postgres / federation]");
+    if args.len() < 2
+        || (args[1] != "postgres" && args[1] != "federation" && args[1] != "store-dsn")
+    {
+        eprintln!("Usage: cargo run --release [postgres / federation / store-dsn]");
         exit(-1);
     }
 
+    if args[1] == "store-dsn" {
+        if args.len() < 3 {
+            eprintln!("Usage: cargo run --release store-dsn <name> < dsn_file");
+            exit(-1);
+        }
+
+        // The DSN is read from the standard input so that its password isn't in the history
+        let mut dsn = String::new();
+        std::io::stdin().read_line(&mut dsn)?;
+
+        if let Err(e) = store_dsn(&args[2], dsn.trim()) {
+            eprintln!("Couldn't save the DSN: {}", e);
+            exit(-1);
+        }
+
+        println!(
+            "Saved the DSN in the keyring, use it with `postgres keyring:{}`",
+            args[2]
+        );
+        return Ok(());
+    }
+
     let mut ssl_builder =
         SslAcceptor::mozilla_intermediate(SslMethod::tls()).expect("Failed to create SSL builder");
     ssl_builder
@@ -60,24 +96,39 @@ fn main() -> std::io::Result<()> {
         .expect("Failed to load TLS certificate");
 
     if args[1] == "postgres" {
-        if args.len() < 5 {
-            eprintln!("Usage: cargo run --release postgres <db_addr> <db_username> <db_name>");
+        let config = match args.len() {
+            3 if args[2].starts_with("keyring:") => keyring_dsn(&args[2]["keyring:".len()..])
+                .and_then(|dsn| DatabaseConfig::from_dsn(&dsn)),
+            3 => DatabaseConfig::from_dsn(&args[2]),
+            n if n >= 5 => Ok(DatabaseConfig::from_parts(&args[2], &args[3], &args[4])),
+            _ => {
+                eprintln!("Usage: cargo run --release postgres <db_addr> <db_username> <db_name>");
+                eprintln!("   or: cargo run --release postgres <dsn>");
+                eprintln!("   or: cargo run --release postgres keyring:<name>");
+                exit(-1);
+            }
+        };
+        let config = config.unwrap_or_else(|e| {
+            eprintln!("Invalid DSN: {}", e);
             exit(-1);
-        }
-
-        let db_username = args[3].clone();
-        let db_addr = args[2].clone();
-        let db_name = args[4].clone();
+        });
+        let tls_mode = config.tls_mode().unwrap_or_else(|e| {
+            eprintln!("Failed to set up TLS: {}", e);
+            exit(-1);
+        });
 
         println!("Backend in postgres mode");
 
+        if config.settings.read_only {
+            println!("The transactions are read-only");
+        }
+
         let cpu_pool = CpuPool::new_num_cpus();
-        let manager = PostgresConnectionManager::new(
-            format!("postgres://{}@{}/{}", db_username, db_addr, db_name).as_str(),
-            TlsMode::None,
-        )
-        .unwrap();
-        let pg_pool = r2d2::Pool::new(manager).expect("Failed to create pool");
+        let manager = PostgresConnectionManager::new(config.url.as_str(), tls_mode).unwrap();
+        let pg_pool = r2d2::Pool::builder()
+            .connection_customizer(Box::new(config.settings.clone()))
+            .build(manager)
+            .expect("Failed to create pool");
 
         let db = web::Data::new(Database { cpu_pool, pg_pool });
 
@@ -97,12 +148,15 @@ fn main() -> std::io::Result<()> {
                             .body("")
                     }),
                 )
+                .service(web::resource("/visualisations/health").to_async(pg_health))
                 .service(web::resource("/visualisations/deepest/{roomId}").to_async(pg_deepest))
                 .service(web::resource("/visualisations/ancestors/{roomId}").to_async(pg_ancestors))
                 .service(
                     web::resource("/visualisations/descendants/{roomId}").to_async(pg_descendants),
                 )
                 .service(web::resource("/visualisations/state/{roomId}").to_async(pg_state))
+                .service(web::resource("/visualisations/event/{roomId}").to_async(pg_event))
+                .service(web::resource("/visualisations/live/{roomId}").to_async(pg_live))
                 .service(web::resource("/visualisations/stop/{roomId}").to(|| {
                     HttpResponse::Ok()
                         .header("Access-Control-Allow-Origin", "*")
@@ -162,6 +216,16 @@ fn main() -> std::io::Result<()> {
                             .body("")
                     }),
                 )
+                .service(web::resource("/visualisations/health").to(|| {
+                    HttpResponse::Ok()
+                        .header("Access-Control-Allow-Origin", "*")
+                        .header("Access-Control-Allow-Methods", "GET, POST")
+                        .header(
+                            "Access-Control-Allow-Headers",
+                            "Origin, X-Requested-With, Content-Type, Accept",
+                        )
+                        .body("OK")
+                }))
                 .service(
                     web::resource("/visualisations/deepest/{roomId}").to_async(federation_deepest),
                 )
@@ -1,22 +1,82 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
+use actix_web::error::{BlockingError, ResponseError};
 use actix_web::{web, Error, HttpResponse};
 use futures::future;
 use futures::Future;
 use futures_cpupool::CpuPool;
+use log::warn;
 use r2d2::Pool;
 use r2d2_postgres::postgres::error::Error as PgError;
 use r2d2_postgres::PostgresConnectionManager;
 use serde_derive::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{Map, Value as JsonValue};
 
 pub struct Database {
     pub cpu_pool: CpuPool,
     pub pg_pool: Pool<PostgresConnectionManager>,
 }
 
+// The errors of the requests to the database. The handlers answer them with a 503 when no
+// connection could be taken from the pool, since the database may only be overloaded, and with a
+// 500 otherwise.
+#[derive(Debug)]
+pub(crate) enum DbError {
+    Pool(r2d2::Error),       // No connection could be taken from the pool in time
+    Query(PgError),          // The query failed
+    Json(serde_json::Error), // The JSON of an event couldn't be deserialized
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Pool(err) => write!(f, "No connection to the database: {}", err),
+            DbError::Query(err) => write!(f, "Error with the database: {}", err),
+            DbError::Json(err) => write!(f, "Failed to deserialize the event: {}", err),
+        }
+    }
+}
+
+impl From<r2d2::Error> for DbError {
+    fn from(err: r2d2::Error) -> Self {
+        DbError::Pool(err)
+    }
+}
+
+impl From<PgError> for DbError {
+    fn from(err: PgError) -> Self {
+        DbError::Query(err)
+    }
+}
+
+impl From<serde_json::Error> for DbError {
+    fn from(err: serde_json::Error) -> Self {
+        DbError::Json(err)
+    }
+}
+
+impl ResponseError for DbError {
+    fn error_response(&self) -> HttpResponse {
+        let mut response = match self {
+            DbError::Pool(_) => HttpResponse::ServiceUnavailable(),
+            DbError::Query(_) | DbError::Json(_) => HttpResponse::InternalServerError(),
+        };
+
+        response
+            .header("Access-Control-Allow-Origin", "*")
+            .header("Access-Control-Allow-Methods", "GET, POST")
+            .header(
+                "Access-Control-Allow-Headers",
+                "Origin, X-Requested-With, Content-Type, Accept",
+            )
+            .body(self.to_string())
+    }
+}
+
 #[derive(Default, Clone, Deserialize, Serialize)]
-struct Event {
+#[serde(default)]
+pub(crate) struct Event {
     room_id: String,       // Room identifier
     sender: String,        // The ID of the user who has sent this event
     origin: String,        // The `server_name` of the homeserver which created this event
@@ -33,6 +93,8 @@ struct Event {
     pub event_id: String,        // The event ID
     hashes: JsonValue, // Content hashes of the PDU, following the algorithm specified in `Signing Events`
     signatures: JsonValue, // Signatures for the PDU, following the algorithm specified in `Signing Events`
+    #[serde(flatten)]
+    unknown_fields: Map<String, JsonValue>, // Fields not defined above, kept so they are sent to the client
 }
 
 #[derive(Clone, Deserialize)]
@@ -42,15 +104,106 @@ pub struct RequestQuery {
 }
 
 #[derive(Clone, Serialize)]
-struct ResponseObject {
+pub(crate) struct ResponseObject {
     events: Vec<Event>,
+    rejections: HashMap<String, String>, // The reasons of the rejection of the rejected events
+    soft_failed: Vec<String>,            // The IDs of the events which have soft-failed
+    stream_orderings: HashMap<String, i64>, // The positions of the events in the stream of the server
+    received_ts: HashMap<String, i64>,      // When the server received the events
+}
+
+impl ResponseObject {
+    // Builds the response for `events`, annotated with their rejections, soft-failures,
+    // positions in the stream of the server and the times it received them
+    pub(crate) fn with_annotations(
+        events: Vec<Event>,
+        pg_pool: &Pool<PostgresConnectionManager>,
+    ) -> Result<ResponseObject, DbError> {
+        let mut response = ResponseObject {
+            events,
+            rejections: HashMap::new(),
+            soft_failed: Vec::new(),
+            stream_orderings: HashMap::new(),
+            received_ts: HashMap::new(),
+        };
+
+        annotate(&mut response, pg_pool)?;
+
+        Ok(response)
+    }
+
+    // Tells whether the response has no events
+    pub(crate) fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+// Builds the response of the handlers for the events `ids`, once they are annotated. Their
+// bodies and their annotations are queried in a blocking task, like in the `event` handler, so
+// that the event loop isn't held by the database.
+fn annotated_response<I>(
+    ids: I,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    I: IntoIterator<Item = String> + Send + 'static,
+{
+    let pool = pg_pool.clone();
+
+    web::block(move || -> Result<ResponseObject, DbError> {
+        let events = get_event_bodies(ids, &pool)?;
+
+        ResponseObject::with_annotations(events, &pool)
+    })
+    .then(|result| -> Result<HttpResponse, Error> {
+        let response_object = match result {
+            Ok(response_object) => response_object,
+            Err(BlockingError::Error(err)) => return Err(err.into()),
+            Err(BlockingError::Canceled) => {
+                return Err(actix_web::error::ErrorInternalServerError(
+                    "Error with the database",
+                ))
+            }
+        };
+        let response_string = serde_json::to_string(&response_object)
+            .expect("Failed to serialize the response object");
+
+        Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .header("Access-Control-Allow-Origin", "*")
+            .header("Access-Control-Allow-Methods", "GET, POST")
+            .header(
+                "Access-Control-Allow-Headers",
+                "Origin, X-Requested-With, Content-Type, Accept",
+            )
+            .body(response_string))
+    })
+}
+
+// Makes requests to the database to get the bodies of the events `ids`
+fn get_event_bodies(
+    ids: impl IntoIterator<Item = String>,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> Result<Vec<Event>, DbError> {
+    ids.into_iter()
+        .map(|id| -> Result<Event, DbError> {
+            let json = get_json(&id, pg_pool)?.expect("Failed to get event's JSON");
+
+            Ok(serde_json::from_value(json)?)
+        })
+        .collect()
 }
 
 // Handler for the `/visualisations/deepest/{roomId}` request
 pub fn deepest(
     (path, db): (web::Path<String>, web::Data<Database>),
 ) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
-    if !room_exists(&path, &db.pg_pool) {
+    let exists = match room_exists(&path, &db.pg_pool) {
+        Ok(exists) => exists,
+        Err(err) => return Box::new(future::err(err.into())),
+    };
+
+    if !exists {
         return Box::new(futures::future::ok(
             HttpResponse::NotFound()
                 .header("Access-Control-Allow-Origin", "*")
@@ -63,49 +216,43 @@ pub fn deepest(
         ));
     }
 
-    Box::new(get_deepest_events(&path, &db.cpu_pool, &db.pg_pool).then(
-        move |result| match result {
-            Ok(deepest_events) => {
-                let event_bodies: Vec<Event> = deepest_events
-                    .iter()
-                    .map(|id| {
-                        let ev: Event = serde_json::from_value(
-                            get_json(id, &db.pg_pool).expect("Failed to get event's JSON"),
-                        )
-                        .expect("Failed to deserialize Event");
-
-                        ev
-                    })
-                    .collect();
+    Box::new(
+        get_deepest_events(&path, &db.cpu_pool, &db.pg_pool)
+            .map_err(Error::from)
+            .and_then(move |event_ids| annotated_response(event_ids, &db.pg_pool)),
+    )
+}
+
+// Handler for the `/visualisations/health` request, which checks that the database answers
+pub fn health(db: web::Data<Database>) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let pool = db.pg_pool.clone();
 
-                let response_object = ResponseObject {
-                    events: event_bodies,
+    Box::new(
+        db.cpu_pool
+            .spawn_fn(move || -> Result<bool, ()> {
+                Ok(match pool.get() {
+                    Ok(client) => client.query("SELECT 1", &[]).is_ok(),
+                    Err(_) => false,
+                })
+            })
+            .then(|result| {
+                let up = result.unwrap_or(false);
+                let mut response = if up {
+                    HttpResponse::Ok()
+                } else {
+                    HttpResponse::ServiceUnavailable()
                 };
-                let response_string = serde_json::to_string(&response_object)
-                    .expect("Failed to serialize the response object");
 
-                HttpResponse::Ok()
-                    .content_type("application/json")
-                    .header("Access-Control-Allow-Origin", "*")
-                    .header("Access-Control-Allow-Methods", "GET, POST")
-                    .header(
-                        "Access-Control-Allow-Headers",
-                        "Origin, X-Requested-With, Content-Type, Accept",
-                    )
-                    .body(response_string)
-            }
-            Err(_) => {
-                return HttpResponse::InternalServerError()
+                response
                     .header("Access-Control-Allow-Origin", "*")
                     .header("Access-Control-Allow-Methods", "GET, POST")
                     .header(
                         "Access-Control-Allow-Headers",
                         "Origin, X-Requested-With, Content-Type, Accept",
                     )
-                    .body("Error with the database");
-            }
-        },
-    ))
+                    .body(if up { "OK" } else { "Error with the database" })
+            }),
+    )
 }
 
 // Handler for the `/visualisations/ancestors/{roomId}` request
@@ -118,7 +265,12 @@ pub fn ancestors(
 ) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
     let limit = query.limit.unwrap_or(10);
 
-    if !room_exists(&path, &db.pg_pool) {
+    let exists = match room_exists(&path, &db.pg_pool) {
+        Ok(exists) => exists,
+        Err(err) => return Box::new(future::err(err.into())),
+    };
+
+    if !exists {
         return Box::new(futures::future::ok(
             HttpResponse::NotFound()
                 .header("Access-Control-Allow-Origin", "*")
@@ -140,49 +292,9 @@ pub fn ancestors(
         .collect();
 
     Box::new(
-        get_ancestor_events(&path, &db.cpu_pool, &db.pg_pool, &deepest_events, limit).then(
-            move |result| match result {
-                Ok(ancestor_events) => {
-                    let event_bodies: Vec<Event> = ancestor_events
-                        .iter()
-                        .map(|id| {
-                            let ev: Event = serde_json::from_value(
-                                get_json(id, &db.pg_pool).expect("Failed to get event's JSON"),
-                            )
-                            .expect("Failed to deserialize Event");
-
-                            ev
-                        })
-                        .collect();
-
-                    let response_object = ResponseObject {
-                        events: event_bodies,
-                    };
-                    let response_string = serde_json::to_string(&response_object)
-                        .expect("Failed to serialize the response object");
-
-                    HttpResponse::Ok()
-                        .content_type("application/json")
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Access-Control-Allow-Methods", "GET, POST")
-                        .header(
-                            "Access-Control-Allow-Headers",
-                            "Origin, X-Requested-With, Content-Type, Accept",
-                        )
-                        .body(response_string)
-                }
-                Err(_) => {
-                    return HttpResponse::InternalServerError()
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Access-Control-Allow-Methods", "GET, POST")
-                        .header(
-                            "Access-Control-Allow-Headers",
-                            "Origin, X-Requested-With, Content-Type, Accept",
-                        )
-                        .body("Error with the database");
-                }
-            },
-        ),
+        get_ancestor_events(&path, &db.cpu_pool, &db.pg_pool, &deepest_events, limit)
+            .map_err(Error::from)
+            .and_then(move |event_ids| annotated_response(event_ids, &db.pg_pool)),
     )
 }
 
@@ -196,7 +308,12 @@ pub fn descendants(
 ) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
     let limit = query.limit.unwrap_or(10);
 
-    if !room_exists(&path, &db.pg_pool) {
+    let exists = match room_exists(&path, &db.pg_pool) {
+        Ok(exists) => exists,
+        Err(err) => return Box::new(future::err(err.into())),
+    };
+
+    if !exists {
         return Box::new(futures::future::ok(
             HttpResponse::NotFound()
                 .header("Access-Control-Allow-Origin", "*")
@@ -218,49 +335,9 @@ pub fn descendants(
         .collect();
 
     Box::new(
-        get_descendants_events(&path, &db.cpu_pool, &db.pg_pool, &highest_events, limit).then(
-            move |result| match result {
-                Ok(descendant_events) => {
-                    let event_bodies: Vec<Event> = descendant_events
-                        .iter()
-                        .map(|id| {
-                            let ev: Event = serde_json::from_value(
-                                get_json(id, &db.pg_pool).expect("Failed to get event's JSON"),
-                            )
-                            .expect("Failed to deserialize Event");
-
-                            ev
-                        })
-                        .collect();
-
-                    let response_object = ResponseObject {
-                        events: event_bodies,
-                    };
-                    let response_string = serde_json::to_string(&response_object)
-                        .expect("Failed to serialize the response object");
-
-                    HttpResponse::Ok()
-                        .content_type("application/json")
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Access-Control-Allow-Methods", "GET, POST")
-                        .header(
-                            "Access-Control-Allow-Headers",
-                            "Origin, X-Requested-With, Content-Type, Accept",
-                        )
-                        .body(response_string)
-                }
-                Err(_) => {
-                    return HttpResponse::InternalServerError()
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Access-Control-Allow-Methods", "GET, POST")
-                        .header(
-                            "Access-Control-Allow-Headers",
-                            "Origin, X-Requested-With, Content-Type, Accept",
-                        )
-                        .body("Error with the database");
-                }
-            },
-        ),
+        get_descendants_events(&path, &db.cpu_pool, &db.pg_pool, &highest_events, limit)
+            .map_err(Error::from)
+            .and_then(move |event_ids| annotated_response(event_ids, &db.pg_pool)),
     )
 }
 
@@ -274,7 +351,12 @@ pub fn state(
     let from_event = &query.from;
     let db_clone = db.clone();
 
-    if !room_exists(&path, &db.pg_pool) {
+    let exists = match room_exists(&path, &db.pg_pool) {
+        Ok(exists) => exists,
+        Err(err) => return Box::new(future::err(err.into())),
+    };
+
+    if !exists {
         return Box::new(futures::future::ok(
             HttpResponse::NotFound()
                 .header("Access-Control-Allow-Origin", "*")
@@ -289,70 +371,176 @@ pub fn state(
 
     Box::new(
         get_state_group(&db.cpu_pool, &db.pg_pool, from_event)
-            .map_err(|err| {
-                actix_web::error::ErrorInternalServerError(format!(
-                    "Error with the database: {}",
-                    err
-                ))
-            })
+            .map_err(Error::from)
             .and_then(move |state_group| match state_group {
                 Some(sg) => future::Either::A(
-                    get_state_event_ids(&db_clone.cpu_pool, &db_clone.pg_pool, sg).map_err(|err| {
-                        actix_web::error::ErrorInternalServerError(format!(
-                            "Error with the database: {}",
-                            err
-                        ))
-                    }),
+                    get_state_event_ids(&db_clone.cpu_pool, &db_clone.pg_pool, sg)
+                        .map_err(Error::from),
                 ),
                 None => future::Either::B(future::err(actix_web::error::ErrorNotFound(
                     "There is no such event",
                 ))),
             })
-            .and_then(move |event_ids| {
-                let event_bodies: Vec<Event> = event_ids
-                    .iter()
-                    .map(|id| {
-                        let ev: Event = serde_json::from_value(
-                            get_json(id, &db.pg_pool).expect("Failed to get event's JSON"),
-                        )
-                        .expect("Failed to deserialize Event");
-
-                        ev
-                    })
-                    .collect();
+            .and_then(move |event_ids| annotated_response(event_ids, &db.pg_pool)),
+    )
+}
 
-                let response_object = ResponseObject {
-                    events: event_bodies,
-                };
-                let response_string = serde_json::to_string(&response_object)
-                    .expect("Failed to serialize the response object");
+// Handler for the `/visualisations/event/{roomId}` request
+pub fn event(
+    (path, query, db): (
+        web::Path<String>,
+        web::Query<RequestQuery>,
+        web::Data<Database>,
+    ),
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let room_id = path.into_inner();
+    let event_id = query.from.clone();
+    let pool = db.pg_pool.clone();
 
-                future::ok(
-                    HttpResponse::Ok()
-                        .content_type("application/json")
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Access-Control-Allow-Methods", "GET, POST")
-                        .header(
-                            "Access-Control-Allow-Headers",
-                            "Origin, X-Requested-With, Content-Type, Accept",
-                        )
-                        .body(response_string),
+    Box::new(
+        web::block(move || -> Result<EventLookup, DbError> {
+            if !room_exists(&room_id, &pool)? {
+                return Ok(EventLookup::NoRoom);
+            }
+
+            let json = match get_room_event_json(&room_id, &event_id, &pool)? {
+                Some(json) => json,
+                None => return Ok(EventLookup::NoEvent),
+            };
+            let ev: Event = serde_json::from_str(&json)?;
+
+            ResponseObject::with_annotations(vec![ev], &pool).map(EventLookup::Found)
+        })
+        .then(|result| -> Result<HttpResponse, Error> {
+            let (mut response, body) = match result {
+                Ok(EventLookup::Found(response_object)) => {
+                    let mut response = HttpResponse::Ok();
+                    response.content_type("application/json");
+
+                    (
+                        response,
+                        serde_json::to_string(&response_object)
+                            .expect("Failed to serialize the response object"),
+                    )
+                }
+                Ok(EventLookup::NoRoom) => (
+                    HttpResponse::NotFound(),
+                    "This room doesn't exist".to_string(),
+                ),
+                Ok(EventLookup::NoEvent) => (
+                    HttpResponse::NotFound(),
+                    "There is no such event in this room".to_string(),
+                ),
+                Err(BlockingError::Error(err)) => return Err(err.into()),
+                Err(BlockingError::Canceled) => (
+                    HttpResponse::InternalServerError(),
+                    "Error with the database".to_string(),
+                ),
+            };
+
+            Ok(response
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Access-Control-Allow-Methods", "GET, POST")
+                .header(
+                    "Access-Control-Allow-Headers",
+                    "Origin, X-Requested-With, Content-Type, Accept",
                 )
-            }),
+                .body(body))
+        }),
     )
 }
 
+// What the `/visualisations/event/{roomId}` request found
+enum EventLookup {
+    NoRoom,
+    NoEvent,
+    Found(ResponseObject),
+}
+
 // Makes a request to the database to check whether the room `room_id` exists
-fn room_exists(room_id: &str, pg_pool: &Pool<PostgresConnectionManager>) -> bool {
+pub(crate) fn room_exists(
+    room_id: &str,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> Result<bool, DbError> {
     let pool = pg_pool.clone();
-    let client = pool.get().unwrap();
+    let client = pool.get()?;
 
     let nb_ev = client
-        .query("SELECT * FROM events WHERE room_id = $1", &[&room_id])
-        .unwrap()
+        .query("SELECT * FROM events WHERE room_id = $1", &[&room_id])?
         .len();
 
-    nb_ev > 0
+    Ok(nb_ev > 0)
+}
+
+// Makes a request to the database to get the position of the latest event of the room `room_id`
+// in the stream of the events persisted by the homeserver
+pub(crate) fn get_stream_position(
+    room_id: &str,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> Result<i64, DbError> {
+    let pool = pg_pool.clone();
+    let client = pool.get()?;
+
+    let position: Option<i64> = client
+        .query(
+            "SELECT MAX(stream_ordering) FROM events WHERE room_id = $1",
+            &[&room_id],
+        )?
+        .iter()
+        .next()
+        .and_then(|row| row.get("max"));
+
+    Ok(position.unwrap_or(0))
+}
+
+// Makes a request to the database to get the events of the room `room_id` persisted after the
+// position `since` in the stream of the events, annotated like any response, along with the
+// position of the last of them. The events whose JSON can't be deserialised are left out.
+pub(crate) fn get_events_since(
+    room_id: &str,
+    since: i64,
+    cpu_pool: &CpuPool,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> impl Future<Item = (ResponseObject, i64), Error = DbError> {
+    let room_id = room_id.to_string();
+    let pool = pg_pool.clone();
+
+    let f = cpu_pool.spawn_fn(move || -> Result<_, DbError> {
+        let client = pool.get()?;
+
+        let rows = client.query(
+            "SELECT e.event_id, e.stream_ordering, j.json FROM events AS e
+            JOIN event_json AS j USING (event_id)
+            WHERE e.room_id = $1 AND e.stream_ordering > $2
+            ORDER BY e.stream_ordering",
+            &[&room_id, &since],
+        )?;
+
+        let position = rows
+            .iter()
+            .last()
+            .map(|row| row.get("stream_ordering"))
+            .unwrap_or(since);
+        let events: Vec<Event> = rows
+            .iter()
+            .filter_map(|row| {
+                let event_id: String = row.get("event_id");
+                let json: String = row.get("json");
+
+                match serde_json::from_str(&json) {
+                    Ok(ev) => Some(ev),
+                    Err(err) => {
+                        warn!("Skipping the event {}: {}", event_id, err);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Ok((ResponseObject::with_annotations(events, &pool)?, position))
+    });
+
+    f
 }
 
 // Makes requests to the database to get the events with the greatest depth of the room `room_id`
@@ -360,19 +548,18 @@ fn get_deepest_events(
     room_id: &str,
     cpu_pool: &CpuPool,
     pg_pool: &Pool<PostgresConnectionManager>,
-) -> impl Future<Item = Vec<String>, Error = PgError> {
+) -> impl Future<Item = Vec<String>, Error = DbError> {
     let room_id = room_id.to_string();
     let pool = pg_pool.clone();
 
-    let f = cpu_pool.spawn_fn(move || -> Result<_, PgError> {
-        let client = pool.get().unwrap();
+    let f = cpu_pool.spawn_fn(move || -> Result<_, DbError> {
+        let client = pool.get()?;
 
         let max_depth: i64 = client
             .query(
                 "SELECT MAX(depth) FROM events WHERE room_id = $1",
                 &[&room_id],
-            )
-            .unwrap()
+            )?
             .iter()
             .next()
             .expect("Failed to get max_depth")
@@ -382,8 +569,7 @@ fn get_deepest_events(
             .query(
                 "SELECT event_id FROM events WHERE room_id = $1 AND depth = $2",
                 &[&room_id, &max_depth],
-            )
-            .unwrap()
+            )?
             .iter()
             .map(|row| row.get("event_id"))
             .collect())
@@ -399,12 +585,12 @@ fn get_ancestor_events(
     pg_pool: &Pool<PostgresConnectionManager>,
     deepest_events: &Vec<String>,
     limit: usize,
-) -> impl Future<Item = HashSet<String>, Error = PgError> {
+) -> impl Future<Item = HashSet<String>, Error = DbError> {
     let room_id = room_id.to_string();
     let pool = pg_pool.clone();
     let deepest_events = deepest_events.clone();
 
-    let f = cpu_pool.spawn_fn(move || -> Result<_, PgError> {
+    let f = cpu_pool.spawn_fn(move || -> Result<_, DbError> {
         let mut seen_events: HashSet<String> = HashSet::new();
         let mut front: HashSet<String> = deepest_events.iter().cloned().collect();
         let mut event_results: HashSet<String> = HashSet::new();
@@ -413,7 +599,7 @@ fn get_ancestor_events(
             let mut new_front: HashSet<String> = HashSet::new();
 
             for event_id in front.iter() {
-                let client = pool.get().unwrap();
+                let client = pool.get()?;
 
                 let query_limit = limit - event_results.len();
 
@@ -421,8 +607,7 @@ fn get_ancestor_events(
                     .query(
                         "SELECT prev_event_id FROM event_edges WHERE room_id = $1 AND event_id = $2 AND is_state = False LIMIT $3",
                         &[&room_id, &event_id, &(query_limit as i64)],
-                    )
-                    .unwrap()
+                    )?
                     .iter()
                     .map(|row| row.get("prev_event_id"))
                     .filter(|id| !seen_events.contains(id))
@@ -451,12 +636,12 @@ fn get_descendants_events(
     pg_pool: &Pool<PostgresConnectionManager>,
     highest_events: &Vec<String>,
     limit: usize,
-) -> impl Future<Item = HashSet<String>, Error = PgError> {
+) -> impl Future<Item = HashSet<String>, Error = DbError> {
     let room_id = room_id.to_string();
     let pool = pg_pool.clone();
     let highest_events = highest_events.clone();
 
-    let f = cpu_pool.spawn_fn(move || -> Result<_, PgError> {
+    let f = cpu_pool.spawn_fn(move || -> Result<_, DbError> {
         let mut seen_events: HashSet<String> = HashSet::new();
         let mut front: HashSet<String> = highest_events.iter().cloned().collect();
         let mut event_results: HashSet<String> = HashSet::new();
@@ -465,7 +650,7 @@ fn get_descendants_events(
             let mut new_front: HashSet<String> = HashSet::new();
 
             for event_id in front.iter() {
-                let client = pool.get().unwrap();
+                let client = pool.get()?;
 
                 let query_limit = limit - event_results.len();
 
@@ -473,8 +658,7 @@ fn get_descendants_events(
                     .query(
                         "SELECT event_id FROM event_edges WHERE room_id = $1 AND prev_event_id = $2 AND is_state = False LIMIT $3",
                         &[&room_id, &event_id, &(query_limit as i64)],
-                    )
-                    .unwrap()
+                    )?
                     .iter()
                     .map(|row| row.get("event_id"))
                     .filter(|id| !seen_events.contains(id))
@@ -500,19 +684,18 @@ fn get_state_group(
     cpu_pool: &CpuPool,
     pg_pool: &Pool<PostgresConnectionManager>,
     event: &str,
-) -> impl Future<Item = Option<i64>, Error = PgError> {
+) -> impl Future<Item = Option<i64>, Error = DbError> {
     let pool = pg_pool.clone();
     let event = event.to_string();
 
-    let f = cpu_pool.spawn_fn(move || -> Result<_, PgError> {
-        let client = pool.get().unwrap();
+    let f = cpu_pool.spawn_fn(move || -> Result<_, DbError> {
+        let client = pool.get()?;
 
         let state_group: Option<i64> = client
             .query(
                 "SELECT state_group FROM event_to_state_groups WHERE event_id = $1",
                 &[&event],
-            )
-            .unwrap()
+            )?
             .iter()
             .next()
             .map(|row| row.get("state_group"));
@@ -527,11 +710,11 @@ fn get_state_event_ids(
     cpu_pool: &CpuPool,
     pg_pool: &Pool<PostgresConnectionManager>,
     state_group: i64,
-) -> impl Future<Item = Vec<String>, Error = PgError> {
+) -> impl Future<Item = Vec<String>, Error = DbError> {
     let pool = pg_pool.clone();
 
-    let f = cpu_pool.spawn_fn(move || -> Result<_, PgError> {
-        let client = pool.get().unwrap();
+    let f = cpu_pool.spawn_fn(move || -> Result<_, DbError> {
+        let client = pool.get()?;
 
         let event_ids: Vec<String> = client
             .query(
@@ -549,8 +732,7 @@ fn get_state_event_ids(
                     SELECT state_group FROM state
                 )",
                 &[&state_group],
-            )
-            .unwrap()
+            )?
             .iter()
             .map(|row| row.get("event_id"))
             .collect();
@@ -562,16 +744,98 @@ fn get_state_event_ids(
 }
 
 // Makes a request to the database to get the JSON body of the event `id`
-fn get_json(id: &str, pg_pool: &Pool<PostgresConnectionManager>) -> Option<JsonValue> {
+pub(crate) fn get_json(
+    id: &str,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> Result<Option<JsonValue>, DbError> {
     let pool = pg_pool.clone();
-    let client = pool.get().unwrap();
+    let client = pool.get()?;
 
     let json_str: Option<String> = client
-        .query("SELECT json FROM event_json WHERE event_id = $1", &[&id])
-        .unwrap()
+        .query("SELECT json FROM event_json WHERE event_id = $1", &[&id])?
         .iter()
         .next()
         .map(|row| row.get("json"));
 
-    json_str.map(|json_str| serde_json::from_str(&json_str).expect("Failed to deserialize Event"))
+    match json_str {
+        Some(json_str) => Ok(Some(serde_json::from_str(&json_str)?)),
+        None => Ok(None),
+    }
+}
+
+// Makes a request to the database to get the JSON body of the event `id`, if it belongs to the
+// room `room_id`
+fn get_room_event_json(
+    room_id: &str,
+    id: &str,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> Result<Option<String>, DbError> {
+    let pool = pg_pool.clone();
+    let client = pool.get()?;
+
+    let rows = client.query(
+        "SELECT json FROM event_json WHERE event_id = $1 AND room_id = $2",
+        &[&id, &room_id],
+    )?;
+
+    Ok(rows.iter().next().map(|row| row.get("json")))
+}
+
+// Makes a request to the database to annotate the events of `response` with the reasons of the
+// rejection of those which have been rejected, the IDs of those which have soft-failed, their
+// positions in the stream of the server, which tell the order in which they were persisted, and
+// when the server received them, if it is known
+fn annotate(
+    response: &mut ResponseObject,
+    pg_pool: &Pool<PostgresConnectionManager>,
+) -> Result<(), DbError> {
+    let pool = pg_pool.clone();
+    let client = pool.get()?;
+    let ids: Vec<&str> = response
+        .events
+        .iter()
+        .map(|ev| ev.event_id.as_str())
+        .collect();
+
+    let rows = client.query(
+        "SELECT j.event_id, j.internal_metadata, r.reason, e.stream_ordering, e.received_ts
+        FROM event_json AS j
+        LEFT JOIN rejections AS r USING (event_id)
+        LEFT JOIN events AS e USING (event_id)
+        WHERE j.event_id = ANY($1)",
+        &[&ids],
+    )?;
+
+    for row in rows.iter() {
+        let event_id: String = row.get("event_id");
+        let internal_metadata: String = row.get("internal_metadata");
+        let reason: Option<String> = row.get("reason");
+        let stream_ordering: Option<i64> = row.get("stream_ordering");
+        let received_ts: Option<i64> = row.get("received_ts");
+
+        let is_soft_failed = serde_json::from_str::<JsonValue>(&internal_metadata)
+            .ok()
+            .and_then(|metadata| metadata["soft_failed"].as_bool())
+            .unwrap_or(false);
+
+        if is_soft_failed {
+            response.soft_failed.push(event_id.clone());
+        }
+
+        if let Some(reason) = reason {
+            response.rejections.insert(event_id.clone(), reason);
+        }
+
+        if let Some(stream_ordering) = stream_ordering {
+            response
+                .stream_orderings
+                .insert(event_id.clone(), stream_ordering);
+        }
+
+        if let Some(received_ts) = received_ts {
+            response.received_ts.insert(event_id, received_ts);
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,479 @@
+//! End-to-end tests against a Synapse running in a container along with its PostgreSQL database.
+//!
+//! They create a room in which several users send messages on top of the same event so that its
+//! DAG forks, and check that the DAGs built by the model of the frontend from the events
+//! retrieved through the backend in "postgres mode" and through the CS API match the one stored
+//! in the database.
+//!
+//! These tests need Docker and are only built with the `integration-tests` feature:
+//! `cargo test --features integration-tests`.
+
+#![cfg(feature = "integration-tests")]
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::Duration;
+
+use matrix_dag::cs_api::{self, MessagesResponse, SyncResponse, CLIENT_API_PREFIX};
+use matrix_dag::dag::RoomEvents;
+use matrix_dag::source::SourceEvents;
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::rsa::Rsa;
+use openssl::x509::{X509NameBuilder, X509};
+use percent_encoding::{utf8_percent_encode, USERINFO_ENCODE_SET};
+use r2d2_postgres::postgres::{Connection, TlsMode};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value as JsonValue};
+use testcontainers::clients::Cli;
+use testcontainers::images::generic::{GenericImage, WaitFor};
+use testcontainers::{Docker, RunArgs};
+
+const NETWORK: &str = "matrix-visualisations-tests";
+const DB_HOST: &str = "synapse-db";
+const DB_NAME: &str = "synapse";
+const BACKEND_URL: &str = "https://localhost:8482";
+const USERS: usize = 3;
+
+// The version of Synapse the tests run against. It must serve the v3 endpoints of the CS API,
+// which the frontend calls, and stage the push actions of the events in
+// `event_push_actions_staging` before persisting them, which `fork` relies on.
+const SYNAPSE_IMAGE: &str = "matrixdotorg/synapse:v1.98.0";
+
+// The DAG of a room as a map from the IDs of its events to the IDs of their `prev_events`
+type Dag = HashMap<String, HashSet<String>>;
+
+#[test]
+fn fetched_dags_match_the_database() {
+    let docker = Cli::default();
+
+    let db = docker.run_with_args(
+        GenericImage::new("postgres:13")
+            .with_env_var("POSTGRES_DB", DB_NAME)
+            .with_env_var("POSTGRES_PASSWORD", "synapse")
+            .with_env_var("POSTGRES_HOST_AUTH_METHOD", "trust")
+            .with_env_var(
+                "POSTGRES_INITDB_ARGS",
+                "--encoding=UTF-8 --lc-collate=C --lc-ctype=C",
+            )
+            .with_wait_for(WaitFor::message_on_stderr(
+                "database system is ready to accept connections",
+            )),
+        RunArgs::default().with_network(NETWORK).with_name(DB_HOST),
+    );
+    // The image doesn't generate its configuration from environment variables anymore
+    let synapse_dir = synapse_dir();
+    let synapse = docker.run_with_args(
+        GenericImage::new(SYNAPSE_IMAGE)
+            .with_volume(synapse_dir.to_str().unwrap(), "/data")
+            .with_env_var("SYNAPSE_CONFIG_PATH", "/data/homeserver.yaml")
+            .with_wait_for(WaitFor::message_on_stderr(
+                "Synapse now listening on TCP port 8008",
+            )),
+        RunArgs::default().with_network(NETWORK),
+    );
+
+    let db_addr = format!("localhost:{}", db.get_host_port(5432).unwrap());
+    let hs_url = format!("http://localhost:{}", synapse.get_host_port(8008).unwrap());
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .expect("Failed to build the HTTP client");
+
+    let tokens: Vec<String> = (0..USERS)
+        .map(|i| register(&client, &hs_url, &format!("user{}", i)))
+        .collect();
+    let room_id = create_room(&client, &hs_url, &tokens[0]);
+
+    for token in &tokens[1..] {
+        join_room(&client, &hs_url, token, &room_id);
+    }
+
+    let db_url = format!("postgres://postgres@{}/{}", db_addr, DB_NAME);
+    fork(&client, &hs_url, &db_url, &tokens, &room_id);
+
+    // A last message merges the branches of the fork
+    send_message(&client, &hs_url, &tokens[0], &room_id, "merge");
+
+    let expected = database_dag(&db_url, &room_id);
+    assert!(has_fork(&expected), "Failed to create a fork in the DAG");
+
+    let backend_dir = backend_dir();
+    let _backend = BackendProcess::start(&backend_dir, &db_addr);
+
+    assert_eq!(structure(&backend_dag(&client, &room_id)), expected);
+    assert_eq!(
+        structure(&cs_dag(&client, &hs_url, &tokens[0], &room_id)),
+        expected
+    );
+
+    fs::remove_dir_all(backend_dir).ok();
+    fs::remove_dir_all(synapse_dir).ok();
+}
+
+// Registers the user `username` and returns its access token
+fn register(client: &Client, hs_url: &str, username: &str) -> String {
+    let url = format!("{}{}/register", hs_url, CLIENT_API_PREFIX);
+    let mut body = json!({ "username": username, "password": "password" });
+
+    // The first request only starts the user-interactive authentication
+    let res: JsonValue = client
+        .post(&url)
+        .json(&body)
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    body["auth"] = json!({ "type": "m.login.dummy", "session": res["session"] });
+
+    let res: JsonValue = send_with_retries(|| client.post(&url).json(&body));
+
+    res["access_token"].as_str().unwrap().to_string()
+}
+
+fn create_room(client: &Client, hs_url: &str, token: &str) -> String {
+    let url = format!("{}{}/createRoom", hs_url, CLIENT_API_PREFIX);
+    let res: JsonValue = send_with_retries(|| {
+        client
+            .post(&url)
+            .bearer_auth(token)
+            .json(&json!({ "preset": "public_chat" }))
+    });
+
+    res["room_id"].as_str().unwrap().to_string()
+}
+
+fn join_room(client: &Client, hs_url: &str, token: &str, room_id: &str) {
+    let url = format!("{}{}/join/{}", hs_url, CLIENT_API_PREFIX, room_id);
+
+    send_with_retries(|| client.post(&url).bearer_auth(token).json(&json!({})));
+}
+
+fn send_message(client: &Client, hs_url: &str, token: &str, room_id: &str, txn_id: &str) {
+    let url = format!(
+        "{}{}/rooms/{}/send/m.room.message/{}",
+        hs_url, CLIENT_API_PREFIX, room_id, txn_id
+    );
+    let body = json!({ "msgtype": "m.text", "body": txn_id });
+
+    send_with_retries(|| client.put(&url).bearer_auth(token).json(&body));
+}
+
+// Makes every user send a message on top of the same event. Synapse picks the `prev_events` of
+// an event when it creates it, and only stages its push actions before persisting it: the staging
+// table is locked until every user's event waits for it, so that none of them can become a
+// `prev_event` of the others.
+fn fork(client: &Client, hs_url: &str, db_url: &str, tokens: &[String], room_id: &str) {
+    let conn = Connection::connect(db_url, TlsMode::None).expect("Failed to connect to the DB");
+    let lock = conn.transaction().unwrap();
+    lock.execute(
+        "LOCK TABLE event_push_actions_staging IN EXCLUSIVE MODE",
+        &[],
+    )
+    .unwrap();
+
+    let senders: Vec<_> = tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| {
+            let (client, hs_url, token, room_id) = (
+                client.clone(),
+                hs_url.to_string(),
+                token.clone(),
+                room_id.to_string(),
+            );
+            let txn_id = format!("fork-{}", i);
+
+            thread::spawn(move || send_message(&client, &hs_url, &token, &room_id, &txn_id))
+        })
+        .collect();
+
+    let waiting = Connection::connect(db_url, TlsMode::None).expect("Failed to connect to the DB");
+
+    while waiting_events(&waiting) < tokens.len() {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    lock.commit().unwrap();
+
+    for sender in senders {
+        sender.join().expect("A sender panicked");
+    }
+}
+
+// Returns the number of events waiting for the lock on the staging table of the push actions
+fn waiting_events(conn: &Connection) -> usize {
+    let rows = conn
+        .query(
+            "SELECT COUNT(*) AS waiting FROM pg_locks
+            WHERE relation = 'event_push_actions_staging'::regclass AND NOT granted",
+            &[],
+        )
+        .unwrap();
+    let waiting: i64 = rows.get(0).get("waiting");
+
+    waiting as usize
+}
+
+// Sends the request built by `request` again as long as Synapse rate-limits it
+fn send_with_retries<F>(request: F) -> JsonValue
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    loop {
+        let mut res = request().send().expect("Failed to send the request");
+        let body: JsonValue = res.json().unwrap_or(JsonValue::Null);
+
+        match res.status() {
+            StatusCode::TOO_MANY_REQUESTS => {
+                let delay = body["retry_after_ms"].as_u64().unwrap_or(1000);
+
+                thread::sleep(Duration::from_millis(delay));
+            }
+            status if status.is_success() => return body,
+            status => panic!("{}: {}", status, body),
+        }
+    }
+}
+
+// Reads the DAG of the room `room_id` straight from the database of Synapse
+fn database_dag(db_url: &str, room_id: &str) -> Dag {
+    let conn = Connection::connect(db_url, TlsMode::None).expect("Failed to connect to the DB");
+    let mut dag = Dag::new();
+
+    for row in &conn
+        .query(
+            "SELECT event_id FROM events WHERE room_id = $1",
+            &[&room_id],
+        )
+        .unwrap()
+    {
+        dag.insert(row.get("event_id"), HashSet::new());
+    }
+
+    for row in &conn
+        .query(
+            "SELECT event_id, prev_event_id FROM event_edges WHERE room_id = $1 AND is_state = False",
+            &[&room_id],
+        )
+        .unwrap()
+    {
+        let event_id: String = row.get("event_id");
+
+        dag.entry(event_id)
+            .or_insert_with(HashSet::new)
+            .insert(row.get("prev_event_id"));
+    }
+
+    dag
+}
+
+// Retrieves the whole DAG of the room through the backend, from the deepest events to the
+// creation of the room, asking for the ancestors of the earliest events of the DAG like the
+// postgres views of the frontend
+fn backend_dag(client: &Client, room_id: &str) -> RoomEvents {
+    let url = format!("{}/visualisations/deepest/{}", BACKEND_URL, room_id);
+    let res = send_with_retries(|| client.get(&url));
+
+    let mut dag = RoomEvents::from_source_events(room_id, "localhost", source_events(res));
+
+    loop {
+        let url = format!("{}/visualisations/ancestors/{}", BACKEND_URL, room_id);
+        let from = dag.earliest_events.join(",");
+        let res = send_with_retries(|| {
+            client
+                .get(&url)
+                .query(&[("from", from.as_str()), ("limit", "1000")])
+        });
+
+        if dag.add_source_events(source_events(res)).added == 0 {
+            break;
+        }
+    }
+
+    dag
+}
+
+fn source_events(res: JsonValue) -> SourceEvents {
+    serde_json::from_value(res).expect("Invalid response from the backend")
+}
+
+// Retrieves the whole DAG of the room through the CS API with the requests of the CS backend of
+// the frontend, built and mapped to events by `cs_api`: an initial sync with the filter of the
+// views, followed by back-paginations
+fn cs_dag(client: &Client, hs_url: &str, token: &str, room_id: &str) -> RoomEvents {
+    let url = format!("{}{}/account/whoami", hs_url, CLIENT_API_PREFIX);
+    let res = send_with_retries(|| client.get(&url).bearer_auth(token));
+    let user_id = res["user_id"].as_str().unwrap();
+
+    let url = format!("{}{}/user/{}/filter", hs_url, CLIENT_API_PREFIX, user_id);
+    let filter = cs_api::sync_filter(room_id, 10);
+    let res = send_with_retries(|| client.post(&url).bearer_auth(token).json(&filter));
+    let filter_id = res["filter_id"].as_str().unwrap();
+
+    let url = format!("{}{}", hs_url, cs_api::sync_path(filter_id, None));
+    let res: SyncResponse =
+        serde_json::from_value(send_with_retries(|| client.get(&url).bearer_auth(token)))
+            .expect("Invalid sync response");
+
+    let (events, prev_batch) = res.room_events(room_id);
+    let mut dag = RoomEvents::from_source_events(room_id, "localhost", events);
+    let mut from = prev_batch.expect("No token to paginate from");
+
+    let filter =
+        utf8_percent_encode(&cs_api::event_filter().to_string(), USERINFO_ENCODE_SET).to_string();
+
+    loop {
+        let url = format!(
+            "{}{}",
+            hs_url,
+            cs_api::messages_path(room_id, &from, &filter)
+        );
+        let res: MessagesResponse =
+            serde_json::from_value(send_with_retries(|| client.get(&url).bearer_auth(token)))
+                .expect("Invalid messages response");
+
+        let (events, end) = res.into_source_events();
+
+        // The start of the history of the room was reached
+        if events.events.is_empty() {
+            break;
+        }
+
+        dag.add_source_events(events);
+        from = end;
+    }
+
+    dag
+}
+
+// Returns the `prev_events` of each event of `dag`
+fn structure(dag: &RoomEvents) -> Dag {
+    dag.to_stored()
+        .events
+        .iter()
+        .map(|ev| {
+            let prev_events: HashSet<String> =
+                ev.get_prev_events().into_iter().map(String::from).collect();
+
+            (ev.event_id.clone(), prev_events)
+        })
+        .collect()
+}
+
+// Tells whether several events of `dag` have the same `prev_event`
+fn has_fork(dag: &Dag) -> bool {
+    let mut parents = HashSet::new();
+
+    dag.values()
+        .flatten()
+        .any(|prev_event| !parents.insert(prev_event))
+}
+
+// Creates the data directory of Synapse with its configuration and its signing key
+fn synapse_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("matrix-vis-synapse-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let config = format!(
+        "server_name: localhost
+pid_file: /data/homeserver.pid
+listeners:
+  - port: 8008
+    type: http
+    tls: false
+    bind_addresses: ['0.0.0.0']
+    resources:
+      - names: [client, federation]
+database:
+  name: psycopg2
+  args:
+    user: postgres
+    password: synapse
+    database: {}
+    host: {}
+media_store_path: /data/media_store
+signing_key_path: /data/localhost.signing.key
+trusted_key_servers: []
+report_stats: false
+enable_registration: true
+enable_registration_without_verification: true
+",
+        DB_NAME, DB_HOST
+    );
+
+    // An ed25519 signing key is only its seed
+    let mut seed = [0; 32];
+    rand_bytes(&mut seed).unwrap();
+    let key = format!(
+        "ed25519 a_test {}",
+        base64::encode_config(&seed, base64::STANDARD_NO_PAD)
+    );
+
+    fs::write(dir.join("homeserver.yaml"), config).unwrap();
+    fs::write(dir.join("localhost.signing.key"), key).unwrap();
+
+    // Synapse runs as another user in the container
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+
+    dir
+}
+
+// Creates a directory in which the backend can find its TLS certificate
+fn backend_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("matrix-vis-tests-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let rsa = Rsa::generate(2048).unwrap();
+    let key = PKey::from_rsa(rsa).unwrap();
+
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut cert = X509::builder().unwrap();
+    cert.set_version(2).unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+
+    fs::write(dir.join("key.pem"), key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+    fs::write(dir.join("cert.pem"), cert.build().to_pem().unwrap()).unwrap();
+
+    dir
+}
+
+// The backend running in "postgres mode", which is killed when this is dropped
+struct BackendProcess(Child);
+
+impl BackendProcess {
+    fn start(dir: &Path, db_addr: &str) -> Self {
+        let child = Command::new(env!("CARGO_BIN_EXE_matrix-visualisations-backend"))
+            .args(&["postgres", db_addr, "postgres", DB_NAME])
+            .current_dir(dir)
+            .spawn()
+            .expect("Failed to start the backend");
+
+        // Leave the backend some time to bind its port
+        thread::sleep(Duration::from_secs(2));
+
+        BackendProcess(child)
+    }
+}
+
+impl Drop for BackendProcess {
+    fn drop(&mut self) {
+        self.0.kill().ok();
+    }
+}
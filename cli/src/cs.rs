@@ -0,0 +1,136 @@
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::Value as JsonValue;
+
+use matrix_dag::cs_api::{
+    event_filter, messages_path, sync_filter, sync_path, MessagesResponse, SyncResponse,
+    CLIENT_API_PREFIX,
+};
+use matrix_dag::source::{EventSource, SourceEvents, SourceRequest};
+
+use crate::Error;
+
+// The number of events requested to the homeserver at once
+const PAGE_SIZE: usize = 100;
+
+/// Up to `limit` of the events of the room `room_id` retrieved from the homeserver `server_name`
+/// through the CS API, with the user whose access token is `access_token`. The user must be in
+/// the room. The events are in the federation format, like in the web application.
+///
+/// The CS API paginates with tokens, so the IDs of the earlier and later events requested are
+/// ignored: the earlier events are those preceding the last page and the later events are those
+/// synced since the last sync.
+pub struct CsSource {
+    base: String,
+    access_token: String,
+    room_id: String,
+    limit: usize,
+    fetched: usize,             // The number of events retrieved so far
+    prev_batch: Option<String>, // Where the pagination backwards resumes, none at the start of the room
+    next_batch: Option<String>, // Where the syncs resume
+}
+
+impl CsSource {
+    pub fn new(server_name: &str, access_token: &str, room_id: &str, limit: usize) -> CsSource {
+        CsSource {
+            base: format!("https://{}", server_name),
+            access_token: access_token.to_string(),
+            room_id: room_id.to_string(),
+            limit,
+            fetched: 0,
+            prev_batch: None,
+            next_batch: None,
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<JsonValue, Error> {
+        let res = ureq::get(&format!("{}{}", self.base, path))
+            .set("Authorization", &format!("Bearer {}", self.access_token))
+            .call()?
+            .into_json()?;
+
+        Ok(res)
+    }
+
+    // The number of events to request next, none once `limit` is reached
+    fn page_size(&self) -> usize {
+        PAGE_SIZE.min(self.limit.saturating_sub(self.fetched))
+    }
+
+    fn sync(&mut self) -> Result<SourceEvents, Error> {
+        let filter = encode(&sync_filter(&self.room_id, self.page_size() as u32));
+        let res: SyncResponse =
+            serde_json::from_value(self.get(&sync_path(&filter, self.next_batch.as_deref()))?)?;
+
+        let initial = self.next_batch.is_none();
+        self.next_batch = Some(res.next_batch.clone());
+
+        if initial && !res.rooms.join.contains_key(&self.room_id) {
+            return Err(format!("The user isn't in the room {}", self.room_id).into());
+        }
+
+        let (events, prev_batch) = res.room_events(&self.room_id);
+        if initial {
+            self.prev_batch = prev_batch;
+        }
+        self.fetched += events.events.len();
+
+        Ok(events)
+    }
+
+    fn messages(&mut self) -> Result<SourceEvents, Error> {
+        let from = match &self.prev_batch {
+            Some(from) if self.page_size() > 0 => from,
+            _ => return Ok(SourceEvents::default()),
+        };
+        let path = format!(
+            "{}&limit={}",
+            messages_path(
+                &encode_str(&self.room_id),
+                &encode_str(from),
+                &encode(&event_filter())
+            ),
+            self.page_size(),
+        );
+        let res: MessagesResponse = serde_json::from_value(self.get(&path)?)?;
+        let (events, end) = res.into_source_events();
+
+        // There is no end once the start of the history of the room is reached
+        self.prev_batch = if events.events.is_empty() || end.is_empty() {
+            None
+        } else {
+            Some(end)
+        };
+        self.fetched += events.events.len();
+
+        Ok(events)
+    }
+}
+
+impl EventSource for CsSource {
+    type Error = Error;
+
+    fn fetch(&mut self, request: &SourceRequest) -> Result<SourceEvents, Error> {
+        match request {
+            SourceRequest::Initial | SourceRequest::After(_) => self.sync(),
+            SourceRequest::Before(_) => self.messages(),
+            SourceRequest::Single(event_id) => {
+                let path = format!(
+                    "{}/rooms/{}/event/{}",
+                    CLIENT_API_PREFIX,
+                    encode_str(&self.room_id),
+                    encode_str(event_id),
+                );
+
+                Ok(SourceEvents::from_events(vec![self.get(&path)?]))
+            }
+        }
+    }
+}
+
+fn encode(value: &JsonValue) -> String {
+    encode_str(&value.to_string())
+}
+
+fn encode_str(value: &str) -> String {
+    utf8_percent_encode(value, NON_ALPHANUMERIC).to_string()
+}
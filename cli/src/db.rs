@@ -0,0 +1,271 @@
+use std::fs;
+
+use native_tls::{Certificate, TlsConnector};
+use postgres::{Client, Row};
+use postgres_native_tls::MakeTlsConnector;
+use serde_json::Value as JsonValue;
+
+use matrix_dag::source::{EventSource, SourceEvents, SourceRequest};
+
+use crate::Error;
+
+/// How the connections to the database are secured, following the `sslmode` of libpq, like in
+/// the Postgres backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SslMode {
+    Disable,    // Plain connections only
+    Prefer,     // TLS if the server supports it, without checking its certificate
+    Require,    // TLS only, without checking the certificate of the server
+    VerifyCa,   // TLS only, the certificate of the server being signed by a trusted CA
+    VerifyFull, // As `VerifyCa`, the certificate also matching the host of the server
+}
+
+impl SslMode {
+    fn parse(mode: &str) -> Result<SslMode, String> {
+        match mode {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => Err(format!("unknown sslmode `{}`", mode)),
+        }
+    }
+
+    // The `sslmode` understood by the client, which doesn't check the certificates itself
+    fn client_mode(self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            _ => "require",
+        }
+    }
+}
+
+/// The options of a DSN about TLS, with the DSN the client is given once they are taken out of
+/// it.
+#[derive(Debug, PartialEq)]
+struct TlsOptions {
+    dsn: String,
+    ssl_mode: SslMode,
+    ssl_root_cert: Option<String>, // The file of the CAs to trust
+}
+
+impl TlsOptions {
+    /// Takes `sslmode` and `sslrootcert` out of `dsn`, which is either a URL like
+    /// `postgres://user@host/synapse?sslmode=verify-full` or a list of `key=value` pairs like
+    /// `host=localhost sslmode=require`. Without `sslmode`, the connections are plain.
+    fn parse(dsn: &str) -> Result<TlsOptions, String> {
+        let url = dsn.starts_with("postgres://") || dsn.starts_with("postgresql://");
+
+        let (base, params): (&str, Vec<&str>) = if url {
+            match dsn.find('?') {
+                Some(i) => (&dsn[..i], dsn[i + 1..].split('&').collect()),
+                None => (dsn, Vec::new()),
+            }
+        } else {
+            ("", dsn.split_whitespace().collect())
+        };
+
+        let mut options = TlsOptions {
+            dsn: String::new(),
+            ssl_mode: SslMode::Disable,
+            ssl_root_cert: None,
+        };
+        let mut passed_on = Vec::new();
+
+        for param in params.into_iter().filter(|param| !param.is_empty()) {
+            let (key, value) = match param.find('=') {
+                Some(i) => (&param[..i], &param[i + 1..]),
+                None => (param, ""),
+            };
+
+            match key {
+                "sslmode" => options.ssl_mode = SslMode::parse(value)?,
+                "sslrootcert" => options.ssl_root_cert = Some(value.to_string()),
+                _ => passed_on.push(param.to_string()),
+            }
+        }
+
+        passed_on.push(format!("sslmode={}", options.ssl_mode.client_mode()));
+
+        options.dsn = if url {
+            format!("{}?{}", base, passed_on.join("&"))
+        } else {
+            passed_on.join(" ")
+        };
+
+        Ok(options)
+    }
+
+    /// Builds the TLS connector of the connections from `ssl_mode` and `ssl_root_cert`.
+    fn connector(&self) -> Result<MakeTlsConnector, Error> {
+        let mut builder = TlsConnector::builder();
+
+        if let Some(path) = &self.ssl_root_cert {
+            let pem = fs::read(path).map_err(|e| format!("failed to load {}: {}", path, e))?;
+
+            builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        match self.ssl_mode {
+            SslMode::Prefer | SslMode::Require => {
+                builder.danger_accept_invalid_certs(true);
+                builder.danger_accept_invalid_hostnames(true);
+            }
+            SslMode::VerifyCa => {
+                builder.danger_accept_invalid_hostnames(true);
+            }
+            SslMode::Disable | SslMode::VerifyFull => {}
+        }
+
+        Ok(MakeTlsConnector::new(builder.build()?))
+    }
+}
+
+// The columns of the events selected by the queries, which are turned into `SourceEvents` by
+// `source_events`
+const EVENT_COLUMNS: &str =
+    "SELECT e.event_id, j.json, j.internal_metadata, r.reason FROM events AS e
+    JOIN event_json AS j USING (event_id)
+    LEFT JOIN rejections AS r USING (event_id)";
+
+/// The events of the room `room_id` retrieved from the database of a Synapse homeserver, along
+/// with the reasons of their rejections and their soft-failures, like the Postgres backend does.
+/// The initial events are the `limit` deepest ones, and the earlier and later events are the
+/// prev_events and the children of the events requested.
+pub struct PgSource {
+    client: Client,
+    room_id: String,
+    limit: usize,
+}
+
+impl PgSource {
+    /// Connects to the database. `dsn` is a libpq connection string, e.g.
+    /// `host=localhost user=synapse dbname=synapse`, or a URL. Its `sslmode` can be `disable`,
+    /// `prefer`, `require`, `verify-ca` or `verify-full`, with `sslrootcert` giving the file of
+    /// the CAs to trust.
+    pub fn connect(dsn: &str, room_id: &str, limit: usize) -> Result<PgSource, Error> {
+        let tls = TlsOptions::parse(dsn)?;
+        let client = Client::connect(&tls.dsn, tls.connector()?)?;
+
+        Ok(PgSource {
+            client,
+            room_id: room_id.to_string(),
+            limit,
+        })
+    }
+}
+
+impl EventSource for PgSource {
+    type Error = Error;
+
+    fn fetch(&mut self, request: &SourceRequest) -> Result<SourceEvents, Error> {
+        let rows = match request {
+            SourceRequest::Initial => self.client.query(
+                format!(
+                    "{} WHERE e.room_id = $1 ORDER BY e.depth DESC, e.stream_ordering DESC LIMIT $2",
+                    EVENT_COLUMNS
+                )
+                .as_str(),
+                &[&self.room_id, &(self.limit as i64)],
+            )?,
+            SourceRequest::Before(from) => self.client.query(
+                format!(
+                    "{} WHERE e.room_id = $1 AND e.event_id IN
+                    (SELECT prev_event_id FROM event_edges WHERE event_id = ANY($2))",
+                    EVENT_COLUMNS
+                )
+                .as_str(),
+                &[&self.room_id, from],
+            )?,
+            SourceRequest::After(from) => self.client.query(
+                format!(
+                    "{} WHERE e.room_id = $1 AND e.event_id IN
+                    (SELECT event_id FROM event_edges WHERE prev_event_id = ANY($2))",
+                    EVENT_COLUMNS
+                )
+                .as_str(),
+                &[&self.room_id, from],
+            )?,
+            SourceRequest::Single(event_id) => self.client.query(
+                format!("{} WHERE e.room_id = $1 AND e.event_id = $2", EVENT_COLUMNS).as_str(),
+                &[&self.room_id, event_id],
+            )?,
+        };
+
+        source_events(rows)
+    }
+}
+
+// Builds the events and their annotations from the rows selected with `EVENT_COLUMNS`
+fn source_events(rows: Vec<Row>) -> Result<SourceEvents, Error> {
+    let mut res = SourceEvents::default();
+
+    for row in rows {
+        let event_id: String = row.get("event_id");
+        let json: String = row.get("json");
+        let internal_metadata: String = row.get("internal_metadata");
+        let reason: Option<String> = row.get("reason");
+
+        // The IDs of the events of the recent room versions aren't part of their JSON
+        let mut event: JsonValue = serde_json::from_str(&json)?;
+        match event.as_object_mut() {
+            Some(fields) => fields.insert("event_id".to_string(), event_id.clone().into()),
+            None => return Err(format!("The JSON of {} isn't an object", event_id).into()),
+        };
+
+        let metadata: JsonValue = serde_json::from_str(&internal_metadata)?;
+        if metadata["soft_failed"].as_bool().unwrap_or(false) {
+            res.soft_failed.push(event_id.clone());
+        }
+        if let Some(reason) = reason {
+            res.rejections.insert(event_id, reason);
+        }
+
+        res.events.push(event);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_tls_options_out_of_the_urls() {
+        let options = TlsOptions::parse(
+            "postgres://synapse@db/synapse?sslmode=verify-full&sslrootcert=/ca.pem&connect_timeout=5",
+        )
+        .unwrap();
+
+        assert_eq!(
+            options,
+            TlsOptions {
+                dsn: "postgres://synapse@db/synapse?connect_timeout=5&sslmode=require".to_string(),
+                ssl_mode: SslMode::VerifyFull,
+                ssl_root_cert: Some("/ca.pem".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn takes_the_tls_options_out_of_the_key_value_pairs() {
+        let options = TlsOptions::parse("host=db user=synapse sslmode=verify-ca").unwrap();
+
+        assert_eq!(options.dsn, "host=db user=synapse sslmode=require");
+        assert_eq!(options.ssl_mode, SslMode::VerifyCa);
+        assert_eq!(options.ssl_root_cert, None);
+    }
+
+    #[test]
+    fn connects_in_plaintext_by_default() {
+        let options = TlsOptions::parse("postgres://synapse@db/synapse").unwrap();
+
+        assert_eq!(options.dsn, "postgres://synapse@db/synapse?sslmode=disable");
+        assert_eq!(options.ssl_mode, SslMode::Disable);
+
+        assert!(TlsOptions::parse("host=db sslmode=sometimes").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+extern crate matrix_dag;
+extern crate native_tls;
+extern crate percent_encoding;
+extern crate postgres;
+extern crate postgres_native_tls;
+extern crate serde_json;
+extern crate ureq;
+
+mod cs;
+mod db;
+mod render;
+
+use std::collections::HashSet;
+use std::env::args;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::exit;
+
+use matrix_dag::dag::RoomEvents;
+use matrix_dag::depth_anomaly::DEFAULT_DEPTH_JUMP_THRESHOLD;
+use matrix_dag::event::Field;
+use matrix_dag::layout_options::LayoutOptions;
+use matrix_dag::source::{EventSource, SourceRequest};
+use matrix_dag::standalone;
+use matrix_dag::theme::Theme;
+
+use crate::render::Format;
+
+pub type Error = Box<dyn std::error::Error>;
+
+// The number of events retrieved when `--limit` isn't given
+const DEFAULT_LIMIT: usize = 500;
+
+const USAGE: &str = "Usage:
+    matrix-vis-cli cs <server_name> <access_token> <room_id> [options]
+    matrix-vis-cli postgres <connection_string> <room_id> [options]
+
+Options:
+    --output <file>      Where to write the rendering, the standard output by default
+    --format <format>    dot, svg, png or html, guessed from the extension of the output by
+                         default
+    --limit <n>          The number of events to retrieve, 500 by default
+    --fields <fields>    The fields of the events in the labels, separated by commas, among
+                         sender, origin, origin_server_ts, type, state_key, prev_events, depth,
+                         redacts, event_id and JSON pointers like /content/body (event_id by
+                         default)
+    --depth-jump <n>     Warn about the events deeper than their prev_events by more than n, 100
+                         by default
+
+The png format needs Graphviz's dot command.";
+
+// Where the events of the room are retrieved from
+enum Source {
+    Cs {
+        server_name: String,
+        access_token: String,
+    },
+    Postgres {
+        dsn: String,
+    },
+}
+
+struct Options {
+    source: Source,
+    room_id: String,
+    output: Option<String>,
+    format: Option<Format>,
+    limit: usize,
+    fields: HashSet<Field>,
+    depth_jump: i64, // The largest difference between the depths of an event and of its prev_events not warned about
+}
+
+fn main() {
+    let args: Vec<String> = args().skip(1).collect();
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+
+    if let Err(e) = run(options) {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+}
+
+fn run(options: Options) -> Result<(), Error> {
+    let mut source: Box<dyn EventSource<Error = Error>> = match &options.source {
+        Source::Cs {
+            server_name,
+            access_token,
+        } => Box::new(cs::CsSource::new(
+            server_name,
+            access_token,
+            &options.room_id,
+            options.limit,
+        )),
+        Source::Postgres { dsn } => {
+            Box::new(db::PgSource::connect(dsn, &options.room_id, options.limit)?)
+        }
+    };
+
+    let res = source.fetch(&SourceRequest::Initial)?;
+    let server_name = match &options.source {
+        Source::Cs { server_name, .. } => server_name.clone(),
+        // The database doesn't tell which server it belongs to, so the origin of its deepest
+        // event is taken as the local server to colour the nodes
+        Source::Postgres { .. } => res
+            .events
+            .first()
+            .and_then(|ev| ev["origin"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+    };
+
+    let mut dag = RoomEvents::from_source_events(&options.room_id, &server_name, res);
+
+    // The earlier events are retrieved until there are enough of them or until the start of the
+    // room is reached
+    while dag.event_count() < options.limit {
+        let request = SourceRequest::Before(dag.earliest_events.clone());
+
+        if dag.fetch_from(source.as_mut(), &request)?.added == 0 {
+            break;
+        }
+    }
+
+    if let Some(cycle) = dag.cycle() {
+        return Err(format!("The events form a cycle: {}", cycle.join(" -> ")).into());
+    }
+
+    // The depths are only checked, the DAG is rendered anyway
+    for anomaly in dag.depth_anomalies(options.depth_jump) {
+        eprintln!(
+            "Warning: suspicious depth of {}: {}",
+            anomaly.id,
+            anomaly.description()
+        );
+    }
+
+    let format = options
+        .format
+        .or_else(|| {
+            options
+                .output
+                .as_ref()
+                .and_then(|output| output.rsplit('.').next())
+                .and_then(Format::from_name)
+        })
+        .unwrap_or(Format::Dot);
+
+    let data_set = dag.create_data_set(&options.fields);
+    let rendering = match format {
+        Format::Html => standalone::to_html(
+            &options.room_id,
+            &data_set,
+            &LayoutOptions::default(),
+            Theme::default(),
+        )
+        .into_bytes(),
+        Format::Dot => render::to_dot(&data_set).into_bytes(),
+        Format::Svg => render::to_svg(&data_set).into_bytes(),
+        Format::Png => render::to_png(&render::to_dot(&data_set))?,
+    };
+
+    match &options.output {
+        Some(output) => File::create(output)?.write_all(&rendering)?,
+        None => io::stdout().write_all(&rendering)?,
+    }
+
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let (source, rest) = match args.first().map(String::as_str) {
+        Some("cs") if args.len() >= 4 => (
+            Source::Cs {
+                server_name: args[1].clone(),
+                access_token: args[2].clone(),
+            },
+            &args[3..],
+        ),
+        Some("postgres") if args.len() >= 3 => (
+            Source::Postgres {
+                dsn: args[1].clone(),
+            },
+            &args[2..],
+        ),
+        _ => return Err("Missing arguments".to_string()),
+    };
+
+    let mut options = Options {
+        source,
+        room_id: rest[0].clone(),
+        output: None,
+        format: None,
+        limit: DEFAULT_LIMIT,
+        fields: [Field::EventID].iter().cloned().collect(),
+        depth_jump: DEFAULT_DEPTH_JUMP_THRESHOLD,
+    };
+
+    let mut rest = rest[1..].iter();
+
+    while let Some(option) = rest.next() {
+        let value = rest
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", option))?;
+
+        match option.as_str() {
+            "--output" => options.output = Some(value.clone()),
+            "--format" => {
+                options.format = Some(
+                    Format::from_name(value).ok_or_else(|| format!("Unknown format {}", value))?,
+                )
+            }
+            "--limit" => {
+                options.limit = value
+                    .parse()
+                    .map_err(|_| format!("Invalid limit {}", value))?
+            }
+            "--fields" => {
+                options.fields = value
+                    .split(',')
+                    .map(|name| parse_field(name.trim()))
+                    .collect::<Result<_, _>>()?
+            }
+            "--depth-jump" => {
+                options.depth_jump = value
+                    .parse()
+                    .map_err(|_| format!("Invalid depth jump {}", value))?
+            }
+            _ => return Err(format!("Unknown option {}", option)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_field(name: &str) -> Result<Field, String> {
+    match name {
+        "sender" => Ok(Field::Sender),
+        "origin" => Ok(Field::Origin),
+        "origin_server_ts" => Ok(Field::OriginServerTS),
+        "type" => Ok(Field::Type),
+        "state_key" => Ok(Field::StateKey),
+        "prev_events" => Ok(Field::PrevEvents),
+        "depth" => Ok(Field::Depth),
+        "redacts" => Ok(Field::Redacts),
+        "event_id" => Ok(Field::EventID),
+        "unknown_fields" => Ok(Field::UnknownFields),
+        pointer if pointer.starts_with('/') => Ok(Field::Pointer(pointer.to_string())),
+        _ => Err(format!("Unknown field {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_the_cs_source_with_the_defaults() {
+        let options =
+            parse_args(&args(&["cs", "example.org", "token", "!room:example.org"])).unwrap();
+
+        match options.source {
+            Source::Cs {
+                server_name,
+                access_token,
+            } => {
+                assert_eq!(server_name, "example.org");
+                assert_eq!(access_token, "token");
+            }
+            Source::Postgres { .. } => panic!("Expected the CS source"),
+        }
+        assert_eq!(options.room_id, "!room:example.org");
+        assert_eq!(options.output, None);
+        assert_eq!(options.format, None);
+        assert_eq!(options.limit, DEFAULT_LIMIT);
+        assert_eq!(options.fields, [Field::EventID].iter().cloned().collect());
+        assert_eq!(options.depth_jump, DEFAULT_DEPTH_JUMP_THRESHOLD);
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let options = parse_args(&args(&[
+            "postgres",
+            "host=localhost",
+            "!room:example.org",
+            "--output",
+            "dag.svg",
+            "--format",
+            "png",
+            "--limit",
+            "20",
+            "--fields",
+            "sender, depth,/content/body",
+            "--depth-jump",
+            "5",
+        ]))
+        .unwrap();
+
+        match options.source {
+            Source::Postgres { dsn } => assert_eq!(dsn, "host=localhost"),
+            Source::Cs { .. } => panic!("Expected the Postgres source"),
+        }
+        assert_eq!(options.output.as_deref(), Some("dag.svg"));
+        assert_eq!(options.format, Some(Format::Png));
+        assert_eq!(options.limit, 20);
+        assert_eq!(
+            options.fields,
+            [
+                Field::Sender,
+                Field::Depth,
+                Field::Pointer("/content/body".to_string())
+            ]
+            .iter()
+            .cloned()
+            .collect()
+        );
+        assert_eq!(options.depth_jump, 5);
+    }
+
+    #[test]
+    fn rejects_the_invalid_arguments() {
+        assert!(parse_args(&args(&["cs", "example.org", "token"])).is_err());
+        assert!(parse_args(&args(&["ftp", "example.org", "!room:example.org"])).is_err());
+        assert!(parse_args(&args(&["postgres", "dsn", "!room", "--limit"])).is_err());
+        assert!(parse_args(&args(&["postgres", "dsn", "!room", "--limit", "many"])).is_err());
+        assert!(parse_args(&args(&["postgres", "dsn", "!room", "--format", "gif"])).is_err());
+        assert!(parse_args(&args(&["postgres", "dsn", "!room", "--fields", "colour"])).is_err());
+        assert!(parse_args(&args(&["postgres", "dsn", "!room", "--verbose", "yes"])).is_err());
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+
+use matrix_dag::dag::{DataSet, DataSetNode};
+use matrix_dag::layout::{layered_positions, LayoutGraph};
+use matrix_dag::layout_cache::Position;
+use matrix_dag::layout_options::LayoutOptions;
+
+use crate::Error;
+
+// The width of the characters of the labels, which are in a monospace font, and the height of
+// their lines, in pixels
+const CHAR_WIDTH: f64 = 7.2;
+const LINE_HEIGHT: f64 = 16.0;
+const FONT_SIZE: f64 = 12.0;
+
+// The space around the labels in the nodes, and around the nodes in the image
+const PADDING: f64 = 8.0;
+const MARGIN: f64 = 20.0;
+
+// The length and the half width of the heads of the edges
+const ARROW_LENGTH: f64 = 10.0;
+const ARROW_HALF_WIDTH: f64 = 4.0;
+
+// The colour of the edges which have none, as in vis.js
+const EDGE_COLOR: &str = "#848484";
+
+/// The formats the DAG can be rendered in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Dot,
+    Svg,
+    Png,
+    Html, // A standalone page drawing the DAG with vis.js, see `standalone::to_html`
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "dot" | "gv" => Some(Format::Dot),
+            "svg" => Some(Format::Svg),
+            "png" => Some(Format::Png),
+            "html" | "htm" => Some(Format::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `data_set` in the DOT language of Graphviz. Like in the vis.js networks, the nodes of
+/// the same level are on the same rank and the earliest events are at the top, with the edges
+/// going from the events to their `prev_events`.
+pub fn to_dot(data_set: &DataSet) -> String {
+    let mut dot = String::new();
+    let mut levels: BTreeMap<i64, Vec<&str>> = BTreeMap::new();
+
+    dot.push_str("digraph dag {\n");
+    dot.push_str("    node [shape=box, style=filled, fontname=\"monospace\"];\n");
+
+    for node in &data_set.nodes {
+        let mut attributes = format!(
+            "label=\"{}\", color=\"{}\", fillcolor=\"{}\"",
+            escape(&node.label),
+            node.color.border,
+            node.color.background
+        );
+
+        if let Some(width) = node.border_width {
+            let _ = write!(attributes, ", penwidth={}", width);
+        }
+        if node.shape_properties.is_some() {
+            attributes.push_str(", style=\"filled,dashed\"");
+        }
+        if let Some(title) = &node.title {
+            let _ = write!(attributes, ", tooltip=\"{}\"", escape(&tooltip_text(title)));
+        }
+
+        let _ = writeln!(dot, "    \"{}\" [{}];", escape(&node.id), attributes);
+        levels.entry(node.level).or_default().push(&node.id);
+    }
+
+    for ids in levels.values() {
+        let ids: Vec<String> = ids.iter().map(|id| format!("\"{}\"", escape(id))).collect();
+        let _ = writeln!(dot, "    {{ rank=same; {} }}", ids.join("; "));
+    }
+
+    // The edges are reversed so that the `prev_events` are ranked above the events, and then
+    // drawn backwards so that they still point to the `prev_events`
+    for edge in &data_set.edges {
+        let mut attributes = String::from("dir=back");
+
+        if let Some(color) = &edge.color {
+            let _ = write!(attributes, ", color=\"{}\"", color.color);
+        }
+        if edge.dashes {
+            attributes.push_str(", style=dashed");
+        }
+        if let Some(label) = &edge.label {
+            let _ = write!(attributes, ", label=\"{}\"", escape(label));
+        }
+
+        let _ = writeln!(
+            dot,
+            "    \"{}\" -> \"{}\" [{}];",
+            escape(&edge.to),
+            escape(&edge.from),
+            attributes
+        );
+    }
+
+    dot.push_str("}\n");
+
+    dot
+}
+
+/// Draws `data_set` as an SVG image, the nodes being where the layered layout puts them (see
+/// `layout::layered_positions`), so that no other program is needed. As in the vis.js networks,
+/// the earliest events are at the top and the edges point to the `prev_events`.
+pub fn to_svg(data_set: &DataSet) -> String {
+    let positions = layered_positions(
+        &LayoutGraph::from_data_set(data_set),
+        &LayoutOptions::default(),
+    );
+    let boxes: HashMap<&str, NodeBox> = data_set
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            let position = positions.get(&node.id)?;
+
+            Some((node.id.as_str(), NodeBox::new(node, *position)))
+        })
+        .collect();
+
+    let (min_x, min_y, max_x, max_y) = if boxes.is_empty() {
+        (0.0, 0.0, 0.0, 0.0)
+    } else {
+        boxes.values().fold(
+            (
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(min_x, min_y, max_x, max_y), b| {
+                (
+                    min_x.min(b.x - b.half_width),
+                    min_y.min(b.y - b.half_height),
+                    max_x.max(b.x + b.half_width),
+                    max_y.max(b.y + b.half_height),
+                )
+            },
+        )
+    };
+    let (width, height) = (max_x - min_x + 2.0 * MARGIN, max_y - min_y + 2.0 * MARGIN);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+         viewBox=\"{:.1} {:.1} {:.1} {:.1}\" font-family=\"monospace\" font-size=\"{}\">",
+        width,
+        height,
+        min_x - MARGIN,
+        min_y - MARGIN,
+        width,
+        height,
+        FONT_SIZE
+    );
+
+    // The edges are drawn first so that the nodes are above them
+    for edge in &data_set.edges {
+        let (from, to) = match (boxes.get(edge.from.as_str()), boxes.get(edge.to.as_str())) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        let color = edge.color.as_ref().map_or(EDGE_COLOR, |c| c.color.as_str());
+        let (x1, y1) = from.border_towards(to.x, to.y);
+        let (x2, y2) = to.border_towards(from.x, from.y);
+
+        let _ = write!(
+            svg,
+            "  <line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\"",
+            x1,
+            y1,
+            x2,
+            y2,
+            xml_escape(color)
+        );
+        if edge.dashes {
+            svg.push_str(" stroke-dasharray=\"5,5\"");
+        }
+        svg.push_str("/>\n");
+
+        let length = (x2 - x1).hypot(y2 - y1);
+        if length > 0.0 {
+            let (ux, uy) = ((x2 - x1) / length, (y2 - y1) / length);
+            let (bx, by) = (x2 - ux * ARROW_LENGTH, y2 - uy * ARROW_LENGTH);
+
+            let _ = writeln!(
+                svg,
+                "  <polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"{}\"/>",
+                x2,
+                y2,
+                bx - uy * ARROW_HALF_WIDTH,
+                by + ux * ARROW_HALF_WIDTH,
+                bx + uy * ARROW_HALF_WIDTH,
+                by - ux * ARROW_HALF_WIDTH,
+                xml_escape(color)
+            );
+        }
+
+        if let Some(label) = &edge.label {
+            let _ = writeln!(
+                svg,
+                "  <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" fill=\"{}\">{}</text>",
+                (x1 + x2) / 2.0,
+                (y1 + y2) / 2.0,
+                xml_escape(color),
+                xml_escape(label)
+            );
+        }
+    }
+
+    for node in &data_set.nodes {
+        let b = match boxes.get(node.id.as_str()) {
+            Some(b) => b,
+            None => continue,
+        };
+
+        svg.push_str("  <g>");
+        if let Some(title) = &node.title {
+            let _ = write!(svg, "<title>{}</title>", xml_escape(&tooltip_text(title)));
+        }
+
+        let _ = write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"3\" \
+             fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"",
+            b.x - b.half_width,
+            b.y - b.half_height,
+            2.0 * b.half_width,
+            2.0 * b.half_height,
+            xml_escape(&node.color.background),
+            xml_escape(&node.color.border),
+            node.border_width.unwrap_or(1)
+        );
+        if node.shape_properties.is_some() {
+            svg.push_str(" stroke-dasharray=\"5,5\"");
+        }
+        svg.push_str("/>");
+
+        let _ = write!(svg, "<text text-anchor=\"middle\">");
+        for (i, line) in b.lines.iter().enumerate() {
+            let _ = write!(
+                svg,
+                "<tspan x=\"{:.1}\" y=\"{:.1}\">{}</tspan>",
+                b.x,
+                b.y - b.half_height + PADDING + FONT_SIZE + LINE_HEIGHT * i as f64,
+                xml_escape(line)
+            );
+        }
+        svg.push_str("</text></g>\n");
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+// The rectangle of a node, centred on its position and fitting the lines of its label
+struct NodeBox {
+    x: f64,
+    y: f64,
+    half_width: f64,
+    half_height: f64,
+    lines: Vec<String>,
+}
+
+impl NodeBox {
+    fn new(node: &DataSetNode, position: Position) -> NodeBox {
+        // The labels with a font use the HTML markup of vis.js, which is left out
+        let label = if node.font.is_some() {
+            strip_markup(&node.label)
+        } else {
+            node.label.clone()
+        };
+        let lines: Vec<String> = label.lines().map(|line| line.to_string()).collect();
+        let longest = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        NodeBox {
+            x: position.x,
+            y: position.y,
+            half_width: (longest as f64 * CHAR_WIDTH) / 2.0 + PADDING,
+            half_height: (lines.len().max(1) as f64 * LINE_HEIGHT) / 2.0 + PADDING,
+            lines,
+        }
+    }
+
+    // Returns where the segment from the centre of the box to the point `(x, y)` crosses the
+    // border of the box, so that the edges end at the nodes rather than under them
+    fn border_towards(&self, x: f64, y: f64) -> (f64, f64) {
+        let (dx, dy) = (x - self.x, y - self.y);
+
+        if dx == 0.0 && dy == 0.0 {
+            return (self.x, self.y);
+        }
+
+        let scale = (self.half_width / dx.abs()).min(self.half_height / dy.abs());
+
+        (self.x + dx * scale, self.y + dy * scale)
+    }
+}
+
+/// Renders `dot` as a PNG image with the `dot` command of Graphviz, which must be installed.
+pub fn to_png(dot: &str) -> Result<Vec<u8>, Error> {
+    let mut child = Command::new("dot")
+        .arg("-Tpng")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run Graphviz's dot, is it installed? {}", e))?;
+
+    // The input is closed once written, so that dot starts rendering
+    child
+        .stdin
+        .take()
+        .ok_or("Failed to write to the input of Graphviz's dot")?
+        .write_all(dot.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!("Graphviz's dot failed with {}", output.status).into())
+    }
+}
+
+// The tooltips of the nodes are made for vis.js, which renders them as HTML
+fn tooltip_text(title: &str) -> String {
+    title
+        .replace("<br>", "\n")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+// Removes the tags of the labels with the HTML markup of vis.js, such as `<b>` or `<code>`
+fn strip_markup(label: &str) -> String {
+    let mut text = String::with_capacity(label.len());
+    let mut in_tag = false;
+
+    for c in label.replace("<br>", "\n").chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    tooltip_text(&text)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matrix_dag::dag::{DataSetEdge, DataSetNode, EdgeColor, NodeColor};
+
+    fn node(id: &str, label: &str, level: i64) -> DataSetNode {
+        DataSetNode {
+            id: id.to_string(),
+            label: label.to_string(),
+            title: None,
+            font: None,
+            level,
+            color: NodeColor {
+                border: "#000000".to_string(),
+                background: "#ffffff".to_string(),
+            },
+            value: None,
+            border_width: None,
+            shape_properties: None,
+        }
+    }
+
+    fn edge(from: &str, to: &str) -> DataSetEdge {
+        DataSetEdge {
+            id: format!("{}{}", from, to),
+            from: from.to_string(),
+            to: to.to_string(),
+            label: None,
+            color: None,
+            dashes: false,
+        }
+    }
+
+    #[test]
+    fn writes_the_nodes_and_the_edges() {
+        let mut data_set = DataSet::new();
+        data_set.nodes.push(node("$a", "A", 0));
+        data_set.nodes.push(node("$b", "B", 1));
+        data_set.edges.push(edge("$b", "$a"));
+
+        let dot = to_dot(&data_set);
+
+        assert!(dot.starts_with("digraph dag {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(
+            dot.contains("    \"$a\" [label=\"A\", color=\"#000000\", fillcolor=\"#ffffff\"];\n")
+        );
+        // The edge is reversed, and drawn backwards to point to the `prev_event`
+        assert!(dot.contains("    \"$a\" -> \"$b\" [dir=back];\n"));
+    }
+
+    #[test]
+    fn writes_the_attributes_of_the_edges() {
+        let mut data_set = DataSet::new();
+        data_set.nodes.push(node("$a", "A", 0));
+        data_set.nodes.push(node("$b", "B", 1));
+        data_set.edges.push(DataSetEdge {
+            label: Some("m.thread".to_string()),
+            color: Some(EdgeColor {
+                color: "#ff0000".to_string(),
+            }),
+            dashes: true,
+            ..edge("$b", "$a")
+        });
+
+        let dot = to_dot(&data_set);
+
+        assert!(dot.contains(
+            "    \"$a\" -> \"$b\" [dir=back, color=\"#ff0000\", style=dashed, label=\"m.thread\"];\n"
+        ));
+    }
+
+    #[test]
+    fn ranks_the_nodes_of_the_same_level_together() {
+        let mut data_set = DataSet::new();
+        data_set.nodes.push(node("$b", "B", 1));
+        data_set.nodes.push(node("$a", "A", 0));
+        data_set.nodes.push(node("$c", "C", 1));
+
+        let dot = to_dot(&data_set);
+        let first = dot.find("{ rank=same; \"$a\" }").unwrap();
+        let second = dot.find("{ rank=same; \"$b\"; \"$c\" }").unwrap();
+
+        // The ranks are written by increasing level
+        assert!(first < second);
+    }
+
+    #[test]
+    fn escapes_the_labels_and_the_tooltips() {
+        let mut data_set = DataSet::new();
+        data_set.nodes.push(DataSetNode {
+            title: Some("&lt;b&gt;<br>\"quoted\"".to_string()),
+            ..node("$a", "say \"hi\"\nback\\slash", 0)
+        });
+
+        let dot = to_dot(&data_set);
+
+        assert!(dot.contains("label=\"say \\\"hi\\\"\\nback\\\\slash\""));
+        assert!(dot.contains("tooltip=\"<b>\\n\\\"quoted\\\"\""));
+    }
+
+    #[test]
+    fn draws_the_nodes_and_the_edges_in_svg() {
+        let mut data_set = DataSet::new();
+        data_set.nodes.push(node("$a", "A & <B>", 0));
+        data_set.nodes.push(DataSetNode {
+            title: Some("Tooltip".to_string()),
+            ..node("$b", "B\nsecond line", 1)
+        });
+        data_set.edges.push(DataSetEdge {
+            label: Some("m.thread".to_string()),
+            ..edge("$b", "$a")
+        });
+        data_set.edges.push(edge("$b", "$unknown"));
+
+        let svg = to_svg(&data_set);
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect ").count(), 2);
+        // The edge to the event which isn't drawn is left out
+        assert_eq!(svg.matches("<line ").count(), 1);
+        assert_eq!(svg.matches("<polygon ").count(), 1);
+        assert!(svg.contains(">A &amp; &lt;B&gt;</tspan>"));
+        assert!(svg.contains(">second line</tspan>"));
+        assert!(svg.contains("<title>Tooltip</title>"));
+        assert!(svg.contains(">m.thread</text>"));
+    }
+
+    #[test]
+    fn draws_an_empty_data_set_in_svg() {
+        let svg = to_svg(&DataSet::new());
+
+        assert!(svg.contains("viewBox=\"-20.0 -20.0 40.0 40.0\""));
+        assert!(!svg.contains("<rect "));
+    }
+
+    #[test]
+    fn ends_the_edges_at_the_borders_of_the_nodes() {
+        let b = NodeBox::new(&node("$a", "A", 0), Position { x: 0.0, y: 0.0 });
+        let (half_width, half_height) = (CHAR_WIDTH / 2.0 + PADDING, LINE_HEIGHT / 2.0 + PADDING);
+
+        assert_eq!((b.half_width, b.half_height), (half_width, half_height));
+        assert_eq!(b.border_towards(0.0, 100.0), (0.0, half_height));
+        assert_eq!(b.border_towards(-100.0, 0.0), (-half_width, 0.0));
+        assert_eq!(b.border_towards(0.0, 0.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn leaves_the_markup_out_of_the_labels() {
+        assert_eq!(
+            strip_markup("<b>m.room.message</b><br><code>$a</code> &amp; 1 &lt; 2"),
+            "m.room.message\n$a & 1 < 2"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Benchmarks of the construction and the rendering of the events DAG on synthetic rooms.
+//!
+//! Run them with `cargo bench`. The sizes go up to 100k events, which is far from unusual for
+//! the rooms users load.
+
+use std::collections::HashSet;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use serde_json::{json, Value as JsonValue};
+
+use matrix_dag::dag::RoomEvents;
+use matrix_dag::event::Field;
+
+const ROOM_ID: &str = "!bench:example.org";
+const SERVER_NAME: &str = "example.org";
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+// Generates a room of `size` events in the federation format. Most of the events follow the
+// previous one, but in every group of 10 events, two of them fork from the same parent and the
+// next one merges them back, so the DAG isn't a mere chain. Every 50th event is a state event.
+fn synthetic_events(size: usize) -> Vec<JsonValue> {
+    let id = |i: usize| format!("${}:example.org", i);
+
+    (0..size)
+        .map(|i| {
+            let prev_events: Vec<String> = match i {
+                0 => Vec::new(),
+                i if i % 10 == 5 => vec![id(i - 2)],
+                i if i % 10 == 6 => vec![id(i - 1), id(i - 2)],
+                i => vec![id(i - 1)],
+            };
+
+            let mut event = json!({
+                "room_id": ROOM_ID,
+                "sender": format!("@user{}:example.org", i % 20),
+                "origin": SERVER_NAME,
+                "origin_server_ts": 1_500_000_000_000i64 + i as i64 * 1000,
+                "type": "m.room.message",
+                "content": { "msgtype": "m.text", "body": format!("Message {}", i) },
+                "prev_events": prev_events,
+                "depth": i as i64 + 1,
+                "auth_events": [],
+                "event_id": id(i),
+                "hashes": { "sha256": "" },
+                "signatures": {},
+            });
+
+            if i % 50 == 0 {
+                event["type"] = json!("m.room.member");
+                event["state_key"] = json!(format!("@user{}:example.org", i % 20));
+                event["content"] = json!({ "membership": "join" });
+            }
+
+            event
+        })
+        .collect()
+}
+
+fn fields() -> HashSet<Field> {
+    [Field::Sender, Field::Type, Field::Depth]
+        .iter()
+        .cloned()
+        .collect()
+}
+
+fn add_events(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_events");
+    group.sample_size(10);
+
+    for size in SIZES.iter() {
+        let events = synthetic_events(*size);
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &events, |b, events| {
+            b.iter_batched(
+                || {
+                    let dag = RoomEvents::from_events(ROOM_ID, SERVER_NAME, &Vec::new());
+
+                    (dag, events.clone())
+                },
+                |(mut dag, events)| dag.add_events(events),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+// `update_event_edges` is private, `rebuild_indices` runs it after rebuilding the maps of the
+// events, which is cheap in comparison
+fn update_event_edges(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_event_edges");
+    group.sample_size(10);
+
+    for size in SIZES.iter() {
+        let mut dag = RoomEvents::from_events(ROOM_ID, SERVER_NAME, &synthetic_events(*size));
+
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| dag.rebuild_indices())
+        });
+    }
+
+    group.finish();
+}
+
+fn create_data_set(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_data_set");
+    group.sample_size(10);
+
+    for size in SIZES.iter() {
+        let dag = RoomEvents::from_events(ROOM_ID, SERVER_NAME, &synthetic_events(*size));
+        let fields = fields();
+
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| dag.create_data_set(&fields))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, add_events, update_event_edges, create_data_set);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+/// The colours the annotations can be tagged with, so that the notes of an investigation can be
+/// told apart at a glance, e.g. the suspects from the culprit.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Tag {
+    #[default]
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl Tag {
+    pub const ALL: [Tag; 6] = [
+        Tag::Red,
+        Tag::Orange,
+        Tag::Yellow,
+        Tag::Green,
+        Tag::Blue,
+        Tag::Purple,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tag::Red => "red",
+            Tag::Orange => "orange",
+            Tag::Yellow => "yellow",
+            Tag::Green => "green",
+            Tag::Blue => "blue",
+            Tag::Purple => "purple",
+        }
+    }
+
+    /// The colour of the border of the annotated nodes, which reads on every theme.
+    pub fn colour(self) -> &'static str {
+        match self {
+            Tag::Red => "#e60000",
+            Tag::Orange => "#ff8000",
+            Tag::Yellow => "#e6c300",
+            Tag::Green => "#00b33c",
+            Tag::Blue => "#0066ff",
+            Tag::Purple => "#9933ff",
+        }
+    }
+}
+
+/// A note pinned by the user to an event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Annotation {
+    pub note: String,
+    #[serde(default)]
+    pub tag: Tag,
+}
+
+/// The annotations of an investigation, by the ID of the event they are pinned to.
+pub type Annotations = BTreeMap<String, Annotation>;
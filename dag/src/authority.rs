@@ -0,0 +1,288 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::rc::Rc;
+
+use serde_derive::Serialize;
+use serde_json::Value as JsonValue;
+
+use super::event::Event;
+
+// The power levels which apply when the room has no `m.room.power_levels` event, or when the
+// event doesn't give them
+const CREATOR_LEVEL: i64 = 100;
+const DEFAULT_STATE_LEVEL: i64 = 50;
+
+/// The membership and the power level of the sender of an event in the state of the room right
+/// before it, and the power level required to send it.
+#[derive(Clone, Debug, Serialize)]
+pub struct SenderAuthority {
+    pub membership: Option<String>, // `None` if the sender never had a membership
+    pub power_level: i64,
+    pub required_level: i64,
+}
+
+impl SenderAuthority {
+    /// Returns why the sender shouldn't have been able to send the event, if they shouldn't.
+    pub fn violation(&self) -> Option<String> {
+        if self.membership.as_deref() != Some("join") {
+            Some(format!(
+                "Sender not joined ({})",
+                self.membership.as_deref().unwrap_or("never was")
+            ))
+        } else if self.power_level < self.required_level {
+            Some(format!(
+                "Power level {} below the {} required",
+                self.power_level, self.required_level
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+// Where an event is replayed: its depth, its timestamp and its index, the state set by the later
+// of two events winning when the states of two branches are merged
+type Position = (i64, i64, usize);
+
+/// The part of the state of a room which tells who can send what.
+#[derive(Clone, Default)]
+struct AuthState {
+    creator: Option<String>,
+    members: HashMap<String, (String, Position)>, // The membership of each user, and where it was set
+    power_levels: Option<(JsonValue, Position)>,  // The content of the `m.room.power_levels` event
+}
+
+impl AuthState {
+    fn power_level_of(&self, user_id: &str) -> i64 {
+        match &self.power_levels {
+            Some((content, _)) => content["users"][user_id]
+                .as_i64()
+                .or_else(|| content["users_default"].as_i64())
+                .unwrap_or(0),
+            None if self.creator.as_deref() == Some(user_id) => CREATOR_LEVEL,
+            None => 0,
+        }
+    }
+
+    fn required_level_of(&self, event: &Event) -> i64 {
+        let content = match &self.power_levels {
+            Some((content, _)) => content,
+            // Without power levels, anyone who joined can send anything
+            None => return 0,
+        };
+
+        // The memberships are governed by their own levels rather than by `state_default`
+        if let ("m.room.member", Some(state_key)) = (event.get_type(), event.get_state_key()) {
+            if state_key == event.get_sender() {
+                return 0;
+            }
+
+            let (key, default) = match event.get_content()["membership"].as_str() {
+                Some("ban") => ("ban", DEFAULT_STATE_LEVEL),
+                Some("leave") => ("kick", DEFAULT_STATE_LEVEL),
+                Some("invite") => ("invite", 0),
+                _ => return 0,
+            };
+
+            return content[key].as_i64().unwrap_or(default);
+        }
+
+        content["events"][event.get_type()]
+            .as_i64()
+            .unwrap_or_else(|| {
+                if event.is_state() {
+                    content["state_default"]
+                        .as_i64()
+                        .unwrap_or(DEFAULT_STATE_LEVEL)
+                } else {
+                    content["events_default"].as_i64().unwrap_or(0)
+                }
+            })
+    }
+
+    fn authority_of(&self, event: &Event) -> SenderAuthority {
+        let sender = event.get_sender();
+        let membership = match (event.get_type(), event.get_state_key()) {
+            // The creation of the room and the changes of their own membership (joining,
+            // accepting an invite, knocking...) don't require the sender to be joined
+            ("m.room.create", _) => Some("join".to_string()),
+            ("m.room.member", Some(state_key)) if state_key == sender => Some("join".to_string()),
+            _ => self
+                .members
+                .get(sender)
+                .map(|(membership, _)| membership.clone()),
+        };
+
+        SenderAuthority {
+            membership,
+            power_level: self.power_level_of(sender),
+            required_level: self.required_level_of(event),
+        }
+    }
+
+    fn changes_with(event: &Event) -> bool {
+        matches!(
+            (event.get_type(), event.get_state_key()),
+            ("m.room.create", Some(""))
+                | ("m.room.power_levels", Some(""))
+                | ("m.room.member", Some(_))
+        )
+    }
+
+    fn apply(&mut self, event: &Event, position: Position) {
+        match (event.get_type(), event.get_state_key()) {
+            ("m.room.create", Some("")) => {
+                let creator = event.get_content()["creator"]
+                    .as_str()
+                    .unwrap_or_else(|| event.get_sender());
+
+                self.creator = Some(creator.to_string());
+            }
+            ("m.room.member", Some(state_key)) => {
+                if let Some(membership) = event.get_content()["membership"].as_str() {
+                    self.members
+                        .insert(state_key.to_string(), (membership.to_string(), position));
+                }
+            }
+            ("m.room.power_levels", Some("")) => {
+                self.power_levels = Some((event.get_content().clone(), position));
+            }
+            _ => {}
+        }
+    }
+
+    // Adds the state of another branch, keeping what was set last when both branches set it.
+    fn merge(&mut self, other: &AuthState) {
+        if self.creator.is_none() {
+            self.creator = other.creator.clone();
+        }
+
+        for (user_id, (membership, position)) in &other.members {
+            match self.members.get(user_id) {
+                Some((_, known)) if known >= position => {}
+                _ => {
+                    self.members
+                        .insert(user_id.clone(), (membership.clone(), *position));
+                }
+            }
+        }
+
+        match (&self.power_levels, &other.power_levels) {
+            (Some((_, known)), Some((_, position))) if known >= position => {}
+            (_, Some(power_levels)) => self.power_levels = Some(power_levels.clone()),
+            (_, None) => {}
+        }
+    }
+}
+
+// Builds the state right before an event from the states after its `prev_events`, which are
+// shared as long as no branch changes them.
+fn resolve(inherited: Vec<Rc<AuthState>>) -> Rc<AuthState> {
+    let mut inherited = inherited.into_iter();
+    let first = match inherited.next() {
+        Some(first) => first,
+        None => return Rc::new(AuthState::default()),
+    };
+    let others: Vec<Rc<AuthState>> = inherited
+        .filter(|state| !Rc::ptr_eq(state, &first))
+        .collect();
+
+    if others.is_empty() {
+        return first;
+    }
+
+    let mut merged = (*first).clone();
+
+    for state in others {
+        merged.merge(&state);
+    }
+
+    Rc::new(merged)
+}
+
+/// Computes the authority of the sender of each of `events`, given along with the indices in
+/// `events` of their `prev_events` which are known.
+///
+/// The state of the room before each event is built by replaying the `m.room.create`,
+/// `m.room.member` and `m.room.power_levels` events of its branch, i.e. of its ancestors. When
+/// branches merge, what was set by the deepest event wins, which approximates the resolution of
+/// the state. The events are replayed once all their `prev_events` are, the order of those ready
+/// at the same time being kept in a heap rather than sorting them all at once.
+///
+/// The events whose `prev_events` are all missing are judged with the state replayed so far in
+/// every branch, and the events in a cycle of `prev_events` aren't judged.
+pub fn replay(events: &[(&Event, Vec<usize>)]) -> HashMap<String, SenderAuthority> {
+    let position = |idx: usize| {
+        let event = events[idx].0;
+
+        (event.depth, event.get_origin_server_ts(), idx)
+    };
+
+    let mut children = vec![Vec::new(); events.len()];
+    let mut waiting: Vec<usize> = events.iter().map(|(_, parents)| parents.len()).collect();
+
+    for (idx, (_, parents)) in events.iter().enumerate() {
+        for &parent in parents {
+            children[parent].push(idx);
+        }
+    }
+
+    // The state after each event, kept until all its children are replayed
+    let mut states: Vec<Option<Rc<AuthState>>> = vec![None; events.len()];
+    let mut unreplayed_children: Vec<usize> = children.iter().map(Vec::len).collect();
+    // Every event replayed in turn, for the events with no known `prev_events`
+    let mut everything = AuthState::default();
+
+    let mut ready: BinaryHeap<Reverse<Position>> = (0..events.len())
+        .filter(|&idx| waiting[idx] == 0)
+        .map(|idx| Reverse(position(idx)))
+        .collect();
+    let mut authorities = HashMap::with_capacity(events.len());
+
+    while let Some(Reverse(pos)) = ready.pop() {
+        let idx = pos.2;
+        let (event, parents) = &events[idx];
+
+        let mut state = if parents.is_empty() {
+            Rc::new(everything.clone())
+        } else {
+            let inherited = parents
+                .iter()
+                .map(|&parent| states[parent].clone().expect("Parent replayed"))
+                .collect();
+
+            // Forget the states no other child needs, so that the state of a chain is changed
+            // in place rather than copied
+            for &parent in parents {
+                unreplayed_children[parent] -= 1;
+
+                if unreplayed_children[parent] == 0 {
+                    states[parent] = None;
+                }
+            }
+
+            resolve(inherited)
+        };
+
+        authorities.insert(event.event_id.clone(), state.authority_of(event));
+
+        if AuthState::changes_with(event) {
+            Rc::make_mut(&mut state).apply(event, pos);
+            everything.apply(event, pos);
+        }
+
+        for &child in &children[idx] {
+            waiting[child] -= 1;
+
+            if waiting[child] == 0 {
+                ready.push(Reverse(position(child)));
+            }
+        }
+
+        if !children[idx].is_empty() {
+            states[idx] = Some(state);
+        }
+    }
+
+    authorities
+}
@@ -0,0 +1,63 @@
+use serde_derive::Serialize;
+
+use super::dag::format_latency;
+
+/// The smallest difference between the clock of a server and the arrival of its events which is
+/// reported by default. The estimates of the arrivals are only as precise as the requests are
+/// fast, and the servers legitimately take a few seconds to send their events.
+pub const DEFAULT_CLOCK_SKEW_THRESHOLD: i64 = 30_000;
+
+/// When a server received an event, estimated from the `unsigned.age` of its copy of the event
+/// and from when the response which brought it was received: `received_at - age`. Every server is
+/// queried by the same client, so the arrivals of the copies of an event on different servers
+/// can be compared, regardless of the clocks of these servers.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Arrival {
+    pub server: String,
+    pub ts: i64, // In ms since the epoch, by the clock of the client
+}
+
+/// An origin server whose clock disagrees with the arrivals of its events, which frequently
+/// explains the confusing orderings of the events by their `origin_server_ts`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ClockSkew {
+    pub origin: String,
+    pub events: usize, // The number of events of the origin whose skew could be estimated
+    pub skew: i64, // The median difference between their `origin_server_ts` and their arrivals, in ms
+}
+
+impl ClockSkew {
+    /// Describes the skew in a few words.
+    pub fn description(&self) -> String {
+        format!(
+            "{}: clock {} by {} (over {} events)",
+            self.origin,
+            if self.skew > 0 { "ahead" } else { "behind" },
+            format_latency(self.skew.abs()),
+            self.events
+        )
+    }
+}
+
+/// Estimates how far ahead of the arrivals of an event its `origin_server_ts` is, in ms. The
+/// arrival on the origin server itself, if it is among `arrivals`, is when the event was
+/// created, so the difference is the skew of the clock of the origin. Otherwise, the event can
+/// only have arrived late, so only an `origin_server_ts` later than the earliest arrival betrays
+/// a skew.
+pub fn event_skew(origin: &str, origin_server_ts: i64, arrivals: &[Arrival]) -> Option<i64> {
+    match arrivals.iter().find(|arrival| arrival.server == origin) {
+        Some(arrival) => Some(origin_server_ts - arrival.ts),
+        None => arrivals
+            .iter()
+            .map(|arrival| origin_server_ts - arrival.ts)
+            .max()
+            .filter(|skew| *skew > 0),
+    }
+}
+
+/// Returns the median of `skews`, which mustn't be empty.
+pub fn median(skews: &mut [i64]) -> i64 {
+    skews.sort_unstable();
+
+    skews[skews.len() / 2]
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+use super::source::SourceEvents;
+
+/// The prefix of the paths of the endpoints of the CS API.
+pub const CLIENT_API_PREFIX: &str = "/_matrix/client/v3";
+
+/// The fields of the events kept by the filters, which are all the fields of the PDUs.
+pub const EVENT_FIELDS: [&str; 15] = [
+    "room_id",
+    "sender",
+    "origin",
+    "origin_server_ts",
+    "type",
+    "state_key",
+    "content",
+    "prev_events",
+    "depth",
+    "auth_events",
+    "redacts",
+    "unsigned",
+    "event_id",
+    "hashes",
+    "signatures",
+];
+
+/// Returns the filter giving the events in the federation format with only the fields required
+/// to observe the room. The events in the federation format include informations like their
+/// depth in the DAG and the IDs of their previous events, which allow the DAG to be built.
+pub fn event_filter() -> JsonValue {
+    json!({
+        "event_fields": EVENT_FIELDS,
+        "event_format": "federation",
+    })
+}
+
+/// Returns the filter of the syncs, which drops everything but the timeline, the state, the read
+/// receipts and the fully-read marker of the room `room_id`, so that the initial sync of an
+/// account in many rooms stays small.
+pub fn sync_filter(room_id: &str, timeline_limit: u32) -> JsonValue {
+    json!({
+        "event_fields": EVENT_FIELDS,
+        "event_format": "federation",
+        "presence": { "types": [] },
+        "account_data": { "types": [] },
+        "room": {
+            "rooms": [room_id],
+            "timeline": { "limit": timeline_limit },
+            "ephemeral": { "types": ["m.receipt"] },
+            "account_data": { "types": ["m.fully_read"] },
+        },
+    })
+}
+
+/// Returns the path of a sync from the token `since`, or of the initial sync, with `filter`,
+/// either the ID of a filter or a percent-encoded filter.
+pub fn sync_path(filter: &str, since: Option<&str>) -> String {
+    let mut path = format!(
+        "{}/sync?filter={}&set_presence=offline&timeout=5000",
+        CLIENT_API_PREFIX, filter
+    );
+
+    if let Some(since) = since {
+        path.push_str("&since=");
+        path.push_str(since);
+    }
+
+    path
+}
+
+/// Returns the path of a request for the events of the room `room_id` preceding the token `from`,
+/// with the percent-encoded `filter`.
+pub fn messages_path(room_id: &str, from: &str, filter: &str) -> String {
+    format!(
+        "{}/rooms/{}/messages?from={}&dir=b&filter={}",
+        CLIENT_API_PREFIX, room_id, from, filter
+    )
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/v3/sync` request.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SyncResponse {
+    pub next_batch: String,
+    #[serde(default)]
+    pub rooms: Rooms,
+    presence: Option<JsonValue>,
+    #[serde(default)]
+    account_data: JsonValue,
+    to_device: Option<JsonValue>,
+    device_lists: Option<JsonValue>,
+    #[serde(default)]
+    device_one_time_keys_count: HashMap<String, u64>,
+}
+
+impl SyncResponse {
+    /// Returns the response without the private data of the user which the model doesn't use,
+    /// i.e. the to-device messages and the account data, except for the fully-read markers of
+    /// the rooms, so that it can be attached to a bug report.
+    pub fn without_private_data(&self) -> SyncResponse {
+        let mut res = self.clone();
+
+        res.account_data = JsonValue::Null;
+        res.to_device = None;
+
+        for room in res.rooms.join.values_mut() {
+            room.account_data
+                .events
+                .retain(|ev| ev["type"] == "m.fully_read");
+        }
+
+        res
+    }
+
+    /// Returns the events of the timeline of the room `room_id`, with the token from which its
+    /// earlier events are paginated. There are none if the user isn't in the room.
+    pub fn room_events(&self, room_id: &str) -> (SourceEvents, Option<String>) {
+        match self.rooms.join.get(room_id) {
+            Some(room) => (
+                SourceEvents::from_events(room.timeline.events.clone()),
+                room.timeline.prev_batch.clone(),
+            ),
+            None => (SourceEvents::default(), None),
+        }
+    }
+}
+
+/// Represents the list of rooms in `SyncResponse`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Rooms {
+    #[serde(default)]
+    leave: HashMap<String, JsonValue>,
+    #[serde(default)]
+    pub join: HashMap<String, JoinedRoom>,
+    #[serde(default)]
+    invite: HashMap<String, JsonValue>,
+}
+
+/// Represents the list of rooms joined by the user in `SyncResponse`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JoinedRoom {
+    #[serde(default)]
+    pub unread_notifications: JsonValue,
+    #[serde(default)]
+    pub timeline: Timeline,
+    #[serde(default)]
+    pub state: State,
+    #[serde(default)]
+    pub account_data: EventList,
+    #[serde(default)]
+    pub ephemeral: EventList,
+}
+
+/// Represents the ephemeral events or the account data events of a room in `SyncResponse`, e.g.
+/// the read receipts and the fully-read marker of the user.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct EventList {
+    #[serde(default)]
+    pub events: Vec<JsonValue>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    // TODO: Implement StateEvent
+    #[serde(default)]
+    pub events: Vec<JsonValue>,
+}
+
+/// Represents the timeline of a room in `SyncResponse`. These are the events of the DAG the
+/// application must build for the observed room.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Timeline {
+    #[serde(default)]
+    pub limited: bool,
+    pub prev_batch: Option<String>,
+    // TODO: Implement RoomEvent
+    #[serde(default)]
+    pub events: Vec<JsonValue>,
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/v3/rooms/{roomId}/messages`
+/// request.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MessagesResponse {
+    pub start: String,
+    #[serde(default)]
+    pub end: String,
+    pub chunk: Vec<JsonValue>,
+}
+
+impl MessagesResponse {
+    /// Returns the events of the page, with the token from which the next page is paginated.
+    pub fn into_source_events(self) -> (SourceEvents, String) {
+        (SourceEvents::from_events(self.chunk), self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_paths_of_the_v3_endpoints() {
+        assert_eq!(
+            sync_path("42", None),
+            "/_matrix/client/v3/sync?filter=42&set_presence=offline&timeout=5000"
+        );
+        assert_eq!(
+            sync_path("42", Some("s72_1")),
+            "/_matrix/client/v3/sync?filter=42&set_presence=offline&timeout=5000&since=s72_1"
+        );
+        assert_eq!(
+            messages_path("!room:example.org", "t47", "%7B%7D"),
+            "/_matrix/client/v3/rooms/!room:example.org/messages?from=t47&dir=b&filter=%7B%7D"
+        );
+    }
+
+    #[test]
+    fn asks_for_the_pdus() {
+        let filter = sync_filter("!room:example.org", 20);
+
+        assert_eq!(filter["event_format"], "federation");
+        assert_eq!(filter["room"]["rooms"], json!(["!room:example.org"]));
+        assert_eq!(filter["room"]["timeline"]["limit"], 20);
+        assert_eq!(filter["event_fields"], event_filter()["event_fields"]);
+        assert!(EVENT_FIELDS.contains(&"prev_events"));
+        assert!(EVENT_FIELDS.contains(&"depth"));
+    }
+
+    #[test]
+    fn maps_the_sync_to_the_events_of_the_room() {
+        let res: SyncResponse = serde_json::from_value(json!({
+            "next_batch": "s72_2",
+            "rooms": {
+                "join": {
+                    "!room:example.org": {
+                        "timeline": {
+                            "limited": true,
+                            "prev_batch": "t47",
+                            "events": [{ "event_id": "$2:example.org" }],
+                        },
+                    },
+                    "!other:example.org": {
+                        "timeline": { "events": [{ "event_id": "$3:example.org" }] },
+                    },
+                },
+            },
+            "to_device": { "events": [] },
+        }))
+        .unwrap();
+
+        let (events, prev_batch) = res.room_events("!room:example.org");
+        assert_eq!(events.events, vec![json!({ "event_id": "$2:example.org" })]);
+        assert_eq!(prev_batch.as_deref(), Some("t47"));
+
+        let (events, prev_batch) = res.room_events("!left:example.org");
+        assert!(events.events.is_empty());
+        assert_eq!(prev_batch, None);
+    }
+
+    #[test]
+    fn maps_the_messages_to_their_events() {
+        let res: MessagesResponse = serde_json::from_value(json!({
+            "start": "t47",
+            "end": "t12",
+            "chunk": [{ "event_id": "$1:example.org" }],
+        }))
+        .unwrap();
+
+        let (events, end) = res.into_source_events();
+        assert_eq!(events.events, vec![json!({ "event_id": "$1:example.org" })]);
+        assert_eq!(end, "t12");
+
+        // The last page has no end
+        let res: MessagesResponse =
+            serde_json::from_value(json!({ "start": "t12", "chunk": [] })).unwrap();
+        assert_eq!(res.into_source_events().1, "");
+    }
+}
@@ -0,0 +1,4841 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::mem;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use petgraph::algo::{astar, has_path_connecting, toposort, DfsSpace};
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::visit::{Bfs, EdgeRef};
+use petgraph::{Directed, Direction};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::annotation::Annotations;
+use super::authority::{self, SenderAuthority};
+use super::clock_skew::{self, Arrival, ClockSkew, DEFAULT_CLOCK_SKEW_THRESHOLD};
+use super::dataset::{edge_id, EdgeRegistry};
+pub use super::dataset::{
+    DataSet, DataSetEdge, DataSetNode, EdgeColor, NodeColor, NodeFont, ShapeProperties,
+};
+use super::depth_anomaly::{DepthAnomaly, DepthAnomalyKind};
+use super::event::{escape_html, Event, Field, RelationType};
+use super::event_table::{self, EventRow, SortKey};
+use super::export::ExportedView;
+use super::expression::{ColourRule, Expression};
+use super::genealogy::RoomIncarnation;
+use super::id_shortener::IdShortener;
+use super::inspector::{self, ContentView};
+use super::interner::StringInterner;
+use super::journal::{JournalEntry, JournalEvent, ParseStatus};
+use super::label_format::LabelFormat;
+use super::metrics::{DagMetrics, Fork};
+use super::outline::{self, OutlineEntry, OutlineMarker};
+use super::provenance::FetchBatch;
+use super::query::EventFilter;
+use super::receipts::{MarkerKind, ReadMarkers};
+use super::reconcile::Reconciler;
+use super::reference_hash;
+use super::room_version::{room_version_of, RoomVersionRules, VersionMismatch};
+use super::source::{EventSource, SourceEvents, SourceRequest};
+use super::state_timeline::{self, StateTimelineEntry};
+use super::stats::{self, RoomStats};
+use super::theme::Theme;
+
+/// The internal representation of the events DAG of the room being observed as well as various
+/// informations and `HashMap`s which makes easier to locate the events.
+pub struct RoomEvents {
+    room_id: String,          // The ID of the room
+    server_name: String,      // The name of the server this DAG was retrieved from
+    display: DisplaySettings, // How the DAG is drawn in the vis.js network
+
+    dag: Graph<Event, (), Directed>,         // The DAG of the events
+    interner: StringInterner, // The strings shared by the events of the DAG, e.g. their types and senders
+    events_map: HashMap<String, NodeIndex>, // Allows to quickly locate an event in the DAG with its ID
+    depth_map: HashMap<i64, Vec<NodeIndex>>, // Allows to quickly locate events at a given depth in the DAG
+    observed_depths: Vec<i64>, // The depths at which the DAG has events, in increasing order
+    pub latest_events: Vec<String>, // The ID of the latest events in the DAG
+    pub earliest_events: Vec<String>, // The ID of the earliest events in the DAG
+    pub orphan_events: Vec<OrphanInfo>, // The events with missing ancestors in the DAG, and these ancestors
+    max_depth: i64,                     // Minimal depth of the events in the DAG
+    min_depth: i64,                     // Maximal depth of the events in the DAG
+
+    servers: Vec<String>, // For a merged DAG, the names of the servers whose DAGs were merged
+    presence: HashMap<String, u64>, // For a merged DAG, the bitmask of the servers which have each event
+    arrivals: HashMap<String, Vec<Arrival>>, // For a merged DAG, when each server received each event, if it can be estimated
+    dead_servers: HashSet<String>,           // The origin servers which couldn't be reached
+    outage: Option<SimulatedOutage>, // A server taken offline to see which events it couldn't have received
+    annotations: Annotations,        // The notes pinned to the events by the user
+    hidden: Vec<HiddenItem>,         // The events left out of the vis.js network by the user
+    highlighted: HashSet<String>,    // The events whose nodes are outlined in the vis.js network
+    collapsed_groups: Vec<HashSet<String>>, // The groups of events each drawn as a single node
+    stream_orderings: HashMap<String, i64>, // The order in which the server persisted the events, if the source knows it
+    received_ts: HashMap<String, i64>, // When the server which persisted the events received them, if the source knows it
+    batches: Vec<FetchBatch>, // The requests which brought events to the DAG, in the order they were recorded
+    provenance: HashMap<String, usize>, // The index in `batches` of the request each event first came in
+    arrival_ranks: HashMap<String, u64>, // The order in which the events were first added to the DAG, see `arrival_order`
+    next_arrival_rank: u64,              // The rank of the next event added to the DAG
+    read_markers: ReadMarkers, // Where the read receipts and fully-read markers of the users point
+    create_version: Option<String>, // The version of the room given by its creation event, once it is in the DAG
+    declared_version: Option<String>, // The version of the room given by the source, e.g. before its creation event is in the DAG
+
+    state_conflicts: Vec<StateConflict>, // The pairs of concurrent state events for the same state
+    authority: HashMap<String, SenderAuthority>, // The authority of the sender of each event, if the creation of the room is known
+    conflict_report: ConflictReport, // For a merged DAG, the events whose copies differ across the DAGs merged
+    cycle: Option<Vec<String>>, // A cycle of `prev_events` found in malformed data, if there is one
+    unparsed_events: Vec<UnparsedEvent>, // The events received which couldn't be parsed
+    reconciler: Reconciler, // Suppresses the events delivered more than once by the concurrent streams
+    predecessor: Option<Box<RoomEvents>>, // The DAG of the room this one replaced, stitched above it
+    derived_depths: bool, // Whether the depths of the events were derived from their `prev_events`
+    derived_nodes: usize, // The number of nodes whose depths are up to date, the others were added since
+    analyses: Analyses,   // The analyses of the events computed since the DAG last changed
+}
+
+/// The analyses of a DAG which are too costly to be computed whenever the page is rendered. They
+/// are computed on first use and forgotten whenever events are added to or removed from the DAG,
+/// or their rejections or the version of the room change.
+#[derive(Default)]
+struct Analyses {
+    stats: OnceLock<RoomStats>, // The statistics of the events
+    interesting_events: OnceLock<Vec<InterestingEvent>>, // All the interesting events, from the most to the least
+    incarnation: OnceLock<RoomIncarnation>, // What the events tell about the room and its upgrades
+    version_mismatches: OnceLock<(Vec<VersionMismatch>, HashMap<String, usize>)>, // The events which can't be valid in the version of the room, and where each of them is in the list
+    redundant_edges: OnceLock<HashSet<(NodeIndex, NodeIndex)>>, // The edges implied by longer paths
+    descendants: OnceLock<Vec<usize>>, // The number of descendants of each event, by node index
+    outline: OnceLock<Vec<OutlineEntry>>, // The text outline, which is also forgotten when the IDs are shortened differently
+    clock_skews: OnceLock<Vec<ClockSkew>>, // The skews of all the origin servers, by decreasing skew
+    depth_anomalies: Mutex<Option<(i64, Vec<DepthAnomaly>)>>, // The depth anomalies, along with the jump threshold they were found with
+    event_rows: Mutex<Option<SortedRows>>, // The rows of the list view, as they were last sorted
+}
+
+// The rows of the list view, along with the column they are sorted by and whether they are in
+// descending order
+type SortedRows = (SortKey, bool, Arc<Vec<EventRow>>);
+
+/// How a DAG is drawn in the vis.js network. The DAGs derived from a DAG (merged with others,
+/// extracted from it or stitched above it) are drawn like it.
+#[derive(Clone, Default)]
+struct DisplaySettings {
+    size_metric: Option<SizeMetric>, // The metric used to scale the nodes of the vis.js network
+    show_relations: bool, // Whether the relations between events are added as edges to the vis.js network
+    show_ghosts: bool, // Whether the missing `prev_events` are added as ghost nodes to the vis.js network
+    show_authority: bool, // Whether the membership and the power level of the senders are shown on the nodes
+    compress_depths: bool, // Whether the levels of the nodes are the ranks of the depths rather than the depths
+    transitive_reduction: bool, // Whether the edges implied by longer paths are left out of the vis.js network
+    color_by_latency: bool, // Whether the nodes are coloured by the delivery latency of their events
+    color_by_batch: bool, // Whether the nodes are coloured by the request which brought their events
+    show_read_markers: bool, // Whether the read receipts and fully-read markers are shown on the nodes
+    colour_rules: Vec<(Expression, String)>, // The expressions giving their colours to the nodes of the events they match
+    id_shortener: IdShortener, // Shortens the event IDs in the labels on the nodes of the vis.js network
+    label_format: LabelFormat, // Lays out the fields in the labels on the nodes of the vis.js network
+    theme: Theme,              // Gives its colours to the vis.js network
+}
+
+/// A simulated outage of a server, which stopped receiving the events of the others after a given
+/// event.
+#[derive(Clone, Debug)]
+pub struct SimulatedOutage {
+    pub server: String,                   // The server taken offline
+    pub last_event: String,               // The last event the server received before going offline
+    pub missed: HashSet<String>,          // The events of the DAG the server couldn't have received
+    pub created_offline: HashSet<String>, // The events the server created while offline, which the others couldn't have received
+}
+
+/// Events hidden together by the user, which are brought back together.
+#[derive(Clone, Debug)]
+pub struct HiddenItem {
+    pub root: String, // The event hidden, or the first event of the branch hidden
+    pub events: HashSet<String>, // Every event hidden, the root included
+}
+
+/// What the user left out of the vis.js network, outlined and collapsed, so that it can be put
+/// back when a filter is undone.
+#[derive(Clone, Debug, Default)]
+pub struct FilterState {
+    hidden: Vec<HiddenItem>,
+    highlighted: HashSet<String>,
+    collapsed_groups: Vec<HashSet<String>>,
+}
+
+/// An event which couldn't be parsed, and which is therefore not in the DAG.
+#[derive(Clone, Debug)]
+pub struct UnparsedEvent {
+    pub json: JsonValue,
+    pub error: String,
+}
+
+/// An event removed from the DAG by `RoomEvents::remove_events`, along with what the DAG knew
+/// about it besides its content, so that `RoomEvents::restore_events` adds it back as it was.
+pub struct RemovedEvent {
+    pub event: Event,
+    stream_ordering: Option<i64>,
+    received_ts: Option<i64>,
+    provenance: Option<usize>,
+    presence: Option<u64>,
+    arrivals: Option<Vec<Arrival>>,
+    arrival_rank: Option<u64>,
+}
+
+// The delivery latencies in ms from which the events get the next colour of `Palette::latency`.
+const LATENCY_THRESHOLDS: [i64; 3] = [1_000, 10_000, 60_000];
+
+// The kind of the edges from the events to their `prev_events`, see `dataset::edge_id`.
+const PREV_EVENT_EDGE: &str = "prev_event";
+
+// The badges representing the servers of a merged DAG in the labels of the nodes.
+const SERVER_BADGES: [&str; 7] = ["🟥", "🟦", "🟩", "🟨", "🟪", "🟧", "🟫"];
+const ABSENT_BADGE: &str = "⬜";
+
+/// An event of the DAG whose `prev_events` aren't all in the DAG, which borders a gap in the
+/// history known of the room.
+#[derive(Clone, Debug, Serialize)]
+pub struct OrphanInfo {
+    pub id: String, // The ID of the orphan, which is the child of the missing events
+    pub depth: i64,
+    pub level: i64,           // The level of the event in the vis.js network
+    pub missing: Vec<String>, // The IDs of the `prev_events` of the orphan which are not in the DAG
+}
+
+/// A pair of concurrent state events, i.e. neither of them is an ancestor of the other, with the
+/// same type and state key. These are the events state resolution has to arbitrate between.
+#[derive(Clone, Debug, Serialize)]
+pub struct StateConflict {
+    pub etype: String,
+    pub state_key: String,
+    pub events: (String, String),
+}
+
+/// The metrics which can be used to scale the nodes of the vis.js network, so that the
+/// structurally important events stand out.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SizeMetric {
+    Descendants,      // The number of events which have the event as ancestor
+    PrevEvents,       // The number of `prev_events` of the event
+    ContentSize,      // The size of the serialized content of the event
+    CarrierServers,   // The number of servers which have the event in a merged DAG
+    SenderPowerLevel, // The power level of the sender of the event right before it
+}
+
+/// The serializable form of a `RoomEvents`, used to store the DAG and restore it later. The
+/// structure of the DAG is rebuilt from the events when it is restored.
+#[derive(Deserialize, Serialize)]
+pub struct StoredRoomEvents {
+    pub room_id: String,
+    pub server_name: String,
+    pub events: Vec<Event>,
+    #[serde(default)]
+    pub rejections: HashMap<String, String>, // The reasons of the rejection of the rejected events, which the events don't serialize
+    #[serde(default)]
+    pub soft_failed: Vec<String>, // The IDs of the events which have soft-failed
+}
+
+/// Two copies of the same event, received from different sources, which differ. This is what
+/// happens when the servers disagree on the event, a failure of the federation worth looking at.
+#[derive(Clone, Debug, Serialize)]
+pub struct DivergentCopies {
+    pub event_id: String,
+    pub sources: (String, String), // The sources of the first copy found and of the divergent one
+    pub fields: Vec<&'static str>, // The fields in which the copies differ
+}
+
+/// Lists the events of a merged DAG whose copies differ across the DAGs which were merged.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ConflictReport {
+    pub divergent: Vec<DivergentCopies>,
+}
+
+impl ConflictReport {
+    /// Returns the divergent copies of the event `id`, if they diverge.
+    pub fn get(&self, id: &str) -> Option<&DivergentCopies> {
+        self.divergent.iter().find(|copies| copies.event_id == id)
+    }
+}
+
+/// Lists the events referenced in the `prev_events` of some events of the DAG which are not in
+/// the DAG, i.e. the events the server never sent us.
+#[derive(Clone, Debug, Serialize)]
+pub struct DagHealthReport {
+    pub missing_events: Vec<MissingEvent>,
+}
+
+/// An event missing from the DAG along with the events referencing it.
+#[derive(Clone, Debug, Serialize)]
+pub struct MissingEvent {
+    pub id: String,
+    pub count: usize,
+    pub referenced_by: Vec<String>,
+}
+
+/// What a DAG is made of at a glance, cheap enough to be computed whenever the page is rendered.
+#[derive(Clone, Debug, Serialize)]
+pub struct RoomSummary {
+    pub room_id: String,
+    pub server_name: String,
+    pub events: usize,
+    pub depth_range: Option<(i64, i64)>, // The minimal and the maximal depth of the events, if there are some
+    pub forward_extremities: usize,
+    pub backward_extremities: usize,
+    pub orphans: usize, // Number of events with missing `prev_events`
+    pub room_version: Option<String>, // The version of the room, if it is known
+}
+
+/// The reasons why an event is worth a look, from the most to the least important.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Interest {
+    Anomaly, // The event was rejected, soft-failed, misses ancestors, is in a state conflict or mismatches its origin
+    Moderation, // The event bans or kicks a user, or changes the ACLs, the rules or the power levels
+    Fork,       // Several events have the event in their `prev_events`
+    Merge,      // The event has several `prev_events`
+    StateChange, // The event changes another part of the state of the room
+}
+
+/// A noteworthy event of the DAG, see `RoomEvents::interesting_events`.
+#[derive(Clone, Debug)]
+pub struct InterestingEvent {
+    pub id: String,
+    pub depth: i64,
+    pub interest: Interest,
+    pub description: String,
+}
+
+// The state events changing who can do what in the room, along with their description
+const MODERATION_EVENTS: [(&str, &str); 4] = [
+    ("m.room.server_acl", "Server ACL change"),
+    ("m.room.power_levels", "Power levels change"),
+    ("m.room.join_rules", "Join rules change"),
+    ("m.room.history_visibility", "History visibility change"),
+];
+
+/// A region of unknown history below an orphan event, i.e. the ancestors of the event which are
+/// not in the DAG.
+#[derive(Clone, Debug, Serialize)]
+pub struct FogRegion {
+    pub orphan: String,       // The ID of the orphan event bordering the region
+    pub depth: i64,           // The depth of the orphan event
+    pub level: i64,           // The level of the orphan event in the vis.js network
+    pub missing: Vec<String>, // The IDs of the `prev_events` of the orphan which are not in the DAG
+    pub estimate: i64,        // A lower bound of the number of events in the region
+}
+
+/// Describes what adding some events to the DAG would change, without actually adding them.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AddPreview {
+    pub new_nodes: usize,
+    pub new_edges: usize,
+    pub resolved_orphans: Vec<String>, // The orphans whose ancestors would all be in the DAG
+    pub added_latest_events: Vec<String>,
+    pub removed_latest_events: Vec<String>,
+    pub added_earliest_events: Vec<String>,
+    pub removed_earliest_events: Vec<String>,
+}
+
+/// What adding some events to the DAG did, so that the redundant fetches can be detected.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct AddOutcome {
+    pub added: usize,    // The events added as new nodes
+    pub skipped: usize,  // The events which were already in the DAG, or delivered twice
+    pub unparsed: usize, // The events which couldn't be parsed
+}
+
+impl AddOutcome {
+    /// Tells whether events were received but none of them was new.
+    pub fn is_redundant(&self) -> bool {
+        self.added == 0 && self.skipped > 0
+    }
+}
+
+impl RoomEvents {
+    /// Creates an event DAG from the events retrieved from a backend.
+    pub fn from_source_events(room_id: &str, server_name: &str, res: SourceEvents) -> RoomEvents {
+        let mut dag = RoomEvents::from_events(room_id, server_name, &res.events);
+
+        dag.annotate_rejections(&res.rejections, &res.soft_failed);
+        dag.stream_orderings = res.stream_orderings;
+        dag.received_ts = res.received_ts;
+
+        dag
+    }
+
+    /// Creates an event DAG from the initial events of `source`.
+    pub fn from_source<S: EventSource + ?Sized>(
+        room_id: &str,
+        server_name: &str,
+        source: &mut S,
+    ) -> Result<RoomEvents, S::Error> {
+        let res = source.fetch(&SourceRequest::Initial)?;
+
+        Ok(RoomEvents::from_source_events(room_id, server_name, res))
+    }
+
+    /// Creates an event DAG from a list of events encoded as JSON values.
+    pub fn from_events(room_id: &str, server_name: &str, events: &Vec<JsonValue>) -> RoomEvents {
+        let (events, unparsed_events) = parse_events(events, || None);
+
+        let mut dag = RoomEvents {
+            room_id: room_id.to_string(),
+            server_name: server_name.to_string(),
+            display: DisplaySettings::default(),
+
+            dag: Graph::new(),
+            events_map: HashMap::with_capacity(events.len()),
+            depth_map: HashMap::with_capacity(events.len()),
+            observed_depths: Vec::new(),
+            latest_events: Vec::new(),
+            earliest_events: Vec::new(),
+            orphan_events: Vec::new(),
+            max_depth: -1,
+            min_depth: -1,
+
+            servers: Vec::new(),
+            presence: HashMap::new(),
+            arrivals: HashMap::new(),
+            dead_servers: HashSet::new(),
+            outage: None,
+            annotations: Annotations::new(),
+            hidden: Vec::new(),
+            highlighted: HashSet::new(),
+            collapsed_groups: Vec::new(),
+            stream_orderings: HashMap::new(),
+            received_ts: HashMap::new(),
+            batches: Vec::new(),
+            provenance: HashMap::new(),
+            arrival_ranks: HashMap::new(),
+            next_arrival_rank: 0,
+            read_markers: ReadMarkers::new(),
+            create_version: None,
+            declared_version: None,
+
+            state_conflicts: Vec::new(),
+            authority: HashMap::new(),
+            conflict_report: ConflictReport::default(),
+            cycle: None,
+            unparsed_events,
+            reconciler: Reconciler::default(),
+            interner: StringInterner::new(),
+            predecessor: None,
+            derived_depths: false,
+            derived_nodes: 0,
+            analyses: Analyses::default(),
+        };
+
+        dag.add_event_nodes(events);
+        dag.update_event_edges();
+
+        dag
+    }
+
+    /// Creates an event DAG from its stored form.
+    pub fn from_stored(stored: StoredRoomEvents) -> RoomEvents {
+        let mut dag = RoomEvents::from_events(&stored.room_id, &stored.server_name, &Vec::new());
+
+        dag.add_event_nodes(stored.events);
+        dag.annotate_rejections(&stored.rejections, &stored.soft_failed);
+        dag.update_event_edges();
+
+        dag
+    }
+
+    /// Creates the union of the DAGs `dags` retrieved from different servers. Each event of the
+    /// merged DAG carries a bitmask telling which servers have it (the bit `i` being set if the
+    /// DAG `dags[i]` has it), which is rendered as badges in the labels of the nodes. Only the
+    /// first 64 DAGs are taken into account.
+    ///
+    /// The copies of each event found in the DAGs are compared with the first one, and those
+    /// which differ are listed in the `ConflictReport` of the merged DAG.
+    pub fn merged(dags: &[&RoomEvents]) -> Option<RoomEvents> {
+        let first = dags.first()?;
+        let servers: Vec<String> = dags
+            .iter()
+            .take(64)
+            .map(|dag| dag.server_name.clone())
+            .collect();
+
+        let mut merged = RoomEvents::from_events(&first.room_id, &servers.join(", "), &Vec::new());
+        let mut events: Vec<Event> = Vec::new();
+        // The DAG in which the first copy of each event was found, and its index in `events`
+        let mut first_copies: HashMap<String, (usize, usize)> = HashMap::new();
+
+        for (i, dag) in dags.iter().take(64).enumerate() {
+            for node in dag.dag.raw_nodes() {
+                let id = &node.weight.event_id;
+
+                match first_copies.get(id) {
+                    None => {
+                        first_copies.insert(id.clone(), (i, events.len()));
+                        events.push(node.weight.clone());
+
+                        // The latency of the first copy is the one shown
+                        if let Some(&received_ts) = dag.received_ts.get(id) {
+                            merged.received_ts.insert(id.clone(), received_ts);
+                        }
+                    }
+                    Some(&(first_dag, first)) => {
+                        let fields = events[first].divergent_fields(&node.weight);
+
+                        if !fields.is_empty() {
+                            merged.conflict_report.divergent.push(DivergentCopies {
+                                event_id: id.clone(),
+                                sources: (servers[first_dag].clone(), servers[i].clone()),
+                                fields,
+                            });
+                        }
+                    }
+                }
+
+                *merged.presence.entry(id.clone()).or_insert(0) |= 1 << i;
+
+                if let Some(ts) = dag.arrival_ts(id) {
+                    merged
+                        .arrivals
+                        .entry(id.clone())
+                        .or_default()
+                        .push(Arrival {
+                            server: servers[i].clone(),
+                            ts,
+                        });
+                }
+            }
+
+            merged.read_markers.extend(&dag.read_markers);
+        }
+
+        merged.display = first.display.clone();
+        merged.declared_version = first.declared_version.clone();
+        merged.set_dedup_window(first.reconciler.window());
+        merged.dead_servers = first.dead_servers.clone();
+        merged.outage = first.outage.clone();
+        merged.annotations = first.annotations.clone();
+        merged.hidden = first.hidden.clone();
+        merged.highlighted = first.highlighted.clone();
+        merged.collapsed_groups = first.collapsed_groups.clone();
+        merged.servers = servers;
+        merged.add_event_nodes(events);
+        merged.update_event_edges();
+
+        Some(merged)
+    }
+
+    /// Extracts the events of the DAG whose IDs are in `ids`, along with the edges between them,
+    /// into a DAG of their own with the same display settings. The IDs of events which are not in
+    /// the DAG are ignored.
+    ///
+    /// The `prev_events` of the extracted events which were left out make them orphans of the
+    /// subgraph, like in any partial DAG.
+    pub fn subgraph(&self, ids: &[String]) -> RoomEvents {
+        let mut sub = RoomEvents::from_events(&self.room_id, &self.server_name, &Vec::new());
+        let events: Vec<Event> = ids
+            .iter()
+            .filter_map(|id| self.events_map.get(id))
+            .map(|&idx| self.dag[idx].clone())
+            .collect();
+
+        for ev in &events {
+            if let Some(&mask) = self.presence.get(&ev.event_id) {
+                sub.presence.insert(ev.event_id.clone(), mask);
+            }
+
+            if let Some(arrivals) = self.arrivals.get(&ev.event_id) {
+                sub.arrivals.insert(ev.event_id.clone(), arrivals.clone());
+            }
+
+            if let Some(&stream_ordering) = self.stream_orderings.get(&ev.event_id) {
+                sub.stream_orderings
+                    .insert(ev.event_id.clone(), stream_ordering);
+            }
+
+            if let Some(&received_ts) = self.received_ts.get(&ev.event_id) {
+                sub.received_ts.insert(ev.event_id.clone(), received_ts);
+            }
+
+            if let Some(&batch) = self.provenance.get(&ev.event_id) {
+                sub.provenance.insert(ev.event_id.clone(), batch);
+            }
+        }
+
+        // The batches keep their indices, even those of which no event was extracted
+        sub.batches = self.batches.clone();
+        sub.read_markers = self.read_markers.clone();
+        sub.declared_version = self.declared_version.clone();
+
+        sub.display = self.display.clone();
+        sub.set_dedup_window(self.reconciler.window());
+        sub.dead_servers = self.dead_servers.clone();
+        sub.annotations = self.annotations.clone();
+        sub.hidden = self.hidden.clone();
+        sub.highlighted = self.highlighted.clone();
+        sub.collapsed_groups = self.collapsed_groups.clone();
+        sub.servers = self.servers.clone();
+        sub.add_event_nodes(events);
+        sub.update_event_edges();
+
+        sub
+    }
+
+    /// Returns the servers of a merged DAG along with their badges.
+    pub fn server_badges(&self) -> Vec<(String, &'static str)> {
+        self.servers
+            .iter()
+            .enumerate()
+            .map(|(i, server)| (server.clone(), SERVER_BADGES[i % SERVER_BADGES.len()]))
+            .collect()
+    }
+
+    /// Converts the DAG into a form which can be stored.
+    pub fn to_stored(&self) -> StoredRoomEvents {
+        let mut rejections = HashMap::new();
+        let mut soft_failed = Vec::new();
+
+        for node in self.dag.raw_nodes() {
+            let ev = &node.weight;
+
+            if let Some(reason) = ev.get_rejection() {
+                rejections.insert(ev.event_id.clone(), reason.to_string());
+            }
+            if ev.is_soft_failed() {
+                soft_failed.push(ev.event_id.clone());
+            }
+        }
+
+        StoredRoomEvents {
+            room_id: self.room_id.clone(),
+            server_name: self.server_name.clone(),
+            events: self
+                .dag
+                .raw_nodes()
+                .iter()
+                .map(|n| n.weight.clone())
+                .collect(),
+            rejections,
+            soft_failed,
+        }
+    }
+
+    /// Converts the DAG into its exported form, keeping the annotations of the events and the
+    /// origin servers known to be dead.
+    pub fn to_exported(&self) -> ExportedView {
+        let stored = self.to_stored();
+
+        ExportedView {
+            room_id: stored.room_id,
+            server_name: stored.server_name,
+            events: stored.events,
+            rejections: stored.rejections,
+            soft_failed: stored.soft_failed,
+            dead_servers: self.dead_servers.iter().cloned().collect(),
+            view_idx: None,
+        }
+    }
+
+    /// Rebuilds a DAG from its exported form.
+    pub fn from_exported(exported: ExportedView) -> RoomEvents {
+        let stored = StoredRoomEvents {
+            room_id: exported.room_id,
+            server_name: exported.server_name,
+            events: exported.events,
+            rejections: exported.rejections,
+            soft_failed: exported.soft_failed,
+        };
+
+        let mut dag = RoomEvents::from_stored(stored);
+        dag.set_dead_servers(exported.dead_servers.into_iter().collect());
+
+        dag
+    }
+
+    /// Adds the events of `stored` which are not already in the DAG.
+    pub fn merge_stored(&mut self, stored: StoredRoomEvents) {
+        self.merge_stored_deferred(stored);
+        self.rebuild_indices();
+    }
+
+    /// Same as `merge_stored`, but leaves the edges and the extremities of the DAG out of date
+    /// until `rebuild_indices` is called.
+    pub fn merge_stored_deferred(&mut self, stored: StoredRoomEvents) {
+        let new_events: Vec<Event> = stored
+            .events
+            .into_iter()
+            .filter(|ev| !self.events_map.contains_key(&ev.event_id))
+            .collect();
+
+        self.add_event_nodes(new_events);
+        self.annotate_rejections(&stored.rejections, &stored.soft_failed);
+    }
+
+    /// Removes the events whose IDs are in `ids` from the DAG, e.g. to undo the operation which
+    /// added them, and returns them so that `restore_events` can add them back. Their deliveries
+    /// are forgotten, so that they can be fetched again, and what the DAG knew about them is taken
+    /// along with them.
+    pub fn remove_events(&mut self, ids: &[String]) -> Vec<RemovedEvent> {
+        let ids: HashSet<&str> = ids.iter().map(|id| id.as_str()).collect();
+        let (nodes, _) = mem::replace(&mut self.dag, Graph::new()).into_nodes_edges();
+        let mut removed = Vec::new();
+
+        // The remaining events keep their order, see `events_added_since`
+        for node in nodes {
+            let event = node.weight;
+
+            if ids.contains(event.event_id.as_str()) {
+                let id = event.event_id.as_str();
+
+                self.reconciler.forget(id);
+                removed.push(RemovedEvent {
+                    stream_ordering: self.stream_orderings.remove(id),
+                    received_ts: self.received_ts.remove(id),
+                    provenance: self.provenance.remove(id),
+                    presence: self.presence.remove(id),
+                    arrivals: self.arrivals.remove(id),
+                    arrival_rank: self.arrival_ranks.remove(id),
+                    event,
+                });
+            } else {
+                self.dag.add_node(event);
+            }
+        }
+
+        // The levels derived from the `prev_events` may go down without the removed events
+        self.derived_nodes = 0;
+
+        if removed
+            .iter()
+            .any(|removed| removed.event.get_type() == "m.room.create")
+        {
+            self.create_version = None;
+        }
+
+        self.rebuild_indices();
+
+        removed
+    }
+
+    /// Adds back the events removed by `remove_events`.
+    pub fn restore_events(&mut self, removed: Vec<RemovedEvent>) {
+        let mut events = Vec::with_capacity(removed.len());
+
+        for removed in removed {
+            let id = removed.event.event_id.clone();
+
+            if let Some(stream_ordering) = removed.stream_ordering {
+                self.stream_orderings.insert(id.clone(), stream_ordering);
+            }
+            if let Some(received_ts) = removed.received_ts {
+                self.received_ts.insert(id.clone(), received_ts);
+            }
+            if let Some(batch) = removed.provenance {
+                self.provenance.insert(id.clone(), batch);
+            }
+            if let Some(mask) = removed.presence {
+                self.presence.insert(id.clone(), mask);
+            }
+            if let Some(arrivals) = removed.arrivals {
+                self.arrivals.insert(id.clone(), arrivals);
+            }
+            if let Some(rank) = removed.arrival_rank {
+                self.arrival_ranks.insert(id, rank);
+            }
+
+            events.push(removed.event);
+        }
+
+        self.add_event_nodes(events);
+        self.rebuild_indices();
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.dag.node_count()
+    }
+
+    /// Returns the IDs of the events added to the DAG since it had `count` events. The events are
+    /// only ever added after the ones already in the DAG, so these are its last ones.
+    pub fn events_added_since(&self, count: usize) -> Vec<String> {
+        self.dag
+            .raw_nodes()
+            .iter()
+            .skip(count)
+            .map(|n| n.weight.event_id.clone())
+            .collect()
+    }
+
+    /// Adds `events` to the DAG. The events which are already in the DAG, or which were
+    /// delivered recently by another stream, are suppressed, so that the live sync and the
+    /// backfill can overlap without adding any event twice. Returns how many events were added
+    /// and suppressed.
+    pub fn add_events(&mut self, events: Vec<JsonValue>) -> AddOutcome {
+        let outcome = self.add_events_deferred(events);
+        self.rebuild_indices();
+
+        outcome
+    }
+
+    /// Same as `add_events`, but leaves the edges and the extremities of the DAG out of date
+    /// until `rebuild_indices` is called.
+    pub fn add_events_deferred(&mut self, events: Vec<JsonValue>) -> AddOutcome {
+        // The reconciler is taken out of the DAG while the events are checked against the DAG
+        let mut reconciler = mem::replace(&mut self.reconciler, Reconciler::new(0));
+        let (delivered, suppressed) = self.reconcile(events, &mut reconciler);
+        self.reconciler = reconciler;
+
+        let (events, unparsed_events) = parse_events(&delivered, || self.room_version());
+        let unparsed = unparsed_events.len();
+
+        self.unparsed_events.extend(unparsed_events);
+
+        let mut outcome = self.add_event_nodes(events);
+        outcome.skipped += suppressed;
+        outcome.unparsed = unparsed;
+
+        outcome
+    }
+
+    // Passes the deliveries of `events` through `reconciler`. Returns the events which must be
+    // processed, and the number of deliveries suppressed.
+    fn reconcile(
+        &self,
+        events: Vec<JsonValue>,
+        reconciler: &mut Reconciler,
+    ) -> (Vec<JsonValue>, usize) {
+        let mut delivered = Vec::with_capacity(events.len());
+        let mut suppressed = 0;
+
+        // The events without an ID get the one derived from their reference hash before being
+        // reconciled, so that their copies are suppressed too
+        let version = if events.iter().any(|ev| ev["event_id"].as_str().is_none()) {
+            batch_room_version(&events, || self.room_version())
+        } else {
+            None
+        };
+
+        for mut ev in events {
+            if ev["event_id"].as_str().is_none() {
+                if let (Some(id), Some(fields)) = (
+                    reference_hash::event_id(&ev, version.as_deref()),
+                    ev.as_object_mut(),
+                ) {
+                    fields.insert("event_id".to_string(), JsonValue::String(id));
+                }
+            }
+
+            let accepted = match ev["event_id"].as_str() {
+                Some(id) => reconciler.accept(id, self.events_map.contains_key(id)),
+                None => true, // Its ID can't be derived either, it will be reported as unparsed
+            };
+
+            if accepted {
+                delivered.push(ev);
+            } else {
+                suppressed += 1;
+            }
+        }
+
+        (delivered, suppressed)
+    }
+
+    /// Adds the events retrieved from a backend to the DAG. Returns how many events were
+    /// added and suppressed.
+    pub fn add_source_events(&mut self, res: SourceEvents) -> AddOutcome {
+        let outcome = self.add_events_deferred(res.events);
+        self.annotate_rejections(&res.rejections, &res.soft_failed);
+        self.stream_orderings.extend(res.stream_orderings);
+        self.received_ts.extend(res.received_ts);
+        self.rebuild_indices();
+
+        outcome
+    }
+
+    /// Makes the request `request` to `source` and adds the events it brought to the DAG.
+    pub fn fetch_from<S: EventSource + ?Sized>(
+        &mut self,
+        source: &mut S,
+        request: &SourceRequest,
+    ) -> Result<AddOutcome, S::Error> {
+        let res = source.fetch(request)?;
+
+        Ok(self.add_source_events(res))
+    }
+
+    /// Recomputes everything derived from the events of the DAG: the maps locating them by ID
+    /// and by depth, the edges, the extremities, the orphans, the cycle and the state conflicts.
+    ///
+    /// The `_deferred` operations skip this so that several of them can be composed with a
+    /// single recomputation at the end, instead of one full pass over the DAG each.
+    pub fn rebuild_indices(&mut self) {
+        self.events_map.clear();
+        self.depth_map.clear();
+        self.max_depth = -1;
+        self.min_depth = -1;
+
+        for idx in self.dag.node_indices() {
+            let event = &self.dag[idx];
+
+            self.events_map.insert(event.event_id.clone(), idx);
+            self.depth_map.entry(event.depth).or_default().push(idx);
+
+            if self.max_depth == -1 || event.depth > self.max_depth {
+                self.max_depth = event.depth;
+            }
+            if self.min_depth == -1 || event.depth < self.min_depth {
+                self.min_depth = event.depth;
+            }
+        }
+
+        self.update_event_edges();
+    }
+
+    /// Annotates the events of the DAG with the reasons of their rejection (`rejections` maps
+    /// their IDs to these reasons) and with their soft-failure, as reported by the Postgres
+    /// backend.
+    pub fn annotate_rejections(
+        &mut self,
+        rejections: &HashMap<String, String>,
+        soft_failed: &[String],
+    ) {
+        let soft_failed: HashSet<&str> = soft_failed.iter().map(|id| id.as_str()).collect();
+        self.analyses = Analyses::default();
+
+        for (id, idx) in &self.events_map {
+            let rejection = rejections.get(id).cloned();
+            let is_soft_failed = soft_failed.contains(id.as_str());
+
+            if rejection.is_some() || is_soft_failed {
+                self.dag[*idx].set_rejection(rejection, is_soft_failed);
+            }
+        }
+    }
+
+    /// Reports what `add_events` would change in the DAG if it was called with `events`, without
+    /// modifying the DAG. This allows to preview big imports before doing them.
+    pub fn dry_run_add(&self, events: &[JsonValue]) -> AddPreview {
+        // The deliveries are suppressed like by `add_events`, with a copy of the reconciler
+        let (delivered, _) = self.reconcile(events.to_vec(), &mut self.reconciler.clone());
+        let (events, _) = parse_events(&delivered, || self.room_version());
+
+        // The events which would be added, without duplicates
+        let mut new_events: HashMap<&str, &Event> = HashMap::new();
+        for ev in &events {
+            if !self.events_map.contains_key(&ev.event_id) {
+                new_events.insert(&ev.event_id, ev);
+            }
+        }
+
+        let all_events: Vec<&Event> = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .map(|n| &n.weight)
+            .chain(new_events.values().cloned())
+            .collect();
+        let is_known = |id: &str| self.events_map.contains_key(id) || new_events.contains_key(id);
+
+        let mut nb_edges = 0;
+        let mut referenced: HashSet<&str> = HashSet::new();
+        let mut earliest_events: HashSet<&str> = HashSet::new();
+        let mut resolved_orphans: Vec<String> = Vec::new();
+
+        for ev in &all_events {
+            let prev_events: HashSet<&str> = ev.get_prev_events().into_iter().collect();
+            let known_prev_events: Vec<&str> = prev_events
+                .iter()
+                .cloned()
+                .filter(|id| is_known(id))
+                .collect();
+
+            nb_edges += known_prev_events.len();
+            referenced.extend(known_prev_events.iter().cloned());
+
+            if known_prev_events.is_empty() {
+                earliest_events.insert(&ev.event_id);
+            }
+
+            let was_orphan = self.orphan_events.iter().any(|info| info.id == ev.event_id);
+
+            if was_orphan && known_prev_events.len() == prev_events.len() {
+                resolved_orphans.push(ev.event_id.clone());
+            }
+        }
+
+        let latest_events: HashSet<&str> = all_events
+            .iter()
+            .map(|ev| ev.event_id.as_str())
+            .filter(|id| !referenced.contains(id))
+            .collect();
+        let old_latest_events: HashSet<&str> =
+            self.latest_events.iter().map(|id| id.as_str()).collect();
+        let old_earliest_events: HashSet<&str> =
+            self.earliest_events.iter().map(|id| id.as_str()).collect();
+
+        let to_strings = |ids: HashSet<&&str>| -> Vec<String> {
+            let mut ids: Vec<String> = ids.into_iter().map(|id| id.to_string()).collect();
+            ids.sort();
+            ids
+        };
+
+        AddPreview {
+            new_nodes: new_events.len(),
+            new_edges: nb_edges.saturating_sub(self.dag.edge_count()),
+            resolved_orphans,
+            added_latest_events: to_strings(latest_events.difference(&old_latest_events).collect()),
+            removed_latest_events: to_strings(
+                old_latest_events.difference(&latest_events).collect(),
+            ),
+            added_earliest_events: to_strings(
+                earliest_events.difference(&old_earliest_events).collect(),
+            ),
+            removed_earliest_events: to_strings(
+                old_earliest_events.difference(&earliest_events).collect(),
+            ),
+        }
+    }
+
+    // Adds a node for each event of `events` which isn't in the DAG yet, including the events
+    // appearing more than once in `events`, which are only added once.
+    fn add_event_nodes(&mut self, events: Vec<Event>) -> AddOutcome {
+        let mut outcome = AddOutcome::default();
+        self.analyses = Analyses::default();
+
+        for mut event in events {
+            if self.events_map.contains_key(&event.event_id) {
+                outcome.skipped += 1;
+                continue; // Never add a second node for the same event
+            }
+
+            // The depths derived from another DAG don't hold in this one
+            event.depth = event.get_raw_depth().unwrap_or(0);
+
+            if event.get_type() == "m.room.create" && self.create_version.is_none() {
+                self.create_version = Some(room_version_of(event.get_content()));
+            }
+
+            event.intern(&mut self.interner);
+
+            let id = event.event_id.clone();
+            let depth = event.depth;
+            let index = self.dag.add_node(event); // Add each event as a node in the DAG
+
+            // The restored events keep the rank they first arrived with
+            if !self.arrival_ranks.contains_key(&id) {
+                self.arrival_ranks
+                    .insert(id.clone(), self.next_arrival_rank);
+                self.next_arrival_rank += 1;
+            }
+
+            self.events_map.insert(id, index); // Update the events map
+            self.depth_map.entry(depth).or_default().push(index);
+
+            if self.max_depth == -1 || depth > self.max_depth {
+                self.max_depth = depth;
+            }
+
+            if self.min_depth == -1 || depth < self.min_depth {
+                self.min_depth = depth;
+            }
+
+            outcome.added += 1;
+        }
+
+        outcome
+    }
+
+    fn update_event_edges(&mut self) {
+        self.analyses = Analyses::default();
+
+        // Update the edges in the DAG
+        for src_idx in self.dag.node_indices() {
+            let prev_indices: Vec<NodeIndex> = self
+                .dag
+                .node_weight(src_idx)
+                .unwrap()
+                .get_prev_events()
+                .iter()
+                .filter_map(|id| self.events_map.get(*id).cloned()) // Only take into account events which are really in the DAG
+                .collect();
+
+            for dst_idx in prev_indices {
+                self.dag.update_edge(src_idx, dst_idx, ());
+            }
+        }
+
+        self.update_derived_depths();
+
+        self.latest_events.clear();
+        self.earliest_events.clear();
+        self.orphan_events.clear();
+
+        self.observed_depths = self.depth_map.keys().cloned().collect();
+        self.observed_depths.sort();
+
+        // Update the earliest and latest events of the DAG
+        for idx in self.dag.node_indices() {
+            let event = &self.dag[idx];
+            let outgoing = self.dag.edges_directed(idx, Direction::Outgoing).count();
+
+            if outgoing == 0 {
+                self.earliest_events.push(event.event_id.clone());
+            }
+
+            if self
+                .dag
+                .edges_directed(idx, Direction::Incoming)
+                .next()
+                .is_none()
+            {
+                self.latest_events.push(event.event_id.clone());
+            }
+
+            if outgoing < event.prev_events_count() {
+                let missing = event
+                    .get_prev_events()
+                    .into_iter()
+                    .filter(|prev_id| !self.events_map.contains_key(*prev_id))
+                    .map(|prev_id| prev_id.to_string())
+                    .collect();
+
+                let info = OrphanInfo {
+                    id: event.event_id.clone(),
+                    depth: event.depth,
+                    level: self.level_of(event.depth),
+                    missing,
+                };
+
+                self.orphan_events.push(info);
+            }
+        }
+
+        self.cycle = self.find_cycle().map(|cycle| {
+            cycle
+                .into_iter()
+                .map(|idx| self.dag[idx].event_id.clone())
+                .collect()
+        });
+
+        self.update_state_conflicts();
+        self.update_authority();
+        self.update_outage();
+    }
+
+    // Lays the events out by longest-path layering over their `prev_events` when their depths
+    // can't be used, i.e. when some events have none, or when they all have the same one although
+    // some of them are the `prev_events` of others. Each event is then one level below the
+    // deepest of its `prev_events` in the DAG, and the events without any are at level 1.
+    //
+    // The nodes are only ever appended to the graph, so the events added since the last update
+    // are those from `derived_nodes` on. Only they and their descendants can get new ancestors,
+    // so the levels of the other events are kept.
+    fn update_derived_depths(&mut self) {
+        let raw_depths: Vec<Option<i64>> = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .map(|node| node.weight.get_raw_depth())
+            .collect();
+        let unusable = raw_depths.iter().any(|depth| depth.is_none())
+            || (self.dag.edge_count() > 0 && raw_depths.windows(2).all(|w| w[0] == w[1]));
+
+        let was_derived = self.derived_depths;
+        let levels = if unusable {
+            self.derive_levels(if was_derived { self.derived_nodes } else { 0 })
+        } else {
+            None
+        };
+
+        self.derived_depths = levels.is_some();
+        self.derived_nodes = self.dag.node_count();
+
+        let depths = match levels {
+            Some(levels) => levels,
+            // The events get their raw depths back
+            None if was_derived => self
+                .dag
+                .node_indices()
+                .map(|idx| (idx, raw_depths[idx.index()].unwrap_or(0)))
+                .collect(),
+            None => return,
+        };
+
+        self.move_to_depths(depths);
+    }
+
+    // Derives the levels of the events added from the node `from` on and of their descendants
+    // from the levels of their `prev_events`, which are kept for the other events. The events
+    // are visited from the earliest to the latest with Kahn's algorithm. Returns `None` if the
+    // `prev_events` of these events form a cycle.
+    fn derive_levels(&self, from: usize) -> Option<Vec<(NodeIndex, i64)>> {
+        // The edges go from the events to their `prev_events`, so the descendants are incoming
+        let mut affected: HashSet<NodeIndex> = HashSet::new();
+        let mut stack: Vec<NodeIndex> = (from..self.dag.node_count()).map(NodeIndex::new).collect();
+
+        while let Some(idx) = stack.pop() {
+            if affected.insert(idx) {
+                stack.extend(self.dag.neighbors_directed(idx, Direction::Incoming));
+            }
+        }
+
+        let mut remaining_parents: HashMap<NodeIndex, usize> = affected
+            .iter()
+            .map(|&idx| {
+                let parents = self
+                    .dag
+                    .neighbors_directed(idx, Direction::Outgoing)
+                    .filter(|parent| affected.contains(parent))
+                    .count();
+
+                (idx, parents)
+            })
+            .collect();
+        let mut ready: Vec<NodeIndex> = remaining_parents
+            .iter()
+            .filter(|(_, parents)| **parents == 0)
+            .map(|(idx, _)| *idx)
+            .collect();
+        let mut levels: HashMap<NodeIndex, i64> = HashMap::with_capacity(affected.len());
+
+        while let Some(idx) = ready.pop() {
+            let level = self
+                .dag
+                .neighbors_directed(idx, Direction::Outgoing)
+                .map(|parent| {
+                    levels
+                        .get(&parent)
+                        .cloned()
+                        .unwrap_or(self.dag[parent].depth)
+                        + 1
+                })
+                .max()
+                .unwrap_or(1);
+            levels.insert(idx, level);
+
+            // The children of the affected events are affected too
+            for child in self.dag.neighbors_directed(idx, Direction::Incoming) {
+                let parents = remaining_parents.get_mut(&child).unwrap();
+                *parents -= 1;
+
+                if *parents == 0 {
+                    ready.push(child);
+                }
+            }
+        }
+
+        // The events of a cycle are never ready
+        if levels.len() < affected.len() {
+            return None;
+        }
+
+        Some(levels.into_iter().collect())
+    }
+
+    // Gives the events at the indices of `depths` their new depth, and moves them in the map of
+    // the depths accordingly.
+    fn move_to_depths(&mut self, depths: Vec<(NodeIndex, i64)>) {
+        let mut moved: HashSet<NodeIndex> = HashSet::new();
+        let mut left_depths: HashSet<i64> = HashSet::new();
+
+        for (idx, depth) in depths {
+            let event = &mut self.dag[idx];
+
+            if event.depth != depth {
+                left_depths.insert(event.depth);
+                moved.insert(idx);
+                event.depth = depth;
+            }
+        }
+
+        if moved.is_empty() {
+            return;
+        }
+
+        for depth in left_depths {
+            if let Some(indices) = self.depth_map.get_mut(&depth) {
+                indices.retain(|idx| !moved.contains(idx));
+
+                if indices.is_empty() {
+                    self.depth_map.remove(&depth);
+                }
+            }
+        }
+
+        for idx in moved {
+            let depth = self.dag[idx].depth;
+            self.depth_map.entry(depth).or_default().push(idx);
+        }
+
+        self.max_depth = self.depth_map.keys().max().cloned().unwrap_or(-1);
+        self.min_depth = self.depth_map.keys().min().cloned().unwrap_or(-1);
+    }
+
+    /// Tells whether the depths of the events were derived from their `prev_events`, because the
+    /// source gave none or only bogus ones.
+    pub fn has_derived_depths(&self) -> bool {
+        self.derived_depths
+    }
+
+    // Looks for a cycle in the graph, which can only be due to malformed data or to a buggy
+    // server since the events must form a DAG. The cycle is returned as the list of its events,
+    // each of them having the next one in its `prev_events` and the last one having the first.
+    fn find_cycle(&self) -> Option<Vec<NodeIndex>> {
+        // The events being visited are in `on_path`, those whose ancestors were fully visited
+        // are in `done`
+        let mut on_path: HashSet<NodeIndex> = HashSet::new();
+        let mut done: HashSet<NodeIndex> = HashSet::new();
+
+        for start in self.dag.node_indices() {
+            if done.contains(&start) {
+                continue;
+            }
+
+            let mut path: Vec<NodeIndex> = vec![start];
+            let mut stack: Vec<Vec<NodeIndex>> = vec![self.dag.neighbors(start).collect()];
+            on_path.insert(start);
+
+            while let Some(neighbors) = stack.last_mut() {
+                match neighbors.pop() {
+                    Some(idx) if on_path.contains(&idx) => {
+                        let pos = path.iter().position(|i| *i == idx).unwrap();
+
+                        return Some(path.split_off(pos));
+                    }
+                    Some(idx) if !done.contains(&idx) => {
+                        path.push(idx);
+                        stack.push(self.dag.neighbors(idx).collect());
+                        on_path.insert(idx);
+                    }
+                    Some(_) => {}
+                    None => {
+                        let idx = path.pop().unwrap();
+
+                        stack.pop();
+                        on_path.remove(&idx);
+                        done.insert(idx);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the cycle of `prev_events` found in the graph as a list of event IDs, or `None`
+    /// if the graph is really a DAG. The layout of a graph with a cycle would be meaningless.
+    pub fn cycle(&self) -> Option<&[String]> {
+        self.cycle.as_deref()
+    }
+
+    // Replays the events of the DAG to find the authority of their senders, see
+    // `authority::replay`. Without the creation of the room, the membership of the senders who
+    // joined before the earliest events would be unknown, so nothing is computed.
+    fn update_authority(&mut self) {
+        let has_create = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .any(|node| node.weight.get_type() == "m.room.create");
+
+        if !has_create {
+            self.authority.clear();
+            return;
+        }
+
+        // The indices of the nodes are those of the events given to the replay
+        let events: Vec<(&Event, Vec<usize>)> = self
+            .dag
+            .node_indices()
+            .map(|idx| {
+                let parents = self
+                    .dag
+                    .neighbors_directed(idx, Direction::Outgoing)
+                    .map(|parent| parent.index())
+                    .collect();
+
+                (&self.dag[idx], parents)
+            })
+            .collect();
+
+        self.authority = authority::replay(&events);
+    }
+
+    /// Returns the membership and the power level of the sender of the event `id` right before
+    /// it, or `None` if they are unknown because the creation of the room isn't in the DAG.
+    pub fn authority_of(&self, id: &str) -> Option<&SenderAuthority> {
+        self.authority.get(id)
+    }
+
+    // Looks for the pairs of concurrent state events with the same type and state key. This must
+    // be done again whenever the edges of the DAG change. Only the states set by several events
+    // can conflict, so the others are left out of the search.
+    fn update_state_conflicts(&mut self) {
+        let mut states: HashMap<(&str, &str), Vec<NodeIndex>> = HashMap::new();
+
+        for idx in self.dag.node_indices() {
+            let event = self.dag.node_weight(idx).unwrap();
+
+            if let Some(state_key) = event.get_state_key() {
+                states
+                    .entry((event.get_type(), state_key))
+                    .or_default()
+                    .push(idx);
+            }
+        }
+
+        states.retain(|_, indices| indices.len() > 1);
+        if states.is_empty() {
+            self.state_conflicts = Vec::new();
+            return;
+        }
+
+        // The position of each contested state event in the bitsets of its state ancestors
+        let bits: HashMap<NodeIndex, usize> = states
+            .values()
+            .flatten()
+            .enumerate()
+            .map(|(bit, &idx)| (idx, bit))
+            .collect();
+        let ancestors = self.state_ancestors(&bits);
+        let is_ancestor = |set: &[u64], idx: NodeIndex| {
+            let bit = bits[&idx];
+            set[bit / 64] & (1 << (bit % 64)) != 0
+        };
+
+        let mut space = DfsSpace::new(&self.dag);
+        let mut state_conflicts = Vec::new();
+
+        for ((etype, state_key), indices) in &states {
+            for (i, &a) in indices.iter().enumerate() {
+                for &b in &indices[i + 1..] {
+                    // The edges go from the events to their `prev_events`
+                    let concurrent = match &ancestors {
+                        Some(ancestors) => {
+                            !is_ancestor(&ancestors[&a], b) && !is_ancestor(&ancestors[&b], a)
+                        }
+                        None => {
+                            !has_path_connecting(&self.dag, a, b, Some(&mut space))
+                                && !has_path_connecting(&self.dag, b, a, Some(&mut space))
+                        }
+                    };
+
+                    if concurrent {
+                        state_conflicts.push(StateConflict {
+                            etype: etype.to_string(),
+                            state_key: state_key.to_string(),
+                            events: (self.dag[a].event_id.clone(), self.dag[b].event_id.clone()),
+                        });
+                    }
+                }
+            }
+        }
+
+        self.state_conflicts = state_conflicts;
+    }
+
+    // Finds the events of `bits` among the ancestors of each of them, in a single pass over the
+    // events from the earliest to the latest. The ancestors are given as bitsets, in which the
+    // position of each event is given by `bits`, and are kept for the other events until every
+    // child of the event took them. Returns `None` if the `prev_events` form a cycle.
+    fn state_ancestors(
+        &self,
+        bits: &HashMap<NodeIndex, usize>,
+    ) -> Option<HashMap<NodeIndex, Vec<u64>>> {
+        let order = toposort(&self.dag, None).ok()?;
+        let words = bits.len().div_ceil(64);
+
+        let mut remaining_children: Vec<usize> = self
+            .dag
+            .node_indices()
+            .map(|idx| self.dag.edges_directed(idx, Direction::Incoming).count())
+            .collect();
+        let mut ancestors: Vec<Option<Vec<u64>>> = vec![None; self.dag.node_count()];
+        let mut state_ancestors = HashMap::with_capacity(bits.len());
+
+        // The children come before their parents in the topological order
+        for idx in order.into_iter().rev() {
+            let mut set = vec![0u64; words];
+
+            for parent in self.dag.neighbors_directed(idx, Direction::Outgoing) {
+                if let Some(&bit) = bits.get(&parent) {
+                    set[bit / 64] |= 1 << (bit % 64);
+                }
+
+                if let Some(parent_set) = &ancestors[parent.index()] {
+                    for (word, parent_word) in set.iter_mut().zip(parent_set) {
+                        *word |= parent_word;
+                    }
+                }
+
+                remaining_children[parent.index()] -= 1;
+                if remaining_children[parent.index()] == 0 {
+                    ancestors[parent.index()] = None;
+                }
+            }
+
+            if bits.contains_key(&idx) {
+                state_ancestors.insert(idx, set.clone());
+            }
+            if remaining_children[idx.index()] > 0 {
+                ancestors[idx.index()] = Some(set);
+            }
+        }
+
+        Some(state_ancestors)
+    }
+
+    /// Returns the pairs of concurrent state events with the same type and state key.
+    pub fn state_conflicts(&self) -> &[StateConflict] {
+        &self.state_conflicts
+    }
+
+    /// Returns the events of the DAG setting the state `(etype, state_key)`, in the order of the
+    /// DAG, each with the changes from the content of the previous one. The concurrent events,
+    /// which can't be ordered by the DAG, are ordered by depth, time stamp and ID, and flagged as
+    /// such.
+    pub fn state_timeline(&self, etype: &str, state_key: &str) -> Vec<StateTimelineEntry> {
+        let mut indices: Vec<NodeIndex> = self
+            .dag
+            .node_indices()
+            .filter(|&idx| {
+                let ev = &self.dag[idx];
+
+                ev.get_type() == etype && ev.get_state_key() == Some(state_key)
+            })
+            .collect();
+
+        // An event is always deeper than its ancestors
+        indices.sort_by(|&a, &b| {
+            let (a, b) = (&self.dag[a], &self.dag[b]);
+
+            a.depth
+                .cmp(&b.depth)
+                .then_with(|| a.get_origin_server_ts().cmp(&b.get_origin_server_ts()))
+                .then_with(|| a.event_id.cmp(&b.event_id))
+        });
+
+        let mut space = DfsSpace::new(&self.dag);
+        let mut previous: Option<NodeIndex> = None;
+        let mut timeline = Vec::with_capacity(indices.len());
+
+        for idx in indices {
+            let ev = &self.dag[idx];
+            let (concurrent, changes) = match previous {
+                // The edges go from the events to their `prev_events`
+                Some(prev) => (
+                    !has_path_connecting(&self.dag, idx, prev, Some(&mut space)),
+                    state_timeline::content_diff(self.dag[prev].get_content(), ev.get_content()),
+                ),
+                None => (false, state_timeline::content_added(ev.get_content())),
+            };
+
+            timeline.push(StateTimelineEntry {
+                id: ev.event_id.clone(),
+                depth: ev.depth,
+                sender: ev.get_sender().to_string(),
+                origin_server_ts: ev.get_origin_server_ts(),
+                content: ev.get_content().clone(),
+                concurrent,
+                changes,
+            });
+            previous = Some(idx);
+        }
+
+        timeline
+    }
+
+    /// Returns the readable rendering of the content of the event `id` if it is of a type the
+    /// inspector knows. The previous membership and power levels are taken from the previous
+    /// event of the state timeline, or for a membership from the `prev_content` the server may
+    /// have given in `unsigned` when the DAG doesn't have it.
+    pub fn content_view(&self, id: &str) -> Option<ContentView> {
+        let ev = self.get_event(id)?;
+
+        match ev.get_type() {
+            "m.room.create" => Some(inspector::create_view(ev.get_content())),
+            "m.room.member" => {
+                let target = ev.get_state_key()?;
+                let timeline = self.state_timeline("m.room.member", target);
+                let pos = timeline.iter().position(|entry| entry.id == id)?;
+                let previous = match pos {
+                    0 => ev.get_pointer("/unsigned/prev_content/membership"),
+                    _ => timeline[pos - 1].content.get("membership").cloned(),
+                };
+                let previous = previous.and_then(|v| v.as_str().map(String::from));
+
+                let content = ev.get_content();
+                let membership = content["membership"].as_str().unwrap_or_default();
+                let string_of = |key: &str| content[key].as_str().map(String::from);
+
+                Some(ContentView::Membership {
+                    target: target.to_string(),
+                    description: inspector::membership_transition(
+                        previous.as_deref(),
+                        membership,
+                        target,
+                        ev.get_sender(),
+                    ),
+                    previous,
+                    membership: membership.to_string(),
+                    displayname: string_of("displayname"),
+                    reason: string_of("reason"),
+                })
+            }
+            "m.room.power_levels" => {
+                let state_key = ev.get_state_key()?;
+
+                self.state_timeline("m.room.power_levels", state_key)
+                    .into_iter()
+                    .find(|entry| entry.id == id)
+                    .map(|entry| ContentView::PowerLevels {
+                        changes: entry.changes,
+                    })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the IDs of the events which are referenced in the `prev_events` of the orphan
+    /// events but which are not in the DAG.
+    pub fn missing_prev_events(&self) -> Vec<String> {
+        let mut missing: Vec<String> = Vec::new();
+
+        for info in &self.orphan_events {
+            for prev_id in &info.missing {
+                if !missing.contains(prev_id) {
+                    missing.push(prev_id.clone());
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// Picks at most `n` missing events to request in order to fill the gaps of the DAG, the
+    /// deepest gaps first: those are the closest to the latest events, which is usually where
+    /// the history matters, and filling them reveals the most of the DAG for each request. The
+    /// gaps at the same depth are ordered by their orphans' IDs, so that the targets are stable.
+    pub fn backfill_targets(&self, n: usize) -> Vec<String> {
+        let mut orphans: Vec<&OrphanInfo> = self.orphan_events.iter().collect();
+        orphans.sort_by(|a, b| b.depth.cmp(&a.depth).then_with(|| a.id.cmp(&b.id)));
+
+        let mut targets: Vec<String> = Vec::new();
+
+        for prev_id in orphans.into_iter().flat_map(|info| info.missing.iter()) {
+            if targets.len() == n {
+                break;
+            }
+
+            if !targets.contains(prev_id) {
+                targets.push(prev_id.clone());
+            }
+        }
+
+        targets
+    }
+
+    /// Builds a report of the dangling `prev_events` references of the DAG, grouped by missing
+    /// event and sorted by decreasing number of references.
+    pub fn health_report(&self) -> DagHealthReport {
+        let mut missing: HashMap<String, Vec<String>> = HashMap::new();
+
+        for info in &self.orphan_events {
+            for prev_id in &info.missing {
+                missing
+                    .entry(prev_id.clone())
+                    .or_default()
+                    .push(info.id.clone());
+            }
+        }
+
+        let mut missing_events: Vec<MissingEvent> = missing
+            .into_iter()
+            .map(|(id, referenced_by)| MissingEvent {
+                id,
+                count: referenced_by.len(),
+                referenced_by,
+            })
+            .collect();
+
+        missing_events.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.id.cmp(&b.id)));
+
+        DagHealthReport { missing_events }
+    }
+
+    /// Returns the regions of unknown history bordered by the orphan events of the DAG.
+    ///
+    /// The size of a region is estimated from the gap between the depth of its orphan and the
+    /// closest depth below it at which the DAG has events, since there is at least one missing
+    /// event at each depth of this gap. Without any event below, the gap extends to the creation
+    /// of the room, at depth 1.
+    pub fn fog_regions(&self) -> Vec<FogRegion> {
+        self.orphan_events
+            .iter()
+            .map(|info| {
+                let known_below = self
+                    .depth_map
+                    .keys()
+                    .filter(|depth| **depth < info.depth)
+                    .max()
+                    .cloned()
+                    .unwrap_or(0);
+                let estimate = (info.depth - known_below - 1).max(info.missing.len() as i64);
+
+                FogRegion {
+                    orphan: info.id.clone(),
+                    depth: info.depth,
+                    level: info.level,
+                    missing: info.missing.clone(),
+                    estimate,
+                }
+            })
+            .collect()
+    }
+
+    /// Lists the events a newcomer to the DAG should look at first, at most `max` of them: the
+    /// anomalies, the moderation events, the forks and merges and the other state changes, in
+    /// this order and from the latest to the earliest.
+    pub fn interesting_events(&self, max: usize) -> Vec<InterestingEvent> {
+        self.analyses
+            .interesting_events
+            .get_or_init(|| self.compute_interesting_events())
+            .iter()
+            .take(max)
+            .cloned()
+            .collect()
+    }
+
+    fn compute_interesting_events(&self) -> Vec<InterestingEvent> {
+        let orphans: HashSet<&str> = self
+            .orphan_events
+            .iter()
+            .map(|info| info.id.as_str())
+            .collect();
+        let in_conflict: HashSet<&str> = self
+            .state_conflicts
+            .iter()
+            .flat_map(|conflict| vec![conflict.events.0.as_str(), conflict.events.1.as_str()])
+            .collect();
+
+        let mut interesting: Vec<InterestingEvent> = self
+            .dag
+            .node_indices()
+            .filter_map(|idx| {
+                let ev = &self.dag[idx];
+                let id = ev.event_id.as_str();
+                let nb_children = self.dag.edges_directed(idx, Direction::Incoming).count();
+                let nb_prev_events = ev.get_prev_events().len();
+
+                let (interest, description) = if let Some(reason) = ev.get_rejection() {
+                    (Interest::Anomaly, format!("Rejected: {}", reason))
+                } else if ev.is_soft_failed() {
+                    (Interest::Anomaly, "Soft-failed".to_string())
+                } else if orphans.contains(id) {
+                    (Interest::Anomaly, "Missing ancestors".to_string())
+                } else if in_conflict.contains(id) {
+                    (
+                        Interest::Anomaly,
+                        format!("State conflict on {}", ev.get_type()),
+                    )
+                } else if let Some(violation) = self
+                    .authority
+                    .get(id)
+                    .and_then(|authority| authority.violation())
+                {
+                    (Interest::Anomaly, format!("Unauthorised: {}", violation))
+                } else if let Some(mismatch) = ev.origin_mismatch() {
+                    (
+                        Interest::Anomaly,
+                        format!("Origin mismatch: {}", mismatch.description()),
+                    )
+                } else if let Some(description) = moderation_description(ev) {
+                    (Interest::Moderation, description)
+                } else if nb_children > 1 {
+                    (
+                        Interest::Fork,
+                        format!("Fork into {} branches", nb_children),
+                    )
+                } else if nb_prev_events > 1 {
+                    (
+                        Interest::Merge,
+                        format!("Merge of {} branches", nb_prev_events),
+                    )
+                } else if ev.is_state() {
+                    (Interest::StateChange, format!("{} change", ev.get_type()))
+                } else {
+                    return None;
+                };
+
+                Some(InterestingEvent {
+                    id: id.to_string(),
+                    depth: ev.depth,
+                    interest,
+                    description,
+                })
+            })
+            .collect();
+
+        interesting.sort_by(|a, b| {
+            a.interest
+                .cmp(&b.interest)
+                .then_with(|| b.depth.cmp(&a.depth))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        interesting
+    }
+
+    /// Checks the `depth` of every event against the ones of its `prev_events` in the DAG: an
+    /// event must be deeper than all of them, and by at most `jump_threshold`. The events and the
+    /// `prev_events` without a depth given by the source are left out, as are the depths derived
+    /// from the `prev_events`, which are consistent by construction. The anomalies are sorted by
+    /// depth.
+    pub fn depth_anomalies(&self, jump_threshold: i64) -> Vec<DepthAnomaly> {
+        let mut cached = self.analyses.depth_anomalies.lock().unwrap();
+
+        match cached.as_ref() {
+            Some((threshold, anomalies)) if *threshold == jump_threshold => anomalies.clone(),
+            _ => {
+                let anomalies = self.compute_depth_anomalies(jump_threshold);
+                *cached = Some((jump_threshold, anomalies.clone()));
+
+                anomalies
+            }
+        }
+    }
+
+    fn compute_depth_anomalies(&self, jump_threshold: i64) -> Vec<DepthAnomaly> {
+        let mut anomalies: Vec<DepthAnomaly> = self
+            .dag
+            .node_indices()
+            .filter_map(|idx| {
+                let ev = &self.dag[idx];
+                let depth = ev.get_raw_depth()?;
+                let (parent, parent_depth) = self
+                    .dag
+                    .neighbors_directed(idx, Direction::Outgoing)
+                    .filter_map(|parent| {
+                        let parent = &self.dag[parent];
+
+                        parent.get_raw_depth().map(|depth| (parent, depth))
+                    })
+                    .max_by(|(a, depth_a), (b, depth_b)| {
+                        depth_a
+                            .cmp(depth_b)
+                            .then_with(|| b.event_id.cmp(&a.event_id))
+                    })?;
+
+                let kind = if depth <= parent_depth {
+                    DepthAnomalyKind::NotDeeper
+                } else if depth - parent_depth > jump_threshold {
+                    DepthAnomalyKind::Jump
+                } else {
+                    return None;
+                };
+
+                Some(DepthAnomaly {
+                    id: ev.event_id.clone(),
+                    depth,
+                    parent: parent.event_id.clone(),
+                    parent_depth,
+                    kind,
+                })
+            })
+            .collect();
+
+        anomalies.sort_by(|a, b| a.depth.cmp(&b.depth).then_with(|| a.id.cmp(&b.id)));
+
+        anomalies
+    }
+
+    /// Returns the journal of the events loaded in the DAG of the view `view_id`, which were
+    /// retrieved with the backend `source`: the events of the DAG sorted by depth, followed by
+    /// the events which couldn't be parsed. See `journal::to_jsonl`.
+    pub fn journal<'a>(
+        &'a self,
+        view_id: usize,
+        source: &'a str,
+    ) -> impl Iterator<Item = JournalEntry<'a>> + 'a {
+        let mut indices: Vec<NodeIndex> = self.dag.node_indices().collect();
+        indices.sort_by_key(|idx| (self.dag[*idx].depth, self.dag[*idx].event_id.clone()));
+
+        let parsed = indices.into_iter().map(move |idx| {
+            let ev = &self.dag[idx];
+            let unknown_fields = ev.unknown_fields();
+
+            JournalEntry {
+                view: view_id,
+                source,
+                server_name: &self.server_name,
+                room_id: &self.room_id,
+                event_id: Some(&ev.event_id),
+                status: if unknown_fields.is_empty() {
+                    ParseStatus::Parsed
+                } else {
+                    ParseStatus::UnknownFields
+                },
+                error: None,
+                unknown_fields,
+                rejection: ev.get_rejection(),
+                soft_failed: ev.is_soft_failed(),
+                event: JournalEvent::Parsed(ev),
+            }
+        });
+
+        let unparsed = self
+            .unparsed_events
+            .iter()
+            .map(move |unparsed| JournalEntry {
+                view: view_id,
+                source,
+                server_name: &self.server_name,
+                room_id: &self.room_id,
+                event_id: unparsed.json["event_id"].as_str(),
+                status: ParseStatus::Failed,
+                error: Some(&unparsed.error),
+                unknown_fields: Vec::new(),
+                rejection: None,
+                soft_failed: false,
+                event: JournalEvent::Raw(&unparsed.json),
+            });
+
+        parsed.chain(unparsed)
+    }
+
+    /// Lists the events of the DAG from the earliest to the latest depth, for the text outline
+    /// of the DAG. Each event tells whether it is a fork (several children), a merge (several
+    /// parents), an extremity, or an orphan whose ancestors are missing. The first child of an
+    /// event, by depth and ID, stays on the branch of its parent while the others are indented
+    /// one branch further, and a merge goes back to the least indented branch it joins.
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        self.analyses
+            .outline
+            .get_or_init(|| self.compute_outline())
+            .clone()
+    }
+
+    fn compute_outline(&self) -> Vec<OutlineEntry> {
+        let mut depths: Vec<&i64> = self.depth_map.keys().collect();
+        depths.sort();
+
+        let mut branches: HashMap<NodeIndex, usize> = HashMap::with_capacity(self.dag.node_count());
+        let mut first_children: HashMap<NodeIndex, Option<NodeIndex>> = HashMap::new();
+        let mut entries = Vec::with_capacity(self.dag.node_count());
+
+        for depth in depths {
+            let mut indices = self.depth_map[depth].clone();
+            indices.sort_by_key(|idx| self.dag[*idx].event_id.clone());
+
+            for idx in indices {
+                let event = &self.dag[idx];
+                let nb_children = self.dag.edges_directed(idx, Direction::Incoming).count();
+                let mut parents: Vec<String> = self
+                    .dag
+                    .neighbors_directed(idx, Direction::Outgoing)
+                    .map(|parent| {
+                        self.display
+                            .id_shortener
+                            .shorten(&self.dag[parent].event_id)
+                    })
+                    .collect();
+                let nb_missing = event.get_prev_events().len().saturating_sub(parents.len());
+
+                // The parents with an inconsistent depth may not have a branch yet
+                let branch = self
+                    .dag
+                    .neighbors_directed(idx, Direction::Outgoing)
+                    .filter_map(|parent| {
+                        let first_child = *first_children.entry(parent).or_insert_with(|| {
+                            self.dag
+                                .neighbors_directed(parent, Direction::Incoming)
+                                .min_by_key(|child| {
+                                    (self.dag[*child].depth, &self.dag[*child].event_id)
+                                })
+                        });
+
+                        branches.get(&parent).map(|branch| {
+                            if first_child == Some(idx) {
+                                *branch
+                            } else {
+                                branch + 1
+                            }
+                        })
+                    })
+                    .min()
+                    .unwrap_or(0);
+                branches.insert(idx, branch);
+
+                let mut markers = Vec::new();
+                if nb_children > 1 {
+                    markers.push(OutlineMarker::Fork(nb_children));
+                }
+                if parents.len() > 1 {
+                    parents.sort();
+                    markers.push(OutlineMarker::Merge(parents));
+                }
+                if nb_missing > 0 {
+                    markers.push(OutlineMarker::Orphan(nb_missing));
+                }
+                if nb_children == 0 {
+                    markers.push(OutlineMarker::Latest);
+                }
+
+                entries.push(OutlineEntry {
+                    event_id: event.event_id.clone(),
+                    short_id: self.display.id_shortener.shorten(&event.event_id),
+                    depth: event.depth,
+                    branch,
+                    etype: event.get_type().to_string(),
+                    sender: event.get_sender().to_string(),
+                    markers,
+                });
+            }
+        }
+
+        entries
+    }
+
+    /// Renders the DAG as a plain text outline, usable in a terminal or with a screen reader.
+    /// The events are grouped by depth and indented by branch, see `outline`:
+    ///
+    /// ```text
+    /// Depth 12
+    ///   $abc (m.room.message from @alice:example.org) [fork: 2 children]
+    /// Depth 13
+    ///   $def (m.room.message from @bob:example.org)
+    ///     $ghi (m.room.member from @carol:example.com) [latest]
+    /// Depth 14
+    ///   $jkl (m.room.message from @alice:example.org) [merge of $def, $ghi] [latest]
+    /// ```
+    pub fn to_text_outline(&self) -> String {
+        let entries = self.outline();
+        let mut outline = String::new();
+
+        for (depth, entries) in outline::by_depth(&entries) {
+            outline.push_str(&format!("Depth {}\n", depth));
+
+            for entry in entries {
+                outline.push_str(&"  ".repeat(entry.branch + 1));
+                outline.push_str(&entry.description());
+                outline.push('\n');
+            }
+        }
+
+        outline
+    }
+
+    /// Computes the statistics of the events of the DAG, see `RoomStats`.
+    pub fn stats(&self) -> RoomStats {
+        self.analyses
+            .stats
+            .get_or_init(|| self.compute_stats())
+            .clone()
+    }
+
+    fn compute_stats(&self) -> RoomStats {
+        let mut per_sender: HashMap<String, usize> = HashMap::new();
+        let mut per_origin: HashMap<String, usize> = HashMap::new();
+        let mut parents = 0;
+        let mut edges = 0;
+        let mut max_children = 0;
+        let mut memory = self.interner.memory_size();
+
+        for idx in self.dag.node_indices() {
+            let ev = &self.dag[idx];
+
+            memory += ev.memory_size();
+
+            *per_sender.entry(ev.get_sender().to_string()).or_insert(0) += 1;
+            *per_origin.entry(ev.get_origin().to_string()).or_insert(0) += 1;
+
+            let nb_children = self.dag.edges_directed(idx, Direction::Incoming).count();
+
+            if nb_children > 0 {
+                parents += 1;
+                edges += nb_children;
+                max_children = max_children.max(nb_children);
+            }
+        }
+
+        RoomStats {
+            events: self.dag.node_count(),
+            per_sender: stats::ranked(per_sender),
+            per_origin: stats::ranked(per_origin),
+            branch_factor: if parents == 0 {
+                0.0
+            } else {
+                edges as f64 / parents as f64
+            },
+            max_children,
+            forward_extremities: self.latest_events.len(),
+            backward_extremities: self.earliest_events.len(),
+            orphans: self.orphan_events.len(),
+            interned_strings: self.interner.len(),
+            memory,
+        }
+    }
+
+    /// Measures how tangled the DAG is, see `DagMetrics`. Finding the longest fork goes through
+    /// the descendants of every fork point, so this is only computed on demand.
+    pub fn metrics(&self) -> DagMetrics {
+        let mut metrics = DagMetrics::default();
+
+        for idx in self.dag.node_indices() {
+            if self
+                .dag
+                .neighbors_directed(idx, Direction::Outgoing)
+                .count()
+                > 1
+            {
+                metrics.merge_events += 1;
+            }
+
+            if self
+                .dag
+                .neighbors_directed(idx, Direction::Incoming)
+                .count()
+                > 1
+            {
+                metrics.fork_points += 1;
+
+                if let Some(fork) = self.fork_from(idx) {
+                    let longer = match &metrics.longest_fork {
+                        Some(longest) => fork.length > longest.length,
+                        None => true,
+                    };
+
+                    if longer {
+                        metrics.longest_fork = Some(fork);
+                    }
+                }
+            }
+        }
+
+        // The smallest depth wins the ties so that the result doesn't depend on the hashing
+        if let Some((depth, events)) =
+            self.depth_map
+                .iter()
+                .max_by(|(depth_a, events_a), (depth_b, events_b)| {
+                    events_a
+                        .len()
+                        .cmp(&events_b.len())
+                        .then_with(|| depth_b.cmp(depth_a))
+                })
+        {
+            metrics.max_concurrent_branches = events.len();
+            metrics.widest_depth = Some(*depth);
+        }
+
+        metrics
+    }
+
+    // Follows the branches starting from the children of the fork point at `idx` down to the
+    // shallowest event descending from more than one of them, where they merge again.
+    fn fork_from(&self, idx: NodeIndex) -> Option<Fork> {
+        let fork_point = self.dag.node_weight(idx)?;
+        let mut branches: HashMap<NodeIndex, u64> = HashMap::new();
+
+        // Each branch gets a bit, and only the first 64 are followed
+        for (bit, child) in self
+            .dag
+            .neighbors_directed(idx, Direction::Incoming)
+            .take(64)
+            .enumerate()
+        {
+            let mut stack = vec![child];
+
+            while let Some(idx) = stack.pop() {
+                let mask = branches.entry(idx).or_insert(0);
+
+                if *mask & (1 << bit) == 0 {
+                    *mask |= 1 << bit;
+                    stack.extend(self.dag.neighbors_directed(idx, Direction::Incoming));
+                }
+            }
+        }
+
+        let merge_point = branches
+            .iter()
+            .filter(|(_, mask)| mask.count_ones() > 1)
+            .map(|(idx, _)| &self.dag[*idx])
+            .min_by(|a, b| {
+                a.depth
+                    .cmp(&b.depth)
+                    .then_with(|| a.event_id.cmp(&b.event_id))
+            });
+
+        Some(match merge_point {
+            Some(merge_point) => Fork {
+                fork_point: fork_point.event_id.clone(),
+                merge_point: Some(merge_point.event_id.clone()),
+                length: merge_point.depth - fork_point.depth,
+            },
+            None => Fork {
+                fork_point: fork_point.event_id.clone(),
+                merge_point: None,
+                length: self.max_depth - fork_point.depth,
+            },
+        })
+    }
+
+    /// Summarises the DAG, see `RoomSummary`. Unlike `stats`, it doesn't go through the events.
+    pub fn summary(&self) -> RoomSummary {
+        RoomSummary {
+            room_id: self.room_id.clone(),
+            server_name: self.server_name.clone(),
+            events: self.dag.node_count(),
+            depth_range: self.depth_bounds(),
+            forward_extremities: self.latest_events.len(),
+            backward_extremities: self.earliest_events.len(),
+            orphans: self.orphan_events.len(),
+            room_version: self.room_version(),
+        }
+    }
+
+    /// Returns the IDs of the events in the order in which the server received them, for replaying
+    /// their arrival: the order of their `stream_ordering` if the source gave it (the Postgres
+    /// backend does), otherwise the order in which they were first added to the DAG, which is the
+    /// order in which the syncs delivered them, even if they were removed and restored since. The
+    /// events without a stream ordering come last.
+    pub fn arrival_order(&self) -> Vec<String> {
+        let mut indices: Vec<NodeIndex> = self.dag.node_indices().collect();
+
+        indices.sort_by_key(|idx| {
+            let id = &self.dag[*idx].event_id;
+            let stream_ordering = self.stream_orderings.get(id);
+
+            (
+                stream_ordering.cloned().unwrap_or(i64::MAX),
+                self.arrival_ranks.get(id).cloned().unwrap_or(u64::MAX),
+            )
+        });
+
+        indices
+            .into_iter()
+            .map(|idx| self.dag[idx].event_id.clone())
+            .collect()
+    }
+
+    /// Returns the number of events at each depth of the DAG, sorted by increasing depth.
+    pub fn depth_histogram(&self) -> Vec<(i64, usize)> {
+        let mut histogram: Vec<(i64, usize)> = self
+            .depth_map
+            .iter()
+            .map(|(depth, indices)| (*depth, indices.len()))
+            .collect();
+
+        histogram.sort_by_key(|(depth, _)| *depth);
+
+        histogram
+    }
+
+    /// Returns the events of the DAG as the rows of the list view, sorted by the column `key`.
+    /// The rows are only rebuilt when the events or the sort change.
+    pub fn event_rows(&self, key: SortKey, descending: bool) -> Arc<Vec<EventRow>> {
+        let mut cached = self.analyses.event_rows.lock().unwrap();
+
+        if let Some((cached_key, cached_descending, rows)) = cached.as_ref() {
+            if *cached_key == key && *cached_descending == descending {
+                return rows.clone();
+            }
+        }
+
+        let mut rows: Vec<EventRow> = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .map(|node| EventRow::from_event(&node.weight))
+            .collect();
+
+        event_table::sort_rows(&mut rows, key, descending);
+
+        let rows = Arc::new(rows);
+        *cached = Some((key, descending, rows.clone()));
+
+        rows
+    }
+
+    /// Returns the IDs of the events whose depth is between `min_depth` and `max_depth`
+    /// (inclusive).
+    pub fn events_in_depth_range(&self, min_depth: i64, max_depth: i64) -> Vec<String> {
+        self.depth_map
+            .iter()
+            .filter(|(depth, _)| **depth >= min_depth && **depth <= max_depth)
+            .flat_map(|(_, indices)| indices.iter())
+            .map(|idx| self.dag.node_weight(*idx).unwrap().event_id.clone())
+            .collect()
+    }
+
+    /// Returns the IDs of the events whose `origin_server_ts` is between `start` and `end`
+    /// (inclusive, in milliseconds).
+    pub fn events_in_time_range(&self, start: i64, end: i64) -> Vec<String> {
+        self.dag
+            .raw_nodes()
+            .iter()
+            .map(|n| &n.weight)
+            .filter(|ev| ev.get_origin_server_ts() >= start && ev.get_origin_server_ts() <= end)
+            .map(|ev| ev.event_id.clone())
+            .collect()
+    }
+
+    /// Returns what the events of the DAG tell about its room and about the rooms it was upgraded
+    /// from and to.
+    pub fn incarnation(&self) -> RoomIncarnation {
+        self.analyses
+            .incarnation
+            .get_or_init(|| self.compute_incarnation())
+            .clone()
+    }
+
+    fn compute_incarnation(&self) -> RoomIncarnation {
+        let mut incarnation = RoomIncarnation::referenced(&self.room_id);
+        incarnation.events = self.dag.node_count();
+        incarnation.time_range = self.time_bounds();
+
+        for node in self.dag.raw_nodes() {
+            let content = node.weight.get_content();
+
+            match node.weight.get_type() {
+                "m.room.create" => {
+                    incarnation.room_version = Some(room_version_of(content));
+                    incarnation.predecessor = content["predecessor"]["room_id"]
+                        .as_str()
+                        .map(|room_id| room_id.to_string());
+                }
+                "m.room.tombstone" => {
+                    if let Some(room_id) = content["replacement_room"].as_str() {
+                        incarnation.successor = Some(room_id.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        incarnation
+    }
+
+    /// Returns the version of the room, given by its creation event if it is in the DAG,
+    /// otherwise by the source if it told it.
+    pub fn room_version(&self) -> Option<String> {
+        self.create_version
+            .clone()
+            .or_else(|| self.declared_version.clone())
+    }
+
+    /// Sets the version of the room as the source gives it, e.g. from the state of the room,
+    /// which is used as long as the creation event of the room isn't in the DAG.
+    pub fn set_declared_room_version(&mut self, version: String) {
+        self.declared_version = Some(version);
+        self.analyses = Analyses::default();
+    }
+
+    /// Returns the events which can't be valid in the version of the room, with the reason why,
+    /// sorted by depth. Nothing is returned as long as the version isn't known.
+    pub fn version_mismatches(&self) -> Vec<VersionMismatch> {
+        self.cached_version_mismatches().0.clone()
+    }
+
+    // Returns why the event `event_id` can't be valid in the version of the room, if it can't
+    fn version_problem(&self, event_id: &str) -> Option<&str> {
+        let (mismatches, index) = self.cached_version_mismatches();
+
+        index
+            .get(event_id)
+            .map(|idx| mismatches[*idx].problem.as_str())
+    }
+
+    fn cached_version_mismatches(&self) -> &(Vec<VersionMismatch>, HashMap<String, usize>) {
+        self.analyses.version_mismatches.get_or_init(|| {
+            let mismatches = self.compute_version_mismatches();
+            let index = mismatches
+                .iter()
+                .enumerate()
+                .map(|(idx, mismatch)| (mismatch.id.clone(), idx))
+                .collect();
+
+            (mismatches, index)
+        })
+    }
+
+    fn compute_version_mismatches(&self) -> Vec<VersionMismatch> {
+        let rules = match self.room_version() {
+            Some(version) => RoomVersionRules::of(&version),
+            None => return Vec::new(),
+        };
+
+        let mut mismatches: Vec<(i64, VersionMismatch)> = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .filter_map(|node| {
+                let ev = &node.weight;
+
+                rules.check(ev).map(|problem| {
+                    (
+                        ev.depth,
+                        VersionMismatch {
+                            id: ev.event_id.clone(),
+                            problem,
+                        },
+                    )
+                })
+            })
+            .collect();
+        mismatches.sort_by_key(|(depth, _)| *depth);
+
+        mismatches
+            .into_iter()
+            .map(|(_, mismatch)| mismatch)
+            .collect()
+    }
+
+    /// Returns the ID of the room this one replaced and the ID of its last event (its tombstone),
+    /// as given by the creation event of the room, if it is in the DAG.
+    pub fn predecessor_link(&self) -> Option<(String, String)> {
+        self.dag
+            .raw_nodes()
+            .iter()
+            .map(|node| &node.weight)
+            .filter(|ev| ev.get_type() == "m.room.create")
+            .find_map(|ev| {
+                let predecessor = &ev.get_content()["predecessor"];
+
+                match (
+                    predecessor["room_id"].as_str(),
+                    predecessor["event_id"].as_str(),
+                ) {
+                    (Some(room_id), Some(event_id)) => {
+                        Some((room_id.to_string(), event_id.to_string()))
+                    }
+                    _ => None,
+                }
+            })
+    }
+
+    /// Stitches the events `res` of the room `room_id`, which this room replaced, into the DAG of
+    /// this room. They are kept in a separate DAG, rendered above the events of this room with a
+    /// node marking the upgrade between them, and the events stitched later are added to it.
+    pub fn stitch_predecessor(&mut self, room_id: &str, res: SourceEvents) {
+        match &mut self.predecessor {
+            Some(predecessor) if predecessor.room_id == room_id => {
+                predecessor.add_source_events(res);
+            }
+            _ => {
+                let mut predecessor =
+                    RoomEvents::from_source_events(room_id, &self.server_name, res);
+                predecessor.display = self.display.clone();
+                predecessor.set_compress_depths(self.display.compress_depths);
+
+                self.predecessor = Some(Box::new(predecessor));
+            }
+        }
+    }
+
+    /// Returns the DAG of the room this one replaced, if it was stitched into this one.
+    pub fn predecessor(&self) -> Option<&RoomEvents> {
+        self.predecessor.as_deref()
+    }
+
+    /// Returns the earliest and the latest `origin_server_ts` of the events of the DAG, or `None`
+    /// if the DAG is empty.
+    pub fn time_bounds(&self) -> Option<(i64, i64)> {
+        let timestamps = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .map(|n| n.weight.get_origin_server_ts());
+
+        timestamps.fold(None, |bounds, ts| match bounds {
+            None => Some((ts, ts)),
+            Some((min, max)) => Some((min.min(ts), max.max(ts))),
+        })
+    }
+
+    /// Returns the minimal and the maximal depth of the events of the DAG, or `None` if the DAG
+    /// is empty.
+    pub fn depth_bounds(&self) -> Option<(i64, i64)> {
+        if self.dag.node_count() == 0 {
+            None
+        } else {
+            Some((self.min_depth, self.max_depth))
+        }
+    }
+
+    /// Get an `Event` from its ID.
+    pub fn get_event(&self, id: &str) -> Option<&Event> {
+        self.events_map
+            .get(id)
+            .map(|idx| self.dag.node_weight(*idx).unwrap())
+    }
+
+    /// Returns the IDs of the events of the DAG in the `prev_events` of the event `id`, ordered
+    /// by creation time.
+    pub fn parents_of(&self, id: &str) -> Vec<String> {
+        self.neighbours_of(id, Direction::Outgoing)
+    }
+
+    /// Returns the IDs of the events of the DAG which have the event `id` in their
+    /// `prev_events`, ordered by creation time.
+    pub fn children_of(&self, id: &str) -> Vec<String> {
+        self.neighbours_of(id, Direction::Incoming)
+    }
+
+    /// Returns the IDs of the events of the DAG at the same depth as the event `id`, including
+    /// it, ordered by creation time.
+    pub fn siblings_at_depth(&self, id: &str) -> Vec<String> {
+        let indices = self
+            .get_event(id)
+            .and_then(|ev| self.depth_map.get(&ev.depth))
+            .cloned()
+            .unwrap_or_default();
+
+        self.sorted_ids(indices)
+    }
+
+    /// Returns the shortest chain of `prev_events` linking the events `a` and `b`, from the one
+    /// which depends on the other down to it, or `None` if neither depends on the other, i.e. if
+    /// they are concurrent, or if one of them isn't in the DAG.
+    pub fn path_between(&self, a: &str, b: &str) -> Option<Vec<String>> {
+        let a = *self.events_map.get(a)?;
+        let b = *self.events_map.get(b)?;
+
+        // The edges go from the events to their `prev_events`
+        let shortest = |from: NodeIndex, to: NodeIndex| {
+            astar(&self.dag, from, |idx| idx == to, |_| 1, |_| 0).map(|(_, path)| path)
+        };
+
+        // The deepest event is the most likely to depend on the other, but the depths given by
+        // the servers can't be trusted
+        let (from, to) = if self.dag[a].depth >= self.dag[b].depth {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let path = shortest(from, to).or_else(|| shortest(to, from))?;
+
+        Some(
+            path.into_iter()
+                .map(|idx| self.dag[idx].event_id.clone())
+                .collect(),
+        )
+    }
+
+    fn neighbours_of(&self, id: &str, direction: Direction) -> Vec<String> {
+        let indices = match self.events_map.get(id) {
+            Some(idx) => self.dag.neighbors_directed(*idx, direction).collect(),
+            None => Vec::new(),
+        };
+
+        self.sorted_ids(indices)
+    }
+
+    // Returns the IDs of the events at `indices`, ordered by creation time and then by ID so
+    // that the order is the same every time.
+    fn sorted_ids(&self, indices: Vec<NodeIndex>) -> Vec<String> {
+        let mut events: Vec<&Event> = indices.into_iter().map(|idx| &self.dag[idx]).collect();
+
+        events.sort_by(|a, b| {
+            a.get_origin_server_ts()
+                .cmp(&b.get_origin_server_ts())
+                .then_with(|| a.event_id.cmp(&b.event_id))
+        });
+        events.dedup_by(|a, b| a.event_id == b.event_id);
+
+        events.into_iter().map(|ev| ev.event_id.clone()).collect()
+    }
+
+    /// Creates a data set for creating a vis.js network, whose labels include the `fields` of
+    /// the events.
+    pub fn create_data_set(&self, fields: &HashSet<Field>) -> DataSet {
+        let nodes: Vec<DataSetNode> = self
+            .events_map
+            .values()
+            .map(|idx| self.to_data_set_node(*idx, fields))
+            .collect();
+
+        let mut edges: Vec<DataSetEdge> = self
+            .dag
+            .edge_references()
+            .filter(|edge| !self.is_hidden_edge(&(edge.source(), edge.target())))
+            .map(|edge| {
+                let from = self
+                    .dag
+                    .node_weight(edge.source())
+                    .unwrap()
+                    .event_id
+                    .clone();
+                let to = self
+                    .dag
+                    .node_weight(edge.target())
+                    .unwrap()
+                    .event_id
+                    .clone();
+
+                DataSetEdge {
+                    id: edge_id(&from, &to, PREV_EVENT_EDGE),
+                    from,
+                    to,
+                    label: None,
+                    color: None,
+                    dashes: false,
+                }
+            })
+            .collect();
+
+        edges.extend(self.relation_edges(|_, _| true));
+
+        let mut data_set = DataSet { nodes, edges };
+        self.add_ghosts(&mut data_set, |_| true);
+        self.fold_data_set(&mut data_set);
+        self.add_predecessor(&mut data_set, fields);
+
+        data_set
+    }
+
+    /// Creates a data set containing only a sample of the events of the DAG, for a quick first
+    /// look at huge rooms. The extremities, forks, merges, state events and orphans are always
+    /// kept, the other events are kept with a probability of `1 / rate`. Each kept event is linked
+    /// to its closest kept ancestors.
+    pub fn create_sampled_data_set(&self, rate: usize, fields: &HashSet<Field>) -> DataSet {
+        let kept: HashSet<NodeIndex> = self
+            .dag
+            .node_indices()
+            .filter(|idx| self.is_kept_in_sample(*idx, rate))
+            .collect();
+
+        let nodes: Vec<DataSetNode> = kept
+            .iter()
+            .map(|idx| self.to_data_set_node(*idx, fields))
+            .collect();
+
+        let mut edges: Vec<DataSetEdge> = Vec::new();
+
+        for &src_idx in &kept {
+            let mut seen: HashSet<NodeIndex> = HashSet::new();
+            let mut stack: Vec<NodeIndex> = self
+                .dag
+                .neighbors_directed(src_idx, Direction::Outgoing)
+                .collect();
+
+            // Walk through the ancestors which were left out until reaching kept events
+            while let Some(idx) = stack.pop() {
+                if !seen.insert(idx) {
+                    continue;
+                }
+
+                if kept.contains(&idx) {
+                    edges.push(self.to_data_set_edge((src_idx, idx)).unwrap());
+                } else {
+                    stack.extend(self.dag.neighbors_directed(idx, Direction::Outgoing));
+                }
+            }
+        }
+
+        edges.extend(self.relation_edges(|src, dst| kept.contains(&src) && kept.contains(&dst)));
+
+        let mut data_set = DataSet { nodes, edges };
+        self.add_ghosts(&mut data_set, |idx| kept.contains(&idx));
+        self.fold_data_set(&mut data_set);
+
+        data_set
+    }
+
+    /// Creates a data set containing every events whose depth is between `min_depth` and
+    /// `max_depth` as well as the edges between them. This is used to fill a region of a sampled
+    /// data set.
+    pub fn densify(&self, min_depth: i64, max_depth: i64, fields: &HashSet<Field>) -> DataSet {
+        self.create_data_set_range(min_depth, max_depth, fields)
+    }
+
+    /// Creates a data set containing only the events whose depth is between `min_depth` and
+    /// `max_depth` as well as the edges between them, so that a large DAG can be rendered one
+    /// window of depths at a time.
+    pub fn create_data_set_range(
+        &self,
+        min_depth: i64,
+        max_depth: i64,
+        fields: &HashSet<Field>,
+    ) -> DataSet {
+        let indices: HashSet<NodeIndex> = self
+            .depth_map
+            .iter()
+            .filter(|(depth, _)| **depth >= min_depth && **depth <= max_depth)
+            .flat_map(|(_, indices)| indices.iter().cloned())
+            .collect();
+
+        self.create_data_set_of(&indices, fields)
+    }
+
+    /// Creates a data set centred on the event `id`, containing only its ancestors and its
+    /// descendants within `hops` edges of it, as well as the edges between them, so that a single
+    /// merge can be looked at without drawing the whole DAG. Increasing `hops` one at a time
+    /// expands the data set ring by ring. It is empty if the event isn't in the DAG.
+    pub fn create_data_set_around(
+        &self,
+        id: &str,
+        hops: usize,
+        fields: &HashSet<Field>,
+    ) -> DataSet {
+        let indices: HashSet<NodeIndex> = match self.events_map.get(id) {
+            Some(idx) => [Direction::Outgoing, Direction::Incoming]
+                .iter()
+                .flat_map(|direction| self.within_hops(*idx, hops, *direction))
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        self.create_data_set_of(&indices, fields)
+    }
+
+    // Returns the events reached from the event at `idx` by following at most `hops` edges in
+    // `direction`, towards the ancestors or the descendants, the event itself included.
+    fn within_hops(&self, idx: NodeIndex, hops: usize, direction: Direction) -> HashSet<NodeIndex> {
+        let mut reached: HashSet<NodeIndex> = HashSet::new();
+        let mut ring = vec![idx];
+        reached.insert(idx);
+
+        for _ in 0..hops {
+            ring = ring
+                .iter()
+                .flat_map(|idx| self.dag.neighbors_directed(*idx, direction))
+                .filter(|neighbour| reached.insert(*neighbour))
+                .collect();
+
+            if ring.is_empty() {
+                break;
+            }
+        }
+
+        reached
+    }
+
+    // Creates a data set containing the events at `indices` and the edges between them.
+    fn create_data_set_of(&self, indices: &HashSet<NodeIndex>, fields: &HashSet<Field>) -> DataSet {
+        let nodes: Vec<DataSetNode> = indices
+            .iter()
+            .map(|idx| self.to_data_set_node(*idx, fields))
+            .collect();
+
+        let mut edges: Vec<DataSetEdge> = self
+            .dag
+            .edge_references()
+            .filter(|e| indices.contains(&e.source()) && indices.contains(&e.target()))
+            .filter(|e| !self.is_hidden_edge(&(e.source(), e.target())))
+            .map(|e| self.to_data_set_edge((e.source(), e.target())).unwrap())
+            .collect();
+
+        edges.extend(
+            self.relation_edges(|src, dst| indices.contains(&src) && indices.contains(&dst)),
+        );
+
+        let mut data_set = DataSet { nodes, edges };
+        self.add_ghosts(&mut data_set, |idx| indices.contains(&idx));
+        self.fold_data_set(&mut data_set);
+
+        data_set
+    }
+
+    // Tells whether the event at `idx` must be kept in a sampled data set. The sampling of the
+    // events which are not structurally important is based on the hash of their ID so that it
+    // stays the same across renderings.
+    fn is_kept_in_sample(&self, idx: NodeIndex, rate: usize) -> bool {
+        let event = self.dag.node_weight(idx).unwrap();
+        let nb_children = self.dag.edges_directed(idx, Direction::Incoming).count();
+        let nb_parents = self.dag.edges_directed(idx, Direction::Outgoing).count();
+
+        if nb_children != 1 || nb_parents != 1 || event.is_state() {
+            return true;
+        }
+
+        if nb_parents < event.get_prev_events().len() {
+            return true;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        event.event_id.hash(&mut hasher);
+
+        hasher.finish().is_multiple_of(rate.max(1) as u64)
+    }
+
+    /// Adds to `data_set` every events in the DAG which are earlier than the events which IDs are
+    /// in `from`, along with their edges, including those to the events already drawn.
+    pub fn add_earlier_events_to_data_set(
+        &self,
+        data_set: &mut DataSet,
+        from: Vec<String>,
+        fields: &HashSet<Field>,
+    ) {
+        let from_indices: HashSet<NodeIndex> = from
+            .iter()
+            .map(|id| *self.events_map.get(id).unwrap())
+            .collect();
+
+        let (new_node_indices, new_edges) = new_nodes_edges(&self.dag, from_indices);
+        new_node_indices
+            .iter()
+            .map(|idx| self.to_data_set_node(*idx, fields))
+            .for_each(|node| data_set.nodes.push(node));
+
+        new_edges
+            .iter()
+            .filter(|edge| !self.is_hidden_edge(edge))
+            .map(|(src, dst)| self.to_data_set_edge((*src, *dst)).unwrap())
+            .for_each(|edge| data_set.edges.push(edge));
+
+        data_set.edges.extend(self.relation_edges(|src, dst| {
+            new_node_indices.contains(&src) || new_node_indices.contains(&dst)
+        }));
+
+        self.add_ghosts(data_set, |idx| new_node_indices.contains(&idx));
+        self.fold_data_set(data_set);
+    }
+
+    /// Adds to `data_set` every events in the DAG which are newer than the events which IDs are
+    /// in `from`, along with their edges, including those to the events already drawn.
+    pub fn add_new_events_to_data_set(
+        &self,
+        data_set: &mut DataSet,
+        from: Vec<String>,
+        fields: &HashSet<Field>,
+    ) {
+        // TODO: Make a shadow copy instead of a real one
+        let mut rev_dag = self.dag.clone();
+        rev_dag.reverse();
+
+        let from_indices: HashSet<NodeIndex> = from
+            .iter()
+            .map(|id| *self.events_map.get(id).unwrap())
+            .collect();
+
+        let (new_node_indices, rev_new_edges) = new_nodes_edges(&rev_dag, from_indices);
+
+        // We have to reverse the edges again
+        let new_edges: HashSet<(NodeIndex, NodeIndex)> = rev_new_edges
+            .into_iter()
+            .map(|(src, dst)| (dst, src))
+            .collect();
+        new_node_indices
+            .iter()
+            .map(|idx| self.to_data_set_node(*idx, fields))
+            .for_each(|node| data_set.nodes.push(node));
+
+        new_edges
+            .iter()
+            .filter(|edge| !self.is_hidden_edge(edge))
+            .map(|(src, dst)| self.to_data_set_edge((*src, *dst)).unwrap())
+            .for_each(|edge| data_set.edges.push(edge));
+
+        data_set.edges.extend(self.relation_edges(|src, dst| {
+            new_node_indices.contains(&src) || new_node_indices.contains(&dst)
+        }));
+
+        self.add_ghosts(data_set, |idx| new_node_indices.contains(&idx));
+        self.fold_data_set(data_set);
+    }
+
+    /// Changes the way the event IDs are shortened in the labels on the nodes of the vis.js
+    /// network.
+    pub fn change_id_shortener(&mut self, id_shortener: IdShortener) {
+        self.display.id_shortener = id_shortener;
+        self.analyses.outline = OnceLock::new();
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.change_id_shortener(id_shortener);
+        }
+    }
+
+    /// Changes the way the fields are laid out in the labels on the nodes of the vis.js network.
+    pub fn change_label_format(&mut self, label_format: LabelFormat) {
+        self.display.label_format = label_format;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.change_label_format(label_format);
+        }
+    }
+
+    /// Changes the number of recent deliveries remembered to suppress the duplicate events.
+    pub fn set_dedup_window(&mut self, window: usize) {
+        self.reconciler.set_window(window);
+    }
+
+    /// Takes over the deliveries remembered by `previous`, the DAG this one replaces (e.g. when an
+    /// operation is undone), so that the deliveries in flight are still suppressed. The events
+    /// of `previous` which aren't in this DAG are forgotten, so that they can be fetched again.
+    pub fn take_deliveries(&mut self, previous: &mut RoomEvents) {
+        let mut reconciler = mem::take(&mut previous.reconciler);
+
+        for id in previous.events_map.keys() {
+            if !self.events_map.contains_key(id) {
+                reconciler.forget(id);
+            }
+        }
+        reconciler.set_window(self.reconciler.window());
+
+        self.reconciler = reconciler;
+    }
+
+    /// Returns the number of duplicate deliveries of events which were suppressed.
+    pub fn suppressed_duplicates(&self) -> usize {
+        self.reconciler.suppressed()
+    }
+
+    /// Shows or hides the membership and the power level of the sender of each event on its node,
+    /// and highlights the events whose sender shouldn't have been able to send them.
+    pub fn set_show_authority(&mut self, show_authority: bool) {
+        self.display.show_authority = show_authority;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_show_authority(show_authority);
+        }
+    }
+
+    /// Shows or hides the events referenced in the `prev_events` of the events of the DAG but
+    /// which are not in it as ghost nodes of the vis.js network, so that the holes in the DAG
+    /// can be seen where they are.
+    pub fn set_show_ghosts(&mut self, show_ghosts: bool) {
+        self.display.show_ghosts = show_ghosts;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_show_ghosts(show_ghosts);
+        }
+    }
+
+    /// Changes the theme giving its colours to the nodes and the edges of the vis.js network.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.display.theme = theme;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_theme(theme);
+        }
+    }
+
+    /// Makes the levels of the nodes of the vis.js network the ranks of the depths at which the
+    /// DAG has events rather than the depths themselves, or the other way around. The order of
+    /// the events is preserved, but the gaps between the depths (left by a purge of the history,
+    /// for example) are removed, so that they don't make the network absurdly tall. The original
+    /// depths can still be shown in the labels with the `Depth` field.
+    pub fn set_compress_depths(&mut self, compress_depths: bool) {
+        self.display.compress_depths = compress_depths;
+
+        for i in 0..self.orphan_events.len() {
+            let level = self.level_of(self.orphan_events[i].depth);
+            self.orphan_events[i].level = level;
+        }
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_compress_depths(compress_depths);
+        }
+    }
+
+    pub fn compresses_depths(&self) -> bool {
+        self.display.compress_depths
+    }
+
+    /// Leaves out of the vis.js network the edges of the DAG which are implied by longer paths,
+    /// i.e. the edges from an event to those of its `prev_events` which are also ancestors of its
+    /// other `prev_events`, or puts them back. The DAG itself keeps every edge, only the data
+    /// sets are reduced, which makes the dense regions of merges readable. The sampled data sets
+    /// already only link the events to their closest kept ancestors and are left as they are.
+    pub fn set_transitive_reduction(&mut self, transitive_reduction: bool) {
+        self.display.transitive_reduction = transitive_reduction;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_transitive_reduction(transitive_reduction);
+        }
+    }
+
+    pub fn is_transitively_reduced(&self) -> bool {
+        self.display.transitive_reduction
+    }
+
+    /// Colours the nodes by the delivery latency of their events (see `latency_of`) rather than
+    /// by their origin, or stops doing so. The events whose latency isn't known keep their
+    /// colour.
+    pub fn set_color_by_latency(&mut self, color_by_latency: bool) {
+        self.display.color_by_latency = color_by_latency;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_color_by_latency(color_by_latency);
+        }
+    }
+
+    /// Returns the delivery latency of the event `event_id` in milliseconds: the time between its
+    /// `origin_server_ts` and its receipt by the server which persisted it. It is only known when
+    /// the source gives the `received_ts` of the events, i.e. with the Postgres backend. It may
+    /// be negative if the clocks of the servers disagree.
+    pub fn latency_of(&self, event_id: &str) -> Option<i64> {
+        let received_ts = self.received_ts.get(event_id)?;
+        let event = self.get_event(event_id)?;
+
+        Some(received_ts - event.get_origin_server_ts())
+    }
+
+    /// Returns when the server this DAG was retrieved from received the event `event_id`, in ms
+    /// since the epoch by the clock of the client: the time the response which brought the event
+    /// was received, minus the `age` the server gave in its `unsigned` data, since the server
+    /// computes the `age` when it sends the response. It is only known for the events whose
+    /// request was recorded (see `record_batch`) and whose copy has an `age`.
+    pub fn arrival_ts(&self, event_id: &str) -> Option<i64> {
+        let (_, batch) = self.provenance_of(event_id)?;
+        let age = self.get_event(event_id)?.get_unsigned()?["age"].as_i64()?;
+
+        Some(batch.received_at.unwrap_or(batch.requested_at) - age)
+    }
+
+    /// For a merged DAG, returns when each server received the event `event_id`, as far as it
+    /// can be estimated, see `arrival_ts`.
+    pub fn arrivals_of(&self, event_id: &str) -> &[Arrival] {
+        self.arrivals.get(event_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// For a merged DAG, returns the origin servers whose clock is ahead or behind the arrivals of
+    /// their events by at least `threshold` ms, by decreasing skew. The skew of each origin is
+    /// the median of the skews of its events, see `clock_skew::event_skew`.
+    pub fn clock_skews(&self, threshold: i64) -> Vec<ClockSkew> {
+        self.analyses
+            .clock_skews
+            .get_or_init(|| self.compute_clock_skews())
+            .iter()
+            .filter(|skew| skew.skew.abs() >= threshold)
+            .cloned()
+            .collect()
+    }
+
+    fn compute_clock_skews(&self) -> Vec<ClockSkew> {
+        let mut samples: HashMap<&str, Vec<i64>> = HashMap::new();
+
+        for (id, arrivals) in &self.arrivals {
+            if let Some(ev) = self.get_event(id) {
+                let skew =
+                    clock_skew::event_skew(ev.get_origin(), ev.get_origin_server_ts(), arrivals);
+
+                if let Some(skew) = skew {
+                    samples.entry(ev.get_origin()).or_default().push(skew);
+                }
+            }
+        }
+
+        let mut skews: Vec<ClockSkew> = samples
+            .into_iter()
+            .map(|(origin, mut skews)| ClockSkew {
+                origin: origin.to_string(),
+                events: skews.len(),
+                skew: clock_skew::median(&mut skews),
+            })
+            .collect();
+
+        skews.sort_by(|a, b| {
+            b.skew
+                .abs()
+                .cmp(&a.skew.abs())
+                .then_with(|| a.origin.cmp(&b.origin))
+        });
+
+        skews
+    }
+
+    /// Whether the delivery latency of some events is known.
+    pub fn has_latencies(&self) -> bool {
+        !self.received_ts.is_empty()
+    }
+
+    /// Records that `events` were brought by the request `batch`, whether they were added to the
+    /// DAG already or not. The events already brought by an earlier request keep their
+    /// provenance.
+    pub fn record_batch(&mut self, mut batch: FetchBatch, events: &[JsonValue]) {
+        let index = self.batches.len();
+
+        for id in events.iter().filter_map(|ev| ev["event_id"].as_str()) {
+            if !self.provenance.contains_key(id) {
+                self.provenance.insert(id.to_string(), index);
+                batch.events += 1;
+            }
+        }
+
+        self.batches.push(batch);
+    }
+
+    /// Returns the request the event `event_id` first came in, and its index among the requests
+    /// recorded, if it is known.
+    pub fn provenance_of(&self, event_id: &str) -> Option<(usize, &FetchBatch)> {
+        let index = *self.provenance.get(event_id)?;
+
+        Some((index, &self.batches[index]))
+    }
+
+    /// Returns the requests which brought events to the DAG, in the order they were recorded.
+    pub fn batches(&self) -> &[FetchBatch] {
+        &self.batches
+    }
+
+    /// Colours the nodes by the request their events first came in (see `record_batch`) rather
+    /// than by their origin, or stops doing so. The events whose provenance isn't known keep
+    /// their colour.
+    pub fn set_color_by_batch(&mut self, color_by_batch: bool) {
+        self.display.color_by_batch = color_by_batch;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_color_by_batch(color_by_batch);
+        }
+    }
+
+    /// Updates the read receipts with the ephemeral events of the room in a `/sync` response, and
+    /// the fully-read marker of `user_id` with the account data events of the room in it. Returns
+    /// whether any marker moved, in which case the labels of the nodes need to be updated.
+    pub fn update_read_markers(
+        &mut self,
+        user_id: &str,
+        ephemeral: &[JsonValue],
+        account_data: &[JsonValue],
+    ) -> bool {
+        let receipts_moved = self.read_markers.update_from_ephemeral(ephemeral);
+        let fully_read_moved = self
+            .read_markers
+            .update_from_account_data(user_id, account_data);
+
+        receipts_moved || fully_read_moved
+    }
+
+    /// Returns where the read receipts and fully-read markers of the users point.
+    pub fn read_markers(&self) -> &ReadMarkers {
+        &self.read_markers
+    }
+
+    /// Shows the read receipts and fully-read markers pointing to the events on their nodes, or
+    /// stops doing so. They are in the tooltips of the nodes either way.
+    pub fn set_show_read_markers(&mut self, show_read_markers: bool) {
+        self.display.show_read_markers = show_read_markers;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_show_read_markers(show_read_markers);
+        }
+    }
+
+    /// Colours the nodes of the events matching the expressions of `rules`, the first rule an
+    /// event matches deciding its colour, or returns why one of the expressions is invalid, in
+    /// which case the rules are left unchanged. The colours of the rules come after the ones of
+    /// the latency and of the batches, but before the ones flagging the unreachable servers.
+    pub fn set_colour_rules(&mut self, rules: &[ColourRule]) -> Result<(), String> {
+        let compiled = rules
+            .iter()
+            .map(ColourRule::compile)
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.display.colour_rules = compiled.clone();
+        }
+        self.display.colour_rules = compiled;
+
+        Ok(())
+    }
+
+    /// Returns the number of edges of the DAG which are implied by longer paths, and which are
+    /// left out of the vis.js network by the transitive reduction.
+    pub fn redundant_edge_count(&self) -> usize {
+        self.redundant_edges().len()
+    }
+
+    // Tells whether `edge` is left out of the data sets, which the redundant edges are if the
+    // transitive reduction is enabled
+    fn is_hidden_edge(&self, edge: &(NodeIndex, NodeIndex)) -> bool {
+        self.display.transitive_reduction && self.redundant_edges().contains(edge)
+    }
+
+    // Returns the edges from the events to those of their `prev_events` which are also ancestors
+    // of their other `prev_events`, which are only looked for once per change of the DAG
+    fn redundant_edges(&self) -> &HashSet<(NodeIndex, NodeIndex)> {
+        self.analyses
+            .redundant_edges
+            .get_or_init(|| self.find_redundant_edges())
+    }
+
+    // Finds the redundant edges, see `redundant_edges`. Nothing is returned if the DAG has a
+    // cycle, as its transitive reduction wouldn't be unique.
+    fn find_redundant_edges(&self) -> HashSet<(NodeIndex, NodeIndex)> {
+        let mut redundant = HashSet::new();
+
+        let order = match toposort(&self.dag, None) {
+            Ok(order) => order,
+            Err(_) => return redundant,
+        };
+        let position: HashMap<NodeIndex, usize> = order
+            .into_iter()
+            .enumerate()
+            .map(|(i, idx)| (idx, i))
+            .collect();
+
+        for idx in self.dag.node_indices() {
+            let parents: HashSet<NodeIndex> = self
+                .dag
+                .neighbors_directed(idx, Direction::Outgoing)
+                .collect();
+
+            if parents.len() < 2 {
+                continue;
+            }
+
+            // The ancestors of an event come after it in the topological order, so the ancestors
+            // coming after the last parent can't lead back to any of them
+            let bound = parents.iter().map(|p| position[p]).max().unwrap();
+            let mut seen: HashSet<NodeIndex> = HashSet::new();
+            let mut stack: Vec<NodeIndex> = parents
+                .iter()
+                .flat_map(|p| self.dag.neighbors_directed(*p, Direction::Outgoing))
+                .collect();
+
+            while let Some(ancestor) = stack.pop() {
+                if position[&ancestor] > bound || !seen.insert(ancestor) {
+                    continue;
+                }
+
+                if parents.contains(&ancestor) {
+                    redundant.insert((idx, ancestor));
+                }
+
+                stack.extend(self.dag.neighbors_directed(ancestor, Direction::Outgoing));
+            }
+        }
+
+        redundant
+    }
+
+    /// Returns the IDs of the events matching `filter`, by increasing depth.
+    pub fn select_where(&self, filter: &EventFilter) -> Vec<String> {
+        self.select_by(|ev| filter.matches(ev))
+    }
+
+    /// Returns the IDs of the events matching `expression`, by increasing depth.
+    pub fn select_matching(&self, expression: &Expression) -> Vec<String> {
+        self.select_by(|ev| expression.matches(ev))
+    }
+
+    fn select_by<F>(&self, predicate: F) -> Vec<String>
+    where
+        F: Fn(&Event) -> bool,
+    {
+        let mut events: Vec<&Event> = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .map(|node| &node.weight)
+            .filter(|ev| predicate(ev))
+            .collect();
+
+        events.sort_by(|a, b| {
+            a.depth
+                .cmp(&b.depth)
+                .then_with(|| a.event_id.cmp(&b.event_id))
+        });
+
+        events.into_iter().map(|ev| ev.event_id.clone()).collect()
+    }
+
+    /// Leaves the events whose IDs are in `ids` out of the vis.js network, along with their
+    /// edges, each of them being an item of the restore list. The IDs of events which are not in
+    /// the DAG or already hidden are ignored.
+    pub fn hide_events(&mut self, ids: &[String]) {
+        let hidden = self.hidden_events();
+        let items: Vec<HiddenItem> = ids
+            .iter()
+            .filter(|id| self.events_map.contains_key(*id) && !hidden.contains(id.as_str()))
+            .map(|id| HiddenItem {
+                root: id.clone(),
+                events: [id.clone()].iter().cloned().collect(),
+            })
+            .collect();
+
+        self.hidden.extend(items);
+    }
+
+    /// Leaves the branch starting at the event `id` out of the vis.js network, as a single item
+    /// of the restore list: the event and its descendants whose parents are all in the branch,
+    /// so that the branch stops where it merges with the rest of the DAG. Returns the number of
+    /// events hidden.
+    pub fn hide_branch(&mut self, id: &str) -> usize {
+        let root_idx = match self.events_map.get(id) {
+            Some(idx) => *idx,
+            None => return 0,
+        };
+
+        let mut branch: HashSet<NodeIndex> = HashSet::new();
+        let mut stack = vec![root_idx];
+        branch.insert(root_idx);
+
+        while let Some(idx) = stack.pop() {
+            for child in self.dag.neighbors_directed(idx, Direction::Incoming) {
+                let in_branch = self
+                    .dag
+                    .neighbors_directed(child, Direction::Outgoing)
+                    .all(|parent| branch.contains(&parent));
+
+                if in_branch && branch.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+
+        let events: HashSet<String> = branch
+            .into_iter()
+            .map(|idx| self.dag[idx].event_id.clone())
+            .collect();
+        let count = events.len();
+
+        // The events of the branch hidden before are now part of its item
+        self.hidden.retain(|item| !item.events.is_subset(&events));
+        self.hidden.push(HiddenItem {
+            root: id.to_string(),
+            events,
+        });
+
+        count
+    }
+
+    /// Brings back the events of the item of the restore list whose root is `root` into the
+    /// vis.js network.
+    pub fn restore_hidden(&mut self, root: &str) {
+        self.hidden.retain(|item| item.root != root);
+    }
+
+    /// Brings back every hidden events into the vis.js network.
+    pub fn show_hidden_events(&mut self) {
+        self.hidden.clear();
+    }
+
+    /// Returns the items of the restore list, in the order in which they were hidden.
+    pub fn hidden_items(&self) -> &[HiddenItem] {
+        &self.hidden
+    }
+
+    pub fn hidden_count(&self) -> usize {
+        self.hidden_events().len()
+    }
+
+    // Returns the IDs of every hidden events.
+    fn hidden_events(&self) -> HashSet<&str> {
+        self.hidden
+            .iter()
+            .flat_map(|item| item.events.iter().map(|id| id.as_str()))
+            .collect()
+    }
+
+    /// Outlines the nodes of the events whose IDs are in `ids`, replacing the previous ones.
+    pub fn set_highlighted(&mut self, ids: &[String]) {
+        self.highlighted = ids.iter().cloned().collect();
+    }
+
+    pub fn highlighted_count(&self) -> usize {
+        self.highlighted.len()
+    }
+
+    /// Draws the events whose IDs are in `ids` as a single node, which takes over their edges.
+    /// The events already collapsed and those which are not in the DAG are ignored.
+    pub fn collapse_events(&mut self, ids: &[String]) {
+        let group: HashSet<String> = ids
+            .iter()
+            .filter(|id| self.events_map.contains_key(*id))
+            .filter(|id| !self.collapsed_groups.iter().any(|g| g.contains(*id)))
+            .cloned()
+            .collect();
+
+        if !group.is_empty() {
+            self.collapsed_groups.push(group);
+        }
+    }
+
+    /// Draws the collapsed events as separate nodes again.
+    pub fn expand_all(&mut self) {
+        self.collapsed_groups.clear();
+    }
+
+    pub fn collapsed_count(&self) -> usize {
+        self.collapsed_groups.len()
+    }
+
+    /// Returns the events hidden, highlighted and collapsed by the user.
+    pub fn filter_state(&self) -> FilterState {
+        FilterState {
+            hidden: self.hidden.clone(),
+            highlighted: self.highlighted.clone(),
+            collapsed_groups: self.collapsed_groups.clone(),
+        }
+    }
+
+    /// Hides, highlights and collapses the events as they were in `state`.
+    pub fn set_filter_state(&mut self, state: FilterState) {
+        self.hidden = state.hidden;
+        self.highlighted = state.highlighted;
+        self.collapsed_groups = state.collapsed_groups;
+    }
+
+    // Applies the hidden and the collapsed events to `data_set`: the hidden events are removed
+    // along with their edges, and the collapsed events are replaced by the node of their group,
+    // to which their edges are redirected. The node of a group is placed at the level of its
+    // latest event, and it is only added if some of its events are in `data_set`. The edges
+    // given twice to `data_set`, e.g. by an incremental update reaching them from both of their
+    // ends, are left out along the way.
+    fn fold_data_set(&self, data_set: &mut DataSet) {
+        if self.hidden.is_empty() && self.collapsed_groups.is_empty() {
+            data_set.dedup_edges();
+            return;
+        }
+
+        let hidden_events = self.hidden_events();
+
+        let palette = self.display.theme.palette();
+        let mut group_of: HashMap<&str, String> = HashMap::new();
+        let mut group_nodes: Vec<DataSetNode> = Vec::new();
+
+        for (i, group) in self.collapsed_groups.iter().enumerate() {
+            let group_id = format!("collapsed_{}", i);
+
+            for id in group {
+                group_of.insert(id, group_id.clone());
+            }
+
+            if !data_set.nodes.iter().any(|node| group.contains(&node.id)) {
+                continue;
+            }
+
+            let level = group
+                .iter()
+                .filter_map(|id| self.events_map.get(id))
+                .map(|&idx| self.level_of(self.dag[idx].depth))
+                .max()
+                .unwrap_or_default();
+
+            group_nodes.push(DataSetNode {
+                id: group_id,
+                label: format!("{} collapsed events", group.len()),
+                title: Some("Expand the collapsed events to draw them again".to_string()),
+                font: None,
+                level,
+                color: palette.collapsed.node_color(),
+                value: None,
+                border_width: Some(2),
+                shape_properties: None,
+            });
+        }
+
+        data_set.nodes.retain(|node| {
+            !hidden_events.contains(node.id.as_str()) && !group_of.contains_key(node.id.as_str())
+        });
+        data_set.nodes.extend(group_nodes);
+
+        let mut registry = EdgeRegistry::new();
+        let edges = std::mem::take(&mut data_set.edges);
+
+        for mut edge in edges {
+            if hidden_events.contains(edge.from.as_str())
+                || hidden_events.contains(edge.to.as_str())
+            {
+                continue;
+            }
+
+            let from = group_of.get(edge.from.as_str()).cloned();
+            let to = group_of.get(edge.to.as_str()).cloned();
+
+            if from.is_some() || to.is_some() {
+                edge.from = from.unwrap_or(edge.from);
+                edge.to = to.unwrap_or(edge.to);
+                // The relations keep their kind, so that they stay apart from the other edges
+                edge.id = edge_id(
+                    &edge.from,
+                    &edge.to,
+                    edge.label.as_deref().unwrap_or(PREV_EVENT_EDGE),
+                );
+
+                // The edges between the events of a group disappear with them
+                if edge.from == edge.to {
+                    continue;
+                }
+            }
+
+            if registry.admit(&edge) {
+                data_set.edges.push(edge);
+            }
+        }
+    }
+
+    /// Returns the level in the vis.js network of the events at depth `depth`. When the depths
+    /// are compressed, a depth at which the DAG has no event gets the level of the closest depth
+    /// below it at which it has some.
+    pub fn level_of(&self, depth: i64) -> i64 {
+        if !self.display.compress_depths {
+            return depth;
+        }
+
+        match self.observed_depths.binary_search(&depth) {
+            Ok(rank) => rank as i64 + 1,
+            Err(rank) => rank as i64,
+        }
+    }
+
+    /// Returns the range of depths whose events have their level between `min_level` and
+    /// `max_level` in the vis.js network.
+    pub fn levels_to_depths(&self, min_level: i64, max_level: i64) -> (i64, i64) {
+        if !self.display.compress_depths || self.observed_depths.is_empty() {
+            return (min_level, max_level);
+        }
+
+        // The levels outside of those of the events are mapped to the depths just beyond them
+        let depth_of = |level: i64| {
+            if level < 1 {
+                self.observed_depths[0] + level - 1
+            } else if level as usize > self.observed_depths.len() {
+                self.observed_depths[self.observed_depths.len() - 1] + level
+                    - self.observed_depths.len() as i64
+            } else {
+                self.observed_depths[level as usize - 1]
+            }
+        };
+
+        (depth_of(min_level), depth_of(max_level))
+    }
+
+    /// Returns the level of every event of the DAG in the vis.js network, so that the nodes
+    /// already in it can be moved when adding events changes the ranks of the depths.
+    pub fn event_levels(&self) -> Vec<(String, i64)> {
+        self.dag
+            .raw_nodes()
+            .iter()
+            .map(|node| {
+                (
+                    node.weight.event_id.clone(),
+                    self.level_of(node.weight.depth),
+                )
+            })
+            .collect()
+    }
+
+    /// Shows or hides the relations between the events (threads, edits, reactions and replies)
+    /// as additional edges of the vis.js network.
+    pub fn set_show_relations(&mut self, show_relations: bool) {
+        self.display.show_relations = show_relations;
+
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_show_relations(show_relations);
+        }
+    }
+
+    /// Returns the events whose copies differ across the DAGs merged into this one.
+    pub fn conflict_report(&self) -> &ConflictReport {
+        &self.conflict_report
+    }
+
+    /// Returns the names of the servers from which the events of the DAG originate.
+    pub fn origin_servers(&self) -> HashSet<String> {
+        self.dag
+            .raw_nodes()
+            .iter()
+            .map(|n| n.weight.get_origin().to_string())
+            .collect()
+    }
+
+    /// Sets the origin servers which couldn't be reached, so that their events are marked in the
+    /// vis.js network.
+    pub fn set_dead_servers(&mut self, dead_servers: HashSet<String>) {
+        self.dead_servers = dead_servers;
+    }
+
+    /// Sets the notes pinned to the events, which are drawn as markers on their nodes.
+    pub fn set_annotations(&mut self, annotations: Annotations) {
+        if let Some(predecessor) = &mut self.predecessor {
+            predecessor.set_annotations(annotations.clone());
+        }
+
+        self.annotations = annotations;
+    }
+
+    /// Simulates that the server `server` went offline right after receiving the event
+    /// `last_event`: the events it couldn't have received are greyed out in the vis.js network.
+    /// Returns `false` if `last_event` isn't in the DAG.
+    pub fn simulate_outage(&mut self, server: &str, last_event: &str) -> bool {
+        if !self.events_map.contains_key(last_event) {
+            return false;
+        }
+
+        self.outage = Some(SimulatedOutage {
+            server: server.to_string(),
+            last_event: last_event.to_string(),
+            missed: HashSet::new(),
+            created_offline: HashSet::new(),
+        });
+        self.update_outage();
+
+        true
+    }
+
+    /// Ends the simulation of an outage, if there is one.
+    pub fn end_outage_simulation(&mut self) {
+        self.outage = None;
+    }
+
+    /// Returns the outage being simulated, if there is one.
+    pub fn simulated_outage(&self) -> Option<&SimulatedOutage> {
+        self.outage.as_ref()
+    }
+
+    // Finds the events the server of the simulated outage could have while offline: the last
+    // event it received and its ancestors, as well as the events it created itself on top of
+    // those. This must be done again whenever the edges of the DAG change.
+    fn update_outage(&mut self) {
+        let (server, last_event) = match &self.outage {
+            Some(outage) => (outage.server.clone(), outage.last_event.clone()),
+            None => return,
+        };
+
+        let mut received: HashSet<NodeIndex> = HashSet::new();
+        let mut stack: Vec<NodeIndex> = self
+            .events_map
+            .get(&last_event)
+            .cloned()
+            .into_iter()
+            .collect();
+
+        while let Some(idx) = stack.pop() {
+            if received.insert(idx) {
+                stack.extend(self.dag.neighbors_directed(idx, Direction::Outgoing));
+            }
+        }
+
+        let mut own_events: Vec<NodeIndex> = self
+            .dag
+            .node_indices()
+            .filter(|idx| !received.contains(idx) && self.dag[*idx].get_origin() == server)
+            .collect();
+        own_events.sort_by_key(|idx| self.dag[*idx].depth);
+
+        // An event of the server can only be created once all of its `prev_events` are known
+        // to it, which may come from another event it created while offline
+        let mut created_offline: HashSet<String> = HashSet::new();
+
+        loop {
+            let count = created_offline.len();
+
+            own_events.retain(|idx| {
+                let known = self
+                    .dag
+                    .neighbors_directed(*idx, Direction::Outgoing)
+                    .all(|prev| received.contains(&prev));
+
+                if known {
+                    received.insert(*idx);
+                    created_offline.insert(self.dag[*idx].event_id.clone());
+                }
+
+                !known
+            });
+
+            if created_offline.len() == count {
+                break;
+            }
+        }
+
+        let missed = self
+            .dag
+            .node_indices()
+            .filter(|idx| !received.contains(idx))
+            .map(|idx| self.dag[idx].event_id.clone())
+            .collect();
+
+        if let Some(outage) = self.outage.as_mut() {
+            outage.missed = missed;
+            outage.created_offline = created_offline;
+        }
+    }
+
+    /// Change the metric used to scale the nodes of the vis.js network, or disable the scaling if
+    /// `size_metric` is `None`.
+    pub fn change_size_metric(&mut self, size_metric: Option<SizeMetric>) {
+        self.display.size_metric = size_metric;
+    }
+
+    // Counts the descendants of every event, by node index, in a single pass over the events from
+    // the latest to the earliest: the descendants of an event are its children and theirs, which
+    // are kept as bitsets until every parent of the child took them.
+    fn count_descendants(&self) -> Vec<usize> {
+        let nb_nodes = self.dag.node_count();
+        let words = nb_nodes.div_ceil(64);
+
+        // The children come before their parents, unless a cycle makes it impossible
+        let order = match toposort(&self.dag, None) {
+            Ok(order) => order,
+            Err(_) => return self.count_descendants_one_by_one(),
+        };
+
+        let mut remaining_parents: Vec<usize> = self
+            .dag
+            .node_indices()
+            .map(|idx| self.dag.edges_directed(idx, Direction::Outgoing).count())
+            .collect();
+        let mut descendants: Vec<Option<Vec<u64>>> = vec![None; nb_nodes];
+        let mut counts = vec![0; nb_nodes];
+
+        for idx in order {
+            let mut set = vec![0u64; words];
+
+            for child in self.dag.neighbors_directed(idx, Direction::Incoming) {
+                set[child.index() / 64] |= 1 << (child.index() % 64);
+
+                if let Some(child_set) = &descendants[child.index()] {
+                    for (word, child_word) in set.iter_mut().zip(child_set) {
+                        *word |= child_word;
+                    }
+                }
+
+                remaining_parents[child.index()] -= 1;
+                if remaining_parents[child.index()] == 0 {
+                    descendants[child.index()] = None;
+                }
+            }
+
+            counts[idx.index()] = set.iter().map(|word| word.count_ones() as usize).sum();
+
+            if remaining_parents[idx.index()] > 0 {
+                descendants[idx.index()] = Some(set);
+            }
+        }
+
+        counts
+    }
+
+    // Counts the descendants of every event, by node index, by going through the descendants of
+    // each of them, which still works if the `prev_events` form a cycle.
+    fn count_descendants_one_by_one(&self) -> Vec<usize> {
+        self.dag
+            .node_indices()
+            .map(|idx| {
+                let mut descendants: HashSet<NodeIndex> = HashSet::new();
+                let mut stack: Vec<NodeIndex> = vec![idx];
+
+                while let Some(idx) = stack.pop() {
+                    for child in self.dag.neighbors_directed(idx, Direction::Incoming) {
+                        if descendants.insert(child) {
+                            stack.push(child);
+                        }
+                    }
+                }
+
+                descendants.len()
+            })
+            .collect()
+    }
+
+    // Computes the value of the metric `size_metric` for the event at `idx`.
+    fn size_of(&self, idx: NodeIndex, size_metric: SizeMetric) -> f64 {
+        let event = self.dag.node_weight(idx).unwrap();
+
+        match size_metric {
+            SizeMetric::Descendants => {
+                self.analyses
+                    .descendants
+                    .get_or_init(|| self.count_descendants())[idx.index()] as f64
+            }
+            SizeMetric::PrevEvents => event.get_prev_events().len() as f64,
+            SizeMetric::ContentSize => event.content_size() as f64,
+            SizeMetric::CarrierServers => match self.presence.get(&event.event_id) {
+                Some(presence) => presence.count_ones() as f64,
+                None => 1.0,
+            },
+            SizeMetric::SenderPowerLevel => match self.authority.get(&event.event_id) {
+                Some(authority) => authority.power_level.max(0) as f64,
+                None => 0.0,
+            },
+        }
+    }
+
+    // Converts the event at `idx` into a node of a vis.js data set. For a merged DAG, the label
+    // of the node includes the badges of the servers which have the event, and the node is
+    // highlighted if some servers don't have it, or outlined if their copies of the event differ.
+    // The events whose origin server is dead are greyed out.
+    fn to_data_set_node(&self, idx: NodeIndex, fields: &HashSet<Field>) -> DataSetNode {
+        let event = self.dag.node_weight(idx).unwrap();
+        let palette = self.display.theme.palette();
+        let mut node = event.to_data_set_node(
+            &self.server_name,
+            fields,
+            &self.display.id_shortener,
+            &self.display.label_format,
+            palette,
+        );
+        node.level = self.level_of(event.depth);
+
+        node.value = self
+            .display
+            .size_metric
+            .map(|size_metric| self.size_of(idx, size_metric));
+
+        if !self.servers.is_empty() {
+            let presence = self.presence.get(&event.event_id).cloned().unwrap_or(0);
+            let badges: String = (0..self.servers.len())
+                .map(|i| {
+                    if presence & (1 << i) != 0 {
+                        SERVER_BADGES[i % SERVER_BADGES.len()]
+                    } else {
+                        ABSENT_BADGE
+                    }
+                })
+                .collect();
+
+            node.label.push('\n');
+            node.label.push_str(&badges);
+
+            node.color = if presence.count_ones() as usize == self.servers.len() {
+                palette.everywhere_event.node_color()
+            } else {
+                palette.partial_event.node_color()
+            };
+        }
+
+        if let Some(latency) = self.latency_of(&event.event_id) {
+            if let Some(title) = &mut node.title {
+                title.push_str(&format!(
+                    "<br>Delivery latency: {}",
+                    format_latency(latency)
+                ));
+            }
+
+            if self.display.color_by_latency {
+                let bucket = LATENCY_THRESHOLDS
+                    .iter()
+                    .take_while(|&&threshold| latency >= threshold)
+                    .count();
+
+                node.color = palette.latency[bucket].node_color();
+            }
+        }
+
+        let arrivals = self.arrivals_of(&event.event_id);
+        if let Some(first) = arrivals.iter().map(|arrival| arrival.ts).min() {
+            if let Some(title) = &mut node.title {
+                let arrivals: Vec<String> = arrivals
+                    .iter()
+                    .map(|arrival| {
+                        format!(
+                            "{} +{}",
+                            escape_html(&arrival.server),
+                            format_latency(arrival.ts - first)
+                        )
+                    })
+                    .collect();
+
+                title.push_str(&format!("<br>Arrivals: {}", arrivals.join(", ")));
+            }
+
+            let skew =
+                clock_skew::event_skew(event.get_origin(), event.get_origin_server_ts(), arrivals);
+
+            if let Some(skew) = skew.filter(|skew| skew.abs() >= DEFAULT_CLOCK_SKEW_THRESHOLD) {
+                node.label.push_str(&format!(
+                    "\n⏱ Timestamp {} {} its arrival",
+                    format_latency(skew.abs()),
+                    if skew > 0 { "ahead of" } else { "behind" }
+                ));
+            }
+        }
+
+        if let Some((index, batch)) = self.provenance_of(&event.event_id) {
+            if let Some(title) = &mut node.title {
+                title.push_str(&format!(
+                    "<br>Fetched in batch {}: {}",
+                    index + 1,
+                    escape_html(&batch.description())
+                ));
+            }
+
+            if self.display.color_by_batch {
+                node.color = palette.batches[index % palette.batches.len()].node_color();
+            }
+        }
+
+        if let Some((_, colour)) = self
+            .display
+            .colour_rules
+            .iter()
+            .find(|(expression, _)| expression.matches(event))
+        {
+            node.color.background = colour.clone();
+        }
+
+        let markers: Vec<_> = self.read_markers.on_event(&event.event_id).collect();
+        if !markers.is_empty() {
+            if let Some(title) = &mut node.title {
+                for marker in &markers {
+                    title.push_str(&format!(
+                        "<br>Pointed by the {}",
+                        escape_html(&marker.description())
+                    ));
+                }
+            }
+
+            if self.display.show_read_markers {
+                let mut readers: Vec<&str> = markers
+                    .iter()
+                    .filter(|marker| marker.kind != MarkerKind::FullyRead)
+                    .map(|marker| marker.user_id.as_str())
+                    .collect();
+                readers.dedup(); // A user can have several receipts on the event, e.g. in threads
+                let fully_read: Vec<&str> = markers
+                    .iter()
+                    .filter(|marker| marker.kind == MarkerKind::FullyRead)
+                    .map(|marker| marker.user_id.as_str())
+                    .collect();
+
+                if !readers.is_empty() {
+                    node.label.push_str("\n👁 Read by ");
+                    node.label.push_str(&readers.join(", "));
+                }
+
+                if !fully_read.is_empty() {
+                    node.label.push_str("\n📌 Fully read by ");
+                    node.label.push_str(&fully_read.join(", "));
+                }
+            }
+        }
+
+        // Both events of a conflicting pair carry the same badge so that they can be linked
+        let conflict_badges: Vec<String> = self
+            .state_conflicts
+            .iter()
+            .enumerate()
+            .filter(|(_, conflict)| {
+                conflict.events.0 == event.event_id || conflict.events.1 == event.event_id
+            })
+            .map(|(i, _)| format!("⚔{}", i + 1))
+            .collect();
+
+        if !conflict_badges.is_empty() {
+            node.label.push_str("\nIn conflict: ");
+            node.label.push_str(&conflict_badges.join(" "));
+        }
+
+        if self.dead_servers.contains(event.get_origin()) {
+            node.label.push_str("\nOrigin server unreachable");
+            node.color = palette.unreachable_origin.node_color();
+        }
+
+        if let Some(outage) = &self.outage {
+            if outage.missed.contains(&event.event_id) {
+                node.label
+                    .push_str(&format!("\nNot received by {}", outage.server));
+                node.color = palette.unreceived.node_color();
+            } else if outage.created_offline.contains(&event.event_id) {
+                node.label
+                    .push_str(&format!("\nCreated by {} while offline", outage.server));
+            } else if outage.last_event == event.event_id {
+                node.label
+                    .push_str(&format!("\nLast event received by {}", outage.server));
+                node.border_width = Some(4);
+            }
+        }
+
+        if self.display.show_authority {
+            match self.authority.get(&event.event_id) {
+                Some(authority) => {
+                    node.label.push_str(&format!(
+                        "\n{}, power level {} (needs {})",
+                        authority.membership.as_deref().unwrap_or("never joined"),
+                        authority.power_level,
+                        authority.required_level
+                    ));
+
+                    if let Some(violation) = authority.violation() {
+                        node.label.push_str("\n⛔ ");
+                        node.label.push_str(&violation);
+                        node.color.border = palette.unauthorised_border.to_string();
+                        node.border_width = Some(4);
+                    }
+                }
+                None => node
+                    .label
+                    .push_str("\nAuthority unknown (no room creation)"),
+            }
+        }
+
+        // Shown regardless of the fields chosen, like the rejections
+        if let Some(mismatch) = event.origin_mismatch() {
+            node.label.push_str("\nOrigin mismatch: ");
+            node.label.push_str(&mismatch.description());
+            node.color.border = palette.mismatch_border.to_string();
+            node.border_width = Some(4);
+        }
+
+        // Looked up in the analyses, the version being checked once per change of the DAG
+        if let Some(problem) = self.version_problem(&event.event_id) {
+            node.label.push_str("\n⚠ Room version: ");
+            node.label.push_str(problem);
+            node.color.border = palette.mismatch_border.to_string();
+            node.border_width = Some(4);
+        }
+
+        if let Some(copies) = self.conflict_report.get(&event.event_id) {
+            node.label.push_str("\nDivergent copies: ");
+            node.label.push_str(&copies.fields.join(", "));
+            node.color.border = palette.divergent_border.to_string();
+            node.border_width = Some(4);
+        }
+
+        if self.highlighted.contains(&event.event_id) {
+            node.color.border = palette.highlight_border.to_string();
+            node.border_width = Some(5);
+        }
+
+        // The annotations come last so that their marker stands out over the other borders
+        if let Some(annotation) = self.annotations.get(&event.event_id) {
+            node.label.push_str("\n📌 ");
+            node.label
+                .push_str(&self.display.label_format.value(&annotation.note));
+            node.color.border = annotation.tag.colour().to_string();
+            node.border_width = Some(6);
+        }
+
+        node
+    }
+
+    fn to_data_set_edge(&self, (src, dst): (NodeIndex, NodeIndex)) -> Option<DataSetEdge> {
+        let from = self.dag.node_weight(src)?.event_id.clone();
+        let to = self.dag.node_weight(dst)?.event_id.clone();
+
+        Some(DataSetEdge {
+            id: edge_id(&from, &to, PREV_EVENT_EDGE),
+            from,
+            to,
+            label: None,
+            color: None,
+            dashes: false,
+        })
+    }
+
+    // Adds to `data_set` a ghost node for each event missing from the `prev_events` of the
+    // events accepted by `keep`, if they are shown, along with the edges to them. A ghost is
+    // placed just above the earliest event referencing it.
+    fn add_ghosts<F>(&self, data_set: &mut DataSet, keep: F)
+    where
+        F: Fn(NodeIndex) -> bool,
+    {
+        if !self.display.show_ghosts {
+            return;
+        }
+
+        let palette = self.display.theme.palette();
+        let mut ghosts: HashMap<&str, i64> = HashMap::new();
+
+        for idx in self.dag.node_indices().filter(|idx| keep(*idx)) {
+            let event = &self.dag[idx];
+
+            for prev_id in event.get_prev_events() {
+                if self.events_map.contains_key(prev_id) {
+                    continue;
+                }
+
+                let above = self.level_of(event.depth) - 1;
+                let level = ghosts.entry(prev_id).or_insert(above);
+                *level = (*level).min(above);
+
+                let ghost_id = format!("ghost_{}", prev_id);
+
+                data_set.edges.push(DataSetEdge {
+                    id: edge_id(&event.event_id, &ghost_id, "ghost"),
+                    from: event.event_id.clone(),
+                    to: ghost_id,
+                    label: None,
+                    color: Some(EdgeColor {
+                        color: palette.ghost_edge.to_string(),
+                    }),
+                    dashes: true,
+                });
+            }
+        }
+
+        for (id, level) in ghosts {
+            data_set.nodes.push(DataSetNode {
+                id: format!("ghost_{}", id),
+                label: format!("{}\n(missing)", self.display.id_shortener.shorten(id)),
+                title: Some(format!("{} was never received", id)),
+                font: None,
+                level,
+                color: palette.ghost.node_color(),
+                value: None,
+                border_width: Some(2),
+                shape_properties: Some(ShapeProperties {
+                    border_dashes: vec![5, 5],
+                }),
+            });
+        }
+    }
+
+    // Adds to `data_set` the DAG of the predecessor of the room, if it was stitched, with its
+    // latest events just above a node marking the upgrade, itself just above the earliest events
+    // of this room. The creation event of this room is linked to this node, which is linked to
+    // the latest events of the predecessor.
+    fn add_predecessor(&self, data_set: &mut DataSet, fields: &HashSet<Field>) {
+        let predecessor = match &self.predecessor {
+            Some(predecessor) => predecessor,
+            None => return,
+        };
+        let (min_depth, pred_max_depth) = match (self.depth_bounds(), predecessor.depth_bounds()) {
+            (Some((min_depth, _)), Some((_, pred_max_depth))) => (min_depth, pred_max_depth),
+            _ => return,
+        };
+
+        let palette = self.display.theme.palette();
+        let boundary_level = self.level_of(min_depth) - 1;
+        let offset = boundary_level - 1 - predecessor.level_of(pred_max_depth);
+        let boundary_id = format!("upgrade_{}", predecessor.room_id);
+
+        let mut pred_data_set = predecessor.create_data_set(fields);
+
+        for node in &mut pred_data_set.nodes {
+            node.level += offset;
+        }
+
+        data_set.nodes.extend(pred_data_set.nodes);
+        data_set.edges.extend(pred_data_set.edges);
+
+        let versions = match (
+            predecessor.incarnation().room_version,
+            self.incarnation().room_version,
+        ) {
+            (Some(old), Some(new)) => format!("\nVersion {} → {}", old, new),
+            _ => String::new(),
+        };
+
+        data_set.nodes.push(DataSetNode {
+            id: boundary_id.clone(),
+            label: format!(
+                "Room upgrade\n{} → {}{}",
+                predecessor.room_id, self.room_id, versions
+            ),
+            title: None,
+            font: None,
+            level: boundary_level,
+            color: palette.upgrade.node_color(),
+            value: None,
+            border_width: Some(3),
+            shape_properties: Some(ShapeProperties {
+                border_dashes: vec![10, 5],
+            }),
+        });
+
+        let boundary_edge = |from: &str, to: &str| DataSetEdge {
+            id: edge_id(from, to, "upgrade"),
+            from: from.to_string(),
+            to: to.to_string(),
+            label: None,
+            color: Some(EdgeColor {
+                color: palette.upgrade_edge.to_string(),
+            }),
+            dashes: true,
+        };
+
+        for node in self.dag.raw_nodes() {
+            if node.weight.get_type() == "m.room.create" {
+                data_set
+                    .edges
+                    .push(boundary_edge(&node.weight.event_id, &boundary_id));
+            }
+        }
+
+        for latest in &predecessor.latest_events {
+            data_set.edges.push(boundary_edge(&boundary_id, latest));
+        }
+    }
+
+    // Creates the edges representing the relations between the events of the DAG, if they are
+    // shown, for the pairs of events (relating event, related event) accepted by `keep`.
+    fn relation_edges<F>(&self, keep: F) -> Vec<DataSetEdge>
+    where
+        F: Fn(NodeIndex, NodeIndex) -> bool,
+    {
+        if !self.display.show_relations {
+            return Vec::new();
+        }
+
+        let palette = self.display.theme.palette();
+        let mut edges = Vec::new();
+
+        for src_idx in self.dag.node_indices() {
+            let event = &self.dag[src_idx];
+
+            for (rel_type, related_id) in event.get_relations() {
+                let dst_idx = match self.events_map.get(related_id) {
+                    Some(idx) => *idx,
+                    None => continue,
+                };
+
+                if !keep(src_idx, dst_idx) {
+                    continue;
+                }
+
+                let (label, color) = match rel_type {
+                    RelationType::Thread => ("thread", palette.thread),
+                    RelationType::Edit => ("edit", palette.edit),
+                    RelationType::Annotation => ("reaction", palette.annotation),
+                    RelationType::Reply => ("reply", palette.reply),
+                };
+                let from = event.event_id.clone();
+                let to = related_id.to_string();
+
+                edges.push(DataSetEdge {
+                    id: edge_id(&from, &to, label),
+                    from,
+                    to,
+                    label: Some(label.to_string()),
+                    color: Some(EdgeColor {
+                        color: color.to_string(),
+                    }),
+                    dashes: true,
+                });
+            }
+        }
+
+        edges
+    }
+}
+
+// Parses the events `json_events`, setting aside the events which can't be parsed rather than
+// failing. The events without an ID get the ID derived from their reference hash, which depends
+// on the version of the room, see `batch_room_version`: `room_version` is only called if needed.
+fn parse_events(
+    json_events: &Vec<JsonValue>,
+    room_version: impl Fn() -> Option<String>,
+) -> (Vec<Event>, Vec<UnparsedEvent>) {
+    let mut events = Vec::with_capacity(json_events.len());
+    let mut unparsed_events = Vec::new();
+    let mut version: Option<Option<String>> = None;
+
+    for json in json_events {
+        match serde_json::from_value::<Event>(json.clone()) {
+            Ok(mut ev) if ev.event_id.is_empty() => {
+                let version =
+                    version.get_or_insert_with(|| batch_room_version(json_events, &room_version));
+
+                match reference_hash::event_id(json, version.as_deref()) {
+                    Some(id) => {
+                        ev.event_id = id;
+                        events.push(ev);
+                    }
+                    None => unparsed_events.push(UnparsedEvent {
+                        json: json.clone(),
+                        error: "The event has no ID, and the version of its room doesn't \
+                            derive it from its reference hash"
+                            .to_string(),
+                    }),
+                }
+            }
+            Ok(ev) => events.push(ev),
+            Err(e) => unparsed_events.push(UnparsedEvent {
+                json: json.clone(),
+                error: format!("{}", e),
+            }),
+        }
+    }
+
+    (events, unparsed_events)
+}
+
+// Returns the version of the room of the events `json_events`: the one given by their creation
+// event if it is among them, otherwise the one returned by `room_version`.
+fn batch_room_version(
+    json_events: &[JsonValue],
+    room_version: impl Fn() -> Option<String>,
+) -> Option<String> {
+    json_events
+        .iter()
+        .find(|json| json["type"] == "m.room.create")
+        .map(|create| room_version_of(&create["content"]))
+        .or_else(room_version)
+}
+
+// Returns the nodes of `dag` reachable from the nodes `from_indices` along the direction of its
+// edges, and the edges to add along with them: those between the new nodes, and those crossing
+// the boundary of the new region in either direction. The latter go to the `from` nodes, but
+// also to the other nodes already present, e.g. an event displayed as an orphan before its
+// missing `prev_events` came in the new region, so that the expansion doesn't leave disconnected
+// components.
+fn new_nodes_edges(
+    dag: &Graph<Event, ()>,
+    from_indices: HashSet<NodeIndex>,
+) -> (HashSet<NodeIndex>, HashSet<(NodeIndex, NodeIndex)>) {
+    let mut node_indices: HashSet<NodeIndex> = HashSet::from_iter(from_indices.iter().copied());
+
+    for &from_idx in from_indices.iter() {
+        let mut bfs = Bfs::new(&dag, from_idx);
+
+        while let Some(idx) = bfs.next(&dag) {
+            node_indices.insert(idx);
+        }
+    }
+
+    let new_node_indices: HashSet<NodeIndex> =
+        node_indices.difference(&from_indices).copied().collect();
+
+    let mut new_edges: HashSet<(NodeIndex, NodeIndex)> = HashSet::new();
+
+    for &idx in &new_node_indices {
+        let edges = dag
+            .edges_directed(idx, Direction::Incoming)
+            .chain(dag.edges_directed(idx, Direction::Outgoing));
+
+        for e in edges {
+            new_edges.insert((e.source(), e.target()));
+        }
+    }
+
+    (new_node_indices, new_edges)
+}
+
+/// Formats the delivery latency `latency` in ms (see `RoomEvents::latency_of`) with the most
+/// readable unit.
+pub fn format_latency(latency: i64) -> String {
+    if latency.abs() < 1_000 {
+        format!("{} ms", latency)
+    } else if latency.abs() < 60_000 {
+        format!("{:.1} s", latency as f64 / 1_000.0)
+    } else {
+        format!("{:.1} min", latency as f64 / 60_000.0)
+    }
+}
+
+// Describes `ev` if it is a moderation event, i.e. a ban, a kick, or a change of the state events
+// controlling the access to the room.
+fn moderation_description(ev: &Event) -> Option<String> {
+    if ev.get_type() == "m.room.member" {
+        let target = ev.get_state_key().unwrap_or_default();
+
+        return match ev.get_content()["membership"].as_str() {
+            Some("ban") => Some(format!("Ban of {}", target)),
+            Some("leave") if target != ev.get_sender() => Some(format!("Kick of {}", target)),
+            _ => None,
+        };
+    }
+
+    MODERATION_EVENTS
+        .iter()
+        .find(|(etype, _)| *etype == ev.get_type())
+        .map(|(_, description)| description.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const ROOM_ID: &str = "!room:example.org";
+
+    fn id(i: usize) -> String {
+        format!("${}:example.org", i)
+    }
+
+    // The event `i`, whose `prev_events` are the events `prev`. The depth of each event is its
+    // number, so that the events are numbered in an order of the DAG.
+    fn event(i: usize, prev: &[usize]) -> JsonValue {
+        let prev_events: Vec<String> = prev.iter().map(|p| id(*p)).collect();
+
+        json!({
+            "room_id": ROOM_ID,
+            "sender": "@alice:example.org",
+            "origin": "example.org",
+            "origin_server_ts": 1_500_000_000_000i64 + i as i64,
+            "type": "m.room.message",
+            "content": { "msgtype": "m.text", "body": format!("Message {}", i) },
+            "prev_events": prev_events,
+            "depth": i,
+            "auth_events": [],
+            "event_id": id(i),
+            "hashes": { "sha256": "" },
+            "signatures": {},
+        })
+    }
+
+    // The event `i` setting the state `(etype, state_key)`.
+    fn state_event(i: usize, prev: &[usize], etype: &str, state_key: &str) -> JsonValue {
+        let mut ev = event(i, prev);
+
+        ev["type"] = json!(etype);
+        ev["state_key"] = json!(state_key);
+        ev["content"] = json!({ "name": format!("Name {}", i) });
+
+        ev
+    }
+
+    fn dag_of(events: Vec<JsonValue>) -> RoomEvents {
+        RoomEvents::from_events(ROOM_ID, "example.org", &events)
+    }
+
+    // Gives `ev` the depth `depth`, or none at all
+    fn with_depth(mut ev: JsonValue, depth: Option<i64>) -> JsonValue {
+        match depth {
+            Some(depth) => ev["depth"] = json!(depth),
+            None => {
+                ev.as_object_mut().unwrap().remove("depth");
+            }
+        }
+
+        ev
+    }
+
+    fn depth_of(dag: &RoomEvents, i: usize) -> i64 {
+        dag.get_event(&id(i)).unwrap().depth
+    }
+
+    fn idx(dag: &RoomEvents, i: usize) -> NodeIndex {
+        dag.events_map[&id(i)]
+    }
+
+    // The conflicting states with the IDs of their events, in a deterministic order
+    fn conflicts(dag: &RoomEvents) -> Vec<(String, String, String, String)> {
+        let mut conflicts: Vec<_> = dag
+            .state_conflicts()
+            .iter()
+            .map(|c| {
+                let (a, b) = c.events.clone();
+                let (a, b) = if a < b { (a, b) } else { (b, a) };
+
+                (c.etype.clone(), c.state_key.clone(), a, b)
+            })
+            .collect();
+        conflicts.sort();
+
+        conflicts
+    }
+
+    #[test]
+    fn finds_the_concurrent_state_events_of_a_fork() {
+        // `$2` and `$3` both set the name of the room on each side of a fork from `$1`, which
+        // `$4` merges
+        let dag = dag_of(vec![
+            event(1, &[]),
+            state_event(2, &[1], "m.room.name", ""),
+            state_event(3, &[1], "m.room.name", ""),
+            event(4, &[2, 3]),
+        ]);
+
+        assert_eq!(
+            conflicts(&dag),
+            vec![("m.room.name".to_string(), String::new(), id(2), id(3))]
+        );
+    }
+
+    #[test]
+    fn ignores_the_concurrent_state_events_of_different_states() {
+        let dag = dag_of(vec![
+            event(1, &[]),
+            state_event(2, &[1], "m.room.name", ""),
+            state_event(3, &[1], "m.room.topic", ""),
+            state_event(4, &[1], "m.room.member", "@alice:example.org"),
+            state_event(5, &[1], "m.room.member", "@bob:example.org"),
+        ]);
+
+        assert!(conflicts(&dag).is_empty());
+    }
+
+    #[test]
+    fn ignores_the_state_events_ordered_by_the_dag() {
+        // `$4` has `$2` as ancestor through `$3`, and `$5` has both of them
+        let mut dag = dag_of(vec![
+            event(1, &[]),
+            state_event(2, &[1], "m.room.name", ""),
+            event(3, &[2]),
+            state_event(4, &[3], "m.room.name", ""),
+            state_event(5, &[4], "m.room.name", ""),
+        ]);
+
+        assert!(conflicts(&dag).is_empty());
+
+        // An event setting the name on another branch from `$1` conflicts with each of them
+        dag.add_events(vec![state_event(6, &[1], "m.room.name", "")]);
+
+        assert_eq!(
+            conflicts(&dag),
+            vec![
+                ("m.room.name".to_string(), String::new(), id(2), id(6)),
+                ("m.room.name".to_string(), String::new(), id(4), id(6)),
+                ("m.room.name".to_string(), String::new(), id(5), id(6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn previews_what_adding_the_events_does() {
+        let mut dag = dag_of(vec![event(1, &[]), event(2, &[1]), event(3, &[2])]);
+
+        // `$6` was delivered before, but couldn't be parsed, so it is still remembered by the
+        // reconciler without being in the DAG
+        dag.add_events(vec![json!({ "event_id": id(6), "type": 6 })]);
+
+        // `$2` and `$3` are already in the DAG, `$4` is delivered twice and `$6` again
+        let events = vec![
+            event(2, &[1]),
+            event(4, &[3]),
+            event(5, &[4]),
+            event(4, &[3]),
+            event(3, &[2]),
+            event(6, &[5]),
+        ];
+
+        let preview = dag.dry_run_add(&events);
+        let edge_count = dag.dag.edge_count();
+        let latest_events = dag.latest_events.clone();
+
+        let outcome = dag.add_events(events);
+
+        assert_eq!(preview.new_nodes, outcome.added);
+        assert_eq!(preview.new_nodes, 2);
+        assert_eq!(preview.new_edges, dag.dag.edge_count() - edge_count);
+        assert_eq!(preview.removed_latest_events, latest_events);
+        assert_eq!(preview.added_latest_events, dag.latest_events);
+        assert!(preview.added_earliest_events.is_empty());
+        assert!(preview.removed_earliest_events.is_empty());
+    }
+
+    #[test]
+    fn counts_the_events_added_skipped_and_unparsed() {
+        let mut dag = dag_of(vec![event(1, &[])]);
+
+        let outcome = dag.add_events(vec![
+            event(1, &[]),
+            event(2, &[1]),
+            event(2, &[1]),
+            json!({ "event_id": id(3), "type": 3 }),
+        ]);
+
+        assert_eq!(
+            outcome,
+            AddOutcome {
+                added: 1,
+                skipped: 2,
+                unparsed: 1,
+            }
+        );
+        assert_eq!(dag.event_count(), 2);
+    }
+
+    #[test]
+    fn rebuilds_the_indices_like_the_immediate_additions() {
+        let mut immediate = dag_of(Vec::new());
+        immediate.add_events(vec![event(3, &[2]), event(4, &[3])]);
+        immediate.add_events(vec![event(1, &[]), event(2, &[1])]);
+
+        let mut deferred = dag_of(Vec::new());
+        deferred.add_events_deferred(vec![event(3, &[2]), event(4, &[3])]);
+        deferred.add_events_deferred(vec![event(1, &[]), event(2, &[1])]);
+        deferred.rebuild_indices();
+
+        assert_eq!(deferred.earliest_events, immediate.earliest_events);
+        assert_eq!(deferred.latest_events, immediate.latest_events);
+        assert_eq!(deferred.dag.edge_count(), immediate.dag.edge_count());
+        assert!(deferred.orphan_events.is_empty());
+        assert_eq!((deferred.min_depth, deferred.max_depth), (1, 4));
+
+        for i in 2..=4 {
+            assert_eq!(deferred.parents_of(&id(i)), vec![id(i - 1)]);
+        }
+    }
+
+    #[test]
+    fn reports_the_missing_prev_events() {
+        // `$2` is referenced by `$3` and `$4`, `$9` only by `$4`
+        let dag = dag_of(vec![event(1, &[]), event(3, &[1, 2]), event(4, &[2, 9])]);
+
+        assert_eq!(dag.orphan_events.len(), 2);
+        assert_eq!(dag.earliest_events, vec![id(1), id(4)]);
+
+        let report = dag.health_report();
+        let missing: Vec<(&str, usize)> = report
+            .missing_events
+            .iter()
+            .map(|missing| (missing.id.as_str(), missing.count))
+            .collect();
+
+        assert_eq!(missing, vec![(id(2).as_str(), 2), (id(9).as_str(), 1)]);
+
+        let mut referenced_by = report.missing_events[0].referenced_by.clone();
+        referenced_by.sort();
+        assert_eq!(referenced_by, vec![id(3), id(4)]);
+    }
+
+    #[test]
+    fn targets_the_deepest_gaps_first() {
+        let dag = dag_of(vec![
+            event(1, &[]),
+            event(3, &[1, 2]),
+            event(4, &[2, 9]),
+            event(5, &[8]),
+        ]);
+
+        assert_eq!(dag.backfill_targets(1), vec![id(8)]);
+        assert_eq!(dag.backfill_targets(2), vec![id(8), id(2)]);
+        // `$2` is only targeted once although two orphans miss it
+        assert_eq!(dag.backfill_targets(10), vec![id(8), id(2), id(9)]);
+        assert!(dag.backfill_targets(0).is_empty());
+    }
+
+    #[test]
+    fn reports_the_cycles_of_prev_events() {
+        let dag = dag_of(vec![
+            event(1, &[3]),
+            event(2, &[1]),
+            event(3, &[2]),
+            event(4, &[3]),
+        ]);
+
+        let mut cycle = dag.cycle().unwrap().to_vec();
+        cycle.sort();
+        assert_eq!(cycle, vec![id(1), id(2), id(3)]);
+
+        // Without depths, the levels can't be derived from a cycle
+        let dag = dag_of(vec![
+            with_depth(event(1, &[2]), None),
+            with_depth(event(2, &[1]), None),
+        ]);
+
+        assert!(dag.cycle().is_some());
+        assert!(!dag.has_derived_depths());
+        assert!(dag_of(vec![event(1, &[]), event(2, &[1])])
+            .cycle()
+            .is_none());
+    }
+
+    #[test]
+    fn derives_the_missing_depths_from_the_prev_events() {
+        let dag = dag_of(vec![
+            with_depth(event(1, &[]), None),
+            with_depth(event(2, &[1]), None),
+            with_depth(event(3, &[2]), None),
+            with_depth(event(4, &[1, 3]), None),
+        ]);
+
+        assert!(dag.has_derived_depths());
+        assert_eq!(
+            (1..=4).map(|i| depth_of(&dag, i)).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+
+        // The same depth everywhere is as useless as none
+        let dag = dag_of(vec![
+            with_depth(event(1, &[]), Some(7)),
+            with_depth(event(2, &[1]), Some(7)),
+        ]);
+
+        assert!(dag.has_derived_depths());
+        assert_eq!((depth_of(&dag, 1), depth_of(&dag, 2)), (1, 2));
+    }
+
+    #[test]
+    fn derives_the_depths_of_the_added_events_and_of_their_descendants() {
+        let mut dag = dag_of(vec![
+            with_depth(event(2, &[1]), None),
+            with_depth(event(3, &[2]), None),
+            with_depth(event(5, &[]), None),
+        ]);
+
+        assert_eq!(
+            (depth_of(&dag, 2), depth_of(&dag, 3), depth_of(&dag, 5)),
+            (1, 2, 1)
+        );
+
+        // The backfill of `$1` pushes its descendants a level down, but not `$5`
+        dag.add_events(vec![with_depth(event(1, &[]), None)]);
+
+        assert_eq!(
+            (1..=3).map(|i| depth_of(&dag, i)).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(depth_of(&dag, 5), 1);
+        assert_eq!(dag.depth_map[&1].len(), 2);
+        assert_eq!(dag.depth_map[&3], vec![idx(&dag, 3)]);
+        assert_eq!((dag.min_depth, dag.max_depth), (1, 3));
+
+        // The depths go back up once `$1` is removed
+        dag.remove_events(&[id(1)]);
+
+        assert_eq!((depth_of(&dag, 2), depth_of(&dag, 3)), (1, 2));
+        assert!(!dag.depth_map.contains_key(&3));
+        assert_eq!(dag.max_depth, 2);
+
+        // Events with depths don't make the DAG usable while some still have none
+        dag.add_events(vec![event(4, &[3])]);
+
+        assert!(dag.has_derived_depths());
+        assert_eq!(depth_of(&dag, 4), 3);
+    }
+
+    #[test]
+    fn compresses_the_gaps_between_the_depths() {
+        let mut dag = dag_of(vec![event(1, &[]), event(5, &[1]), event(10, &[5])]);
+
+        assert_eq!(dag.level_of(5), 5);
+        assert_eq!(dag.levels_to_depths(1, 10), (1, 10));
+
+        dag.set_compress_depths(true);
+
+        assert_eq!(
+            [1, 5, 7, 10]
+                .iter()
+                .map(|d| dag.level_of(*d))
+                .collect::<Vec<_>>(),
+            vec![1, 2, 2, 3]
+        );
+        assert_eq!(dag.levels_to_depths(1, 3), (1, 10));
+        assert_eq!(dag.levels_to_depths(2, 2), (5, 5));
+        // The levels beyond those of the events map to the depths just beyond them
+        assert_eq!(dag.levels_to_depths(0, 4), (0, 11));
+    }
+
+    #[test]
+    fn finds_the_path_between_two_events() {
+        let dag = dag_of(vec![
+            event(1, &[]),
+            event(2, &[1]),
+            event(3, &[2]),
+            event(4, &[1]),
+        ]);
+
+        assert_eq!(
+            dag.path_between(&id(3), &id(1)),
+            Some(vec![id(3), id(2), id(1)])
+        );
+        assert_eq!(
+            dag.path_between(&id(1), &id(3)),
+            Some(vec![id(3), id(2), id(1)])
+        );
+        assert_eq!(dag.path_between(&id(3), &id(3)), Some(vec![id(3)]));
+        // `$3` and `$4` are concurrent
+        assert_eq!(dag.path_between(&id(3), &id(4)), None);
+        assert_eq!(dag.path_between(&id(3), &id(9)), None);
+    }
+
+    #[test]
+    fn adds_the_boundary_edges_of_the_new_nodes_in_both_directions() {
+        // `$4`, already drawn, has `$1` in its `prev_events`, so the edge from `$4` to `$1`
+        // comes with `$1` although `$4` isn't reached from `$3`
+        let dag = dag_of(vec![
+            event(1, &[]),
+            event(2, &[1]),
+            event(3, &[2]),
+            event(4, &[1]),
+        ]);
+
+        let from: HashSet<NodeIndex> = [idx(&dag, 3)].iter().cloned().collect();
+        let (nodes, edges) = new_nodes_edges(&dag.dag, from);
+
+        let expected_nodes: HashSet<NodeIndex> =
+            [idx(&dag, 1), idx(&dag, 2)].iter().cloned().collect();
+        let expected_edges: HashSet<(NodeIndex, NodeIndex)> = [
+            (idx(&dag, 3), idx(&dag, 2)),
+            (idx(&dag, 2), idx(&dag, 1)),
+            (idx(&dag, 4), idx(&dag, 1)),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(nodes, expected_nodes);
+        assert_eq!(edges, expected_edges);
+    }
+
+    #[test]
+    fn reduces_a_diamond_transitively() {
+        // `$4` merges the fork of `$2` and `$3` from `$1`, and also has `$1` as prev event,
+        // which is implied by the two branches
+        let mut dag = dag_of(vec![
+            event(1, &[]),
+            event(2, &[1]),
+            event(3, &[1]),
+            event(4, &[2, 3, 1]),
+        ]);
+
+        let redundant: Vec<(NodeIndex, NodeIndex)> =
+            dag.redundant_edges().iter().cloned().collect();
+        assert_eq!(redundant, vec![(idx(&dag, 4), idx(&dag, 1))]);
+        assert_eq!(dag.redundant_edge_count(), 1);
+
+        let edge_count = |dag: &RoomEvents| {
+            dag.create_data_set(&[Field::EventID].iter().cloned().collect())
+                .edges
+                .len()
+        };
+
+        assert_eq!(edge_count(&dag), 5);
+        dag.set_transitive_reduction(true);
+        assert_eq!(edge_count(&dag), 4);
+
+        // The diamond itself has no redundant edge
+        let dag = dag_of(vec![
+            event(1, &[]),
+            event(2, &[1]),
+            event(3, &[1]),
+            event(4, &[2, 3]),
+        ]);
+        assert_eq!(dag.redundant_edge_count(), 0);
+    }
+
+    #[test]
+    fn measures_the_forks_and_merges() {
+        let dag = dag_of(vec![
+            event(1, &[]),
+            with_depth(event(2, &[1]), Some(2)),
+            with_depth(event(3, &[1]), Some(2)),
+            with_depth(event(4, &[2, 3]), Some(3)),
+            with_depth(event(5, &[4]), Some(4)),
+            with_depth(event(6, &[4]), Some(4)),
+        ]);
+
+        let metrics = dag.metrics();
+
+        assert_eq!((metrics.fork_points, metrics.merge_events), (2, 1));
+        assert_eq!(metrics.max_concurrent_branches, 2);
+        // The ties go to the smallest depth
+        assert_eq!(metrics.widest_depth, Some(2));
+
+        // The fork from `$4` hasn't merged yet, but is shorter than the one merged at `$4`
+        let fork = metrics.longest_fork.unwrap();
+        assert_eq!(fork.fork_point, id(1));
+        assert_eq!(fork.merge_point, Some(id(4)));
+        assert_eq!(fork.length, 2);
+    }
+
+    #[test]
+    fn hides_the_branches_until_they_merge() {
+        // The branch of `$2` merges with the one of `$4` at `$5`
+        let mut dag = dag_of(vec![
+            event(1, &[]),
+            event(2, &[1]),
+            event(3, &[2]),
+            event(4, &[1]),
+            event(5, &[3, 4]),
+            event(6, &[5]),
+        ]);
+
+        assert_eq!(dag.hide_branch(&id(2)), 2);
+        assert_eq!(dag.hidden_count(), 2);
+
+        // Hiding the branch of `$1` takes over the item of `$2`
+        assert_eq!(dag.hide_branch(&id(1)), 6);
+        assert_eq!(dag.hidden_items().len(), 1);
+        assert_eq!(dag.hidden_items()[0].root, id(1));
+
+        dag.restore_hidden(&id(1));
+        assert_eq!(dag.hidden_count(), 0);
+        assert_eq!(dag.hide_branch(&id(9)), 0);
+    }
+
+    #[test]
+    fn reports_the_depths_inconsistent_with_the_prev_events() {
+        let dag = dag_of(vec![
+            event(1, &[]),
+            event(2, &[1]),
+            with_depth(event(3, &[2]), Some(2)),
+            with_depth(event(4, &[2]), Some(12)),
+            event(5, &[2]),
+        ]);
+
+        let anomalies: Vec<(String, String, DepthAnomalyKind)> = dag
+            .depth_anomalies(5)
+            .into_iter()
+            .map(|anomaly| (anomaly.id, anomaly.parent, anomaly.kind))
+            .collect();
+
+        assert_eq!(
+            anomalies,
+            vec![
+                (id(3), id(2), DepthAnomalyKind::NotDeeper),
+                (id(4), id(2), DepthAnomalyKind::Jump),
+            ]
+        );
+        assert_eq!(dag.depth_anomalies(10).len(), 1);
+    }
+}
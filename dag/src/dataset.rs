@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use serde_derive::Serialize;
+
+use super::layout_cache;
+
+/// The data set containing events which will be added to the vis.js network.
+#[derive(Debug, Default, Serialize)]
+pub struct DataSet {
+    pub nodes: Vec<DataSetNode>,
+    pub edges: Vec<DataSetEdge>,
+}
+
+impl DataSet {
+    pub fn new() -> DataSet {
+        DataSet {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Returns the key under which the layout of the nodes of the data set is cached, see
+    /// `layout_cache::node_set_key`. It must be computed before adding a prefix to them.
+    pub fn layout_key(&self) -> String {
+        layout_cache::node_set_key(self.nodes.iter().map(|n| n.id.as_str()))
+    }
+
+    /// Adds a prefix `pref` to the events in the `DataSet` so that they can be associated with
+    /// a certain view identified in `pref`.
+    pub fn add_prefix(&mut self, pref: &str) {
+        for n in &mut self.nodes {
+            n.id.insert_str(0, pref);
+        }
+
+        for e in &mut self.edges {
+            e.id.insert_str(0, pref);
+            e.from.insert_str(0, pref);
+            e.to.insert_str(0, pref);
+        }
+    }
+
+    /// Leaves out the edges whose ID was already given to an earlier edge of the data set, since
+    /// vis.js refuses to add a data set with duplicate IDs.
+    pub fn dedup_edges(&mut self) {
+        let mut registry = EdgeRegistry::new();
+
+        self.edges.retain(|edge| registry.admit(edge));
+    }
+}
+
+/// Returns the ID of the edge of kind `kind` (e.g. `prev_event` or the type of a relation) from
+/// the node `from` to the node `to`.
+///
+/// The ID is made of the three of them, so that an edge always gets the same ID when it is added
+/// again. The lengths of the ends come first, so that the IDs of different edges can't collide
+/// like the bare concatenations of their ends did (`a` to `bc` and `ab` to `c`), even once
+/// prefixed with their view.
+pub fn edge_id(from: &str, to: &str, kind: &str) -> String {
+    format!("edge_{}_{}_{}{}{}", from.len(), to.len(), from, to, kind)
+}
+
+/// The IDs of the edges already given to a data set, so that the same edge is never added twice
+/// to it, e.g. when an incremental update reaches an edge from both of its ends.
+///
+/// It only lasts as long as the data set: vis.js refuses duplicate IDs within the edges it is
+/// given at once, but the incremental data sets are applied with `edges.update`, which replaces
+/// an edge already in the network by the one with the same ID. Since an edge always gets the same
+/// ID, see `edge_id`, adding it again across data sets is harmless, whereas a registry kept with
+/// the DAG would have to forget the edges of the removed events and be emptied whenever the
+/// network is built again.
+#[derive(Debug, Default)]
+pub struct EdgeRegistry {
+    ids: HashSet<String>,
+}
+
+impl EdgeRegistry {
+    pub fn new() -> Self {
+        EdgeRegistry::default()
+    }
+
+    /// Registers the edge `edge`, and returns whether it wasn't registered yet.
+    pub fn admit(&mut self, edge: &DataSetEdge) -> bool {
+        if self.ids.contains(&edge.id) {
+            return false;
+        }
+
+        self.ids.insert(edge.id.clone())
+    }
+}
+
+/// A node of the vis.js data set.
+#[derive(Debug, Serialize)]
+pub struct DataSetNode {
+    pub id: String,
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>, // The tooltip of the node
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<NodeFont>,
+    pub level: i64,
+    pub color: NodeColor,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>, // Used by vis.js to scale the node
+    #[serde(rename = "borderWidth", skip_serializing_if = "Option::is_none")]
+    pub border_width: Option<u32>,
+    #[serde(rename = "shapeProperties", skip_serializing_if = "Option::is_none")]
+    pub shape_properties: Option<ShapeProperties>,
+}
+
+/// The font of the label of the data set's node.
+#[derive(Debug, Serialize)]
+pub struct NodeFont {
+    pub multi: &'static str, // The markup of the label, if any
+}
+
+impl NodeFont {
+    /// The font of the labels with the HTML markup of vis.js, e.g. `<code>` for monospace.
+    pub fn html() -> NodeFont {
+        NodeFont { multi: "html" }
+    }
+}
+
+/// The properties of the shape of the data set's node.
+#[derive(Debug, Serialize)]
+pub struct ShapeProperties {
+    #[serde(rename = "borderDashes")]
+    pub border_dashes: Vec<u32>, // Lengths of the dashes and of the gaps of the border
+}
+
+/// The colors of the data set's node.
+#[derive(Debug, Serialize)]
+pub struct NodeColor {
+    pub border: String,
+    pub background: String,
+}
+
+/// An edge of the vis.js data set.
+#[derive(Debug, Serialize)]
+pub struct DataSetEdge {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<EdgeColor>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dashes: bool,
+}
+
+/// The color of the data set's edge.
+#[derive(Debug, Serialize)]
+pub struct EdgeColor {
+    pub color: String,
+}
@@ -0,0 +1,42 @@
+use serde_derive::Serialize;
+
+/// The largest difference between the depth of an event and the deepest of its `prev_events`
+/// which isn't reported by default. Honest servers give a depth of exactly one more, but the
+/// events received over federation after a gap may legitimately be a bit deeper.
+pub const DEFAULT_DEPTH_JUMP_THRESHOLD: i64 = 100;
+
+/// An event whose `depth` is inconsistent with the ones of its `prev_events`, which is a symptom
+/// of the attacks manipulating the depths to get events sorted before or after the others.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct DepthAnomaly {
+    pub id: String,
+    pub depth: i64,        // The `depth` of the event, as given by the source
+    pub parent: String,    // The deepest of its `prev_events` in the DAG
+    pub parent_depth: i64, // The `depth` of this parent
+    pub kind: DepthAnomalyKind,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum DepthAnomalyKind {
+    NotDeeper, // The depth of the event isn't greater than the one of one of its `prev_events`
+    Jump, // The depth of the event is greater than the ones of its `prev_events` by more than the threshold
+}
+
+impl DepthAnomaly {
+    /// Describes the anomaly in a few words.
+    pub fn description(&self) -> String {
+        match self.kind {
+            DepthAnomalyKind::NotDeeper => format!(
+                "depth {} isn't greater than the depth {} of its prev_event {}",
+                self.depth, self.parent_depth, self.parent
+            ),
+            DepthAnomalyKind::Jump => format!(
+                "depth {} jumps {} above the depth {} of its prev_event {}",
+                self.depth,
+                self.depth - self.parent_depth,
+                self.parent_depth,
+                self.parent
+            ),
+        }
+    }
+}
@@ -0,0 +1,612 @@
+use std::collections::HashSet;
+use std::mem;
+use std::sync::{Arc, OnceLock};
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+
+use super::dataset::{DataSetNode, NodeFont, ShapeProperties};
+use super::id_shortener::IdShortener;
+use super::interner::{self, StringInterner};
+use super::label_format::LabelFormat;
+use super::theme::Palette;
+
+/// The internal representation of an event in the DAG.
+///
+/// The parsing is tolerant so that events from newer or unstable versions of the spec can still
+/// be displayed: missing fields take a default value, and the top-level fields which aren't known
+/// here are kept in `unknown_fields` (the unknown fields of the content are kept in `content`).
+#[derive(Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Event {
+    room_id: Arc<str>,     // Room identifier
+    sender: Arc<str>,      // The ID of the user who has sent this event
+    origin: Arc<str>,      // The `server_name` of the homeserver which created this event
+    origin_server_ts: i64, // Timestamp in milliseconds on origin homeserver when this event was created
+    #[serde(rename = "type")]
+    etype: Arc<str>, // Event type
+    state_key: Option<String>, // Indicate whether this event is a state event
+    content: JsonValue,    // The content of the event
+    prev_events: Vec<JsonValue>, // Event IDs for the most recent events in the room that the homeserver was aware of when it made this event
+    #[serde(rename = "depth", skip_serializing_if = "Option::is_none")]
+    raw_depth: Option<i64>, // The maximum depth of the `prev_events`, plus one, if the source gave it
+    auth_events: Vec<JsonValue>, // Event IDs and reference hashes for the authorization events that would allow this event to be in the room
+    redacts: Option<String>,     // For redaction events, the ID of the event being redacted
+    unsigned: Option<JsonValue>, // Additional data added by the origin server but not covered by the `signatures`
+    pub event_id: String,        // The event ID
+    hashes: JsonValue, // Content hashes of the PDU, following the algorithm specified in `Signing Events`
+    signatures: JsonValue, // Signatures for the PDU, following the algorithm specified in `Signing Events`
+    #[serde(flatten)]
+    unknown_fields: Map<String, JsonValue>, // Fields not defined above, e.g. unstable ones from MSCs
+
+    #[serde(skip)]
+    rejection: Option<String>, // The reason why the HS has rejected this event, if it has
+    #[serde(skip)]
+    soft_failed: bool, // Whether this event has soft-failed on the HS
+    #[serde(skip)]
+    pub depth: i64, // The depth the event is laid out at, which is derived by the DAG if `raw_depth` is unusable
+    #[serde(skip)]
+    json: OnceLock<JsonValue>, // The event as JSON, built the first time a JSON pointer is looked up in it
+}
+
+/// Defines the fields of the events which will be included in the labels of the DAG's nodes.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Field {
+    Sender,
+    Origin,
+    OriginServerTS,
+    Type,
+    StateKey,
+    PrevEvents,
+    Depth,
+    Redacts,
+    EventID,
+    UnknownFields,
+    Pointer(String), // Any value of the event, designated by a JSON pointer like `/content/body`
+}
+
+/// The kinds of relations between events, expressed by the `m.relates_to` field of their
+/// content.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RelationType {
+    Thread,     // The event is part of the thread of the related event
+    Edit,       // The event replaces the content of the related event
+    Annotation, // The event is a reaction to the related event
+    Reply,      // The event is a reply to the related event
+}
+
+/// The servers named by an event which disagree with its `origin`, which often betrays bridged
+/// or forged traffic.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OriginMismatch {
+    pub sender_server: Option<String>, // The server of the sender, if it isn't the origin
+    pub signature_servers: Vec<String>, // The servers which signed the event, if the origin isn't one of them
+}
+
+impl OriginMismatch {
+    /// Describes the mismatch in a few words.
+    pub fn description(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(server) = &self.sender_server {
+            parts.push(format!("sender on {}", server));
+        }
+        if !self.signature_servers.is_empty() {
+            parts.push(format!("signed by {}", self.signature_servers.join(", ")));
+        }
+
+        parts.join(", ")
+    }
+}
+
+impl Event {
+    /// This function is needed because the content of a the `prev_events` field can change
+    /// across the versions of rooms: the room versions 1 and 2 give pairs of an ID and of the
+    /// reference hashes of the event (`[["$id:example.org", {"sha256": "..."}]]`), the later ones
+    /// only the IDs (`["$id"]`). The malformed entries are ignored.
+    pub fn get_prev_events(&self) -> Vec<&str> {
+        self.prev_events.iter().filter_map(prev_event_id).collect()
+    }
+
+    /// Returns the number of events in the `prev_events` of this event, without collecting their
+    /// IDs.
+    pub fn prev_events_count(&self) -> usize {
+        self.prev_events
+            .iter()
+            .filter(|prev_ev| prev_event_id(prev_ev).is_some())
+            .count()
+    }
+
+    /// Returns the content of this event.
+    pub fn get_content(&self) -> &JsonValue {
+        &self.content
+    }
+
+    /// Returns the ID of the user who has sent this event.
+    pub fn get_sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// Returns the name of the server which created this event.
+    pub fn get_origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Returns the name of the server of the sender of this event, i.e. the part of its user ID
+    /// after the first colon.
+    pub fn get_sender_server(&self) -> Option<&str> {
+        self.sender.split_once(':').map(|(_, server)| server)
+    }
+
+    /// Returns how the server of the sender and the servers which signed this event disagree with
+    /// its `origin`, or `None` if they don't. The events without an `origin`, and those whose
+    /// signatures were stripped (e.g. by the CS API), can't be checked that way.
+    pub fn origin_mismatch(&self) -> Option<OriginMismatch> {
+        if self.origin.is_empty() {
+            return None;
+        }
+
+        let sender_server = self
+            .get_sender_server()
+            .filter(|server| *server != &*self.origin)
+            .map(|server| server.to_string());
+        let mut signature_servers: Vec<String> = self
+            .signatures
+            .as_object()
+            .map(|signatures| signatures.keys().cloned().collect())
+            .unwrap_or_default();
+
+        if signature_servers
+            .iter()
+            .any(|server| **server == *self.origin)
+        {
+            signature_servers.clear();
+        }
+        signature_servers.sort();
+
+        if sender_server.is_none() && signature_servers.is_empty() {
+            None
+        } else {
+            Some(OriginMismatch {
+                sender_server,
+                signature_servers,
+            })
+        }
+    }
+
+    /// Replaces the type, the sender, the origin and the room ID of this event by the copies
+    /// kept by `interner`, so that the events of a DAG share them rather than each having its
+    /// own.
+    pub fn intern(&mut self, interner: &mut StringInterner) {
+        self.room_id = interner.intern(&self.room_id);
+        self.sender = interner.intern(&self.sender);
+        self.origin = interner.intern(&self.origin);
+        self.etype = interner.intern(&self.etype);
+    }
+
+    /// Returns a rough estimate of the memory used by this event, in bytes. The interned strings
+    /// aren't counted, since they are shared with the other events.
+    pub fn memory_size(&self) -> usize {
+        let json_values = [
+            &self.content,
+            &self.hashes,
+            &self.signatures,
+            self.unsigned.as_ref().unwrap_or(&JsonValue::Null),
+        ];
+
+        mem::size_of::<Event>()
+            + self.event_id.capacity()
+            + self.state_key.as_ref().map_or(0, String::capacity)
+            + self.redacts.as_ref().map_or(0, String::capacity)
+            + self.rejection.as_ref().map_or(0, String::capacity)
+            + json_values
+                .iter()
+                .map(|json| interner::json_memory_size(json))
+                .sum::<usize>()
+            + self
+                .prev_events
+                .iter()
+                .chain(&self.auth_events)
+                .map(|json| mem::size_of::<JsonValue>() + interner::json_memory_size(json))
+                .sum::<usize>()
+            + self
+                .unknown_fields
+                .iter()
+                .map(|(key, value)| {
+                    key.capacity() + mem::size_of::<JsonValue>() + interner::json_memory_size(value)
+                })
+                .sum::<usize>()
+    }
+
+    /// Returns the `depth` field of this event, or `None` if the source didn't give it.
+    pub fn get_raw_depth(&self) -> Option<i64> {
+        self.raw_depth
+    }
+
+    /// Returns the time stamp in milliseconds at which the origin server created this event.
+    pub fn get_origin_server_ts(&self) -> i64 {
+        self.origin_server_ts
+    }
+
+    /// Returns the ID of the room of this event.
+    pub fn get_room_id(&self) -> &str {
+        &self.room_id
+    }
+
+    /// Returns the ID of the event redacted by this event, if it is a redaction.
+    pub fn get_redacts(&self) -> Option<&str> {
+        self.redacts.as_deref()
+    }
+
+    /// Returns the data added by the server this event was retrieved from, if there is any.
+    pub fn get_unsigned(&self) -> Option<&JsonValue> {
+        self.unsigned.as_ref()
+    }
+
+    /// Returns the type of the event.
+    pub fn get_type(&self) -> &str {
+        &self.etype
+    }
+
+    /// Returns the state key of the event, or `None` if it isn't a state event.
+    pub fn get_state_key(&self) -> Option<&str> {
+        self.state_key.as_deref()
+    }
+
+    /// Returns whether this event is a state event.
+    pub fn is_state(&self) -> bool {
+        self.state_key.is_some()
+    }
+
+    /// Returns the reason why the HS has rejected this event, or `None` if it hasn't.
+    pub fn get_rejection(&self) -> Option<&str> {
+        self.rejection.as_deref()
+    }
+
+    /// Returns whether this event has soft-failed on the HS, i.e. whether it has been accepted in
+    /// the DAG but not in the current state of the room.
+    pub fn is_soft_failed(&self) -> bool {
+        self.soft_failed
+    }
+
+    /// Annotates this event with the reason of its rejection and its soft-failure, as reported
+    /// by the HS.
+    pub fn set_rejection(&mut self, rejection: Option<String>, soft_failed: bool) {
+        self.rejection = rejection;
+        self.soft_failed = soft_failed;
+    }
+
+    /// Returns the names of the fields in which this event and `other`, a copy of the same event
+    /// received from another source, differ. A field is only compared if both copies have it,
+    /// since the CS API strips some of them (e.g. the signatures), and the age of the event in
+    /// `unsigned` is left out, as it depends on when the copy was sent.
+    pub fn divergent_fields(&self, other: &Event) -> Vec<&'static str> {
+        let differ = |a: &JsonValue, b: &JsonValue| !a.is_null() && !b.is_null() && a != b;
+        let unsigned = |event: &Event| match &event.unsigned {
+            Some(JsonValue::Object(unsigned)) => {
+                let mut unsigned = unsigned.clone();
+                unsigned.remove("age");
+                unsigned.remove("transaction_id");
+
+                JsonValue::Object(unsigned)
+            }
+            _ => JsonValue::Null,
+        };
+
+        let mut fields = Vec::new();
+
+        if differ(&self.content, &other.content) {
+            fields.push("content");
+        }
+        if differ(&self.signatures, &other.signatures) {
+            fields.push("signatures");
+        }
+        if differ(&self.hashes, &other.hashes) {
+            fields.push("hashes");
+        }
+        if differ(&unsigned(self), &unsigned(other)) {
+            fields.push("unsigned");
+        }
+
+        fields
+    }
+
+    /// Returns the relations of this event to other events along with the IDs of these events.
+    pub fn get_relations(&self) -> Vec<(RelationType, &str)> {
+        let relates_to = &self.content["m.relates_to"];
+        let mut relations = Vec::new();
+
+        let rel_type = match relates_to["rel_type"].as_str() {
+            Some("m.thread") | Some("io.element.thread") => Some(RelationType::Thread),
+            Some("m.replace") => Some(RelationType::Edit),
+            Some("m.annotation") => Some(RelationType::Annotation),
+            _ => None,
+        };
+
+        if let (Some(rel_type), Some(event_id)) = (rel_type, relates_to["event_id"].as_str()) {
+            relations.push((rel_type, event_id));
+        }
+
+        // Replies can also be part of a thread
+        if let Some(event_id) = relates_to["m.in_reply_to"]["event_id"].as_str() {
+            relations.push((RelationType::Reply, event_id));
+        }
+
+        relations
+    }
+
+    /// Returns the size of the content of the event once serialized.
+    pub fn content_size(&self) -> usize {
+        serde_json::to_string(&self.content)
+            .map(|content| content.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns the names of the top-level fields of the event which aren't part of the spec
+    /// supported by the application.
+    pub fn unknown_fields(&self) -> Vec<&str> {
+        self.unknown_fields.keys().map(|k| k.as_str()).collect()
+    }
+
+    /// Returns the value at the JSON pointer `pointer` (e.g. `/content/membership`) within the
+    /// event, including its unknown fields, or `None` if there is no such value.
+    pub fn get_pointer(&self, pointer: &str) -> Option<JsonValue> {
+        self.as_json().pointer(pointer).cloned()
+    }
+
+    // Returns the event as JSON, which is only built once since the fields it is made of never
+    // change, so that the labels with JSON pointers don't serialize the event each time they are
+    // drawn.
+    fn as_json(&self) -> &JsonValue {
+        self.json
+            .get_or_init(|| serde_json::to_value(self).unwrap_or(JsonValue::Null))
+    }
+
+    /// Returns the tooltip of the node of this event, shown by vis.js when the node is hovered:
+    /// its ID, type, state key, sender and timestamp in full, so that the labels can be kept
+    /// minimal. vis.js renders the tooltips as HTML, so the values are escaped.
+    pub fn tooltip(&self) -> String {
+        let mut lines = vec![
+            format!("Event ID: {}", self.event_id),
+            format!("Type: {}", self.etype),
+        ];
+
+        if let Some(state_key) = &self.state_key {
+            lines.push(format!("State key: {}", state_key));
+        }
+
+        lines.push(format!("Sender: {}", self.sender));
+        lines.push(format!("Sent: {}", format_timestamp(self.origin_server_ts)));
+
+        lines
+            .iter()
+            .map(|line| escape_html(line))
+            .collect::<Vec<String>>()
+            .join("<br>")
+    }
+
+    /// Convert an event in a format usable by vis.js.
+    /// `server_name` must be the HS from which the DAG was retrieved for coloring the node.
+    /// `fields` is a set of events fields to include in the label.
+    /// `id_shortener` shortens the event IDs in the label, the full ID being kept in the tooltip,
+    /// along with the other details of the event (see `tooltip`).
+    /// `label_format` lays out the fields in the label.
+    /// `palette` gives the colours of the node.
+    pub fn to_data_set_node(
+        &self,
+        server_name: &str,
+        fields: &HashSet<Field>,
+        id_shortener: &IdShortener,
+        label_format: &LabelFormat,
+        palette: &Palette,
+    ) -> DataSetNode {
+        let swatch = if &*self.origin == server_name {
+            &palette.local_event
+        } else {
+            &palette.remote_event
+        };
+
+        DataSetNode {
+            id: self.event_id.clone(),
+            label: self.label(fields, id_shortener, label_format),
+            title: Some(self.tooltip()),
+            font: if label_format.is_html() {
+                Some(NodeFont::html())
+            } else {
+                None
+            },
+            level: self.depth,
+            color: swatch.node_color(),
+            value: None,
+            border_width: if self.rejection.is_some() || self.soft_failed {
+                Some(3)
+            } else {
+                None
+            },
+            shape_properties: if self.rejection.is_some() {
+                Some(ShapeProperties {
+                    border_dashes: vec![8, 4],
+                })
+            } else if self.soft_failed {
+                Some(ShapeProperties {
+                    border_dashes: vec![2, 4],
+                })
+            } else {
+                None
+            },
+        }
+    }
+
+    fn label(
+        &self,
+        fields: &HashSet<Field>,
+        id_shortener: &IdShortener,
+        format: &LabelFormat,
+    ) -> String {
+        let mut lines: Vec<String> = Vec::new();
+
+        if fields.contains(&Field::Sender) {
+            lines.push(format!("Sender: {}", format.value(&self.sender)));
+        }
+
+        if fields.contains(&Field::Origin) {
+            lines.push(format!("Origin: {}", format.value(&self.origin)));
+        }
+
+        if fields.contains(&Field::OriginServerTS) {
+            lines.push(format!(
+                "Origin server time stamp: {}",
+                self.origin_server_ts
+            ));
+        }
+
+        if fields.contains(&Field::Type) {
+            lines.push(format!("Type: {}", format.value(&self.etype)));
+        }
+
+        if fields.contains(&Field::StateKey) {
+            if let Some(state_key) = &self.state_key {
+                lines.push(format!("State key: {}", format.value(state_key)));
+            }
+        }
+
+        if fields.contains(&Field::PrevEvents) {
+            let mut line = "Previous events:".to_string();
+
+            for prev_ev in self.get_prev_events() {
+                line.push(' ');
+                line.push_str(&format.id(prev_ev, id_shortener));
+            }
+
+            lines.push(line);
+        }
+
+        if fields.contains(&Field::Depth) {
+            match self.raw_depth {
+                Some(depth) if depth == self.depth => lines.push(format!("Depth: {}", depth)),
+                Some(depth) => lines.push(format!("Depth: {} (level {})", depth, self.depth)),
+                None => lines.push(format!("Depth: none (level {})", self.depth)),
+            }
+        }
+
+        if fields.contains(&Field::Redacts) {
+            if let Some(redacts) = &self.redacts {
+                lines.push(format!("Redacts: {}", format.id(redacts, id_shortener)));
+            }
+        }
+
+        if fields.contains(&Field::EventID) {
+            lines.push(format!(
+                "Event ID: {}",
+                format.id(&self.event_id, id_shortener)
+            ));
+        }
+
+        if fields.contains(&Field::UnknownFields) {
+            for name in self.unknown_fields() {
+                // Escape the name of the field as required by the JSON pointer syntax
+                let pointer = format!("/{}", name.replace("~", "~0").replace("/", "~1"));
+
+                if let Some(value) = self.get_pointer(&pointer) {
+                    lines.push(format!("{}: {}", name, format.value(&value.to_string())));
+                }
+            }
+        }
+
+        // The fields designated by pointers are sorted so the labels don't depend on the order
+        // of the set
+        let mut pointers: Vec<&str> = fields
+            .iter()
+            .filter_map(|field| match field {
+                Field::Pointer(pointer) => Some(pointer.as_str()),
+                _ => None,
+            })
+            .collect();
+        pointers.sort();
+
+        if !pointers.is_empty() {
+            let json = self.as_json();
+
+            for pointer in pointers {
+                match json.pointer(pointer) {
+                    Some(JsonValue::String(value)) => {
+                        lines.push(format!("{}: {}", pointer, format.value(value)))
+                    }
+                    Some(value) => {
+                        lines.push(format!("{}: {}", pointer, format.value(&value.to_string())))
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        // Rejections and soft-failures are always shown since they explain the style of the node
+        if let Some(reason) = &self.rejection {
+            lines.push(format!("Rejected: {}", format.value(reason)));
+        } else if self.soft_failed {
+            lines.push("Soft-failed".to_string());
+        }
+
+        format.join(&lines).trim_end().to_string()
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Event) -> bool {
+        self.event_id == other.event_id
+    }
+}
+
+impl Eq for Event {}
+
+// Returns the ID of an entry of `prev_events`, in either of the formats of the room versions
+fn prev_event_id(prev_ev: &JsonValue) -> Option<&str> {
+    match prev_ev {
+        JsonValue::Array(pair) => pair.first().and_then(|id| id.as_str()),
+        prev_ev => prev_ev.as_str(),
+    }
+}
+
+/// Formats `ts`, a number of milliseconds since the Unix epoch, as a date and a time in UTC in
+/// the ISO 8601 format of `Date.prototype.toISOString`, e.g. `2019-03-01T12:34:56.789Z`, so that
+/// the timestamps read the same everywhere. Unlike the latter, it doesn't fail on the timestamps
+/// too far in time, which the events may well have.
+pub fn format_timestamp(ts: i64) -> String {
+    let days = ts.div_euclid(86_400_000);
+    let ms = ts.rem_euclid(86_400_000);
+
+    // Converts the number of days since the epoch into a date of the proleptic Gregorian
+    // calendar, with the eras of 400 years starting on March 1st
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153; // From March
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+// Escapes the characters which have a meaning in HTML
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,72 @@
+use super::event::Event;
+
+/// The column by which the rows of the list view are sorted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SortKey {
+    #[default]
+    Depth,
+    Timestamp, // The `origin_server_ts` of the events
+    Sender,
+    Type,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 4] = [
+        SortKey::Depth,
+        SortKey::Timestamp,
+        SortKey::Sender,
+        SortKey::Type,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Depth => "Depth",
+            SortKey::Timestamp => "Timestamp",
+            SortKey::Sender => "Sender",
+            SortKey::Type => "Type",
+        }
+    }
+}
+
+/// An event of the DAG as a row of the list view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventRow {
+    pub event_id: String,
+    pub depth: i64,
+    pub origin_server_ts: i64,
+    pub sender: String,
+    pub etype: String,
+}
+
+impl EventRow {
+    pub fn from_event(event: &Event) -> EventRow {
+        EventRow {
+            event_id: event.event_id.clone(),
+            depth: event.depth,
+            origin_server_ts: event.get_origin_server_ts(),
+            sender: event.get_sender().to_string(),
+            etype: event.get_type().to_string(),
+        }
+    }
+}
+
+/// Sorts `rows` by the column `key`, the rows with the same value being sorted by depth and then
+/// by ID so that the order is stable across renderings.
+pub fn sort_rows(rows: &mut [EventRow], key: SortKey, descending: bool) {
+    rows.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Depth => a.depth.cmp(&b.depth),
+            SortKey::Timestamp => a.origin_server_ts.cmp(&b.origin_server_ts),
+            SortKey::Sender => a.sender.cmp(&b.sender),
+            SortKey::Type => a.etype.cmp(&b.etype),
+        }
+        .then_with(|| a.depth.cmp(&b.depth))
+        .then_with(|| a.event_id.cmp(&b.event_id));
+
+        if descending {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+}
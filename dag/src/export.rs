@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::annotation::Annotations;
+use super::event::Event;
+use super::preset::DisplayPreset;
+use super::snapshot::Snapshots;
+
+// The version of the format of the exported documents, increased when it changes in a way older
+// versions of the application can't read
+pub const EXPORT_VERSION: u32 = 1;
+
+/// A self-contained snapshot of an investigation: the DAGs of every views along with what is
+/// known about them, and the way they were displayed. It can be attached to a bug report and
+/// loaded back by someone else without any access to the servers.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ExportedDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub preset: DisplayPreset,
+    pub views: Vec<ExportedView>,
+    #[serde(default)]
+    pub annotations: Annotations, // The notes pinned to the events by the user
+    #[serde(default, skip_serializing_if = "Snapshots::is_empty")]
+    pub snapshots: Snapshots, // The named states of the views prepared by the user
+}
+
+/// The DAG of a view, retrieved from the server `server_name`, along with the annotations this
+/// server gave about its events.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ExportedView {
+    pub room_id: String,
+    pub server_name: String,
+    pub events: Vec<Event>,
+    #[serde(default)]
+    pub rejections: HashMap<String, String>, // The reasons of the rejection of the rejected events
+    #[serde(default)]
+    pub soft_failed: Vec<String>, // The IDs of the events which have soft-failed
+    #[serde(default)]
+    pub dead_servers: Vec<String>, // The origin servers which couldn't be reached
+    #[serde(default)]
+    pub view_idx: Option<usize>, // The index of the view when it was exported, the views without a DAG being left out
+}
+
+impl ExportedDocument {
+    pub fn new(
+        preset: DisplayPreset,
+        views: Vec<ExportedView>,
+        annotations: Annotations,
+    ) -> ExportedDocument {
+        ExportedDocument {
+            version: EXPORT_VERSION,
+            preset,
+            views,
+            annotations,
+            snapshots: Snapshots::new(),
+        }
+    }
+
+    /// Serializes the document into a JSON string.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize the document")
+    }
+
+    /// Parses a document previously exported with `to_json`, refusing the documents written by
+    /// newer versions of the application.
+    pub fn from_json(json: &str) -> Result<ExportedDocument, String> {
+        let document: ExportedDocument =
+            serde_json::from_str(json).map_err(|e| format!("{}", e))?;
+
+        if document.version > EXPORT_VERSION {
+            return Err(format!(
+                "The document has the version {}, only the versions up to {} are supported",
+                document.version, EXPORT_VERSION
+            ));
+        }
+
+        Ok(document)
+    }
+}
@@ -0,0 +1,566 @@
+use std::cmp::Ordering;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::event::Event;
+
+/// A predicate on the events written in a tiny expression language, for the filters and the
+/// colouring rules which the fields of `EventFilter` can't express, e.g.:
+///
+/// ```text
+/// sender.endsWith(":bad.server") && type == "m.room.member"
+/// content.membership == "ban" || (depth > 100 && !state_key)
+/// prev_events.contains("$abc") && origin != "example.org"
+/// ```
+///
+/// The identifiers are the fields of the events (`event_id`, `room_id`, `sender`, `origin`,
+/// `type`, `state_key`, `depth`, `origin_server_ts`, `redacts`, `prev_events`), and the dotted
+/// paths within their `content` and `unsigned` data, any other identifier being rejected so that
+/// a typo can't silently match nothing. The missing values are `null`, which is falsy like
+/// `false`, `0` and the empty strings and arrays. Strings can be tested with `startsWith`,
+/// `endsWith` and `contains`, the arrays with `contains`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Literal(JsonValue),
+    Path(Vec<String>),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Box<Node>, CompareOp, Box<Node>),
+    Call(Box<Node>, Method, Box<Node>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Method {
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Str(String),
+    Num(f64),
+    Ident(String),
+    Op(&'static str),
+}
+
+// The fields of the events the paths start with.
+const ROOTS: [&str; 12] = [
+    "event_id",
+    "room_id",
+    "sender",
+    "origin",
+    "type",
+    "state_key",
+    "depth",
+    "origin_server_ts",
+    "redacts",
+    "prev_events",
+    "content",
+    "unsigned",
+];
+
+// The operators and punctuation, the longest ones first so that `<=` isn't read as `<`.
+const OPERATORS: [&str; 14] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "(", ")", ".", ",", "=",
+];
+
+impl Expression {
+    /// Compiles `source`, or returns why it isn't a valid expression.
+    pub fn parse(source: &str) -> Result<Expression, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let root = parser.or()?;
+
+        match parser.peek() {
+            None => Ok(Expression {
+                source: source.trim().to_string(),
+                root,
+            }),
+            Some(token) => Err(format!("Unexpected {}", describe(token))),
+        }
+    }
+
+    /// The text the expression was compiled from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        truthy(&eval(&self.root, event))
+    }
+}
+
+/// Gives the nodes of the events matching an expression a colour, see
+/// `RoomEvents::set_colour_rules`. The first rule an event matches decides its colour.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ColourRule {
+    pub expression: String, // The source of the expression, compiled with `Expression::parse`
+    pub colour: String,     // The CSS colour of the background of the nodes, e.g. `#ff8800`
+}
+
+impl ColourRule {
+    /// Compiles the expression of the rule.
+    pub fn compile(&self) -> Result<(Expression, String), String> {
+        Expression::parse(&self.expression).map(|expression| (expression, self.colour.clone()))
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let mut end = None;
+
+            while let Some((i, ch)) = chars.next() {
+                match ch {
+                    '\\' => match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => break,
+                    },
+                    _ if ch == c => {
+                        end = Some(i + 2);
+                        break;
+                    }
+                    _ => value.push(ch),
+                }
+            }
+
+            match end {
+                Some(end) => rest = &rest[end..],
+                None => return Err("Unterminated string".to_string()),
+            }
+            tokens.push(Token::Str(value));
+        } else if c.is_ascii_digit() || c == '-' {
+            let len = rest[1..]
+                .find(|ch: char| !ch.is_ascii_digit() && ch != '.')
+                .map_or(rest.len(), |i| i + 1);
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| format!("Invalid number {}", &rest[..len]))?;
+
+            tokens.push(Token::Num(number));
+            rest = &rest[len..];
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|ch: char| !ch.is_alphanumeric() && ch != '_')
+                .unwrap_or(rest.len());
+
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or_else(|| format!("Unexpected character '{}'", c))?;
+
+            // A single `=` is taken for `==`, as it is the only thing it can mean here
+            tokens.push(Token::Op(if *op == "=" { "==" } else { op }));
+            rest = &rest[op.len()..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Str(s) => format!("string \"{}\"", s),
+        Token::Num(n) => format!("number {}", n),
+        Token::Ident(i) => format!("identifier {}", i),
+        Token::Op(op) => format!("'{}'", op),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(token) => Err(format!("Expected '{}', found {}", op, describe(token))),
+                None => Err(format!("Expected '{}' at the end", op)),
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+
+        while self.eat("||") {
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+
+        while self.eat("&&") {
+            node = Node::And(Box::new(node), Box::new(self.unary()?));
+        }
+
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat("!") {
+            Ok(Node::Not(Box::new(self.unary()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Node, String> {
+        let lhs = self.operand()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => CompareOp::Eq,
+            Some(Token::Op("!=")) => CompareOp::Ne,
+            Some(Token::Op("<")) => CompareOp::Lt,
+            Some(Token::Op("<=")) => CompareOp::Le,
+            Some(Token::Op(">")) => CompareOp::Gt,
+            Some(Token::Op(">=")) => CompareOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+
+        Ok(Node::Compare(Box::new(lhs), op, Box::new(self.operand()?)))
+    }
+
+    // A value followed by the methods called on it, e.g. `sender.endsWith(":example.org")`.
+    fn operand(&mut self) -> Result<Node, String> {
+        let mut node = self.primary()?;
+
+        while self.eat(".") {
+            let method = match self.next() {
+                Some(Token::Ident(name)) => match name.as_str() {
+                    "startsWith" => Method::StartsWith,
+                    "endsWith" => Method::EndsWith,
+                    "contains" => Method::Contains,
+                    _ => return Err(format!("Unknown method {}", name)),
+                },
+                Some(token) => {
+                    return Err(format!("Expected a method, found {}", describe(&token)))
+                }
+                None => return Err("Expected a method at the end".to_string()),
+            };
+
+            self.expect("(")?;
+            let argument = self.or()?;
+            self.expect(")")?;
+
+            node = Node::Call(Box::new(node), method, Box::new(argument));
+        }
+
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(Node::Literal(JsonValue::from(s))),
+            Some(Token::Num(n)) => Ok(Node::Literal(JsonValue::from(n))),
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Node::Literal(JsonValue::Bool(true))),
+                "false" => Ok(Node::Literal(JsonValue::Bool(false))),
+                "null" => Ok(Node::Literal(JsonValue::Null)),
+                _ if !ROOTS.contains(&name.as_str()) => Err(format!(
+                    "Unknown field {}, expected one of {}",
+                    name,
+                    ROOTS.join(", ")
+                )),
+                _ => {
+                    let mut path = vec![name];
+
+                    // The dotted segments followed by a parenthesis are methods, not fields
+                    while self.peek() == Some(&Token::Op("."))
+                        && self.tokens.get(self.pos + 2) != Some(&Token::Op("("))
+                    {
+                        self.pos += 1;
+
+                        match self.next() {
+                            Some(Token::Ident(segment)) => path.push(segment),
+                            Some(Token::Num(n)) if n.fract() == 0.0 && n >= 0.0 => {
+                                path.push(n.to_string())
+                            }
+                            _ => return Err(format!("Invalid path {}.", path.join("."))),
+                        }
+                    }
+
+                    Ok(Node::Path(path))
+                }
+            },
+            Some(Token::Op("(")) => {
+                let node = self.or()?;
+                self.expect(")")?;
+
+                Ok(node)
+            }
+            Some(token) => Err(format!("Unexpected {}", describe(&token))),
+            None => Err("Unexpected end of the expression".to_string()),
+        }
+    }
+}
+
+fn eval(node: &Node, event: &Event) -> JsonValue {
+    match node {
+        Node::Literal(value) => value.clone(),
+        Node::Path(path) => lookup(path, event),
+        Node::Not(node) => JsonValue::Bool(!truthy(&eval(node, event))),
+        Node::And(lhs, rhs) => {
+            JsonValue::Bool(truthy(&eval(lhs, event)) && truthy(&eval(rhs, event)))
+        }
+        Node::Or(lhs, rhs) => {
+            JsonValue::Bool(truthy(&eval(lhs, event)) || truthy(&eval(rhs, event)))
+        }
+        Node::Compare(lhs, op, rhs) => {
+            let ordering = compare(&eval(lhs, event), &eval(rhs, event));
+
+            JsonValue::Bool(match op {
+                CompareOp::Eq => ordering == Some(Ordering::Equal),
+                CompareOp::Ne => ordering != Some(Ordering::Equal),
+                CompareOp::Lt => ordering == Some(Ordering::Less),
+                CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                CompareOp::Gt => ordering == Some(Ordering::Greater),
+                CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            })
+        }
+        Node::Call(target, method, argument) => {
+            let target = eval(target, event);
+            let argument = eval(argument, event);
+
+            JsonValue::Bool(match (&target, method, &argument) {
+                (JsonValue::String(s), Method::StartsWith, JsonValue::String(a)) => {
+                    s.starts_with(a.as_str())
+                }
+                (JsonValue::String(s), Method::EndsWith, JsonValue::String(a)) => {
+                    s.ends_with(a.as_str())
+                }
+                (JsonValue::String(s), Method::Contains, JsonValue::String(a)) => {
+                    s.contains(a.as_str())
+                }
+                (JsonValue::Array(values), Method::Contains, _) => values
+                    .iter()
+                    .any(|value| compare(value, &argument) == Some(Ordering::Equal)),
+                _ => false,
+            })
+        }
+    }
+}
+
+// The value of the field designated by `path` in `event`, or `null` if there is none.
+fn lookup(path: &[String], event: &Event) -> JsonValue {
+    let (field, rest) = path.split_first().expect("Empty path");
+
+    // The content and the unsigned data are walked through without copying them
+    let nested = match field.as_str() {
+        "content" => Some(event.get_content()),
+        "unsigned" => event.get_unsigned(),
+        "prev_events" if !rest.is_empty() => {
+            let prev_events = JsonValue::from(event.get_prev_events());
+
+            return walk(&prev_events, rest).cloned().unwrap_or(JsonValue::Null);
+        }
+        "event_id" => return JsonValue::from(event.event_id.as_str()),
+        "room_id" => return JsonValue::from(event.get_room_id()),
+        "sender" => return JsonValue::from(event.get_sender()),
+        "origin" => return JsonValue::from(event.get_origin()),
+        "type" => return JsonValue::from(event.get_type()),
+        "state_key" => {
+            return event
+                .get_state_key()
+                .map_or(JsonValue::Null, JsonValue::from)
+        }
+        "depth" => return JsonValue::from(event.depth),
+        "origin_server_ts" => return JsonValue::from(event.get_origin_server_ts()),
+        "redacts" => return event.get_redacts().map_or(JsonValue::Null, JsonValue::from),
+        "prev_events" => return JsonValue::from(event.get_prev_events()),
+        _ => None, // Rejected by the parser
+    };
+
+    nested
+        .and_then(|value| walk(value, rest))
+        .cloned()
+        .unwrap_or(JsonValue::Null)
+}
+
+// The value at the end of the `path` within `value`, if there is one.
+fn walk<'a>(value: &'a JsonValue, path: &[String]) -> Option<&'a JsonValue> {
+    path.iter().try_fold(value, |value, segment| match value {
+        JsonValue::Object(map) => map.get(segment),
+        JsonValue::Array(values) => values.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn truthy(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Null => false,
+        JsonValue::Bool(b) => *b,
+        JsonValue::Number(n) => n.as_f64() != Some(0.0),
+        JsonValue::String(s) => !s.is_empty(),
+        JsonValue::Array(values) => !values.is_empty(),
+        JsonValue::Object(_) => true,
+    }
+}
+
+// Compares the numbers by their value, whatever their representation, and the strings
+// lexicographically. The other values are only equal or not.
+fn compare(lhs: &JsonValue, rhs: &JsonValue) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (JsonValue::Number(a), JsonValue::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (JsonValue::String(a), JsonValue::String(b)) => Some(a.cmp(b)),
+        _ if lhs == rhs => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn event() -> Event {
+        let mut event: Event = serde_json::from_value(json!({
+            "event_id": "$ban",
+            "room_id": "!room:example.org",
+            "sender": "@mod:bad.server",
+            "origin": "bad.server",
+            "origin_server_ts": 1_500_000_000_000i64,
+            "type": "m.room.member",
+            "state_key": "@spammer:example.org",
+            "content": {
+                "membership": "ban",
+                "reason": "Spam",
+                "via": ["example.org", "bad.server"],
+            },
+            "prev_events": ["$a", "$b"],
+            "unsigned": { "age": 1200 },
+        }))
+        .unwrap();
+        event.depth = 120;
+
+        event
+    }
+
+    fn matches(source: &str) -> bool {
+        Expression::parse(source).unwrap().matches(&event())
+    }
+
+    #[test]
+    fn compares_the_fields() {
+        assert!(matches(r#"type == "m.room.member""#));
+        assert!(matches(r#"type = "m.room.member""#));
+        assert!(matches(r#"origin != "example.org""#));
+        assert!(matches("depth > 100 && depth <= 120"));
+        assert!(!matches("depth < 100"));
+        assert!(matches("origin_server_ts >= 1500000000000"));
+    }
+
+    #[test]
+    fn calls_the_methods() {
+        assert!(matches(r#"sender.endsWith(":bad.server")"#));
+        assert!(matches(r#"sender.startsWith("@mod")"#));
+        assert!(matches(r#"content.reason.contains("pam")"#));
+        assert!(matches(r#"prev_events.contains("$b")"#));
+        assert!(!matches(r#"prev_events.contains("$c")"#));
+        assert!(matches(r#"content.via.contains("example.org")"#));
+    }
+
+    #[test]
+    fn follows_the_paths() {
+        assert!(matches(r#"content.membership == "ban""#));
+        assert!(matches(r#"content.via.1 == "bad.server""#));
+        assert!(matches(r#"prev_events.0 == "$a""#));
+        assert!(matches("unsigned.age == 1200"));
+        // The missing values are null, which is falsy
+        assert!(matches("content.missing.field == null"));
+        assert!(!matches("content.missing"));
+        assert!(!matches("redacts"));
+    }
+
+    #[test]
+    fn combines_the_conditions() {
+        assert!(matches(r#"content.membership == "ban" || depth > 1000"#));
+        assert!(!matches(r#"!state_key || type == "m.room.message""#));
+        // `&&` binds tighter than `||`
+        assert!(matches(
+            r#"depth > 1000 && false || sender.endsWith("bad.server")"#
+        ));
+        assert!(!matches(
+            r#"depth > 1000 && (false || sender.endsWith("bad.server"))"#
+        ));
+        assert!(matches(r#"!(type == 'm.room.message')"#));
+    }
+
+    #[test]
+    fn rejects_the_invalid_expressions() {
+        assert!(Expression::parse("sendr == \"@a:b\"")
+            .unwrap_err()
+            .starts_with("Unknown field sendr"));
+        assert!(Expression::parse("sender.endsWith(\":b\"").is_err());
+        assert!(Expression::parse("sender.matches(\"a\")").is_err());
+        assert!(Expression::parse("type == \"m.room.member").is_err());
+        assert!(Expression::parse("depth > 1 2").is_err());
+        assert!(Expression::parse("depth >").is_err());
+        assert!(Expression::parse("depth # 1").is_err());
+    }
+
+    #[test]
+    fn keeps_the_source() {
+        let expression = Expression::parse("  depth > 1 ").unwrap();
+
+        assert_eq!(expression.source(), "depth > 1");
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, HashSet};
+
+/// What the events of a DAG tell about its room and about the rooms it was upgraded from and
+/// to.
+#[derive(Clone, Debug)]
+pub struct RoomIncarnation {
+    pub room_id: String,
+    pub room_version: Option<String>, // From the `m.room.create` event, if it was received
+    pub predecessor: Option<String>,  // The room this one replaced, from its `m.room.create` event
+    pub successor: Option<String>, // The room which replaced this one, from its `m.room.tombstone` event
+    pub events: usize, // The number of events received, 0 for the rooms only known through the others
+    pub time_range: Option<(i64, i64)>, // The earliest and the latest `origin_server_ts` of these events
+}
+
+impl RoomIncarnation {
+    /// Creates the incarnation of a room which is only known through its links with other rooms.
+    pub fn referenced(room_id: &str) -> Self {
+        RoomIncarnation {
+            room_id: room_id.to_string(),
+            room_version: None,
+            predecessor: None,
+            successor: None,
+            events: 0,
+            time_range: None,
+        }
+    }
+
+    // Adds what another DAG of the same room tells about it.
+    fn absorb(&mut self, other: &RoomIncarnation) {
+        self.room_version = self
+            .room_version
+            .take()
+            .or_else(|| other.room_version.clone());
+        self.predecessor = self
+            .predecessor
+            .take()
+            .or_else(|| other.predecessor.clone());
+        self.successor = self.successor.take().or_else(|| other.successor.clone());
+        self.events = self.events.max(other.events);
+        self.time_range = match (self.time_range, other.time_range) {
+            (Some((start, end)), Some((other_start, other_end))) => {
+                Some((start.min(other_start), end.max(other_end)))
+            }
+            (range, None) | (None, range) => range,
+        };
+    }
+}
+
+/// Chains the rooms `rooms` through their upgrades (their tombstones and the predecessors in
+/// their creation events) into lineages, each going from the oldest incarnation of a community's
+/// room to the newest. The rooms referenced by the others but not loaded are part of the lineages,
+/// without any events.
+pub fn lineages(rooms: &[RoomIncarnation]) -> Vec<Vec<RoomIncarnation>> {
+    let mut known: BTreeMap<String, RoomIncarnation> = BTreeMap::new();
+
+    for room in rooms {
+        known
+            .entry(room.room_id.clone())
+            .or_insert_with(|| RoomIncarnation::referenced(&room.room_id))
+            .absorb(room);
+    }
+
+    // Make the links go both ways, adding the rooms which are only referenced
+    let links: Vec<(String, String)> = known
+        .values()
+        .flat_map(|room| {
+            let before = room
+                .predecessor
+                .as_ref()
+                .map(|predecessor| (predecessor.clone(), room.room_id.clone()));
+            let after = room
+                .successor
+                .as_ref()
+                .map(|successor| (room.room_id.clone(), successor.clone()));
+
+            before.into_iter().chain(after)
+        })
+        .collect();
+
+    for (old, new) in links {
+        let old_room = known
+            .entry(old.clone())
+            .or_insert_with(|| RoomIncarnation::referenced(&old));
+        old_room.successor = old_room.successor.take().or_else(|| Some(new.clone()));
+
+        let new_room = known
+            .entry(new.clone())
+            .or_insert_with(|| RoomIncarnation::referenced(&new));
+        new_room.predecessor = new_room.predecessor.take().or_else(|| Some(old.clone()));
+    }
+
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut lineages = Vec::new();
+
+    // Start from the rooms without predecessors, and then from the remaining ones in case the
+    // links form a cycle
+    let roots: Vec<String> = known
+        .values()
+        .filter(|room| room.predecessor.is_none())
+        .chain(known.values().filter(|room| room.predecessor.is_some()))
+        .map(|room| room.room_id.clone())
+        .collect();
+
+    for root in roots {
+        let mut lineage = Vec::new();
+        let mut next = Some(root);
+
+        while let Some(room_id) = next {
+            if !visited.insert(room_id.clone()) {
+                break;
+            }
+
+            let room = &known[&room_id];
+            next = room.successor.clone();
+            lineage.push(room.clone());
+        }
+
+        if !lineage.is_empty() {
+            lineages.push(lineage);
+        }
+    }
+
+    lineages
+}
@@ -0,0 +1,52 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Shortens the IDs of the events when they are displayed, since IDs like `$abcdef...:server`
+/// would otherwise dominate the labels and the lists. The full IDs are still used everywhere
+/// else (tooltips of the nodes, selection, copy).
+///
+/// The default shortener leaves the IDs untouched.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IdShortener {
+    #[serde(default)]
+    pub first: Option<usize>, // Number of characters kept at the start of the ID, after the sigil
+    #[serde(default)]
+    pub last: Option<usize>, // Number of characters kept at the end of the ID, before the domain
+    #[serde(default)]
+    pub hide_domain: bool, // Whether the `:server` part of the IDs of the old room versions is hidden
+}
+
+impl IdShortener {
+    /// Returns the shortened form of `id`.
+    pub fn shorten(&self, id: &str) -> String {
+        // The event IDs of the room versions 1 and 2 are of the form `$opaque_id:domain`
+        let (local, domain) = match id.find(':') {
+            Some(i) => (&id[..i], &id[i..]),
+            None => (id, ""),
+        };
+        let (sigil, opaque) = if local.starts_with('$') {
+            local.split_at(1)
+        } else {
+            ("", local)
+        };
+
+        let chars: Vec<char> = opaque.chars().collect();
+        let first = self.first.unwrap_or(0);
+        let last = self.last.unwrap_or(0);
+
+        let mut short = sigil.to_string();
+
+        if (self.first.is_some() || self.last.is_some()) && first + last < chars.len() {
+            short.extend(&chars[..first]);
+            short.push('…');
+            short.extend(&chars[chars.len() - last..]);
+        } else {
+            short.push_str(opaque);
+        }
+
+        if !self.hide_domain {
+            short.push_str(domain);
+        }
+
+        short
+    }
+}
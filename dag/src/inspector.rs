@@ -0,0 +1,75 @@
+use serde_derive::Serialize;
+use serde_json::Value as JsonValue;
+
+use super::state_timeline::ContentChange;
+
+/// A readable rendering of the content of an event of a well-known type, shown by the inspector
+/// above the JSON of the event, see `RoomEvents::content_view`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ContentView {
+    /// A change of the membership of `target` in the room.
+    Membership {
+        target: String,
+        previous: Option<String>, // The previous membership of `target`, if it is known
+        membership: String,
+        description: String, // The transition in words, e.g. "accepted the invite"
+        displayname: Option<String>,
+        reason: Option<String>,
+    },
+    /// The changes of the power levels from the previous `m.room.power_levels` event, or all the
+    /// values as added for the first one.
+    PowerLevels { changes: Vec<ContentChange> },
+    /// The creation of the room.
+    Create {
+        room_version: String,
+        creator: Option<String>,
+        room_type: Option<String>,   // E.g. `m.space`
+        predecessor: Option<String>, // The ID of the room this one replaces, after an upgrade
+    },
+}
+
+/// Describes in words the transition of the membership of the user `target` from `previous` to
+/// `membership`, following the rules of the spec, when the event was sent by `sender`.
+pub fn membership_transition(
+    previous: Option<&str>,
+    membership: &str,
+    target: &str,
+    sender: &str,
+) -> String {
+    let by_target = target == sender;
+
+    match (previous.unwrap_or("leave"), membership) {
+        ("join", "join") => "changed their profile".to_string(),
+        ("invite", "join") => "accepted the invite".to_string(),
+        (_, "join") => "joined".to_string(),
+        ("invite", "leave") if by_target => "rejected the invite".to_string(),
+        ("invite", "leave") => format!("had the invite withdrawn by {}", sender),
+        ("knock", "leave") if by_target => "withdrew the knock".to_string(),
+        ("knock", "leave") => format!("had the knock rejected by {}", sender),
+        ("ban", "leave") => format!("was unbanned by {}", sender),
+        (_, "leave") if by_target => "left".to_string(),
+        (_, "leave") => format!("was kicked by {}", sender),
+        (_, "ban") => format!("was banned by {}", sender),
+        (_, "invite") => format!("was invited by {}", sender),
+        (_, "knock") => "knocked".to_string(),
+        (_, other) => format!("changed their membership to {}", other),
+    }
+}
+
+/// Returns the rendering of the content of an `m.room.create` event. The rooms created before
+/// the versions were introduced have no `room_version`, and are of the version 1.
+pub fn create_view(content: &JsonValue) -> ContentView {
+    let string_at = |pointer: &str| {
+        content
+            .pointer(pointer)
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    };
+
+    ContentView::Create {
+        room_version: string_at("/room_version").unwrap_or_else(|| "1".to_string()),
+        creator: string_at("/creator"),
+        room_type: string_at("/type"),
+        predecessor: string_at("/predecessor/room_id"),
+    }
+}
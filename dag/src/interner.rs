@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+use std::mem;
+use std::sync::Arc;
+
+use serde_json::Value as JsonValue;
+
+/// Keeps a single copy of the strings which are repeated across the events of a DAG, like their
+/// type, their sender, their origin or the ID of their room: thousands of events only have a few
+/// distinct values for them, and storing each of them once per event makes the large rooms use
+/// too much memory.
+#[derive(Clone, Debug, Default)]
+pub struct StringInterner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringInterner {
+    pub fn new() -> Self {
+        StringInterner::default()
+    }
+
+    /// Returns the shared copy of `string`, which is added if there is none yet.
+    pub fn intern(&mut self, string: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(string) {
+            return interned.clone();
+        }
+
+        let interned: Arc<str> = Arc::from(string);
+        self.strings.insert(interned.clone());
+
+        interned
+    }
+
+    /// Returns the number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns a rough estimate of the memory used by the interned strings, in bytes.
+    pub fn memory_size(&self) -> usize {
+        self.strings
+            .iter()
+            .map(|string| string.len() + 2 * mem::size_of::<usize>()) // The counts of the `Arc`
+            .sum::<usize>()
+            + self.strings.capacity() * mem::size_of::<Arc<str>>()
+    }
+}
+
+/// Returns a rough estimate of the memory allocated for `json`, in bytes, not counting the size
+/// of the `JsonValue` itself.
+pub fn json_memory_size(json: &JsonValue) -> usize {
+    match json {
+        JsonValue::String(string) => string.capacity(),
+        JsonValue::Array(values) => values
+            .iter()
+            .map(|value| mem::size_of::<JsonValue>() + json_memory_size(value))
+            .sum(),
+        JsonValue::Object(map) => map
+            .iter()
+            .map(|(key, value)| {
+                // Each entry of the map also costs a few pointers
+                key.capacity()
+                    + mem::size_of::<String>()
+                    + mem::size_of::<JsonValue>()
+                    + json_memory_size(value)
+            })
+            .sum(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,55 @@
+use serde_derive::Serialize;
+use serde_json::Value as JsonValue;
+
+use super::event::Event;
+
+/// How well an event of the journal could be parsed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseStatus {
+    Parsed,        // Every field of the event is known
+    UnknownFields, // The event has fields which aren't part of the supported spec
+    Failed,        // The event couldn't be parsed, and isn't in the DAG
+}
+
+/// An event as it was loaded, along with where it comes from and how it was parsed. Each entry
+/// is written as one line of the journal.
+#[derive(Serialize)]
+pub struct JournalEntry<'a> {
+    pub view: usize,
+    pub source: &'a str, // The backend the event was retrieved with
+    pub server_name: &'a str,
+    pub room_id: &'a str,
+    pub event_id: Option<&'a str>,
+    pub status: ParseStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>, // Why the parsing failed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_fields: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub soft_failed: bool,
+    pub event: JournalEvent<'a>,
+}
+
+/// The event of a journal entry, which is the raw JSON received when it couldn't be parsed.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum JournalEvent<'a> {
+    Parsed(&'a Event),
+    Raw(&'a JsonValue),
+}
+
+/// Writes `entries` in the JSON Lines format, one entry per line, so that the journal can be
+/// processed by line-oriented tools like `jq`. The entries are serialized one at a time.
+pub fn to_jsonl<'a>(entries: impl Iterator<Item = JournalEntry<'a>>) -> String {
+    let mut jsonl = String::new();
+
+    for entry in entries {
+        jsonl.push_str(&serde_json::to_string(&entry).expect("Failed to serialize the entry"));
+        jsonl.push('\n');
+    }
+
+    jsonl
+}
@@ -0,0 +1,96 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::id_shortener::IdShortener;
+
+/// The number of characters the monospace event IDs are shortened to, unless the IDs are already
+/// shortened otherwise.
+pub const MONOSPACE_ID_CHARS: usize = 8;
+
+/// How the fields of the events are laid out in the labels of the nodes, since long values like
+/// the bodies of the messages would otherwise make the nodes enormous.
+///
+/// The default format puts each field on its own line, in full.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LabelFormat {
+    #[serde(default)]
+    pub max_chars: Option<usize>, // Number of characters kept in the value of each field
+    #[serde(default = "default_line_breaks")]
+    pub line_breaks: bool, // Whether each field is on its own line rather than all on the same one
+    #[serde(default)]
+    pub monospace_ids: bool, // Whether the event IDs are drawn in a monospace font
+}
+
+fn default_line_breaks() -> bool {
+    true
+}
+
+impl Default for LabelFormat {
+    fn default() -> Self {
+        LabelFormat {
+            max_chars: None,
+            line_breaks: true,
+            monospace_ids: false,
+        }
+    }
+}
+
+impl LabelFormat {
+    /// Whether the labels use the HTML markup of vis.js, which is needed for the monospace IDs.
+    pub fn is_html(&self) -> bool {
+        self.monospace_ids
+    }
+
+    /// Returns `value` truncated to the maximum number of characters, and escaped if the labels
+    /// use the HTML markup.
+    pub fn value(&self, value: &str) -> String {
+        let value = match self.max_chars {
+            Some(max_chars) if value.chars().count() > max_chars => {
+                let mut truncated: String = value.chars().take(max_chars).collect();
+                truncated.push('…');
+
+                truncated
+            }
+            _ => value.to_string(),
+        };
+
+        self.escape(&value)
+    }
+
+    /// Returns `id` shortened by `id_shortener`, in a monospace font if needed. The monospace IDs
+    /// are shortened to `MONOSPACE_ID_CHARS` characters if `id_shortener` leaves them untouched.
+    pub fn id(&self, id: &str, id_shortener: &IdShortener) -> String {
+        if !self.monospace_ids {
+            return id_shortener.shorten(id);
+        }
+
+        let short = if id_shortener.first.is_none() && id_shortener.last.is_none() {
+            IdShortener {
+                first: Some(MONOSPACE_ID_CHARS),
+                ..*id_shortener
+            }
+            .shorten(id)
+        } else {
+            id_shortener.shorten(id)
+        };
+
+        format!("<code>{}</code>", self.escape(&short))
+    }
+
+    /// Lays out the `lines` of a label.
+    pub fn join(&self, lines: &[String]) -> String {
+        if self.line_breaks {
+            lines.join("\n")
+        } else {
+            lines.join(" · ")
+        }
+    }
+
+    // Only `&` and `<` have a meaning in the HTML markup of vis.js
+    fn escape(&self, text: &str) -> String {
+        if self.is_html() {
+            text.replace('&', "&amp;").replace('<', "&lt;")
+        } else {
+            text.to_string()
+        }
+    }
+}
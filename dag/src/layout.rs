@@ -0,0 +1,455 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::dataset::DataSet;
+use super::layout_cache::Position;
+use super::layout_options::{Direction, LayoutOptions};
+
+/// The number of sweeps over the ranks done to reduce the crossings of the edges, and then to
+/// straighten them.
+const SWEEPS: usize = 8;
+
+/// The algorithm laying out the nodes of the networks.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum LayoutAlgorithm {
+    #[default]
+    Hierarchical, // The hierarchical layout of vis.js
+    Layered, // The layered layout computed by `layered_positions`
+}
+
+impl LayoutAlgorithm {
+    pub const ALL: [LayoutAlgorithm; 2] = [LayoutAlgorithm::Hierarchical, LayoutAlgorithm::Layered];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LayoutAlgorithm::Hierarchical => "hierarchical",
+            LayoutAlgorithm::Layered => "layered",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LayoutAlgorithm> {
+        LayoutAlgorithm::ALL
+            .iter()
+            .cloned()
+            .find(|algorithm| algorithm.name() == name)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            LayoutAlgorithm::Hierarchical => "Hierarchical (vis.js)",
+            LayoutAlgorithm::Layered => "Layered (computed at once)",
+        }
+    }
+}
+
+/// A node to lay out, at the rank given by its level.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayoutNode {
+    pub id: String,
+    pub level: f64,
+    #[serde(default)]
+    pub group: usize, // The nodes of each group are laid out next to the ones of the previous group
+}
+
+/// An edge between two nodes to lay out, whatever its direction.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayoutEdge {
+    pub from: String,
+    pub to: String,
+}
+
+/// The nodes and the edges of a network to lay out, e.g. read back from vis.js.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LayoutGraph {
+    pub nodes: Vec<LayoutNode>,
+    pub edges: Vec<LayoutEdge>,
+}
+
+impl LayoutGraph {
+    /// Returns the graph of the nodes and the edges of `data_set`, in a single group.
+    pub fn from_data_set(data_set: &DataSet) -> Self {
+        LayoutGraph {
+            nodes: data_set
+                .nodes
+                .iter()
+                .map(|node| LayoutNode {
+                    id: node.id.clone(),
+                    level: node.level as f64,
+                    group: 0,
+                })
+                .collect(),
+            edges: data_set
+                .edges
+                .iter()
+                .map(|edge| LayoutEdge {
+                    from: edge.from.clone(),
+                    to: edge.to.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// A node of the layered layout, either one of the graph or a virtual one breaking an edge which
+// spans several ranks into edges between consecutive ranks
+struct LayeredNode {
+    node: Option<usize>, // The index of the node in the graph, `None` for the virtual nodes
+    rank: usize,         // The index of its level among the levels of its group
+    above: Vec<usize>,   // Its neighbours at the previous rank
+    below: Vec<usize>,   // Its neighbours at the next rank
+}
+
+/// Lays out `graph` in layers, as in the Sugiyama method: each level is a rank, the order of the
+/// nodes within the ranks is chosen to reduce the crossings of the edges with the barycenter
+/// heuristic, and the nodes are then moved towards their neighbours to straighten the edges. The
+/// groups are laid out next to each other, in order.
+///
+/// Unlike the hierarchical layout of vis.js, which simulates the physics of the network until it
+/// stabilises, this takes a few sweeps over the nodes, so that the biggest DAGs are drawn at once.
+pub fn layered_positions(
+    graph: &LayoutGraph,
+    layout_options: &LayoutOptions,
+) -> HashMap<String, Position> {
+    let spacing = f64::from(layout_options.node_spacing);
+    let separation = f64::from(layout_options.level_separation);
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        groups.entry(node.group).or_default().push(idx);
+    }
+
+    let indices: HashMap<&str, usize> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.id.as_str(), idx))
+        .collect();
+
+    let mut edges_of_group: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    for edge in &graph.edges {
+        if let (Some(&from), Some(&to)) = (
+            indices.get(edge.from.as_str()),
+            indices.get(edge.to.as_str()),
+        ) {
+            let group = graph.nodes[from].group;
+
+            if group == graph.nodes[to].group && from != to {
+                edges_of_group.entry(group).or_default().push((from, to));
+            }
+        }
+    }
+
+    let mut positions = HashMap::with_capacity(graph.nodes.len());
+    let mut offset = 0.0;
+
+    for (group, members) in groups {
+        let edges = edges_of_group.remove(&group).unwrap_or_default();
+        let (along, levels) = lay_out_group(graph, &members, &edges, spacing);
+
+        let min = along.iter().map(|(_, x)| *x).fold(f64::INFINITY, f64::min);
+        let max = along
+            .iter()
+            .map(|(_, x)| *x)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        for (node, x) in along {
+            let x = x - min + offset;
+            let y = levels[&node] * separation;
+
+            positions.insert(
+                graph.nodes[node].id.clone(),
+                place(x, y, layout_options.direction),
+            );
+        }
+
+        offset += max - min + spacing;
+    }
+
+    positions
+}
+
+// Returns the coordinate along its rank of each node of `members`, and the level of each of them
+fn lay_out_group(
+    graph: &LayoutGraph,
+    members: &[usize],
+    edges: &[(usize, usize)],
+    spacing: f64,
+) -> (Vec<(usize, f64)>, HashMap<usize, f64>) {
+    let levels: HashMap<usize, f64> = members
+        .iter()
+        .map(|&node| (node, graph.nodes[node].level.round()))
+        .collect();
+
+    let mut distinct: Vec<i64> = levels.values().map(|level| *level as i64).collect();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let rank_of: HashMap<i64, usize> = distinct
+        .iter()
+        .enumerate()
+        .map(|(rank, level)| (*level, rank))
+        .collect();
+
+    let mut layered: Vec<LayeredNode> = Vec::with_capacity(members.len());
+    let mut layered_of: HashMap<usize, usize> = HashMap::with_capacity(members.len());
+    for &node in members {
+        layered_of.insert(node, layered.len());
+        layered.push(LayeredNode {
+            node: Some(node),
+            rank: rank_of[&(levels[&node] as i64)],
+            above: Vec::new(),
+            below: Vec::new(),
+        });
+    }
+
+    // Break the edges spanning several ranks with a virtual node at each rank they cross
+    for &(from, to) in edges {
+        let (a, b) = (layered_of[&from], layered_of[&to]);
+        let (mut upper, lower) = if layered[a].rank <= layered[b].rank {
+            (a, b)
+        } else {
+            (b, a)
+        };
+
+        if layered[upper].rank == layered[lower].rank {
+            continue;
+        }
+
+        for rank in layered[upper].rank + 1..layered[lower].rank {
+            let virtual_node = layered.len();
+            layered.push(LayeredNode {
+                node: None,
+                rank,
+                above: vec![upper],
+                below: Vec::new(),
+            });
+            layered[upper].below.push(virtual_node);
+            upper = virtual_node;
+        }
+
+        layered[upper].below.push(lower);
+        layered[lower].above.push(upper);
+    }
+
+    let mut ranks: Vec<Vec<usize>> = vec![Vec::new(); distinct.len()];
+    for (idx, node) in layered.iter().enumerate() {
+        ranks[node.rank].push(idx);
+    }
+
+    let mut order = vec![0.0; layered.len()];
+    for rank in &ranks {
+        for (position, &idx) in rank.iter().enumerate() {
+            order[idx] = position as f64;
+        }
+    }
+
+    // Reduce the crossings by sorting each rank by the barycenter of the neighbours of its nodes
+    // in the rank swept just before
+    for sweep in 0..SWEEPS {
+        let downwards = sweep % 2 == 0;
+        let sequence: Vec<usize> = if downwards {
+            (1..ranks.len()).collect()
+        } else {
+            (0..ranks.len().saturating_sub(1)).rev().collect()
+        };
+
+        for rank in sequence {
+            for &idx in &ranks[rank] {
+                let neighbours = if downwards {
+                    &layered[idx].above
+                } else {
+                    &layered[idx].below
+                };
+
+                if let Some(barycenter) = mean(neighbours.iter().map(|n| order[*n])) {
+                    order[idx] = barycenter;
+                }
+            }
+
+            ranks[rank].sort_by(|a, b| order[*a].partial_cmp(&order[*b]).unwrap());
+
+            for (position, &idx) in ranks[rank].iter().enumerate() {
+                order[idx] = position as f64;
+            }
+        }
+    }
+
+    // Start from the nodes side by side, centered on their rank, then move them towards their
+    // neighbours without getting them closer than `spacing`
+    let mut coordinates = vec![0.0; layered.len()];
+    for rank in &ranks {
+        let center = (rank.len() as f64 - 1.0) / 2.0;
+
+        for (position, &idx) in rank.iter().enumerate() {
+            coordinates[idx] = (position as f64 - center) * spacing;
+        }
+    }
+
+    for sweep in 0..SWEEPS {
+        let downwards = sweep % 2 == 0;
+        let sequence: Vec<&Vec<usize>> = if downwards {
+            ranks.iter().collect()
+        } else {
+            ranks.iter().rev().collect()
+        };
+
+        for rank in sequence {
+            let wanted: Vec<f64> = rank
+                .iter()
+                .map(|&idx| {
+                    let node = &layered[idx];
+                    let neighbours = if downwards { &node.above } else { &node.below };
+
+                    mean(neighbours.iter().map(|n| coordinates[*n])).unwrap_or(coordinates[idx])
+                })
+                .collect();
+
+            let mut placed: Vec<f64> = Vec::with_capacity(rank.len());
+            for x in &wanted {
+                placed.push(match placed.last() {
+                    Some(previous) => x.max(previous + spacing),
+                    None => *x,
+                });
+            }
+
+            // Pushing the nodes apart shifted them to the right, which is compensated
+            let shift = (wanted.iter().sum::<f64>() - placed.iter().sum::<f64>())
+                / rank.len().max(1) as f64;
+
+            for (&idx, x) in rank.iter().zip(placed) {
+                coordinates[idx] = x + shift;
+            }
+        }
+    }
+
+    let along = layered
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, node)| Some((node.node?, coordinates[idx])))
+        .collect();
+
+    (along, levels)
+}
+
+fn mean<I: Iterator<Item = f64>>(values: I) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+
+    if count == 0 {
+        None
+    } else {
+        Some(sum / f64::from(count))
+    }
+}
+
+// Returns the position of a node at `x` along its rank and `y` along the levels, given the
+// direction in which the levels follow each other
+fn place(x: f64, y: f64, direction: Direction) -> Position {
+    match direction {
+        Direction::UD => Position { x, y },
+        Direction::DU => Position { x, y: -y },
+        Direction::LR => Position { x: y, y: x },
+        Direction::RL => Position { x: -y, y: x },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACING: f64 = 50.0;
+    const SEPARATION: f64 = 100.0;
+
+    fn options() -> LayoutOptions {
+        LayoutOptions {
+            direction: Direction::UD,
+            level_separation: SEPARATION as u32,
+            node_spacing: SPACING as u32,
+            algorithm: LayoutAlgorithm::Layered,
+        }
+    }
+
+    fn graph(nodes: &[(&str, f64, usize)], edges: &[(&str, &str)]) -> LayoutGraph {
+        LayoutGraph {
+            nodes: nodes
+                .iter()
+                .map(|(id, level, group)| LayoutNode {
+                    id: id.to_string(),
+                    level: *level,
+                    group: *group,
+                })
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|(from, to)| LayoutEdge {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn lays_out_a_fork_around_its_parent() {
+        // The edges go from the children to their parents, like in the DAGs
+        let graph = graph(
+            &[("$a", 0.0, 0), ("$b", 1.0, 0), ("$c", 1.0, 0)],
+            &[("$b", "$a"), ("$c", "$a")],
+        );
+        let positions = layered_positions(&graph, &options());
+
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions["$a"].y, 0.0);
+        assert_eq!(positions["$b"].y, SEPARATION);
+        assert_eq!(positions["$c"].y, SEPARATION);
+        assert!((positions["$b"].x - positions["$c"].x).abs() >= SPACING);
+        assert_eq!(
+            positions["$a"].x,
+            (positions["$b"].x + positions["$c"].x) / 2.0
+        );
+    }
+
+    #[test]
+    fn lays_out_a_merge_below_its_parents() {
+        let graph = graph(
+            &[("$a", 0.0, 0), ("$b", 0.0, 0), ("$c", 1.0, 0)],
+            &[("$c", "$a"), ("$c", "$b")],
+        );
+        let positions = layered_positions(&graph, &options());
+
+        assert_eq!(positions["$a"].y, 0.0);
+        assert_eq!(positions["$b"].y, 0.0);
+        assert_eq!(positions["$c"].y, SEPARATION);
+        assert!((positions["$a"].x - positions["$b"].x).abs() >= SPACING);
+        assert_eq!(
+            positions["$c"].x,
+            (positions["$a"].x + positions["$b"].x) / 2.0
+        );
+    }
+
+    #[test]
+    fn keeps_the_disconnected_components_apart() {
+        // Two chains sharing their levels, and a third one in another group
+        let graph = graph(
+            &[
+                ("$a1", 0.0, 0),
+                ("$a2", 1.0, 0),
+                ("$b1", 0.0, 0),
+                ("$b2", 1.0, 0),
+                ("$c1", 0.0, 1),
+                ("$c2", 1.0, 1),
+            ],
+            &[("$a2", "$a1"), ("$b2", "$b1"), ("$c2", "$c1")],
+        );
+        let positions = layered_positions(&graph, &options());
+
+        // Each chain is drawn straight, the chains not overlapping
+        for (first, second) in &[("$a1", "$a2"), ("$b1", "$b2"), ("$c1", "$c2")] {
+            assert_eq!(positions[*first].x, positions[*second].x);
+        }
+        assert!((positions["$a1"].x - positions["$b1"].x).abs() >= SPACING);
+
+        // The group is laid out to the right of the previous one
+        let first_group = positions["$a1"].x.max(positions["$b1"].x);
+        assert!(positions["$c1"].x >= first_group + SPACING);
+    }
+}
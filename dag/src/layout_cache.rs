@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::layout_options::LayoutOptions;
+
+/// The maximum number of layouts kept by a `LayoutCache`, the least recently used one being
+/// dropped first. A layout of a room of 100k events weighs a few MB.
+pub const MAX_CACHED_LAYOUTS: usize = 4;
+
+/// The position of a node in a vis.js network.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CachedLayout {
+    key: String, // See `node_set_key`
+    layout_options: LayoutOptions,
+    positions: HashMap<String, Position>,
+}
+
+/// The positions of the nodes of the networks once laid out by vis.js, so that a network showing
+/// the same nodes with the same layout options can be drawn at once instead of being laid out
+/// again, which takes tens of seconds on big rooms. The layouts are keyed by the set of the IDs
+/// of their nodes, see `node_set_key`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct LayoutCache {
+    layouts: Vec<CachedLayout>, // From the least to the most recently used
+    #[serde(skip)]
+    unsaved: bool, // Whether a layout was added since the cache was last saved
+}
+
+impl LayoutCache {
+    pub fn new() -> Self {
+        LayoutCache::default()
+    }
+
+    /// Returns the positions of the nodes of the layout `key` computed with `layout_options`, if
+    /// it is cached, and makes it the most recently used one.
+    pub fn get(
+        &mut self,
+        key: &str,
+        layout_options: LayoutOptions,
+    ) -> Option<&HashMap<String, Position>> {
+        let idx = self
+            .layouts
+            .iter()
+            .position(|l| l.key == key && l.layout_options == layout_options)?;
+
+        let layout = self.layouts.remove(idx);
+        self.layouts.push(layout);
+
+        self.layouts.last().map(|l| &l.positions)
+    }
+
+    /// Caches the positions of the nodes of the layout `key` computed with `layout_options`,
+    /// replacing any previous ones and dropping the least recently used layout if there are too
+    /// many.
+    pub fn insert(
+        &mut self,
+        key: String,
+        layout_options: LayoutOptions,
+        positions: HashMap<String, Position>,
+    ) {
+        self.layouts
+            .retain(|l| l.key != key || l.layout_options != layout_options);
+        self.layouts.push(CachedLayout {
+            key,
+            layout_options,
+            positions,
+        });
+
+        if self.layouts.len() > MAX_CACHED_LAYOUTS {
+            self.layouts.remove(0);
+        }
+
+        self.unsaved = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.layouts.clear();
+        self.unsaved = true;
+    }
+
+    /// Tells whether the cache changed since this was last called, i.e. whether it needs to be
+    /// saved again.
+    pub fn take_unsaved(&mut self) -> bool {
+        std::mem::replace(&mut self.unsaved, false)
+    }
+}
+
+/// Returns the key of the layout of the nodes whose IDs are `ids`, whatever their order: the
+/// SHA-256 of the sorted IDs, in unpadded URL-safe base64.
+pub fn node_set_key<'a, I>(ids: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut ids: Vec<&str> = ids.into_iter().collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut hasher = Sha256::new();
+    for id in ids {
+        hasher.update(id.as_bytes());
+        hasher.update(b"\n");
+    }
+
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
@@ -0,0 +1,69 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::layout::LayoutAlgorithm;
+
+/// The direction in which the levels of the hierarchical layout of vis.js follow each other, i.e.
+/// in which the depths grow.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Direction {
+    #[default]
+    UD, // From the top to the bottom
+    DU, // From the bottom to the top
+    LR, // From the left to the right
+    RL, // From the right to the left
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::UD, Direction::DU, Direction::LR, Direction::RL];
+
+    /// The name of the direction in the options of vis.js.
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::UD => "UD",
+            Direction::DU => "DU",
+            Direction::LR => "LR",
+            Direction::RL => "RL",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Direction::UD => "Top to bottom",
+            Direction::DU => "Bottom to top",
+            Direction::LR => "Left to right",
+            Direction::RL => "Right to left",
+        }
+    }
+
+    /// Whether the levels follow each other horizontally.
+    pub fn is_horizontal(self) -> bool {
+        self == Direction::LR || self == Direction::RL
+    }
+
+    /// Whether the levels grow towards the top or the left of the screen.
+    pub fn is_reversed(self) -> bool {
+        self == Direction::DU || self == Direction::RL
+    }
+}
+
+/// The options of the hierarchical layout with which the DAG of a view is drawn.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LayoutOptions {
+    #[serde(default)]
+    pub direction: Direction,
+    pub level_separation: u32, // The distance between two levels, i.e. two depths, in pixels
+    pub node_spacing: u32, // The minimum distance between two nodes of the same level, in pixels
+    #[serde(default)]
+    pub algorithm: LayoutAlgorithm,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        LayoutOptions {
+            direction: Direction::default(),
+            level_separation: 250,
+            node_spacing: 500,
+            algorithm: LayoutAlgorithm::default(),
+        }
+    }
+}
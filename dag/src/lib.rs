@@ -0,0 +1,43 @@
+//! The model of the events DAG of a Matrix room: the DAG itself, built from the events in their
+//! JSON form, and everything computed from it, down to the data sets drawn by vis.js.
+//!
+//! It is shared by the web application and by `matrix-vis-cli`, and doesn't depend on anything
+//! specific to the browser.
+
+pub mod annotation;
+pub mod authority;
+pub mod clock_skew;
+pub mod cs_api;
+pub mod dag;
+pub mod dataset;
+pub mod depth_anomaly;
+pub mod event;
+pub mod event_table;
+pub mod export;
+pub mod expression;
+pub mod genealogy;
+pub mod id_shortener;
+pub mod inspector;
+pub mod interner;
+pub mod journal;
+pub mod label_format;
+pub mod layout;
+pub mod layout_cache;
+pub mod layout_options;
+pub mod metrics;
+pub mod outline;
+pub mod preset;
+pub mod progress;
+pub mod provenance;
+pub mod query;
+pub mod receipts;
+pub mod reconcile;
+pub mod reference_hash;
+pub mod room_version;
+pub mod snapshot;
+pub mod source;
+pub mod standalone;
+pub mod state_timeline;
+pub mod stats;
+pub mod theme;
+pub mod workspace;
@@ -0,0 +1,53 @@
+use serde_derive::Serialize;
+
+/// Measures of how tangled the history of a room got: the more forks, merges and concurrent
+/// branches, the more the servers disagreed on the latest events at some point.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DagMetrics {
+    pub fork_points: usize,  // Number of events with more than one child
+    pub merge_events: usize, // Number of events with more than one parent in the DAG
+    pub max_concurrent_branches: usize, // Largest number of events at the same depth
+    pub widest_depth: Option<i64>, // The depth at which there are that many events
+    pub longest_fork: Option<Fork>, // The fork whose branches took the most depths to merge again
+}
+
+/// A fork of the DAG, from the event with several children to the first event descending from
+/// more than one of its branches.
+#[derive(Clone, Debug, Serialize)]
+pub struct Fork {
+    pub fork_point: String,          // The ID of the event the branches start from
+    pub merge_point: Option<String>, // The ID of the event where the branches merge, if they do
+    pub length: i64, // The number of depths between the two, or until the latest depth if the branches haven't merged yet
+}
+
+impl DagMetrics {
+    /// Describes the metrics in a few lines, e.g. for the report of the frontend.
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Fork points: {}", self.fork_points),
+            format!("Merge events: {}", self.merge_events),
+        ];
+
+        if let Some(depth) = self.widest_depth {
+            lines.push(format!(
+                "Concurrent branches: at most {} (at depth {})",
+                self.max_concurrent_branches, depth
+            ));
+        }
+
+        if let Some(fork) = &self.longest_fork {
+            lines.push(match &fork.merge_point {
+                Some(merge_point) => format!(
+                    "Longest fork: {} depths, from {} to {}",
+                    fork.length, fork.fork_point, merge_point
+                ),
+                None => format!(
+                    "Longest fork: {} depths from {}, still unmerged",
+                    fork.length, fork.fork_point
+                ),
+            });
+        }
+
+        lines
+    }
+}
@@ -0,0 +1,61 @@
+/// What makes an event of the text outline stand out from the chain it is part of.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OutlineMarker {
+    Fork(usize),        // The number of children of the event
+    Merge(Vec<String>), // The shortened IDs of the parents of the event
+    Orphan(usize),      // The number of `prev_events` of the event which are not in the DAG
+    Latest,             // The event has no children yet
+}
+
+impl OutlineMarker {
+    pub fn description(&self) -> String {
+        match self {
+            OutlineMarker::Fork(children) => format!("fork: {} children", children),
+            OutlineMarker::Merge(parents) => format!("merge of {}", parents.join(", ")),
+            OutlineMarker::Orphan(missing) => format!("orphan: {} missing ancestors", missing),
+            OutlineMarker::Latest => "latest".to_string(),
+        }
+    }
+}
+
+/// An event of the text outline of a DAG, which lists the events by depth rather than drawing
+/// them, so that they can be read with a screen reader, searched with the browser and copied.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OutlineEntry {
+    pub event_id: String,
+    pub short_id: String, // The ID as shortened by the `IdShortener` of the DAG
+    pub depth: i64,
+    pub branch: usize, // How far the event is indented: 0 on the main chain, one more for each fork away from it
+    pub etype: String,
+    pub sender: String,
+    pub markers: Vec<OutlineMarker>,
+}
+
+impl OutlineEntry {
+    /// Describes the event on a single line, e.g.
+    /// `$abc (m.room.message from @alice:example.org) [fork: 2 children]`.
+    pub fn description(&self) -> String {
+        let mut description = format!("{} ({} from {})", self.short_id, self.etype, self.sender);
+
+        for marker in &self.markers {
+            description.push_str(&format!(" [{}]", marker.description()));
+        }
+
+        description
+    }
+}
+
+/// Groups the entries of an outline, already in depth order, by depth.
+pub fn by_depth(entries: &[OutlineEntry]) -> Vec<(i64, &[OutlineEntry])> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+
+    for end in 1..=entries.len() {
+        if end == entries.len() || entries[end].depth != entries[start].depth {
+            groups.push((entries[start].depth, &entries[start..end]));
+            start = end;
+        }
+    }
+
+    groups
+}
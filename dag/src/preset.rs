@@ -0,0 +1,88 @@
+use std::collections::{BTreeSet, HashSet};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::event::Field;
+use super::expression::ColourRule;
+use super::id_shortener::IdShortener;
+use super::label_format::LabelFormat;
+use super::theme::Theme;
+
+/// The display configuration of the application, which can be exported as a small JSON document
+/// and imported back so that the same way of viewing DAGs can be shared across users.
+///
+/// The palette and the filters are optional, so that the presets exported before they were part
+/// of it leave them as they are when they are imported.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DisplayPreset {
+    #[serde(default)]
+    pub fields: BTreeSet<Field>, // Events fields included in the labels of the nodes, sorted so that the same preset is always exported the same way
+    #[serde(default)]
+    pub id_shortener: IdShortener, // How the event IDs are shortened when they are displayed
+    #[serde(default)]
+    pub label_format: LabelFormat, // How the fields are laid out in the labels of the nodes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<PresetPalette>, // How the nodes are coloured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filters: Option<PresetFilters>, // Which events and edges are shown, and how they are laid out
+}
+
+/// The colours of a display preset.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PresetPalette {
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub colour_rules: Vec<ColourRule>, // Applied in order, the first matching rule colouring the node
+    #[serde(default)]
+    pub color_by_latency: bool,
+    #[serde(default)]
+    pub color_by_batch: bool,
+}
+
+/// The toggles of a display preset which change the events and the edges drawn.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PresetFilters {
+    #[serde(default)]
+    pub show_relations: bool,
+    #[serde(default)]
+    pub show_ghosts: bool,
+    #[serde(default)]
+    pub show_authority: bool,
+    #[serde(default)]
+    pub show_read_markers: bool,
+    #[serde(default)]
+    pub compress_depths: bool,
+    #[serde(default)]
+    pub transitive_reduction: bool,
+}
+
+impl DisplayPreset {
+    pub fn new(
+        fields: &HashSet<Field>,
+        id_shortener: IdShortener,
+        label_format: LabelFormat,
+    ) -> DisplayPreset {
+        DisplayPreset {
+            fields: fields.iter().cloned().collect(),
+            id_shortener,
+            label_format,
+            palette: None,
+            filters: None,
+        }
+    }
+
+    /// Serializes the preset into a JSON string.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize the preset")
+    }
+
+    /// Parses a preset previously exported with `to_json`.
+    pub fn from_json(json: &str) -> Result<DisplayPreset, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn field_set(&self) -> HashSet<Field> {
+        self.fields.iter().cloned().collect()
+    }
+}
@@ -0,0 +1,54 @@
+/// The progress of a backfill made of many requests, e.g. filling every gap of a DAG, so that
+/// the user can tell whether it is worth waiting for.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BackfillProgress {
+    pub requests: usize,            // The number of requests answered so far
+    pub events_fetched: usize,      // The number of events they brought which weren't in the DAG
+    pub reached_depth: Option<i64>, // The lowest depth of the DAG, if it has events
+    pub remaining: usize, // The number of regions still to fetch, e.g. the gaps left, as far as is known
+}
+
+impl BackfillProgress {
+    pub fn new(reached_depth: Option<i64>, remaining: usize) -> Self {
+        BackfillProgress {
+            requests: 0,
+            events_fetched: 0,
+            reached_depth,
+            remaining,
+        }
+    }
+
+    /// Records a response which brought `new_events` events, after which the lowest depth of the
+    /// DAG is `reached_depth` and `remaining` regions are left to fetch.
+    pub fn record(&mut self, new_events: usize, reached_depth: Option<i64>, remaining: usize) {
+        self.requests += 1;
+        self.events_fetched += new_events;
+        self.reached_depth = reached_depth;
+        self.remaining = remaining;
+    }
+
+    /// How much of the backfill is done, between 0 and 1. The regions left to fetch can reveal
+    /// new ones, so this is an estimate which can go back.
+    pub fn fraction(&self) -> f64 {
+        if self.requests + self.remaining == 0 {
+            1.0
+        } else {
+            self.requests as f64 / (self.requests + self.remaining) as f64
+        }
+    }
+
+    /// Describes the progress in a few words, e.g. for the progress bar.
+    pub fn description(&self) -> String {
+        let mut description = format!(
+            "{} requests, {} events fetched",
+            self.requests, self.events_fetched
+        );
+
+        if let Some(depth) = self.reached_depth {
+            description.push_str(&format!(", depth {} reached", depth));
+        }
+        description.push_str(&format!(", {} left", self.remaining));
+
+        description
+    }
+}
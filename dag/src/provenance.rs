@@ -0,0 +1,35 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// A request which brought events to a DAG, e.g. a `/sync` or a page of `/messages`. Each event
+/// keeps the batch it first came in, which helps debugging the pagination and understanding in
+/// which order the servers give the events.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FetchBatch {
+    pub endpoint: String, // The endpoint the request was made to, e.g. `/messages`
+    pub token: Option<String>, // The pagination token or the event the request started from, if any
+    pub requested_at: i64, // When the request was made, in ms since the epoch
+    #[serde(default)]
+    pub received_at: Option<i64>, // When its response was received, in ms since the epoch, unknown for the batches saved before it was recorded
+    #[serde(default)]
+    pub events: usize, // The number of events which first came in this batch
+}
+
+impl FetchBatch {
+    pub fn new(endpoint: &str, token: Option<String>, requested_at: i64, received_at: i64) -> Self {
+        FetchBatch {
+            endpoint: endpoint.to_string(),
+            token,
+            requested_at,
+            received_at: Some(received_at),
+            events: 0,
+        }
+    }
+
+    /// Describes the batch in a few words, e.g. for the tooltips of the nodes.
+    pub fn description(&self) -> String {
+        match &self.token {
+            Some(token) => format!("{} from {}", self.endpoint, token),
+            None => self.endpoint.clone(),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::event::Event;
+
+/// The criteria of a query selecting events in bulk, see `RoomEvents::select_where`. An event
+/// matches if it meets every criterion which is set, so the empty filter matches every event.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EventFilter {
+    pub sender: Option<String>, // The exact ID of the sender
+    pub etype: Option<String>, // The type of the events, a trailing `*` matching any suffix, e.g. `m.room.*`
+    pub origin: Option<String>, // The server which created the events
+    pub body: Option<String>,  // Some text found in the `body` of the content, whatever the case
+}
+
+impl EventFilter {
+    /// Whether no criterion is set.
+    pub fn is_empty(&self) -> bool {
+        self.sender.is_none()
+            && self.etype.is_none()
+            && self.origin.is_none()
+            && self.body.is_none()
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        if let Some(sender) = &self.sender {
+            if event.get_sender() != sender {
+                return false;
+            }
+        }
+
+        if let Some(etype) = &self.etype {
+            let matches = match etype.strip_suffix('*') {
+                Some(prefix) => event.get_type().starts_with(prefix),
+                None => event.get_type() == etype,
+            };
+
+            if !matches {
+                return false;
+            }
+        }
+
+        if let Some(origin) = &self.origin {
+            if event.get_origin() != origin {
+                return false;
+            }
+        }
+
+        if let Some(text) = &self.body {
+            let body = event.get_content()["body"].as_str().unwrap_or_default();
+
+            if !body.to_lowercase().contains(&text.to_lowercase()) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Describes the criteria, e.g. for the activity log.
+    pub fn description(&self) -> String {
+        let mut criteria = Vec::new();
+
+        if let Some(sender) = &self.sender {
+            criteria.push(format!("sent by {}", sender));
+        }
+        if let Some(etype) = &self.etype {
+            criteria.push(format!("of type {}", etype));
+        }
+        if let Some(origin) = &self.origin {
+            criteria.push(format!("created by {}", origin));
+        }
+        if let Some(body) = &self.body {
+            criteria.push(format!("whose body contains \"{}\"", body));
+        }
+
+        if criteria.is_empty() {
+            "every event".to_string()
+        } else {
+            format!("the events {}", criteria.join(" and "))
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value as JsonValue;
+
+/// The kinds of markers which tell how far a user has read a room.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum MarkerKind {
+    Read,        // A public read receipt, `m.read`
+    ReadPrivate, // A private read receipt, `m.read.private`, only given to its user
+    FullyRead,   // The fully-read marker, `m.fully_read`, kept in the account data of its user
+}
+
+impl MarkerKind {
+    fn from_receipt_type(receipt_type: &str) -> Option<MarkerKind> {
+        match receipt_type {
+            "m.read" => Some(MarkerKind::Read),
+            "m.read.private" => Some(MarkerKind::ReadPrivate),
+            _ => None,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            MarkerKind::Read => "read receipt",
+            MarkerKind::ReadPrivate => "private read receipt",
+            MarkerKind::FullyRead => "fully-read marker",
+        }
+    }
+}
+
+/// The event a user's read receipt or fully-read marker currently points to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadMarker {
+    pub user_id: String,
+    pub kind: MarkerKind,
+    pub thread_id: Option<String>, // The thread of a threaded receipt, `main` for the main timeline
+    pub event_id: String,
+    pub ts: Option<i64>, // When the receipt was sent, in ms since the epoch
+}
+
+impl ReadMarker {
+    /// Describes the marker in a few words, e.g. for the tooltips of the nodes.
+    pub fn description(&self) -> String {
+        match &self.thread_id {
+            Some(thread_id) => format!(
+                "{} of {} in thread {}",
+                self.kind.description(),
+                self.user_id,
+                thread_id
+            ),
+            None => format!("{} of {}", self.kind.description(), self.user_id),
+        }
+    }
+}
+
+/// The latest read receipts and fully-read markers of the users of a room, as given by `/sync`.
+///
+/// The receipts in `/sync` only tell which markers moved since the previous sync, so each of them
+/// replaces the previous marker of its user, kind and thread.
+#[derive(Clone, Debug, Default)]
+pub struct ReadMarkers {
+    markers: BTreeMap<(String, MarkerKind, Option<String>), ReadMarker>,
+}
+
+impl ReadMarkers {
+    pub fn new() -> Self {
+        ReadMarkers::default()
+    }
+
+    /// Updates the markers with the `m.receipt` events among the ephemeral events of a room in a
+    /// `/sync` response, and returns whether any of them moved.
+    pub fn update_from_ephemeral(&mut self, events: &[JsonValue]) -> bool {
+        let mut moved = false;
+
+        for ev in events.iter().filter(|ev| ev["type"] == "m.receipt") {
+            let content = match ev["content"].as_object() {
+                Some(content) => content,
+                None => continue,
+            };
+
+            for (event_id, receipt_types) in content {
+                let receipt_types = match receipt_types.as_object() {
+                    Some(receipt_types) => receipt_types,
+                    None => continue,
+                };
+
+                for (receipt_type, users) in receipt_types {
+                    let kind = match MarkerKind::from_receipt_type(receipt_type) {
+                        Some(kind) => kind,
+                        None => continue,
+                    };
+
+                    for (user_id, receipt) in users.as_object().into_iter().flatten() {
+                        moved |= self.set(ReadMarker {
+                            user_id: user_id.clone(),
+                            kind,
+                            thread_id: receipt["thread_id"].as_str().map(str::to_string),
+                            event_id: event_id.clone(),
+                            ts: receipt["ts"].as_i64(),
+                        });
+                    }
+                }
+            }
+        }
+
+        moved
+    }
+
+    /// Updates the fully-read marker of `user_id` with the `m.fully_read` event among the room
+    /// account data events of a `/sync` response made by this user, and returns whether it moved.
+    pub fn update_from_account_data(&mut self, user_id: &str, events: &[JsonValue]) -> bool {
+        let mut moved = false;
+
+        for ev in events.iter().filter(|ev| ev["type"] == "m.fully_read") {
+            if let Some(event_id) = ev["content"]["event_id"].as_str() {
+                moved |= self.set(ReadMarker {
+                    user_id: user_id.to_string(),
+                    kind: MarkerKind::FullyRead,
+                    thread_id: None,
+                    event_id: event_id.to_string(),
+                    ts: None,
+                });
+            }
+        }
+
+        moved
+    }
+
+    // Replaces the previous marker of the user, kind and thread of `marker`, and returns whether
+    // it moved
+    fn set(&mut self, marker: ReadMarker) -> bool {
+        let key = (
+            marker.user_id.clone(),
+            marker.kind,
+            marker.thread_id.clone(),
+        );
+
+        if self.markers.get(&key) == Some(&marker) {
+            return false;
+        }
+
+        self.markers.insert(key, marker);
+
+        true
+    }
+
+    /// Adds the markers of `other` which are not known here, e.g. to show in a merged DAG the
+    /// markers given by each server.
+    pub fn extend(&mut self, other: &ReadMarkers) {
+        for (key, marker) in &other.markers {
+            self.markers
+                .entry(key.clone())
+                .or_insert_with(|| marker.clone());
+        }
+    }
+
+    /// Returns the markers pointing to the event `event_id`.
+    pub fn on_event<'a>(&'a self, event_id: &'a str) -> impl Iterator<Item = &'a ReadMarker> {
+        self.markers
+            .values()
+            .filter(move |marker| marker.event_id == event_id)
+    }
+
+    /// Returns every marker, ordered by user, kind and thread.
+    pub fn iter(&self) -> impl Iterator<Item = &ReadMarker> {
+        self.markers.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+}
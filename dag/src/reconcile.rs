@@ -0,0 +1,294 @@
+use std::collections::{HashSet, VecDeque};
+
+/// The number of deliveries remembered by default by a `Reconciler`.
+pub const DEFAULT_DEDUP_WINDOW: usize = 1000;
+
+/// Reconciles the events delivered to a DAG by streams running concurrently (the live sync, the
+/// backfill, the filling of the gaps...), which overlap when the backfill reaches events the sync
+/// has already delivered or the other way around.
+///
+/// The events already in the DAG are always suppressed, but an event can also be delivered again
+/// before it made it into the DAG, or without ever making it (if it couldn't be parsed). The
+/// reconciler therefore remembers the IDs of the last `window` events delivered, so that these
+/// deliveries are suppressed as well instead of being reported twice.
+#[derive(Clone)]
+pub struct Reconciler {
+    window: usize, // The number of deliveries remembered, 0 to only rely on the DAG
+    recent: VecDeque<String>, // The IDs of the events delivered recently, oldest first
+    recent_ids: HashSet<String>, // The same IDs, to look them up quickly
+    suppressed: usize, // The number of deliveries suppressed so far
+}
+
+impl Reconciler {
+    pub fn new(window: usize) -> Self {
+        Reconciler {
+            window,
+            recent: VecDeque::with_capacity(window),
+            recent_ids: HashSet::with_capacity(window),
+            suppressed: 0,
+        }
+    }
+
+    /// Changes the number of deliveries remembered, forgetting the oldest ones if it shrinks.
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window;
+        self.forget_oldest();
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Returns the number of deliveries which were suppressed so far.
+    pub fn suppressed(&self) -> usize {
+        self.suppressed
+    }
+
+    /// Tells whether the delivery of the event `id` must be processed, given whether the event
+    /// is already in the DAG (`known`), and remembers it.
+    pub fn accept(&mut self, id: &str, known: bool) -> bool {
+        if known || self.recent_ids.contains(id) {
+            self.suppressed += 1;
+            return false;
+        }
+
+        if self.window > 0 {
+            self.recent.push_back(id.to_string());
+            self.recent_ids.insert(id.to_string());
+            self.forget_oldest();
+        }
+
+        true
+    }
+
+    /// Forgets the delivery of the event `id`, e.g. because an undo removed it from the DAG, so
+    /// that it isn't suppressed when it is delivered again.
+    pub fn forget(&mut self, id: &str) {
+        if self.recent_ids.remove(id) {
+            self.recent.retain(|recent| recent != id);
+        }
+    }
+
+    fn forget_oldest(&mut self) {
+        while self.recent.len() > self.window {
+            if let Some(id) = self.recent.pop_front() {
+                self.recent_ids.remove(&id);
+            }
+        }
+    }
+}
+
+impl Default for Reconciler {
+    fn default() -> Self {
+        Reconciler::new(DEFAULT_DEDUP_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use serde_json::{json, Value as JsonValue};
+
+    use super::*;
+    use crate::dag::RoomEvents;
+    use crate::dataset::DataSet;
+    use crate::event::Field;
+
+    #[test]
+    fn accepts_the_events_in_any_order() {
+        let mut reconciler = Reconciler::new(10);
+
+        // The backfill delivers the earlier events after the sync delivered the later ones
+        assert!(reconciler.accept("$3", false));
+        assert!(reconciler.accept("$1", false));
+        assert!(reconciler.accept("$2", false));
+        assert_eq!(reconciler.suppressed(), 0);
+    }
+
+    #[test]
+    fn suppresses_the_interleaved_duplicates() {
+        let mut reconciler = Reconciler::new(10);
+
+        assert!(reconciler.accept("$1", false));
+        assert!(reconciler.accept("$2", false));
+        assert!(!reconciler.accept("$1", false));
+        assert!(reconciler.accept("$3", false));
+        assert!(!reconciler.accept("$2", false));
+        assert_eq!(reconciler.suppressed(), 2);
+    }
+
+    #[test]
+    fn suppresses_the_events_already_in_the_dag() {
+        let mut reconciler = Reconciler::new(0);
+
+        assert!(!reconciler.accept("$1", true));
+        assert!(reconciler.accept("$2", false));
+        // Nothing is remembered without a window
+        assert!(reconciler.accept("$2", false));
+        assert_eq!(reconciler.suppressed(), 1);
+    }
+
+    #[test]
+    fn evicts_the_oldest_deliveries() {
+        let mut reconciler = Reconciler::new(2);
+
+        assert!(reconciler.accept("$1", false));
+        assert!(reconciler.accept("$2", false));
+        assert!(reconciler.accept("$3", false));
+        // `$1` has left the window
+        assert!(reconciler.accept("$1", false));
+        assert!(!reconciler.accept("$3", false));
+
+        reconciler.set_window(1);
+        assert!(reconciler.accept("$3", false));
+        assert!(!reconciler.accept("$3", false));
+    }
+
+    #[test]
+    fn accepts_the_forgotten_events_again() {
+        let mut reconciler = Reconciler::new(10);
+
+        assert!(reconciler.accept("$1", false));
+        assert!(reconciler.accept("$2", false));
+        reconciler.forget("$1");
+
+        assert!(reconciler.accept("$1", false));
+        assert!(!reconciler.accept("$2", false));
+    }
+
+    // The tests below deliver the events through `RoomEvents::add_events`, as the live sync and
+    // the backfill do, the events `$1` to `$6` forming a chain
+
+    fn id(i: usize) -> String {
+        format!("${}:example.org", i)
+    }
+
+    fn event(i: usize) -> JsonValue {
+        let prev_events: Vec<String> = if i > 1 { vec![id(i - 1)] } else { Vec::new() };
+
+        json!({
+            "room_id": "!room:example.org",
+            "sender": "@alice:example.org",
+            "origin": "example.org",
+            "origin_server_ts": 1_500_000_000_000i64 + i as i64,
+            "type": "m.room.message",
+            "content": { "msgtype": "m.text", "body": format!("Message {}", i) },
+            "prev_events": prev_events,
+            "depth": i,
+            "auth_events": [],
+            "event_id": id(i),
+            "hashes": { "sha256": "" },
+            "signatures": {},
+        })
+    }
+
+    fn batch(ids: &[usize]) -> Vec<JsonValue> {
+        ids.iter().map(|i| event(*i)).collect()
+    }
+
+    fn fields() -> HashSet<Field> {
+        [Field::EventID].iter().cloned().collect()
+    }
+
+    // Returns the data set the view draws once `events` is added to `dag`: the earlier and the
+    // newer events are drawn only when the extremities of the DAG move, like in the web
+    // application
+    fn delta(dag: &mut RoomEvents, events: Vec<JsonValue>) -> DataSet {
+        let earliest_events = dag.earliest_events.clone();
+        let latest_events = dag.latest_events.clone();
+
+        dag.add_events(events);
+
+        let mut delta = DataSet::new();
+
+        if dag.earliest_events != earliest_events {
+            dag.add_earlier_events_to_data_set(&mut delta, earliest_events, &fields());
+        }
+        if dag.latest_events != latest_events {
+            dag.add_new_events_to_data_set(&mut delta, latest_events, &fields());
+        }
+
+        delta
+    }
+
+    #[test]
+    fn adds_the_interleaved_deliveries_once() {
+        let mut dag = RoomEvents::from_events("!room:example.org", "example.org", &Vec::new());
+
+        // The live sync and the backfill overlap, and the sync repeats its last event
+        let outcome = dag.add_events(batch(&[3, 4]));
+        assert_eq!((outcome.added, outcome.skipped), (2, 0));
+
+        let outcome = dag.add_events(batch(&[5, 4, 5]));
+        assert_eq!((outcome.added, outcome.skipped), (1, 2));
+
+        let outcome = dag.add_events(batch(&[2, 3]));
+        assert_eq!((outcome.added, outcome.skipped), (1, 1));
+
+        let outcome = dag.add_events(batch(&[1, 5, 2, 6]));
+        assert_eq!((outcome.added, outcome.skipped), (2, 2));
+
+        let outcome = dag.add_events(batch(&[6, 1]));
+        assert!(outcome.is_redundant());
+
+        assert_eq!(dag.event_count(), 6);
+        assert_eq!(dag.suppressed_duplicates(), 7);
+        assert_eq!(dag.earliest_events, vec![id(1)]);
+        assert_eq!(dag.latest_events, vec![id(6)]);
+
+        for i in 2..=6 {
+            assert_eq!(dag.parents_of(&id(i)), vec![id(i - 1)]);
+        }
+
+        let data_set = dag.create_data_set(&fields());
+        let nodes: HashSet<&str> = data_set.nodes.iter().map(|n| n.id.as_str()).collect();
+        let edges: HashSet<(&str, &str)> = data_set
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str()))
+            .collect();
+
+        assert_eq!(data_set.nodes.len(), 6);
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(data_set.edges.len(), 5);
+        assert_eq!(edges.len(), 5);
+    }
+
+    #[test]
+    fn draws_each_delivered_event_once() {
+        let mut dag = RoomEvents::from_events("!room:example.org", "example.org", &Vec::new());
+        dag.add_events(batch(&[3, 4]));
+
+        let initial = dag.create_data_set(&fields());
+        let mut nodes: Vec<String> = initial.nodes.iter().map(|n| n.id.clone()).collect();
+        let mut edges: Vec<String> = initial.edges.iter().map(|e| e.id.clone()).collect();
+
+        let batches: [&[usize]; 5] = [&[5, 4, 5], &[2, 3], &[3, 4], &[1, 5, 2, 6], &[6, 1]];
+        let mut drawn_deltas = 0;
+
+        for ids in batches.iter() {
+            let delta = delta(&mut dag, batch(ids));
+
+            if !delta.nodes.is_empty() {
+                drawn_deltas += 1;
+            }
+
+            // Neither the duplicates within a batch nor those of the events already drawn are
+            // drawn again
+            for node in delta.nodes {
+                assert!(!nodes.contains(&node.id), "{} drawn twice", node.id);
+                nodes.push(node.id);
+            }
+            for edge in delta.edges {
+                assert!(!edges.contains(&edge.id), "{} drawn twice", edge.id);
+                edges.push(edge.id);
+            }
+        }
+
+        // The batches made only of duplicates don't change the data set
+        assert_eq!(drawn_deltas, 3);
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(edges.len(), 5);
+    }
+}
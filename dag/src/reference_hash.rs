@@ -0,0 +1,273 @@
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use serde_json::{Map, Value as JsonValue};
+use sha2::{Digest, Sha256};
+
+// The top-level keys of the events kept by the redaction algorithm in every room version
+const KEPT_KEYS: [&str; 11] = [
+    "type",
+    "room_id",
+    "sender",
+    "state_key",
+    "content",
+    "hashes",
+    "depth",
+    "prev_events",
+    "auth_events",
+    "origin_server_ts",
+    "signatures",
+];
+
+// The top-level keys which are only kept by the redaction algorithm before the room version 11
+const KEPT_KEYS_BEFORE_V11: [&str; 3] = ["origin", "membership", "prev_state"];
+
+// The room version whose rules apply to the unknown versions, e.g. the unstable ones
+pub(crate) const LATEST_ROOM_VERSION: u32 = 11;
+
+/// Returns the ID of the event `pdu` derived from its reference hash, as the room versions 3 and
+/// later define it: the version 3 uses the standard base64 alphabet, the later ones the URL-safe
+/// one. The events of the room versions 1 and 2 carry IDs which aren't derived from their
+/// content, so `None` is returned for them. If the room version isn't known, the rules of the
+/// latest one are used.
+pub fn event_id(pdu: &JsonValue, room_version: Option<&str>) -> Option<String> {
+    let version = version_number(room_version);
+
+    if version <= 2 {
+        return None;
+    }
+
+    let hash = reference_hash(pdu, room_version);
+
+    if version == 3 {
+        Some(format!("${}", STANDARD_NO_PAD.encode(hash)))
+    } else {
+        Some(format!("${}", URL_SAFE_NO_PAD.encode(hash)))
+    }
+}
+
+/// Computes the reference hash of the event `pdu`: the SHA-256 hash of the canonical JSON of the
+/// event once redacted and stripped of its signatures.
+pub fn reference_hash(pdu: &JsonValue, room_version: Option<&str>) -> Vec<u8> {
+    let mut redacted = redact(pdu, room_version);
+
+    if let JsonValue::Object(fields) = &mut redacted {
+        fields.remove("signatures");
+
+        // The ID isn't part of the events whose ID is derived from their content
+        if version_number(room_version) > 2 {
+            fields.remove("event_id");
+        }
+    }
+
+    Sha256::digest(canonical_json(&redacted).as_bytes()).to_vec()
+}
+
+/// Redacts the event `pdu` following the redaction algorithm of its room version, which only
+/// keeps the keys needed to authorise the events.
+pub fn redact(pdu: &JsonValue, room_version: Option<&str>) -> JsonValue {
+    let version = version_number(room_version);
+    let etype = pdu["type"].as_str().unwrap_or_default();
+
+    let mut redacted = Map::new();
+
+    for (key, value) in pdu.as_object().into_iter().flatten() {
+        let kept = KEPT_KEYS.contains(&key.as_str())
+            || key == "event_id"
+            || (version < 11 && KEPT_KEYS_BEFORE_V11.contains(&key.as_str()));
+
+        if kept {
+            redacted.insert(key.clone(), value.clone());
+        }
+    }
+
+    if let Some(JsonValue::Object(content)) = redacted.get_mut("content") {
+        redact_content(content, etype, version);
+    }
+
+    JsonValue::Object(redacted)
+}
+
+// Only keeps the keys of `content` preserved by the redaction algorithm for the events of type
+// `etype` in the room version `version`
+fn redact_content(content: &mut Map<String, JsonValue>, etype: &str, version: u32) {
+    let mut kept: Vec<&str> = match etype {
+        "m.room.member" => vec!["membership"],
+        "m.room.create" if version >= 11 => return, // The whole content is kept
+        "m.room.create" => vec!["creator"],
+        "m.room.join_rules" => vec!["join_rule"],
+        "m.room.power_levels" => vec![
+            "ban",
+            "events",
+            "events_default",
+            "kick",
+            "redact",
+            "state_default",
+            "users",
+            "users_default",
+        ],
+        "m.room.aliases" if version <= 5 => vec!["aliases"],
+        "m.room.history_visibility" => vec!["history_visibility"],
+        "m.room.redaction" if version >= 11 => vec!["redacts"],
+        _ => vec![],
+    };
+
+    match etype {
+        "m.room.member" if version >= 9 => kept.push("join_authorised_via_users_server"),
+        "m.room.join_rules" if version >= 8 => kept.push("allow"),
+        "m.room.power_levels" if version >= 11 => kept.push("invite"),
+        _ => {}
+    }
+
+    // Only the signed part of the third party invites is kept from the room version 11
+    let signed = match etype {
+        "m.room.member" if version >= 11 => content
+            .get("third_party_invite")
+            .and_then(|invite| invite.get("signed"))
+            .cloned(),
+        _ => None,
+    };
+
+    content.retain(|key, _| kept.contains(&key.as_str()));
+
+    if let Some(signed) = signed {
+        let mut invite = Map::new();
+        invite.insert("signed".to_string(), signed);
+
+        content.insert("third_party_invite".to_string(), JsonValue::Object(invite));
+    }
+}
+
+/// Encodes `value` in the canonical JSON of Matrix: the keys of the objects are sorted and there
+/// is no whitespace.
+pub fn canonical_json(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| {
+                    format!(
+                        "{}:{}",
+                        JsonValue::String(key.clone()),
+                        canonical_json(&fields[key])
+                    )
+                })
+                .collect();
+
+            format!("{{{}}}", fields.join(","))
+        }
+        JsonValue::Array(values) => {
+            let values: Vec<String> = values.iter().map(canonical_json).collect();
+
+            format!("[{}]", values.join(","))
+        }
+        value => value.to_string(),
+    }
+}
+
+// Returns the number of the room version `room_version`, the unknown versions being considered
+// as the latest one
+pub(crate) fn version_number(room_version: Option<&str>) -> u32 {
+    room_version
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(LATEST_ROOM_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // The expected IDs were computed independently of this module, from the canonical JSON of the
+    // redacted events with Python's `json`, `hashlib` and `base64`
+
+    fn message() -> JsonValue {
+        json!({
+            "auth_events": ["$aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"],
+            "content": { "body": "Hello", "msgtype": "m.text" },
+            "depth": 12,
+            "hashes": { "sha256": "sJz7Sbn9wnUu0NEnZgbiRhDLcWtXkP8iWQJ2b8PqZBk" },
+            "origin": "example.org",
+            "origin_server_ts": 1_570_000_000_000u64,
+            "prev_events": ["$bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"],
+            "room_id": "!room:example.org",
+            "sender": "@alice:example.org",
+            "signatures": { "example.org": { "ed25519:a_key": "c2lnbmF0dXJl" } },
+            "type": "m.room.message",
+            "unsigned": { "age": 42 },
+        })
+    }
+
+    fn member() -> JsonValue {
+        json!({
+            "auth_events": ["$ccccccccccccccccccccccccccccccccccccccccccc"],
+            "content": { "displayname": "Bob", "membership": "join" },
+            "depth": 3,
+            "hashes": { "sha256": "p3eUkkc2pFUZS3HZH7aaIVJ+a3eH+zwOMGhQMcmuBXI" },
+            "origin": "example.org",
+            "origin_server_ts": 1_600_000_000_000u64,
+            "prev_events": ["$ddddddddddddddddddddddddddddddddddddddddddd"],
+            "room_id": "!room:example.org",
+            "sender": "@bob:example.org",
+            "state_key": "@bob:example.org",
+            "signatures": { "example.org": { "ed25519:a_key": "c2lnbmF0dXJl" } },
+            "type": "m.room.member",
+            "unsigned": { "age": 7 },
+        })
+    }
+
+    #[test]
+    fn keeps_the_ids_of_the_versions_1_and_2() {
+        let mut pdu = message();
+        pdu["event_id"] = json!("$1570000000000abcde:example.org");
+
+        assert_eq!(event_id(&pdu, Some("1")), None);
+        assert_eq!(event_id(&pdu, Some("2")), None);
+
+        // The ID is part of the hash of the events which carry it
+        assert_eq!(redact(&pdu, Some("1"))["event_id"], pdu["event_id"]);
+
+        let mut other = pdu.clone();
+        other["event_id"] = json!("$other:example.org");
+        assert_ne!(
+            reference_hash(&pdu, Some("1")),
+            reference_hash(&other, Some("1"))
+        );
+    }
+
+    #[test]
+    fn derives_the_ids_of_the_version_3_in_standard_base64() {
+        assert_eq!(
+            event_id(&message(), Some("3")).as_deref(),
+            Some("$mxBrXC+/aqQ0uZMCSkx6CSEE31QS0l5gIJb8f0TxPp0")
+        );
+    }
+
+    #[test]
+    fn derives_the_ids_of_the_later_versions_in_url_safe_base64() {
+        let id = "$eT2NJQ6dFSNmCGSZ2TU3cn3_H90gh43MmlWRQS0QF0Y";
+
+        assert_eq!(event_id(&member(), Some("4")).as_deref(), Some(id));
+        assert_eq!(event_id(&member(), Some("10")).as_deref(), Some(id));
+        assert_eq!(
+            event_id(&member(), Some("3")).as_deref(),
+            Some("$eT2NJQ6dFSNmCGSZ2TU3cn3/H90gh43MmlWRQS0QF0Y")
+        );
+    }
+
+    #[test]
+    fn ignores_what_the_redaction_strips() {
+        let mut pdu = member();
+        pdu["content"]["displayname"] = json!("Robert");
+        pdu["unsigned"] = json!({ "age": 1000 });
+        pdu["signatures"] = json!({});
+        pdu["event_id"] = json!("$eT2NJQ6dFSNmCGSZ2TU3cn3_H90gh43MmlWRQS0QF0Y");
+
+        assert_eq!(
+            event_id(&pdu, Some("6")).as_deref(),
+            Some("$eT2NJQ6dFSNmCGSZ2TU3cn3_H90gh43MmlWRQS0QF0Y")
+        );
+    }
+}
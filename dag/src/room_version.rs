@@ -0,0 +1,217 @@
+use serde_json::Value as JsonValue;
+
+use super::event::Event;
+use super::reference_hash::{version_number, LATEST_ROOM_VERSION};
+
+// The largest integer allowed in the canonical JSON of the events from the room version 6
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// How the IDs of the events of a room version are formed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EventIdFormat {
+    Opaque,    // `$opaque:server`, chosen by the origin server (versions 1 and 2)
+    Base64,    // The reference hash in standard base64 (version 3)
+    UrlBase64, // The reference hash in URL-safe base64 (versions 4 and later)
+}
+
+/// Returns the version of a room given by `create_content`, the content of its `m.room.create`
+/// event. The rooms created without a version are version 1 rooms.
+pub fn room_version_of(create_content: &JsonValue) -> String {
+    create_content["room_version"]
+        .as_str()
+        .unwrap_or("1")
+        .to_string()
+}
+
+/// The rules of a room version which tell whether its events can be valid, along with the changes
+/// of the authorization rules worth knowing when investigating them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoomVersionRules {
+    pub version: String,
+    pub known: bool, // Whether the version is a stable one, the others getting the rules of the latest one
+    pub event_id_format: EventIdFormat,
+    number: u32,
+}
+
+/// An event which can't be valid in the version of its room.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionMismatch {
+    pub id: String,
+    pub problem: String,
+}
+
+impl RoomVersionRules {
+    /// Returns the rules of the room version `version`, e.g. `"10"`.
+    pub fn of(version: &str) -> Self {
+        let number = version_number(Some(version));
+        let known = version
+            .parse::<u32>()
+            .map(|n| (1..=LATEST_ROOM_VERSION).contains(&n))
+            .unwrap_or(false);
+
+        let event_id_format = match number {
+            1 | 2 => EventIdFormat::Opaque,
+            3 => EventIdFormat::Base64,
+            _ => EventIdFormat::UrlBase64,
+        };
+
+        RoomVersionRules {
+            version: version.to_string(),
+            known,
+            event_id_format,
+            number,
+        }
+    }
+
+    /// Returns the changes of the authorization rules and of the format of the events brought by
+    /// the version or by the ones before it, most recent first.
+    pub fn hints(&self) -> Vec<&'static str> {
+        let mut hints = Vec::new();
+
+        if !self.known {
+            hints.push("Unknown version, checked with the rules of the latest one");
+        }
+
+        let changes: [(u32, &str); 9] = [
+            (
+                11,
+                "The creator is the sender of the m.room.create event, and redactions keep less",
+            ),
+            (
+                10,
+                "The power levels are integers, and knock_restricted joins are allowed",
+            ),
+            (
+                9,
+                "The redactions keep the authorising user of restricted joins",
+            ),
+            (8, "Restricted joins through the membership of other rooms"),
+            (7, "Knocking"),
+            (
+                6,
+                "Strict canonical JSON, and no notifications power level for @room",
+            ),
+            (5, "The signing keys are only valid until their expiry"),
+            (4, "The event IDs are URL-safe hashes"),
+            (3, "The event IDs are the reference hashes of the events"),
+        ];
+
+        hints.extend(
+            changes
+                .iter()
+                .filter(|(version, _)| self.number >= *version)
+                .map(|(_, change)| *change),
+        );
+
+        hints
+    }
+
+    /// Returns why `event` can't be valid in a room of this version, if it can't.
+    pub fn check(&self, event: &Event) -> Option<String> {
+        let id = &event.event_id;
+
+        match self.event_id_format {
+            EventIdFormat::Opaque if !id.contains(':') => {
+                return Some(format!(
+                    "the ID {} isn't of the form $opaque:server of the version {}",
+                    id, self.version
+                ));
+            }
+            EventIdFormat::Base64 | EventIdFormat::UrlBase64 if id.contains(':') => {
+                return Some(format!(
+                    "the ID {} isn't a hash, as in the version {}",
+                    id, self.version
+                ));
+            }
+            EventIdFormat::Base64 if id.contains('-') || id.contains('_') => {
+                return Some(format!(
+                    "the ID {} is URL-safe, unlike in the version 3",
+                    id
+                ));
+            }
+            EventIdFormat::UrlBase64 if id.contains('+') || id.contains('/') => {
+                return Some(format!(
+                    "the ID {} isn't URL-safe, as in the version {}",
+                    id, self.version
+                ));
+            }
+            _ => {}
+        }
+
+        let content = event.get_content();
+
+        match event.get_type() {
+            "m.room.member" if content["membership"] == "knock" && self.number < 7 => {
+                return Some(format!(
+                    "knocking came with the version 7, not {}",
+                    self.version
+                ));
+            }
+            "m.room.join_rules" => {
+                let since = match content["join_rule"].as_str() {
+                    Some("restricted") => 8,
+                    Some("knock") => 7,
+                    Some("knock_restricted") => 10,
+                    _ => 0,
+                };
+
+                if self.number < since {
+                    return Some(format!(
+                        "the join rule {} came with the version {}, not {}",
+                        content["join_rule"], since, self.version
+                    ));
+                }
+            }
+            "m.room.power_levels" if self.number >= 10 => {
+                if let Some(level) = find_value(content, &|value| value.is_string()) {
+                    return Some(format!(
+                        "the power level {} isn't an integer, as in the version {}",
+                        level, self.version
+                    ));
+                }
+            }
+            "m.room.create" if self.number < 11 && content["creator"].is_null() => {
+                return Some(format!(
+                    "the creation has no creator, which only the version 11 allows, not {}",
+                    self.version
+                ));
+            }
+            _ => {}
+        }
+
+        if self.number >= 6 {
+            let invalid = find_value(content, &|value| match value {
+                JsonValue::Number(n) => n
+                    .as_i64()
+                    .map(|n| !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&n))
+                    .unwrap_or(true),
+                _ => false,
+            });
+
+            if let Some(number) = invalid {
+                return Some(format!(
+                    "the number {} isn't allowed by the canonical JSON of the version {}",
+                    number, self.version
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+// Returns the first value in `json`, at any level, for which `invalid` is true
+fn find_value<'a>(
+    json: &'a JsonValue,
+    invalid: &dyn Fn(&JsonValue) -> bool,
+) -> Option<&'a JsonValue> {
+    if invalid(json) {
+        return Some(json);
+    }
+
+    match json {
+        JsonValue::Array(values) => values.iter().find_map(|value| find_value(value, invalid)),
+        JsonValue::Object(fields) => fields.values().find_map(|value| find_value(value, invalid)),
+        _ => None,
+    }
+}
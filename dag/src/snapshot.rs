@@ -0,0 +1,96 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::event::Field;
+use super::export::ExportedView;
+use super::expression::ColourRule;
+use super::preset::DisplayPreset;
+use super::query::EventFilter;
+
+/// Where the camera of a vis.js network looks: the position at the centre of the network, and
+/// the zoom level.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Camera {
+    pub x: f64,
+    pub y: f64,
+    pub scale: f64,
+}
+
+/// A view saved in a snapshot: its DAG and the fields of the labels of its nodes.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SnapshotView {
+    pub view_id: usize, // The index of the view, since the views without a DAG are left out
+    pub dag: ExportedView,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+/// A named state of the views the user can switch back to, e.g. the "before" and the "after" of
+/// an incident prepared for a presentation: the events loaded, the way they are filtered and
+/// displayed, the events selected and where the cameras look.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ViewSnapshot {
+    pub name: String,
+    pub views: Vec<SnapshotView>,
+    #[serde(default)]
+    pub preset: DisplayPreset, // Only its shortener of the IDs and its layout of the labels, the fields are those of each view
+    #[serde(default)]
+    pub query: EventFilter, // The criteria of the query form
+    #[serde(default)]
+    pub expression: String, // The expression of the query form
+    #[serde(default)]
+    pub colour_rules: Vec<ColourRule>,
+    #[serde(default)]
+    pub time_range: Option<(i64, i64)>, // Only the events created in this range are displayed
+    #[serde(default)]
+    pub selection: Vec<String>, // The IDs of the events selected
+    #[serde(default)]
+    pub cameras: Vec<Camera>, // The camera of each network, the single one or those of the split views
+}
+
+/// The snapshots of an investigation, in the order they were taken.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Snapshots {
+    snapshots: Vec<ViewSnapshot>,
+}
+
+impl Snapshots {
+    pub fn new() -> Self {
+        Snapshots {
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Adds `snapshot`, replacing the snapshot with the same name in place if there is one.
+    pub fn insert(&mut self, snapshot: ViewSnapshot) {
+        match self.snapshots.iter_mut().find(|s| s.name == snapshot.name) {
+            Some(existing) => *existing = snapshot,
+            None => self.snapshots.push(snapshot),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ViewSnapshot> {
+        self.snapshots.iter().find(|s| s.name == name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ViewSnapshot> {
+        let idx = self.snapshots.iter().position(|s| s.name == name)?;
+
+        Some(self.snapshots.remove(idx))
+    }
+
+    /// Adds the snapshots of `other`, e.g. of an imported investigation, those with the same name
+    /// being replaced.
+    pub fn extend(&mut self, other: Snapshots) {
+        for snapshot in other.snapshots {
+            self.insert(snapshot);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ViewSnapshot> {
+        self.snapshots.iter()
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// The events retrieved from a source of events DAG, along with the annotations the source may
+/// have about them.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SourceEvents {
+    pub events: Vec<JsonValue>,
+    pub rejections: HashMap<String, String>, // The reasons of the rejection of the rejected events
+    pub soft_failed: Vec<String>,            // The IDs of the events which have soft-failed
+    pub stream_orderings: HashMap<String, i64>, // The positions of the events in the stream of the server which persisted them
+    pub received_ts: HashMap<String, i64>, // When the server which persisted the events received them
+}
+
+impl SourceEvents {
+    /// Wraps `events` without any annotation.
+    pub fn from_events(events: Vec<JsonValue>) -> Self {
+        SourceEvents {
+            events,
+            ..Default::default()
+        }
+    }
+}
+
+/// A request for events of the DAG of a room, which every kind of source answers with
+/// `SourceEvents`. The sources which paginate with tokens rather than with event IDs ignore the
+/// IDs of `Before` and `After` and keep track of their tokens themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceRequest {
+    Initial,             // The events the DAG is built from, usually the latest ones
+    Before(Vec<String>), // Some of the events preceding these events
+    After(Vec<String>),  // Some of the events following these events
+    Single(String),      // This event alone
+}
+
+/// A source of events DAG which answers the requests as they are made, e.g. a homeserver or its
+/// database queried from the command line. The web application can't block on its requests, so
+/// it has its own trait for them, built on the same requests and responses.
+pub trait EventSource {
+    type Error;
+
+    /// Makes the request `request` and returns the events it brought.
+    fn fetch(&mut self, request: &SourceRequest) -> Result<SourceEvents, Self::Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::dag::RoomEvents;
+
+    fn id(i: usize) -> String {
+        format!("${}:example.org", i)
+    }
+
+    fn event(i: usize) -> JsonValue {
+        let prev_events: Vec<String> = if i > 1 { vec![id(i - 1)] } else { Vec::new() };
+
+        json!({
+            "room_id": "!room:example.org",
+            "sender": "@alice:example.org",
+            "origin": "example.org",
+            "origin_server_ts": 1_500_000_000_000i64 + i as i64,
+            "type": "m.room.message",
+            "content": { "msgtype": "m.text", "body": format!("Message {}", i) },
+            "prev_events": prev_events,
+            "depth": i,
+            "auth_events": [],
+            "event_id": id(i),
+            "hashes": { "sha256": "" },
+            "signatures": {},
+        })
+    }
+
+    // A room whose events `$1` to `$last` form a chain, paginated by event IDs
+    struct Chain {
+        last: usize,
+        requests: Vec<SourceRequest>,
+    }
+
+    impl EventSource for Chain {
+        type Error = String;
+
+        fn fetch(&mut self, request: &SourceRequest) -> Result<SourceEvents, String> {
+            self.requests.push(request.clone());
+
+            let index = |event_id: &str| {
+                (1..=self.last)
+                    .find(|i| id(*i) == event_id)
+                    .ok_or_else(|| format!("Unknown event {}", event_id))
+            };
+
+            let events = match request {
+                SourceRequest::Initial => vec![event(self.last)],
+                SourceRequest::Before(from) => {
+                    let i = index(&from[0])?;
+                    (i.saturating_sub(2).max(1)..i).map(event).collect()
+                }
+                SourceRequest::After(from) => {
+                    let i = index(&from[0])?;
+                    (i + 1..=self.last).map(event).collect()
+                }
+                SourceRequest::Single(event_id) => vec![event(index(event_id)?)],
+            };
+
+            Ok(SourceEvents::from_events(events))
+        }
+    }
+
+    #[test]
+    fn builds_the_dag_from_the_requests_to_a_source() {
+        let mut source = Chain {
+            last: 5,
+            requests: Vec::new(),
+        };
+
+        let mut dag =
+            RoomEvents::from_source("!room:example.org", "example.org", &mut source).unwrap();
+        assert_eq!(dag.event_count(), 1);
+
+        while dag.earliest_events != vec![id(1)] {
+            let from = SourceRequest::Before(dag.earliest_events.clone());
+            let outcome = dag.fetch_from(&mut source, &from).unwrap();
+            assert!(outcome.added > 0);
+        }
+
+        assert_eq!(dag.event_count(), 5);
+        assert_eq!(dag.latest_events, vec![id(5)]);
+        assert_eq!(
+            source.requests,
+            vec![
+                SourceRequest::Initial,
+                SourceRequest::Before(vec![id(5)]),
+                SourceRequest::Before(vec![id(3)]),
+            ]
+        );
+
+        let outcome = dag
+            .fetch_from(&mut source, &SourceRequest::Single(id(2)))
+            .unwrap();
+        assert!(outcome.is_redundant());
+
+        let error = dag.fetch_from(&mut source, &SourceRequest::After(vec![id(9)]));
+        assert_eq!(error, Err(format!("Unknown event {}", id(9))));
+    }
+}
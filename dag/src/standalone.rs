@@ -0,0 +1,90 @@
+use super::dataset::DataSet;
+use super::event;
+use super::layout_options::LayoutOptions;
+use super::theme::Theme;
+
+/// The vis.js library loaded by the standalone pages, the same version as the one of the web
+/// application.
+pub const VIS_JS_URL: &str = "https://cdnjs.cloudflare.com/ajax/libs/vis/4.21.0/vis.min.js";
+
+/// Returns the element of the standalone pages loading vis.js from `VIS_JS_URL`. It can be
+/// replaced by the source of the library to get a page which works offline.
+pub fn vis_js_tag() -> String {
+    format!("<script src=\"{}\"></script>", VIS_JS_URL)
+}
+
+/// Writes a single HTML page drawing `data_set` with vis.js, as the web application does, so that
+/// an interactive DAG can be attached to a ticket and opened by anyone with a browser: the nodes
+/// can be moved around and zoomed on, and hovering them shows their tooltips.
+pub fn to_html(
+    title: &str,
+    data_set: &DataSet,
+    layout_options: &LayoutOptions,
+    theme: Theme,
+) -> String {
+    let palette = theme.palette();
+
+    // The data set is written within a script element, which no string may close
+    let data = serde_json::to_string(data_set)
+        .expect("Failed to serialize the data set")
+        .replace("</", "<\\/");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+    <head>
+        <meta charset="utf-8">
+        <title>{title}</title>
+        {vis_js}
+        <style>
+            body {{ margin: 0; font-family: sans-serif; }}
+            body.theme-dark {{ color: #dddddd; background-color: #1e1e1e; }}
+            h1 {{ margin: 8px; font-size: 1.2em; }}
+            #dag {{ position: absolute; top: 48px; bottom: 0; left: 0; right: 0; }}
+        </style>
+    </head>
+    <body class="{css_class}">
+        <h1>{title}</h1>
+        <div id="dag"></div>
+        <script>
+            var data = {data};
+            var options = {{
+                layout: {{
+                    improvedLayout: true,
+                    hierarchical: {{
+                        enabled: true,
+                        direction: "{direction}",
+                        levelSeparation: {level_separation},
+                        nodeSpacing: {node_spacing},
+                        treeSpacing: 300,
+                        sortMethod: "directed"
+                    }}
+                }},
+                nodes: {{
+                    shape: "box",
+                    font: {{ color: "{label}" }},
+                    widthConstraint: {{ minimum: 200, maximum: 300 }},
+                    scaling: {{ label: {{ enabled: true, min: 14, max: 40 }} }}
+                }},
+                edges: {{ arrows: "to", smooth: true }},
+                physics: {{ enabled: false }}
+            }};
+
+            new vis.Network(document.getElementById("dag"), {{
+                nodes: new vis.DataSet(data.nodes),
+                edges: new vis.DataSet(data.edges)
+            }}, options);
+        </script>
+    </body>
+</html>
+"#,
+        title = event::escape_html(title),
+        vis_js = vis_js_tag(),
+        css_class = palette.css_class,
+        data = data,
+        direction = layout_options.direction.name(),
+        level_separation = layout_options.level_separation,
+        node_spacing = layout_options.node_spacing,
+        label = palette.label,
+    )
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use serde_derive::Serialize;
+use serde_json::{Map, Value as JsonValue};
+
+/// An event of the timeline of a piece of state, see `RoomEvents::state_timeline`.
+#[derive(Clone, Debug, Serialize)]
+pub struct StateTimelineEntry {
+    pub id: String,
+    pub depth: i64,
+    pub sender: String,
+    pub origin_server_ts: i64,
+    pub content: JsonValue,
+    pub concurrent: bool, // Whether the previous event of the timeline isn't an ancestor of this one
+    pub changes: Vec<ContentChange>, // The changes from the content of the previous event of the timeline
+}
+
+/// A value which differs between two contents, see `content_diff`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ContentChange {
+    pub pointer: String, // The JSON pointer of the value, e.g. `/users/@alice:example.org`
+    pub old: Option<JsonValue>, // The previous value, or `None` if it was added
+    pub new: Option<JsonValue>, // The new value, or `None` if it was removed
+}
+
+impl fmt::Display for ContentChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} → {}", self.pointer, old, new),
+            (None, Some(new)) => write!(f, "+ {}: {}", self.pointer, new),
+            (Some(old), None) => write!(f, "- {}: {}", self.pointer, old),
+            (None, None) => write!(f, "{}", self.pointer),
+        }
+    }
+}
+
+/// Returns the values which differ between the contents `old` and `new`, sorted by pointer. The
+/// objects are compared key by key, so that a change of a single user in `m.room.power_levels`
+/// is reported alone, but the other values, including the arrays, are compared as a whole.
+pub fn content_diff(old: &JsonValue, new: &JsonValue) -> Vec<ContentChange> {
+    let mut changes = Vec::new();
+
+    diff_at(String::new(), Some(old), Some(new), &mut changes);
+
+    changes
+}
+
+/// Returns the values of the content `new` as if they were all added, which is how the first
+/// event of a timeline is described.
+pub fn content_added(new: &JsonValue) -> Vec<ContentChange> {
+    content_diff(&JsonValue::Object(Map::new()), new)
+}
+
+fn diff_at(
+    pointer: String,
+    old: Option<&JsonValue>,
+    new: Option<&JsonValue>,
+    changes: &mut Vec<ContentChange>,
+) {
+    match (old, new) {
+        (Some(JsonValue::Object(old)), Some(JsonValue::Object(new))) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                // Escape the key as required by the JSON pointer syntax
+                let child = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+
+                diff_at(child, old.get(key), new.get(key), changes);
+            }
+        }
+        (old, new) if old != new => changes.push(ContentChange {
+            pointer,
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+        _ => {}
+    }
+}
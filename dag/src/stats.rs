@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+
+use serde_derive::Serialize;
+
+/// Statistics about the events of a DAG, which help spotting the senders or the servers flooding
+/// a room and the bridges creating an unusual number of forks.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RoomStats {
+    pub events: usize,
+    pub per_sender: Vec<(String, usize)>, // Number of events of each sender, most active first
+    pub per_origin: Vec<(String, usize)>, // Number of events of each origin server, most active first
+    pub branch_factor: f64, // Average number of children of the events which have some
+    pub max_children: usize, // Largest number of children of a single event
+    pub forward_extremities: usize, // Number of events without children
+    pub backward_extremities: usize, // Number of events without parents in the DAG
+    pub orphans: usize,     // Number of events with missing `prev_events`
+    pub interned_strings: usize, // Number of distinct types, senders, origins and room IDs shared by the events
+    pub memory: usize,           // Rough estimate of the memory used by the events, in bytes
+}
+
+/// Turns the number of events of each key into a list sorted by decreasing count, and then by
+/// key.
+pub fn ranked(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+
+    ranked.sort_by(|(key_a, count_a), (key_b, count_b)| {
+        count_b.cmp(count_a).then_with(|| key_a.cmp(key_b))
+    });
+
+    ranked
+}
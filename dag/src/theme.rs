@@ -0,0 +1,400 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::dataset::NodeColor;
+
+/// The themes the application can be displayed with.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Theme {
+    #[default]
+    Default,
+    Dark,
+    ColourBlindSafe, // Only uses colours from the Okabe-Ito palette, told apart by every kind of colour blindness
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Default, Theme::Dark, Theme::ColourBlindSafe];
+
+    /// The name under which the theme is persisted.
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::Dark => "dark",
+            Theme::ColourBlindSafe => "colour-blind-safe",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Theme> {
+        Theme::ALL
+            .iter()
+            .cloned()
+            .find(|theme| theme.name() == name)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Theme::Default => "Default",
+            Theme::Dark => "Dark",
+            Theme::ColourBlindSafe => "Colour-blind safe",
+        }
+    }
+
+    pub fn palette(self) -> &'static Palette {
+        match self {
+            Theme::Default => &DEFAULT_PALETTE,
+            Theme::Dark => &DARK_PALETTE,
+            Theme::ColourBlindSafe => &COLOUR_BLIND_SAFE_PALETTE,
+        }
+    }
+}
+
+/// The colours of the border and of the background of a node.
+pub struct Swatch {
+    pub border: &'static str,
+    pub background: &'static str,
+}
+
+impl Swatch {
+    pub fn node_color(&self) -> NodeColor {
+        NodeColor {
+            border: self.border.to_string(),
+            background: self.background.to_string(),
+        }
+    }
+}
+
+/// The colours used to draw the networks, by the role of what they are used for.
+pub struct Palette {
+    pub css_class: &'static str, // The class of the page's body, which gives its colours to the rest of the page
+
+    pub local_event: Swatch, // The events created by the server the DAG was retrieved from
+    pub remote_event: Swatch, // The events created by the other servers
+    pub everywhere_event: Swatch, // For a merged DAG, the events every servers have
+    pub partial_event: Swatch, // For a merged DAG, the events some servers are missing
+    pub unreachable_origin: Swatch, // The events whose origin server couldn't be reached
+    pub unreceived: Swatch, // The events a server taken offline in a simulation couldn't have received
+    pub ghost: Swatch,      // The missing `prev_events`
+    pub upgrade: Swatch,    // The node marking the upgrade of a room
+    pub fog: Swatch,        // The regions of unknown history below the orphan events
+    pub collapsed: Swatch,  // The nodes standing for groups of collapsed events
+    pub latency: [Swatch; 4], // The events delivered within a second, ten seconds, a minute, and later
+    pub batches: [Swatch; 6], // The events brought by each request, the colours being reused after the last one
+    pub divergent_border: &'static str, // The border of the events whose copies differ across servers
+    pub unauthorised_border: &'static str, // The border of the events whose sender lacked the membership or the power level
+    pub mismatch_border: &'static str, // The border of the events whose sender or signatures disagree with their origin
+    pub highlight_border: &'static str, // The border of the events highlighted by the user
+
+    pub label: &'static str,        // The text of the labels
+    pub fog_label: &'static str,    // The text of the labels of the fog
+    pub ghost_edge: &'static str,   // The edges to the missing `prev_events`
+    pub upgrade_edge: &'static str, // The edges through the node marking an upgrade
+    pub thread: &'static str,
+    pub edit: &'static str,
+    pub annotation: &'static str,
+    pub reply: &'static str,
+}
+
+const DEFAULT_PALETTE: Palette = Palette {
+    css_class: "theme-default",
+
+    local_event: Swatch {
+        border: "#006633",
+        background: "#009900",
+    },
+    remote_event: Swatch {
+        border: "#990000",
+        background: "#ff6600",
+    },
+    everywhere_event: Swatch {
+        border: "#006633",
+        background: "#009900",
+    },
+    partial_event: Swatch {
+        border: "#990000",
+        background: "#ff6600",
+    },
+    unreachable_origin: Swatch {
+        border: "#333333",
+        background: "#999999",
+    },
+    unreceived: Swatch {
+        border: "#bbbbbb",
+        background: "#f4f4f4",
+    },
+    ghost: Swatch {
+        border: "#999999",
+        background: "#eeeeee",
+    },
+    upgrade: Swatch {
+        border: "#336699",
+        background: "#ddeeff",
+    },
+    fog: Swatch {
+        border: "#d0d0d0",
+        background: "rgba(220, 220, 220, 0.6)",
+    },
+    collapsed: Swatch {
+        border: "#666699",
+        background: "#ccccff",
+    },
+    latency: [
+        Swatch {
+            border: "#006633",
+            background: "#33cc66",
+        },
+        Swatch {
+            border: "#998800",
+            background: "#ffee55",
+        },
+        Swatch {
+            border: "#994400",
+            background: "#ff9933",
+        },
+        Swatch {
+            border: "#990000",
+            background: "#ff3333",
+        },
+    ],
+    batches: [
+        Swatch {
+            border: "#1f4e99",
+            background: "#5b8def",
+        },
+        Swatch {
+            border: "#8a5a00",
+            background: "#f2b84b",
+        },
+        Swatch {
+            border: "#5c2d91",
+            background: "#a67de0",
+        },
+        Swatch {
+            border: "#0f6e6e",
+            background: "#4cc3c3",
+        },
+        Swatch {
+            border: "#99335c",
+            background: "#e77fa8",
+        },
+        Swatch {
+            border: "#4d5b1f",
+            background: "#a3b84d",
+        },
+    ],
+    divergent_border: "#cc00cc",
+    unauthorised_border: "#ffcc00",
+    mismatch_border: "#3366ff",
+    highlight_border: "#ff6600",
+
+    label: "#343434",
+    fog_label: "#808080",
+    ghost_edge: "#999999",
+    upgrade_edge: "#336699",
+    thread: "#0066cc",
+    edit: "#9933cc",
+    annotation: "#cc9900",
+    reply: "#009999",
+};
+
+const DARK_PALETTE: Palette = Palette {
+    css_class: "theme-dark",
+
+    local_event: Swatch {
+        border: "#33cc66",
+        background: "#1e6b35",
+    },
+    remote_event: Swatch {
+        border: "#ff8844",
+        background: "#8a3a10",
+    },
+    everywhere_event: Swatch {
+        border: "#33cc66",
+        background: "#1e6b35",
+    },
+    partial_event: Swatch {
+        border: "#ff8844",
+        background: "#8a3a10",
+    },
+    unreachable_origin: Swatch {
+        border: "#aaaaaa",
+        background: "#444444",
+    },
+    unreceived: Swatch {
+        border: "#666666",
+        background: "#1c1c1c",
+    },
+    ghost: Swatch {
+        border: "#777777",
+        background: "#2a2a2a",
+    },
+    upgrade: Swatch {
+        border: "#6699cc",
+        background: "#1f3347",
+    },
+    fog: Swatch {
+        border: "#555555",
+        background: "rgba(80, 80, 80, 0.6)",
+    },
+    collapsed: Swatch {
+        border: "#9999cc",
+        background: "#3d3d66",
+    },
+    latency: [
+        Swatch {
+            border: "#33cc66",
+            background: "#1e6b35",
+        },
+        Swatch {
+            border: "#ffee55",
+            background: "#6b6115",
+        },
+        Swatch {
+            border: "#ff9933",
+            background: "#7a4210",
+        },
+        Swatch {
+            border: "#ff5555",
+            background: "#7a1a1a",
+        },
+    ],
+    batches: [
+        Swatch {
+            border: "#5b8def",
+            background: "#1f3a6b",
+        },
+        Swatch {
+            border: "#f2b84b",
+            background: "#6b4e10",
+        },
+        Swatch {
+            border: "#a67de0",
+            background: "#44285f",
+        },
+        Swatch {
+            border: "#4cc3c3",
+            background: "#134d4d",
+        },
+        Swatch {
+            border: "#e77fa8",
+            background: "#6b2244",
+        },
+        Swatch {
+            border: "#a3b84d",
+            background: "#3d4a14",
+        },
+    ],
+    divergent_border: "#ff66ff",
+    unauthorised_border: "#ffee55",
+    mismatch_border: "#66aaff",
+    highlight_border: "#ffaa33",
+
+    label: "#eeeeee",
+    fog_label: "#aaaaaa",
+    ghost_edge: "#777777",
+    upgrade_edge: "#6699cc",
+    thread: "#4da6ff",
+    edit: "#c080ff",
+    annotation: "#ffcc33",
+    reply: "#33cccc",
+};
+
+const COLOUR_BLIND_SAFE_PALETTE: Palette = Palette {
+    css_class: "theme-colour-blind-safe",
+
+    local_event: Swatch {
+        border: "#0072b2",
+        background: "#56b4e9",
+    },
+    remote_event: Swatch {
+        border: "#d55e00",
+        background: "#e69f00",
+    },
+    everywhere_event: Swatch {
+        border: "#0072b2",
+        background: "#56b4e9",
+    },
+    partial_event: Swatch {
+        border: "#d55e00",
+        background: "#e69f00",
+    },
+    unreachable_origin: Swatch {
+        border: "#000000",
+        background: "#999999",
+    },
+    unreceived: Swatch {
+        border: "#bbbbbb",
+        background: "#f4f4f4",
+    },
+    ghost: Swatch {
+        border: "#999999",
+        background: "#eeeeee",
+    },
+    upgrade: Swatch {
+        border: "#009e73",
+        background: "#ccece3",
+    },
+    fog: Swatch {
+        border: "#d0d0d0",
+        background: "rgba(220, 220, 220, 0.6)",
+    },
+    collapsed: Swatch {
+        border: "#0072b2",
+        background: "#cfe6f3",
+    },
+    latency: [
+        Swatch {
+            border: "#006b4f",
+            background: "#009e73",
+        },
+        Swatch {
+            border: "#a89e00",
+            background: "#f0e442",
+        },
+        Swatch {
+            border: "#a87000",
+            background: "#e69f00",
+        },
+        Swatch {
+            border: "#8a3d00",
+            background: "#d55e00",
+        },
+    ],
+    batches: [
+        Swatch {
+            border: "#00527a",
+            background: "#0072b2",
+        },
+        Swatch {
+            border: "#3a7fa8",
+            background: "#56b4e9",
+        },
+        Swatch {
+            border: "#a87000",
+            background: "#e69f00",
+        },
+        Swatch {
+            border: "#006b4f",
+            background: "#009e73",
+        },
+        Swatch {
+            border: "#8a3d00",
+            background: "#d55e00",
+        },
+        Swatch {
+            border: "#8a4f74",
+            background: "#cc79a7",
+        },
+    ],
+    divergent_border: "#cc79a7",
+    unauthorised_border: "#f0e442",
+    mismatch_border: "#000000",
+    highlight_border: "#e69f00",
+
+    label: "#000000",
+    fog_label: "#808080",
+    ghost_edge: "#999999",
+    upgrade_edge: "#009e73",
+    thread: "#0072b2",
+    edit: "#cc79a7",
+    annotation: "#e69f00",
+    reply: "#009e73",
+};
@@ -0,0 +1,132 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::annotation::{Annotation, Annotations};
+
+/// A set of event IDs, e.g. the result of a search, a diff or an analysis.
+pub type EventSet = BTreeSet<String>;
+
+/// The operations which can be used to combine two event sets.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SetOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+/// An operation initiated by the user, such as a fetch, an import or an export.
+#[derive(Clone, Debug)]
+pub struct Activity {
+    pub timestamp: String,
+    pub description: String,
+}
+
+/// Holds the state of an investigation which is shared by every views, such as the named sets of
+/// events built by the user, the notes pinned to the events and the log of the operations which
+/// led to the displayed DAG.
+#[derive(Default)]
+pub struct Workspace {
+    event_sets: HashMap<String, EventSet>,
+    annotations: Annotations,
+    activity_log: Vec<Activity>,
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        Workspace {
+            event_sets: HashMap::new(),
+            annotations: Annotations::new(),
+            activity_log: Vec::new(),
+        }
+    }
+
+    /// Saves `events` as the event set named `name`, replacing any previous set with this name.
+    pub fn add_event_set(&mut self, name: &str, events: EventSet) {
+        self.event_sets.insert(name.to_string(), events);
+    }
+
+    /// Get an event set from its name.
+    pub fn event_set(&self, name: &str) -> Option<&EventSet> {
+        self.event_sets.get(name)
+    }
+
+    pub fn remove_event_set(&mut self, name: &str) -> Option<EventSet> {
+        self.event_sets.remove(name)
+    }
+
+    /// Returns the names of the event sets along with their number of events, sorted by name.
+    pub fn event_sets(&self) -> Vec<(String, usize)> {
+        let mut sets: Vec<(String, usize)> = self
+            .event_sets
+            .iter()
+            .map(|(name, set)| (name.clone(), set.len()))
+            .collect();
+
+        sets.sort();
+
+        sets
+    }
+
+    /// Combines the event sets `lhs` and `rhs` with `op` and saves the result as the event set
+    /// `result`. Returns the resulting set, or `None` if one of the operands doesn't exist.
+    pub fn combine(
+        &mut self,
+        op: SetOperation,
+        lhs: &str,
+        rhs: &str,
+        result: &str,
+    ) -> Option<&EventSet> {
+        let combined: EventSet = {
+            let lhs = self.event_sets.get(lhs)?;
+            let rhs = self.event_sets.get(rhs)?;
+
+            match op {
+                SetOperation::Union => lhs.union(rhs).cloned().collect(),
+                SetOperation::Intersection => lhs.intersection(rhs).cloned().collect(),
+                SetOperation::Difference => lhs.difference(rhs).cloned().collect(),
+            }
+        };
+
+        self.event_sets.insert(result.to_string(), combined);
+
+        self.event_sets.get(result)
+    }
+
+    /// Pins `annotation` to the event `id`, replacing its previous annotation.
+    pub fn annotate(&mut self, id: &str, annotation: Annotation) {
+        self.annotations.insert(id.to_string(), annotation);
+    }
+
+    pub fn remove_annotation(&mut self, id: &str) -> Option<Annotation> {
+        self.annotations.remove(id)
+    }
+
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    /// Replaces the annotations, e.g. by those of an imported investigation.
+    pub fn set_annotations(&mut self, annotations: Annotations) {
+        self.annotations = annotations;
+    }
+
+    /// Records that the operation described by `description` happened at `timestamp`.
+    pub fn log_activity(&mut self, timestamp: String, description: String) {
+        self.activity_log.push(Activity {
+            timestamp,
+            description,
+        });
+    }
+
+    pub fn activity_log(&self) -> &[Activity] {
+        &self.activity_log
+    }
+
+    /// Returns the activity log as plain text, one operation per line in chronological order, so
+    /// it can be pasted in an investigation report.
+    pub fn activity_report(&self) -> String {
+        self.activity_log
+            .iter()
+            .map(|activity| format!("{} {}\n", activity.timestamp, activity.description))
+            .collect()
+    }
+}
@@ -0,0 +1,282 @@
+use std::sync::{Arc, RwLock};
+
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use yew::callback::Callback;
+use yew::format::{Json, Nothing};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response, Uri};
+
+use super::session::Session;
+use crate::backend_error::BackendError;
+use crate::cs_backend::backend::MessagesResponse;
+use crate::dag_source::{DagSource, SourceEvents, SourceRequest};
+
+/// Represents the backend used to communicate with a Synapse homeserver via its admin API. This
+/// allows operators with an admin access token to observe rooms they are not joined to.
+pub struct AdminBackend {
+    fetch: FetchService,
+    session: Arc<RwLock<Session>>,
+}
+
+/// Represents the JSON body of a response to a `GET /_synapse/admin/v1/rooms/{roomId}/state`
+/// request.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RoomStateResponse {
+    pub state: Vec<JsonValue>,
+}
+
+/// Represents the JSON body of a response to a `GET /_synapse/admin/v1/event_reports` request.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct EventReportsResponse {
+    pub event_reports: Vec<JsonValue>,
+    pub total: u64,
+}
+
+impl AdminBackend {
+    /// Creates a new admin backend linked to the given `session`.
+    pub fn with_session(session: Arc<RwLock<Session>>) -> Self {
+        AdminBackend {
+            fetch: FetchService::new(),
+            session,
+        }
+    }
+
+    /// Sends a request to the homeserver to get the events of the room to observe from the
+    /// pagination token `from` in the direction `dir` ("b" or "f") and then calls `callback`
+    /// when it gets the response. If there is no token, the events are taken from the end of
+    /// the room.
+    pub fn messages(
+        &mut self,
+        callback: Callback<Result<MessagesResponse, Error>>,
+        from: Option<String>,
+        dir: &str,
+    ) -> FetchTask {
+        let room_id = self.session.read().unwrap().room_id.clone();
+
+        let filter = crate::cs_backend::backend::build_filter();
+        let mut query_params = format!(
+            "/_synapse/admin/v1/rooms/{}/messages?dir={}&filter={}",
+            room_id, dir, filter
+        );
+        if let Some(from) = from {
+            query_params.push_str("&from=");
+            query_params.push_str(&from);
+        }
+
+        let handler = move |response: Response<Json<Result<MessagesResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                callback.emit(Err(BackendError::from_status(meta.status.as_u16()).into()))
+            }
+        };
+
+        let request = self.build_request(&query_params);
+
+        self.fetch.fetch(request, handler.into())
+    }
+
+    /// Sends a request to the homeserver to get the current state of the room to observe and
+    /// then calls `callback` when it gets the response.
+    pub fn room_state(
+        &mut self,
+        callback: Callback<Result<RoomStateResponse, Error>>,
+    ) -> FetchTask {
+        let room_id = self.session.read().unwrap().room_id.clone();
+
+        let handler = move |response: Response<Json<Result<RoomStateResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                callback.emit(Err(BackendError::from_status(meta.status.as_u16()).into()))
+            }
+        };
+
+        let request = self.build_request(&format!("/_synapse/admin/v1/rooms/{}/state", room_id));
+
+        self.fetch.fetch(request, handler.into())
+    }
+
+    /// Sends a request to the homeserver to get the reports of events of the room to observe
+    /// and then calls `callback` when it gets the response.
+    pub fn event_reports(
+        &mut self,
+        callback: Callback<Result<EventReportsResponse, Error>>,
+    ) -> FetchTask {
+        let room_id = self.session.read().unwrap().room_id.clone();
+
+        let handler = move |response: Response<Json<Result<EventReportsResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                callback.emit(Err(BackendError::from_status(meta.status.as_u16()).into()))
+            }
+        };
+
+        let request = self.build_request(&format!(
+            "/_synapse/admin/v1/event_reports?room_id={}",
+            room_id
+        ));
+
+        self.fetch.fetch(request, handler.into())
+    }
+
+    /// Sends a request to the homeserver to get the event `event_id` of the room to observe, with
+    /// its context, and then calls `callback` when it gets the response.
+    pub fn event_context(
+        &mut self,
+        callback: Callback<Result<JsonValue, Error>>,
+        event_id: &str,
+    ) -> FetchTask {
+        let room_id = self.session.read().unwrap().room_id.clone();
+
+        let handler = move |response: Response<Json<Result<JsonValue, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                callback.emit(Err(BackendError::from_status(meta.status.as_u16()).into()))
+            }
+        };
+
+        let request = self.build_request(&format!(
+            "/_synapse/admin/v1/rooms/{}/context/{}?limit=1&filter={}",
+            room_id,
+            event_id,
+            crate::cs_backend::backend::build_filter()
+        ));
+
+        self.fetch.fetch(request, handler.into())
+    }
+
+    // Builds an authenticated `GET` request to the admin API.
+    fn build_request(&self, path_and_query: &str) -> Request<Nothing> {
+        let (server_name, access_token) = {
+            let session = self.session.read().unwrap();
+
+            (session.server_name.clone(), session.access_token.clone())
+        };
+
+        let uri = Uri::builder()
+            .scheme("https")
+            .authority(server_name.as_str())
+            .path_and_query(path_and_query)
+            .build()
+            .expect("Failed to build URI.");
+
+        Request::get(uri)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", access_token))
+            .body(Nothing)
+            .expect("Failed to build request.")
+    }
+}
+
+impl AdminBackend {
+    // Retrieves the events with which the DAG is built, the latest ones.
+    fn fetch_initial_events(
+        &mut self,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> FetchTask {
+        let session = self.session.clone();
+
+        self.messages(
+            Callback::from(move |response: Result<MessagesResponse, Error>| {
+                callback.emit(response.map(|res| {
+                    let mut session = session.write().unwrap();
+
+                    // `start` is the position of the latest events of the room, new events will
+                    // be fetched forward from there while earlier ones will be fetched from `end`
+                    session.next_batch_token = Some(res.start.clone());
+                    session.prev_batch_token = Some(res.end.clone());
+
+                    res.into_source_events().0
+                }))
+            }),
+            None,
+            "b",
+        )
+    }
+
+    // Retrieves the page of events preceding the previous one.
+    fn fetch_earlier_events(
+        &mut self,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> FetchTask {
+        let session = self.session.clone();
+        let prev_batch_token = session.read().unwrap().prev_batch_token.clone();
+
+        self.messages(
+            Callback::from(move |response: Result<MessagesResponse, Error>| {
+                callback.emit(response.map(|res| {
+                    let (events, end) = res.into_source_events();
+
+                    // There is no end once the start of the room is reached
+                    if !end.is_empty() {
+                        session.write().unwrap().prev_batch_token = Some(end);
+                    }
+
+                    events
+                }))
+            }),
+            prev_batch_token,
+            "b",
+        )
+    }
+
+    // Retrieves the events which came since the latest ones retrieved.
+    fn fetch_later_events(&mut self, callback: Callback<Result<SourceEvents, Error>>) -> FetchTask {
+        let session = self.session.clone();
+        let next_batch_token = session.read().unwrap().next_batch_token.clone();
+
+        self.messages(
+            Callback::from(move |response: Result<MessagesResponse, Error>| {
+                callback.emit(response.map(|res| {
+                    let (events, end) = res.into_source_events();
+
+                    // There is no end when there is no new event
+                    if !end.is_empty() {
+                        session.write().unwrap().next_batch_token = Some(end);
+                    }
+
+                    events
+                }))
+            }),
+            next_batch_token,
+            "f",
+        )
+    }
+}
+
+// The admin API paginates with tokens like the CS API: the IDs of the earlier and later events
+// requested are ignored, the tokens of the session are used and updated instead.
+impl DagSource for AdminBackend {
+    fn fetch(
+        &mut self,
+        request: &SourceRequest,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> Option<FetchTask> {
+        match request {
+            SourceRequest::Initial => Some(self.fetch_initial_events(callback)),
+            SourceRequest::Before(_) => Some(self.fetch_earlier_events(callback)),
+            SourceRequest::After(_) => Some(self.fetch_later_events(callback)),
+            SourceRequest::Single(event_id) => Some(self.event_context(
+                Callback::from(move |response: Result<JsonValue, Error>| {
+                    callback.emit(
+                        response
+                            .map(|mut res| SourceEvents::from_events(vec![res["event"].take()])),
+                    )
+                }),
+                event_id,
+            )),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+/// Holds every informations allowing the application to retrieve the events of the room to
+/// observe via the admin API of Synapse.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub server_name: String,
+    pub room_id: String,
+    pub access_token: String,
+    pub connected: bool,
+
+    pub next_batch_token: Option<String>,
+    pub prev_batch_token: Option<String>,
+}
+
+impl Session {
+    pub fn empty() -> Self {
+        Session {
+            server_name: String::new(),
+            room_id: String::new(),
+            access_token: String::new(),
+            connected: false,
+
+            next_batch_token: None,
+            prev_batch_token: None,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+
+use failure::{Error, Fail};
+use serde_json::Value as JsonValue;
+use yew::services::fetch::HeaderMap;
+
+/// The delay before the first retry of a request which failed temporarily, doubled at each
+/// attempt.
+const BASE_RETRY_DELAY_MS: u64 = 1000;
+
+/// The number of times a request which failed temporarily is retried before giving up.
+pub const MAX_RETRIES: u32 = 5;
+
+/// The header in which the delay given by the body of an `M_LIMIT_EXCEEDED` error is put when the
+/// response is received, since the `Retry-After` header isn't exposed to the page by CORS.
+pub const RETRY_AFTER_MS_HEADER: &str = "x-retry-after-ms";
+
+/// The header put in the 401 responses whose body tells that the access token has expired, see
+/// `token_expired`, for the same reason as `RETRY_AFTER_MS_HEADER`.
+pub const TOKEN_EXPIRED_HEADER: &str = "x-token-expired";
+
+/// The errors of the requests made by the backends, whether to a homeserver or to the Postgres
+/// backend. They are given to the callbacks of the backends as `failure::Error`s, from which they
+/// can be recovered with `BackendError::from_error`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackendError {
+    Network,                  // No response was received
+    Auth(u16),                // The credentials or the access token were rejected
+    TokenExpired,             // The access token has expired, and may be refreshed
+    RateLimited(Option<u64>), // Too many requests, with the delay in ms asked before retrying
+    Parse(String),            // The body of the response wasn't what was expected
+    NotFound,                 // The room, the event or the endpoint is unknown
+    Server(u16),              // Any other error status
+}
+
+impl BackendError {
+    /// Classifies the error response whose status is `status`.
+    pub fn from_status(status: u16) -> BackendError {
+        match status {
+            401 | 403 => BackendError::Auth(status),
+            404 => BackendError::NotFound,
+            // The fetch service reports the requests which got no response with this status
+            408 => BackendError::Network,
+            429 => BackendError::RateLimited(None),
+            _ => BackendError::Server(status),
+        }
+    }
+
+    /// Classifies the error response whose status is `status`, reading the delay asked by the
+    /// server before the next request if it rate limited us: the `retry_after_ms` of the body,
+    /// see `RETRY_AFTER_MS_HEADER`, or else its `Retry-After` header. Only the delays in seconds
+    /// are understood in the latter, not the dates. The rejected access tokens are told apart
+    /// from the expired ones with `TOKEN_EXPIRED_HEADER`.
+    pub fn from_response(status: u16, headers: &HeaderMap) -> BackendError {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+
+        match BackendError::from_status(status) {
+            BackendError::RateLimited(_) => BackendError::RateLimited(
+                header(RETRY_AFTER_MS_HEADER)
+                    .or_else(|| header("Retry-After").map(|secs| secs * 1000)),
+            ),
+            BackendError::Auth(401) if headers.contains_key(TOKEN_EXPIRED_HEADER) => {
+                BackendError::TokenExpired
+            }
+            error => error,
+        }
+    }
+
+    /// Recovers the `BackendError` given to a callback. The other errors can only come from the
+    /// deserialization of the body of a successful response.
+    pub fn from_error(error: Error) -> BackendError {
+        match error.downcast::<BackendError>() {
+            Ok(error) => error,
+            Err(error) => BackendError::Parse(error.to_string()),
+        }
+    }
+
+    /// Whether the request may succeed if it is made again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BackendError::RateLimited(_) => true,
+            BackendError::Server(status) => *status == 502,
+            _ => false,
+        }
+    }
+
+    /// Returns the delay in ms the server asked to wait for before the next request, if any.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            BackendError::RateLimited(delay) => *delay,
+            _ => None,
+        }
+    }
+}
+
+/// Reads the delay in ms asked by the homeserver before the next request from the body of an
+/// `M_LIMIT_EXCEEDED` error, if it gives one.
+pub fn retry_after_ms(body: &str) -> Option<u64> {
+    let error: JsonValue = serde_json::from_str(body).ok()?;
+
+    if error["errcode"] != "M_LIMIT_EXCEEDED" {
+        return None;
+    }
+
+    error["retry_after_ms"].as_u64()
+}
+
+/// Whether the body of a 401 error tells that the access token has expired rather than that it
+/// was revoked or that the request is forbidden: an `M_UNKNOWN_TOKEN` error with `soft_logout`
+/// set, or whose message says the token has expired.
+pub fn token_expired(body: &str) -> bool {
+    let error: JsonValue = match serde_json::from_str(body) {
+        Ok(error) => error,
+        Err(_) => return false,
+    };
+
+    error["errcode"] == "M_UNKNOWN_TOKEN"
+        && (error["soft_logout"] == true
+            || error["error"]
+                .as_str()
+                .is_some_and(|message| message.to_lowercase().contains("expired")))
+}
+
+/// Returns the delay in ms before the retry number `attempt` (starting at 0) of a request which
+/// failed temporarily, which doubles at each attempt.
+pub fn retry_delay(attempt: u32) -> u64 {
+    BASE_RETRY_DELAY_MS << attempt.min(16)
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendError::Network => write!(f, "the server couldn't be reached"),
+            BackendError::Auth(status) => write!(f, "{}: the credentials were rejected", status),
+            BackendError::TokenExpired => write!(f, "401: the access token has expired"),
+            BackendError::RateLimited(None) => write!(f, "too many requests, rate limited"),
+            BackendError::RateLimited(Some(delay)) => {
+                write!(f, "too many requests, rate limited for {} ms", delay)
+            }
+            BackendError::Parse(e) => write!(f, "unexpected response: {}", e),
+            BackendError::NotFound => write!(f, "not found"),
+            BackendError::Server(status) => write!(f, "error {}", status),
+        }
+    }
+}
+
+impl Fail for BackendError {}
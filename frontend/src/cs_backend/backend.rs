@@ -1,23 +1,35 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 
-use failure::{format_err, Error};
+use failure::Error;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use yew::callback::Callback;
-use yew::format::{Json, Nothing};
+use yew::format::{Json, Nothing, Text};
 use yew::services::fetch::{FetchService, FetchTask, Request, Response, Uri};
 
+use super::event_cache::EventCache;
 use super::session::Session;
+use crate::backend_error::{self, BackendError, RETRY_AFTER_MS_HEADER, TOKEN_EXPIRED_HEADER};
+use crate::dag_source::{DagSource, SourceEvents, SourceRequest};
+use crate::model::cs_api;
+pub use crate::model::cs_api::{MessagesResponse, SyncResponse};
+use crate::model::room_version::room_version_of;
 
 /// Represents the backend used to communicate with a homeserver via the Client-Server HTTP REST
 /// API.
 pub struct CSBackend {
     fetch: FetchService,
     session: Arc<RwLock<Session>>,
+    completed: Arc<AtomicUsize>, // The number of requests which got a response
+    cache: Arc<RwLock<EventCache>>, // The responses about single events, see `EventCache`
 }
 
-/// Represents the JSON body of a `POST /_matrix/client/r0/login` request.
+/// The number of events requested around an event with `get_event_context`.
+const CONTEXT_LIMIT: usize = 10;
+
+/// Represents the JSON body of a `POST /_matrix/client/v3/login` request.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ConnectionRequest {
     #[serde(rename = "type")]
@@ -25,6 +37,24 @@ pub struct ConnectionRequest {
     identifier: Identifier,
     password: String,
     initial_device_display_name: String,
+    refresh_token: bool,
+}
+
+/// Represents the JSON body of a `POST /_matrix/client/v3/login` request completing the SSO
+/// flow with the login token given by the homeserver.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokenLoginRequest {
+    #[serde(rename = "type")]
+    typo: String,
+    token: String,
+    initial_device_display_name: String,
+    refresh_token: bool,
+}
+
+/// Represents the JSON body of a `POST /_matrix/client/v3/register?kind=guest` request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GuestRegistrationRequest {
+    initial_device_display_name: String,
 }
 
 /// Represents the `identifier` field in `ConnectionRequest`.
@@ -35,88 +65,92 @@ pub struct Identifier {
     user: String,
 }
 
-/// Represents the JSON body of a response to a `POST /_matrix/client/r0/login` request.
+/// Represents the JSON body of a response to a `POST /_matrix/client/v3/login` request, or to a
+/// `POST /_matrix/client/v3/register?kind=guest` one.
 #[derive(Debug, Deserialize)]
 pub struct ConnectionResponse {
     pub user_id: String,
     pub access_token: String,
     pub device_id: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>, // Only given by the homeservers whose access tokens expire
 }
 
-/// Represents the JSON body of a response to a `GET /_matrix/client/r0/joined_rooms` request.
+/// Represents the JSON body of a `POST /_matrix/client/v3/refresh` request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+/// Represents the JSON body of a response to a `POST /_matrix/client/v3/user/{userId}/filter`
+/// request.
 #[derive(Debug, Deserialize)]
-pub struct JoinedRooms {
-    pub joined_rooms: Vec<String>,
+pub struct FilterResponse {
+    pub filter_id: String,
 }
 
-/// Represents the JSON body of a response to a `GET /_matrix/client/r0/sync` request.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct SyncResponse {
-    pub next_batch: String,
-    #[serde(default)]
-    pub rooms: Rooms,
-    presence: Option<JsonValue>,
-    #[serde(default)]
-    account_data: JsonValue,
-    to_device: Option<JsonValue>,
-    device_lists: Option<JsonValue>,
+/// Represents the JSON body of a response to a `POST /_matrix/client/v3/refresh` request.
+#[derive(Debug, Deserialize)]
+pub struct RefreshResponse {
+    pub access_token: String,
     #[serde(default)]
-    device_one_time_keys_count: HashMap<String, u64>,
+    pub refresh_token: Option<String>,
 }
 
-/// Represents the list of rooms in `SyncResponse`.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct Rooms {
-    #[serde(default)]
-    leave: HashMap<String, JsonValue>,
-    #[serde(default)]
-    pub join: HashMap<String, JoinedRoom>,
-    #[serde(default)]
-    invite: HashMap<String, JsonValue>,
+/// Represents the JSON body of a response to a `GET /.well-known/matrix/client` request.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WellKnownClient {
+    #[serde(rename = "m.homeserver")]
+    pub homeserver: HomeserverInformation,
 }
 
-/// Represents the list of rooms joined by the user in `SyncResponse`.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct JoinedRoom {
-    #[serde(default)]
-    pub unread_notifications: JsonValue,
-    #[serde(default)]
-    pub timeline: Timeline,
-    #[serde(default)]
-    pub state: State,
-    #[serde(default)]
-    pub account_data: JsonValue,
-    #[serde(default)]
-    pub ephemeral: JsonValue,
+#[derive(Clone, Debug, Deserialize)]
+pub struct HomeserverInformation {
+    pub base_url: String,
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct State {
-    // TODO: Implement StateEvent
-    #[serde(default)]
-    pub events: Vec<JsonValue>,
+/// Represents the JSON body of a response to a `GET /_matrix/client/v3/joined_rooms` request.
+#[derive(Debug, Deserialize)]
+pub struct JoinedRooms {
+    pub joined_rooms: Vec<String>,
 }
 
-/// Represents the timeline of a room in `SyncResponse`. These are the events of the DAG the
-/// application must build for the observed room.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct Timeline {
+/// Represents the JSON body of a `POST /_matrix/client/v3/publicRooms` request.
+#[derive(Debug, Serialize)]
+pub struct PublicRoomsRequest {
+    limit: u64,
+    filter: PublicRoomsFilter,
+}
+
+/// Represents the `filter` field in `PublicRoomsRequest`.
+#[derive(Debug, Serialize)]
+pub struct PublicRoomsFilter {
+    generic_search_term: String,
+}
+
+/// Represents the JSON body of a response to a `POST /_matrix/client/v3/publicRooms` request.
+#[derive(Debug, Deserialize)]
+pub struct PublicRoomsResponse {
+    pub chunk: Vec<PublicRoom>,
+}
+
+/// Represents a room of the directory in `PublicRoomsResponse`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PublicRoom {
+    pub room_id: String,
     #[serde(default)]
-    pub limited: bool,
-    pub prev_batch: Option<String>,
-    // TODO: Implement RoomEvent
+    pub name: Option<String>,
     #[serde(default)]
-    pub events: Vec<JsonValue>,
+    pub canonical_alias: Option<String>,
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub num_joined_members: u64,
 }
 
-/// Represents the JSON body of a response to a `GET /_matrix/client/r0/rooms/{roomId}/messages`
-/// request.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct MessagesResponse {
-    pub start: String,
-    pub end: String,
-    pub chunk: Vec<JsonValue>,
-}
+/// The display names of the rooms joined by the user, by room ID. The rooms with neither a name
+/// nor a canonical alias aren't in it.
+pub type RoomNames = HashMap<String, String>;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct ContextResponse {
@@ -134,17 +168,114 @@ impl CSBackend {
         CSBackend {
             fetch: FetchService::new(),
             session,
+            completed: Arc::new(AtomicUsize::new(0)),
+            cache: Arc::new(RwLock::new(EventCache::new())),
+        }
+    }
+
+    /// Returns the number of requests made by the backend which got a response.
+    pub fn completed_requests(&self) -> usize {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    // Makes `request` and gives its response to `handler`, counting it as completed. With the
+    // token of an application service, the request acts as the user chosen for it.
+    fn send<IN, OUT, F>(&mut self, mut request: Request<IN>, handler: F) -> FetchTask
+    where
+        IN: Into<Text>,
+        OUT: From<Text> + 'static,
+        F: Fn(Response<OUT>) + 'static,
+    {
+        let completed = self.completed.clone();
+
+        {
+            let session = self.session.read().unwrap();
+
+            if session.appservice {
+                let uri = request.uri().to_string();
+                let separator = if uri.contains('?') { '&' } else { '?' };
+                let uri = format!(
+                    "{}{}user_id={}",
+                    uri,
+                    separator,
+                    percent_encoding::utf8_percent_encode(
+                        &session.appservice_user_id,
+                        percent_encoding::USERINFO_ENCODE_SET
+                    )
+                );
+
+                *request.uri_mut() = uri.parse::<Uri>().expect("Failed to build request.");
+            }
         }
+
+        self.fetch.fetch(
+            request,
+            Callback::from(move |response: Response<Text>| {
+                completed.fetch_add(1, Ordering::Relaxed);
+
+                // The delay asked by a homeserver which rate limited us and whether the access
+                // token has expired are only readable from the body, which the handlers parse as
+                // the response they expect
+                let (mut meta, body) = response.into_parts();
+                match meta.status.as_u16() {
+                    429 => {
+                        if let Some(delay) = body
+                            .as_ref()
+                            .ok()
+                            .and_then(|body| backend_error::retry_after_ms(body))
+                        {
+                            meta.headers.insert(RETRY_AFTER_MS_HEADER, delay.into());
+                        }
+                    }
+                    401 => {
+                        if body
+                            .as_ref()
+                            .is_ok_and(|body| backend_error::token_expired(body))
+                        {
+                            meta.headers.insert(TOKEN_EXPIRED_HEADER, 1.into());
+                        }
+                    }
+                    _ => {}
+                }
+
+                handler(Response::from_parts(meta, OUT::from(body)))
+            }),
+        )
+    }
+
+    /// Looks for the base URL of the CS API advertised by the server name of the session, e.g.
+    /// `example.org` for a homeserver at `https://matrix.example.org`, and then calls `callback`
+    /// with it, or with `None` if there is none.
+    pub fn discover(&mut self, callback: Callback<Option<WellKnownClient>>) -> FetchTask {
+        let server_name = self.session.read().unwrap().server_name.clone();
+
+        let uri = format!("https://{}/.well-known/matrix/client", server_name);
+
+        let request = Request::get(uri)
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<WellKnownClient, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data.ok())
+            } else {
+                callback.emit(None)
+            }
+        };
+
+        self.fetch.fetch(request, handler.into())
     }
 
     /// Sends a login request to the homeserver and then calls `callback` when it gets the
     /// response.
     pub fn connect(&mut self, callback: Callback<Result<ConnectionResponse, Error>>) -> FetchTask {
-        let (server_name, username, password) = {
+        let (base_url, username, password) = {
             let session = self.session.read().unwrap();
 
             (
-                session.server_name.clone(),
+                session.base_url(),
                 session.username.clone(),
                 session.password.clone(),
             )
@@ -158,9 +289,10 @@ impl CSBackend {
             },
             password,
             initial_device_display_name: String::from("Matrix visualisations"),
+            refresh_token: true,
         };
 
-        let uri = format!("https://{}/_matrix/client/r0/login", server_name);
+        let uri = format!("{}/_matrix/client/v3/login", base_url);
 
         let request = Request::post(uri)
             .header("Content-Type", "application/json")
@@ -173,29 +305,149 @@ impl CSBackend {
             if meta.status.is_success() {
                 callback.emit(data)
             } else {
-                callback.emit(Err(format_err!("{}: error connecting", meta.status)))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
+    }
+
+    /// Registers a guest account on the homeserver, which needs no credentials but can only
+    /// observe the rooms open to guests, and then calls `callback` when it gets the response.
+    pub fn register_guest(
+        &mut self,
+        callback: Callback<Result<ConnectionResponse, Error>>,
+    ) -> FetchTask {
+        let base_url = self.session.read().unwrap().base_url();
+
+        let body = GuestRegistrationRequest {
+            initial_device_display_name: String::from("Matrix visualisations"),
+        };
+
+        let uri = format!("{}/_matrix/client/v3/register?kind=guest", base_url);
+
+        let request = Request::post(uri)
+            .header("Content-Type", "application/json")
+            .body(Json(&body))
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<ConnectionResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        self.send(request, handler)
+    }
+
+    /// Returns the URL the user must be sent to in order to log in with the SSO of the
+    /// homeserver, which will then redirect them to `redirect_url` with a login token.
+    pub fn sso_redirect_url(&self, redirect_url: &str) -> String {
+        let base_url = self.session.read().unwrap().base_url();
+
+        format!(
+            "{}/_matrix/client/v3/login/sso/redirect?redirectUrl={}",
+            base_url,
+            percent_encoding::utf8_percent_encode(
+                redirect_url,
+                percent_encoding::USERINFO_ENCODE_SET
+            )
+        )
+    }
+
+    /// Exchanges the login token given by the homeserver at the end of the SSO flow for an
+    /// access token and then calls `callback` when it gets the response.
+    pub fn connect_with_token(
+        &mut self,
+        callback: Callback<Result<ConnectionResponse, Error>>,
+        login_token: &str,
+    ) -> FetchTask {
+        let base_url = self.session.read().unwrap().base_url();
+
+        let body = TokenLoginRequest {
+            typo: String::from("m.login.token"),
+            token: login_token.to_string(),
+            initial_device_display_name: String::from("Matrix visualisations"),
+            refresh_token: true,
+        };
+
+        let uri = format!("{}/_matrix/client/v3/login", base_url);
+
+        let request = Request::post(uri)
+            .header("Content-Type", "application/json")
+            .body(Json(&body))
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<ConnectionResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        self.send(request, handler)
+    }
+
+    /// Sends a request to the homeserver to get a new access token with the refresh token of the
+    /// session and then calls `callback` when it gets the response.
+    pub fn refresh(&mut self, callback: Callback<Result<RefreshResponse, Error>>) -> FetchTask {
+        let (base_url, refresh_token) = {
+            let session = self.session.read().unwrap();
+
+            (session.base_url(), session.refresh_token.clone())
+        };
+
+        let body = RefreshRequest {
+            refresh_token: refresh_token.expect("No refresh token"),
+        };
+
+        let uri = format!("{}/_matrix/client/v3/refresh", base_url);
+
+        let request = Request::post(uri)
+            .header("Content-Type", "application/json")
+            .body(Json(&body))
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<RefreshResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        self.send(request, handler)
     }
 
     /// Sends a request to the homeserver in order to get the list of the rooms currently joined
     /// by the user and then calls `callback` when it gets the response.
     pub fn list_rooms(&mut self, callback: Callback<Result<JoinedRooms, Error>>) -> FetchTask {
-        let (server_name, access_token) = {
+        let (base_url, access_token) = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.access_token.clone())
+            (session.base_url(), session.access_token.clone())
         };
 
-        let uri = format!("https://{}/_matrix/client/r0/joined_rooms", server_name);
+        let uri = format!("{}/_matrix/client/v3/joined_rooms", base_url);
 
         let request = Request::get(uri)
             .header("Content-Type", "application/json")
             .header(
                 "Authorization",
-                format!("Bearer {}", access_token.expect("No access token")),
+                format!("Bearer {}", access_token.unwrap_or_default()),
             )
             .body(Nothing)
             .expect("Failed to build request.");
@@ -206,39 +458,145 @@ impl CSBackend {
             if meta.status.is_success() {
                 callback.emit(data)
             } else {
-                callback.emit(Err(format_err!(
-                    "{}: error listing joined rooms",
-                    meta.status
-                )))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
+    }
+
+    /// Sends a request to the homeserver in order to get the names and the canonical aliases of
+    /// the rooms joined by the user and then calls `callback` with their display names when it
+    /// gets the response.
+    ///
+    /// This is a sync with a filter which leaves out everything but these two state events, whose
+    /// token isn't kept so that it doesn't interfere with the sync of the room being observed.
+    pub fn room_names(&mut self, callback: Callback<Result<RoomNames, Error>>) -> FetchTask {
+        let (base_url, access_token) = {
+            let session = self.session.read().unwrap();
+
+            (session.base_url(), session.access_token.clone())
+        };
+
+        let filter = serde_json::json!({
+            "room": {
+                "state": { "types": ["m.room.name", "m.room.canonical_alias"] },
+                "timeline": { "limit": 0 },
+                "ephemeral": { "types": [] },
+                "account_data": { "types": [] },
+            },
+            "presence": { "types": [] },
+            "account_data": { "types": [] },
+        });
+        let uri = format!(
+            "{}/_matrix/client/v3/sync?filter={}&set_presence=offline",
+            base_url,
+            percent_encoding::utf8_percent_encode(
+                &serde_json::to_string(&filter).unwrap(),
+                percent_encoding::USERINFO_ENCODE_SET,
+            )
+        );
+
+        let request = Request::get(uri)
+            .header("Content-Type", "application/json")
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<SyncResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data.map(|res| {
+                    res.rooms
+                        .join
+                        .into_iter()
+                        .filter_map(|(room_id, room)| {
+                            room_display_name(&room.state.events).map(|name| (room_id, name))
+                        })
+                        .collect()
+                }))
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        self.send(request, handler)
+    }
+
+    /// Sends a request to the homeserver in order to search its public rooms directory for
+    /// `search_term` and then calls `callback` when it gets the response.
+    pub fn public_rooms(
+        &mut self,
+        callback: Callback<Result<PublicRoomsResponse, Error>>,
+        search_term: &str,
+    ) -> FetchTask {
+        let (base_url, access_token) = {
+            let session = self.session.read().unwrap();
+
+            (session.base_url(), session.access_token.clone())
+        };
+
+        let body = PublicRoomsRequest {
+            limit: 50,
+            filter: PublicRoomsFilter {
+                generic_search_term: search_term.to_string(),
+            },
+        };
+
+        let uri = format!("{}/_matrix/client/v3/publicRooms", base_url);
+
+        let request = Request::post(uri)
+            .header("Content-Type", "application/json")
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
+            .body(Json(&body))
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<PublicRoomsResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        self.send(request, handler)
     }
 
     /// Sends a request to the homeserver to join the room to observe and then calls `callback`
     /// when it gets the response.
     pub fn join_room(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
-        let (server_name, access_token, room_id) = {
+        let (base_url, access_token, room_id) = {
             let session = self.session.read().unwrap();
 
             (
-                session.server_name.clone(),
+                session.base_url(),
                 session.access_token.clone(),
                 session.room_id.clone(),
             )
         };
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(format!("/_matrix/client/r0/rooms/{}/join", room_id).as_str())
-            .build()
+        let uri = format!("{}/_matrix/client/v3/rooms/{}/join", base_url, room_id)
+            .parse::<Uri>()
             .expect("Failed to build URI.");
 
         let request = Request::post(uri)
             .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
             .body(Nothing)
             .expect("Failed to build request.");
 
@@ -248,60 +606,119 @@ impl CSBackend {
             if meta.status.is_success() {
                 callback.emit(Ok(()))
             } else {
-                callback.emit(Err(format_err!("{}: error joining the room", meta.status)))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
+    }
+
+    /// Sends a request to the homeserver to create the filter of the syncs, which only keeps the
+    /// room to observe with at most `timeline_limit` events in its timeline, and then calls
+    /// `callback` when it gets the response.
+    pub fn create_filter(
+        &mut self,
+        callback: Callback<Result<FilterResponse, Error>>,
+        timeline_limit: u32,
+    ) -> FetchTask {
+        let (base_url, access_token, user_id, room_id) = {
+            let session = self.session.read().unwrap();
+
+            (
+                session.base_url(),
+                session.access_token.clone(),
+                session.user_id.clone(),
+                session.room_id.clone(),
+            )
+        };
+
+        let uri = format!("{}/_matrix/client/v3/user/{}/filter", base_url, user_id)
+            .parse::<Uri>()
+            .expect("Failed to build URI.");
+
+        let body = cs_api::sync_filter(&room_id, timeline_limit);
+
+        let request = Request::post(uri)
+            .header("Content-Type", "application/json")
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
+            .body(Json(&body))
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<FilterResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        self.send(request, handler)
     }
 
     /// Sends a request to the homeserver for making the initial sync or receiving new events and
     /// then calls `callback` when it gets the response.
+    ///
+    /// The filter created by `create_filter` is used if there is one, otherwise the whole account
+    /// is synced.
     pub fn sync(
         &mut self,
         callback: Callback<Result<SyncResponse, Error>>,
         next_batch_token: Option<String>,
     ) -> FetchTask {
-        let (server_name, access_token) = {
+        let (base_url, access_token, filter_id) = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.access_token.clone())
+            (
+                session.base_url(),
+                session.access_token.clone(),
+                session.filter_id.clone(),
+            )
         };
 
-        let filter = build_filter();
-        let mut query_params = format!(
-            "/_matrix/client/r0/sync?filter={}&set_presence=offline&timeout=5000",
-            filter
-        );
-        if let Some(next_batch_token) = next_batch_token {
-            query_params.push_str("&since=");
-            query_params.push_str(&next_batch_token);
-        }
+        let filter = filter_id.unwrap_or_else(build_filter);
+        let path = cs_api::sync_path(&filter, next_batch_token.as_deref());
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(query_params.as_str())
-            .build()
+        let uri = format!("{}{}", base_url, path)
+            .parse::<Uri>()
             .expect("Failed to build URI.");
 
         let request = Request::get(uri)
             .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
             .body(Nothing)
             .expect("Failed to build request.");
 
+        let cache = self.cache.clone();
         let handler = move |response: Response<Json<Result<SyncResponse, Error>>>| {
             let (meta, Json(data)) = response.into_parts();
 
             if meta.status.is_success() {
+                if let Ok(res) = &data {
+                    let mut cache = cache.write().unwrap();
+
+                    for room in res.rooms.join.values() {
+                        cache.forget_redacted(&base_url, &room.timeline.events);
+                    }
+                }
+
                 callback.emit(data)
             } else {
-                callback.emit(Err(format_err!("{}: error syncing", meta.status)))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
     }
 
     /// Sends a request to the homeserver to get earlier events from the room to observe and then
@@ -310,11 +727,11 @@ impl CSBackend {
         &mut self,
         callback: Callback<Result<MessagesResponse, Error>>,
     ) -> FetchTask {
-        let (server_name, access_token, room_id, prev_batch_token) = {
+        let (base_url, access_token, room_id, prev_batch_token) = {
             let session = self.session.read().unwrap();
 
             (
-                session.server_name.clone(),
+                session.base_url(),
                 session.access_token.clone(),
                 session.room_id.clone(),
                 session.prev_batch_token.clone(),
@@ -323,41 +740,277 @@ impl CSBackend {
 
         let filter = build_filter();
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(
-                format!(
-                    "/_matrix/client/r0/rooms/{}/messages?from={}&dir=b&filter={}",
-                    room_id,
-                    prev_batch_token.clone().unwrap_or_default(),
-                    filter,
-                )
-                .as_str(),
-            )
-            .build()
+        let path = cs_api::messages_path(&room_id, &prev_batch_token.unwrap_or_default(), &filter);
+
+        let uri = format!("{}{}", base_url, path)
+            .parse::<Uri>()
             .expect("Failed to build URI.");
 
         let request = Request::get(uri)
             .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
             .body(Nothing)
             .expect("Failed to build request.");
 
+        let cache = self.cache.clone();
         let handler = move |response: Response<Json<Result<MessagesResponse, Error>>>| {
             let (meta, Json(data)) = response.into_parts();
 
             if meta.status.is_success() {
+                if let Ok(res) = &data {
+                    cache
+                        .write()
+                        .unwrap()
+                        .forget_redacted(&base_url, &res.chunk);
+                }
+
                 callback.emit(data)
             } else {
-                callback.emit(Err(format_err!(
-                    "{}: error retrieving previous messages",
-                    meta.status
-                )))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
+    }
+
+    /// Sends a request to the homeserver to get the event `event_id` as well as some of the
+    /// events preceding it and then calls `callback` when it gets the response. If the context
+    /// of the event was already fetched, `callback` is called at once with it and no request is
+    /// made.
+    pub fn get_event_context(
+        &mut self,
+        callback: Callback<Result<ContextResponse, Error>>,
+        event_id: &str,
+    ) -> Option<FetchTask> {
+        let (base_url, access_token, room_id) = {
+            let session = self.session.read().unwrap();
+
+            (
+                session.base_url(),
+                session.access_token.clone(),
+                session.room_id.clone(),
+            )
+        };
+
+        let key = (base_url.clone(), event_id.to_string(), CONTEXT_LIMIT);
+        if let Some(res) = self.cache.write().unwrap().contexts.get(&key) {
+            callback.emit(Ok(res));
+            return None;
+        }
+
+        let filter = build_filter();
+
+        let uri = format!(
+            "{}/_matrix/client/v3/rooms/{}/context/{}?limit={}&filter={}",
+            base_url, room_id, event_id, CONTEXT_LIMIT, filter,
+        )
+        .parse::<Uri>()
+        .expect("Failed to build URI.");
+
+        let request = Request::get(uri)
+            .header("Content-Type", "application/json")
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let cache = self.cache.clone();
+        let handler = move |response: Response<Json<Result<ContextResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                if let Ok(res) = &data {
+                    cache
+                        .write()
+                        .unwrap()
+                        .contexts
+                        .insert(key.clone(), res.clone());
+                }
+
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        Some(self.send(request, handler))
+    }
+
+    /// Sends a request to the homeserver to get the event `event_id` of the room `room_id`,
+    /// which isn't necessarily the room to observe (e.g. the room it replaced), as well as `limit`
+    /// of the events preceding it and then calls `callback` when it gets the response. Like with
+    /// `get_event_context`, no request is made if the response is cached.
+    pub fn get_room_event_context(
+        &mut self,
+        callback: Callback<Result<ContextResponse, Error>>,
+        room_id: &str,
+        event_id: &str,
+        limit: usize,
+    ) -> Option<FetchTask> {
+        let (base_url, access_token) = {
+            let session = self.session.read().unwrap();
+
+            (session.base_url(), session.access_token.clone())
+        };
+
+        let key = (base_url.clone(), event_id.to_string(), limit);
+        if let Some(res) = self.cache.write().unwrap().contexts.get(&key) {
+            callback.emit(Ok(res));
+            return None;
+        }
+
+        let filter = build_filter();
+
+        let uri = format!(
+            "{}/_matrix/client/v3/rooms/{}/context/{}?limit={}&filter={}",
+            base_url, room_id, event_id, limit, filter,
+        )
+        .parse::<Uri>()
+        .expect("Failed to build URI.");
+
+        let request = Request::get(uri)
+            .header("Content-Type", "application/json")
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let cache = self.cache.clone();
+        let handler = move |response: Response<Json<Result<ContextResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                if let Ok(res) = &data {
+                    cache
+                        .write()
+                        .unwrap()
+                        .contexts
+                        .insert(key.clone(), res.clone());
+                }
+
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        Some(self.send(request, handler))
+    }
+
+    /// Sends a request to the homeserver to get the event `event_id` and then calls `callback`
+    /// when it gets the response. If the event was already fetched, `callback` is called at once
+    /// with it and no request is made.
+    pub fn get_event(
+        &mut self,
+        callback: Callback<Result<JsonValue, Error>>,
+        event_id: &str,
+    ) -> Option<FetchTask> {
+        let (base_url, access_token, room_id) = {
+            let session = self.session.read().unwrap();
+
+            (
+                session.base_url(),
+                session.access_token.clone(),
+                session.room_id.clone(),
+            )
+        };
+
+        let key = (base_url.clone(), event_id.to_string());
+        if let Some(event) = self.cache.write().unwrap().events.get(&key) {
+            callback.emit(Ok(event));
+            return None;
+        }
+
+        let uri = format!(
+            "{}/_matrix/client/v3/rooms/{}/event/{}",
+            base_url, room_id, event_id
+        )
+        .parse::<Uri>()
+        .expect("Failed to build URI.");
+
+        let request = Request::get(uri)
+            .header("Content-Type", "application/json")
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let cache = self.cache.clone();
+        let handler = move |response: Response<Json<Result<JsonValue, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                if let Ok(event) = &data {
+                    cache
+                        .write()
+                        .unwrap()
+                        .events
+                        .insert(key.clone(), event.clone());
+                }
+
+                callback.emit(data)
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        Some(self.send(request, handler))
+    }
+
+    /// Sends a request to the homeserver to get the content of the creation event of the room
+    /// and then calls `callback` with the version of the room it gives when it gets the response.
+    pub fn room_version(&mut self, callback: Callback<Result<String, Error>>) -> FetchTask {
+        let (base_url, access_token, room_id) = {
+            let session = self.session.read().unwrap();
+
+            (
+                session.base_url(),
+                session.access_token.clone(),
+                session.room_id.clone(),
+            )
+        };
+
+        let uri = format!(
+            "{}/_matrix/client/v3/rooms/{}/state/m.room.create/",
+            base_url, room_id
+        )
+        .parse::<Uri>()
+        .expect("Failed to build URI.");
+
+        let request = Request::get(uri)
+            .header("Content-Type", "application/json")
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<JsonValue, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data.map(|content| room_version_of(&content)))
+            } else {
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
+            }
+        };
+
+        self.send(request, handler)
     }
 
     pub fn room_state(
@@ -365,32 +1018,29 @@ impl CSBackend {
         callback: Callback<Result<ContextResponse, Error>>,
         event_id: &str,
     ) -> FetchTask {
-        let (server_name, access_token, room_id) = {
+        let (base_url, access_token, room_id) = {
             let session = self.session.read().unwrap();
 
             (
-                session.server_name.clone(),
+                session.base_url(),
                 session.access_token.clone(),
                 session.room_id.clone(),
             )
         };
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(
-                format!(
-                    "/_matrix/client/r0/rooms/{}/context/{}?limit=0",
-                    room_id, event_id,
-                )
-                .as_str(),
-            )
-            .build()
-            .expect("Failed to build URI.");
+        let uri = format!(
+            "{}/_matrix/client/v3/rooms/{}/context/{}?limit=0",
+            base_url, room_id, event_id,
+        )
+        .parse::<Uri>()
+        .expect("Failed to build URI.");
 
         let request = Request::get(uri)
             .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
             .body(Nothing)
             .expect("Failed to build request.");
 
@@ -400,39 +1050,37 @@ impl CSBackend {
             if meta.status.is_success() {
                 callback.emit(data)
             } else {
-                callback.emit(Err(format_err!(
-                    "{}: error retrieving previous messages",
-                    meta.status
-                )))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
     }
 
     /// Sends a request to the homeserver to leave the room which was observed and then calls
     /// `callback` when it gets the response.
     pub fn leave_room(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
-        let (server_name, access_token, room_id) = {
+        let (base_url, access_token, room_id) = {
             let session = self.session.read().unwrap();
 
             (
-                session.server_name.clone(),
+                session.base_url(),
                 session.access_token.clone(),
                 session.room_id.clone(),
             )
         };
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(format!("/_matrix/client/r0/rooms/{}/leave", room_id).as_str())
-            .build()
+        let uri = format!("{}/_matrix/client/v3/rooms/{}/leave", base_url, room_id)
+            .parse::<Uri>()
             .expect("Failed to build URI.");
 
         let request = Request::post(uri)
             .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
+            .header(
+                "Authorization",
+                format!("Bearer {}", access_token.unwrap_or_default()),
+            )
             .body(Nothing)
             .expect("Failed to build request.");
 
@@ -442,29 +1090,30 @@ impl CSBackend {
             if meta.status.is_success() {
                 callback.emit(Ok(()))
             } else {
-                callback.emit(Err(format_err!("{}: error leaving the room", meta.status)))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
     }
 
     /// Sends a request to the homeserver to logout and then calls `callback` when it gets the
     /// response.
     pub fn disconnect(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
-        let (server_name, access_token) = {
+        let (base_url, access_token) = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.access_token.clone())
+            (session.base_url(), session.access_token.clone())
         };
 
-        let uri = format!("https://{}/_matrix/client/r0/logout", server_name);
+        let uri = format!("{}/_matrix/client/v3/logout", base_url);
 
         let request = Request::post(uri)
             .header("Content-Type", "application/json")
             .header(
                 "Authorization",
-                format!("Bearer {}", access_token.expect("No access token")),
+                format!("Bearer {}", access_token.unwrap_or_default()),
             )
             .body(Nothing)
             .expect("Failed to build request.");
@@ -475,42 +1124,140 @@ impl CSBackend {
             if meta.status.is_success() {
                 callback.emit(Ok(()))
             } else {
-                callback.emit(Err(format_err!("{}: error disconnecting", meta.status)))
+                let error = BackendError::from_response(meta.status.as_u16(), &meta.headers);
+                callback.emit(Err(error.into()))
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        self.send(request, handler)
     }
 }
 
-// Builds a filter which allows the application to get events in the federation format with only
-// the fields required to observe the room. Events in the federation format includes informations
-// like the depth of the event in the DAG and the ID of the previous events, it allows the
-// application to properly build the events DAG of a room.
-fn build_filter() -> String {
-    let filter = serde_json::json!({
-        "event_fields": [
-            "room_id",
-            "sender",
-            "origin",
-            "origin_server_ts",
-            "type",
-            "state_key",
-            "content",
-            "prev_events",
-            "depth",
-            "auth_events",
-            "redacts",
-            "unsigned",
-            "event_id",
-            "hashes",
-            "signatures",
-        ],
-        "event_format": "federation",
-    });
+impl CSBackend {
+    // Retrieves the events with which the DAG is built, the latest ones.
+    fn fetch_initial_events(
+        &mut self,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> FetchTask {
+        let session = self.session.clone();
+
+        self.sync(
+            Callback::from(move |response: Result<SyncResponse, Error>| {
+                callback.emit(response.map(|res| {
+                    let mut session = session.write().unwrap();
+                    session.next_batch_token = Some(res.next_batch.clone());
+
+                    let (events, prev_batch) = res.room_events(&session.room_id);
+                    session.prev_batch_token = prev_batch;
+
+                    events
+                }))
+            }),
+            None,
+        )
+    }
+
+    // Retrieves the page of events preceding the previous one.
+    fn fetch_earlier_events(
+        &mut self,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> FetchTask {
+        let session = self.session.clone();
+
+        self.get_prev_messages(Callback::from(
+            move |response: Result<MessagesResponse, Error>| {
+                callback.emit(response.map(|res| {
+                    let (events, end) = res.into_source_events();
+
+                    // Save the prev batch token for the next `/messages` request
+                    session.write().unwrap().prev_batch_token = Some(end);
+
+                    events
+                }))
+            },
+        ))
+    }
+
+    // Retrieves the events which came since the latest ones retrieved.
+    fn fetch_later_events(&mut self, callback: Callback<Result<SourceEvents, Error>>) -> FetchTask {
+        let session = self.session.clone();
+        let next_batch_token = session.read().unwrap().next_batch_token.clone();
+
+        self.sync(
+            Callback::from(move |response: Result<SyncResponse, Error>| {
+                callback.emit(response.map(|res| {
+                    let mut session = session.write().unwrap();
+                    session.next_batch_token = Some(res.next_batch.clone());
+
+                    res.room_events(&session.room_id).0
+                }))
+            }),
+            next_batch_token,
+        )
+    }
+}
+
+/// The CS API paginates with tokens rather than with the IDs of the earlier and later events
+/// requested, which are kept up to date in the session of the backend instead.
+impl DagSource for CSBackend {
+    fn fetch(
+        &mut self,
+        request: &SourceRequest,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> Option<FetchTask> {
+        match request {
+            SourceRequest::Initial => Some(self.fetch_initial_events(callback)),
+            SourceRequest::Before(_) => Some(self.fetch_earlier_events(callback)),
+            SourceRequest::After(_) => Some(self.fetch_later_events(callback)),
+            SourceRequest::Single(event_id) => self.get_event(
+                Callback::from(move |response: Result<JsonValue, Error>| {
+                    callback.emit(response.map(|event| SourceEvents::from_events(vec![event])))
+                }),
+                event_id,
+            ),
+        }
+    }
+}
+
+/// Returns the name of a room given by the `m.room.name` event in its `state_events`, or its
+/// canonical alias if it has no name.
+pub fn room_display_name(state_events: &[JsonValue]) -> Option<String> {
+    let content_of = |etype: &str, field: &str| {
+        state_events
+            .iter()
+            .filter(|ev| ev["type"] == etype)
+            .filter_map(|ev| ev["content"][field].as_str())
+            .find(|value| !value.is_empty())
+            .map(String::from)
+    };
+
+    content_of("m.room.name", "name").or_else(|| content_of("m.room.canonical_alias", "alias"))
+}
+
+/// Returns the base URL of the CS API of `server_name`, given its `.well-known` discovery. The
+/// server name itself is used when it advertises nothing, or something which isn't a URL.
+pub fn discovered_base_url(server_name: &str, well_known: Option<WellKnownClient>) -> String {
+    match well_known {
+        Some(well_known)
+            if well_known.homeserver.base_url.starts_with("https://")
+                || well_known.homeserver.base_url.starts_with("http://") =>
+        {
+            // The paths of the endpoints are appended to the base URL
+            well_known
+                .homeserver
+                .base_url
+                .trim_end_matches('/')
+                .to_string()
+        }
+        _ => format!("https://{}", server_name),
+    }
+}
 
+// Builds the filter which allows the application to get the events in the federation format,
+// see `cs_api::event_filter`, percent-encoded for the query strings.
+pub fn build_filter() -> String {
     percent_encoding::utf8_percent_encode(
-        &serde_json::to_string(&filter).unwrap(),
+        &cs_api::event_filter().to_string(),
         percent_encoding::USERINFO_ENCODE_SET,
     )
     .to_string()
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde_json::Value as JsonValue;
+use stdweb::web::Date;
+
+use super::backend::ContextResponse;
+
+/// The number of responses kept by each cache of a CS backend.
+pub const EVENT_CACHE_CAPACITY: usize = 256;
+
+/// How long, in ms, the contexts of the events are cached. The events around an event change as
+/// new ones are sent or backfilled by the homeserver.
+pub const CONTEXT_MAX_AGE_MS: f64 = 30_000.0;
+
+/// A cache keeping the `capacity` responses last used, the least recently used one being evicted
+/// to make room for a new one. The responses can also be given a maximum age, after which they
+/// are fetched again.
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    max_age: Option<f64>,               // In ms
+    entries: HashMap<K, (u64, f64, V)>, // The responses, along with the last time they were used and the time they were cached
+    clock: u64,                         // Counts the uses of the cache, to order them
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            max_age: None,
+            entries: HashMap::with_capacity(capacity),
+            clock: 0,
+        }
+    }
+
+    /// Creates a cache whose responses are forgotten `max_age` ms after being cached.
+    pub fn with_max_age(capacity: usize, max_age: f64) -> Self {
+        LruCache {
+            max_age: Some(max_age),
+            ..LruCache::new(capacity)
+        }
+    }
+
+    /// Returns a copy of the response cached under `key`, if any and it isn't too old, which
+    /// then becomes the most recently used one.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        if let (Some(max_age), Some((_, cached, _))) = (self.max_age, self.entries.get(key)) {
+            if Date::now() - cached > max_age {
+                self.entries.remove(key);
+                return None;
+            }
+        }
+
+        self.entries.get_mut(key).map(|(used, _, value)| {
+            *used = clock;
+
+            value.clone()
+        })
+    }
+
+    /// Forgets the responses whose keys match `predicate`.
+    pub fn remove_where<P: Fn(&K) -> bool>(&mut self, predicate: P) {
+        self.entries.retain(|key, _| !predicate(key));
+    }
+
+    /// Caches `value` under `key`, evicting the least recently used response if the cache is
+    /// full.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.clock += 1;
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _, _))| *used)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(key, (self.clock, Date::now(), value));
+    }
+}
+
+/// The responses of a homeserver about single events, so that expanding the same region of the
+/// DAG again doesn't fetch them again.
+///
+/// The events are keyed by the server which gave them and their ID, and the contexts also by the
+/// number of events requested around the event. An event only changes when it is redacted, so it
+/// is forgotten when its redaction is received. The contexts are only kept for
+/// `CONTEXT_MAX_AGE_MS`, since the events around an event can change.
+#[derive(Debug)]
+pub struct EventCache {
+    pub events: LruCache<(String, String), JsonValue>,
+    pub contexts: LruCache<(String, String, usize), ContextResponse>,
+}
+
+impl EventCache {
+    pub fn new() -> Self {
+        EventCache::default()
+    }
+
+    /// Forgets the responses given by `server` about the events redacted by the redactions among
+    /// `events`.
+    pub fn forget_redacted(&mut self, server: &str, events: &[JsonValue]) {
+        for event in events {
+            if event["type"] != "m.room.redaction" {
+                continue;
+            }
+
+            // The ID of the redacted event moved into the content in the room version 11
+            let redacts = match event["redacts"]
+                .as_str()
+                .or_else(|| event["content"]["redacts"].as_str())
+            {
+                Some(redacts) => redacts,
+                None => continue,
+            };
+
+            self.events
+                .remove_where(|(s, id)| s == server && id == redacts);
+            self.contexts
+                .remove_where(|(s, id, _)| s == server && id == redacts);
+        }
+    }
+}
+
+impl Default for EventCache {
+    fn default() -> Self {
+        EventCache {
+            events: LruCache::new(EVENT_CACHE_CAPACITY),
+            contexts: LruCache::with_max_age(EVENT_CACHE_CAPACITY, CONTEXT_MAX_AGE_MS),
+        }
+    }
+}
@@ -1,2 +1,5 @@
 pub mod backend;
+pub mod event_cache;
+pub mod scheduler;
 pub mod session;
+pub mod sso;
@@ -0,0 +1,103 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::BkCommand;
+
+/// The maximum number of requests made at once to a homeserver, the long polling of `/sync`
+/// included.
+pub const MAX_IN_FLIGHT: usize = 3;
+
+// The commands waiting to make their request to a homeserver, and until when it must be left
+// alone after rate limiting us
+#[derive(Debug, Default)]
+struct ServerQueue {
+    queued: VecDeque<BkCommand>,
+    paused_until: Option<f64>, // In ms since the epoch
+}
+
+/// Schedules the commands making requests to the homeservers through the CS API, so that
+/// backfilling a room aggressively doesn't get the account rate limited, or banned: at most
+/// `MAX_IN_FLIGHT` requests are made at once to each homeserver, and none is made to a
+/// homeserver which answered with a 429 until the delay it asked for is over. The other commands
+/// wait in a queue, in the order in which they were issued.
+#[derive(Debug, Default)]
+pub struct RequestScheduler {
+    servers: HashMap<String, ServerQueue>,
+}
+
+impl RequestScheduler {
+    pub fn new() -> Self {
+        RequestScheduler::default()
+    }
+
+    /// Tells whether `cmd` can make its request to `server` at the time `now`, while
+    /// `in_flight` requests are being made to it. If it can't, or if other commands are already
+    /// waiting for their turn, it is queued unless it already is.
+    pub fn admit(&mut self, server: &str, cmd: BkCommand, in_flight: usize, now: f64) -> bool {
+        let queue = self.servers.entry(server.to_string()).or_default();
+
+        if queue.queued.is_empty() && !is_paused(queue, now) && in_flight < MAX_IN_FLIGHT {
+            return true;
+        }
+
+        if !queue.queued.contains(&cmd) {
+            queue.queued.push_back(cmd);
+        }
+
+        false
+    }
+
+    /// Takes the next command queued for `server` if it can make its request at the time `now`,
+    /// while `in_flight` requests are being made to it.
+    pub fn next_ready(&mut self, server: &str, in_flight: usize, now: f64) -> Option<BkCommand> {
+        let queue = self.servers.get_mut(server)?;
+
+        if is_paused(queue, now) || in_flight >= MAX_IN_FLIGHT {
+            return None;
+        }
+
+        queue.queued.pop_front()
+    }
+
+    /// Drops `cmd` from the queues of every server, e.g. when the backfill it belongs to is
+    /// cancelled.
+    pub fn drop_queued(&mut self, cmd: BkCommand) {
+        for queue in self.servers.values_mut() {
+            queue.queued.retain(|queued| *queued != cmd);
+        }
+    }
+
+    /// Leaves `server` alone until `until`, after it rate limited a request.
+    pub fn pause(&mut self, server: &str, until: f64) {
+        let queue = self.servers.entry(server.to_string()).or_default();
+
+        queue.paused_until = Some(queue.paused_until.unwrap_or(until).max(until));
+    }
+
+    /// Returns the servers which have commands waiting.
+    pub fn waiting_servers(&self) -> Vec<String> {
+        self.servers
+            .iter()
+            .filter(|(_, queue)| !queue.queued.is_empty())
+            .map(|(server, _)| server.clone())
+            .collect()
+    }
+
+    pub fn queued(&self, server: &str) -> usize {
+        self.servers
+            .get(server)
+            .map_or(0, |queue| queue.queued.len())
+    }
+
+    /// Returns how long `server` is still left alone at the time `now`, in ms, if it is.
+    pub fn paused_for(&self, server: &str, now: f64) -> Option<u64> {
+        self.servers
+            .get(server)
+            .and_then(|queue| queue.paused_until)
+            .filter(|until| now < *until)
+            .map(|until| (until - now) as u64)
+    }
+}
+
+fn is_paused(queue: &ServerQueue, now: f64) -> bool {
+    queue.paused_until.is_some_and(|until| now < until)
+}
@@ -3,12 +3,18 @@
 #[derive(Clone, Debug)]
 pub struct Session {
     pub server_name: String,
+    pub base_url: Option<String>, // The base URL of the CS API, once discovered with the `.well-known` of the server name
     pub room_id: String,
 
     pub username: String,
     pub user_id: String,
     pub password: String,
     pub access_token: Option<String>,
+    pub refresh_token: Option<String>, // Allows to get a new access token once it has expired
+
+    pub as_token: String, // The token of an application service, to act as one of its users instead of logging in
+    pub appservice_user_id: String, // The user the application service acts as
+    pub appservice: bool, // Whether the access token is `as_token`, the requests then acting as `appservice_user_id`
 
     pub device_id: Option<String>,
     pub filter_id: Option<String>,
@@ -20,6 +26,7 @@ impl Session {
     pub fn empty() -> Self {
         Session {
             server_name: String::new(),
+            base_url: None,
             room_id: String::new(),
 
             username: String::new(),
@@ -27,6 +34,11 @@ impl Session {
             password: String::new(),
 
             access_token: None,
+            refresh_token: None,
+
+            as_token: String::new(),
+            appservice_user_id: String::new(),
+            appservice: false,
 
             device_id: None,
             filter_id: None,
@@ -34,4 +46,13 @@ impl Session {
             prev_batch_token: None,
         }
     }
+
+    /// Returns the base URL of the CS API of the homeserver, e.g. `https://matrix.example.org`,
+    /// which is the one discovered for the server name if any, or else the server name itself.
+    pub fn base_url(&self) -> String {
+        match &self.base_url {
+            Some(base_url) => base_url.clone(),
+            None => format!("https://{}", self.server_name),
+        }
+    }
 }
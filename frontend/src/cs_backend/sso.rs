@@ -0,0 +1,67 @@
+use serde_derive::{Deserialize, Serialize};
+use stdweb::unstable::TryInto;
+
+use crate::ViewIndex;
+
+// The key under which the login in progress is kept in the session storage of the browser
+const PENDING_KEY: &str = "sso_pending";
+
+/// A login with the SSO of a homeserver, which is kept while the user is away on the pages of
+/// the identity provider, since the application is reloaded when they are redirected back.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PendingLogin {
+    pub view_id: ViewIndex,
+    pub server_name: String,
+    #[serde(default)]
+    pub base_url: Option<String>, // The base URL of the CS API discovered for the server name
+    pub room_id: String,
+}
+
+/// Keeps `pending` and then sends the user to `sso_url`, the SSO redirect endpoint of the
+/// homeserver.
+pub fn start(pending: &PendingLogin, sso_url: &str) {
+    let pending = serde_json::to_string(pending).expect("Failed to serialize the pending login");
+
+    js! {
+        sessionStorage.setItem(@{PENDING_KEY}, @{pending});
+        window.location.href = @{sso_url};
+    };
+}
+
+/// Returns the URL the homeserver must redirect the user to at the end of the SSO flow, i.e.
+/// the current page without its query string.
+pub fn redirect_url() -> String {
+    let url = js! {
+        return window.location.origin + window.location.pathname;
+    };
+
+    url.try_into().expect("Failed to get the URL of the page")
+}
+
+/// Returns the login in progress along with the login token given by the homeserver, if the
+/// user has just been redirected back by it, and forgets them.
+pub fn take_pending() -> Option<(PendingLogin, String)> {
+    let found: Option<Vec<String>> = js! {
+        var token = new URLSearchParams(window.location.search).get("loginToken");
+        var pending = sessionStorage.getItem(@{PENDING_KEY});
+
+        if (token == null || pending == null) {
+            return null;
+        }
+
+        // Don't let the token in the address bar nor in the history
+        sessionStorage.removeItem(@{PENDING_KEY});
+        window.history.replaceState(null, "", window.location.pathname);
+
+        return [pending, token];
+    }
+    .try_into()
+    .unwrap_or(None);
+
+    match found.as_ref().map(|found| found.as_slice()) {
+        Some([pending, token]) => serde_json::from_str(pending)
+            .ok()
+            .map(|pending| (pending, token.clone())),
+        _ => None,
+    }
+}
@@ -0,0 +1,29 @@
+use failure::Error;
+use yew::callback::Callback;
+use yew::services::fetch::FetchTask;
+
+pub use crate::model::source::{SourceEvents, SourceRequest};
+
+/// A source from which the events DAG of a room can be retrieved in the browser, e.g. a
+/// homeserver via the CS API or its admin API, or the database of a homeserver via the Postgres
+/// backend.
+///
+/// This is the counterpart of `EventSource` for the web application, which can't block on its
+/// requests: they are answered the same way, but later. `fetch` sends the request to the source
+/// and returns at once, `callback` being called when the response arrives. The returned task must
+/// be kept until then, dropping it cancels the request.
+///
+/// Every kind of view fetches the initial, the earlier, the later and the single events of its DAG
+/// through its source, see `View::dag_fetch`, and gives them to the DAG as `SourceEvents`, so that
+/// a new source only has to implement this trait. Only the CS views sync rather than fetch their
+/// initial and later events, since the syncs also bring the read receipts of the room.
+pub trait DagSource {
+    /// Makes the request `request` to the source, whose events are given to `callback`. No task
+    /// is returned if the source could answer at once, e.g. with an event it already knew,
+    /// `callback` having then been called.
+    fn fetch(
+        &mut self,
+        request: &SourceRequest,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> Option<FetchTask>;
+}
@@ -0,0 +1,55 @@
+use crate::model::standalone;
+
+/// Makes the browser download a file named `file_name` containing `content`, so that data of the
+/// application can be saved outside of the browser.
+pub fn download(file_name: &str, mime_type: &str, content: &str) {
+    js! {
+        var blob = new Blob([@{content}], { type: @{mime_type} });
+        var url = URL.createObjectURL(blob);
+        var link = document.createElement("a");
+
+        link.href = url;
+        link.download = @{file_name};
+        document.body.appendChild(link);
+        link.click();
+        document.body.removeChild(link);
+
+        URL.revokeObjectURL(url);
+    };
+}
+
+/// Makes the browser download the standalone page `html` (see `standalone::to_html`) as
+/// `file_name`. The source of vis.js is fetched and inlined in the page so that it also works
+/// offline; if it can't be fetched, the page loads it from its CDN.
+pub fn download_html(file_name: &str, html: &str) {
+    js! {
+        var html = @{html};
+        var vis_js_tag = @{standalone::vis_js_tag()};
+
+        fetch(@{standalone::VIS_JS_URL})
+            .then(function (response) {
+                return response.ok ? response.text() : Promise.reject(response.status);
+            })
+            .then(function (source) {
+                var inlined = "<script>" + source.split("</script").join("<\\/script") + "</script>";
+
+                // A function as replacement keeps the `$` of the source as they are
+                return html.replace(vis_js_tag, function () { return inlined; });
+            }, function () {
+                return html;
+            })
+            .then(function (html) {
+                var blob = new Blob([html], { type: "text/html" });
+                var url = URL.createObjectURL(blob);
+                var link = document.createElement("a");
+
+                link.href = url;
+                link.download = @{file_name};
+                document.body.appendChild(link);
+                link.click();
+                document.body.removeChild(link);
+
+                URL.revokeObjectURL(url);
+            });
+    };
+}
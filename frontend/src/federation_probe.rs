@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+use failure::{format_err, Error};
+use serde_derive::Deserialize;
+use yew::callback::Callback;
+use yew::format::{Json, Nothing};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+// The default port of the federation API when the server name doesn't have one
+const DEFAULT_FEDERATION_PORT: u16 = 8448;
+
+/// Represents the JSON body of a response to a `GET /.well-known/matrix/server` request.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WellKnownServer {
+    #[serde(rename = "m.server")]
+    pub server: String,
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/federation/v1/version` request.
+#[derive(Clone, Debug, Deserialize)]
+pub struct VersionResponse {
+    pub server: ServerVersion,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ServerVersion {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+/// The result of the probing of a server.
+#[derive(Clone, Debug)]
+pub enum ServerStatus {
+    Probing,
+    Alive(ServerVersion),
+    Dead,
+}
+
+/// This service probes the federation API of the servers from which events originate, in order
+/// to detect the servers which are unreachable or dead.
+pub struct FederationProbe {
+    fetch: FetchService,
+    pub tasks: HashMap<String, FetchTask>, // The requests of the probes in progress, by server
+}
+
+impl FederationProbe {
+    pub fn new() -> Self {
+        FederationProbe {
+            fetch: FetchService::new(),
+            tasks: HashMap::new(),
+        }
+    }
+
+    /// Looks for a delegation of the federation API of `server_name` to another server and then
+    /// calls `callback` with it, or with `None` if there is no delegation.
+    pub fn well_known(
+        &mut self,
+        callback: Callback<Option<WellKnownServer>>,
+        server_name: &str,
+    ) -> FetchTask {
+        let uri = format!("https://{}/.well-known/matrix/server", server_name);
+
+        let request = Request::get(uri)
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<WellKnownServer, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data.ok())
+            } else {
+                callback.emit(None)
+            }
+        };
+
+        self.fetch.fetch(request, handler.into())
+    }
+
+    /// Requests the version of the server whose federation API is at `host` and then calls
+    /// `callback` when it gets the response.
+    pub fn version(
+        &mut self,
+        callback: Callback<Result<VersionResponse, Error>>,
+        host: &str,
+    ) -> FetchTask {
+        let uri = format!("https://{}/_matrix/federation/v1/version", host);
+
+        let request = Request::get(uri)
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<VersionResponse, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                callback.emit(Err(format_err!(
+                    "{}: error getting the version of the server",
+                    meta.status
+                )))
+            }
+        };
+
+        self.fetch.fetch(request, handler.into())
+    }
+}
+
+/// Returns whether the federation API of `server_name` can be delegated to another server with
+/// `.well-known/matrix/server`, which isn't the case of the server names with an explicit port
+/// or which are IP addresses.
+pub fn can_delegate(server_name: &str) -> bool {
+    let is_ip = server_name.starts_with('[') || server_name.parse::<Ipv4Addr>().is_ok();
+
+    !is_ip && !has_port(server_name)
+}
+
+/// Returns the host of the federation API of `server_name`, given its `.well-known` delegation.
+pub fn federation_host(server_name: &str, well_known: Option<WellKnownServer>) -> String {
+    let host = match well_known {
+        Some(well_known) if can_delegate(server_name) => well_known.server,
+        _ => server_name.to_string(),
+    };
+
+    if has_port(&host) {
+        host
+    } else {
+        format!("{}:{}", host, DEFAULT_FEDERATION_PORT)
+    }
+}
+
+// The port is the part after the last colon, unless this colon is part of an IPv6 address
+fn has_port(host: &str) -> bool {
+    match host.rfind(':') {
+        Some(i) => !host[i..].contains(']'),
+        None => false,
+    }
+}
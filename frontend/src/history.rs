@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use crate::model::dag::{FilterState, RemovedEvent};
+use crate::model::event::Field;
+use crate::model::export::ExportedView;
+use crate::model::expression::ColourRule;
+use crate::model::query::EventFilter;
+use crate::ViewIndex;
+
+/// How to revert what an operation did to the DAG of a view.
+pub enum DagChange {
+    Added(Vec<String>), // The IDs of the events the operation added, which are removed
+    Removed(Vec<RemovedEvent>), // The events the operation removed, which are added back
+    Replaced(Option<ExportedView>), // The DAG the operation replaced, which is imported back, if the view had one
+    Filters(FilterState), // The events hidden, highlighted and collapsed before the operation
+}
+
+/// The query form, the colour rules and the time range, as an operation found them.
+pub struct QueryState {
+    pub query: EventFilter,
+    pub expression: String,
+    pub colour_rules: Vec<ColourRule>,
+    pub time_range: Option<(i64, i64)>,
+}
+
+/// How to revert what an operation changed. Only the changes are kept, so that the events
+/// received since the operation stay in the DAGs when it is undone.
+pub struct Reversal {
+    pub dags: Vec<(ViewIndex, DagChange)>, // The changes of the DAGs of the views changed by the operation
+    pub fields: Vec<(ViewIndex, HashSet<Field>)>, // The fields of the labels of the views whose labels the operation changed
+    pub query: Option<QueryState>,                // The query state, if the operation changed it
+}
+
+impl Reversal {
+    pub fn of_dags(dags: Vec<(ViewIndex, DagChange)>) -> Self {
+        Reversal {
+            dags,
+            fields: Vec::new(),
+            query: None,
+        }
+    }
+
+    pub fn of_fields(view_id: ViewIndex, fields: HashSet<Field>) -> Self {
+        Reversal {
+            dags: Vec::new(),
+            fields: vec![(view_id, fields)],
+            query: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dags.is_empty() && self.fields.is_empty() && self.query.is_none()
+    }
+}
+
+/// An operation which can be undone or redone, along with how to revert it.
+pub struct HistoryEntry {
+    pub description: String,
+    pub reversal: Reversal,
+}
+
+/// The history of the operations mutating the DAGs (loading earlier events, restoring saved
+/// events, filtering the events, changing the fields of the labels...), so that they can be
+/// undone and redone.
+///
+/// The entries which add events keep the events only once they are undone, so only the last
+/// `capacity` operations are kept.
+pub struct History {
+    undo: Vec<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Records how to revert a new operation. The operations undone until now can't be redone
+    /// anymore.
+    pub fn record(&mut self, entry: HistoryEntry) {
+        self.redo.clear();
+        self.push_undo(entry);
+    }
+
+    /// Takes the last operation done, to undo it.
+    pub fn take_undo(&mut self) -> Option<HistoryEntry> {
+        self.undo.pop()
+    }
+
+    /// Takes the last operation undone, to redo it.
+    pub fn take_redo(&mut self) -> Option<HistoryEntry> {
+        self.redo.pop()
+    }
+
+    /// Records how to revert an operation which was redone.
+    pub fn push_undo(&mut self, entry: HistoryEntry) {
+        self.undo.push(entry);
+
+        if self.undo.len() > self.capacity {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Records how to revert an operation which was undone.
+    pub fn push_redo(&mut self, entry: HistoryEntry) {
+        self.redo.push(entry);
+    }
+
+    /// Returns the description of the operation which would be undone.
+    pub fn next_undo(&self) -> Option<&str> {
+        self.undo.last().map(|entry| entry.description.as_str())
+    }
+
+    /// Returns the description of the operation which would be redone.
+    pub fn next_redo(&self) -> Option<&str> {
+        self.redo.last().map(|entry| entry.description.as_str())
+    }
+}
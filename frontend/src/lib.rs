@@ -1,8 +1,8 @@
 #![recursion_limit = "512"]
 
 extern crate failure;
+extern crate matrix_dag;
 extern crate percent_encoding;
-extern crate petgraph;
 extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
@@ -10,269 +10,242 @@ extern crate serde_json;
 extern crate stdweb;
 extern crate yew;
 
+mod admin_backend;
+mod backend_error;
 mod cs_backend;
-mod model;
+mod dag_source;
+mod download;
+mod federation_probe;
+mod history;
+mod list_view;
+mod minimap;
 mod pg_backend;
+mod server_manager;
+mod session_log;
+mod storage;
+mod token_refresh;
+mod vault;
+mod vault_ui;
+mod views;
+mod views_manager;
 mod visjs;
+mod workspace;
 
-use std::collections::HashSet;
+pub use matrix_dag as model;
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
 use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use stdweb::unstable::TryInto;
 use stdweb::web;
+use stdweb::web::event::{IEvent, IKeyboardEvent, KeyDownEvent};
+use stdweb::web::IEventTarget;
 use stdweb::web::IParentNode;
-use yew::services::fetch::FetchTask;
 use yew::services::timeout::TimeoutTask;
 use yew::services::{ConsoleService, TimeoutService};
-use yew::{html, Callback, Component, ComponentLink, Html, Renderable, ShouldRender};
+use yew::{html, Component, ComponentLink, Html, Renderable, ShouldRender};
 
+use admin_backend::backend::{EventReportsResponse, RoomStateResponse};
+use backend_error::{retry_delay, BackendError, MAX_RETRIES};
 use cs_backend::backend::{
-    CSBackend, ConnectionResponse, ContextResponse, JoinedRooms, MessagesResponse, SyncResponse,
+    discovered_base_url, ConnectionResponse, ContextResponse, FilterResponse, JoinedRooms,
+    PublicRoom, PublicRoomsResponse, RefreshResponse, RoomNames, SyncResponse, WellKnownClient,
+};
+use cs_backend::scheduler::RequestScheduler;
+use cs_backend::sso::{self, PendingLogin};
+use dag_source::{DagSource, SourceEvents, SourceRequest};
+use federation_probe::{
+    can_delegate, federation_host, FederationProbe, ServerStatus, VersionResponse, WellKnownServer,
 };
-use cs_backend::session::Session as CSSession;
-use model::dag::RoomEvents;
-use model::event::Field;
-use pg_backend::backend::{EventsResponse, PostgresBackend};
-use pg_backend::session::Session as PgSession;
-use visjs::VisJsService;
+use history::{DagChange, History, HistoryEntry, QueryState, Reversal};
+use list_view::ListView;
+use model::annotation::{Annotations, Tag};
+use model::clock_skew::{ClockSkew, DEFAULT_CLOCK_SKEW_THRESHOLD};
+use model::dag::{
+    format_latency, AddOutcome, AddPreview, HiddenItem, InterestingEvent, RoomEvents, SizeMetric,
+    StoredRoomEvents,
+};
+use model::depth_anomaly::{DepthAnomaly, DEFAULT_DEPTH_JUMP_THRESHOLD};
+use model::event::{format_timestamp, Field};
+use model::event_table::{EventRow, SortKey};
+use model::export::{ExportedDocument, ExportedView};
+use model::expression::{ColourRule, Expression};
+use model::genealogy::{self, RoomIncarnation};
+use model::id_shortener::IdShortener;
+use model::inspector::ContentView;
+use model::journal;
+use model::label_format::LabelFormat;
+use model::layout::LayoutAlgorithm;
+use model::layout_cache::LayoutCache;
+use model::layout_options::{Direction, LayoutOptions};
+use model::metrics::DagMetrics;
+use model::outline::{self, OutlineEntry};
+use model::preset::{DisplayPreset, PresetFilters, PresetPalette};
+use model::progress::BackfillProgress;
+use model::provenance::FetchBatch;
+use model::receipts::ReadMarker;
+use model::reconcile::DEFAULT_DEDUP_WINDOW;
+use model::room_version::{RoomVersionRules, VersionMismatch};
+use model::snapshot::{SnapshotView, ViewSnapshot};
+use model::standalone;
+use model::state_timeline::{ContentChange, StateTimelineEntry};
+use model::theme::Theme;
+use model::workspace::SetOperation;
+use pg_backend::backend::EventsResponse;
+use server_manager::{ConnectionStatus, ManagedServer, ServerPanel};
+use session_log::{RecordedView, Recorder, SessionLog};
+use storage::StorageService;
+use vault::{Credentials, CredentialsKind};
+use vault_ui::VaultPanel;
+use views::{CSView, View};
+use views_manager::{Layout, Pin, ViewsManager, DEFAULT_PIN_HOPS};
+use workspace::WorkspacePanel;
 
 pub type ViewIndex = usize;
 
 pub struct Model {
     console: ConsoleService,
     timeout: TimeoutService,
-    vis: VisJsService,
+    vis: ViewsManager,
+    storage: StorageService,
+    vault: VaultPanel,
+    probe: FederationProbe,
+    server_status: BTreeMap<String, ServerStatus>, // The status of the origin servers of the events
+    server_panel: ServerPanel,
     link: ComponentLink<Self>,
 
     bk_type: Arc<RwLock<BackendChoice>>,
     view_idx: ViewIndex,
     views: Vec<View>,
     event_body: Option<String>,
+    event_latency: Option<i64>, // The delivery latency of the event whose body is displayed, if known
+    event_batch: Option<(usize, FetchBatch)>, // The request this event first came in, and its index, if known
+    content_view: Option<ContentView>, // The readable rendering of the content of this event, if its type is known
     room_state: Option<String>,
+    dag_metrics: Option<(ViewIndex, DagMetrics)>, // The metrics last computed, and the view whose DAG they measure
     fields_choice: FieldsChoice,
+    workspace_panel: WorkspacePanel,
+    colour_rules: Vec<ColourRule>, // The expressions giving their colours to the nodes of the events they match
+    colour_rule_form: ColourRule,  // The colour rule being written
+    list_view: ListView,
+    text_outline: bool, // Whether the events are also outlined as text, for the screen readers
+    shared_selection: Option<(String, Vec<ViewIndex>)>, // The event selected in every view, and the views missing it
+    selection_moved: Rc<Cell<bool>>, // Whether the last move of the selection with the arrow keys found an event to move to
+    preset_json: String,
+    export_json: String,
+    room_export_json: String, // The rows of the events of a room exported from the database of Synapse
+    recorder: Recorder,
+    sampling: bool,
+    lazy_rendering: bool,
+    fog: bool,
+    size_metric: Option<SizeMetric>,
+    show_relations: bool,
+    show_ghosts: bool,
+    show_authority: bool,
+    outage: Option<(String, String)>, // The server taken offline in a simulation and the last event it received
+    compress_depths: bool,
+    transitive_reduction: bool, // Whether the edges implied by longer paths are left out of the networks
+    color_by_latency: bool, // Whether the nodes are coloured by the delivery latency of their events
+    color_by_batch: bool, // Whether the nodes are coloured by the request which brought their events
+    show_read_markers: bool, // Whether the read receipts and fully-read markers are shown on the nodes
+    theme: Theme,
+    id_shortener: IdShortener,
+    label_format: LabelFormat,
+    dedup_window: usize, // The number of recent deliveries remembered to suppress the duplicate events
+    depth_jump: i64, // The largest difference between the depths of an event and of its `prev_events` not reported
+    time_range: Option<(i64, i64)>, // Only the events created in this range are displayed
+    replay: Option<Replay>, // The replay of the arrival of the events, if one is in progress
+    replay_speed: u32, // The number of events revealed per second by the replays
+    room_search: String, // Filters the rooms of the room picker, and is searched in the public rooms directory
+    timeline_limit: u32, // The number of events of the timeline of the room returned by each sync of the CS views
+    timeline_state: (String, String), // The type and the state key of the state whose timeline is displayed
+    retries: HashMap<BkCommand, (u32, TimeoutTask)>, // The commands waiting to be retried, with the number of attempts
+    pending_fetches: HashMap<(ViewIndex, &'static str), (Option<String>, f64)>, // The token and the time of the requests of each view to each endpoint, until their events are added
+    scheduler: RequestScheduler, // Holds back the requests to the homeservers which are busy or rate limiting us
+    toasts: Vec<(u64, String)>,  // The errors displayed to the user, with their ID
+    toast_tasks: HashMap<u64, TimeoutTask>, // Dismiss the toasts after a while
+    next_toast: u64,
+    pending_restore: Option<(ViewIndex, StoredRoomEvents, AddPreview)>, // A restoration waiting for the user's confirmation
+    earlier_fetches: HashMap<ViewIndex, BackfillProgress>, // The progress of the loading of earlier events of each view, while it runs
+    history: History,
 }
 
-pub enum View {
-    CS(CSView),
-    Postgres(PgView),
-}
-
-impl View {
-    pub fn get_id(&self) -> ViewIndex {
-        match self {
-            View::CS(v) => v.id,
-            View::Postgres(v) => v.id,
-        }
-    }
-
-    pub fn get_events_dag(&self) -> &Option<Arc<RwLock<RoomEvents>>> {
-        match self {
-            View::CS(v) => &v.events_dag,
-            View::Postgres(v) => &v.events_dag,
-        }
-    }
-}
-
-// This contains every informations needed for the observation of a room from a given HS by using
-// the CS API.
-pub struct CSView {
-    id: ViewIndex,
-
-    connection_callback: Callback<Result<ConnectionResponse, Error>>,
-    connection_task: Option<FetchTask>,
-
-    listing_rooms_callback: Callback<Result<JoinedRooms, Error>>,
-    listing_rooms_task: Option<FetchTask>,
-
-    joining_room_callback: Callback<Result<(), Error>>,
-    joining_room_task: Option<FetchTask>,
-
-    sync_callback: Callback<Result<SyncResponse, Error>>,
-    sync_task: Option<FetchTask>,
-
-    more_msg_callback: Callback<Result<MessagesResponse, Error>>,
-    more_msg_task: Option<FetchTask>,
-
-    state_callback: Callback<Result<ContextResponse, Error>>,
-    state_task: Option<FetchTask>,
-
-    leaving_room_callback: Callback<Result<(), Error>>,
-    leaving_room_task: Option<FetchTask>,
-
-    disconnection_callback: Callback<Result<(), Error>>,
-    disconnection_task: Option<FetchTask>,
-
-    session: Arc<RwLock<CSSession>>,
-    backend: CSBackend,
-    events_dag: Option<Arc<RwLock<RoomEvents>>>,
-}
-
-impl CSView {
-    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> CSView {
-        let session = Arc::new(RwLock::new(CSSession::empty()));
-
-        CSView {
-            id,
-
-            connection_callback: link.send_back(
-                move |response: Result<ConnectionResponse, Error>| match response {
-                    Ok(res) => Msg::BkRes(BkResponse::Connected(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::ConnectionFailed(id)),
-                },
-            ),
-            connection_task: None,
-
-            listing_rooms_callback: link.send_back(move |response: Result<JoinedRooms, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::RoomsList(id, res)),
-                    Err(e) => {
-                        ConsoleService::new().log(&format!("{}", e));
-                        Msg::BkRes(BkResponse::ListingRoomsFailed(id))
-                    }
-                }
-            }),
-            listing_rooms_task: None,
-
-            joining_room_callback: link.send_back(
-                move |response: Result<(), Error>| match response {
-                    Ok(_) => Msg::BkRes(BkResponse::RoomJoined(id)),
-                    Err(_) => Msg::BkRes(BkResponse::JoiningRoomFailed(id)),
-                },
-            ),
-            joining_room_task: None,
-
-            sync_callback: link.send_back(move |response: Result<SyncResponse, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::Synced(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::SyncFailed(id)),
-                }
-            }),
-            sync_task: None,
-
-            more_msg_callback: link.send_back(move |response: Result<MessagesResponse, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::MsgGot(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::MoreMsgFailed(id)),
-                }
-            }),
-            more_msg_task: None,
-
-            state_callback: link.send_back(move |response: Result<ContextResponse, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::StateFetched(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::FetchStateFailed(id)),
-                }
-            }),
-            state_task: None,
-
-            leaving_room_callback: link.send_back(
-                move |response: Result<(), Error>| match response {
-                    Ok(_) => Msg::BkRes(BkResponse::RoomLeft(id)),
-                    Err(_) => Msg::BkRes(BkResponse::LeavingRoomFailed(id)),
-                },
-            ),
-            leaving_room_task: None,
+// The number of operations which can be undone
+const HISTORY_CAPACITY: usize = 20;
 
-            disconnection_callback: link.send_back(
-                move |response: Result<(), Error>| match response {
-                    Ok(_) => Msg::BkRes(BkResponse::Disconnected(id)),
-                    Err(_) => Msg::BkRes(BkResponse::DisconnectionFailed(id)),
-                },
-            ),
-            disconnection_task: None,
+// The number of events listed as a starting point for exploring a DAG
+const INTERESTING_EVENTS_MAX: usize = 20;
 
-            session: session.clone(),
-            backend: CSBackend::with_session(session),
-            events_dag: None,
-        }
-    }
-}
+// The number of events of the timeline of the room returned by each sync by default
+const DEFAULT_TIMELINE_LIMIT: u32 = 50;
 
-// This contains every informations needed for the observation of a room from a given HS by using
-// the PostgreSQL backend.
-pub struct PgView {
-    id: ViewIndex,
+// The number of events of the room a room replaced which are retrieved when stitching it
+const PREDECESSOR_EVENTS: usize = 50;
 
-    deepest_callback: Callback<Result<EventsResponse, Error>>,
-    deepest_task: Option<FetchTask>,
+// The number of depths shown above and below an event when jumping to it
+const JUMP_MARGIN: i64 = 3;
 
-    ancestors_callback: Callback<Result<EventsResponse, Error>>,
-    ancestors_task: Option<FetchTask>,
+// The number of senders and of origin servers listed in the statistics panel
+const STATS_TOP_ENTRIES: usize = 10;
 
-    stop_callback: Callback<Result<(), Error>>,
-    stop_task: Option<FetchTask>,
+// The key under which the name of the theme chosen is saved with the `StorageService`
+const THEME_KEY: &str = "theme";
 
-    descendants_callback: Callback<Result<EventsResponse, Error>>,
-    descendants_task: Option<FetchTask>,
-    descendants_timeout_task: Option<TimeoutTask>,
+// The key under which the annotations of the events are saved with the `StorageService`
+const ANNOTATIONS_KEY: &str = "annotations";
 
-    state_callback: Callback<Result<EventsResponse, Error>>,
-    state_task: Option<FetchTask>,
+// The key under which the configured servers are saved with the `StorageService`
+const SERVERS_KEY: &str = "servers";
 
-    session: Arc<RwLock<PgSession>>,
-    backend: PostgresBackend,
-    events_dag: Option<Arc<RwLock<RoomEvents>>>,
-}
+// The key under which the layout options of the views are saved with the `StorageService`
+const LAYOUT_OPTIONS_KEY: &str = "layout_options";
 
-impl PgView {
-    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> PgView {
-        let session = Arc::new(RwLock::new(PgSession::empty()));
+// The key under which the positions of the nodes of the networks once laid out are saved with
+// the `StorageService`
+const LAYOUT_CACHE_KEY: &str = "layout_cache";
 
-        PgView {
-            id,
+// The number of seconds between two health checks of the configured servers
+const HEALTH_CHECK_INTERVAL: u64 = 60;
 
-            deepest_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::DeepestEvents(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::DeepestRqFailed(id)),
-                }
-            }),
-            deepest_task: None,
+// The number of events revealed per second by the replays by default
+const DEFAULT_REPLAY_SPEED: u32 = 2;
 
-            ancestors_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::Ancestors(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::AncestorsRqFailed(id)),
-                }
-            }),
-            ancestors_task: None,
+// The number of seconds the errors are displayed to the user
+const TOAST_DURATION: u64 = 8;
 
-            descendants_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::Descendants(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::DescendantsRqFailed(id)),
-                }
-            }),
-            descendants_task: None,
-            descendants_timeout_task: None,
+// The colour the new colour rules start with
+const DEFAULT_RULE_COLOUR: &str = "#ff8800";
 
-            state_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
-                match response {
-                    Ok(res) => Msg::BkRes(BkResponse::State(id, res)),
-                    Err(_) => Msg::BkRes(BkResponse::StateRqFailed(id)),
-                }
-            }),
-            state_task: None,
+// The sampling rate used by the sampling mode
+const SAMPLING_RATE: usize = 10;
 
-            stop_callback: link.send_back(move |response: Result<(), Error>| match response {
-                Ok(_) => Msg::BkRes(BkResponse::Disconnected(id)),
-                Err(_) => Msg::BkRes(BkResponse::DisconnectionFailed(id)),
-            }),
-            stop_task: None,
+// The number of depths above and below the viewport whose events are kept in the networks when
+// the lazy rendering is enabled
+const LAZY_RENDERING_MARGIN: i64 = 50;
 
-            session: session.clone(),
-            backend: PostgresBackend::with_session(session),
-            events_dag: None,
-        }
-    }
-}
+// The endpoints whose requests are recorded as the provenance of the events they bring, see
+// `RoomEvents::record_batch`
+const SYNC_ENDPOINT: &str = "/sync";
+const MESSAGES_ENDPOINT: &str = "/messages";
+const CONTEXT_ENDPOINT: &str = "/context";
+const DEEPEST_ENDPOINT: &str = "/visualisations/deepest";
+const ANCESTORS_ENDPOINT: &str = "/visualisations/ancestors";
+const DESCENDANTS_ENDPOINT: &str = "/visualisations/descendants";
+const EVENT_ENDPOINT: &str = "/event";
+const ADMIN_BACKWARDS_ENDPOINT: &str = "/_synapse/admin/v1/messages?dir=b";
+const ADMIN_FORWARDS_ENDPOINT: &str = "/_synapse/admin/v1/messages?dir=f";
 
 // This defines which backend is used by the application for the retrieval of the events DAG.
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum BackendChoice {
     CS,
     Postgres,
+    Admin,
 }
 
 // This defines which fields of the event body will be displayed in the nodes of the displayed DAG.
@@ -286,28 +259,60 @@ struct FieldsChoice {
     depth: bool,
     redacts: bool,
     event_id: bool,
+    unknown_fields: bool,
+    pointer: String, // The JSON pointer being entered, before it is added to `fields`
 
     fields: HashSet<Field>,
 }
 
+impl FieldsChoice {
+    fn from_fields(fields: HashSet<Field>) -> FieldsChoice {
+        FieldsChoice {
+            sender: fields.contains(&Field::Sender),
+            origin: fields.contains(&Field::Origin),
+            origin_server_ts: fields.contains(&Field::OriginServerTS),
+            etype: fields.contains(&Field::Type),
+            state_key: fields.contains(&Field::StateKey),
+            prev_events: fields.contains(&Field::PrevEvents),
+            depth: fields.contains(&Field::Depth),
+            redacts: fields.contains(&Field::Redacts),
+            event_id: fields.contains(&Field::EventID),
+            unknown_fields: fields.contains(&Field::UnknownFields),
+            pointer: String::new(),
+
+            fields,
+        }
+    }
+}
+
 pub enum Msg {
     UI(UIEvent),
     UICmd(UICommand),
     BkCmd(BkCommand),
     BkRes(BkResponse),
+    Retry(BkCommand), // Makes again the requests of a command which failed temporarily
 }
 
 /// These messages notifies the application of changes in the data modifiable via the UI.
 pub enum UIEvent {
     ChooseCSBackend,
     ChoosePostgresBackend,
+    ChooseAdminBackend,
     ViewChoice(ViewIndex),
     AddView,
     ServerName(html::ChangeData),
     RoomId(html::ChangeData),
+    RoomSearch(html::ChangeData),
+    TimelineLimit(html::ChangeData),
+    TimelineType(html::ChangeData),
+    TimelineStateKey(html::ChangeData),
+    DismissToast(u64),
 
     Username(html::ChangeData),
     Password(html::ChangeData),
+    AsToken(html::ChangeData),
+    AppserviceUserId(html::ChangeData),
+    AccessToken(html::ChangeData),
 
     ToggleSender,
     ToggleOrigin,
@@ -318,53 +323,300 @@ pub enum UIEvent {
     ToggleDepth,
     ToggleRedacts,
     ToggleEventID,
+    ToggleUnknownFields,
+    Pointer(html::ChangeData),
+    AddPointer,
+    RemovePointer(String),
+
+    ToggleSampling,
+    ToggleLazyRendering,
+    ToggleFog,
+    ToggleSplitLayout,
+    ToggleListView,
+    SortList(SortKey),
+    ListScrolled,
+    ToggleTextOutline,
+    ToggleMergeView,
+    ChooseSizeMetric(Option<SizeMetric>),
+    ToggleRelations,
+    ToggleGhosts,
+    ToggleAuthority,
+    SimulateOutage(String),
+    EndOutageSimulation,
+    ToggleDepthCompression,
+    ToggleTransitiveReduction,
+    ToggleColorByLatency,
+    ToggleColorByBatch,
+    ToggleReadMarkers,
+    ChooseTheme(Theme),
+    IdFirstChars(html::ChangeData),
+    IdLastChars(html::ChangeData),
+    ToggleHideDomain,
+    LabelMaxChars(html::ChangeData),
+    ToggleLabelLineBreaks,
+    ToggleMonospaceIds,
+    DedupWindow(html::ChangeData),
+
+    SetName(html::ChangeData),
+    SetLhs(html::ChangeData),
+    SetRhs(html::ChangeData),
+    AnnotationNote(html::ChangeData),
+    AnnotationTag(Tag),
+    QuerySender(html::ChangeData),
+    QueryType(html::ChangeData),
+    QueryOrigin(html::ChangeData),
+    QueryBody(html::ChangeData),
+    QueryExpression(html::ChangeData),
+    ColourRuleExpression(html::ChangeData),
+    ColourRuleColour(html::ChangeData),
+    SnapshotName(html::ChangeData),
+
+    PresetJson(html::ChangeData),
+    ExportJson(html::ChangeData),
+    RoomExportJson(html::ChangeData),
+    RecordingJson(html::ChangeData),
+    VaultPassphrase(html::ChangeData),
+    ServerFormName(html::ChangeData),
+    ServerFormUrl(html::ChangeData),
+    ServerFormToken(html::ChangeData),
+    ServerFormBackend(BackendChoice),
+    TimeRangeStart(html::ChangeData),
+    TimeRangeEnd(html::ChangeData),
+    ResetTimeRange,
+    ReplaySpeed(html::ChangeData),
+    ReplayPosition(html::ChangeData),
+    LayoutDirection(Direction),
+    LayoutAlgorithm(LayoutAlgorithm),
+    LevelSeparation(html::ChangeData),
+    NodeSpacing(html::ChangeData),
+    DepthJump(html::ChangeData),
 }
 
 pub enum UICommand {
     DisplayEventBody,
+    JumpToDepths(i64, i64),
+    JumpToEvent(String),
+    FocusRow(String),
+    ShareSelection,
+    SaveSelectionAsSet,
+    AnnotateSelection,
+    RemoveAnnotation(String),
+    AnnotationsLoaded(Option<String>),
+    ExtractSelection,
+    ExportSelection,
+    SelectByQuery,
+    SelectByExpression,
+    HideByExpression,
+    HighlightByExpression,
+    CollapseByExpression,
+    AddColourRule,
+    RemoveColourRule(usize),
+    HideSelection,
+    HideBranches,
+    RestoreHidden(String),
+    ShowHiddenEvents,
+    HighlightSelection,
+    FindPath,
+    PinEvent,
+    ExpandPin,
+    Unpin,
+    ClearHighlights,
+    ComputeMetrics,
+    CollapseSelection,
+    ExpandCollapsed,
+    CombineSets(SetOperation),
+    SelectSet(String),
+    RemoveSet(String),
+    TakeSnapshot,
+    ApplySnapshot(String),
+    RemoveSnapshot(String),
+    CancelBackfill(ViewIndex),
+    CancelEarlierFetch(ViewIndex),
+    ExportPreset,
+    ImportPreset,
+    ExportViews,
+    DownloadExport,
+    ImportViews,
+    DownloadJournal,
+    DownloadOutline,
+    DownloadStandalone,
+    LoadRoomExport,
+    ToggleRecording,
+    DownloadRecording,
+    PlayRecording,
+    StopPlayback,
+    SaveDag,
+    RestoreDag,
+    DagRestored(ViewIndex, Option<String>),
+    ConfirmRestore,
+    CancelRestore,
+    ViewportMoved,
+    UnlockVault,
+    VaultLoaded(Option<String>),
+    CreateVault,
+    CancelVault,
+    VaultCreated(bool),
+    ThemeLoaded(Option<String>),
+    LayoutOptionsLoaded(Option<String>),
+    LayoutCacheLoaded(Option<String>),
+    ClearLayoutCache,
+    ServersLoaded(Option<String>),
+    AddServer,
+    RemoveServer(String),
+    UseServer(String),
+    VaultUnlocked(Option<Credentials>),
+    SaveCredentials,
+    VaultSealed(Option<String>),
+    LockVault,
+    Undo,
+    Redo,
+    ObserveRoom(String),
+    PickRoom(ViewIndex, String),
+    MoveSelection(Step),
+    StartReplay,
+    ToggleReplay,
+    StopReplay,
+    ReplayTick,
+}
+
+/// A replay of the arrival of the events, which reveals them one at a time in the order in which
+/// the server received them, see `RoomEvents::arrival_order`.
+struct Replay {
+    step: usize,               // The number of events revealed in each DAG
+    length: usize,             // The number of events of the largest DAG
+    task: Option<TimeoutTask>, // Reveals the next event, unless the replay is paused
+}
+
+/// The moves of the selection through the DAG with the arrow keys.
+#[derive(Clone, Copy)]
+pub enum Step {
+    Parent,          // To the earliest event in the `prev_events` of the selected event
+    Child,           // To the earliest event with the selected event in its `prev_events`
+    PreviousSibling, // To the event created just before the selected one at the same depth
+    NextSibling,     // To the event created just after the selected one at the same depth
 }
 
 /// These messages are used by the frontend to send commands to the backend.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum BkCommand {
     Connect(ViewIndex),
     ListRooms(ViewIndex),
+    ResolveRoomNames(ViewIndex),
+    SearchPublicRooms(ViewIndex),
     JoinRoom(ViewIndex),
+    CreateFilter(ViewIndex),
     Sync(ViewIndex),
     MoreMsg,
     FetchState,
+    FillGaps(ViewIndex),
+    RevealRegion,
+    EventReports(ViewIndex),
     LeaveRoom(ViewIndex),
     Disconnect(ViewIndex),
+    ProbeServers,
+    CheckServers,
+    SsoLogin(ViewIndex),
+    GuestLogin(ViewIndex),
+    AppserviceLogin(ViewIndex),
+    RefreshToken(ViewIndex),
+    StitchPredecessor(ViewIndex),
+    FetchRoomVersion(ViewIndex),
+}
+
+impl BkCommand {
+    /// Returns the view whose access token `self` uses, if it makes authenticated requests to
+    /// the homeserver of a view. The logins and the refreshes of the token don't.
+    fn authenticated_view(self) -> Option<ViewIndex> {
+        match self {
+            BkCommand::ListRooms(view_id)
+            | BkCommand::ResolveRoomNames(view_id)
+            | BkCommand::SearchPublicRooms(view_id)
+            | BkCommand::JoinRoom(view_id)
+            | BkCommand::CreateFilter(view_id)
+            | BkCommand::Sync(view_id)
+            | BkCommand::FillGaps(view_id)
+            | BkCommand::EventReports(view_id)
+            | BkCommand::LeaveRoom(view_id)
+            | BkCommand::Disconnect(view_id)
+            | BkCommand::StitchPredecessor(view_id)
+            | BkCommand::FetchRoomVersion(view_id) => Some(view_id),
+            BkCommand::Connect(_)
+            | BkCommand::SsoLogin(_)
+            | BkCommand::GuestLogin(_)
+            | BkCommand::AppserviceLogin(_)
+            | BkCommand::RefreshToken(_)
+            | BkCommand::MoreMsg
+            | BkCommand::FetchState
+            | BkCommand::RevealRegion
+            | BkCommand::ProbeServers
+            | BkCommand::CheckServers => None,
+        }
+    }
 }
 
 /// These messages are responses from the backend to the frontend.
 pub enum BkResponse {
     Connected(ViewIndex, ConnectionResponse),
     RoomsList(ViewIndex, JoinedRooms),
+    RoomNamesResolved(ViewIndex, RoomNames),
+    PublicRoomsFound(ViewIndex, PublicRoomsResponse),
     RoomJoined(ViewIndex),
+    FilterCreated(ViewIndex, FilterResponse),
     Synced(ViewIndex, SyncResponse),
-    MsgGot(ViewIndex, MessagesResponse),
     StateFetched(ViewIndex, ContextResponse),
+    GapFetched(ViewIndex, ContextResponse),
     RoomLeft(ViewIndex),
     Disconnected(ViewIndex),
-
-    ConnectionFailed(ViewIndex),
-    ListingRoomsFailed(ViewIndex),
-    JoiningRoomFailed(ViewIndex),
-    SyncFailed(ViewIndex),
-    MoreMsgFailed(ViewIndex),
-    FetchStateFailed(ViewIndex),
-    LeavingRoomFailed(ViewIndex),
-    DisconnectionFailed(ViewIndex),
-
-    DeepestEvents(ViewIndex, EventsResponse),
-    Ancestors(ViewIndex, EventsResponse),
-    Descendants(ViewIndex, EventsResponse),
+    TokenRefreshed(ViewIndex, RefreshResponse),
+    PredecessorEvents(ViewIndex, SourceEvents),
+    RoomVersionFetched(ViewIndex, String),
+
+    ConnectionFailed(ViewIndex, BackendError),
+    ListingRoomsFailed(ViewIndex, BackendError),
+    ResolvingRoomNamesFailed(ViewIndex, BackendError),
+    SearchingPublicRoomsFailed(ViewIndex, BackendError),
+    JoiningRoomFailed(ViewIndex, BackendError),
+    CreatingFilterFailed(ViewIndex, BackendError),
+    SyncFailed(ViewIndex, BackendError),
+    MoreMsgFailed(ViewIndex, BackendError),
+    FetchStateFailed(ViewIndex, BackendError),
+    FillGapsFailed(ViewIndex, BackendError),
+    LeavingRoomFailed(ViewIndex, BackendError),
+    DisconnectionFailed(ViewIndex, BackendError),
+    RefreshFailed(ViewIndex, BackendError),
+    PredecessorFailed(ViewIndex, BackendError),
+    FetchingRoomVersionFailed(ViewIndex, BackendError),
+
+    DeepestEvents(ViewIndex, SourceEvents),
+    EventsBefore(ViewIndex, SourceEvents),
+    SingleEvent(ViewIndex, String, SourceEvents),
+    Descendants(ViewIndex, SourceEvents),
     State(ViewIndex, EventsResponse),
-
-    DeepestRqFailed(ViewIndex),
-    AncestorsRqFailed(ViewIndex),
-    DescendantsRqFailed(ViewIndex),
-    StateRqFailed(ViewIndex),
+    LiveEvents(ViewIndex, SourceEvents),
+    LiveOpened(ViewIndex),
+    LiveClosed(ViewIndex),
+
+    DeepestRqFailed(ViewIndex, BackendError),
+    AncestorsRqFailed(ViewIndex, BackendError),
+    SingleEventFailed(ViewIndex, BackendError),
+    DescendantsRqFailed(ViewIndex, BackendError),
+    LiveEventsFailed(ViewIndex, BackendError),
+    StateRqFailed(ViewIndex, BackendError),
+
+    AdminMessages(ViewIndex, SourceEvents),
+    AdminNewMessages(ViewIndex, SourceEvents),
+    AdminState(ViewIndex, RoomStateResponse),
+    EventReports(ViewIndex, EventReportsResponse),
+
+    AdminMessagesFailed(ViewIndex, BackendError),
+    AdminNewMessagesFailed(ViewIndex, BackendError),
+    EventReportsFailed(ViewIndex, BackendError),
+
+    ServerDelegation(String, Option<WellKnownServer>),
+    HomeserverDiscovered(ViewIndex, BkCommand, Option<WellKnownClient>), // The command waiting for the discovery
+    ServerVersion(String, VersionResponse),
+    ServerUnreachable(String),
+    ServerChecked(String, Result<String, BackendError>),
 }
 
 impl Component for Model {
@@ -373,26 +625,114 @@ impl Component for Model {
 
     fn create(_: Self::Properties, mut link: ComponentLink<Self>) -> Self {
         let bk_type = Arc::new(RwLock::new(BackendChoice::CS));
-        let default_view = vec![View::CS(CSView::new(0, &mut link))];
+        let mut default_view = vec![View::CS(CSView::new(0, &mut link))];
 
-        let default_fields_choice = FieldsChoice {
-            sender: false,
-            origin: false,
-            origin_server_ts: false,
-            etype: false,
-            state_key: false,
-            prev_events: false,
-            depth: false,
-            redacts: false,
-            event_id: true,
+        // Complete the login with the SSO of a homeserver if it has just redirected the user back
+        if let Some((pending, login_token)) = sso::take_pending() {
+            while default_view.len() <= pending.view_id {
+                let view = View::CS(CSView::new(default_view.len(), &mut link));
 
-            fields: [Field::EventID].iter().cloned().collect(),
-        };
+                default_view.push(view);
+            }
+
+            let view = &mut default_view[pending.view_id];
+            view.set_server_name(pending.server_name);
+            view.set_room_id(pending.room_id);
+
+            if let View::CS(view) = view {
+                view.session.write().unwrap().base_url = pending.base_url;
+                view.connection_task = Some(
+                    view.backend
+                        .connect_with_token(view.connection_callback.clone(), &login_token),
+                );
+            }
+        }
+
+        // Bring back the theme chosen the last time the application was used
+        let storage = StorageService::new();
+        storage.load(
+            THEME_KEY,
+            link.send_back(|name: Option<String>| Msg::UICmd(UICommand::ThemeLoaded(name))),
+        );
+        storage.load(
+            ANNOTATIONS_KEY,
+            link.send_back(|json: Option<String>| Msg::UICmd(UICommand::AnnotationsLoaded(json))),
+        );
+        storage.load(
+            SERVERS_KEY,
+            link.send_back(|json: Option<String>| Msg::UICmd(UICommand::ServersLoaded(json))),
+        );
+        storage.load(
+            LAYOUT_OPTIONS_KEY,
+            link.send_back(|json: Option<String>| Msg::UICmd(UICommand::LayoutOptionsLoaded(json))),
+        );
+        storage.load(
+            LAYOUT_CACHE_KEY,
+            link.send_back(|json: Option<String>| Msg::UICmd(UICommand::LayoutCacheLoaded(json))),
+        );
+
+        // Ctrl+Z undoes the last operation while Ctrl+Shift+Z and Ctrl+Y redo it, and the arrow
+        // keys move the selection through the DAG, unless a text is being entered
+        let history_callback = link.send_back(Msg::UICmd);
+        let selection_moved = Rc::new(Cell::new(false));
+        let moved = selection_moved.clone();
+        web::document().add_event_listener(move |e: KeyDownEvent| {
+            let editing: bool = js! {
+                var element = document.activeElement;
+
+                return element != null && (element.tagName == "TEXTAREA" || (
+                    element.tagName == "INPUT" && element.type != "checkbox" && element.type != "radio"
+                ));
+            }
+            .try_into()
+            .unwrap_or(false);
+
+            if editing {
+                return;
+            }
+
+            if e.ctrl_key() || e.meta_key() {
+                let cmd = match (e.key().to_lowercase().as_str(), e.shift_key()) {
+                    ("z", false) => UICommand::Undo,
+                    ("z", true) | ("y", false) => UICommand::Redo,
+                    _ => return,
+                };
+
+                e.prevent_default();
+                history_callback.emit(cmd);
+            } else {
+                // The earliest events are at the top of the networks
+                let step = match e.key().as_str() {
+                    "ArrowUp" => Step::Parent,
+                    "ArrowDown" => Step::Child,
+                    "ArrowLeft" => Step::PreviousSibling,
+                    "ArrowRight" => Step::NextSibling,
+                    _ => return,
+                };
+
+                // The message is processed before `emit` returns, since no other is being
+                // processed while the listener runs. The page only stops scrolling if the
+                // selection moved.
+                moved.set(false);
+                history_callback.emit(UICommand::MoveSelection(step));
+
+                if moved.get() {
+                    e.prevent_default();
+                }
+            }
+        });
+
+        let default_fields_choice = FieldsChoice::from_fields(views_manager::default_fields());
 
         Model {
             console: ConsoleService::new(),
             timeout: TimeoutService::new(),
-            vis: VisJsService::new(bk_type.clone()),
+            vis: ViewsManager::new(bk_type.clone()),
+            storage,
+            vault: VaultPanel::default(),
+            probe: FederationProbe::new(),
+            server_status: BTreeMap::new(),
+            server_panel: ServerPanel::default(),
 
             link,
 
@@ -400,8 +740,59 @@ impl Component for Model {
             view_idx: 0,
             views: default_view,
             event_body: None,
+            event_latency: None,
+            event_batch: None,
+            content_view: None,
+            dag_metrics: None,
             room_state: None,
             fields_choice: default_fields_choice,
+            workspace_panel: WorkspacePanel::default(),
+            colour_rules: Vec::new(),
+            colour_rule_form: ColourRule {
+                expression: String::new(),
+                colour: DEFAULT_RULE_COLOUR.to_string(),
+            },
+            list_view: ListView::default(),
+            text_outline: false,
+            shared_selection: None,
+            selection_moved,
+            preset_json: String::new(),
+            export_json: String::new(),
+            room_export_json: String::new(),
+            recorder: Recorder::default(),
+            sampling: false,
+            lazy_rendering: false,
+            fog: false,
+            size_metric: None,
+            show_relations: false,
+            show_ghosts: false,
+            show_authority: false,
+            outage: None,
+            compress_depths: false,
+            transitive_reduction: false,
+            color_by_latency: false,
+            color_by_batch: false,
+            show_read_markers: false,
+            theme: Theme::default(),
+            id_shortener: IdShortener::default(),
+            label_format: LabelFormat::default(),
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            depth_jump: DEFAULT_DEPTH_JUMP_THRESHOLD,
+            time_range: None,
+            replay: None,
+            replay_speed: DEFAULT_REPLAY_SPEED,
+            room_search: String::new(),
+            timeline_limit: DEFAULT_TIMELINE_LIMIT,
+            timeline_state: ("m.room.power_levels".to_string(), String::new()),
+            retries: HashMap::new(),
+            pending_fetches: HashMap::new(),
+            scheduler: RequestScheduler::new(),
+            toasts: Vec::new(),
+            toast_tasks: HashMap::new(),
+            next_toast: 0,
+            pending_restore: None,
+            earlier_fetches: HashMap::new(),
+            history: History::new(HISTORY_CAPACITY),
         }
     }
 
@@ -409,8 +800,31 @@ impl Component for Model {
         match msg {
             Msg::UI(ui) => self.process_ui_event(ui),
             Msg::UICmd(cmd) => self.process_ui_command(cmd),
-            Msg::BkCmd(cmd) => self.process_bk_command(cmd),
-            Msg::BkRes(res) => self.process_bk_response(res),
+            // Nothing is requested from the backends while a recording is played back
+            Msg::BkCmd(_) | Msg::Retry(_) if self.recorder.playing_back => {}
+            Msg::BkCmd(cmd) => {
+                // The command succeeded if it is issued again, e.g. for the next sync
+                self.retries.remove(&cmd);
+                self.process_bk_command(cmd)
+            }
+            Msg::BkRes(res) => {
+                if let Some(recording) = &mut self.recorder.log {
+                    recording.record(&res);
+                }
+
+                self.process_bk_response(res)
+            }
+            Msg::Retry(cmd) => self.process_bk_command(cmd),
+        }
+
+        // A request may have completed, or the delay asked by a homeserver may be over
+        if !self.recorder.playing_back {
+            self.drain_request_queues();
+        }
+
+        // A network may have been laid out
+        if let Some(json) = self.vis.unsaved_layout_cache() {
+            self.storage.store(LAYOUT_CACHE_KEY, json);
         }
 
         true
@@ -419,54 +833,14 @@ impl Component for Model {
 
 impl Model {
     fn process_ui_event(&mut self, event: UIEvent) {
+        let (view_idx, fields) = (self.view_idx, self.fields_choice.fields.clone());
+
         // Change the informations of the session whenever their corresponding entries in the UI
         // are changed
         match event {
-            UIEvent::ChooseCSBackend => {
-                *self.bk_type.write().unwrap() = BackendChoice::CS;
-
-                let mut new_views: Vec<CSView> = (0..self.views.len())
-                    .map(|id| CSView::new(id, &mut self.link))
-                    .collect();
-
-                for (old_view, new_view) in self.views.iter().zip(new_views.iter_mut()) {
-                    if let View::Postgres(old_view) = old_view {
-                        let pg_session = old_view.session.read().unwrap();
-                        let mut new_session = new_view.session.write().unwrap();
-
-                        new_session.server_name = pg_session.server_name.clone();
-                        new_session.room_id = pg_session.room_id.clone();
-                    }
-                }
-
-                let new_views = new_views.into_iter().map(|view| View::CS(view)).collect();
-
-                self.views = new_views;
-            }
-            UIEvent::ChoosePostgresBackend => {
-                *self.bk_type.write().unwrap() = BackendChoice::Postgres;
-
-                let mut new_views: Vec<PgView> = (0..self.views.len())
-                    .map(|id| PgView::new(id, &mut self.link))
-                    .collect();
-
-                for (old_view, new_view) in self.views.iter().zip(new_views.iter_mut()) {
-                    if let View::CS(old_view) = old_view {
-                        let cs_session = old_view.session.read().unwrap();
-                        let mut new_session = new_view.session.write().unwrap();
-
-                        new_session.server_name = cs_session.server_name.clone();
-                        new_session.room_id = cs_session.room_id.clone();
-                    }
-                }
-
-                let new_views = new_views
-                    .into_iter()
-                    .map(|view| View::Postgres(view))
-                    .collect();
-
-                self.views = new_views;
-            }
+            UIEvent::ChooseCSBackend => self.switch_backend(BackendChoice::CS),
+            UIEvent::ChoosePostgresBackend => self.switch_backend(BackendChoice::Postgres),
+            UIEvent::ChooseAdminBackend => self.switch_backend(BackendChoice::Admin),
             UIEvent::ViewChoice(vc) => {
                 let input: web::html_element::InputElement = web::document()
                     .query_selector("#server-name-input")
@@ -500,17 +874,40 @@ impl Model {
                         .try_into()
                         .unwrap();
                     input.set_raw_value("");
+
+                    let input: web::html_element::InputElement = web::document()
+                        .query_selector("#as-token-input")
+                        .expect("Couldn't get document element")
+                        .expect("Couldn't get document element")
+                        .try_into()
+                        .unwrap();
+                    input.set_raw_value("");
+
+                    let input: web::html_element::InputElement = web::document()
+                        .query_selector("#appservice-user-input")
+                        .expect("Couldn't get document element")
+                        .expect("Couldn't get document element")
+                        .try_into()
+                        .unwrap();
+                    input.set_raw_value("");
+                }
+
+                if *self.bk_type.read().unwrap() == BackendChoice::Admin {
+                    let input: web::html_element::InputElement = web::document()
+                        .query_selector("#access-token-input")
+                        .expect("Couldn't get document element")
+                        .expect("Couldn't get document element")
+                        .try_into()
+                        .unwrap();
+                    input.set_raw_value("");
                 }
 
                 self.view_idx = vc;
+                self.fields_choice = FieldsChoice::from_fields(self.vis.fields(vc));
             }
             UIEvent::AddView => {
-                let view = match *self.bk_type.read().unwrap() {
-                    BackendChoice::CS => View::CS(CSView::new(self.views.len(), &mut self.link)),
-                    BackendChoice::Postgres => {
-                        View::Postgres(PgView::new(self.views.len(), &mut self.link))
-                    }
-                };
+                let bk_type = *self.bk_type.read().unwrap();
+                let view = View::new(bk_type, self.views.len(), &mut self.link);
 
                 self.views.push(view);
 
@@ -518,26 +915,49 @@ impl Model {
             }
             UIEvent::ServerName(sn) => {
                 if let html::ChangeData::Value(sn) = sn {
-                    match &self.views[self.view_idx] {
-                        View::CS(view) => view.session.write().unwrap().server_name = sn,
-                        View::Postgres(view) => view.session.write().unwrap().server_name = sn,
+                    self.views[self.view_idx].set_server_name(sn);
+
+                    if let View::CS(view) = &mut self.views[self.view_idx] {
+                        view.discovery_task = None;
                     }
                 }
             }
             UIEvent::RoomId(ri) => {
                 if let html::ChangeData::Value(ri) = ri {
                     for view in &self.views {
-                        match view {
-                            View::CS(view) => {
-                                view.session.write().unwrap().room_id = ri.clone();
-                            }
-                            View::Postgres(view) => {
-                                view.session.write().unwrap().room_id = ri.clone();
-                            }
-                        }
+                        view.set_room_id(ri.clone());
+                    }
+                }
+            }
+            UIEvent::RoomSearch(search) => {
+                if let html::ChangeData::Value(search) = search {
+                    self.room_search = search;
+                }
+            }
+            UIEvent::TimelineLimit(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.trim().parse() {
+                        Ok(limit) if limit > 0 => self.timeline_limit = limit,
+                        _ => self
+                            .console
+                            .log("The timeline limit must be a positive number"),
                     }
                 }
             }
+            UIEvent::TimelineType(etype) => {
+                if let html::ChangeData::Value(etype) = etype {
+                    self.timeline_state.0 = etype;
+                }
+            }
+            UIEvent::TimelineStateKey(state_key) => {
+                if let html::ChangeData::Value(state_key) = state_key {
+                    self.timeline_state.1 = state_key;
+                }
+            }
+            UIEvent::DismissToast(id) => {
+                self.toasts.retain(|(toast, _)| *toast != id);
+                self.toast_tasks.remove(&id);
+            }
             UIEvent::Username(u) => {
                 if let html::ChangeData::Value(u) = u {
                     if let View::CS(view) = &mut self.views[self.view_idx] {
@@ -552,6 +972,27 @@ impl Model {
                     }
                 }
             }
+            UIEvent::AsToken(t) => {
+                if let html::ChangeData::Value(t) = t {
+                    if let View::CS(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().as_token = t;
+                    }
+                }
+            }
+            UIEvent::AppserviceUserId(u) => {
+                if let html::ChangeData::Value(u) = u {
+                    if let View::CS(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().appservice_user_id = u;
+                    }
+                }
+            }
+            UIEvent::AccessToken(t) => {
+                if let html::ChangeData::Value(t) = t {
+                    if let View::Admin(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().access_token = t;
+                    }
+                }
+            }
             UIEvent::ToggleSender => {
                 let fc = &mut self.fields_choice;
 
@@ -563,19 +1004,7 @@ impl Model {
                     fc.fields.remove(&Field::Sender);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::ToggleOrigin => {
                 let fc = &mut self.fields_choice;
@@ -588,19 +1017,7 @@ impl Model {
                     fc.fields.remove(&Field::Origin);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::ToggleOriginServerTS => {
                 let fc = &mut self.fields_choice;
@@ -613,19 +1030,7 @@ impl Model {
                     fc.fields.remove(&Field::OriginServerTS);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::ToggleType => {
                 let fc = &mut self.fields_choice;
@@ -638,19 +1043,7 @@ impl Model {
                     fc.fields.remove(&Field::Type);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::ToggleStateKey => {
                 let fc = &mut self.fields_choice;
@@ -663,19 +1056,7 @@ impl Model {
                     fc.fields.remove(&Field::StateKey);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::TogglePrevEvents => {
                 let fc = &mut self.fields_choice;
@@ -688,19 +1069,7 @@ impl Model {
                     fc.fields.remove(&Field::PrevEvents);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::ToggleDepth => {
                 let fc = &mut self.fields_choice;
@@ -713,19 +1082,7 @@ impl Model {
                     fc.fields.remove(&Field::Depth);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::ToggleRedacts => {
                 let fc = &mut self.fields_choice;
@@ -738,19 +1095,7 @@ impl Model {
                     fc.fields.remove(&Field::Redacts);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
             UIEvent::ToggleEventID => {
                 let fc = &mut self.fields_choice;
@@ -763,673 +1108,5889 @@ impl Model {
                     fc.fields.remove(&Field::EventID);
                 }
 
-                for view in &self.views {
-                    if let Some(events_dag) = view.get_events_dag() {
-                        let mut events_dag = events_dag.write().unwrap();
-
-                        events_dag.change_fields(&fc.fields);
-                    }
-
-                    if self.vis.is_active() {
-                        if let Some(events_dag) = view.get_events_dag() {
-                            self.vis.update_labels(events_dag.clone(), view.get_id());
-                        }
-                    }
-                }
+                self.apply_fields();
             }
-        }
-    }
+            UIEvent::ToggleUnknownFields => {
+                let fc = &mut self.fields_choice;
 
-    fn process_ui_command(&mut self, cmd: UICommand) {
-        match cmd {
-            UICommand::DisplayEventBody => {
-                let view_selection_input: web::html_element::InputElement = web::document()
-                    .query_selector("#targeted-view")
-                    .expect("Couldn't get document element")
-                    .expect("Couldn't get document element")
-                    .try_into()
-                    .unwrap();
-                let view_id: ViewIndex = view_selection_input
-                    .raw_value()
-                    .parse()
-                    .expect("Failed to parse view_id");
+                fc.unknown_fields = !fc.unknown_fields;
 
-                let event_id_input: web::html_element::InputElement = web::document()
-                    .query_selector("#selected-event")
-                    .expect("Couldn't get document element")
-                    .expect("Couldn't get document element")
-                    .try_into()
-                    .unwrap();
-                let event_id = event_id_input.raw_value();
+                if fc.unknown_fields {
+                    fc.fields.insert(Field::UnknownFields);
+                } else {
+                    fc.fields.remove(&Field::UnknownFields);
+                }
 
-                if let Some(dag) = self.views[view_id].get_events_dag() {
-                    self.event_body = dag
-                        .read()
-                        .unwrap()
-                        .get_event(&event_id)
-                        .map(|ev| serde_json::to_string_pretty(ev).unwrap());
+                self.apply_fields();
+            }
+            UIEvent::Pointer(p) => {
+                if let html::ChangeData::Value(p) = p {
+                    self.fields_choice.pointer = p;
                 }
             }
-        }
-    }
-
-    fn process_bk_command(&mut self, cmd: BkCommand) {
-        let console_msg = match cmd {
-            BkCommand::Connect(_) => "Connecting...",
-            BkCommand::ListRooms(_) => "Listing joined rooms...",
-            BkCommand::JoinRoom(_) => "Joining the room...",
-            BkCommand::Sync(_) => "Syncing...",
-            BkCommand::MoreMsg => "Retrieving previous messages...",
-            BkCommand::FetchState => "Fetching the state of the room...",
-            BkCommand::LeaveRoom(_) => "Leaving the room...",
-            BkCommand::Disconnect(_) => "Disconnecting...",
-        };
+            UIEvent::AddPointer => {
+                let pointer = self.fields_choice.pointer.trim().to_string();
+
+                // The empty pointer, designating the whole event, isn't useful in a label
+                if pointer.starts_with('/') {
+                    self.fields_choice
+                        .fields
+                        .insert(Field::Pointer(pointer.clone()));
+                    self.fields_choice.pointer = String::new();
+                    self.apply_fields();
+                    self.log_activity(format!("Added the field {} to the labels", pointer));
+                } else {
+                    self.console
+                        .log("A JSON pointer must start with / (e.g. /content/body)");
+                }
+            }
+            UIEvent::RemovePointer(pointer) => {
+                self.fields_choice
+                    .fields
+                    .remove(&Field::Pointer(pointer.clone()));
+                self.apply_fields();
+                self.log_activity(format!("Removed the field {} from the labels", pointer));
+            }
+            UIEvent::ToggleSampling => {
+                self.sampling = !self.sampling;
 
-        self.console.log(console_msg);
+                self.vis.set_sampling(if self.sampling {
+                    Some(SAMPLING_RATE)
+                } else {
+                    None
+                });
+                self.log_activity(format!(
+                    "Turned the sampling mode {}",
+                    if self.sampling { "on" } else { "off" }
+                ));
+            }
+            UIEvent::ToggleLazyRendering => {
+                self.lazy_rendering = !self.lazy_rendering;
 
-        // Order the backend to make requests to the homeserver according to the command received
-        match cmd {
-            BkCommand::Connect(view_id) => match &mut self.views[view_id] {
-                View::CS(view) => match view.session.read().unwrap().access_token {
-                    None => match view.connection_task {
-                        None => {
-                            view.connection_task =
-                                Some(view.backend.connect(view.connection_callback.clone()))
-                        }
-                        Some(_) => self.console.log("Already connecting"),
-                    },
-                    Some(_) => self.console.log("You are already connected"),
-                },
-                View::Postgres(view) => match view.events_dag {
-                    None => match view.deepest_task {
-                        None => {
-                            view.deepest_task =
-                                Some(view.backend.deepest(view.deepest_callback.clone()))
-                        }
-                        Some(_) => self.console.log("Already fetching deepest events"),
-                    },
-                    Some(_) => self.console.log("Deepest events already fetched"),
-                },
-            },
-            BkCommand::ListRooms(view_id) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.listing_rooms_task =
-                        Some(view.backend.list_rooms(view.listing_rooms_callback.clone()))
+                self.vis.set_lazy_rendering(if self.lazy_rendering {
+                    Some(LAZY_RENDERING_MARGIN)
+                } else {
+                    None
+                });
+                self.log_activity(format!(
+                    "Turned the lazy rendering {}",
+                    if self.lazy_rendering { "on" } else { "off" }
+                ));
+            }
+            UIEvent::ToggleFog => {
+                self.fog = !self.fog;
+
+                self.vis.set_fog(self.fog);
+                self.log_activity(format!(
+                    "Turned the frontier fog {}",
+                    if self.fog { "on" } else { "off" }
+                ));
+            }
+            UIEvent::ToggleListView => {
+                self.list_view.shown = !self.list_view.shown;
+                self.list_view.scroll = 0;
+            }
+            UIEvent::ToggleTextOutline => {
+                self.text_outline = !self.text_outline;
+            }
+            UIEvent::SortList(key) => {
+                // Sorting again by the same column reverses the order
+                if self.list_view.sort == key {
+                    self.list_view.descending = !self.list_view.descending;
+                } else {
+                    self.list_view.sort = key;
+                    self.list_view.descending = false;
                 }
+
+                self.scroll_list_to_focus();
             }
-            BkCommand::JoinRoom(view_id) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.joining_room_task =
-                        Some(view.backend.join_room(view.joining_room_callback.clone()))
+            UIEvent::ListScrolled => {
+                self.list_view.scroll = js! {
+                    var list = document.getElementById("event-list");
+
+                    return list != null ? Math.round(list.scrollTop) : 0;
                 }
+                .try_into()
+                .unwrap_or(0);
             }
-            BkCommand::Sync(view_id) => match &mut self.views[view_id] {
-                View::CS(view) => {
-                    let next_batch_token = view.session.read().unwrap().next_batch_token.clone();
+            UIEvent::ToggleSplitLayout => {
+                let layout = match self.vis.layout() {
+                    Layout::Single => Layout::Split,
+                    Layout::Split => Layout::Single,
+                };
 
-                    view.sync_task = Some(
-                        view.backend
-                            .sync(view.sync_callback.clone(), next_batch_token),
-                    )
+                self.vis.set_layout(layout);
+                self.log_activity(format!("Switched to the {:?} layout", layout));
+            }
+            UIEvent::ChooseSizeMetric(size_metric) => {
+                self.size_metric = size_metric;
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag.write().unwrap().change_size_metric(size_metric);
+                    }
                 }
-                View::Postgres(view) => {
-                    if let Some(dag) = &view.events_dag {
-                        let from = &dag.read().unwrap().latest_events;
 
-                        view.descendants_task = Some(
-                            view.backend
-                                .descendants(view.descendants_callback.clone(), from),
-                        );
+                // The nodes which already have a size can't be reset, so everything is redrawn
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                self.log_activity(format!("Scaled the nodes by {:?}", size_metric));
+            }
+            UIEvent::ToggleRelations => {
+                self.show_relations = !self.show_relations;
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .set_show_relations(self.show_relations);
                     }
                 }
-            },
-            BkCommand::MoreMsg => {
-                let view_selection_input: web::html_element::InputElement = web::document()
-                    .query_selector("#targeted-view")
-                    .expect("Couldn't get document element")
-                    .expect("Couldn't get document element")
-                    .try_into()
-                    .unwrap();
-                let view_id: ViewIndex = view_selection_input
-                    .raw_value()
-                    .parse()
-                    .expect("Failed to parse view_id");
 
-                match &mut self.views[view_id] {
-                    View::CS(view) => match view.more_msg_task {
-                        None => {
-                            view.more_msg_task = Some(
-                                view.backend
-                                    .get_prev_messages(view.more_msg_callback.clone()),
-                            );
-                        }
-                        Some(_) => self.console.log("Already fetching previous messages"),
-                    },
-                    View::Postgres(view) => match view.ancestors_task {
-                        None => match &view.events_dag {
-                            Some(_) => {
-                                let input: web::html_element::InputElement = web::document()
-                                    .query_selector("#ancestors-id")
-                                    .expect("Couldn't get document element")
-                                    .expect("Couldn't get document element")
-                                    .try_into()
-                                    .unwrap();
-                                let from = vec![input.raw_value()];
-
-                                view.ancestors_task = Some(
-                                    view.backend
-                                        .ancestors(view.ancestors_callback.clone(), &from),
-                                );
-                            }
-                            None => self.console.log("There was no DAG"),
-                        },
-                        Some(_) => self.console.log("Already fetching ancestors"),
-                    },
+                if self.vis.is_active() {
+                    self.vis.redraw();
                 }
+
+                self.log_activity(format!(
+                    "Turned the relation edges {}",
+                    if self.show_relations { "on" } else { "off" }
+                ));
             }
-            BkCommand::FetchState => {
-                let view_selection_input: web::html_element::InputElement = web::document()
-                    .query_selector("#targeted-view")
-                    .expect("Couldn't get document element")
-                    .expect("Couldn't get document element")
-                    .try_into()
-                    .unwrap();
-                let view_id: ViewIndex = view_selection_input
-                    .raw_value()
-                    .parse()
-                    .expect("Failed to parse view_id");
+            UIEvent::ToggleGhosts => {
+                self.show_ghosts = !self.show_ghosts;
 
-                let event_id_input: web::html_element::InputElement = web::document()
-                    .query_selector("#selected-event")
-                    .expect("Couldn't get document element")
-                    .expect("Couldn't get document element")
-                    .try_into()
-                    .unwrap();
-                let event_id = event_id_input.raw_value();
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .set_show_ghosts(self.show_ghosts);
+                    }
+                }
 
-                match &mut self.views[view_id] {
-                    View::CS(view) => match view.state_task {
-                        None => {
-                            view.state_task = Some(
-                                view.backend
-                                    .room_state(view.state_callback.clone(), &event_id),
-                            )
-                        }
-                        Some(_) => self.console.log("Already fetching the state of the room"),
-                    },
-                    View::Postgres(view) => match view.state_task {
-                        None => {
-                            view.state_task =
-                                Some(view.backend.state(view.state_callback.clone(), &event_id))
-                        }
-                        Some(_) => self.console.log("Already fetching the state of the room"),
-                    },
+                if self.vis.is_active() {
+                    self.vis.redraw();
                 }
+
+                self.log_activity(format!(
+                    "Turned the ghost nodes {}",
+                    if self.show_ghosts { "on" } else { "off" }
+                ));
             }
-            BkCommand::LeaveRoom(view_id) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    match view.leaving_room_task {
-                        None => {
-                            view.leaving_room_task =
-                                Some(view.backend.leave_room(view.leaving_room_callback.clone()))
-                        }
-                        Some(_) => self.console.log("Already leaving the room"),
+            UIEvent::ToggleAuthority => {
+                self.show_authority = !self.show_authority;
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .set_show_authority(self.show_authority);
                     }
                 }
+
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                self.log_activity(format!(
+                    "Turned the authority overlay {}",
+                    if self.show_authority { "on" } else { "off" }
+                ));
             }
-            BkCommand::Disconnect(view_id) => match &mut self.views[view_id] {
-                View::CS(view) => match view.session.read().unwrap().access_token {
+            UIEvent::SimulateOutage(server) => {
+                let last_event = match self.vis.selected_events().into_iter().next() {
+                    Some(last_event) => last_event,
                     None => {
-                        self.console.log("You were not connected");
+                        self.console
+                            .log("Select the last event the server received before going offline");
+                        return;
                     }
-                    Some(_) => match view.disconnection_task {
-                        None => {
-                            view.disconnection_task =
-                                Some(view.backend.disconnect(view.disconnection_callback.clone()))
-                        }
-                        Some(_) => self.console.log("Already disconnecting"),
-                    },
-                },
-                View::Postgres(view) => {
-                    if view.session.read().unwrap().connected {
-                        self.console.log("Stopping the backend");
+                };
 
-                        match view.stop_task {
-                            None => {
-                                view.stop_task = Some(view.backend.stop(view.stop_callback.clone()))
-                            }
-                            Some(_) => self.console.log("Already stopping the backend"),
-                        }
-                    } else {
-                        self.console.log("You were not connected");
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .simulate_outage(&server, &last_event);
                     }
                 }
-            },
-        }
-    }
 
-    fn process_bk_response(&mut self, res: BkResponse) {
-        match res {
-            BkResponse::Connected(view_id, res) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.connection_task = None;
-
-                    let mut session = view.session.write().unwrap();
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
 
-                    // Save the informations given by the homeserver when connecting to it. The access
-                    // token will be used for authenticating subsequent requests.
-                    session.user_id = res.user_id;
-                    session.access_token = Some(res.access_token);
-                    session.device_id = Some(res.device_id);
+                self.log_activity(format!(
+                    "Simulated an outage of {} after the event {}",
+                    server, last_event
+                ));
+                self.outage = Some((server, last_event));
+            }
+            UIEvent::EndOutageSimulation => {
+                self.outage = None;
 
-                    self.console.log(&format!(
-                        "Connected with token: {} and as {}",
-                        session.access_token.as_ref().unwrap(),
-                        session.device_id.as_ref().unwrap()
-                    ));
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag.write().unwrap().end_outage_simulation();
+                    }
+                }
 
-                    // Request the list of the rooms joined by the user as soon as we are connected
-                    self.link
-                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
-                        .emit(());
+                if self.vis.is_active() {
+                    self.vis.redraw();
                 }
+
+                self.log_activity("Ended the simulation of an outage".to_string());
             }
-            BkResponse::RoomsList(view_id, res) => {
-                self.console.log("Looking up in joined rooms");
+            UIEvent::ToggleDepthCompression => {
+                self.compress_depths = !self.compress_depths;
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.listing_rooms_task = None;
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .set_compress_depths(self.compress_depths);
+                    }
+                }
 
-                    if res
-                        .joined_rooms
-                        .contains(&view.session.read().unwrap().room_id)
-                    {
-                        // If the user is already in the room to observe, make the initial sync
-                        self.link
-                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
-                            .emit(());
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                self.log_activity(format!(
+                    "Turned the depth compression {}",
+                    if self.compress_depths { "on" } else { "off" }
+                ));
+            }
+            UIEvent::ToggleColorByLatency => {
+                self.color_by_latency = !self.color_by_latency;
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .set_color_by_latency(self.color_by_latency);
+                    }
+                }
+
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                self.log_activity(format!(
+                    "Turned the colouring by delivery latency {}",
+                    if self.color_by_latency { "on" } else { "off" }
+                ));
+            }
+            UIEvent::ToggleColorByBatch => {
+                self.color_by_batch = !self.color_by_batch;
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .set_color_by_batch(self.color_by_batch);
+                    }
+                }
+
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                self.log_activity(format!(
+                    "Turned the colouring by fetch batch {}",
+                    if self.color_by_batch { "on" } else { "off" }
+                ));
+            }
+            UIEvent::ToggleReadMarkers => {
+                self.show_read_markers = !self.show_read_markers;
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        events_dag
+                            .write()
+                            .unwrap()
+                            .set_show_read_markers(self.show_read_markers);
+                    }
+                }
+
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                self.log_activity(format!(
+                    "Turned the read markers {}",
+                    if self.show_read_markers { "on" } else { "off" }
+                ));
+            }
+            UIEvent::ToggleTransitiveReduction => {
+                self.transitive_reduction = !self.transitive_reduction;
+
+                let mut redundant = 0;
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.set_transitive_reduction(self.transitive_reduction);
+                        redundant += events_dag.redundant_edge_count();
+                    }
+                }
+
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                self.log_activity(format!(
+                    "Turned the transitive reduction {} ({} redundant edges)",
+                    if self.transitive_reduction {
+                        "on"
                     } else {
-                        // Join the room if the user is not already in it
-                        self.link
-                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::JoinRoom(view_id)))
-                            .emit(());
+                        "off"
+                    },
+                    redundant
+                ));
+            }
+            UIEvent::LayoutDirection(direction) => {
+                let mut layout_options = self.vis.layout_options(self.view_idx);
+                layout_options.direction = direction;
+
+                self.change_layout_options(layout_options);
+            }
+            UIEvent::LayoutAlgorithm(algorithm) => {
+                let mut layout_options = self.vis.layout_options(self.view_idx);
+                layout_options.algorithm = algorithm;
+
+                self.change_layout_options(layout_options);
+            }
+            UIEvent::LevelSeparation(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    if let Ok(separation) = v.trim().parse() {
+                        let mut layout_options = self.vis.layout_options(self.view_idx);
+                        layout_options.level_separation = separation;
+
+                        self.change_layout_options(layout_options);
                     }
                 }
             }
-            BkResponse::RoomJoined(view_id) => {
-                self.console.log("Room joined!");
+            UIEvent::DepthJump(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.trim().parse::<i64>() {
+                        Ok(jump) if jump > 0 => self.depth_jump = jump,
+                        _ => self.console.log("The depth jump must be a positive number"),
+                    }
+                }
+            }
+            UIEvent::NodeSpacing(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    if let Ok(spacing) = v.trim().parse() {
+                        let mut layout_options = self.vis.layout_options(self.view_idx);
+                        layout_options.node_spacing = spacing;
+
+                        self.change_layout_options(layout_options);
+                    }
+                }
+            }
+            UIEvent::ChooseTheme(theme) => {
+                self.apply_theme(theme);
+                self.storage.store(THEME_KEY, theme.name().to_string());
+
+                self.log_activity(format!("Switched to the {} theme", theme.name()));
+            }
+            UIEvent::IdFirstChars(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    self.id_shortener.first = v.trim().parse().ok();
+                    self.apply_id_shortener();
+                }
+            }
+            UIEvent::IdLastChars(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    self.id_shortener.last = v.trim().parse().ok();
+                    self.apply_id_shortener();
+                }
+            }
+            UIEvent::ToggleHideDomain => {
+                self.id_shortener.hide_domain = !self.id_shortener.hide_domain;
+                self.apply_id_shortener();
+            }
+            UIEvent::LabelMaxChars(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    self.label_format.max_chars = v.trim().parse().ok();
+                    self.apply_label_format();
+                }
+            }
+            UIEvent::ToggleLabelLineBreaks => {
+                self.label_format.line_breaks = !self.label_format.line_breaks;
+                self.apply_label_format();
+            }
+            UIEvent::ToggleMonospaceIds => {
+                self.label_format.monospace_ids = !self.label_format.monospace_ids;
+                self.apply_label_format();
+            }
+            UIEvent::DedupWindow(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    if let Ok(window) = v.trim().parse() {
+                        self.dedup_window = window;
+
+                        for view in &self.views {
+                            if let Some(events_dag) = view.get_events_dag() {
+                                events_dag.write().unwrap().set_dedup_window(window);
+                            }
+                        }
+
+                        self.log_activity(format!(
+                            "Set the duplicate-suppression window to {} events",
+                            window
+                        ));
+                    }
+                }
+            }
+            UIEvent::ToggleMergeView => {
+                let merge_view = !self.vis.merge_view();
+
+                self.vis.set_merge_view(merge_view);
+                self.log_activity(format!(
+                    "Turned the merge view {}",
+                    if merge_view { "on" } else { "off" }
+                ));
+            }
+            UIEvent::SetName(n) => {
+                if let html::ChangeData::Value(n) = n {
+                    self.workspace_panel.set_form.name = n;
+                }
+            }
+            UIEvent::SetLhs(l) => {
+                if let html::ChangeData::Value(l) = l {
+                    self.workspace_panel.set_form.lhs = l;
+                }
+            }
+            UIEvent::SetRhs(r) => {
+                if let html::ChangeData::Value(r) = r {
+                    self.workspace_panel.set_form.rhs = r;
+                }
+            }
+            UIEvent::AnnotationNote(note) => {
+                if let html::ChangeData::Value(note) = note {
+                    self.workspace_panel.annotation_form.note = note;
+                }
+            }
+            UIEvent::AnnotationTag(tag) => self.workspace_panel.annotation_form.tag = tag,
+            UIEvent::QuerySender(sender) => {
+                if let html::ChangeData::Value(sender) = sender {
+                    self.workspace_panel.query_form.sender = Some(sender).filter(|s| !s.is_empty());
+                }
+            }
+            UIEvent::QueryType(etype) => {
+                if let html::ChangeData::Value(etype) = etype {
+                    self.workspace_panel.query_form.etype = Some(etype).filter(|t| !t.is_empty());
+                }
+            }
+            UIEvent::QueryOrigin(origin) => {
+                if let html::ChangeData::Value(origin) = origin {
+                    self.workspace_panel.query_form.origin = Some(origin).filter(|o| !o.is_empty());
+                }
+            }
+            UIEvent::QueryBody(body) => {
+                if let html::ChangeData::Value(body) = body {
+                    self.workspace_panel.query_form.body = Some(body).filter(|b| !b.is_empty());
+                }
+            }
+            UIEvent::QueryExpression(expression) => {
+                if let html::ChangeData::Value(expression) = expression {
+                    self.workspace_panel.query_expression = expression;
+                }
+            }
+            UIEvent::ColourRuleExpression(expression) => {
+                if let html::ChangeData::Value(expression) = expression {
+                    self.colour_rule_form.expression = expression;
+                }
+            }
+            UIEvent::ColourRuleColour(colour) => {
+                if let html::ChangeData::Value(colour) = colour {
+                    self.colour_rule_form.colour = colour;
+                }
+            }
+            UIEvent::SnapshotName(name) => {
+                if let html::ChangeData::Value(name) = name {
+                    self.workspace_panel.snapshot_name = name;
+                }
+            }
+            UIEvent::PresetJson(p) => {
+                if let html::ChangeData::Value(p) = p {
+                    self.preset_json = p;
+                }
+            }
+            UIEvent::ExportJson(e) => {
+                if let html::ChangeData::Value(e) = e {
+                    self.export_json = e;
+                }
+            }
+            UIEvent::RoomExportJson(e) => {
+                if let html::ChangeData::Value(e) = e {
+                    self.room_export_json = e;
+                }
+            }
+            UIEvent::RecordingJson(e) => {
+                if let html::ChangeData::Value(e) = e {
+                    self.recorder.json = e;
+                }
+            }
+            UIEvent::VaultPassphrase(p) => {
+                if let html::ChangeData::Value(p) = p {
+                    self.vault.passphrase = p;
+                }
+            }
+            UIEvent::ServerFormName(name) => {
+                if let html::ChangeData::Value(name) = name {
+                    self.server_panel.form.name = name.trim().to_string();
+                }
+            }
+            UIEvent::ServerFormUrl(url) => {
+                if let html::ChangeData::Value(url) = url {
+                    self.server_panel.form.base_url = url.trim().to_string();
+                }
+            }
+            UIEvent::ServerFormToken(token) => {
+                if let html::ChangeData::Value(token) = token {
+                    self.server_panel.form.access_token = token;
+                }
+            }
+            UIEvent::ServerFormBackend(backend) => self.server_panel.form.backend = backend,
+            UIEvent::TimeRangeStart(start) => {
+                if let html::ChangeData::Value(start) = start {
+                    if let (Ok(start), Some(bounds)) = (start.parse::<i64>(), self.time_bounds()) {
+                        let (_, end) = self.time_range.unwrap_or(bounds);
+
+                        self.time_range = Some((start.min(end), end));
+                        self.vis.set_time_range(self.time_range);
+                    }
+                }
+            }
+            UIEvent::TimeRangeEnd(end) => {
+                if let html::ChangeData::Value(end) = end {
+                    if let (Ok(end), Some(bounds)) = (end.parse::<i64>(), self.time_bounds()) {
+                        let (start, _) = self.time_range.unwrap_or(bounds);
+
+                        self.time_range = Some((start, end.max(start)));
+                        self.vis.set_time_range(self.time_range);
+                    }
+                }
+            }
+            UIEvent::ResetTimeRange => {
+                self.time_range = None;
+                self.vis.set_time_range(None);
+            }
+            UIEvent::ReplaySpeed(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.trim().parse() {
+                        Ok(speed) if speed > 0 => self.replay_speed = speed,
+                        _ => self
+                            .console
+                            .log("The replay speed must be a positive number"),
+                    }
+                }
+            }
+            UIEvent::ReplayPosition(cd) => {
+                if let (html::ChangeData::Value(v), Some(replay)) = (cd, &mut self.replay) {
+                    if let Ok(step) = v.parse::<usize>() {
+                        replay.step = step.min(replay.length);
+                        self.vis.set_replay(Some(replay.step));
+                    }
+                }
+            }
+        }
+
+        // Choosing another view shows its fields, it doesn't change them
+        if self.view_idx == view_idx && self.fields_choice.fields != fields {
+            self.record(
+                format!("Changed the fields of the labels of view {}", view_idx + 1),
+                Reversal::of_fields(view_idx, fields),
+            );
+        }
+    }
+
+    fn process_ui_command(&mut self, cmd: UICommand) {
+        match cmd {
+            UICommand::DisplayEventBody => {
+                let (view_id, event_id) = self.targeted_event();
+
+                // The events of the merge view may come from any view
+                let dag = if self.vis.merge_view() {
+                    self.vis.merged_dag()
+                } else {
+                    self.views[view_id].get_events_dag().as_ref()
+                };
+
+                if let Some(dag) = dag {
+                    let dag = dag.read().unwrap();
+
+                    self.event_body = dag
+                        .get_event(&event_id)
+                        .map(|ev| serde_json::to_string_pretty(ev).unwrap());
+                    self.event_latency = dag.latency_of(&event_id);
+                    self.event_batch = dag
+                        .provenance_of(&event_id)
+                        .map(|(index, batch)| (index, batch.clone()));
+                    self.content_view = dag.content_view(&event_id);
+                }
+
+                self.list_view.focus = Some(event_id);
+                self.scroll_list_to_focus();
+            }
+            UICommand::SaveSelectionAsSet => self.save_selection_as_set(),
+            UICommand::AnnotateSelection => self.annotate_selection(),
+            UICommand::RemoveAnnotation(id) => self.remove_annotation(id),
+            UICommand::AnnotationsLoaded(json) => self.load_annotations(json),
+            UICommand::ExtractSelection => {
+                if let Some(sub) = self.selection_subgraph() {
+                    let view_id = self.views.len();
+                    let description = format!(
+                        "Extracted {} selected events into the view {}",
+                        sub.summary().events,
+                        view_id
+                    );
+
+                    self.import_view(view_id, sub.to_exported());
+                    self.log_activity(description);
+                }
+            }
+            UICommand::ExportSelection => {
+                if let Some(sub) = self.selection_subgraph() {
+                    let preset = self.display_preset();
+                    let description = format!("Exported {} selected events", sub.summary().events);
+
+                    let annotations: Annotations = self
+                        .workspace_panel
+                        .workspace
+                        .annotations()
+                        .iter()
+                        .filter(|(id, _)| sub.get_event(id).is_some())
+                        .map(|(id, annotation)| (id.clone(), annotation.clone()))
+                        .collect();
+
+                    self.export_json =
+                        ExportedDocument::new(preset, vec![sub.to_exported()], annotations)
+                            .to_json();
+                    self.log_activity(description);
+                }
+            }
+            UICommand::SelectByQuery => {
+                if self.vis.is_active() {
+                    let dag = if self.vis.merge_view() {
+                        self.vis.merged_dag()
+                    } else {
+                        self.views[self.view_idx].get_events_dag().as_ref()
+                    };
+
+                    if let Some(dag) = dag {
+                        let events = dag
+                            .read()
+                            .unwrap()
+                            .select_where(&self.workspace_panel.query_form);
+                        let description = format!(
+                            "Selected {} events: {}",
+                            events.len(),
+                            self.workspace_panel.query_form.description()
+                        );
+
+                        self.vis.select_events(&events);
+                        self.log_activity(description);
+                    }
+                }
+            }
+            UICommand::SelectByExpression => {
+                let expression = match self.parse_query_expression() {
+                    Some(expression) => expression,
+                    None => return,
+                };
+
+                if self.vis.is_active() {
+                    let dag = if self.vis.merge_view() {
+                        self.vis.merged_dag()
+                    } else {
+                        self.views[self.view_idx].get_events_dag().as_ref()
+                    };
+
+                    if let Some(dag) = dag {
+                        let events = dag.read().unwrap().select_matching(&expression);
+                        let description = format!(
+                            "Selected {} events matching {}",
+                            events.len(),
+                            expression.source()
+                        );
+
+                        self.vis.select_events(&events);
+                        self.log_activity(description);
+                    }
+                }
+            }
+            UICommand::HideByExpression => {
+                if let Some(expression) = self.parse_query_expression() {
+                    let description = format!("Hid the events matching {}", expression.source());
+
+                    self.record_filters(description.clone());
+                    self.apply_to_dags(|dag| {
+                        let matching = dag.select_matching(&expression);
+                        dag.hide_events(&matching);
+                    });
+                    self.log_activity(description);
+                }
+            }
+            UICommand::HighlightByExpression => {
+                if let Some(expression) = self.parse_query_expression() {
+                    let description =
+                        format!("Highlighted the events matching {}", expression.source());
+
+                    self.record_filters(description.clone());
+                    self.apply_to_dags(|dag| {
+                        let matching = dag.select_matching(&expression);
+                        dag.set_highlighted(&matching);
+                    });
+                    self.log_activity(description);
+                }
+            }
+            UICommand::CollapseByExpression => {
+                if let Some(expression) = self.parse_query_expression() {
+                    let description =
+                        format!("Collapsed the events matching {}", expression.source());
+
+                    self.record_filters(description.clone());
+                    self.apply_to_dags(|dag| {
+                        let matching = dag.select_matching(&expression);
+                        dag.collapse_events(&matching);
+                    });
+                    self.log_activity(description);
+                }
+            }
+            UICommand::AddColourRule => {
+                let mut rules = self.colour_rules.clone();
+                rules.push(self.colour_rule_form.clone());
+
+                if self.set_colour_rules(rules) {
+                    self.log_activity(format!(
+                        "Coloured the events matching {} in {}",
+                        self.colour_rule_form.expression, self.colour_rule_form.colour
+                    ));
+                    self.colour_rule_form.expression.clear();
+                }
+            }
+            UICommand::RemoveColourRule(idx) => {
+                if idx < self.colour_rules.len() {
+                    let mut rules = self.colour_rules.clone();
+                    let removed = rules.remove(idx);
+
+                    if self.set_colour_rules(rules) {
+                        self.log_activity(format!(
+                            "Stopped colouring the events matching {}",
+                            removed.expression
+                        ));
+                    }
+                }
+            }
+            UICommand::HideSelection => {
+                if let Some(selected) = self.batch_selection() {
+                    let description = format!("Hid {} events", selected.len());
+
+                    self.record_filters(description.clone());
+                    self.apply_to_dags(|dag| dag.hide_events(&selected));
+                    self.log_activity(description);
+                }
+            }
+            UICommand::HideBranches => {
+                if let Some(selected) = self.batch_selection() {
+                    let description =
+                        format!("Hid the branches starting at {} events", selected.len());
+
+                    self.record_filters(description.clone());
+                    self.apply_to_dags(|dag| {
+                        for id in &selected {
+                            dag.hide_branch(id);
+                        }
+                    });
+                    self.log_activity(description);
+                }
+            }
+            UICommand::RestoreHidden(root) => {
+                let description = format!("Showed the events hidden from {} again", root);
+
+                self.record_filters(description.clone());
+                self.apply_to_dags(|dag| dag.restore_hidden(&root));
+                self.log_activity(description);
+            }
+            UICommand::ShowHiddenEvents => {
+                let description = "Showed the hidden events again".to_string();
+
+                self.record_filters(description.clone());
+                self.apply_to_dags(|dag| dag.show_hidden_events());
+                self.log_activity(description);
+            }
+            UICommand::HighlightSelection => {
+                if let Some(selected) = self.batch_selection() {
+                    let description = format!("Highlighted {} events", selected.len());
+
+                    self.record_filters(description.clone());
+                    self.apply_to_dags(|dag| dag.set_highlighted(&selected));
+                    self.log_activity(description);
+                }
+            }
+            UICommand::FindPath => {
+                if let Some(selected) = self.batch_selection() {
+                    if selected.len() == 2 {
+                        self.find_path(&selected[0], &selected[1]);
+                    } else {
+                        self.console
+                            .log("Select two events to find the path between them");
+                    }
+                }
+            }
+            UICommand::PinEvent => {
+                // The pin belongs to the selected view, like the ring expansion and the unpinning,
+                // whichever view the event was selected in
+                let view_id = self.view_idx;
+                let (_, event_id) = self.targeted_event();
+                let in_view = self.views[view_id]
+                    .get_events_dag()
+                    .as_ref()
+                    .is_some_and(|dag| dag.read().unwrap().get_event(&event_id).is_some());
+
+                if in_view {
+                    self.log_activity(format!(
+                        "Pinned {} as the root of view {}",
+                        event_id,
+                        view_id + 1
+                    ));
+                    self.vis.set_pin(
+                        view_id,
+                        Some(Pin {
+                            event_id,
+                            hops: DEFAULT_PIN_HOPS,
+                        }),
+                    );
+                } else if !event_id.is_empty() {
+                    self.console.log(&format!(
+                        "{} isn't in the DAG of view {}",
+                        event_id,
+                        view_id + 1
+                    ));
+                }
+            }
+            UICommand::ExpandPin => {
+                if let Some(mut pin) = self.vis.pin(self.view_idx).cloned() {
+                    pin.hops += 1;
+                    self.log_activity(format!(
+                        "Drew the events within {} edges of {}",
+                        pin.hops, pin.event_id
+                    ));
+                    self.vis.set_pin(self.view_idx, Some(pin));
+                }
+            }
+            UICommand::Unpin => {
+                self.vis.set_pin(self.view_idx, None);
+                self.log_activity(format!("Unpinned the root of view {}", self.view_idx + 1));
+            }
+            UICommand::ClearHighlights => {
+                let description = "Cleared the highlights".to_string();
+
+                self.record_filters(description.clone());
+                self.apply_to_dags(|dag| dag.set_highlighted(&[]));
+                self.log_activity(description);
+            }
+            UICommand::ComputeMetrics => {
+                if let Some(dag) = self.views[self.view_idx].get_events_dag() {
+                    let metrics = dag.read().unwrap().metrics();
+
+                    self.log_activity(format!(
+                        "Measured the DAG of view {}: {} fork points, {} merge events",
+                        self.view_idx + 1,
+                        metrics.fork_points,
+                        metrics.merge_events
+                    ));
+                    self.dag_metrics = Some((self.view_idx, metrics));
+                }
+            }
+            UICommand::CollapseSelection => {
+                if let Some(selected) = self.batch_selection() {
+                    let description = format!("Collapsed {} events", selected.len());
+
+                    self.record_filters(description.clone());
+                    self.apply_to_dags(|dag| dag.collapse_events(&selected));
+                    self.log_activity(description);
+                }
+            }
+            UICommand::ExpandCollapsed => {
+                let description = "Expanded the collapsed events".to_string();
+
+                self.record_filters(description.clone());
+                self.apply_to_dags(|dag| dag.expand_all());
+                self.log_activity(description);
+            }
+            UICommand::CombineSets(op) => self.combine_sets(op),
+            UICommand::SelectSet(name) => self.select_set(name),
+            UICommand::RemoveSet(name) => self.remove_set(name),
+            UICommand::TakeSnapshot => {
+                let name = self.workspace_panel.snapshot_name.trim().to_string();
+
+                if name.is_empty() {
+                    self.console.log("The snapshot needs a name");
+                } else {
+                    let snapshot = self.take_snapshot(name.clone());
+
+                    self.workspace_panel.snapshots.insert(snapshot);
+                    self.log_activity(format!("Took the snapshot {}", name));
+                }
+            }
+            UICommand::ApplySnapshot(name) => {
+                if let Some(snapshot) = self.workspace_panel.snapshots.get(&name).cloned() {
+                    self.apply_snapshot(snapshot);
+                    self.log_activity(format!("Switched to the snapshot {}", name));
+                }
+            }
+            UICommand::RemoveSnapshot(name) => {
+                if self.workspace_panel.snapshots.remove(&name).is_some() {
+                    self.log_activity(format!("Removed the snapshot {}", name));
+                }
+            }
+            UICommand::CancelBackfill(view_id) => {
+                if let Some(View::CS(view)) = self.views.get_mut(view_id) {
+                    // Dropping the task aborts its request, whose response would otherwise
+                    // trigger the next one
+                    view.gap_task = None;
+                    view.filling_gaps = false;
+                    let progress = view.backfill.take();
+
+                    self.pending_fetches.remove(&(view_id, CONTEXT_ENDPOINT));
+                    self.scheduler.drop_queued(BkCommand::FillGaps(view_id));
+
+                    if let Some(progress) = progress {
+                        self.log_activity(format!(
+                            "Cancelled the filling of the gaps of view {} after {}",
+                            view_id,
+                            progress.description()
+                        ));
+                    }
+                }
+            }
+            UICommand::CancelEarlierFetch(view_id) => {
+                if self.earlier_fetches.remove(&view_id).is_some() {
+                    // Dropping the task aborts its request
+                    let endpoint = match &mut self.views[view_id] {
+                        View::CS(view) => {
+                            view.more_msg_task = None;
+                            MESSAGES_ENDPOINT
+                        }
+                        View::Postgres(view) => {
+                            view.ancestors_task = None;
+                            ANCESTORS_ENDPOINT
+                        }
+                        View::Admin(view) => {
+                            view.more_msg_task = None;
+                            ADMIN_BACKWARDS_ENDPOINT
+                        }
+                    };
+
+                    self.pending_fetches.remove(&(view_id, endpoint));
+                    self.log_activity(format!(
+                        "Cancelled the loading of earlier events in view {}",
+                        view_id
+                    ));
+                }
+            }
+            UICommand::ExportPreset => {
+                self.preset_json = self.display_preset().to_json();
+                self.log_activity("Exported the display preset".to_string());
+            }
+            UICommand::ImportPreset => match DisplayPreset::from_json(&self.preset_json) {
+                Ok(preset) => {
+                    self.record(
+                        "Imported a display preset".to_string(),
+                        Reversal::of_fields(self.view_idx, self.fields_choice.fields.clone()),
+                    );
+
+                    self.fields_choice = FieldsChoice::from_fields(preset.field_set());
+                    self.id_shortener = preset.id_shortener;
+                    self.label_format = preset.label_format;
+                    self.apply_fields();
+                    self.apply_id_shortener();
+                    self.apply_label_format();
+                    self.apply_preset_display(&preset);
+                    self.log_activity(format!("Imported the display preset {}", self.preset_json));
+                }
+                Err(e) => self.console.log(&format!("Invalid preset: {}", e)),
+            },
+            UICommand::ExportViews => {
+                let views: Vec<ExportedView> = self
+                    .views
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(view_id, view)| {
+                        let dag = view.get_events_dag().as_ref()?;
+
+                        Some(ExportedView {
+                            view_idx: Some(view_id),
+                            ..dag.read().unwrap().to_exported()
+                        })
+                    })
+                    .collect();
+                let preset = self.display_preset();
+
+                let mut document = ExportedDocument::new(
+                    preset,
+                    views,
+                    self.workspace_panel.workspace.annotations().clone(),
+                );
+                document.snapshots = self.workspace_panel.snapshots.clone();
+
+                self.export_json = document.to_json();
+                self.log_activity("Exported the views".to_string());
+            }
+            UICommand::DownloadExport => {
+                if self.export_json.is_empty() {
+                    self.process_ui_command(UICommand::ExportViews);
+                }
+
+                download::download(
+                    "matrix-visualisation.json",
+                    "application/json",
+                    &self.export_json,
+                );
+            }
+            UICommand::DownloadJournal => {
+                let source = format!("{:?}", *self.bk_type.read().unwrap());
+                let guards: Vec<_> = self
+                    .views
+                    .iter()
+                    .filter_map(|view| {
+                        view.get_events_dag()
+                            .as_ref()
+                            .map(|dag| (view.get_id(), dag.read().unwrap()))
+                    })
+                    .collect();
+
+                let entries = guards
+                    .iter()
+                    .flat_map(|(view_id, dag)| dag.journal(*view_id, &source));
+
+                download::download(
+                    "matrix-events.jsonl",
+                    "application/x-ndjson",
+                    &journal::to_jsonl(entries),
+                );
+            }
+            UICommand::DownloadOutline => {
+                let outlines: Vec<String> = self
+                    .views
+                    .iter()
+                    .filter_map(|view| {
+                        view.get_events_dag().as_ref().map(|dag| {
+                            let dag = dag.read().unwrap();
+
+                            format!(
+                                "View {}: {} on {}\n\n{}",
+                                view.get_id() + 1,
+                                view.get_room_id(),
+                                view.get_server_name(),
+                                dag.to_text_outline()
+                            )
+                        })
+                    })
+                    .collect();
+
+                download::download("matrix-dag.txt", "text/plain", &outlines.join("\n"));
+            }
+            UICommand::DownloadStandalone => {
+                let view = &self.views[self.view_idx];
+
+                // The merge view is exported as it is drawn
+                let (dag, title) = if self.vis.merge_view() {
+                    (
+                        self.vis.merged_dag(),
+                        format!("{} (merged)", view.get_room_id()),
+                    )
+                } else {
+                    (
+                        view.get_events_dag().as_ref(),
+                        format!("{} on {}", view.get_room_id(), view.get_server_name()),
+                    )
+                };
+
+                if let Some(dag) = dag {
+                    let data_set = dag
+                        .read()
+                        .unwrap()
+                        .create_data_set(&self.vis.fields(self.view_idx));
+                    let html = standalone::to_html(
+                        &title,
+                        &data_set,
+                        &self.vis.layout_options(self.view_idx),
+                        self.theme,
+                    );
+
+                    download::download_html("matrix-dag.html", &html);
+                    self.log_activity(format!("Exported {} as a standalone page", title));
+                }
+            }
+            UICommand::ToggleRecording => match self.recorder.log.take() {
+                Some(recording) => {
+                    self.recorder.json = self.recording_to_json(recording);
+                    self.log_activity("Stopped recording the session".to_string());
+                }
+                None => {
+                    let bk_type = *self.bk_type.read().unwrap();
+
+                    self.recorder.log = Some(SessionLog::new(bk_type));
+                    self.log_activity("Started recording the session".to_string());
+                }
+            },
+            UICommand::DownloadRecording => {
+                if let Some(recording) = self.recorder.log.clone() {
+                    self.recorder.json = self.recording_to_json(recording);
+                }
+
+                download::download(
+                    "matrix-session.json",
+                    "application/json",
+                    &self.recorder.json,
+                );
+            }
+            UICommand::PlayRecording => match SessionLog::from_json(&self.recorder.json) {
+                Ok(recording) => self.play_recording(recording),
+                Err(e) => self.console.log(&format!("Invalid recording: {}", e)),
+            },
+            UICommand::StopPlayback => {
+                self.recorder.playing_back = false;
+                self.log_activity("Stopped playing back the recording".to_string());
+            }
+            UICommand::ImportViews => match ExportedDocument::from_json(&self.export_json) {
+                Ok(document) => {
+                    // The views go back where they were, the older documents not telling it
+                    let view_ids: Vec<ViewIndex> = document
+                        .views
+                        .iter()
+                        .enumerate()
+                        .map(|(i, exported)| exported.view_idx.unwrap_or(i))
+                        .collect();
+                    let reversal = Reversal {
+                        dags: self.replaced_dags(&view_ids),
+                        fields: view_ids
+                            .iter()
+                            .map(|view_id| (*view_id, self.vis.fields(*view_id)))
+                            .collect(),
+                        query: None,
+                    };
+                    self.record("Imported the views".to_string(), reversal);
+
+                    // The fields of the preset are given to every imported views
+                    for view_id in &view_ids {
+                        self.vis.set_fields(*view_id, document.preset.field_set());
+                    }
+                    self.fields_choice = FieldsChoice::from_fields(self.vis.fields(self.view_idx));
+                    self.id_shortener = document.preset.id_shortener;
+                    self.label_format = document.preset.label_format;
+                    self.apply_id_shortener();
+                    self.apply_label_format();
+                    self.apply_preset_display(&document.preset);
+
+                    // The annotations of the document are added to those of the investigation
+                    for (id, annotation) in document.annotations {
+                        self.workspace_panel.workspace.annotate(&id, annotation);
+                    }
+                    self.workspace_panel.snapshots.extend(document.snapshots);
+
+                    let nb_views = document.views.len();
+
+                    for (view_id, exported) in view_ids.into_iter().zip(document.views) {
+                        self.import_view(view_id, exported);
+                    }
+
+                    self.apply_annotations();
+
+                    self.log_activity(format!("Imported {} views", nb_views));
+                }
+                Err(e) => self.console.log(&format!("Invalid document: {}", e)),
+            },
+            UICommand::LoadRoomExport => {
+                match EventsResponse::from_room_export(&self.room_export_json) {
+                    Ok(res) => {
+                        let view_id = self.view_idx;
+                        let reversal = Reversal {
+                            dags: self.replaced_dags(&[view_id]),
+                            fields: vec![(view_id, self.vis.fields(view_id))],
+                            query: None,
+                        };
+                        self.record("Loaded a room export".to_string(), reversal);
+
+                        let nb_events = res.events.len();
+                        self.load_room_export(view_id, res);
+
+                        self.log_activity(format!(
+                            "Loaded {} events from a room export in view {}",
+                            nb_events, view_id
+                        ));
+                    }
+                    Err(e) => self.console.log(&format!("Invalid room export: {}", e)),
+                }
+            }
+            UICommand::SaveDag => match self.views[self.view_idx].get_events_dag() {
+                Some(dag) => {
+                    let key = self.storage_key(self.view_idx);
+                    let stored = serde_json::to_string(&dag.read().unwrap().to_stored())
+                        .expect("Failed to serialize the DAG");
+
+                    self.storage.store(&key, stored);
+                    self.log_activity(format!("Saved the DAG of {} locally", key));
+                }
+                None => self.console.log("There is no DAG to save"),
+            },
+            UICommand::RestoreDag => {
+                let view_id = self.view_idx;
+                let key = self.storage_key(view_id);
+
+                self.storage.load(
+                    &key,
+                    self.link.send_back(move |stored: Option<String>| {
+                        Msg::UICmd(UICommand::DagRestored(view_id, stored))
+                    }),
+                );
+            }
+            UICommand::DagRestored(view_id, stored) => {
+                match stored.map(|json| serde_json::from_str::<StoredRoomEvents>(&json)) {
+                    Some(Ok(stored)) => match self.views[view_id].get_events_dag() {
+                        // Let the user check what would be added to the current DAG first
+                        Some(dag) => {
+                            let events: Vec<JsonValue> = stored
+                                .events
+                                .iter()
+                                .map(|ev| serde_json::to_value(ev).unwrap())
+                                .collect();
+                            let preview = dag.read().unwrap().dry_run_add(&events);
+
+                            self.pending_restore = Some((view_id, stored, preview));
+                        }
+                        None => self.confirm_restore(view_id, stored),
+                    },
+                    Some(Err(e)) => self.console.log(&format!("Invalid saved DAG: {}", e)),
+                    None => self.console.log("There is no saved DAG for this room"),
+                }
+            }
+            UICommand::ConfirmRestore => {
+                if let Some((view_id, stored, _)) = self.pending_restore.take() {
+                    self.confirm_restore(view_id, stored);
+                }
+            }
+            UICommand::CancelRestore => {
+                self.pending_restore = None;
+            }
+            UICommand::ViewportMoved => {
+                let viewport_input: web::html_element::InputElement = web::document()
+                    .query_selector("#viewport-depths")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let levels: Vec<i64> = viewport_input
+                    .raw_value()
+                    .split(',')
+                    .filter_map(|level| level.parse().ok())
+                    .collect();
+
+                if let [min_level, max_level] = levels[..] {
+                    self.vis.show_depth_window(min_level, max_level);
+                }
+            }
+            UICommand::UnlockVault => self.unlock_vault(),
+            UICommand::ThemeLoaded(name) => {
+                if let Some(theme) = name.as_ref().and_then(|name| Theme::from_name(name)) {
+                    self.apply_theme(theme);
+                }
+            }
+            UICommand::LayoutOptionsLoaded(json) => {
+                if let Some(json) = json {
+                    match serde_json::from_str::<Vec<LayoutOptions>>(&json) {
+                        Ok(layout_options) => self.vis.set_all_layout_options(layout_options),
+                        Err(e) => self
+                            .console
+                            .log(&format!("Couldn't load the layout options: {}", e)),
+                    }
+                }
+            }
+            UICommand::LayoutCacheLoaded(json) => {
+                if let Some(json) = json {
+                    match serde_json::from_str::<LayoutCache>(&json) {
+                        Ok(layout_cache) => self.vis.set_layout_cache(layout_cache),
+                        Err(e) => self
+                            .console
+                            .log(&format!("Couldn't load the cached layouts: {}", e)),
+                    }
+                }
+            }
+            UICommand::ClearLayoutCache => {
+                self.vis.clear_layout_cache();
+                self.log_activity("Forgot the cached layouts".to_string());
+            }
+            UICommand::ServersLoaded(json) => {
+                if let Some(json) = json {
+                    match self.server_panel.manager.load_json(&json) {
+                        Ok(()) => self.process_bk_command(BkCommand::CheckServers),
+                        Err(e) => self
+                            .console
+                            .log(&format!("Couldn't load the configured servers: {}", e)),
+                    }
+                }
+            }
+            UICommand::AddServer => {
+                if self.server_panel.form.name.is_empty()
+                    || self.server_panel.form.base_url.is_empty()
+                {
+                    self.console.log("The server needs a name and a base URL");
+                } else {
+                    let description = format!(
+                        "Configured the server {} ({})",
+                        self.server_panel.form.name, self.server_panel.form.base_url
+                    );
+
+                    self.server_panel
+                        .manager
+                        .add(self.server_panel.form.clone());
+                    self.storage
+                        .store(SERVERS_KEY, self.server_panel.manager.to_json());
+                    self.log_activity(description);
+
+                    self.process_bk_command(BkCommand::CheckServers);
+                }
+            }
+            UICommand::RemoveServer(name) => {
+                if self.server_panel.manager.remove(&name) {
+                    self.storage
+                        .store(SERVERS_KEY, self.server_panel.manager.to_json());
+                    self.log_activity(format!("Removed the server {}", name));
+                }
+            }
+            UICommand::UseServer(name) => {
+                let config = match self.server_panel.manager.get(&name) {
+                    Some(config) => config.clone(),
+                    None => return,
+                };
+                let token = config.access_token.clone();
+
+                match (&mut self.views[self.view_idx], config.backend) {
+                    (View::CS(view), BackendChoice::CS) => {
+                        if !token.is_empty() {
+                            view.session.write().unwrap().access_token = Some(token);
+                        }
+                    }
+                    (View::Postgres(_), BackendChoice::Postgres) => {}
+                    (View::Admin(view), BackendChoice::Admin) => {
+                        if !token.is_empty() {
+                            view.session.write().unwrap().access_token = token;
+                        }
+                    }
+                    _ => {
+                        self.console.log(&format!(
+                            "The server {} needs a view with the {:?} backend",
+                            name, config.backend
+                        ));
+                        return;
+                    }
+                }
+
+                self.views[self.view_idx].set_server_name(config.host().to_string());
+
+                let input: web::html_element::InputElement = web::document()
+                    .query_selector("#server-name-input")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                input.set_raw_value(config.host());
+
+                self.log_activity(format!(
+                    "Used the server {} in the view {}",
+                    name, self.view_idx
+                ));
+            }
+            UICommand::VaultLoaded(sealed) => self.vault_loaded(sealed),
+            UICommand::CreateVault => self.create_vault(),
+            UICommand::CancelVault => self.cancel_vault(),
+            UICommand::VaultCreated(created) => self.vault_created(created),
+            UICommand::VaultUnlocked(credentials) => self.vault_unlocked(credentials),
+            UICommand::SaveCredentials => self.save_credentials(),
+            UICommand::VaultSealed(sealed) => self.vault_sealed(sealed),
+            UICommand::ObserveRoom(room_id) => {
+                let input: web::html_element::InputElement = web::document()
+                    .query_selector("#room-id-input")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                input.set_raw_value(&room_id);
+
+                for view in &self.views {
+                    view.set_room_id(room_id.clone());
+                }
+            }
+            UICommand::PickRoom(view_id, room_id) => {
+                self.log_activity(format!("Picked the room {} in view {}", room_id, view_id));
+                self.process_ui_command(UICommand::ObserveRoom(room_id));
+
+                // Join the room or sync it, depending on whether the user is already in it
+                self.link
+                    .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
+                    .emit(());
+            }
+            UICommand::StartReplay => {
+                let length = self.replay_length();
+
+                if length == 0 {
+                    self.console.log("There is no event to replay");
+                } else {
+                    self.replay = Some(Replay {
+                        step: 0,
+                        length,
+                        task: None,
+                    });
+                    self.vis.set_replay(Some(0));
+                    self.schedule_replay_tick();
+
+                    self.log_activity(format!(
+                        "Started replaying the arrival of {} events",
+                        length
+                    ));
+                }
+            }
+            UICommand::ToggleReplay => {
+                let paused = match &mut self.replay {
+                    Some(replay) => replay.task.take().is_some(),
+                    None => return,
+                };
+
+                if !paused {
+                    self.schedule_replay_tick();
+                }
+            }
+            UICommand::StopReplay => {
+                self.replay = None;
+                self.vis.set_replay(None);
+            }
+            UICommand::ReplayTick => {
+                let finished = match &mut self.replay {
+                    Some(replay) => {
+                        replay.task = None;
+                        replay.step = (replay.step + 1).min(replay.length);
+                        self.vis.set_replay(Some(replay.step));
+
+                        replay.step == replay.length
+                    }
+                    None => return,
+                };
+
+                if !finished {
+                    self.schedule_replay_tick();
+                }
+            }
+            UICommand::Undo => match self.history.take_undo() {
+                Some(entry) => {
+                    let reversal = self.revert(entry.reversal);
+
+                    self.log_activity(format!("Undid: {}", entry.description));
+                    self.history.push_redo(HistoryEntry {
+                        description: entry.description,
+                        reversal,
+                    });
+                }
+                None => self.console.log("There is nothing to undo"),
+            },
+            UICommand::Redo => match self.history.take_redo() {
+                Some(entry) => {
+                    let reversal = self.revert(entry.reversal);
+
+                    self.log_activity(format!("Redid: {}", entry.description));
+                    self.history.push_undo(HistoryEntry {
+                        description: entry.description,
+                        reversal,
+                    });
+                }
+                None => self.console.log("There is nothing to redo"),
+            },
+            UICommand::LockVault => self.lock_vault(),
+            UICommand::JumpToDepths(min_depth, max_depth) => {
+                if self.vis.is_active() {
+                    if let Some(dag) = self.views[self.view_idx].get_events_dag() {
+                        // Show every events of the region the user is looking at
+                        if self.sampling {
+                            self.vis
+                                .densify(dag.clone(), self.view_idx, min_depth, max_depth);
+                        }
+                        if self.lazy_rendering {
+                            let (min_level, max_level) = {
+                                let dag = dag.read().unwrap();
+                                (dag.level_of(min_depth), dag.level_of(max_depth))
+                            };
+
+                            self.vis.show_depth_window(min_level, max_level);
+                        }
+
+                        self.vis.focus_depth_range(
+                            dag.clone(),
+                            self.view_idx,
+                            min_depth,
+                            max_depth,
+                        );
+                    }
+                }
+            }
+            UICommand::JumpToEvent(event_id) => {
+                let depth = match self.views[self.view_idx].get_events_dag() {
+                    Some(dag) => dag.read().unwrap().get_event(&event_id).map(|ev| ev.depth),
+                    None => None,
+                };
+
+                match depth {
+                    Some(depth) => {
+                        self.process_ui_command(UICommand::JumpToDepths(
+                            depth - JUMP_MARGIN,
+                            depth + JUMP_MARGIN,
+                        ));
+                        self.vis.select_events(&[event_id]);
+                    }
+                    // The event is fetched from the source of the view, and jumped to once added
+                    None => self.fetch_single_event(self.view_idx, event_id),
+                }
+            }
+            UICommand::ShareSelection => {
+                // The merge view already shows every copy of the event in a single node
+                if self.views.len() < 2 || self.vis.merge_view() {
+                    return;
+                }
+
+                let (_, event_id) = self.targeted_event();
+                let missing: Vec<ViewIndex> = self
+                    .views
+                    .iter()
+                    .filter(|view| match view.get_events_dag() {
+                        Some(dag) => dag.read().unwrap().get_event(&event_id).is_none(),
+                        None => false,
+                    })
+                    .map(|view| view.get_id())
+                    .collect();
+
+                self.vis.focus_event(&event_id);
+                self.shared_selection = Some((event_id, missing));
+            }
+            UICommand::FocusRow(event_id) => {
+                self.list_view.focus = Some(event_id.clone());
+                self.process_ui_command(UICommand::JumpToEvent(event_id));
+            }
+            UICommand::MoveSelection(step) => {
+                let selected = match self.vis.selected_events().into_iter().next() {
+                    Some(selected) => selected,
+                    None => return,
+                };
+                let target = match self.views[self.view_idx].get_events_dag() {
+                    Some(dag) => {
+                        let dag = dag.read().unwrap();
+
+                        match step {
+                            Step::Parent => dag.parents_of(&selected).into_iter().next(),
+                            Step::Child => dag.children_of(&selected).into_iter().next(),
+                            Step::PreviousSibling | Step::NextSibling => {
+                                let siblings = dag.siblings_at_depth(&selected);
+
+                                siblings
+                                    .iter()
+                                    .position(|id| *id == selected)
+                                    .map(|pos| match step {
+                                        Step::PreviousSibling => {
+                                            (pos + siblings.len() - 1) % siblings.len()
+                                        }
+                                        _ => (pos + 1) % siblings.len(),
+                                    })
+                                    .map(|pos| siblings[pos].clone())
+                            }
+                        }
+                    }
+                    None => None,
+                };
+
+                // Scroll the viewport along
+                if let Some(target) = target.filter(|target| *target != selected) {
+                    self.selection_moved.set(true);
+                    self.process_ui_command(UICommand::JumpToEvent(target));
+                }
+            }
+        }
+    }
+
+    // Makes the requests of `cmd` again after a delay growing exponentially with the attempts if
+    // `error` is temporary, and returns whether they will be. Otherwise, or once the attempts are
+    // exhausted, the failure described by `description` is displayed to the user.
+    fn report_failure(
+        &mut self,
+        cmd: Option<BkCommand>,
+        description: &str,
+        error: BackendError,
+    ) -> bool {
+        if let Some(cmd) = cmd {
+            if let Some(view_id) = cmd.authenticated_view() {
+                if self.refresh_and_resume(view_id, cmd, &error) {
+                    return true;
+                }
+            }
+
+            let attempt = self
+                .retries
+                .get(&cmd)
+                .map(|(attempt, _)| *attempt)
+                .unwrap_or(0);
+
+            if error.is_retryable() && attempt < MAX_RETRIES {
+                let delay = error.retry_after().unwrap_or_else(|| retry_delay(attempt));
+
+                // Nothing else is requested from a homeserver which rate limited us until then
+                if let (BackendError::RateLimited(_), Some(server)) =
+                    (&error, self.scheduled_server(cmd))
+                {
+                    self.scheduler
+                        .pause(&server, web::Date::now() + delay as f64);
+                }
+
+                let task = self.timeout.spawn(
+                    std::time::Duration::from_millis(delay),
+                    self.link.send_back(move |_: ()| Msg::Retry(cmd)),
+                );
+
+                self.console.log(&format!(
+                    "{}: {}, retrying in {} ms",
+                    description, error, delay
+                ));
+                self.retries.insert(cmd, (attempt + 1, task));
+
+                return true;
+            }
+
+            self.retries.remove(&cmd);
+        }
+
+        self.toast(format!("{}: {}", description, error));
+
+        false
+    }
+
+    // Returns the homeserver `cmd` makes its requests to, if it is a command of a CS view whose
+    // requests are scheduled. The logins, the refreshes of the access token and the commands
+    // leaving the room aren't held back.
+    fn scheduled_server(&self, cmd: BkCommand) -> Option<String> {
+        let view_id = match cmd {
+            BkCommand::ListRooms(view_id)
+            | BkCommand::ResolveRoomNames(view_id)
+            | BkCommand::SearchPublicRooms(view_id)
+            | BkCommand::JoinRoom(view_id)
+            | BkCommand::CreateFilter(view_id)
+            | BkCommand::Sync(view_id)
+            | BkCommand::FillGaps(view_id)
+            | BkCommand::StitchPredecessor(view_id)
+            | BkCommand::FetchRoomVersion(view_id) => view_id,
+            _ => return None,
+        };
+
+        match self.views.get(view_id) {
+            Some(view @ View::CS(_)) => Some(view.get_server_name()),
+            _ => None,
+        }
+    }
+
+    // Returns the number of requests being made to the homeserver `server` by the CS views.
+    fn in_flight(&self, server: &str) -> usize {
+        self.views
+            .iter()
+            .filter_map(|view| match view {
+                View::CS(cs_view) if view.get_server_name() == server => Some(cs_view.in_flight()),
+                _ => None,
+            })
+            .sum()
+    }
+
+    // Issues the commands held back for the homeservers which can now take them.
+    fn drain_request_queues(&mut self) {
+        for server in self.scheduler.waiting_servers() {
+            loop {
+                let in_flight = self.in_flight(&server);
+
+                match self
+                    .scheduler
+                    .next_ready(&server, in_flight, web::Date::now())
+                {
+                    Some(cmd) => self.process_bk_command(cmd),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    // Displays `message` to the user for a while.
+    fn toast(&mut self, message: String) {
+        let id = self.next_toast;
+        let task = self.timeout.spawn(
+            std::time::Duration::new(TOAST_DURATION, 0),
+            self.link
+                .send_back(move |_: ()| Msg::UI(UIEvent::DismissToast(id))),
+        );
+
+        self.console.log(&message);
+        self.toasts.push((id, message));
+        self.toast_tasks.insert(id, task);
+        self.next_toast += 1;
+    }
+
+    // Changes the layout options of the current view and saves the options of every views.
+    fn change_layout_options(&mut self, layout_options: LayoutOptions) {
+        self.vis.set_layout_options(self.view_idx, layout_options);
+
+        let json = serde_json::to_string(self.vis.all_layout_options())
+            .expect("Failed to serialize the layout options");
+        self.storage.store(LAYOUT_OPTIONS_KEY, json);
+
+        self.log_activity(format!(
+            "Changed the layout of view {} to {:?}",
+            self.view_idx, layout_options
+        ));
+    }
+
+    // Serializes `recording` along with the rooms observed by the views.
+    fn recording_to_json(&self, mut recording: SessionLog) -> String {
+        recording.views = self
+            .views
+            .iter()
+            .map(|view| RecordedView {
+                server_name: view.get_server_name(),
+                room_id: view.get_room_id(),
+            })
+            .collect();
+
+        recording.to_json()
+    }
+
+    // Replaces the views by those of `recording` and gives them its responses, in the order in
+    // which they were received. The commands they trigger, e.g. the next syncs, are dropped until
+    // the playback is stopped.
+    fn play_recording(&mut self, recording: SessionLog) {
+        for view_id in 0..self.views.len() {
+            if self.views[view_id].get_events_dag().is_some() && self.vis.is_active() {
+                self.vis.remove_dag(view_id);
+            }
+        }
+
+        self.recorder.log = None;
+        self.recorder.playing_back = true;
+
+        *self.bk_type.write().unwrap() = recording.backend;
+        self.views = recording
+            .views
+            .iter()
+            .enumerate()
+            .map(|(id, recorded)| {
+                let view = View::new(recording.backend, id, &mut self.link);
+                view.set_server_name(recorded.server_name.clone());
+                view.set_room_id(recorded.room_id.clone());
+
+                view
+            })
+            .collect();
+        self.view_idx = 0;
+
+        let count = recording.responses.len();
+
+        for res in recording.responses {
+            self.process_bk_response(res.into_response());
+        }
+
+        self.log_activity(format!(
+            "Played back a recording of {} responses in {} views",
+            count,
+            self.views.len()
+        ));
+    }
+
+    // Returns the number of events of the largest DAG, which is the number of steps of a replay.
+    fn replay_length(&self) -> usize {
+        self.views
+            .iter()
+            .filter_map(|view| view.get_events_dag().as_ref())
+            .map(|dag| dag.read().unwrap().summary().events)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Reveals the next event of the replay after a delay given by the speed of the replays.
+    fn schedule_replay_tick(&mut self) {
+        let delay = 1000 / u64::from(self.replay_speed.max(1));
+        let task = self.timeout.spawn(
+            std::time::Duration::from_millis(delay),
+            self.link
+                .send_back(|_: ()| Msg::UICmd(UICommand::ReplayTick)),
+        );
+
+        if let Some(replay) = &mut self.replay {
+            replay.task = Some(task);
+        }
+    }
+
+    // Returns the earliest and the latest `origin_server_ts` of the events of every views.
+    fn time_bounds(&self) -> Option<(i64, i64)> {
+        self.views
+            .iter()
+            .filter_map(|view| view.get_events_dag().as_ref())
+            .filter_map(|dag| dag.read().unwrap().time_bounds())
+            .fold(None, |bounds, (start, end)| match bounds {
+                None => Some((start, end)),
+                Some((min, max)) => Some((min.min(start), max.max(end))),
+            })
+    }
+
+    // Marks the events whose origin server is dead in the DAGs of every views.
+    fn update_dead_servers(&mut self) {
+        let dead_servers: HashSet<String> = self
+            .server_status
+            .iter()
+            .filter(|(_, status)| match status {
+                ServerStatus::Dead => true,
+                _ => false,
+            })
+            .map(|(server, _)| server.clone())
+            .collect();
+
+        for view in &self.views {
+            if let Some(events_dag) = view.get_events_dag() {
+                events_dag
+                    .write()
+                    .unwrap()
+                    .set_dead_servers(dead_servers.clone());
+
+                if self.vis.is_active() {
+                    self.vis.update_labels(events_dag.clone(), view.get_id());
+                }
+            }
+        }
+    }
+
+    // Returns the current DAGs of the views `view_ids`, before an operation replacing them.
+    fn replaced_dags(&self, view_ids: &[ViewIndex]) -> Vec<(ViewIndex, DagChange)> {
+        view_ids
+            .iter()
+            .map(|&view_id| {
+                let exported = self
+                    .views
+                    .get(view_id)
+                    .and_then(|view| view.get_events_dag().as_ref())
+                    .map(|dag| dag.read().unwrap().to_exported());
+
+                (view_id, DagChange::Replaced(exported))
+            })
+            .collect()
+    }
+
+    // Returns the number of events of the DAGs of the views `view_ids` which have one, before an
+    // operation adding events to them, see `record_additions`.
+    fn event_counts(&self, view_ids: &[ViewIndex]) -> Vec<(ViewIndex, usize)> {
+        view_ids
+            .iter()
+            .filter_map(|&view_id| {
+                self.views
+                    .get(view_id)?
+                    .get_events_dag()
+                    .as_ref()
+                    .map(|dag| (view_id, dag.read().unwrap().event_count()))
+            })
+            .collect()
+    }
+
+    // Records the events an operation added to the DAGs, which had `counts` events before it, so
+    // that it can be undone by removing them.
+    fn record_additions(&mut self, description: String, counts: Vec<(ViewIndex, usize)>) {
+        let dags = counts
+            .into_iter()
+            .filter_map(|(view_id, count)| {
+                let added = self.views[view_id]
+                    .get_events_dag()
+                    .as_ref()?
+                    .read()
+                    .unwrap()
+                    .events_added_since(count);
+
+                if added.is_empty() {
+                    None
+                } else {
+                    Some((view_id, DagChange::Added(added)))
+                }
+            })
+            .collect();
+
+        self.record(description, Reversal::of_dags(dags));
+    }
+
+    // Records the events hidden, highlighted and collapsed in every view before an operation
+    // changing them, so that it can be undone.
+    fn record_filters(&mut self, description: String) {
+        let dags = self
+            .views
+            .iter()
+            .enumerate()
+            .filter_map(|(view_id, view)| {
+                view.get_events_dag().as_ref().map(|dag| {
+                    (
+                        view_id,
+                        DagChange::Filters(dag.read().unwrap().filter_state()),
+                    )
+                })
+            })
+            .collect();
+
+        self.record(description, Reversal::of_dags(dags));
+    }
+
+    fn record(&mut self, description: String, reversal: Reversal) {
+        if !reversal.is_empty() {
+            self.history.record(HistoryEntry {
+                description,
+                reversal,
+            });
+        }
+    }
+
+    // Reverts what `reversal` describes, and returns how to revert that in turn.
+    fn revert(&mut self, reversal: Reversal) -> Reversal {
+        let mut inverse = Reversal::of_dags(Vec::new());
+
+        for (view_id, fields) in reversal.fields {
+            inverse.fields.push((view_id, self.vis.fields(view_id)));
+
+            if view_id == self.view_idx {
+                self.fields_choice = FieldsChoice::from_fields(fields.clone());
+            }
+
+            self.vis.set_fields(view_id, fields);
+        }
+
+        if let Some(query) = reversal.query {
+            inverse.query = Some(self.query_state());
+            self.set_query_state(query);
+        }
+
+        for (view_id, change) in reversal.dags {
+            if let Some(undone) = self.revert_dag(view_id, change) {
+                inverse.dags.push((view_id, undone));
+            }
+        }
+
+        inverse
+    }
+
+    // Reverts the `change` of the DAG of the view `view_id`, and returns how to revert that in
+    // turn. The changes of the DAGs of the views disconnected since are lost.
+    fn revert_dag(&mut self, view_id: ViewIndex, change: DagChange) -> Option<DagChange> {
+        let dag = self
+            .views
+            .get(view_id)
+            .and_then(|view| view.get_events_dag().clone());
+
+        match change {
+            DagChange::Added(ids) => {
+                let dag = dag?;
+                let removed = dag.write().unwrap().remove_events(&ids);
+
+                if self.vis.is_active() {
+                    self.vis.remove_dag(view_id);
+                    self.vis.add_dag(dag, view_id);
+                }
+
+                Some(DagChange::Removed(removed))
+            }
+            DagChange::Removed(events) => {
+                let dag = dag?;
+                let ids = events
+                    .iter()
+                    .map(|removed| removed.event.event_id.clone())
+                    .collect();
+                dag.write().unwrap().restore_events(events);
+
+                if self.vis.is_active() {
+                    self.vis.remove_dag(view_id);
+                    self.vis.add_dag(dag, view_id);
+                }
+
+                Some(DagChange::Added(ids))
+            }
+            DagChange::Replaced(exported) => {
+                match exported {
+                    Some(exported) => self.import_view(view_id, exported),
+                    None => self.clear_dag(view_id),
+                }
+
+                // The events the undo removed can be fetched again
+                let current = self
+                    .views
+                    .get(view_id)
+                    .and_then(|view| view.get_events_dag().clone());
+                if let (Some(previous), Some(current)) = (&dag, current) {
+                    current
+                        .write()
+                        .unwrap()
+                        .take_deliveries(&mut previous.write().unwrap());
+                }
+
+                let exported = dag.map(|previous| previous.read().unwrap().to_exported());
+
+                Some(DagChange::Replaced(exported))
+            }
+            DagChange::Filters(state) => {
+                let current = {
+                    let dag = dag?;
+                    let mut dag = dag.write().unwrap();
+                    let current = dag.filter_state();
+                    dag.set_filter_state(state);
+
+                    current
+                };
+
+                if self.vis.is_active() {
+                    self.vis.redraw();
+                }
+
+                Some(DagChange::Filters(current))
+            }
+        }
+    }
+
+    // Removes the DAG of the view `view_id`, if it has one.
+    fn clear_dag(&mut self, view_id: ViewIndex) {
+        if let Some(view) = self.views.get_mut(view_id) {
+            if view.get_events_dag().is_some() {
+                view.set_events_dag(None);
+
+                if self.vis.is_active() {
+                    self.vis.remove_dag(view_id);
+                }
+            }
+        }
+    }
+
+    fn query_state(&self) -> QueryState {
+        QueryState {
+            query: self.workspace_panel.query_form.clone(),
+            expression: self.workspace_panel.query_expression.clone(),
+            colour_rules: self.colour_rules.clone(),
+            time_range: self.time_range,
+        }
+    }
+
+    fn set_query_state(&mut self, state: QueryState) {
+        self.workspace_panel.query_form = state.query;
+        self.workspace_panel.query_expression = state.expression;
+        self.set_colour_rules(state.colour_rules);
+        self.time_range = state.time_range;
+        self.vis.set_time_range(self.time_range);
+    }
+
+    // The key under which the DAG of the view `view_id` is saved in the local storage.
+    fn storage_key(&self, view_id: ViewIndex) -> String {
+        let view = &self.views[view_id];
+
+        format!("{}|{}", view.get_server_name(), view.get_room_id())
+    }
+
+    fn confirm_restore(&mut self, view_id: ViewIndex, stored: StoredRoomEvents) {
+        let description = format!(
+            "Restored {} saved events of {} in view {}",
+            stored.events.len(),
+            self.storage_key(view_id),
+            view_id
+        );
+
+        let counts = self.event_counts(&[view_id]);
+
+        self.restore_dag(view_id, stored);
+        self.record_additions(description.clone(), counts);
+        self.log_activity(description);
+    }
+
+    // Adds the events of the `stored` DAG to the view `view_id`, creating its DAG if needed.
+    fn restore_dag(&mut self, view_id: ViewIndex, stored: StoredRoomEvents) {
+        match self.views[view_id].get_events_dag().clone() {
+            Some(dag) => {
+                dag.write().unwrap().merge_stored(stored);
+
+                self.vis.update_dag(dag, view_id);
+            }
+            None => {
+                let mut dag = RoomEvents::from_stored(stored);
+                self.configure_dag(&mut dag);
+
+                let dag = Arc::new(RwLock::new(dag));
+                self.views[view_id].set_events_dag(Some(dag.clone()));
+
+                if !self.vis.is_active() {
+                    self.vis.init(
+                        "#dag-vis",
+                        "#targeted-view",
+                        "#more-ev-target",
+                        "#selected-event",
+                        "#display-body-target",
+                        "#ancestors-id",
+                        "#ancestors-target",
+                        "#viewport-depths",
+                        "#viewport-target",
+                        "#reveal-target",
+                        "#shared-selection-target",
+                    );
+                }
+
+                self.vis.add_dag(dag, view_id);
+            }
+        }
+    }
+
+    // Extracts the events selected in the network, from the merged DAG in the merge view and from
+    // the DAG of the current view otherwise.
+    fn selection_subgraph(&mut self) -> Option<RoomEvents> {
+        if !self.vis.is_active() {
+            return None;
+        }
+
+        let selected = self.vis.selected_events();
+
+        if selected.is_empty() {
+            self.console
+                .log("Select the events to extract with Ctrl+click or a long press");
+            return None;
+        }
+
+        let dag = if self.vis.merge_view() {
+            self.vis.merged_dag()
+        } else {
+            self.views[self.view_idx].get_events_dag().as_ref()
+        };
+
+        dag.map(|dag| dag.read().unwrap().subgraph(&selected))
+    }
+
+    // Returns the view and the ID of the event whose node was last targeted in a network, which
+    // the networks write to hidden inputs before clicking on the button of the command to run.
+    fn targeted_event(&self) -> (ViewIndex, String) {
+        let view_selection_input: web::html_element::InputElement = web::document()
+            .query_selector("#targeted-view")
+            .expect("Couldn't get document element")
+            .expect("Couldn't get document element")
+            .try_into()
+            .unwrap();
+        let view_id: ViewIndex = view_selection_input
+            .raw_value()
+            .parse()
+            .expect("Failed to parse view_id");
+
+        let event_id_input: web::html_element::InputElement = web::document()
+            .query_selector("#selected-event")
+            .expect("Couldn't get document element")
+            .expect("Couldn't get document element")
+            .try_into()
+            .unwrap();
+
+        (view_id, event_id_input.raw_value())
+    }
+
+    // Returns the rows of the list view: the events of the current view, or of every view in the
+    // merge view.
+    fn list_rows(&self) -> Arc<Vec<EventRow>> {
+        let dag = if self.vis.merge_view() {
+            self.vis.merged_dag()
+        } else {
+            self.views[self.view_idx].get_events_dag().as_ref()
+        };
+
+        match dag {
+            Some(dag) => dag
+                .read()
+                .unwrap()
+                .event_rows(self.list_view.sort, self.list_view.descending),
+            None => Arc::new(Vec::new()),
+        }
+    }
+
+    // Scrolls the list view to the row of the focused event if it is out of sight, so that the
+    // list follows the selection in the network.
+    fn scroll_list_to_focus(&mut self) {
+        let focus = match &self.list_view.focus {
+            Some(focus) if self.list_view.shown => focus,
+            _ => return,
+        };
+        let index = match self
+            .list_rows()
+            .iter()
+            .position(|row| &row.event_id == focus)
+        {
+            Some(index) => index as u32,
+            None => return,
+        };
+
+        let top = index * list_view::ROW_HEIGHT;
+        let visible = top >= self.list_view.scroll
+            && top + list_view::ROW_HEIGHT <= self.list_view.scroll + list_view::VIEWPORT_HEIGHT;
+
+        if !visible {
+            // The row is put in the middle of the list
+            self.list_view.scroll = top.saturating_sub(list_view::VIEWPORT_HEIGHT / 2);
+
+            js! {
+                var list = document.getElementById("event-list");
+
+                if (list != null) {
+                    list.scrollTop = @{self.list_view.scroll};
+                }
+            };
+        }
+    }
+
+    // Returns the events selected in the network for a batch action, or `None` if there is none.
+    // Parses the expression of the query form, or logs why it is invalid.
+    fn parse_query_expression(&mut self) -> Option<Expression> {
+        match Expression::parse(&self.workspace_panel.query_expression) {
+            Ok(expression) => Some(expression),
+            Err(error) => {
+                self.console.log(&format!("Invalid expression: {}", error));
+                None
+            }
+        }
+    }
+
+    fn batch_selection(&mut self) -> Option<Vec<String>> {
+        if !self.vis.is_active() {
+            return None;
+        }
+
+        let selected = self.vis.selected_events();
+
+        if selected.is_empty() {
+            self.console
+                .log("Select the events with a query, Ctrl+click or a long press");
+            return None;
+        }
+
+        Some(selected)
+    }
+
+    // Highlights the shortest chain of `prev_events` between the events `a` and `b` in the DAG of
+    // the current view, or of the merge view, and tells whether one depends on the other.
+    fn find_path(&mut self, a: &str, b: &str) {
+        let dag = if self.vis.merge_view() {
+            self.vis.merged_dag()
+        } else {
+            self.views[self.view_idx].get_events_dag().as_ref()
+        };
+        let path = match dag {
+            Some(dag) => dag.read().unwrap().path_between(a, b),
+            None => return,
+        };
+
+        match path {
+            Some(path) => {
+                let description = format!(
+                    "{} depends on {} through {} prev_events",
+                    self.id_shortener.shorten(&path[0]),
+                    self.id_shortener.shorten(&path[path.len() - 1]),
+                    path.len() - 1
+                );
+
+                self.apply_to_dags(|dag| dag.set_highlighted(&path));
+                self.vis.select_events(&path);
+                self.toast(description.clone());
+                self.log_activity(description);
+            }
+            None => self.toast(format!(
+                "{} and {} are concurrent: neither depends on the other",
+                self.id_shortener.shorten(a),
+                self.id_shortener.shorten(b)
+            )),
+        }
+    }
+
+    // Applies `action` to the DAG of every view, so that the merged view agrees with them, and
+    // redraws the network.
+    // Captures the current state of the views under the name `name`.
+    fn take_snapshot(&self, name: String) -> ViewSnapshot {
+        ViewSnapshot {
+            name,
+            views: self
+                .views
+                .iter()
+                .enumerate()
+                .filter_map(|(view_id, view)| {
+                    view.get_events_dag().as_ref().map(|dag| SnapshotView {
+                        view_id,
+                        dag: dag.read().unwrap().to_exported(),
+                        fields: self.vis.fields(view_id).into_iter().collect(),
+                    })
+                })
+                .collect(),
+            preset: DisplayPreset::new(
+                &self.fields_choice.fields,
+                self.id_shortener,
+                self.label_format,
+            ),
+            query: self.workspace_panel.query_form.clone(),
+            expression: self.workspace_panel.query_expression.clone(),
+            colour_rules: self.colour_rules.clone(),
+            time_range: self.time_range,
+            selection: if self.vis.is_active() {
+                self.vis.selected_events()
+            } else {
+                Vec::new()
+            },
+            cameras: if self.vis.is_active() {
+                self.vis.cameras()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    // Brings the views back to `snapshot`, the views which had no DAG in it being cleared, which
+    // can be undone like an import.
+    fn apply_snapshot(&mut self, snapshot: ViewSnapshot) {
+        let nb_views = snapshot
+            .views
+            .iter()
+            .map(|view| view.view_id + 1)
+            .max()
+            .unwrap_or(0)
+            .max(self.views.len());
+        let view_ids: Vec<ViewIndex> = (0..nb_views).collect();
+        let undo = Reversal {
+            dags: self.replaced_dags(&view_ids),
+            fields: view_ids
+                .iter()
+                .map(|view_id| (*view_id, self.vis.fields(*view_id)))
+                .collect(),
+            query: Some(self.query_state()),
+        };
+        self.record(format!("Switched to the snapshot {}", snapshot.name), undo);
+
+        self.id_shortener = snapshot.preset.id_shortener;
+        self.label_format = snapshot.preset.label_format;
+        self.apply_id_shortener();
+        self.apply_label_format();
+
+        self.set_query_state(QueryState {
+            query: snapshot.query,
+            expression: snapshot.expression,
+            colour_rules: snapshot.colour_rules,
+            time_range: snapshot.time_range,
+        });
+
+        let saved: HashSet<ViewIndex> = snapshot.views.iter().map(|view| view.view_id).collect();
+        for view_id in view_ids
+            .into_iter()
+            .filter(|view_id| !saved.contains(view_id))
+        {
+            self.clear_dag(view_id);
+        }
+
+        for view in snapshot.views {
+            self.vis
+                .set_fields(view.view_id, view.fields.into_iter().collect());
+            self.import_view(view.view_id, view.dag);
+        }
+        self.fields_choice = FieldsChoice::from_fields(self.vis.fields(self.view_idx));
+
+        if self.vis.is_active() {
+            self.vis.select_events(&snapshot.selection);
+            self.vis.move_cameras(&snapshot.cameras);
+        }
+    }
+
+    // Gives the colour rules `rules` to the DAGs of every view, or logs why one of them is
+    // invalid and keeps the previous ones. Returns whether the rules were set.
+    fn set_colour_rules(&mut self, rules: Vec<ColourRule>) -> bool {
+        if let Err(error) = rules.iter().try_for_each(|rule| rule.compile().map(|_| ())) {
+            self.console.log(&format!("Invalid expression: {}", error));
+            return false;
+        }
+
+        self.apply_to_dags(|dag| {
+            dag.set_colour_rules(&rules)
+                .expect("The rules were already compiled");
+        });
+        self.colour_rules = rules;
+
+        true
+    }
+
+    // The display preset of the current fields, ID shortener, label format, palette and filters.
+    fn display_preset(&self) -> DisplayPreset {
+        DisplayPreset {
+            palette: Some(PresetPalette {
+                theme: self.theme,
+                colour_rules: self.colour_rules.clone(),
+                color_by_latency: self.color_by_latency,
+                color_by_batch: self.color_by_batch,
+            }),
+            filters: Some(PresetFilters {
+                show_relations: self.show_relations,
+                show_ghosts: self.show_ghosts,
+                show_authority: self.show_authority,
+                show_read_markers: self.show_read_markers,
+                compress_depths: self.compress_depths,
+                transitive_reduction: self.transitive_reduction,
+            }),
+            ..DisplayPreset::new(
+                &self.fields_choice.fields,
+                self.id_shortener,
+                self.label_format,
+            )
+        }
+    }
+
+    // Gives the DAGs of every view the palette and the filters of `preset`, those it doesn't have
+    // being left as they are.
+    fn apply_preset_display(&mut self, preset: &DisplayPreset) {
+        if let Some(palette) = &preset.palette {
+            let (color_by_latency, color_by_batch) =
+                (palette.color_by_latency, palette.color_by_batch);
+
+            self.apply_theme(palette.theme);
+            self.set_colour_rules(palette.colour_rules.clone());
+            self.color_by_latency = color_by_latency;
+            self.color_by_batch = color_by_batch;
+            self.apply_to_dags(|dag| {
+                dag.set_color_by_latency(color_by_latency);
+                dag.set_color_by_batch(color_by_batch);
+            });
+        }
+
+        if let Some(filters) = preset.filters {
+            self.show_relations = filters.show_relations;
+            self.show_ghosts = filters.show_ghosts;
+            self.show_authority = filters.show_authority;
+            self.show_read_markers = filters.show_read_markers;
+            self.compress_depths = filters.compress_depths;
+            self.transitive_reduction = filters.transitive_reduction;
+            self.apply_to_dags(|dag| {
+                dag.set_show_relations(filters.show_relations);
+                dag.set_show_ghosts(filters.show_ghosts);
+                dag.set_show_authority(filters.show_authority);
+                dag.set_show_read_markers(filters.show_read_markers);
+                dag.set_compress_depths(filters.compress_depths);
+                dag.set_transitive_reduction(filters.transitive_reduction);
+            });
+        }
+    }
+
+    // Gives a new DAG the display settings chosen for the DAGs which already exist.
+    fn configure_dag(&self, dag: &mut RoomEvents) {
+        dag.change_size_metric(self.size_metric);
+        dag.set_show_relations(self.show_relations);
+        dag.set_show_ghosts(self.show_ghosts);
+        dag.set_show_authority(self.show_authority);
+        if let Some((server, last_event)) = &self.outage {
+            dag.simulate_outage(server, last_event);
+        }
+        dag.set_compress_depths(self.compress_depths);
+        dag.set_transitive_reduction(self.transitive_reduction);
+        dag.set_color_by_latency(self.color_by_latency);
+        dag.set_color_by_batch(self.color_by_batch);
+        dag.set_show_read_markers(self.show_read_markers);
+        dag.set_colour_rules(&self.colour_rules)
+            .expect("The colour rules were already compiled");
+        dag.set_theme(self.theme);
+        dag.change_id_shortener(self.id_shortener);
+        dag.change_label_format(self.label_format);
+        dag.set_annotations(self.workspace_panel.workspace.annotations().clone());
+        dag.set_dedup_window(self.dedup_window);
+    }
+
+    fn apply_to_dags<F>(&mut self, action: F)
+    where
+        F: Fn(&mut RoomEvents),
+    {
+        for view in &self.views {
+            if let Some(events_dag) = view.get_events_dag() {
+                action(&mut events_dag.write().unwrap());
+            }
+        }
+
+        if self.vis.is_active() {
+            self.vis.redraw();
+        }
+    }
+
+    // Replaces the DAG of the view `view_id` by the `exported` one, creating the view if needed.
+    fn import_view(&mut self, view_id: ViewIndex, exported: ExportedView) {
+        while self.views.len() <= view_id {
+            let bk_type = *self.bk_type.read().unwrap();
+            let view = View::new(bk_type, self.views.len(), &mut self.link);
+
+            self.views.push(view);
+        }
+
+        self.views[view_id].set_server_name(exported.server_name.clone());
+        self.views[view_id].set_room_id(exported.room_id.clone());
+
+        if self.views[view_id].get_events_dag().is_some() && self.vis.is_active() {
+            self.vis.remove_dag(view_id);
+        }
+
+        let mut dag = RoomEvents::from_exported(exported);
+        self.configure_dag(&mut dag);
+
+        let dag = Arc::new(RwLock::new(dag));
+        self.views[view_id].set_events_dag(Some(dag.clone()));
+
+        if !self.vis.is_active() {
+            self.vis.init(
+                "#dag-vis",
+                "#targeted-view",
+                "#more-ev-target",
+                "#selected-event",
+                "#display-body-target",
+                "#ancestors-id",
+                "#ancestors-target",
+                "#viewport-depths",
+                "#viewport-target",
+                "#reveal-target",
+                "#shared-selection-target",
+            );
+        }
+
+        self.vis.add_dag(dag, view_id);
+    }
+
+    // Replaces the DAG of the view `view_id` by the events of a room exported from the database of
+    // Synapse, with the annotations the Postgres backend would have given them.
+    fn load_room_export(&mut self, view_id: ViewIndex, res: EventsResponse) {
+        if let Some(room_id) = res.room_id() {
+            self.views[view_id].set_room_id(room_id.to_string());
+        }
+
+        if self.views[view_id].get_events_dag().is_some() && self.vis.is_active() {
+            self.vis.remove_dag(view_id);
+        }
+
+        let mut dag = RoomEvents::from_source_events(
+            &self.views[view_id].get_room_id(),
+            &self.views[view_id].get_server_name(),
+            res.into(),
+        );
+        self.configure_dag(&mut dag);
+
+        let dag = Arc::new(RwLock::new(dag));
+        self.views[view_id].set_events_dag(Some(dag.clone()));
+
+        if !self.vis.is_active() {
+            self.vis.init(
+                "#dag-vis",
+                "#targeted-view",
+                "#more-ev-target",
+                "#selected-event",
+                "#display-body-target",
+                "#ancestors-id",
+                "#ancestors-target",
+                "#viewport-depths",
+                "#viewport-target",
+                "#reveal-target",
+                "#shared-selection-target",
+            );
+        }
+
+        self.vis.add_dag(dag, view_id);
+    }
+
+    // Replaces every views by new ones using the backend `bk_type`, keeping the server names and
+    // the room ID entered by the user.
+    fn switch_backend(&mut self, bk_type: BackendChoice) {
+        *self.bk_type.write().unwrap() = bk_type;
+
+        let new_views: Vec<View> = (0..self.views.len())
+            .map(|id| View::new(bk_type, id, &mut self.link))
+            .collect();
+
+        for (old_view, new_view) in self.views.iter().zip(new_views.iter()) {
+            new_view.set_server_name(old_view.get_server_name());
+            new_view.set_room_id(old_view.get_room_id());
+        }
+
+        self.views = new_views;
+        self.log_activity(format!("Switched to the {:?} backend", bk_type));
+    }
+
+    // Returns the JSON pointers of the fields included in the labels, sorted.
+    fn pointer_fields(&self) -> Vec<String> {
+        let mut pointers: Vec<String> = self
+            .fields_choice
+            .fields
+            .iter()
+            .filter_map(|field| match field {
+                Field::Pointer(pointer) => Some(pointer.clone()),
+                _ => None,
+            })
+            .collect();
+        pointers.sort();
+
+        pointers
+    }
+
+    // Gives the fields currently chosen to the selected view and updates the labels of its
+    // nodes accordingly.
+    fn apply_fields(&mut self) {
+        self.vis
+            .set_fields(self.view_idx, self.fields_choice.fields.clone());
+    }
+
+    // Gives the colours of `theme` to the page and to the DAG of every view, and redraws them.
+    fn apply_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+
+        for view in &self.views {
+            if let Some(events_dag) = view.get_events_dag() {
+                events_dag.write().unwrap().set_theme(theme);
+            }
+        }
+
+        self.vis.set_theme(theme);
+
+        js! {
+            document.body.className = @{theme.palette().css_class};
+        };
+    }
+
+    // Fetches the event `event_id` alone from the source of the view `view_id`, to add it to its
+    // DAG and jump to it.
+    fn fetch_single_event(&mut self, view_id: ViewIndex, event_id: String) {
+        let view = &mut self.views[view_id];
+        if view.get_events_dag().is_none() || view.single_event_task().is_some() {
+            return;
+        }
+
+        let id = event_id.clone();
+        let callback =
+            self.link.send_back(
+                move |response: Result<SourceEvents, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::SingleEvent(view_id, id.clone(), res)),
+                    Err(e) => Msg::BkRes(BkResponse::SingleEventFailed(
+                        view_id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            );
+
+        self.pending_fetches.insert(
+            (view_id, EVENT_ENDPOINT),
+            (Some(event_id.clone()), web::Date::now()),
+        );
+
+        let view = &mut self.views[view_id];
+        let task = view
+            .dag_source()
+            .fetch(&SourceRequest::Single(event_id), callback);
+        *view.single_event_task() = task;
+    }
+
+    // Makes `request` for the events of the DAG of the view `view_id` through its source. Returns
+    // whether the request was made, which it isn't if the view makes it otherwise or if it is
+    // already in flight.
+    fn fetch_dag_events(&mut self, view_id: ViewIndex, request: SourceRequest) -> bool {
+        let (source, task, callback) = match self.views[view_id].dag_fetch(&request) {
+            Some(request) => request,
+            None => return false,
+        };
+
+        if task.is_some() {
+            return false;
+        }
+
+        *task = source.fetch(&request, callback);
+
+        true
+    }
+
+    // Requests the events preceding the earliest ones of the DAG of the view `view_id`. The
+    // Postgres views fetch the ancestors of `ancestors_of` instead, or of the earliest events of
+    // their DAG if it is `None`.
+    fn request_earlier_events(&mut self, view_id: ViewIndex, ancestors_of: Option<Vec<String>>) {
+        // A single request is made each time, whose progress can be followed and cancelled like
+        // the filling of the gaps
+        let progress = BackfillProgress::new(self.views[view_id].reached_depth(), 1);
+
+        let (endpoint, token, from) = match &self.views[view_id] {
+            View::CS(view) => (
+                MESSAGES_ENDPOINT,
+                view.session.read().unwrap().prev_batch_token.clone(),
+                match &view.events_dag {
+                    Some(dag) => dag.read().unwrap().earliest_events.clone(),
+                    None => Vec::new(),
+                },
+            ),
+            View::Postgres(view) => match &view.events_dag {
+                Some(dag) => {
+                    let from = match ancestors_of {
+                        Some(from) => from,
+                        None => dag.read().unwrap().earliest_events.clone(),
+                    };
+
+                    (ANCESTORS_ENDPOINT, from.first().cloned(), from)
+                }
+                None => {
+                    self.console.log("There was no DAG");
+                    return;
+                }
+            },
+            View::Admin(view) => (
+                ADMIN_BACKWARDS_ENDPOINT,
+                view.session.read().unwrap().prev_batch_token.clone(),
+                Vec::new(),
+            ),
+        };
+
+        if self.fetch_dag_events(view_id, SourceRequest::Before(from)) {
+            self.pending_fetches
+                .insert((view_id, endpoint), (token, web::Date::now()));
+            self.earlier_fetches.insert(view_id, progress);
+        } else {
+            self.console.log("Already fetching earlier events");
+        }
+    }
+
+    // Takes the last request of the view `view_id` to `endpoint`, whose token and time were
+    // noted in `pending_fetches` when it was made, as the batch which brought the events of its
+    // response, which is received now.
+    fn take_fetch(&mut self, view_id: ViewIndex, endpoint: &'static str) -> Option<FetchBatch> {
+        let (token, requested_at) = self.pending_fetches.remove(&(view_id, endpoint))?;
+
+        Some(FetchBatch::new(
+            endpoint,
+            token,
+            requested_at as i64,
+            web::Date::now() as i64,
+        ))
+    }
+
+    // Records that `events` were brought to the DAG of the view `view_id` by its last request to
+    // `endpoint`, before they are added to it.
+    fn record_fetch(&mut self, view_id: ViewIndex, endpoint: &'static str, events: &[JsonValue]) {
+        let batch = match self.take_fetch(view_id, endpoint) {
+            Some(batch) => batch,
+            None => return,
+        };
+
+        if let Some(dag) = self.views[view_id].get_events_dag() {
+            dag.write().unwrap().record_batch(batch, events);
+        }
+    }
+
+    // Tells the user when the earlier events fetched for the view `view_id` were all in its DAG
+    // already, e.g. because the same page was fetched twice.
+    // Records that the request for the earlier events of the view `view_id` brought `added` new
+    // events, which ends its loading.
+    fn finish_earlier_fetch(&mut self, view_id: ViewIndex, added: usize) {
+        let reached_depth = self.views[view_id].reached_depth();
+
+        if let Some(mut progress) = self.earlier_fetches.remove(&view_id) {
+            progress.record(added, reached_depth, 0);
+
+            self.log_activity(format!(
+                "Loaded earlier events in view {}: {}",
+                view_id,
+                progress.description()
+            ));
+        }
+    }
+
+    fn report_redundant_fetch(&mut self, view_id: ViewIndex, outcome: AddOutcome) {
+        if outcome.is_redundant() {
+            self.log_activity(format!(
+                "The {} earlier events fetched for view {} were all in its DAG already",
+                outcome.skipped,
+                view_id + 1
+            ));
+        }
+    }
+
+    // Applies the way the event IDs must be shortened to the DAG of every view.
+    fn apply_id_shortener(&mut self) {
+        for view in &self.views {
+            if let Some(events_dag) = view.get_events_dag() {
+                events_dag
+                    .write()
+                    .unwrap()
+                    .change_id_shortener(self.id_shortener);
+
+                if self.vis.is_active() {
+                    self.vis.update_labels(events_dag.clone(), view.get_id());
+                }
+            }
+        }
+    }
+
+    // Applies the way the fields must be laid out in the labels to the DAG of every view.
+    fn apply_label_format(&mut self) {
+        for view in &self.views {
+            if let Some(events_dag) = view.get_events_dag() {
+                events_dag
+                    .write()
+                    .unwrap()
+                    .change_label_format(self.label_format);
+
+                if self.vis.is_active() {
+                    self.vis.update_labels(events_dag.clone(), view.get_id());
+                }
+            }
+        }
+    }
+
+    fn process_bk_command(&mut self, cmd: BkCommand) {
+        // The commands held back are issued again once their homeserver can take them
+        if let Some(server) = self.scheduled_server(cmd) {
+            let in_flight = self.in_flight(&server);
+
+            if !self
+                .scheduler
+                .admit(&server, cmd, in_flight, web::Date::now())
+            {
+                self.console
+                    .log(&format!("Queued {:?} until {} can take it", cmd, server));
+                return;
+            }
+        }
+
+        // The base URL of the CS API of a homeserver is discovered before logging in to it, the
+        // command waiting for the discovery
+        if let BkCommand::Connect(view_id)
+        | BkCommand::SsoLogin(view_id)
+        | BkCommand::GuestLogin(view_id)
+        | BkCommand::AppserviceLogin(view_id) = cmd
+        {
+            if let View::CS(view) = &mut self.views[view_id] {
+                let server_name = {
+                    let session = view.session.read().unwrap();
+
+                    match session.base_url {
+                        None if !session.server_name.is_empty() => {
+                            Some(session.server_name.clone())
+                        }
+                        _ => None,
+                    }
+                };
+
+                if let Some(server_name) = server_name {
+                    if view.discovery_task.is_none() {
+                        view.discovery_task = Some(view.backend.discover(self.link.send_back(
+                            move |well_known: Option<WellKnownClient>| {
+                                Msg::BkRes(BkResponse::HomeserverDiscovered(
+                                    view_id, cmd, well_known,
+                                ))
+                            },
+                        )));
+                    }
+
+                    self.console
+                        .log(&format!("Discovering the homeserver of {}...", server_name));
+                    return;
+                }
+            }
+        }
+
+        let console_msg = match cmd {
+            BkCommand::Connect(_) => "Connecting...",
+            BkCommand::ListRooms(_) => "Listing joined rooms...",
+            BkCommand::ResolveRoomNames(_) => "Resolving the names of the joined rooms...",
+            BkCommand::SearchPublicRooms(_) => "Searching the public rooms directory...",
+            BkCommand::JoinRoom(_) => "Joining the room...",
+            BkCommand::CreateFilter(_) => "Creating the sync filter...",
+            BkCommand::Sync(_) => "Syncing...",
+            BkCommand::MoreMsg => "Retrieving previous messages...",
+            BkCommand::FetchState => "Fetching the state of the room...",
+            BkCommand::FillGaps(_) => "Filling the gaps of the DAG...",
+            BkCommand::RevealRegion => "Revealing a region of unknown history...",
+            BkCommand::EventReports(_) => "Fetching the event reports...",
+            BkCommand::LeaveRoom(_) => "Leaving the room...",
+            BkCommand::Disconnect(_) => "Disconnecting...",
+            BkCommand::ProbeServers => "Probing the origin servers of the events...",
+            BkCommand::CheckServers => "Checking the configured servers...",
+            BkCommand::SsoLogin(_) => "Redirecting to the SSO of the homeserver...",
+            BkCommand::GuestLogin(_) => "Registering a guest account...",
+            BkCommand::AppserviceLogin(_) => "Connecting as a user of the application service...",
+            BkCommand::RefreshToken(_) => "Refreshing the access token...",
+            BkCommand::StitchPredecessor(_) => "Stitching the room this one replaced...",
+            BkCommand::FetchRoomVersion(_) => "Fetching the version of the room...",
+        };
+
+        self.console.log(console_msg);
+
+        // Keep track of the requests made on behalf of the user, the synchronisation with the
+        // homeserver happening in the background
+        let activity_view = match cmd {
+            BkCommand::Connect(view_id)
+            | BkCommand::FillGaps(view_id)
+            | BkCommand::EventReports(view_id)
+            | BkCommand::LeaveRoom(view_id)
+            | BkCommand::Disconnect(view_id)
+            | BkCommand::SsoLogin(view_id)
+            | BkCommand::GuestLogin(view_id)
+            | BkCommand::AppserviceLogin(view_id)
+            | BkCommand::SearchPublicRooms(view_id)
+            | BkCommand::StitchPredecessor(view_id) => Some(view_id),
+            BkCommand::MoreMsg | BkCommand::FetchState | BkCommand::RevealRegion => {
+                Some(self.view_idx)
+            }
+            BkCommand::ListRooms(_)
+            | BkCommand::ResolveRoomNames(_)
+            | BkCommand::JoinRoom(_)
+            | BkCommand::CreateFilter(_)
+            | BkCommand::Sync(_)
+            | BkCommand::RefreshToken(_)
+            | BkCommand::FetchRoomVersion(_)
+            | BkCommand::ProbeServers
+            | BkCommand::CheckServers => None,
+        };
+
+        if let Some(view_id) = activity_view {
+            let description = format!(
+                "{} (view {}, room {} on {})",
+                console_msg.trim_end_matches("..."),
+                view_id,
+                self.views[view_id].get_room_id(),
+                self.views[view_id].get_server_name(),
+            );
+
+            self.log_activity(description);
+        }
+
+        // Order the backend to make requests to the homeserver according to the command received
+        match cmd {
+            BkCommand::Connect(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => {
+                    let (connected, saved) = {
+                        let session = view.session.read().unwrap();
+                        let saved = self.vault.credentials.as_ref().and_then(|credentials| {
+                            credentials
+                                .find(
+                                    CredentialsKind::ClientServer,
+                                    &session.server_name,
+                                    &session.username,
+                                )
+                                .cloned()
+                        });
+
+                        (session.access_token.is_some(), saved)
+                    };
+
+                    if connected {
+                        self.console.log("You are already connected");
+                    } else if view.connection_task.is_some() {
+                        self.console.log("Already connecting");
+                    } else if let Some(saved) = saved {
+                        // Reuse the session saved in the vault instead of logging in again
+                        {
+                            let mut session = view.session.write().unwrap();
+
+                            session.user_id = saved.user_id;
+                            session.access_token = Some(saved.access_token);
+                            session.device_id = saved.device_id;
+                        }
+
+                        self.console
+                            .log("Connected with the credentials of the vault");
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
+                            .emit(());
+                    } else {
+                        view.connection_task =
+                            Some(view.backend.connect(view.connection_callback.clone()));
+                    }
+                }
+                View::Postgres(view) => match view.events_dag {
+                    None => {
+                        if self.fetch_dag_events(view_id, SourceRequest::Initial) {
+                            self.pending_fetches
+                                .insert((view_id, DEEPEST_ENDPOINT), (None, web::Date::now()));
+                        } else {
+                            self.console.log("Already fetching deepest events");
+                        }
+                    }
+                    Some(_) => self.console.log("Deepest events already fetched"),
+                },
+                View::Admin(view) => match view.events_dag {
+                    None => match view.messages_task {
+                        None => {
+                            let mut session = view.session.write().unwrap();
+
+                            if session.access_token.is_empty() {
+                                let saved =
+                                    self.vault.credentials.as_ref().and_then(|credentials| {
+                                        credentials.find(
+                                            CredentialsKind::Admin,
+                                            &session.server_name,
+                                            "",
+                                        )
+                                    });
+
+                                if let Some(saved) = saved {
+                                    session.access_token = saved.access_token.clone();
+                                }
+                            }
+                            drop(session);
+
+                            self.fetch_dag_events(view_id, SourceRequest::Initial);
+                        }
+                        Some(_) => self.console.log("Already fetching the room's messages"),
+                    },
+                    Some(_) => self.console.log("The room's messages were already fetched"),
+                },
+            },
+            BkCommand::ListRooms(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.listing_rooms_task =
+                        Some(view.backend.list_rooms(view.listing_rooms_callback.clone()))
+                }
+            }
+            BkCommand::ResolveRoomNames(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.room_names_task =
+                        Some(view.backend.room_names(view.room_names_callback.clone()))
+                }
+            }
+            BkCommand::SearchPublicRooms(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    if view.session.read().unwrap().access_token.is_none() {
+                        self.console
+                            .log("Connect before searching the public rooms directory");
+                    } else {
+                        view.public_rooms_task =
+                            Some(view.backend.public_rooms(
+                                view.public_rooms_callback.clone(),
+                                &self.room_search,
+                            ))
+                    }
+                }
+            }
+            BkCommand::JoinRoom(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.joining_room_task =
+                        Some(view.backend.join_room(view.joining_room_callback.clone()))
+                }
+            }
+            BkCommand::CreateFilter(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.filter_task = Some(
+                        view.backend
+                            .create_filter(view.filter_callback.clone(), self.timeline_limit),
+                    )
+                }
+            }
+            BkCommand::Sync(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => {
+                    let next_batch_token = view.session.read().unwrap().next_batch_token.clone();
+
+                    self.pending_fetches.insert(
+                        (view_id, SYNC_ENDPOINT),
+                        (next_batch_token.clone(), web::Date::now()),
+                    );
+                    view.sync_task = Some(
+                        view.backend
+                            .sync(view.sync_callback.clone(), next_batch_token),
+                    )
+                }
+                View::Postgres(view) => {
+                    if let Some(dag) = &view.events_dag {
+                        let from = dag.read().unwrap().latest_events.clone();
+
+                        if self.fetch_dag_events(view_id, SourceRequest::After(from.clone())) {
+                            self.pending_fetches.insert(
+                                (view_id, DESCENDANTS_ENDPOINT),
+                                (from.first().cloned(), web::Date::now()),
+                            );
+                        }
+                    }
+                }
+                View::Admin(view) => {
+                    let next_batch_token = view.session.read().unwrap().next_batch_token.clone();
+
+                    if self.fetch_dag_events(view_id, SourceRequest::After(Vec::new())) {
+                        self.pending_fetches.insert(
+                            (view_id, ADMIN_FORWARDS_ENDPOINT),
+                            (next_batch_token, web::Date::now()),
+                        );
+                    }
+                }
+            },
+            BkCommand::MoreMsg => {
+                let view_selection_input: web::html_element::InputElement = web::document()
+                    .query_selector("#targeted-view")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let view_id: ViewIndex = view_selection_input
+                    .raw_value()
+                    .parse()
+                    .expect("Failed to parse view_id");
+
+                if self.vis.merge_view() {
+                    // The merged DAG can't be backfilled itself: every server is asked for its
+                    // earlier events at once, and the merged DAG is rebuilt as each of them
+                    // answers
+                    let backfilled: Vec<ViewIndex> = (0..self.views.len())
+                        .filter(|view_id| self.views[*view_id].get_events_dag().is_some())
+                        .collect();
+
+                    self.log_activity(format!(
+                        "Loading earlier events from the {} views at once",
+                        backfilled.len()
+                    ));
+
+                    for view_id in backfilled {
+                        self.request_earlier_events(view_id, None);
+                    }
+                } else {
+                    let ancestors_input: web::html_element::InputElement = web::document()
+                        .query_selector("#ancestors-id")
+                        .expect("Couldn't get document element")
+                        .expect("Couldn't get document element")
+                        .try_into()
+                        .unwrap();
+
+                    self.request_earlier_events(view_id, Some(vec![ancestors_input.raw_value()]));
+                }
+            }
+            BkCommand::FetchState => {
+                let view_selection_input: web::html_element::InputElement = web::document()
+                    .query_selector("#targeted-view")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let view_id: ViewIndex = view_selection_input
+                    .raw_value()
+                    .parse()
+                    .expect("Failed to parse view_id");
+
+                let event_id_input: web::html_element::InputElement = web::document()
+                    .query_selector("#selected-event")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let event_id = event_id_input.raw_value();
+
+                match &mut self.views[view_id] {
+                    View::CS(view) => match view.state_task {
+                        None => {
+                            view.state_task = Some(
+                                view.backend
+                                    .room_state(view.state_callback.clone(), &event_id),
+                            )
+                        }
+                        Some(_) => self.console.log("Already fetching the state of the room"),
+                    },
+                    View::Postgres(view) => match view.state_task {
+                        None => {
+                            view.state_task =
+                                Some(view.backend.state(view.state_callback.clone(), &event_id))
+                        }
+                        Some(_) => self.console.log("Already fetching the state of the room"),
+                    },
+                    View::Admin(view) => match view.state_task {
+                        None => {
+                            // The admin API only gives the current state of the room
+                            view.state_task =
+                                Some(view.backend.room_state(view.state_callback.clone()))
+                        }
+                        Some(_) => self.console.log("Already fetching the state of the room"),
+                    },
+                }
+            }
+            BkCommand::FillGaps(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    if view.gap_task.is_some() {
+                        self.console.log("Already filling the gaps");
+                        return;
+                    }
+
+                    // Request the best missing event to backfill which has not been requested yet,
+                    // the response will trigger the request for the next one. Among that many
+                    // targets, at least one hasn't been requested.
+                    let next_missing = match &view.events_dag {
+                        Some(dag) => dag
+                            .read()
+                            .unwrap()
+                            .backfill_targets(view.gap_attempted.len() + 1)
+                            .into_iter()
+                            .find(|id| !view.gap_attempted.contains(id)),
+                        None => {
+                            self.console.log("There is no DAG");
+                            return;
+                        }
+                    };
+
+                    let mut finished = None;
+
+                    match next_missing {
+                        Some(event_id) => {
+                            if view.backfill.is_none() {
+                                view.backfill = Some(BackfillProgress::new(
+                                    view.reached_depth(),
+                                    view.gaps_left(),
+                                ));
+                            }
+
+                            self.pending_fetches.insert(
+                                (view_id, CONTEXT_ENDPOINT),
+                                (Some(event_id.clone()), web::Date::now()),
+                            );
+                            view.gap_task = view
+                                .backend
+                                .get_event_context(view.gap_callback.clone(), &event_id);
+                            view.gap_requested = Some(event_id.clone());
+                            view.gap_attempted.insert(event_id);
+                            view.filling_gaps = true;
+                        }
+                        None => {
+                            view.filling_gaps = false;
+                            finished = view
+                                .backfill
+                                .take()
+                                .map(|progress| (progress, view.gap_failures.len()));
+                            self.console.log("There are no more gaps to fill");
+                        }
+                    }
+
+                    if let Some((progress, failures)) = finished {
+                        self.log_activity(format!(
+                            "Filled the gaps of view {}: {}, {} gaps couldn't be fetched",
+                            view_id,
+                            progress.description(),
+                            failures
+                        ));
+                    }
+                }
+            }
+            BkCommand::FetchRoomVersion(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.room_version_task = Some(
+                        view.backend
+                            .room_version(view.room_version_callback.clone()),
+                    );
+                }
+            }
+            BkCommand::StitchPredecessor(view_id) => {
+                let link = match self.views[view_id].get_events_dag() {
+                    Some(dag) => dag.read().unwrap().predecessor_link(),
+                    None => {
+                        self.console.log("There is no DAG");
+                        return;
+                    }
+                };
+                let (room_id, event_id) = match link {
+                    Some(link) => link,
+                    None => {
+                        self.console
+                            .log("The DAG has no creation event with a predecessor room");
+                        return;
+                    }
+                };
+
+                match &mut self.views[view_id] {
+                    View::CS(view) => {
+                        view.predecessor_task = view.backend.get_room_event_context(
+                            view.predecessor_callback.clone(),
+                            &room_id,
+                            &event_id,
+                            PREDECESSOR_EVENTS,
+                        );
+                    }
+                    View::Postgres(view) => {
+                        view.predecessor_tasks = view.backend.predecessor(
+                            view.predecessor_callback.clone(),
+                            &room_id,
+                            &event_id,
+                            PREDECESSOR_EVENTS,
+                        );
+                    }
+                    View::Admin(_) => self
+                        .console
+                        .log("The predecessor room can't be stitched with the admin API"),
+                }
+            }
+            BkCommand::RevealRegion => {
+                let view_selection_input: web::html_element::InputElement = web::document()
+                    .query_selector("#targeted-view")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let view_id: ViewIndex = view_selection_input
+                    .raw_value()
+                    .parse()
+                    .expect("Failed to parse view_id");
+
+                let orphan_input: web::html_element::InputElement = web::document()
+                    .query_selector("#ancestors-id")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let orphan_id = orphan_input.raw_value();
+
+                match &mut self.views[view_id] {
+                    // Fetch the context of the first missing ancestor of the orphan, without
+                    // going on with the other gaps once it is received
+                    View::CS(view) => match view.gap_task {
+                        None => {
+                            let next_missing = view.events_dag.as_ref().and_then(|dag| {
+                                dag.read()
+                                    .unwrap()
+                                    .fog_regions()
+                                    .into_iter()
+                                    .find(|region| region.orphan == orphan_id)
+                                    .and_then(|region| {
+                                        region
+                                            .missing
+                                            .into_iter()
+                                            .find(|id| !view.gap_attempted.contains(id))
+                                    })
+                            });
+
+                            match next_missing {
+                                Some(event_id) => {
+                                    view.gap_task = view
+                                        .backend
+                                        .get_event_context(view.gap_callback.clone(), &event_id);
+                                    view.gap_requested = Some(event_id.clone());
+                                    view.gap_attempted.insert(event_id);
+                                    view.filling_gaps = false;
+                                }
+                                None => self.console.log("This region can't be revealed further"),
+                            }
+                        }
+                        Some(_) => self.console.log("Already filling the gaps"),
+                    },
+                    View::Postgres(view) => match view.ancestors_task {
+                        None => {
+                            view.ancestors_task = view.backend.fetch(
+                                &SourceRequest::Before(vec![orphan_id]),
+                                view.ancestors_callback.clone(),
+                            );
+                        }
+                        Some(_) => self.console.log("Already fetching ancestors"),
+                    },
+                    View::Admin(_) => self
+                        .console
+                        .log("The admin API can't retrieve the ancestors of a given event"),
+                }
+            }
+            BkCommand::EventReports(view_id) => {
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    match view.reports_task {
+                        None => {
+                            view.reports_task =
+                                Some(view.backend.event_reports(view.reports_callback.clone()))
+                        }
+                        Some(_) => self.console.log("Already fetching the event reports"),
+                    }
+                }
+            }
+            BkCommand::LeaveRoom(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    match view.leaving_room_task {
+                        None => {
+                            view.leaving_room_task =
+                                Some(view.backend.leave_room(view.leaving_room_callback.clone()))
+                        }
+                        Some(_) => self.console.log("Already leaving the room"),
+                    }
+                }
+            }
+            BkCommand::Disconnect(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => match view.session.read().unwrap() {
+                    session if session.access_token.is_none() => {
+                        self.console.log("You were not connected");
+                    }
+                    // The token of an application service can't be logged out
+                    session if session.appservice => {
+                        self.link
+                            .send_back(move |_: ()| Msg::BkRes(BkResponse::Disconnected(view_id)))
+                            .emit(());
+                    }
+                    _ => match view.disconnection_task {
+                        None => {
+                            view.disconnection_task =
+                                Some(view.backend.disconnect(view.disconnection_callback.clone()))
+                        }
+                        Some(_) => self.console.log("Already disconnecting"),
+                    },
+                },
+                View::Postgres(view) => {
+                    if view.session.read().unwrap().connected {
+                        self.console.log("Stopping the backend");
+
+                        match view.stop_task {
+                            None => {
+                                view.stop_task = Some(view.backend.stop(view.stop_callback.clone()))
+                            }
+                            Some(_) => self.console.log("Already stopping the backend"),
+                        }
+                    } else {
+                        self.console.log("You were not connected");
+                    }
+                }
+                View::Admin(view) => {
+                    if view.session.read().unwrap().connected {
+                        // There is nothing to stop on the homeserver's side
+                        self.link
+                            .send_back(move |_: ()| Msg::BkRes(BkResponse::Disconnected(view_id)))
+                            .emit(());
+                    } else {
+                        self.console.log("You were not connected");
+                    }
+                }
+            },
+            BkCommand::SsoLogin(view_id) => {
+                let pending = PendingLogin {
+                    view_id,
+                    server_name: self.views[view_id].get_server_name(),
+                    base_url: match &self.views[view_id] {
+                        View::CS(view) => view.session.read().unwrap().base_url.clone(),
+                        View::Postgres(_) | View::Admin(_) => None,
+                    },
+                    room_id: self.views[view_id].get_room_id(),
+                };
+
+                match &self.views[view_id] {
+                    View::CS(view) if !pending.server_name.is_empty() => {
+                        sso::start(
+                            &pending,
+                            &view.backend.sso_redirect_url(&sso::redirect_url()),
+                        );
+                    }
+                    View::CS(_) => self.console.log("Enter the name of the server first"),
+                    View::Postgres(_) | View::Admin(_) => self
+                        .console
+                        .log("The SSO is only supported by the CS backend"),
+                }
+            }
+            BkCommand::GuestLogin(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => {
+                    if view.session.read().unwrap().access_token.is_some() {
+                        self.console.log("You are already connected");
+                    } else if view.connection_task.is_some() {
+                        self.console.log("Already connecting");
+                    } else {
+                        view.connection_task = Some(
+                            view.backend
+                                .register_guest(view.connection_callback.clone()),
+                        );
+                    }
+                }
+                View::Postgres(_) | View::Admin(_) => self
+                    .console
+                    .log("The guest access is only supported by the CS backend"),
+            },
+            BkCommand::AppserviceLogin(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => {
+                    let mut session = view.session.write().unwrap();
+
+                    if session.access_token.is_some() {
+                        self.console.log("You are already connected");
+                    } else if session.as_token.is_empty() || session.appservice_user_id.is_empty() {
+                        self.console.log(
+                            "Enter the token of the application service and the ID of the user it acts as",
+                        );
+                    } else {
+                        // There is no login: every request carries the token of the application
+                        // service and the ID of the user it acts as
+                        session.access_token = Some(session.as_token.clone());
+                        session.user_id = session.appservice_user_id.clone();
+                        session.appservice = true;
+
+                        self.console
+                            .log(&format!("Connected as {}", session.user_id));
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
+                            .emit(());
+                    }
+                }
+                View::Postgres(_) | View::Admin(_) => self
+                    .console
+                    .log("The application services are only supported by the CS backend"),
+            },
+            BkCommand::RefreshToken(view_id) => self.refresh_token(view_id),
+            BkCommand::ProbeServers => {
+                let servers: HashSet<String> = self
+                    .views
+                    .iter()
+                    .filter_map(|view| view.get_events_dag().as_ref())
+                    .flat_map(|dag| dag.read().unwrap().origin_servers())
+                    .filter(|server| !server.is_empty())
+                    .collect();
+
+                for server in &servers {
+                    if self.probe.tasks.contains_key(server) {
+                        continue;
+                    }
+
+                    let callback_server = server.clone();
+                    let delegation =
+                        self.link
+                            .send_back(move |well_known: Option<WellKnownServer>| {
+                                Msg::BkRes(BkResponse::ServerDelegation(
+                                    callback_server.clone(),
+                                    well_known,
+                                ))
+                            });
+
+                    self.server_status
+                        .insert(server.clone(), ServerStatus::Probing);
+
+                    if can_delegate(server) {
+                        let task = self.probe.well_known(delegation, server);
+
+                        self.probe.tasks.insert(server.clone(), task);
+                    } else {
+                        delegation.emit(None);
+                    }
+                }
+
+                self.log_activity(format!("Probed {} origin servers", servers.len()));
+            }
+            BkCommand::CheckServers => {
+                self.server_panel.manager.check_all(self.link.send_back(
+                    |(name, result): (String, Result<String, BackendError>)| {
+                        Msg::BkRes(BkResponse::ServerChecked(name, result))
+                    },
+                ));
+
+                // The servers are checked periodically, as long as there are some
+                self.server_panel.health_task = if self.server_panel.manager.servers().is_empty() {
+                    None
+                } else {
+                    Some(
+                        self.timeout.spawn(
+                            std::time::Duration::new(HEALTH_CHECK_INTERVAL, 0),
+                            self.link
+                                .send_back(|_: ()| Msg::BkCmd(BkCommand::CheckServers)),
+                        ),
+                    )
+                };
+            }
+        }
+    }
+
+    fn process_bk_response(&mut self, res: BkResponse) {
+        match res {
+            BkResponse::Connected(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.connection_task = None;
+
+                    let mut session = view.session.write().unwrap();
+
+                    // Save the informations given by the homeserver when connecting to it. The access
+                    // token will be used for authenticating subsequent requests.
+                    session.user_id = res.user_id;
+                    session.access_token = Some(res.access_token);
+                    session.device_id = Some(res.device_id);
+                    session.refresh_token = res.refresh_token;
+
+                    self.console.log(&format!(
+                        "Connected with token: {} and as {}",
+                        session.access_token.as_ref().unwrap(),
+                        session.device_id.as_ref().unwrap()
+                    ));
+
+                    // Request the list of the rooms joined by the user as soon as we are connected
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::RoomsList(view_id, res) => {
+                self.console.log("Looking up in joined rooms");
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.listing_rooms_task = None;
+
+                    let room_id = view.session.read().unwrap().room_id.clone();
+                    view.joined_rooms = res.joined_rooms;
+
+                    if room_id.is_empty() {
+                        // Let the user pick the room to observe among the joined ones
+                        self.console.log("Pick the room to observe");
+                        self.link
+                            .send_back(move |_: ()| {
+                                Msg::BkCmd(BkCommand::ResolveRoomNames(view_id))
+                            })
+                            .emit(());
+                    } else if view.joined_rooms.contains(&room_id) {
+                        // If the user is already in the room to observe, make the initial sync
+                        // once the filter restricting it to this room is created
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::CreateFilter(view_id)))
+                            .emit(());
+                    } else {
+                        // Join the room if the user is not already in it
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::JoinRoom(view_id)))
+                            .emit(());
+                    }
+                }
+            }
+            BkResponse::RoomNamesResolved(view_id, names) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.room_names_task = None;
+                    view.room_names = names;
+                }
+            }
+            BkResponse::PublicRoomsFound(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.public_rooms_task = None;
+
+                    if res.chunk.is_empty() {
+                        self.console.log("No public room matches the search");
+                    }
+
+                    view.public_rooms = res.chunk;
+                }
+            }
+            BkResponse::RoomJoined(view_id) => {
+                self.console.log("Room joined!");
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.joining_room_task = None;
+
+                    // Make the initial sync as soon as the user has joined the room and the
+                    // filter restricting it to this room is created
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::CreateFilter(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::FilterCreated(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.filter_task = None;
+                    view.session.write().unwrap().filter_id = Some(res.filter_id);
+
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::Synced(view_id, res) => {
+                let batch = self.take_fetch(view_id, SYNC_ENDPOINT);
+                let mut created_dag = None; // Configured once the view isn't borrowed anymore
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.sync_task = None;
+
+                    let mut session = view.session.write().unwrap();
+                    let next_batch_token = res.next_batch.clone(); // Save the next batch token to get new events later
+
+                    match session.next_batch_token {
+                        None => {
+                            // Initialise the prev batch token on the initial sync
+                            if let Some(room) = res.rooms.join.get(&session.room_id) {
+                                session.prev_batch_token = room.timeline.prev_batch.clone();
+                            }
+
+                            match view.events_dag.clone() {
+                                // The DAG may have been restored from the local storage before
+                                // the initial sync, in which case the new events are added to it
+                                Some(dag) => {
+                                    if let Some(room) = res.rooms.join.get(&session.room_id) {
+                                        {
+                                            let mut dag = dag.write().unwrap();
+
+                                            if let Some(batch) = batch {
+                                                dag.record_batch(batch, &room.timeline.events);
+                                            }
+
+                                            let new_events = room
+                                                .timeline
+                                                .events
+                                                .iter()
+                                                .filter(|ev| match ev["event_id"].as_str() {
+                                                    Some(id) => dag.get_event(id).is_none(),
+                                                    None => false,
+                                                })
+                                                .cloned()
+                                                .collect();
+
+                                            dag.add_events(new_events);
+                                        }
+
+                                        self.vis.update_dag(dag, view_id);
+                                    }
+                                }
+                                None => {
+                                    // Create a new DAG if it is the initial sync
+                                    match res.rooms.join.get(&session.room_id) {
+                                        Some(room) => {
+                                            let mut dag = RoomEvents::from_events(
+                                                &session.room_id,
+                                                &session.server_name,
+                                                &room.timeline.events,
+                                            );
+
+                                            if let Some(batch) = batch {
+                                                dag.record_batch(batch, &room.timeline.events);
+                                            }
+
+                                            dag.update_read_markers(
+                                                &session.user_id,
+                                                &room.ephemeral.events,
+                                                &room.account_data.events,
+                                            );
+                                            created_dag = Some(dag);
+                                        }
+                                        None => self.console.log("Failed to build the DAG"),
+                                    }
+                                }
+                            }
+                        }
+                        Some(_) => match view.events_dag.clone() {
+                            // Add new events to the DAG
+                            Some(dag) => {
+                                if let Some(room) = res.rooms.join.get(&session.room_id) {
+                                    {
+                                        let mut dag = dag.write().unwrap();
+
+                                        if let Some(batch) = batch {
+                                            dag.record_batch(batch, &room.timeline.events);
+                                        }
+
+                                        dag.add_events(room.timeline.events.clone());
+                                    }
+
+                                    self.vis.update_dag(dag, view_id);
+                                }
+                            }
+                            None => self.console.log("There is no DAG"),
+                        },
+                    }
+
+                    // The read receipts and the fully-read marker only come with the syncs, and
+                    // they can move without any new event
+                    if let (Some(dag), Some(room)) = (
+                        view.events_dag.clone(),
+                        res.rooms.join.get(&session.room_id),
+                    ) {
+                        let moved = dag.write().unwrap().update_read_markers(
+                            &session.user_id,
+                            &room.ephemeral.events,
+                            &room.account_data.events,
+                        );
+
+                        if moved {
+                            self.vis.update_labels(dag, view_id);
+                        }
+                    }
+
+                    session.next_batch_token = Some(next_batch_token);
+
+                    // Request for futur new events
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                        .emit(());
+                }
+
+                if let Some(mut dag) = created_dag {
+                    self.configure_dag(&mut dag);
+
+                    let dag = Arc::new(RwLock::new(dag));
+                    self.views[view_id].set_events_dag(Some(dag.clone()));
+
+                    // Display the DAG with VisJs now that it has been successfully built
+                    if !self.vis.is_active() {
+                        self.vis.init(
+                            "#dag-vis",
+                            "#targeted-view",
+                            "#more-ev-target",
+                            "#selected-event",
+                            "#display-body-target",
+                            "#ancestors-id",
+                            "#ancestors-target",
+                            "#viewport-depths",
+                            "#viewport-target",
+                            "#reveal-target",
+                            "#shared-selection-target",
+                        );
+                    }
+
+                    // The creation event of the room is rarely among its latest events, its
+                    // content is then asked for
+                    if dag.read().unwrap().room_version().is_none() {
+                        self.link
+                            .send_back(move |_: ()| {
+                                Msg::BkCmd(BkCommand::FetchRoomVersion(view_id))
+                            })
+                            .emit(());
+                    }
+
+                    self.vis.add_dag(dag, view_id);
+                }
+            }
+            BkResponse::StateFetched(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+
+                    let event_bodies = res.state.clone();
+
+                    let object = json!({ "events": event_bodies });
+
+                    self.room_state = match serde_json::to_string_pretty(&object) {
+                        Ok(state) => Some(state),
+                        Err(_) => None,
+                    }
+                }
+            }
+            BkResponse::PredecessorEvents(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.predecessor_task = None;
+                }
+
+                if let Some(dag) = self.views[view_id].get_events_dag().clone() {
+                    let link = dag.read().unwrap().predecessor_link();
+
+                    if let Some((room_id, _)) = link {
+                        let count = res.events.len();
+
+                        dag.write().unwrap().stitch_predecessor(&room_id, res);
+
+                        if self.vis.is_active() {
+                            self.vis.redraw();
+                        }
+
+                        self.log_activity(format!(
+                            "Stitched {} events of the predecessor room {} into view {}",
+                            count, room_id, view_id
+                        ));
+                    }
+                }
+            }
+            BkResponse::GapFetched(view_id, res) => {
+                let counts = self.event_counts(&[view_id]);
+
+                let mut fetched = res.events_before.clone();
+                fetched.push(res.event.clone());
+                self.record_fetch(view_id, CONTEXT_ENDPOINT, &fetched);
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.gap_task = None;
+                    view.gap_requested = None;
+
+                    match view.events_dag.clone() {
+                        // Add the missing events to the DAG and look for the next gap
+                        Some(dag) => {
+                            let mut events = res.events_before;
+                            events.push(res.event);
+
+                            let count = {
+                                let mut dag = dag.write().unwrap();
+                                let new_events: Vec<JsonValue> = events
+                                    .into_iter()
+                                    .filter(|ev| match ev["event_id"].as_str() {
+                                        Some(id) => dag.get_event(id).is_none(),
+                                        None => false,
+                                    })
+                                    .collect();
+                                let count = new_events.len();
+
+                                dag.add_events(new_events);
+
+                                count
+                            };
+
+                            self.vis.update_dag(dag, view_id);
+
+                            if view.filling_gaps {
+                                let (reached_depth, gaps_left) =
+                                    (view.reached_depth(), view.gaps_left());
+
+                                if let Some(progress) = &mut view.backfill {
+                                    progress.record(count, reached_depth, gaps_left);
+                                }
+
+                                self.link
+                                    .send_back(move |_: ()| {
+                                        Msg::BkCmd(BkCommand::FillGaps(view_id))
+                                    })
+                                    .emit(());
+                            }
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+
+                self.record_additions(format!("Filled a gap in view {}", view_id), counts);
+            }
+            BkResponse::RoomLeft(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.leaving_room_task = None;
+
+                    self.console.log("Room left!");
+
+                    // Disconnect as soon as we leave the room
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Disconnect(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::Disconnected(view_id) => {
+                match &mut self.views[view_id] {
+                    View::CS(view) => {
+                        self.console.log("Disconnected");
+
+                        view.sync_task = None; // If a `/sync` request was in progress, cancel it
+                        view.gap_task = None;
+                        view.gap_attempted.clear();
+                        view.gap_failures.clear();
+                        view.backfill = None;
+                        view.disconnection_task = None;
+
+                        let mut session = view.session.write().unwrap();
+
+                        // Erase the current session data so they won't be erroneously used if the user
+                        // logs in again
+                        session.access_token = None;
+                        session.appservice = false;
+                        session.device_id = None;
+                        session.filter_id = None;
+                        session.next_batch_token = None;
+                        session.prev_batch_token = None;
+
+                        view.events_dag = None;
+                        self.vis.remove_dag(view_id);
+
+                        self.event_body = None;
+                        self.room_state = None;
+                    }
+                    View::Postgres(view) => {
+                        self.console.log("Backend stopped");
+
+                        view.stop_task = None;
+
+                        let mut session = view.session.write().unwrap();
+
+                        session.connected = false;
+                        view.descendants_timeout_task = None;
+                        view.live_task = None;
+                        view.live = false;
+                        view.events_dag = None;
+                        self.vis.remove_dag(view_id);
+
+                        self.event_body = None;
+                        self.room_state = None;
+                    }
+                    View::Admin(view) => {
+                        self.console.log("Observation stopped");
+
+                        view.new_msg_task = None;
+                        view.new_msg_timeout_task = None;
+
+                        let mut session = view.session.write().unwrap();
+
+                        session.connected = false;
+                        session.next_batch_token = None;
+                        session.prev_batch_token = None;
+
+                        view.events_dag = None;
+                        self.vis.remove_dag(view_id);
+
+                        self.event_body = None;
+                        self.room_state = None;
+                    }
+                }
+            }
+
+            BkResponse::TokenRefreshed(view_id, res) => self.token_refreshed(view_id, res),
+            BkResponse::ConnectionFailed(view_id, e) => {
+                self.report_failure(Some(BkCommand::Connect(view_id)), "Connection failed", e);
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.connection_task = None;
+                }
+            }
+            BkResponse::ListingRoomsFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::ListRooms(view_id)),
+                    "Failed to get the list of joined rooms",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.listing_rooms_task = None;
+                }
+            }
+            BkResponse::ResolvingRoomNamesFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::ResolveRoomNames(view_id)),
+                    "Failed to resolve the names of the joined rooms",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.room_names_task = None;
+                }
+            }
+            BkResponse::SearchingPublicRoomsFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::SearchPublicRooms(view_id)),
+                    "Failed to search the public rooms directory",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.public_rooms_task = None;
+                }
+            }
+            BkResponse::JoiningRoomFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::JoinRoom(view_id)),
+                    "Failed to join the room",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.joining_room_task = None;
+                }
+            }
+            BkResponse::CreatingFilterFailed(view_id, e) => {
+                let retried = self.report_failure(
+                    Some(BkCommand::CreateFilter(view_id)),
+                    "Could not create the sync filter, the whole account will be synced",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.filter_task = None;
+
+                    if !retried {
+                        // Fall back on the inline filter, which doesn't restrict the rooms
+                        view.session.write().unwrap().filter_id = None;
+
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                            .emit(());
+                    }
+                }
+            }
+            BkResponse::RefreshFailed(view_id, e) => self.refresh_failed(view_id, e),
+            BkResponse::RoomVersionFetched(view_id, version) => {
+                let events_dag = match &mut self.views[view_id] {
+                    View::CS(view) => {
+                        view.room_version_task = None;
+
+                        view.events_dag.clone()
+                    }
+                    _ => return,
+                };
+
+                if let Some(dag) = events_dag {
+                    let mismatches = {
+                        let mut dag = dag.write().unwrap();
+                        dag.set_declared_room_version(version.clone());
+
+                        dag.version_mismatches().len()
+                    };
+
+                    self.log_activity(format!(
+                        "The room of view {} is a version {} room, {} events don't match it",
+                        view_id, version, mismatches
+                    ));
+
+                    if mismatches > 0 {
+                        self.vis.update_labels(dag, view_id);
+                    }
+                }
+            }
+            BkResponse::FetchingRoomVersionFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::FetchRoomVersion(view_id)),
+                    "Could not fetch the version of the room",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.room_version_task = None;
+                }
+            }
+            BkResponse::PredecessorFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::StitchPredecessor(view_id)),
+                    "Could not retrieve the events of the predecessor room",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.predecessor_task = None;
+                }
+            }
+            BkResponse::SyncFailed(view_id, e) => {
+                self.report_failure(Some(BkCommand::Sync(view_id)), "Could not sync", e);
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.sync_task = None;
+                }
+            }
+            BkResponse::MoreMsgFailed(view_id, e) => {
+                if !self.refresh_and_resume(view_id, BkCommand::MoreMsg, &e) {
+                    self.report_failure(None, "Could not retrieve previous messages", e);
+                }
+
+                self.earlier_fetches.remove(&view_id);
+
+                match &mut self.views[view_id] {
+                    View::CS(view) => view.more_msg_task = None,
+                    View::Admin(view) => view.more_msg_task = None,
+                    View::Postgres(_) => {}
+                }
+            }
+            BkResponse::FetchStateFailed(view_id, e) => {
+                if !self.refresh_and_resume(view_id, BkCommand::FetchState, &e) {
+                    self.report_failure(None, "Could not fetch the state of the room", e);
+                }
+
+                match &mut self.views[view_id] {
+                    View::CS(view) => view.more_msg_task = None,
+                    View::Admin(view) => view.state_task = None,
+                    View::Postgres(_) => {}
+                }
+            }
+            BkResponse::FillGapsFailed(view_id, e) => {
+                // The temporary failures are retried, the other gaps waiting for this one, and
+                // the filling stops if the homeserver can't be reached or the session is over
+                let temporary = e.is_retryable()
+                    || matches!(e, BackendError::TokenExpired | BackendError::Auth(401));
+                let retried = temporary
+                    && self.report_failure(
+                        Some(BkCommand::FillGaps(view_id)),
+                        "Could not fill the gap, stopping",
+                        e.clone(),
+                    );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.gap_task = None;
+                    let event_id = view.gap_requested.take();
+
+                    if retried {
+                        // Request the same missing event again
+                        if let Some(event_id) = event_id {
+                            view.gap_attempted.remove(&event_id);
+                        }
+                    } else if temporary {
+                        view.filling_gaps = false;
+                        view.backfill = None;
+                    } else {
+                        // The event may be unknown to the homeserver, the other gaps can still
+                        // be filled
+                        if let Some(event_id) = event_id {
+                            self.console.log(&format!(
+                                "Could not fetch the missing event {}: {}",
+                                event_id, e
+                            ));
+                            view.gap_failures.insert(event_id, e);
+                        }
+
+                        if view.filling_gaps {
+                            self.link
+                                .send_back(move |_: ()| Msg::BkCmd(BkCommand::FillGaps(view_id)))
+                                .emit(());
+                        }
+                    }
+                }
+            }
+            BkResponse::LeavingRoomFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::LeaveRoom(view_id)),
+                    "Failed to leave the room",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.leaving_room_task = None;
+                }
+            }
+            BkResponse::DisconnectionFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::Disconnect(view_id)),
+                    "Could not disconnect",
+                    e,
+                );
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.disconnection_task = None;
+                }
+            }
+
+            BkResponse::DeepestEvents(view_id, res) => {
+                let batch = self.take_fetch(view_id, DEEPEST_ENDPOINT);
+
+                let mut dag = match &mut self.views[view_id] {
+                    View::Postgres(view) => {
+                        view.deepest_task = None;
+
+                        let mut session = view.session.write().unwrap();
+                        session.connected = true;
+
+                        let events = batch.as_ref().map(|_| res.events.clone());
+                        let mut dag = model::dag::RoomEvents::from_source_events(
+                            &session.room_id,
+                            &session.server_name,
+                            res,
+                        );
+                        if let Some((batch, events)) = batch.zip(events) {
+                            dag.record_batch(batch, &events);
+                        }
+
+                        dag
+                    }
+                    View::CS(_) | View::Admin(_) => return,
+                };
+                self.configure_dag(&mut dag);
+
+                let dag = Arc::new(RwLock::new(dag));
+                self.views[view_id].set_events_dag(Some(dag.clone()));
+
+                if !self.vis.is_active() {
+                    self.vis.init(
+                        "#dag-vis",
+                        "#targeted-view",
+                        "#more-ev-target",
+                        "#selected-event",
+                        "#display-body-target",
+                        "#ancestors-id",
+                        "#ancestors-target",
+                        "#viewport-depths",
+                        "#viewport-target",
+                        "#reveal-target",
+                        "#shared-selection-target",
+                    );
+                }
+
+                self.vis.add_dag(dag, view_id);
+
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.descendants_timeout_task = Some(
+                        self.timeout.spawn(
+                            std::time::Duration::new(5, 0),
+                            self.link
+                                .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id))),
+                        ),
+                    );
+
+                    // Polling for the new events goes on until the backend pushes them
+                    view.live_task = Some(
+                        view.backend
+                            .live(view.live_callback.clone(), view.live_notification.clone()),
+                    );
+                }
+            }
+            BkResponse::EventsBefore(view_id, res) => {
+                let counts = self.event_counts(&[view_id]);
+                let endpoint = match self.views[view_id] {
+                    View::CS(_) => MESSAGES_ENDPOINT,
+                    View::Postgres(_) => ANCESTORS_ENDPOINT,
+                    View::Admin(_) => ADMIN_BACKWARDS_ENDPOINT,
+                };
+                self.record_fetch(view_id, endpoint, &res.events);
+
+                let events_dag = match &mut self.views[view_id] {
+                    View::CS(view) => {
+                        view.more_msg_task = None;
+
+                        view.events_dag.clone()
+                    }
+                    View::Postgres(view) => {
+                        view.ancestors_task = None;
+
+                        view.events_dag.clone()
+                    }
+                    View::Admin(view) => {
+                        view.more_msg_task = None;
+
+                        view.events_dag.clone()
+                    }
+                };
+
+                match events_dag {
+                    // Add earlier events to the DAG and display them
+                    Some(dag) => {
+                        let outcome = dag.write().unwrap().add_source_events(res);
+
+                        self.vis.update_dag(dag, view_id);
+                        self.finish_earlier_fetch(view_id, outcome.added);
+                        self.report_redundant_fetch(view_id, outcome);
+                        self.record_additions(
+                            format!("Loaded earlier events in view {}", view_id),
+                            counts,
+                        );
+                    }
+                    None => self.console.log("There was no DAG"),
+                }
+            }
+            BkResponse::SingleEvent(view_id, event_id, res) => {
+                *self.views[view_id].single_event_task() = None;
+                self.record_fetch(view_id, EVENT_ENDPOINT, &res.events);
+
+                if let Some(dag) = self.views[view_id].get_events_dag().clone() {
+                    let counts = self.event_counts(&[view_id]);
+
+                    // The event may not belong to the DAG, e.g. if it is from another room
+                    let added = {
+                        let mut dag = dag.write().unwrap();
+
+                        dag.add_source_events(res);
+                        dag.get_event(&event_id).is_some()
+                    };
+
+                    self.vis.update_dag(dag, view_id);
+                    self.record_additions(
+                        format!("Fetched the event {} in view {}", event_id, view_id),
+                        counts,
+                    );
+
+                    if added && view_id == self.view_idx {
+                        self.process_ui_command(UICommand::JumpToEvent(event_id));
+                    }
+                }
+            }
+            BkResponse::Descendants(view_id, res) => {
+                self.record_fetch(view_id, DESCENDANTS_ENDPOINT, &res.events);
+
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.descendants_task = None;
+
+                    match view.events_dag.clone() {
+                        Some(dag) => {
+                            let caught_up = res.events.is_empty();
+
+                            dag.write().unwrap().add_source_events(res);
+
+                            self.vis.update_dag(dag, view_id);
+
+                            if view.session.read().unwrap().connected {
+                                if !view.live {
+                                    view.descendants_timeout_task = Some(self.timeout.spawn(
+                                        std::time::Duration::new(5, 0),
+                                        self.link.send_back(move |_: ()| {
+                                            Msg::BkCmd(BkCommand::Sync(view_id))
+                                        }),
+                                    ));
+                                } else if !caught_up {
+                                    // Keep catching up with the events persisted before the
+                                    // backend started pushing them
+                                    self.link
+                                        .send_back(move |_: ()| {
+                                            Msg::BkCmd(BkCommand::Sync(view_id))
+                                        })
+                                        .emit(());
+                                }
+                            }
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+            }
+            BkResponse::LiveEvents(view_id, res) => {
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    match view.events_dag.clone() {
+                        Some(dag) => {
+                            dag.write().unwrap().add_source_events(res);
+
+                            self.vis.update_dag(dag, view_id);
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+            }
+            BkResponse::LiveOpened(view_id) => {
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.live = true;
+                    view.descendants_timeout_task = None;
+
+                    // Fetch the events persisted between the first request and the opening of
+                    // the WebSocket, which the backend won't push
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                        .emit(());
+                }
+
+                self.log_activity(format!(
+                    "The backend of view {} now pushes the new events",
+                    view_id
+                ));
+            }
+            BkResponse::LiveClosed(view_id) => {
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    let was_live = view.live;
+
+                    view.live_task = None;
+                    view.live = false;
+
+                    // Go back to polling for the new events
+                    if was_live && view.session.read().unwrap().connected {
+                        self.console
+                            .log("The backend stopped pushing the new events, polling them again");
+
+                        view.descendants_timeout_task = Some(
+                            self.timeout.spawn(
+                                std::time::Duration::new(5, 0),
+                                self.link
+                                    .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id))),
+                            ),
+                        );
+                    }
+                }
+            }
+            BkResponse::State(view_id, res) => {
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+
+                    self.room_state = match serde_json::to_string_pretty(&res) {
+                        Ok(state) => Some(state),
+                        Err(_) => None,
+                    };
+                }
+            }
+
+            BkResponse::DeepestRqFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::Connect(view_id)),
+                    "Could not retrieve the room's deepest events",
+                    e,
+                );
+
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.deepest_task = None;
+                }
+            }
+            BkResponse::AncestorsRqFailed(view_id, e) => {
+                self.report_failure(None, "Could not retrieve the events' ancestors", e);
+                self.earlier_fetches.remove(&view_id);
+
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.ancestors_task = None;
+                }
+            }
+            BkResponse::SingleEventFailed(view_id, e) => {
+                *self.views[view_id].single_event_task() = None;
+                self.report_failure(None, "Could not fetch the event", e);
+            }
+            BkResponse::DescendantsRqFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::Sync(view_id)),
+                    "Could not retrieve the events' descendants",
+                    e,
+                );
+
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.descendants_task = None;
+                }
+            }
+            BkResponse::LiveEventsFailed(_, e) => {
+                self.report_failure(None, "Could not read the events pushed by the backend", e);
+            }
+            BkResponse::StateRqFailed(view_id, e) => {
+                self.report_failure(None, "Could not fetch the state of the room", e);
+
+                if let View::Postgres(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+                }
+            }
+
+            BkResponse::AdminMessages(view_id, res) => {
+                let mut dag = match &mut self.views[view_id] {
+                    View::Admin(view) => {
+                        view.messages_task = None;
+
+                        let mut session = view.session.write().unwrap();
+                        session.connected = true;
+
+                        model::dag::RoomEvents::from_source_events(
+                            &session.room_id,
+                            &session.server_name,
+                            res,
+                        )
+                    }
+                    View::CS(_) | View::Postgres(_) => return,
+                };
+                self.configure_dag(&mut dag);
+
+                let dag = Arc::new(RwLock::new(dag));
+                self.views[view_id].set_events_dag(Some(dag.clone()));
+
+                if !self.vis.is_active() {
+                    self.vis.init(
+                        "#dag-vis",
+                        "#targeted-view",
+                        "#more-ev-target",
+                        "#selected-event",
+                        "#display-body-target",
+                        "#ancestors-id",
+                        "#ancestors-target",
+                        "#viewport-depths",
+                        "#viewport-target",
+                        "#reveal-target",
+                        "#shared-selection-target",
+                    );
+                }
+
+                self.vis.add_dag(dag, view_id);
+
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    view.new_msg_timeout_task = Some(
+                        self.timeout.spawn(
+                            std::time::Duration::new(5, 0),
+                            self.link
+                                .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id))),
+                        ),
+                    );
+                }
+            }
+            BkResponse::AdminNewMessages(view_id, res) => {
+                self.record_fetch(view_id, ADMIN_FORWARDS_ENDPOINT, &res.events);
+
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    view.new_msg_task = None;
+
+                    match view.events_dag.clone() {
+                        Some(dag) => {
+                            dag.write().unwrap().add_source_events(res);
+
+                            self.vis.update_dag(dag, view_id);
+
+                            if view.session.read().unwrap().connected {
+                                view.new_msg_timeout_task = Some(self.timeout.spawn(
+                                    std::time::Duration::new(5, 0),
+                                    self.link.send_back(move |_: ()| {
+                                        Msg::BkCmd(BkCommand::Sync(view_id))
+                                    }),
+                                ));
+                            }
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+            }
+            BkResponse::AdminState(view_id, res) => {
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+
+                    let object = json!({ "events": res.state });
+
+                    self.room_state = match serde_json::to_string_pretty(&object) {
+                        Ok(state) => Some(state),
+                        Err(_) => None,
+                    };
+                }
+            }
+            BkResponse::EventReports(view_id, res) => {
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    view.reports_task = None;
+
+                    self.room_state = match serde_json::to_string_pretty(&res) {
+                        Ok(reports) => Some(reports),
+                        Err(_) => None,
+                    };
+                }
+            }
+
+            BkResponse::AdminMessagesFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::Connect(view_id)),
+                    "Could not retrieve the room's messages",
+                    e,
+                );
+
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    view.messages_task = None;
+                }
+            }
+            BkResponse::AdminNewMessagesFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::Sync(view_id)),
+                    "Could not retrieve the room's new messages",
+                    e,
+                );
+
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    view.new_msg_task = None;
+                }
+            }
+            BkResponse::EventReportsFailed(view_id, e) => {
+                self.report_failure(
+                    Some(BkCommand::EventReports(view_id)),
+                    "Could not retrieve the event reports",
+                    e,
+                );
+
+                if let View::Admin(view) = &mut self.views[view_id] {
+                    view.reports_task = None;
+                }
+            }
+            BkResponse::HomeserverDiscovered(view_id, cmd, well_known) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.discovery_task = None;
+
+                    let mut session = view.session.write().unwrap();
+                    let base_url = discovered_base_url(&session.server_name, well_known);
+
+                    self.console.log(&format!(
+                        "The CS API of {} is at {}",
+                        session.server_name, base_url
+                    ));
+                    session.base_url = Some(base_url);
+                }
+
+                self.process_bk_command(cmd);
+            }
+            BkResponse::ServerDelegation(server, well_known) => {
+                let host = federation_host(&server, well_known);
+                let callback_server = server.clone();
+
+                let task = self.probe.version(
+                    self.link
+                        .send_back(
+                            move |response: Result<VersionResponse, Error>| match response {
+                                Ok(res) => Msg::BkRes(BkResponse::ServerVersion(
+                                    callback_server.clone(),
+                                    res,
+                                )),
+                                Err(_) => Msg::BkRes(BkResponse::ServerUnreachable(
+                                    callback_server.clone(),
+                                )),
+                            },
+                        ),
+                    &host,
+                );
+
+                self.probe.tasks.insert(server, task);
+            }
+            BkResponse::ServerVersion(server, res) => {
+                self.probe.tasks.remove(&server);
+                self.server_status
+                    .insert(server, ServerStatus::Alive(res.server));
+
+                self.update_dead_servers();
+            }
+            BkResponse::ServerUnreachable(server) => {
+                self.console
+                    .log(&format!("The server {} is unreachable", server));
+
+                self.probe.tasks.remove(&server);
+                self.server_status.insert(server, ServerStatus::Dead);
+
+                self.update_dead_servers();
+            }
+            BkResponse::ServerChecked(name, result) => {
+                if let Err(e) = &result {
+                    self.console
+                        .log(&format!("The server {} is down: {}", name, e));
+                }
+
+                self.server_panel.manager.set_status(&name, result);
+            }
+        }
+    }
+
+    fn display_body(&self) -> Html<Model> {
+        match &self.event_body {
+            Some(body) => {
+                let latency = match self.event_latency {
+                    Some(latency) => html! {
+                        <p>{ format!("Delivery latency: {}", format_latency(latency)) }</p>
+                    },
+                    None => html! { <></> },
+                };
+
+                let batch = match &self.event_batch {
+                    Some((index, batch)) => html! {
+                        <p>{ format!("Fetched in batch {}: {}, requested at {}", index + 1, batch.description(), format_timestamp(batch.requested_at)) }</p>
+                    },
+                    None => html! { <></> },
+                };
+
+                html! {
+                    <>
+                        { latency }
+                        { batch }
+                        { self.display_content_view() }
+                        <pre><code>{ body }</code></pre>
+                    </>
+                }
+            }
+            None => {
+                html! {
+                    <p>{ "No JSON body to show yet" }</p>
+                }
+            }
+        }
+    }
+
+    fn display_content_view(&self) -> Html<Model> {
+        match &self.content_view {
+            Some(ContentView::Membership {
+                target,
+                previous,
+                membership,
+                description,
+                displayname,
+                reason,
+            }) => {
+                let name = match displayname {
+                    Some(displayname) => format!("{} ({})", target, displayname),
+                    None => target.clone(),
+                };
+                let transition =
+                    format!("{} → {}", previous.as_deref().unwrap_or("none"), membership);
+                let reason = match reason {
+                    Some(reason) => html! { <p>{ format!("Reason: {}", reason) }</p> },
+                    None => html! { <></> },
+                };
+
+                html! {
+                    <div class="content-view",>
+                        <p>{ format!("{} {}", name, description) }</p>
+                        <p><code>{ transition }</code></p>
+                        { reason }
+                    </div>
+                }
+            }
+            Some(ContentView::PowerLevels { changes }) => {
+                let value = |value: &Option<JsonValue>| match value {
+                    Some(value) => value.to_string(),
+                    None => "-".to_string(),
+                };
+                let row = |change: &ContentChange| {
+                    html! {
+                        <tr>
+                            <td><code>{ &change.pointer }</code></td>
+                            <td>{ value(&change.old) }</td>
+                            <td>{ value(&change.new) }</td>
+                        </tr>
+                    }
+                };
+
+                if changes.is_empty() {
+                    return html! {
+                        <div class="content-view",><p>{ "No change of the power levels" }</p></div>
+                    };
+                }
+
+                html! {
+                    <div class="content-view",>
+                        <table>
+                            <tr><th>{ "Power level" }</th><th>{ "Before" }</th><th>{ "After" }</th></tr>
+                            { for changes.iter().map(row) }
+                        </table>
+                    </div>
+                }
+            }
+            Some(ContentView::Create {
+                room_version,
+                creator,
+                room_type,
+                predecessor,
+            }) => {
+                let line = |label: &str, value: &Option<String>| match value {
+                    Some(value) => html! { <p>{ format!("{}: {}", label, value) }</p> },
+                    None => html! { <></> },
+                };
+
+                html! {
+                    <div class="content-view",>
+                        <p>{ format!("Room version: {}", room_version) }</p>
+                        { line("Creator", creator) }
+                        { line("Room type", room_type) }
+                        { line("Upgraded from", predecessor) }
+                    </div>
+                }
+            }
+            None => html! { <></> },
+        }
+    }
+
+    fn display_room_state(&self) -> Html<Model> {
+        match &self.room_state {
+            Some(room_state) => {
+                html! {
+                    <pre><code>{ room_state }</code></pre>
+                }
+            }
+            None => {
+                html! {
+                    <p>{ "No room state to show yet" }</p>
+                }
+            }
+        }
+    }
+
+    fn display_minimap(&self) -> Html<Self> {
+        let histogram = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag.read().unwrap().depth_histogram(),
+            None => Vec::new(),
+        };
+
+        let buckets = minimap::depth_buckets(&histogram, 50);
+        let max_count = buckets.iter().map(|b| b.count).max().unwrap_or(1);
+
+        let bar = |b: minimap::DepthBucket| {
+            let (min_depth, max_depth) = (b.min_depth, b.max_depth);
+
+            html! {
+                <div class="minimap-bar",
+                     title=format!("Depths {} to {}: {} events", min_depth, max_depth, b.count),
+                     style=format!("width: {}%;", b.count * 100 / max_count),
+                     onclick=|_| Msg::UICmd(UICommand::JumpToDepths(min_depth, max_depth)),>
+                </div>
+            }
+        };
+
+        html! {
+            <section id="minimap",>
+                { for buckets.into_iter().map(bar) }
+            </section>
+        }
+    }
+
+    fn display_list_view(&self) -> Html<Self> {
+        if !self.list_view.shown {
+            return html! { <></> };
+        }
+
+        let rows = self.list_rows();
+        let range = list_view::visible_rows(rows.len(), self.list_view.scroll);
+        let above = range.start as u32 * list_view::ROW_HEIGHT;
+        let below = (rows.len() - range.end) as u32 * list_view::ROW_HEIGHT;
+
+        let header = |key: SortKey| {
+            let arrow = match (key == self.list_view.sort, self.list_view.descending) {
+                (true, false) => " ▲",
+                (true, true) => " ▼",
+                (false, _) => "",
+            };
+
+            html! {
+                <button onclick=|_| Msg::UI(UIEvent::SortList(key)),>{ format!("{}{}", key.name(), arrow) }</button>
+            }
+        };
+        let row = |row: &EventRow| {
+            let event_id = row.event_id.clone();
+            let class = if self.list_view.focus.as_ref() == Some(&row.event_id) {
+                "event-row focused"
+            } else {
+                "event-row"
+            };
+
+            html! {
+                <div class=class, style=format!("height: {}px;", list_view::ROW_HEIGHT), onclick=|_| Msg::UICmd(UICommand::FocusRow(event_id.clone())),>
+                    <span class="depth",>{ row.depth }</span>
+                    <span class="ts",>{ format_timestamp(row.origin_server_ts) }</span>
+                    <span class="sender",>{ &row.sender }</span>
+                    <span class="type",>{ &row.etype }</span>
+                    <code>{ self.id_shortener.shorten(&row.event_id) }</code>
+                </div>
+            }
+        };
+
+        html! {
+            <section class="list-view",>
+                <p>
+                    { format!("{} events, sorted by ", rows.len()) }
+                    { for SortKey::ALL.iter().cloned().map(header) }
+                </p>
+
+                <div id="event-list", style=format!("height: {}px; overflow-y: scroll;", list_view::VIEWPORT_HEIGHT), onscroll=|_| Msg::UI(UIEvent::ListScrolled),>
+                    <div style=format!("height: {}px;", above),></div>
+                    { for rows[range].iter().map(row) }
+                    <div style=format!("height: {}px;", below),></div>
+                </div>
+            </section>
+        }
+    }
+
+    fn display_text_outline(&self) -> Html<Self> {
+        if !self.text_outline {
+            return html! { <></> };
+        }
+
+        let dag = if self.vis.merge_view() {
+            self.vis.merged_dag()
+        } else {
+            self.views[self.view_idx].get_events_dag().as_ref()
+        };
+        let entries = match dag {
+            Some(dag) => dag.read().unwrap().outline(),
+            None => Vec::new(),
+        };
+
+        // The branches are indented rather than nested, so that each depth stays a single list
+        // the screen readers can count the events of
+        let entry = |entry: &OutlineEntry| {
+            let event_id = entry.event_id.clone();
+
+            html! {
+                <li title=&entry.event_id, style=format!("margin-left: {}em;", 2 * entry.branch),>
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>{ "Show" }</button>
+                    { format!(" {}", entry.description()) }
+                </li>
+            }
+        };
+        let depth = |(depth, entries): (i64, &[OutlineEntry])| {
+            html! {
+                <li>
+                    { format!("Depth {}", depth) }
+                    <ul>
+                        { for entries.iter().map(entry) }
+                    </ul>
+                </li>
+            }
+        };
+
+        html! {
+            <section class="text-outline",>
+                <p>{ format!("Text outline: {} events, from the earliest depth to the latest", entries.len()) }</p>
+                <ul>
+                    { for outline::by_depth(&entries).into_iter().map(depth) }
+                </ul>
+            </section>
+        }
+    }
+
+    fn display_cycle_errors(&self) -> Html<Self> {
+        let id_shortener = self.id_shortener;
+        let cycles: Vec<(ViewIndex, Vec<String>)> = self
+            .views
+            .iter()
+            .filter_map(|view| {
+                let dag = view.get_events_dag().as_ref()?.read().unwrap();
+
+                dag.cycle().map(|cycle| {
+                    let cycle = cycle.iter().map(|id| id_shortener.shorten(id)).collect();
+
+                    (view.get_id(), cycle)
+                })
+            })
+            .collect();
+
+        let banner = |(view_id, cycle): (ViewIndex, Vec<String>)| {
+            html! {
+                <p class="error-banner",>
+                    { format!("The events of the view {} are not a DAG, they contain the cycle {} -> {}. The view isn't displayed.", view_id, cycle.join(" -> "), cycle[0]) }
+                </p>
+            }
+        };
+
+        html! {
+            <>
+                { for cycles.into_iter().map(banner) }
+            </>
+        }
+    }
+
+    fn display_recording(&self) -> Html<Self> {
+        let status = match (&self.recorder.log, self.recorder.playing_back) {
+            (Some(recording), _) => format!("{} responses recorded", recording.responses.len()),
+            (None, true) => "Playing back a recording".to_string(),
+            (None, false) => "Not recording".to_string(),
+        };
+        let toggle = if self.recorder.log.is_some() {
+            "Stop recording"
+        } else {
+            "Record the responses of the backends"
+        };
+        let stopped = !self.recorder.playing_back;
+
+        html! {
+            <details class="recording",>
+                <summary>{ "Record or play back the session" }</summary>
+
+                <p>{ status }</p>
+
+                <button onclick=|_| Msg::UICmd(UICommand::ToggleRecording),>{ toggle }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::DownloadRecording),>{ "Download the recording" }</button>
+
+                <textarea id="recording-json", value=&self.recorder.json, onchange=|e| Msg::UI(UIEvent::RecordingJson(e)),/>
+                <button onclick=|_| Msg::UICmd(UICommand::PlayRecording),>{ "Play back" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::StopPlayback), disabled=stopped,>{ "Stop the playback" }</button>
+            </details>
+        }
+    }
+
+    fn display_health_report(&self) -> Html<Self> {
+        let report = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag.read().unwrap().health_report(),
+            None => return html! { <></> },
+        };
+
+        let id_shortener = self.id_shortener;
+        let entry = |missing: model::dag::MissingEvent| {
+            let referenced_by: Vec<String> = missing
+                .referenced_by
+                .iter()
+                .map(|id| id_shortener.shorten(id))
+                .collect();
+
+            html! {
+                <li title=&missing.id,>
+                    { format!("{} (referenced {} times by: {})", id_shortener.shorten(&missing.id), missing.count, referenced_by.join(", ")) }
+                </li>
+            }
+        };
+
+        html! {
+            <details class="health-report",>
+                <summary>{ format!("Missing events: {}", report.missing_events.len()) }</summary>
+
+                <ul>
+                    { for report.missing_events.into_iter().map(entry) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_interesting_events(&self) -> Html<Self> {
+        let interesting = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag
+                .read()
+                .unwrap()
+                .interesting_events(INTERESTING_EVENTS_MAX),
+            None => return html! { <></> },
+        };
+
+        let id_shortener = self.id_shortener;
+        let entry = |interesting: InterestingEvent| {
+            let event_id = interesting.id.clone();
+
+            html! {
+                <li title=&interesting.id,>
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>
+                        { format!("{} (depth {})", id_shortener.shorten(&interesting.id), interesting.depth) }
+                    </button>
+                    { format!(": {}", interesting.description) }
+                </li>
+            }
+        };
+
+        html! {
+            <details class="interesting-events", open=true,>
+                <summary>{ "Where to start looking" }</summary>
+
+                <ul>
+                    { for interesting.into_iter().map(entry) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_state_timeline(&self) -> Html<Self> {
+        let (etype, state_key) = &self.timeline_state;
+        let timeline = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag.read().unwrap().state_timeline(etype, state_key),
+            None => return html! { <></> },
+        };
+
+        let id_shortener = self.id_shortener;
+        let change = |change: &ContentChange| {
+            html! {
+                <li>{ change.to_string() }</li>
+            }
+        };
+        let entry = |entry: StateTimelineEntry| {
+            let event_id = entry.id.clone();
+
+            html! {
+                <li title=&entry.id,>
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>
+                        { format!("{} (depth {})", id_shortener.shorten(&entry.id), entry.depth) }
+                    </button>
+                    { format!(" by {}", entry.sender) }
+                    { if entry.concurrent { " ⚔ concurrent with the previous one" } else { "" } }
+
+                    <ul>
+                        { for entry.changes.iter().map(change) }
+                    </ul>
+                </li>
+            }
+        };
+
+        html! {
+            <details class="state-timeline",>
+                <summary>{ format!("State timeline: {} events", timeline.len()) }</summary>
+
+                <p>
+                    { "Type: " }<input type="text", id="timeline-type", value=etype, onchange=|e| Msg::UI(UIEvent::TimelineType(e)),/>
+                    { " State key: " }<input type="text", id="timeline-state-key", value=state_key, onchange=|e| Msg::UI(UIEvent::TimelineStateKey(e)),/>
+                </p>
+
+                <ol>
+                    { for timeline.into_iter().map(entry) }
+                </ol>
+            </details>
+        }
+    }
+
+    fn display_room_stats(&self) -> Html<Self> {
+        let stats = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag.read().unwrap().stats(),
+            None => return html! { <></> },
+        };
+
+        let entry = |(key, count): &(String, usize)| {
+            html! {
+                <li>{ format!("{}: {}", key, count) }</li>
+            }
+        };
+
+        html! {
+            <details class="room-stats",>
+                <summary>{ format!("Statistics: {} events", stats.events) }</summary>
+
+                <ul>
+                    <li>{ format!("Forward extremities: {}", stats.forward_extremities) }</li>
+                    <li>{ format!("Backward extremities: {}", stats.backward_extremities) }</li>
+                    <li>{ format!("Events with missing ancestors: {}", stats.orphans) }</li>
+                    <li>{ format!("Branch factor: {:.2} (at most {} children)", stats.branch_factor, stats.max_children) }</li>
+                    <li>{ format!("Estimated memory: {:.1} MiB ({} bytes per event, {} shared strings)", stats.memory as f64 / (1024.0 * 1024.0), stats.memory / stats.events.max(1), stats.interned_strings) }</li>
+                </ul>
+
+                <p>{ format!("Most active senders (out of {}):", stats.per_sender.len()) }</p>
+                <ul>
+                    { for stats.per_sender.iter().take(STATS_TOP_ENTRIES).map(entry) }
+                </ul>
+
+                <p>{ format!("Most active origin servers (out of {}):", stats.per_origin.len()) }</p>
+                <ul>
+                    { for stats.per_origin.iter().take(STATS_TOP_ENTRIES).map(entry) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_depth_anomalies(&self) -> Html<Self> {
+        let anomalies = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag.read().unwrap().depth_anomalies(self.depth_jump),
+            None => return html! { <></> },
+        };
+
+        let id_shortener = self.id_shortener;
+        let entry = |anomaly: &DepthAnomaly| {
+            let event_id = anomaly.id.clone();
+
+            html! {
+                <li title=&anomaly.id,>
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>{ id_shortener.shorten(&anomaly.id) }</button>
+                    { format!(" {}", anomaly.description()) }
+                </li>
+            }
+        };
+
+        html! {
+            <details class="depth-anomalies",>
+                <summary>{ format!("Depth anomalies ({})", anomalies.len()) }</summary>
+
+                <p>
+                    <label for="depth-jump",>{ "Largest depth jump allowed: " }</label>
+                    <input type="number", id="depth-jump", min="1", size="5", value=self.depth_jump.to_string(), onchange=|e| Msg::UI(UIEvent::DepthJump(e)),/>
+                </p>
+
+                <ul>
+                    { for anomalies.iter().map(entry) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_room_version(&self) -> Html<Self> {
+        let (version, mismatches) = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => {
+                let dag = dag.read().unwrap();
+
+                match dag.room_version() {
+                    Some(version) => (version, dag.version_mismatches()),
+                    None => return html! { <></> },
+                }
+            }
+            None => return html! { <></> },
+        };
+        let rules = RoomVersionRules::of(&version);
+
+        let id_shortener = self.id_shortener;
+        let entry = |mismatch: &VersionMismatch| {
+            let event_id = mismatch.id.clone();
+
+            html! {
+                <li title=&mismatch.id,>
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>{ id_shortener.shorten(&mismatch.id) }</button>
+                    { format!(" {}", mismatch.problem) }
+                </li>
+            }
+        };
+
+        html! {
+            <details class="room-version",>
+                <summary>{ format!("Room version {} ({} events not matching it)", version, mismatches.len()) }</summary>
+
+                <ul class="room-version-hints",>
+                    { for rules.hints().iter().map(|hint| html! { <li>{ hint }</li> }) }
+                </ul>
+
+                <ul>
+                    { for mismatches.iter().map(entry) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_read_markers(&self) -> Html<Self> {
+        let events_dag = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag,
+            None => return html! { <></> },
+        };
+        let events_dag = events_dag.read().unwrap();
+        let markers: Vec<(ReadMarker, bool)> = events_dag
+            .read_markers()
+            .iter()
+            .map(|marker| {
+                let in_dag = events_dag.get_event(&marker.event_id).is_some();
+
+                (marker.clone(), in_dag)
+            })
+            .collect();
+
+        if markers.is_empty() {
+            return html! { <></> };
+        }
+
+        let id_shortener = self.id_shortener;
+        let entry = |(marker, in_dag): &(ReadMarker, bool)| {
+            let event_id = marker.event_id.clone();
+
+            html! {
+                <li title=&marker.event_id,>
+                    { format!("The {} points to ", marker.description()) }
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>{ id_shortener.shorten(&marker.event_id) }</button>
+                    { if *in_dag { "" } else { " (not in the DAG yet)" } }
+                </li>
+            }
+        };
+
+        html! {
+            <details class="read-markers",>
+                <summary>{ format!("Read markers ({})", markers.len()) }</summary>
+
+                <ul>
+                    { for markers.iter().map(entry) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_dag_metrics(&self) -> Html<Self> {
+        if self.views[self.view_idx].get_events_dag().is_none() {
+            return html! { <></> };
+        }
+
+        // The metrics of another view aren't shown, they must be computed again
+        let report = match &self.dag_metrics {
+            Some((view_id, metrics)) if *view_id == self.view_idx => metrics.report(),
+            _ => Vec::new(),
+        };
+
+        html! {
+            <details class="dag-metrics",>
+                <summary>{ "Metrics" }</summary>
+
+                <button onclick=|_| Msg::UICmd(UICommand::ComputeMetrics),>{ "Measure the forks and the merges" }</button>
+
+                <ul>
+                    { for report.iter().map(|line| html! { <li>{ line }</li> }) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_layout_options(&self) -> Html<Self> {
+        let layout_options = self.vis.layout_options(self.view_idx);
+
+        let choice = |direction: Direction| {
+            let id = format!("direction-{}", direction.name());
+
+            html! {
+                <li>
+                    <input type="radio", id=&id, name="direction", checked=(layout_options.direction == direction), onclick=|_| Msg::UI(UIEvent::LayoutDirection(direction)),/>
+                    <label for=&id,>{ direction.description() }</label>
+                </li>
+            }
+        };
+
+        let algorithm_choice = |algorithm: LayoutAlgorithm| {
+            let id = format!("algorithm-{}", algorithm.name());
+
+            html! {
+                <li>
+                    <input type="radio", id=&id, name="algorithm", checked=(layout_options.algorithm == algorithm), onclick=|_| Msg::UI(UIEvent::LayoutAlgorithm(algorithm)),/>
+                    <label for=&id,>{ algorithm.description() }</label>
+                </li>
+            }
+        };
+
+        html! {
+            <section class="layout-options",>
+                <p>{ format!("Layout of the view {}:", self.view_idx) }</p>
+
+                <ul>
+                    { for Direction::ALL.iter().cloned().map(choice) }
+                </ul>
+
+                <ul>
+                    { for LayoutAlgorithm::ALL.iter().cloned().map(algorithm_choice) }
+                </ul>
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.joining_room_task = None;
+                <p>
+                    <label for="level-separation",>{ "Distance between the depths: " }</label>
+                    <input type="number", id="level-separation", min="10", size="5", value=layout_options.level_separation.to_string(), onchange=|e| Msg::UI(UIEvent::LevelSeparation(e)),/>
+                    <label for="node-spacing",>{ " Distance between the nodes of a depth: " }</label>
+                    <input type="number", id="node-spacing", min="10", size="5", value=layout_options.node_spacing.to_string(), onchange=|e| Msg::UI(UIEvent::NodeSpacing(e)),/>
+                </p>
 
-                    // Make the initial sync as soon as the user has joined the room
-                    self.link
-                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
-                        .emit(());
-                }
-            }
-            BkResponse::Synced(view_id, res) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.sync_task = None;
+                <p>
+                    { format!("{} cached layouts ", self.vis.cached_layouts()) }
+                    <button onclick=|_| Msg::UICmd(UICommand::ClearLayoutCache),>{ "Forget the cached layouts" }</button>
+                </p>
 
-                    let mut session = view.session.write().unwrap();
-                    let next_batch_token = res.next_batch.clone(); // Save the next batch token to get new events later
+                { self.display_pin() }
+            </section>
+        }
+    }
 
-                    match session.next_batch_token {
-                        None => {
-                            // Initialise the prev batch token on the initial sync
-                            if let Some(room) = res.rooms.join.get(&session.room_id) {
-                                session.prev_batch_token = room.timeline.prev_batch.clone();
-                            }
+    fn display_pin(&self) -> Html<Self> {
+        match self.vis.pin(self.view_idx) {
+            Some(pin) => html! {
+                <p>
+                    { format!("Only the events within {} edges of {} are drawn ", pin.hops, self.id_shortener.shorten(&pin.event_id)) }
+                    <button onclick=|_| Msg::UICmd(UICommand::ExpandPin),>{ "Expand by one ring" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::Unpin),>{ "Draw every event" }</button>
+                </p>
+            },
+            None => html! {
+                <p>
+                    <button onclick=|_| Msg::UICmd(UICommand::PinEvent),>{ "Pin the selected event as the root of the view" }</button>
+                </p>
+            },
+        }
+    }
 
-                            // Create a new DAG if it is the initial sync
-                            if let Some(dag) = model::dag::RoomEvents::from_sync_response(
-                                &session.room_id,
-                                &session.server_name,
-                                &self.fields_choice.fields,
-                                res,
-                            ) {
-                                view.events_dag = Some(Arc::new(RwLock::new(dag)));
-                            }
+    fn display_size_metric_choice(&self) -> Html<Self> {
+        let choices = [
+            (None, "Same size for every nodes"),
+            (Some(SizeMetric::Descendants), "Number of descendants"),
+            (Some(SizeMetric::PrevEvents), "Number of previous events"),
+            (Some(SizeMetric::ContentSize), "Size of the content"),
+            (
+                Some(SizeMetric::CarrierServers),
+                "Number of servers having the event (merge view)",
+            ),
+            (
+                Some(SizeMetric::SenderPowerLevel),
+                "Power level of the sender",
+            ),
+        ];
 
-                            match view.events_dag.clone() {
-                                Some(dag) => {
-                                    // Display the DAG with VisJs if it has been successfully built
-                                    if !self.vis.is_active() {
-                                        self.vis.init(
-                                            "#dag-vis",
-                                            "#targeted-view",
-                                            "#more-ev-target",
-                                            "#selected-event",
-                                            "#display-body-target",
-                                            "#ancestors-id",
-                                            "#ancestors-target",
-                                        );
-                                    }
+        let choice = |(size_metric, label): (Option<SizeMetric>, &str)| {
+            let id = format!("size-{:?}", size_metric);
 
-                                    self.vis.add_dag(dag, view_id);
-                                }
-                                None => self.console.log("Failed to build the DAG"),
-                            }
-                        }
-                        Some(_) => match view.events_dag.clone() {
-                            // Add new events to the DAG
-                            Some(dag) => {
-                                if let Some(room) = res.rooms.join.get(&session.room_id) {
-                                    dag.write()
-                                        .unwrap()
-                                        .add_events(room.timeline.events.clone());
-                                    self.vis.update_dag(dag, view_id);
-                                }
-                            }
-                            None => self.console.log("There is no DAG"),
-                        },
-                    }
+            html! {
+                <li>
+                    <input type="radio", id=&id, name="size-metric", checked=(self.size_metric == size_metric), onclick=|_| Msg::UI(UIEvent::ChooseSizeMetric(size_metric)),/>
+                    <label for=&id,>{ label }</label>
+                </li>
+            }
+        };
 
-                    session.next_batch_token = Some(next_batch_token);
+        html! {
+            <section class="size-metric-choice",>
+                <p>{ "Size of the nodes:" }</p>
 
-                    // Request for futur new events
-                    self.link
-                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
-                        .emit(());
-                }
+                <ul>
+                    { for choices.iter().cloned().map(choice) }
+                </ul>
+            </section>
+        }
+    }
+
+    fn display_theme_choice(&self) -> Html<Self> {
+        let choice = |theme: Theme| {
+            let id = format!("theme-{}", theme.name());
+
+            html! {
+                <li>
+                    <input type="radio", id=&id, name="theme", checked=(self.theme == theme), onclick=|_| Msg::UI(UIEvent::ChooseTheme(theme)),/>
+                    <label for=&id,>{ theme.description() }</label>
+                </li>
             }
-            BkResponse::MsgGot(view_id, res) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.more_msg_task = None;
+        };
 
-                    // Save the prev batch token for the next `/messages` request
-                    view.session.write().unwrap().prev_batch_token = Some(res.end);
+        html! {
+            <section class="theme-choice",>
+                <p>{ "Theme:" }</p>
 
-                    match view.events_dag.clone() {
-                        // Add earlier event to the DAG and display them
-                        Some(dag) => {
-                            dag.write().unwrap().add_events(res.chunk);
+                <ul>
+                    { for Theme::ALL.iter().cloned().map(choice) }
+                </ul>
+            </section>
+        }
+    }
 
-                            self.vis.update_dag(dag, view_id);
-                        }
-                        None => self.console.log("There was no DAG"),
-                    }
-                }
+    fn display_merge_legend(&self) -> Html<Self> {
+        let badges = match self.vis.merged_dag() {
+            Some(dag) => dag.read().unwrap().server_badges(),
+            None => return html! { <></> },
+        };
+
+        let entry = |(server, badge): (String, &'static str)| {
+            html! {
+                <li>{ format!("{} {}", badge, server) }</li>
             }
-            BkResponse::StateFetched(view_id, res) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.state_task = None;
+        };
 
-                    let event_bodies = res.state.clone();
+        html! {
+            <ul class="merge-legend",>
+                { for badges.into_iter().map(entry) }
+            </ul>
+        }
+    }
 
-                    let object = json!({ "events": event_bodies });
+    fn display_conflict_report(&self) -> Html<Self> {
+        let report = match self.vis.merged_dag() {
+            Some(dag) => dag.read().unwrap().conflict_report().clone(),
+            None => return html! { <></> },
+        };
 
-                    self.room_state = match serde_json::to_string_pretty(&object) {
-                        Ok(state) => Some(state),
-                        Err(_) => None,
-                    }
-                }
+        if report.divergent.is_empty() {
+            return html! { <></> };
+        }
+
+        let id_shortener = self.id_shortener;
+        let entry = |copies: model::dag::DivergentCopies| {
+            let event_id = copies.event_id.clone();
+
+            html! {
+                <li title=&copies.event_id,>
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>
+                        { id_shortener.shorten(&copies.event_id) }
+                    </button>
+                    { format!(": {} differ between {} and {}", copies.fields.join(", "), copies.sources.0, copies.sources.1) }
+                </li>
             }
-            BkResponse::RoomLeft(view_id) => {
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.leaving_room_task = None;
+        };
 
-                    self.console.log("Room left!");
+        html! {
+            <details class="conflict-report", open=true,>
+                <summary>{ format!("Divergent copies of events: {}", report.divergent.len()) }</summary>
 
-                    // Disconnect as soon as we leave the room
-                    self.link
-                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Disconnect(view_id)))
-                        .emit(());
-                }
+                <ul>
+                    { for report.divergent.into_iter().map(entry) }
+                </ul>
+            </details>
+        }
+    }
+
+    fn display_clock_skews(&self) -> Html<Self> {
+        let skews = match self.vis.merged_dag() {
+            Some(dag) => dag
+                .read()
+                .unwrap()
+                .clock_skews(DEFAULT_CLOCK_SKEW_THRESHOLD),
+            None => return html! { <></> },
+        };
+
+        if skews.is_empty() {
+            return html! { <></> };
+        }
+
+        let entry = |skew: ClockSkew| {
+            html! {
+                <li>{ skew.description() }</li>
             }
-            BkResponse::Disconnected(view_id) => {
-                match &mut self.views[view_id] {
-                    View::CS(view) => {
-                        self.console.log("Disconnected");
+        };
 
-                        view.sync_task = None; // If a `/sync` request was in progress, cancel it
-                        view.disconnection_task = None;
+        html! {
+            <details class="clock-skews", open=true,>
+                <summary>{ format!("Origin servers with a clock skew: {}", skews.len()) }</summary>
 
-                        let mut session = view.session.write().unwrap();
+                <ul>
+                    { for skews.into_iter().map(entry) }
+                </ul>
+            </details>
+        }
+    }
 
-                        // Erase the current session data so they won't be erroneously used if the user
-                        // logs in again
-                        session.access_token = None;
-                        session.device_id = None;
-                        session.filter_id = None;
-                        session.next_batch_token = None;
-                        session.prev_batch_token = None;
+    fn display_restore_preview(&self) -> Html<Self> {
+        let preview = match &self.pending_restore {
+            Some((_, _, preview)) => preview,
+            None => return html! { <></> },
+        };
 
-                        view.events_dag = None;
-                        self.vis.remove_dag(view_id);
+        html! {
+            <section class="restore-preview",>
+                <p>{ "Restoring the saved DAG would:" }</p>
 
-                        self.event_body = None;
-                        self.room_state = None;
-                    }
-                    View::Postgres(view) => {
-                        self.console.log("Backend stopped");
+                <ul>
+                    <li>{ format!("add {} events and {} edges", preview.new_nodes, preview.new_edges) }</li>
+                    <li>{ format!("resolve {} orphan events", preview.resolved_orphans.len()) }</li>
+                    <li>{ format!("add {} and remove {} latest events", preview.added_latest_events.len(), preview.removed_latest_events.len()) }</li>
+                    <li>{ format!("add {} and remove {} earliest events", preview.added_earliest_events.len(), preview.removed_earliest_events.len()) }</li>
+                </ul>
 
-                        view.stop_task = None;
+                <button onclick=|_| Msg::UICmd(UICommand::ConfirmRestore),>{ "Restore" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::CancelRestore),>{ "Cancel" }</button>
+            </section>
+        }
+    }
 
-                        let mut session = view.session.write().unwrap();
+    fn display_history(&self) -> Html<Self> {
+        let undo = match self.history.next_undo() {
+            Some(description) => format!("Undo: {}", description),
+            None => "Undo".to_string(),
+        };
+        let redo = match self.history.next_redo() {
+            Some(description) => format!("Redo: {}", description),
+            None => "Redo".to_string(),
+        };
 
-                        session.connected = false;
-                        view.descendants_timeout_task = None;
-                        view.events_dag = None;
-                        self.vis.remove_dag(view_id);
+        html! {
+            <section class="history",>
+                <button onclick=|_| Msg::UICmd(UICommand::Undo), disabled=self.history.next_undo().is_none(),>{ undo }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::Redo), disabled=self.history.next_redo().is_none(),>{ redo }</button>
+            </section>
+        }
+    }
 
-                        self.event_body = None;
-                        self.room_state = None;
-                    }
-                }
-            }
+    fn display_time_scrubber(&self) -> Html<Self> {
+        let (min, max) = match self.time_bounds() {
+            Some(bounds) => bounds,
+            None => return html! { <></> },
+        };
+        let (start, end) = self.time_range.unwrap_or((min, max));
 
-            BkResponse::ConnectionFailed(view_id) => {
-                self.console.log("Connection failed");
+        html! {
+            <section class="time-scrubber",>
+                <label for="time-start",>{ "From" }</label>
+                <input type="range", id="time-start", min=min, max=max, step=1000, value=start, onchange=|e| Msg::UI(UIEvent::TimeRangeStart(e)),/>
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.connection_task = None;
-                }
-            }
-            BkResponse::ListingRoomsFailed(view_id) => {
-                self.console.log("Failed to get the list of joined rooms");
+                <label for="time-end",>{ "to" }</label>
+                <input type="range", id="time-end", min=min, max=max, step=1000, value=end, onchange=|e| Msg::UI(UIEvent::TimeRangeEnd(e)),/>
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.listing_rooms_task = None;
-                }
-            }
-            BkResponse::JoiningRoomFailed(view_id) => {
-                self.console.log("Failed to join the room");
+                <span>{ format!("{} - {}", format_timestamp(start), format_timestamp(end)) }</span>
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.joining_room_task = None;
-                }
-            }
-            BkResponse::SyncFailed(view_id) => {
-                self.console.log("Could not sync");
+                <button onclick=|_| Msg::UI(UIEvent::ResetTimeRange), disabled=self.time_range.is_none(),>{ "Show every events" }</button>
+            </section>
+        }
+    }
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.sync_task = None;
+    fn display_replay(&self) -> Html<Self> {
+        let replay = match &self.replay {
+            Some(replay) => replay,
+            None if self.replay_length() == 0 => return html! { <></> },
+            None => {
+                return html! {
+                    <section class="replay",>
+                        <button onclick=|_| Msg::UICmd(UICommand::StartReplay),>{ "Replay the arrival of the events" }</button>
+                        <label for="replay-speed",>{ " Events per second: " }</label>
+                        <input type="number", id="replay-speed", min="1", size="4", value=self.replay_speed.to_string(), onchange=|e| Msg::UI(UIEvent::ReplaySpeed(e)),/>
+                    </section>
                 }
             }
-            BkResponse::MoreMsgFailed(view_id) => {
-                self.console.log("Could not retrieve previous messages");
+        };
+        let toggle = if replay.task.is_some() {
+            "Pause"
+        } else {
+            "Play"
+        };
+        let finished = replay.step == replay.length;
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.more_msg_task = None;
-                }
-            }
-            BkResponse::FetchStateFailed(view_id) => {
-                self.console.log("Could not fetch the state of the room");
+        html! {
+            <section class="replay",>
+                <button onclick=|_| Msg::UICmd(UICommand::ToggleReplay), disabled=finished,>{ toggle }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::StopReplay),>{ "Stop the replay" }</button>
+                <input type="range", id="replay-position", min=0, max=replay.length, step=1, value=replay.step, onchange=|e| Msg::UI(UIEvent::ReplayPosition(e)),/>
+                <span>{ format!("{} / {} events", replay.step, replay.length) }</span>
+                <label for="replay-speed",>{ " Events per second: " }</label>
+                <input type="number", id="replay-speed", min="1", size="4", value=self.replay_speed.to_string(), onchange=|e| Msg::UI(UIEvent::ReplaySpeed(e)),/>
+            </section>
+        }
+    }
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.more_msg_task = None;
+    fn display_origin_servers(&self) -> Html<Self> {
+        let entry = |(server, status): (&String, &ServerStatus)| {
+            let status = match status {
+                ServerStatus::Probing => "probing...".to_string(),
+                ServerStatus::Alive(version) => {
+                    format!("alive ({} {})", version.name, version.version)
                 }
-            }
-            BkResponse::LeavingRoomFailed(view_id) => {
-                self.console.log("Failed to leave the room");
+                ServerStatus::Dead => "unreachable".to_string(),
+            };
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.leaving_room_task = None;
-                }
+            html! {
+                <li>{ format!("{}: {}", server, status) }</li>
             }
-            BkResponse::DisconnectionFailed(view_id) => {
-                self.console.log("Could not disconnect");
+        };
 
-                if let View::CS(view) = &mut self.views[view_id] {
-                    view.disconnection_task = None;
-                }
-            }
+        html! {
+            <details class="origin-servers",>
+                <summary>{ "Origin servers" }</summary>
 
-            BkResponse::DeepestEvents(view_id, res) => {
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.deepest_task = None;
+                <button onclick=|_| Msg::BkCmd(BkCommand::ProbeServers),>{ "Probe the origin servers" }</button>
 
-                    let mut session = view.session.write().unwrap();
-                    session.connected = true;
+                <ul>
+                    { for self.server_status.iter().map(entry) }
+                </ul>
 
-                    view.events_dag = Some(Arc::new(RwLock::new(
-                        model::dag::RoomEvents::from_deepest_events(
-                            &session.room_id,
-                            &session.server_name,
-                            &self.fields_choice.fields,
-                            res,
-                        ),
-                    )));
+                { self.display_outage_simulation() }
+            </details>
+        }
+    }
 
-                    match view.events_dag.clone() {
-                        Some(dag) => {
-                            if !self.vis.is_active() {
-                                self.vis.init(
-                                    "#dag-vis",
-                                    "#targeted-view",
-                                    "#more-ev-target",
-                                    "#selected-event",
-                                    "#display-body-target",
-                                    "#ancestors-id",
-                                    "#ancestors-target",
-                                );
-                            }
+    fn display_outage_simulation(&self) -> Html<Self> {
+        let dag = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag.read().unwrap(),
+            None => return html! { <></> },
+        };
 
-                            self.vis.add_dag(dag, view_id);
-                        }
-                        None => self.console.log("Failed to build the DAG"),
-                    }
+        if let Some(outage) = dag.simulated_outage() {
+            return html! {
+                <p class="outage",>
+                    { format!(
+                        "{} offline after {}: {} events not received, {} created while offline ",
+                        outage.server,
+                        outage.last_event,
+                        outage.missed.len(),
+                        outage.created_offline.len()
+                    ) }
+                    <button onclick=|_| Msg::UI(UIEvent::EndOutageSimulation),>{ "End the simulation" }</button>
+                </p>
+            };
+        }
 
-                    view.descendants_timeout_task = Some(
-                        self.timeout.spawn(
-                            std::time::Duration::new(5, 0),
-                            self.link
-                                .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id))),
-                        ),
-                    );
-                }
+        let mut servers: Vec<String> = dag.origin_servers().into_iter().collect();
+        servers.sort();
+
+        let entry = |server: String| {
+            let label = format!("{} offline after the selected event", server);
+
+            html! {
+                <li>
+                    <button onclick=|_| Msg::UI(UIEvent::SimulateOutage(server.clone())),>{ label }</button>
+                </li>
             }
-            BkResponse::Ancestors(view_id, res) => {
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.ancestors_task = None;
+        };
 
-                    match view.events_dag.clone() {
-                        // Add ancestors to the DAG and display them
-                        Some(dag) => {
-                            dag.write().unwrap().add_events(res.events);
+        html! {
+            <>
+                <p>{ "Simulate an outage:" }</p>
+
+                <ul>
+                    { for servers.into_iter().map(entry) }
+                </ul>
+            </>
+        }
+    }
 
-                            self.vis.update_dag(dag, view_id);
-                        }
-                        None => self.console.log("There was no DAG"),
-                    }
-                }
+    fn display_query(&self) -> Html<Self> {
+        let (hidden, highlighted, collapsed) = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => {
+                let dag = dag.read().unwrap();
+
+                (
+                    dag.hidden_count(),
+                    dag.highlighted_count(),
+                    dag.collapsed_count(),
+                )
             }
-            BkResponse::Descendants(view_id, res) => {
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.descendants_task = None;
+            None => (0, 0, 0),
+        };
 
-                    match view.events_dag.clone() {
-                        Some(dag) => {
-                            dag.write().unwrap().add_events(res.events);
+        html! {
+            <section class="query",>
+                <p>{ "Select the events matching a query:" }</p>
+
+                <p>
+                    <label for="query-sender",>{ "Sender: " }</label>
+                    <input type="text", id="query-sender", placeholder="@alice:example.org", onchange=|e| Msg::UI(UIEvent::QuerySender(e)),/>
+                    <label for="query-type",>{ " Type: " }</label>
+                    <input type="text", id="query-type", placeholder="m.room.*", onchange=|e| Msg::UI(UIEvent::QueryType(e)),/>
+                    <label for="query-origin",>{ " Origin: " }</label>
+                    <input type="text", id="query-origin", placeholder="example.org", onchange=|e| Msg::UI(UIEvent::QueryOrigin(e)),/>
+                    <label for="query-body",>{ " Body contains: " }</label>
+                    <input type="text", id="query-body", onchange=|e| Msg::UI(UIEvent::QueryBody(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::SelectByQuery),>{ "Select" }</button>
+                </p>
+
+                <p>
+                    <label for="query-expression",>{ "Or an expression: " }</label>
+                    <input type="text", id="query-expression", class="expression", placeholder="sender.endsWith(\":bad.server\") && type == \"m.room.member\"", onchange=|e| Msg::UI(UIEvent::QueryExpression(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::SelectByExpression),>{ "Select" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::HideByExpression),>{ "Hide" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::HighlightByExpression),>{ "Highlight" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::CollapseByExpression),>{ "Collapse" }</button>
+                </p>
+
+                <p>
+                    <label for="colour-rule-expression",>{ "Colour the events matching: " }</label>
+                    <input type="text", id="colour-rule-expression", class="expression", value=&self.colour_rule_form.expression, placeholder="content.membership == \"ban\"", onchange=|e| Msg::UI(UIEvent::ColourRuleExpression(e)),/>
+                    <label for="colour-rule-colour",>{ " in " }</label>
+                    <input type="color", id="colour-rule-colour", value=&self.colour_rule_form.colour, onchange=|e| Msg::UI(UIEvent::ColourRuleColour(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::AddColourRule),>{ "Add the rule" }</button>
+                </p>
+
+                { self.display_colour_rules() }
+
+                <p>
+                    { "With the selection: " }
+                    <button onclick=|_| Msg::UICmd(UICommand::HideSelection),>{ "Hide" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::HideBranches),>{ "Hide their branches" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::HighlightSelection),>{ "Highlight" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::CollapseSelection),>{ "Collapse" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::ExportSelection),>{ "Export" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::FindPath),>{ "Find the path between the two events" }</button>
+                </p>
+
+                <p>
+                    <button onclick=|_| Msg::UICmd(UICommand::ShowHiddenEvents),>{ format!("Show the {} hidden events", hidden) }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::ClearHighlights),>{ format!("Clear the {} highlights", highlighted) }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::ExpandCollapsed),>{ format!("Expand the {} collapsed groups", collapsed) }</button>
+                </p>
+            </section>
+        }
+    }
 
-                            self.vis.update_dag(dag, view_id);
+    fn display_colour_rules(&self) -> Html<Self> {
+        if self.colour_rules.is_empty() {
+            return html! { <></> };
+        }
 
-                            if view.session.read().unwrap().connected {
-                                view.descendants_timeout_task = Some(self.timeout.spawn(
-                                    std::time::Duration::new(5, 0),
-                                    self.link.send_back(move |_: ()| {
-                                        Msg::BkCmd(BkCommand::Sync(view_id))
-                                    }),
-                                ));
-                            }
-                        }
-                        None => self.console.log("There was no DAG"),
-                    }
-                }
-            }
-            BkResponse::State(view_id, res) => {
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.state_task = None;
+        html! {
+            <ol class="colour-rules",>
+                { for self.colour_rules.iter().enumerate().map(|(idx, rule)| html! {
+                    <li>
+                        <span class="swatch", style=format!("background: {}", rule.colour),></span>
+                        <code>{ &rule.expression }</code>
+                        <button onclick=|_| Msg::UICmd(UICommand::RemoveColourRule(idx)),>{ "Remove" }</button>
+                    </li>
+                }) }
+            </ol>
+        }
+    }
 
-                    self.room_state = match serde_json::to_string_pretty(&res) {
-                        Ok(state) => Some(state),
-                        Err(_) => None,
-                    };
-                }
-            }
+    fn display_hidden_events(&self) -> Html<Self> {
+        let dag = match self.views[self.view_idx].get_events_dag() {
+            Some(dag) => dag.read().unwrap(),
+            None => return html! { <></> },
+        };
 
-            BkResponse::DeepestRqFailed(view_id) => {
-                self.console
-                    .log("Could not retrieve the room's deepest events");
+        if dag.hidden_items().is_empty() {
+            return html! { <></> };
+        }
 
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.deepest_task = None;
-                }
-            }
-            BkResponse::AncestorsRqFailed(view_id) => {
-                self.console.log("Could not retrieve the events' ancestors");
+        let id_shortener = self.id_shortener;
+        let entry = |item: &HiddenItem| {
+            let root = item.root.clone();
+            let description = if item.events.len() == 1 {
+                String::new()
+            } else {
+                format!(" and {} later events", item.events.len() - 1)
+            };
 
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.ancestors_task = None;
-                }
+            html! {
+                <li title=&item.root,>
+                    { id_shortener.shorten(&item.root) }
+                    { description }
+                    <button onclick=|_| Msg::UICmd(UICommand::RestoreHidden(root.clone())),>{ "Restore" }</button>
+                </li>
             }
-            BkResponse::DescendantsRqFailed(view_id) => {
-                self.console
-                    .log("Could not retrieve the events' descendants");
+        };
 
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.descendants_task = None;
-                }
-            }
-            BkResponse::StateRqFailed(view_id) => {
-                self.console.log("Could not fetch the state of the room");
+        html! {
+            <details class="hidden-events",>
+                <summary>{ format!("Hidden events ({})", dag.hidden_count()) }</summary>
 
-                if let View::Postgres(view) = &mut self.views[view_id] {
-                    view.state_task = None;
-                }
-            }
+                <ul>
+                    { for dag.hidden_items().iter().map(entry) }
+                </ul>
+            </details>
         }
     }
 
-    fn display_body(&self) -> Html<Model> {
-        match &self.event_body {
-            Some(body) => {
-                html! {
-                    <pre><code>{ body }</code></pre>
-                }
-            }
-            None => {
-                html! {
-                    <p>{ "No JSON body to show yet" }</p>
-                }
-            }
+    // Displays the lineages of the rooms of the views through their upgrades, with the number of
+    // events received from each incarnation and the period they cover.
+    fn display_genealogy(&self) -> Html<Self> {
+        let rooms: Vec<RoomIncarnation> = self
+            .views
+            .iter()
+            .filter_map(|view| view.get_events_dag().as_ref())
+            .map(|dag| dag.read().unwrap().incarnation())
+            .collect();
+
+        if rooms.is_empty() {
+            return html! { <></> };
         }
-    }
 
-    fn display_room_state(&self) -> Html<Model> {
-        match &self.room_state {
-            Some(room_state) => {
-                html! {
-                    <pre><code>{ room_state }</code></pre>
+        let date = |ts: i64| {
+            let date = format_timestamp(ts);
+
+            date[..10].to_string()
+        };
+
+        let incarnation = |room: RoomIncarnation| {
+            let room_id = room.room_id.clone();
+            let version = match &room.room_version {
+                Some(version) => format!("v{}", version),
+                None => "unknown version".to_string(),
+            };
+            let events = match room.time_range {
+                Some((start, end)) if room.events > 0 => {
+                    format!("{} events, {} to {}", room.events, date(start), date(end))
                 }
+                _ => "not loaded".to_string(),
+            };
+
+            html! {
+                <li>
+                    { format!("{} ({}, {}) ", room.room_id, version, events) }
+                    <button onclick=|_| Msg::UICmd(UICommand::ObserveRoom(room_id.clone())),>{ "Observe" }</button>
+                </li>
             }
-            None => {
-                html! {
-                    <p>{ "No room state to show yet" }</p>
-                }
+        };
+
+        let lineage = |lineage: Vec<RoomIncarnation>| {
+            html! {
+                <li>
+                    <ol>
+                        { for lineage.into_iter().map(&incarnation) }
+                    </ol>
+                </li>
             }
+        };
+
+        html! {
+            <>
+                <p>{ "Room genealogy (from the oldest incarnation to the newest):" }</p>
+
+                <ul class="genealogy",>
+                    { for genealogy::lineages(&rooms).into_iter().map(lineage) }
+                </ul>
+            </>
         }
     }
 
@@ -1439,6 +7000,7 @@ impl Model {
         let connected = self.views.iter().any(|view| match view {
             View::CS(view) => view.session.read().unwrap().access_token.is_some(),
             View::Postgres(view) => view.session.read().unwrap().connected,
+            View::Admin(view) => view.session.read().unwrap().connected,
         });
 
         if !connected {
@@ -1448,6 +7010,8 @@ impl Model {
                     <label for="cs-bk",>{ "CS backend" }</label>
                     <input type="radio", id="pg-bk", name="bk-type", value="pg-bk", checked=(bk_type == BackendChoice::Postgres), onclick=|_| Msg::UI(UIEvent::ChoosePostgresBackend),/>
                     <label for="pg-bk",>{ "Synapse PostgreSQL backend" }</label>
+                    <input type="radio", id="admin-bk", name="bk-type", value="admin-bk", checked=(bk_type == BackendChoice::Admin), onclick=|_| Msg::UI(UIEvent::ChooseAdminBackend),/>
+                    <label for="admin-bk",>{ "Synapse admin API backend" }</label>
                 </>
             }
         } else {
@@ -1471,10 +7035,332 @@ impl Model {
                 </select>
 
                 <button onclick=|_| Msg::UI(UIEvent::AddView),>{ "Add a view" }</button>
+
+                <button onclick=|_| Msg::UICmd(UICommand::SaveDag),>{ "Save DAG locally" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::RestoreDag),>{ "Restore saved DAG" }</button>
             </>
         }
     }
 
+    fn display_view_summaries(&self) -> Html<Self> {
+        let header = |view: &View| {
+            let summary = match view.get_events_dag() {
+                Some(dag) => dag.read().unwrap().summary(),
+                None => return html! { <></> },
+            };
+            let depths = match summary.depth_range {
+                Some((min_depth, max_depth)) => format!("depths {} to {}", min_depth, max_depth),
+                None => "no depths".to_string(),
+            };
+            let version = match &summary.room_version {
+                Some(version) => format!("version {}", version),
+                None => "unknown version".to_string(),
+            };
+            let class = if view.get_id() == self.view_idx {
+                "view-summary current"
+            } else {
+                "view-summary"
+            };
+            let missing = match &self.shared_selection {
+                Some((event_id, missing)) if missing.contains(&view.get_id()) => html! {
+                    <span class="missing-event",>
+                        { format!(" Missing {}", self.id_shortener.shorten(event_id)) }
+                    </span>
+                },
+                _ => html! { <></> },
+            };
+
+            html! {
+                <p class=class,>
+                    { format!(
+                        "View {}: {} ({}) on {}, {} events, {}, {} forward and {} backward extremities, {} with missing ancestors",
+                        view.get_id() + 1,
+                        summary.room_id,
+                        version,
+                        summary.server_name,
+                        summary.events,
+                        depths,
+                        summary.forward_extremities,
+                        summary.backward_extremities,
+                        summary.orphans,
+                    ) }
+                    { missing }
+                </p>
+            }
+        };
+
+        html! {
+            <section class="view-summaries",>
+                { for self.views.iter().map(header) }
+            </section>
+        }
+    }
+
+    fn display_request_progress(&self) -> Html<Self> {
+        let mut servers: Vec<String> = self
+            .views
+            .iter()
+            .filter(|view| matches!(view, View::CS(_)))
+            .map(|view| view.get_server_name())
+            .filter(|server| !server.is_empty())
+            .collect();
+        servers.sort();
+        servers.dedup();
+
+        let now = web::Date::now();
+        let progress = |server: String| {
+            let done: usize = self
+                .views
+                .iter()
+                .filter_map(|view| match view {
+                    View::CS(cs_view) if view.get_server_name() == server => {
+                        Some(cs_view.backend.completed_requests())
+                    }
+                    _ => None,
+                })
+                .sum();
+            let paused = match self.scheduler.paused_for(&server, now) {
+                Some(delay) => format!(", rate limited for {} s", (delay + 999) / 1000),
+                None => String::new(),
+            };
+
+            html! {
+                <li>
+                    { format!(
+                        "{}: {} queued, {} in flight, {} done{}",
+                        server,
+                        self.scheduler.queued(&server),
+                        self.in_flight(&server),
+                        done,
+                        paused,
+                    ) }
+                </li>
+            }
+        };
+
+        if servers.is_empty() {
+            return html! { <></> };
+        }
+
+        html! {
+            <section class="request-progress",>
+                <p>{ "Requests to the homeservers:" }</p>
+
+                <ul>
+                    { for servers.into_iter().map(progress) }
+                </ul>
+            </section>
+        }
+    }
+
+    fn display_backfill_progress(&self) -> Html<Self> {
+        let backfills: Vec<(ViewIndex, &BackfillProgress)> = self
+            .views
+            .iter()
+            .filter_map(|view| match view {
+                View::CS(cs_view) => cs_view
+                    .backfill
+                    .as_ref()
+                    .map(|progress| (cs_view.id, progress)),
+                _ => None,
+            })
+            .collect();
+
+        if backfills.is_empty() && self.earlier_fetches.is_empty() {
+            return html! { <></> };
+        }
+
+        let entry = |(view_id, progress): (ViewIndex, &BackfillProgress)| {
+            html! {
+                <li>
+                    { format!("Filling the gaps of view {}: ", view_id) }
+                    <progress max=1, value=progress.fraction(),></progress>
+                    { format!(" {} ", progress.description()) }
+                    <button onclick=|_| Msg::UICmd(UICommand::CancelBackfill(view_id)),>{ "Cancel" }</button>
+                </li>
+            }
+        };
+        let earlier_entry = |(view_id, progress): (&ViewIndex, &BackfillProgress)| {
+            let view_id = *view_id;
+
+            html! {
+                <li>
+                    { format!("Loading earlier events in view {}: ", view_id) }
+                    <progress max=1, value=progress.fraction(),></progress>
+                    { format!(" {} ", progress.description()) }
+                    <button onclick=|_| Msg::UICmd(UICommand::CancelEarlierFetch(view_id)),>{ "Cancel" }</button>
+                </li>
+            }
+        };
+
+        html! {
+            <section class="backfill-progress",>
+                <ul>
+                    { for backfills.into_iter().map(entry) }
+                    { for self.earlier_fetches.iter().map(earlier_entry) }
+                </ul>
+            </section>
+        }
+    }
+
+    fn display_server_manager(&self) -> Html<Self> {
+        let entry = |server: &ManagedServer| {
+            let status = match &server.status {
+                ConnectionStatus::Unknown => "not checked yet".to_string(),
+                ConnectionStatus::Checking => "checking...".to_string(),
+                ConnectionStatus::Up(description) => description.clone(),
+                ConnectionStatus::Down(e) => format!("down ({})", e),
+            };
+            let class = match server.status {
+                ConnectionStatus::Down(_) => "server-down",
+                _ => "",
+            };
+            let used = server.config.name.clone();
+            let removed = server.config.name.clone();
+
+            html! {
+                <li class=class,>
+                    { format!("{} ({:?}, {}): {}", server.config.name, server.config.backend, server.config.base_url, status) }
+                    <button onclick=|_| Msg::UICmd(UICommand::UseServer(used.clone())),>{ "Use in the current view" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::RemoveServer(removed.clone())),>{ "Remove" }</button>
+                </li>
+            }
+        };
+        let backend = self.server_panel.form.backend;
+
+        html! {
+            <details class="servers",>
+                <summary>{ format!("Configured servers ({})", self.server_panel.manager.servers().len()) }</summary>
+
+                <ul>
+                    { for self.server_panel.manager.servers().iter().map(entry) }
+                </ul>
+
+                <p>
+                    <input type="text", placeholder="Name", onchange=|e| Msg::UI(UIEvent::ServerFormName(e)),/>
+                    <input type="text", placeholder="Base URL", onchange=|e| Msg::UI(UIEvent::ServerFormUrl(e)),/>
+                    <input type="password", placeholder="Access token (optional)", onchange=|e| Msg::UI(UIEvent::ServerFormToken(e)),/>
+                </p>
+
+                <p>
+                    <input type="radio", id="server-cs", name="server-backend", checked=(backend == BackendChoice::CS), onclick=|_| Msg::UI(UIEvent::ServerFormBackend(BackendChoice::CS)),/>
+                    <label for="server-cs",>{ "CS API" }</label>
+                    <input type="radio", id="server-pg", name="server-backend", checked=(backend == BackendChoice::Postgres), onclick=|_| Msg::UI(UIEvent::ServerFormBackend(BackendChoice::Postgres)),/>
+                    <label for="server-pg",>{ "PostgreSQL backend" }</label>
+                    <input type="radio", id="server-admin", name="server-backend", checked=(backend == BackendChoice::Admin), onclick=|_| Msg::UI(UIEvent::ServerFormBackend(BackendChoice::Admin)),/>
+                    <label for="server-admin",>{ "Admin API" }</label>
+                </p>
+
+                <button onclick=|_| Msg::UICmd(UICommand::AddServer),>{ "Add the server" }</button>
+                <button onclick=|_| Msg::BkCmd(BkCommand::CheckServers),>{ "Check now" }</button>
+            </details>
+        }
+    }
+
+    fn display_toasts(&self) -> Html<Self> {
+        let toast = |(id, message): &(u64, String)| {
+            let id = *id;
+
+            html! {
+                <li class="toast",>
+                    { message }
+                    <button onclick=|_| Msg::UI(UIEvent::DismissToast(id)),>{ "×" }</button>
+                </li>
+            }
+        };
+
+        html! {
+            <ul class="toasts",>
+                { for self.toasts.iter().map(toast) }
+            </ul>
+        }
+    }
+
+    // Displays the rooms joined by the user of the current CS view, filtered by the search, along
+    // with the rooms of the public directory found by the last search, so that the room to observe
+    // can be picked rather than entered by hand.
+    fn display_room_picker(&self) -> Html<Self> {
+        let view = match &self.views[self.view_idx] {
+            View::CS(view)
+                if view.events_dag.is_none()
+                    && view.session.read().unwrap().access_token.is_some() =>
+            {
+                view
+            }
+            _ => return html! { <></> },
+        };
+        let view_id = self.view_idx;
+        let search = self.room_search.to_lowercase();
+
+        let mut joined: Vec<(String, String)> = view
+            .joined_rooms
+            .iter()
+            .map(|room_id| {
+                let name = view
+                    .room_names
+                    .get(room_id)
+                    .cloned()
+                    .unwrap_or_else(|| room_id.clone());
+
+                (room_id.clone(), name)
+            })
+            .filter(|(room_id, name)| {
+                room_id.to_lowercase().contains(&search) || name.to_lowercase().contains(&search)
+            })
+            .collect();
+        joined.sort_by(|a, b| a.1.to_lowercase().cmp(&b.1.to_lowercase()));
+
+        let joined_entry = |(room_id, name): (String, String)| {
+            let label = if name == room_id {
+                room_id.clone()
+            } else {
+                format!("{} ({})", name, room_id)
+            };
+
+            html! {
+                <li>
+                    { label }
+                    <button onclick=|_| Msg::UICmd(UICommand::PickRoom(view_id, room_id.clone())),>{ "Observe" }</button>
+                </li>
+            }
+        };
+
+        let public_entry = |room: &PublicRoom| {
+            let room_id = room.room_id.clone();
+            let name = room
+                .name
+                .clone()
+                .or_else(|| room.canonical_alias.clone())
+                .unwrap_or_else(|| room.room_id.clone());
+            let topic = room.topic.clone().unwrap_or_default();
+
+            html! {
+                <li title=topic,>
+                    { format!("{} ({} members) ", name, room.num_joined_members) }
+                    <button onclick=|_| Msg::UICmd(UICommand::PickRoom(view_id, room_id.clone())),>{ "Join and observe" }</button>
+                </li>
+            }
+        };
+
+        html! {
+            <section class="room-picker",>
+                <p>
+                    { "Rooms: " }
+                    <input type="text", id="room-search", placeholder="Name or ID", value=&self.room_search, onchange=|e| Msg::UI(UIEvent::RoomSearch(e)),/>
+                    <button onclick=|_| Msg::BkCmd(BkCommand::SearchPublicRooms(view_id)),>{ "Search the public rooms directory" }</button>
+                </p>
+
+                <ul class="joined-rooms",>
+                    { for joined.into_iter().map(joined_entry) }
+                </ul>
+
+                <ul class="public-rooms",>
+                    { for view.public_rooms.iter().map(public_entry) }
+                </ul>
+            </section>
+        }
+    }
+
     fn display_interaction_list(&self) -> Html<Self> {
         let view_id = self.view_idx;
 
@@ -1486,15 +7372,30 @@ impl Model {
 
                         <li>{ "Room ID: " }<input type="text", id="room-id-input", onchange=|e| Msg::UI(UIEvent::RoomId(e)),/></li>
 
+                        <li>{ "Events per sync: " }<input type="number", id="timeline-limit-input", min="1", size="4", value=self.timeline_limit.to_string(), onchange=|e| Msg::UI(UIEvent::TimelineLimit(e)),/></li>
+
                         <li>{ "Username: " }<input type="text", id="username-input", onchange=|e| Msg::UI(UIEvent::Username(e)),/></li>
 
                         <li>{ "Password: " }<input type="password", id="password-input", onchange=|e| Msg::UI(UIEvent::Password(e)),/></li>
 
                         <li>
                             <button onclick=|_| Msg::BkCmd(BkCommand::Connect(view_id)),>{ "Connect" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::SsoLogin(view_id)),>{ "Connect with SSO" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::GuestLogin(view_id)),>{ "Connect as a guest" }</button>
                             <button onclick=|_| Msg::BkCmd(BkCommand::Disconnect(view_id)),>{ "Disconnect" }</button>
                             <button onclick=|_| Msg::BkCmd(BkCommand::LeaveRoom(view_id)),>{ "Leave room and disconnect" }</button>
                         </li>
+
+                        <li>
+                            { "Application service token: " }<input type="password", id="as-token-input", onchange=|e| Msg::UI(UIEvent::AsToken(e)),/>
+                            { " acting as: " }<input type="text", id="appservice-user-input", placeholder="@bridge_bot:example.org", onchange=|e| Msg::UI(UIEvent::AppserviceUserId(e)),/>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::AppserviceLogin(view_id)),>{ "Connect as the application service" }</button>
+                        </li>
+
+                        <li>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::FillGaps(view_id)),>{ "Fill gaps" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::StitchPredecessor(view_id)),>{ "Stitch the predecessor room" }</button>
+                        </li>
                     </ul>
                 }
             }
@@ -1509,6 +7410,27 @@ impl Model {
                             <button onclick=|_| Msg::BkCmd(BkCommand::Connect(view_id)),>{ "Start observation" }</button>
                             <button onclick=|_| Msg::BkCmd(BkCommand::Disconnect(view_id)),>{ "Stop observation" }</button>
                         </li>
+
+                        <li>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::StitchPredecessor(view_id)),>{ "Stitch the predecessor room" }</button>
+                        </li>
+                    </ul>
+                }
+            }
+            BackendChoice::Admin => {
+                html! {
+                    <ul>
+                        <li>{ "Server name: " }<input type="text", id="server-name-input", onchange=|e| Msg::UI(UIEvent::ServerName(e)),/></li>
+
+                        <li>{ "Room ID: " }<input type="text", id="room-id-input", onchange=|e| Msg::UI(UIEvent::RoomId(e)),/></li>
+
+                        <li>{ "Admin access token: " }<input type="password", id="access-token-input", onchange=|e| Msg::UI(UIEvent::AccessToken(e)),/></li>
+
+                        <li>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Connect(view_id)),>{ "Start observation" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Disconnect(view_id)),>{ "Stop observation" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::EventReports(view_id)),>{ "Event reports" }</button>
+                        </li>
                     </ul>
                 }
             }
@@ -1518,7 +7440,20 @@ impl Model {
 
 impl Renderable<Model> for Model {
     fn view(&self) -> Html<Self> {
+        let pointer_entry = |pointer: String| {
+            let removed = pointer.clone();
+
+            html! {
+                <li>
+                    { pointer }
+                    <button onclick=|_| Msg::UI(UIEvent::RemovePointer(removed.clone())),>{ "Remove" }</button>
+                </li>
+            }
+        };
+
         html! {
+            { self.display_toasts() }
+
             <section class="backend-choice",>
                 { self.display_backend_choice() }
             </section>
@@ -1527,8 +7462,22 @@ impl Renderable<Model> for Model {
                 { self.display_view_choice() }
             </section>
 
+            { self.display_view_summaries() }
+
+            { self.display_request_progress() }
+
+            { self.display_backfill_progress() }
+
+            { self.display_restore_preview() }
+
             { self.display_interaction_list() }
 
+            { self.display_room_picker() }
+
+            { self.display_server_manager() }
+
+            { self.display_vault() }
+
             <section class="fields-choice",>
                 <p>{ "Event fields to show in the DAG:" }</p>
 
@@ -1577,9 +7526,188 @@ impl Renderable<Model> for Model {
                         <input type="checkbox", id="event-id", name="event-id", checked=self.fields_choice.event_id, onclick=|_| Msg::UI(UIEvent::ToggleEventID),/>
                         <label for="event-id",>{ "Event ID" }</label>
                     </li>
+
+                    <li>
+                        <input type="checkbox", id="unknown-fields", name="unknown-fields", checked=self.fields_choice.unknown_fields, onclick=|_| Msg::UI(UIEvent::ToggleUnknownFields),/>
+                        <label for="unknown-fields",>{ "Unknown fields (e.g. unstable fields from MSCs)" }</label>
+                    </li>
+
+                    { for self.pointer_fields().into_iter().map(pointer_entry) }
+
+                    <li>
+                        <input type="text", id="pointer-input", placeholder="/content/membership", value=&self.fields_choice.pointer, onchange=|e| Msg::UI(UIEvent::Pointer(e)),/>
+                        <button onclick=|_| Msg::UI(UIEvent::AddPointer),>{ "Add a field" }</button>
+                    </li>
                 </ul>
+
+                <p>
+                    { "Shorten the event IDs to the first " }
+                    <input type="number", id="id-first-chars", min="0", size="3", onchange=|e| Msg::UI(UIEvent::IdFirstChars(e)),/>
+                    { " and the last " }
+                    <input type="number", id="id-last-chars", min="0", size="3", onchange=|e| Msg::UI(UIEvent::IdLastChars(e)),/>
+                    { " characters " }
+                    <input type="checkbox", id="hide-domain", name="hide-domain", checked=self.id_shortener.hide_domain, onclick=|_| Msg::UI(UIEvent::ToggleHideDomain),/>
+                    <label for="hide-domain",>{ "Hide the domain" }</label>
+                </p>
+                <p>
+                    { "The text outline and the standalone page use the shortened IDs, the other exports keep the full IDs so that they can be imported back." }
+                </p>
+
+                <p>
+                    { "Truncate the values of the fields to " }
+                    <input type="number", id="label-max-chars", min="1", size="4", onchange=|e| Msg::UI(UIEvent::LabelMaxChars(e)),/>
+                    { " characters " }
+                    <input type="checkbox", id="label-line-breaks", name="label-line-breaks", checked=self.label_format.line_breaks, onclick=|_| Msg::UI(UIEvent::ToggleLabelLineBreaks),/>
+                    <label for="label-line-breaks",>{ "One field per line" }</label>
+                    <input type="checkbox", id="monospace-ids", name="monospace-ids", checked=self.label_format.monospace_ids, onclick=|_| Msg::UI(UIEvent::ToggleMonospaceIds),/>
+                    <label for="monospace-ids",>{ "Monospace event IDs" }</label>
+                </p>
+
+                <p>
+                    { "Suppress the events delivered again within the last " }
+                    <input type="number", id="dedup-window", min="0", size="5", value=self.dedup_window.to_string(), onchange=|e| Msg::UI(UIEvent::DedupWindow(e)),/>
+                    { " deliveries (the events already in the DAG are always suppressed)" }
+                </p>
+
+                <p>
+                    <input type="checkbox", id="sampling", name="sampling", checked=self.sampling, onclick=|_| Msg::UI(UIEvent::ToggleSampling),/>
+                    <label for="sampling",>{ "Sampling mode (only show a sample of the events until a region is selected in the minimap)" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="lazy-rendering", name="lazy-rendering", checked=self.lazy_rendering, onclick=|_| Msg::UI(UIEvent::ToggleLazyRendering),/>
+                    <label for="lazy-rendering",>{ "Lazy rendering (only show the events near the viewport)" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="fog", name="fog", checked=self.fog, onclick=|_| Msg::UI(UIEvent::ToggleFog),/>
+                    <label for="fog",>{ "Frontier fog (show the unknown history below the orphan events)" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="split-layout", name="split-layout", checked=(self.vis.layout() == Layout::Split), onclick=|_| Msg::UI(UIEvent::ToggleSplitLayout),/>
+                    <label for="split-layout",>{ "Display each view in its own network, with synchronised panning and zooming" }</label>
+                </p>
+
+                { self.display_layout_options() }
+
+                <p>
+                    <input type="checkbox", id="list-view", name="list-view", checked=self.list_view.shown, onclick=|_| Msg::UI(UIEvent::ToggleListView),/>
+                    <label for="list-view",>{ "List the events in a table under the network" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="text-outline", name="text-outline", checked=self.text_outline, onclick=|_| Msg::UI(UIEvent::ToggleTextOutline),/>
+                    <label for="text-outline",>{ "Outline the events as text under the network, for screen readers and the search of the browser" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="merge-view", name="merge-view", checked=self.vis.merge_view(), onclick=|_| Msg::UI(UIEvent::ToggleMergeView),/>
+                    <label for="merge-view",>{ "Merge view (display the union of the DAGs of every views)" }</label>
+                </p>
+
+                { self.display_merge_legend() }
+                { self.display_conflict_report() }
+                { self.display_clock_skews() }
+
+                { self.display_size_metric_choice() }
+
+                { self.display_theme_choice() }
+
+                <p>
+                    <input type="checkbox", id="relations", name="relations", checked=self.show_relations, onclick=|_| Msg::UI(UIEvent::ToggleRelations),/>
+                    <label for="relations",>{ "Show the relations between events (threads, edits, reactions and replies)" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="ghosts", name="ghosts", checked=self.show_ghosts, onclick=|_| Msg::UI(UIEvent::ToggleGhosts),/>
+                    <label for="ghosts",>{ "Show the missing ancestors of the events as ghost nodes" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="authority", name="authority", checked=self.show_authority, onclick=|_| Msg::UI(UIEvent::ToggleAuthority),/>
+                    <label for="authority",>{ "Show the membership and the power level of the senders, and highlight the events they shouldn't have been able to send" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="compress-depths", name="compress-depths", checked=self.compress_depths, onclick=|_| Msg::UI(UIEvent::ToggleDepthCompression),/>
+                    <label for="compress-depths",>{ "Compress the depths (remove the gaps between the depths of the events)" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="transitive-reduction", name="transitive-reduction", checked=self.transitive_reduction, onclick=|_| Msg::UI(UIEvent::ToggleTransitiveReduction),/>
+                    <label for="transitive-reduction",>{ "Hide the redundant edges (the edges to the events which are already ancestors through another path)" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="color-by-latency", name="color-by-latency", checked=self.color_by_latency, onclick=|_| Msg::UI(UIEvent::ToggleColorByLatency),/>
+                    <label for="color-by-latency",>{ "Colour the events by their delivery latency (Postgres backend): under 1 s, 10 s, 1 min, or more" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="color-by-batch", name="color-by-batch", checked=self.color_by_batch, onclick=|_| Msg::UI(UIEvent::ToggleColorByBatch),/>
+                    <label for="color-by-batch",>{ "Colour the events by the request which brought them (sync batch or page of earlier events)" }</label>
+                </p>
+
+                <p>
+                    <input type="checkbox", id="read-markers", name="read-markers", checked=self.show_read_markers, onclick=|_| Msg::UI(UIEvent::ToggleReadMarkers),/>
+                    <label for="read-markers",>{ "Show where the read receipts and the fully-read marker of the users point (CS API views)" }</label>
+                </p>
+
+                <details class="preset",>
+                    <summary>{ "Display preset" }</summary>
+
+                    <textarea id="preset-json", value=&self.preset_json, onchange=|e| Msg::UI(UIEvent::PresetJson(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::ExportPreset),>{ "Export" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::ImportPreset),>{ "Import" }</button>
+                </details>
+
+                <details class="export",>
+                    <summary>{ "Export or import the views" }</summary>
+
+                    <textarea id="export-json", value=&self.export_json, onchange=|e| Msg::UI(UIEvent::ExportJson(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::ExportViews),>{ "Export" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::DownloadExport),>{ "Download" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::ImportViews),>{ "Import" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::DownloadJournal),>{ "Download the event journal (JSONL)" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::DownloadOutline),>{ "Download the DAGs as text" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::DownloadStandalone),>{ "Download the view as a standalone page" }</button>
+                </details>
+
+                <details class="room-export",>
+                    <summary>{ "Load a room exported from the database of Synapse" }</summary>
+
+                    <textarea id="room-export-json", placeholder="The rows of the events, as a JSON list or one per line", value=&self.room_export_json, onchange=|e| Msg::UI(UIEvent::RoomExportJson(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::LoadRoomExport),>{ "Load in the current view" }</button>
+                </details>
+
+                { self.display_recording() }
             </section>
 
+            { self.display_cycle_errors() }
+
+            { self.display_interesting_events() }
+
+            { self.display_health_report() }
+
+            { self.display_room_stats() }
+
+            { self.display_depth_anomalies() }
+
+            { self.display_read_markers() }
+
+            { self.display_room_version() }
+
+            { self.display_dag_metrics() }
+
+            { self.display_state_timeline() }
+
+            { self.display_workspace() }
+
+            { self.display_activity_log() }
+
+            { self.display_origin_servers() }
+
             <section class="to-hide",>
                 <input type="text", id="targeted-view",/>
 
@@ -1589,12 +7717,29 @@ impl Renderable<Model> for Model {
 
                 <input type="text", id="ancestors-id",/>
                 <button id="ancestors-target", onclick=|_| Msg::BkCmd(BkCommand::MoreMsg),>{ "Ancestors" }</button>
+
+                <input type="text", id="viewport-depths",/>
+                <button id="viewport-target", onclick=|_| Msg::UICmd(UICommand::ViewportMoved),>{ "Viewport" }</button>
+                <button id="reveal-target", onclick=|_| Msg::BkCmd(BkCommand::RevealRegion),>{ "Reveal" }</button>
+                <button id="shared-selection-target", onclick=|_| Msg::UICmd(UICommand::ShareSelection),>{ "Share selection" }</button>
             </section>
 
+            { self.display_history() }
+
+            { self.display_time_scrubber() }
+
+            { self.display_replay() }
+
             <div class="view",>
+                { self.display_minimap() }
+
                 <section id="dag-vis",>
                 </section>
 
+                { self.display_list_view() }
+
+                { self.display_text_outline() }
+
                 <section id="event-body",>
                 { self.display_body() }
                 </section>
@@ -0,0 +1,36 @@
+use std::ops::Range;
+
+use crate::model::event_table::SortKey;
+
+/// The height of a row of the list view, in pixels. Every row has the same one so that the rows
+/// visible at a given scroll position can be computed without rendering the others.
+pub const ROW_HEIGHT: u32 = 24;
+
+/// The height of the scrollable part of the list view, in pixels.
+pub const VIEWPORT_HEIGHT: u32 = 480;
+
+/// Whether the events are also listed in a table, and how this table is sorted and scrolled.
+#[derive(Default)]
+pub struct ListView {
+    pub shown: bool,
+    pub sort: SortKey,
+    pub descending: bool,
+    pub scroll: u32,           // How far down the list view is scrolled, in pixels
+    pub focus: Option<String>, // The event of the row last clicked or selected in the network
+}
+
+// The number of rows rendered above and below the visible ones, so that scrolling a little
+// doesn't reveal blank space before the next rendering
+const OVERSCAN: usize = 10;
+
+/// Returns the range of the rows to render among `total` ones when the list view is scrolled
+/// `scroll_top` pixels down.
+pub fn visible_rows(total: usize, scroll_top: u32) -> Range<usize> {
+    let first = (scroll_top / ROW_HEIGHT) as usize;
+    let count = (VIEWPORT_HEIGHT / ROW_HEIGHT) as usize + 1;
+
+    let start = first.saturating_sub(OVERSCAN).min(total);
+    let end = (first + count + OVERSCAN).min(total);
+
+    start..end
+}
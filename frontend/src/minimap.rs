@@ -0,0 +1,39 @@
+/// A range of depths of the DAG and the number of events within it, displayed as a bar of the
+/// minimap.
+#[derive(Clone, Copy, Debug)]
+pub struct DepthBucket {
+    pub min_depth: i64,
+    pub max_depth: i64,
+    pub count: usize,
+}
+
+/// Groups the depth `histogram` (as returned by `RoomEvents::depth_histogram`) into at most
+/// `max_buckets` buckets of contiguous depths, from the deepest to the shallowest one.
+pub fn depth_buckets(histogram: &[(i64, usize)], max_buckets: usize) -> Vec<DepthBucket> {
+    let (first, last) = match (histogram.first(), histogram.last()) {
+        (Some(first), Some(last)) => (first.0, last.0),
+        _ => return Vec::new(),
+    };
+
+    let span = (last - first + 1) as usize;
+    let bucket_size = ((span + max_buckets - 1) / max_buckets).max(1) as i64;
+
+    let mut buckets: Vec<DepthBucket> = Vec::new();
+
+    for &(depth, count) in histogram {
+        let min_depth = first + (depth - first) / bucket_size * bucket_size;
+
+        match buckets.last_mut() {
+            Some(bucket) if bucket.min_depth == min_depth => bucket.count += count,
+            _ => buckets.push(DepthBucket {
+                min_depth,
+                max_depth: (min_depth + bucket_size - 1).min(last),
+                count,
+            }),
+        }
+    }
+
+    buckets.reverse();
+
+    buckets
+}
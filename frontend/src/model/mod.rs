@@ -1,2 +0,0 @@
-pub mod dag;
-pub mod event;
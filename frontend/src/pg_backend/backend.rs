@@ -1,28 +1,42 @@
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-use failure::{format_err, Error};
+use failure::Error;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use yew::callback::Callback;
 use yew::format::{Json, Nothing};
 use yew::services::fetch::{FetchService, FetchTask, Request, Response, Uri};
+use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
 
 use super::session::Session;
+use crate::backend_error::BackendError;
+use crate::dag_source::{DagSource, SourceEvents, SourceRequest};
 
 pub struct PostgresBackend {
     fetch: FetchService,
+    ws: WebSocketService,
     session: Arc<RwLock<Session>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct EventsResponse {
     pub events: Vec<JsonValue>,
+    #[serde(default)]
+    pub rejections: HashMap<String, String>, // The reasons of the rejection of the rejected events
+    #[serde(default)]
+    pub soft_failed: Vec<String>, // The IDs of the events which have soft-failed
+    #[serde(default)]
+    pub stream_orderings: HashMap<String, i64>, // The positions of the events in the stream of the server
+    #[serde(default)]
+    pub received_ts: HashMap<String, i64>, // When the server received the events
 }
 
 impl PostgresBackend {
     pub fn with_session(session: Arc<RwLock<Session>>) -> Self {
         PostgresBackend {
             fetch: FetchService::new(),
+            ws: WebSocketService::new(),
             session,
         }
     }
@@ -121,6 +135,84 @@ impl PostgresBackend {
         self.request(callback, uri)
     }
 
+    pub fn event(
+        &mut self,
+        callback: Callback<Result<EventsResponse, Error>>,
+        event_id: &str,
+    ) -> FetchTask {
+        let (server_name, room_id) = {
+            let session = self.session.read().unwrap();
+
+            (session.server_name.clone(), session.room_id.clone())
+        };
+
+        let uri = Uri::builder()
+            .scheme("https")
+            .authority(server_name.as_str())
+            .path_and_query(format!("/visualisations/event/{}?from={}", room_id, event_id).as_str())
+            .build()
+            .expect("Failed to build URI.");
+
+        self.request(callback, uri)
+    }
+
+    /// Opens the WebSocket through which the backend pushes the events of the room as soon as
+    /// they are persisted. `callback` is called with each batch of events and `notification`
+    /// when the WebSocket is opened, closed or fails.
+    pub fn live(
+        &mut self,
+        callback: Callback<Result<EventsResponse, Error>>,
+        notification: Callback<WebSocketStatus>,
+    ) -> WebSocketTask {
+        let (server_name, room_id) = {
+            let session = self.session.read().unwrap();
+
+            (session.server_name.clone(), session.room_id.clone())
+        };
+
+        let url = format!("wss://{}/visualisations/live/{}", server_name, room_id);
+        let handler = Callback::from(move |Json(data): Json<Result<EventsResponse, Error>>| {
+            callback.emit(data)
+        });
+
+        self.ws.connect(&url, handler, notification)
+    }
+
+    /// Retrieves the event `event_id` of the room `room_id`, which the room to observe replaced,
+    /// along with `limit` of its ancestors. The event and its ancestors are requested separately,
+    /// `callback` is called with each response.
+    pub fn predecessor(
+        &mut self,
+        callback: Callback<Result<EventsResponse, Error>>,
+        room_id: &str,
+        event_id: &str,
+        limit: usize,
+    ) -> Vec<FetchTask> {
+        let server_name = self.session.read().unwrap().server_name.clone();
+
+        let paths = vec![
+            format!("/visualisations/event/{}?from={}", room_id, event_id),
+            format!(
+                "/visualisations/ancestors/{}?from={}&limit={}",
+                room_id, event_id, limit
+            ),
+        ];
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let uri = Uri::builder()
+                    .scheme("https")
+                    .authority(server_name.as_str())
+                    .path_and_query(path.as_str())
+                    .build()
+                    .expect("Failed to build URI.");
+
+                self.request(callback.clone(), uri)
+            })
+            .collect()
+    }
+
     pub fn stop(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
         let (server_name, room_id) = {
             let session = self.session.read().unwrap();
@@ -146,10 +238,7 @@ impl PostgresBackend {
             if meta.status.is_success() {
                 callback.emit(Ok(()))
             } else {
-                callback.emit(Err(format_err!(
-                    "{}: error stopping the backend",
-                    meta.status
-                )))
+                callback.emit(Err(BackendError::from_status(meta.status.as_u16()).into()))
             }
         };
 
@@ -172,10 +261,48 @@ impl PostgresBackend {
             if meta.status.is_success() {
                 callback.emit(data)
             } else {
-                callback.emit(Err(format_err!("{}: error fetching events", meta.status)))
+                callback.emit(Err(BackendError::from_status(meta.status.as_u16()).into()))
             }
         };
 
         self.fetch.fetch(request, handler.into())
     }
 }
+
+impl From<EventsResponse> for SourceEvents {
+    fn from(res: EventsResponse) -> Self {
+        SourceEvents {
+            events: res.events,
+            rejections: res.rejections,
+            soft_failed: res.soft_failed,
+            stream_orderings: res.stream_orderings,
+            received_ts: res.received_ts,
+        }
+    }
+}
+
+impl DagSource for PostgresBackend {
+    fn fetch(
+        &mut self,
+        request: &SourceRequest,
+        callback: Callback<Result<SourceEvents, Error>>,
+    ) -> Option<FetchTask> {
+        let callback = source_callback(callback);
+
+        Some(match request {
+            SourceRequest::Initial => self.deepest(callback),
+            SourceRequest::Before(from) => self.ancestors(callback, from),
+            SourceRequest::After(from) => self.descendants(callback, from),
+            SourceRequest::Single(event_id) => self.event(callback, event_id),
+        })
+    }
+}
+
+// Converts the responses of the backend before passing them to the `callback` of the `DagSource`
+fn source_callback(
+    callback: Callback<Result<SourceEvents, Error>>,
+) -> Callback<Result<EventsResponse, Error>> {
+    Callback::from(move |response: Result<EventsResponse, Error>| {
+        callback.emit(response.map(SourceEvents::from))
+    })
+}
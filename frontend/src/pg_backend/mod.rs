@@ -1,2 +1,3 @@
 pub mod backend;
+pub mod room_export;
 pub mod session;
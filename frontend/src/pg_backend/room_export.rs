@@ -0,0 +1,107 @@
+use serde_json::Value as JsonValue;
+
+use super::backend::EventsResponse;
+
+impl EventsResponse {
+    /// Reads the events of a room exported from the database of Synapse, so that the DAG can be
+    /// drawn with the rejections, the soft failures and the stream orderings the Postgres backend
+    /// would give, without any access to the database.
+    ///
+    /// The export is a JSON array of rows, an object with the rows in `events`, or one row per
+    /// line. A row is either the JSON of an event, or a row of the `events` table joined with the
+    /// `event_json` one, with the JSON of the event in `json` and its `internal_metadata`,
+    /// `stream_ordering`, `received_ts` and `rejection_reason` next to it. `json` and
+    /// `internal_metadata` can be strings, as they are stored in the database.
+    pub fn from_room_export(export: &str) -> Result<EventsResponse, String> {
+        let rows = match serde_json::from_str::<JsonValue>(export) {
+            Ok(JsonValue::Array(rows)) => rows,
+            Ok(JsonValue::Object(mut object)) => match object.remove("events") {
+                Some(JsonValue::Array(rows)) => rows,
+                _ => vec![JsonValue::Object(object)],
+            },
+            Ok(_) => return Err("The export is neither a list nor an object".to_string()),
+            Err(_) => export
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| {
+                    serde_json::from_str(line).map_err(|e| format!("Line {}: {}", i + 1, e))
+                })
+                .collect::<Result<Vec<JsonValue>, String>>()?,
+        };
+
+        let mut res = EventsResponse {
+            events: Vec::with_capacity(rows.len()),
+            rejections: Default::default(),
+            soft_failed: Vec::new(),
+            stream_orderings: Default::default(),
+            received_ts: Default::default(),
+        };
+
+        for row in rows {
+            res.add_exported_row(row)?;
+        }
+
+        Ok(res)
+    }
+
+    // Adds the event of the exported row `row` along with its metadata
+    fn add_exported_row(&mut self, row: JsonValue) -> Result<(), String> {
+        let mut event = match &row["json"] {
+            JsonValue::String(json) => serde_json::from_str(json)
+                .map_err(|e| format!("Invalid JSON of the event {}: {}", row["event_id"], e))?,
+            JsonValue::Object(_) => row["json"].clone(),
+            _ => row.clone(),
+        };
+
+        // The events of the room versions 3 and later don't carry their ID, which is derived from
+        // their content if the row doesn't give it either
+        if event["event_id"].is_null() {
+            if let Some(event_id) = row["event_id"].as_str() {
+                event["event_id"] = JsonValue::String(event_id.to_string());
+            }
+        }
+
+        if let Some(event_id) = event["event_id"].as_str().map(str::to_string) {
+            let internal_metadata = match &row["internal_metadata"] {
+                JsonValue::String(metadata) => {
+                    serde_json::from_str(metadata).unwrap_or(JsonValue::Null)
+                }
+                metadata => metadata.clone(),
+            };
+
+            if internal_metadata["soft_failed"] == true {
+                self.soft_failed.push(event_id.clone());
+            }
+
+            if let Some(stream_ordering) = row["stream_ordering"]
+                .as_i64()
+                .or_else(|| internal_metadata["stream_ordering"].as_i64())
+            {
+                self.stream_orderings
+                    .insert(event_id.clone(), stream_ordering);
+            }
+
+            if let Some(received_ts) = row["received_ts"].as_i64() {
+                self.received_ts.insert(event_id.clone(), received_ts);
+            }
+
+            // Older exports join the `rejections` table, whose column is `reason`
+            if let Some(reason) = row["rejection_reason"]
+                .as_str()
+                .or_else(|| row["reason"].as_str())
+            {
+                self.rejections.insert(event_id, reason.to_string());
+            }
+        }
+
+        self.events.push(event);
+
+        Ok(())
+    }
+
+    /// Returns the ID of the room of the exported events, if any of them gives it.
+    pub fn room_id(&self) -> Option<&str> {
+        self.events.iter().find_map(|ev| ev["room_id"].as_str())
+    }
+}
@@ -0,0 +1,221 @@
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use yew::callback::Callback;
+use yew::format::{Nothing, Text};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+use yew::services::timeout::TimeoutTask;
+
+use crate::backend_error::BackendError;
+use crate::BackendChoice;
+
+/// A homeserver, or a Postgres backend, configured once to be used by the views.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerConfig {
+    pub name: String,     // The name under which the server is listed
+    pub base_url: String, // e.g. `https://matrix.example.org`, the scheme being optional
+    #[serde(skip)]
+    pub access_token: String, // Never persisted in plaintext, see the credentials vault
+    pub backend: BackendChoice,
+}
+
+impl ServerConfig {
+    /// Returns the host of the server, which is what the views expect as the server name.
+    pub fn host(&self) -> &str {
+        let url = self.base_url.trim_end_matches('/');
+
+        match url.find("://") {
+            Some(i) => &url[i + 3..],
+            None => url,
+        }
+    }
+
+    // Returns the URL of the endpoint `path`, the scheme defaulting to HTTPS
+    fn url(&self, path: &str) -> String {
+        let url = self.base_url.trim_end_matches('/');
+
+        if url.contains("://") {
+            format!("{}{}", url, path)
+        } else {
+            format!("https://{}{}", url, path)
+        }
+    }
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/versions` request.
+#[derive(Clone, Debug, Deserialize)]
+pub struct VersionsResponse {
+    pub versions: Vec<String>,
+}
+
+/// The outcome of the last health check of a server.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionStatus {
+    Unknown,
+    Checking,
+    Up(String), // With a description of what answered
+    Down(BackendError),
+}
+
+pub struct ManagedServer {
+    pub config: ServerConfig,
+    pub status: ConnectionStatus,
+    check_task: Option<FetchTask>,
+}
+
+/// Keeps the servers configured by the user along with their connection status, which is updated
+/// by periodic health checks: the `/versions` endpoint of the homeservers for the CS and the admin
+/// APIs, and the health endpoint of the Postgres backends, which queries the database.
+pub struct ServerManager {
+    fetch: FetchService,
+    servers: Vec<ManagedServer>,
+}
+
+/// The configured servers, with the values of the inputs of the server being configured.
+pub struct ServerPanel {
+    pub manager: ServerManager,
+    pub form: ServerConfig,               // The server being configured
+    pub health_task: Option<TimeoutTask>, // Checks the health of the configured servers again
+}
+
+impl Default for ServerPanel {
+    fn default() -> Self {
+        ServerPanel {
+            manager: ServerManager::new(),
+            form: ServerConfig {
+                name: String::new(),
+                base_url: String::new(),
+                access_token: String::new(),
+                backend: BackendChoice::CS,
+            },
+            health_task: None,
+        }
+    }
+}
+
+impl ServerManager {
+    pub fn new() -> Self {
+        ServerManager {
+            fetch: FetchService::new(),
+            servers: Vec::new(),
+        }
+    }
+
+    pub fn servers(&self) -> &[ManagedServer] {
+        &self.servers
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ServerConfig> {
+        self.servers
+            .iter()
+            .find(|server| server.config.name == name)
+            .map(|server| &server.config)
+    }
+
+    /// Adds `config` to the servers, replacing the server with the same name.
+    pub fn add(&mut self, config: ServerConfig) {
+        self.remove(&config.name);
+
+        self.servers.push(ManagedServer {
+            config,
+            status: ConnectionStatus::Unknown,
+            check_task: None,
+        });
+    }
+
+    /// Removes the server `name`, and returns whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.servers.len();
+
+        self.servers.retain(|server| server.config.name != name);
+
+        self.servers.len() != len
+    }
+
+    /// Checks the health of every server which isn't being checked already. `callback` is called
+    /// with the name of each server and the outcome of its check, which should then be given to
+    /// `set_status`.
+    pub fn check_all(&mut self, callback: Callback<(String, Result<String, BackendError>)>) {
+        for i in 0..self.servers.len() {
+            if self.servers[i].check_task.is_some() {
+                continue;
+            }
+
+            let config = self.servers[i].config.clone();
+            let task = self.check(&config, callback.clone());
+
+            self.servers[i].check_task = Some(task);
+            self.servers[i].status = ConnectionStatus::Checking;
+        }
+    }
+
+    /// Records the outcome of the health check of the server `name`.
+    pub fn set_status(&mut self, name: &str, result: Result<String, BackendError>) {
+        if let Some(server) = self.servers.iter_mut().find(|s| s.config.name == name) {
+            server.check_task = None;
+            server.status = match result {
+                Ok(description) => ConnectionStatus::Up(description),
+                Err(e) => ConnectionStatus::Down(e),
+            };
+        }
+    }
+
+    /// Serializes the configuration of the servers, without their access tokens.
+    pub fn to_json(&self) -> String {
+        let configs: Vec<&ServerConfig> = self.servers.iter().map(|s| &s.config).collect();
+
+        serde_json::to_string(&configs).expect("Failed to serialize the servers")
+    }
+
+    /// Restores the servers serialized by `to_json`.
+    pub fn load_json(&mut self, json: &str) -> Result<(), serde_json::Error> {
+        let configs: Vec<ServerConfig> = serde_json::from_str(json)?;
+
+        for config in configs {
+            self.add(config);
+        }
+
+        Ok(())
+    }
+
+    fn check(
+        &mut self,
+        config: &ServerConfig,
+        callback: Callback<(String, Result<String, BackendError>)>,
+    ) -> FetchTask {
+        let name = config.name.clone();
+        let backend = config.backend;
+        let uri = match backend {
+            BackendChoice::CS | BackendChoice::Admin => config.url("/_matrix/client/versions"),
+            BackendChoice::Postgres => config.url("/visualisations/health"),
+        };
+
+        let request = Request::get(uri)
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Text>| {
+            let (meta, body) = response.into_parts();
+
+            let result = if !meta.status.is_success() {
+                Err(BackendError::from_status(meta.status.as_u16()))
+            } else {
+                match backend {
+                    BackendChoice::CS | BackendChoice::Admin => body
+                        .and_then(|body| {
+                            serde_json::from_str::<VersionsResponse>(&body).map_err(Error::from)
+                        })
+                        .map(|res| match res.versions.last() {
+                            Some(version) => format!("up, spec {}", version),
+                            None => "up".to_string(),
+                        })
+                        .map_err(BackendError::from_error),
+                    BackendChoice::Postgres => Ok("database up".to_string()),
+                }
+            };
+
+            callback.emit((name.clone(), result));
+        };
+
+        self.fetch.fetch(request, handler.into())
+    }
+}
@@ -0,0 +1,163 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::admin_backend::backend::{EventReportsResponse, RoomStateResponse};
+use crate::cs_backend::backend::{ContextResponse, SyncResponse};
+use crate::model::source::SourceEvents;
+use crate::pg_backend::backend::EventsResponse;
+use crate::{BackendChoice, BkResponse, ViewIndex};
+
+/// A response of a backend carrying events, as recorded in a `SessionLog`. The responses which
+/// only drive the connection, like the logins, aren't recorded, so that no access token ends up
+/// in the recordings, and the syncs are recorded without the private data of the user.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum RecordedResponse {
+    Synced(ViewIndex, SyncResponse),
+    StateFetched(ViewIndex, ContextResponse),
+    GapFetched(ViewIndex, ContextResponse),
+    PredecessorEvents(ViewIndex, SourceEvents),
+    DeepestEvents(ViewIndex, SourceEvents),
+    EventsBefore(ViewIndex, SourceEvents),
+    Descendants(ViewIndex, SourceEvents),
+    State(ViewIndex, EventsResponse),
+    LiveEvents(ViewIndex, SourceEvents),
+    AdminMessages(ViewIndex, SourceEvents),
+    AdminNewMessages(ViewIndex, SourceEvents),
+    AdminState(ViewIndex, RoomStateResponse),
+    EventReports(ViewIndex, EventReportsResponse),
+}
+
+impl RecordedResponse {
+    /// Returns the recording of `res`, or `None` if it isn't recorded.
+    pub fn from_response(res: &BkResponse) -> Option<RecordedResponse> {
+        let recorded = match res {
+            BkResponse::Synced(id, res) => {
+                RecordedResponse::Synced(*id, res.without_private_data())
+            }
+            BkResponse::StateFetched(id, res) => RecordedResponse::StateFetched(*id, res.clone()),
+            BkResponse::GapFetched(id, res) => RecordedResponse::GapFetched(*id, res.clone()),
+            BkResponse::PredecessorEvents(id, res) => {
+                RecordedResponse::PredecessorEvents(*id, res.clone())
+            }
+            BkResponse::DeepestEvents(id, res) => RecordedResponse::DeepestEvents(*id, res.clone()),
+            BkResponse::EventsBefore(id, res) => RecordedResponse::EventsBefore(*id, res.clone()),
+            BkResponse::Descendants(id, res) => RecordedResponse::Descendants(*id, res.clone()),
+            BkResponse::State(id, res) => RecordedResponse::State(*id, res.clone()),
+            BkResponse::LiveEvents(id, res) => RecordedResponse::LiveEvents(*id, res.clone()),
+            BkResponse::AdminMessages(id, res) => RecordedResponse::AdminMessages(*id, res.clone()),
+            BkResponse::AdminNewMessages(id, res) => {
+                RecordedResponse::AdminNewMessages(*id, res.clone())
+            }
+            BkResponse::AdminState(id, res) => RecordedResponse::AdminState(*id, res.clone()),
+            BkResponse::EventReports(id, res) => RecordedResponse::EventReports(*id, res.clone()),
+            _ => return None,
+        };
+
+        Some(recorded)
+    }
+
+    /// Returns the response to give to the model to replay this one.
+    pub fn into_response(self) -> BkResponse {
+        match self {
+            RecordedResponse::Synced(id, res) => BkResponse::Synced(id, res),
+            RecordedResponse::StateFetched(id, res) => BkResponse::StateFetched(id, res),
+            RecordedResponse::GapFetched(id, res) => BkResponse::GapFetched(id, res),
+            RecordedResponse::PredecessorEvents(id, res) => BkResponse::PredecessorEvents(id, res),
+            RecordedResponse::DeepestEvents(id, res) => BkResponse::DeepestEvents(id, res),
+            RecordedResponse::EventsBefore(id, res) => BkResponse::EventsBefore(id, res),
+            RecordedResponse::Descendants(id, res) => BkResponse::Descendants(id, res),
+            RecordedResponse::State(id, res) => BkResponse::State(id, res),
+            RecordedResponse::LiveEvents(id, res) => BkResponse::LiveEvents(id, res),
+            RecordedResponse::AdminMessages(id, res) => BkResponse::AdminMessages(id, res),
+            RecordedResponse::AdminNewMessages(id, res) => BkResponse::AdminNewMessages(id, res),
+            RecordedResponse::AdminState(id, res) => BkResponse::AdminState(id, res),
+            RecordedResponse::EventReports(id, res) => BkResponse::EventReports(id, res),
+        }
+    }
+
+    pub fn view_id(&self) -> ViewIndex {
+        match self {
+            RecordedResponse::Synced(id, _)
+            | RecordedResponse::StateFetched(id, _)
+            | RecordedResponse::GapFetched(id, _)
+            | RecordedResponse::PredecessorEvents(id, _)
+            | RecordedResponse::DeepestEvents(id, _)
+            | RecordedResponse::EventsBefore(id, _)
+            | RecordedResponse::Descendants(id, _)
+            | RecordedResponse::State(id, _)
+            | RecordedResponse::LiveEvents(id, _)
+            | RecordedResponse::AdminMessages(id, _)
+            | RecordedResponse::AdminNewMessages(id, _)
+            | RecordedResponse::AdminState(id, _)
+            | RecordedResponse::EventReports(id, _) => *id,
+        }
+    }
+}
+
+/// The room observed by a view during a recording.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedView {
+    pub server_name: String,
+    pub room_id: String,
+}
+
+/// The responses of the backends received during a session, in the order in which they arrived,
+/// along with the views which made the requests. Replaying them through the model rebuilds the
+/// same DAGs without any access to the network, so that a broken layout can be reproduced from
+/// the recording attached to a bug report.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionLog {
+    pub backend: BackendChoice,
+    pub views: Vec<RecordedView>,
+    pub responses: Vec<RecordedResponse>,
+}
+
+/// The recording of the session in progress, if any, with its JSON as displayed to the user.
+#[derive(Default)]
+pub struct Recorder {
+    pub log: Option<SessionLog>, // The responses of the backends recorded so far, if recording
+    pub json: String,
+    pub playing_back: bool, // Whether a recording is played back, the commands to the backends being dropped
+}
+
+impl SessionLog {
+    pub fn new(backend: BackendChoice) -> Self {
+        SessionLog {
+            backend,
+            views: Vec::new(),
+            responses: Vec::new(),
+        }
+    }
+
+    /// Records `res` if it carries events.
+    pub fn record(&mut self, res: &BkResponse) {
+        if let Some(recorded) = RecordedResponse::from_response(res) {
+            self.responses.push(recorded);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Failed to serialize the recording")
+    }
+
+    /// Parses a recording serialized by `to_json`, checking that it has views and that its
+    /// responses were received by them.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let log: SessionLog = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+        if log.views.is_empty() {
+            return Err("the recording has no view".to_string());
+        }
+
+        match log
+            .responses
+            .iter()
+            .find(|res| res.view_id() >= log.views.len())
+        {
+            Some(res) => Err(format!(
+                "a response was received by the view {}, which isn't recorded",
+                res.view_id()
+            )),
+            None => Ok(log),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use stdweb::Once;
+use yew::callback::Callback;
+
+// The names of the IndexedDB database and of its object store where the DAGs are saved
+const DB_NAME: &str = "matrix-visualisations";
+const STORE_NAME: &str = "dags";
+
+/// This service saves data in the IndexedDB database of the browser, so that it is still
+/// available when the application is reopened.
+pub struct StorageService {}
+
+impl StorageService {
+    pub fn new() -> Self {
+        StorageService {}
+    }
+
+    /// Saves `value` under the key `key`, replacing any previous value.
+    pub fn store(&self, key: &str, value: String) {
+        js! {
+            var key = @{key};
+            var value = @{value};
+
+            var request = indexedDB.open(@{DB_NAME}, 1);
+
+            request.onupgradeneeded = function (ev) {
+                ev.target.result.createObjectStore(@{STORE_NAME});
+            };
+            request.onsuccess = function (ev) {
+                var db = ev.target.result;
+                var tx = db.transaction(@{STORE_NAME}, "readwrite");
+
+                tx.objectStore(@{STORE_NAME}).put(value, key);
+                tx.onerror = function () {
+                    console.log("Failed to save " + key);
+                };
+            };
+            request.onerror = function () {
+                console.log("Failed to open the IndexedDB database");
+            };
+        };
+    }
+
+    /// Loads the value saved under the key `key` and then calls `callback` with it, or with
+    /// `None` if there is no such value.
+    pub fn load(&self, key: &str, callback: Callback<Option<String>>) {
+        let callback = move |value: Option<String>| callback.emit(value);
+
+        js! {
+            var key = @{key};
+            var callback = @{Once(callback)};
+
+            var request = indexedDB.open(@{DB_NAME}, 1);
+
+            request.onupgradeneeded = function (ev) {
+                ev.target.result.createObjectStore(@{STORE_NAME});
+            };
+            request.onsuccess = function (ev) {
+                var db = ev.target.result;
+                var get = db.transaction(@{STORE_NAME}, "readonly")
+                    .objectStore(@{STORE_NAME})
+                    .get(key);
+
+                get.onsuccess = function () {
+                    callback(get.result === undefined ? null : get.result);
+                };
+                get.onerror = function () {
+                    callback(null);
+                };
+            };
+            request.onerror = function () {
+                callback(null);
+            };
+        };
+    }
+}
@@ -0,0 +1,99 @@
+use crate::backend_error::BackendError;
+use crate::cs_backend::backend::RefreshResponse;
+use crate::views::View;
+use crate::{BkCommand, Model, Msg, ViewIndex};
+
+impl Model {
+    // Refreshes the access token of the CS view `view_id` if `error` tells that it has expired and
+    // the view has a refresh token, and then runs `cmd` again. Returns whether it did.
+    pub(crate) fn refresh_and_resume(
+        &mut self,
+        view_id: ViewIndex,
+        cmd: BkCommand,
+        error: &BackendError,
+    ) -> bool {
+        let view = match (&mut self.views[view_id], error) {
+            (View::CS(view), BackendError::TokenExpired) => view,
+            _ => return false,
+        };
+
+        if view.session.read().unwrap().refresh_token.is_none() {
+            return false;
+        }
+
+        if !view.resumed_after_refresh.contains(&cmd) {
+            view.resumed_after_refresh.push(cmd);
+        }
+
+        self.console
+            .log("The access token has expired, refreshing it before retrying");
+        self.link
+            .send_back(move |_: ()| Msg::BkCmd(BkCommand::RefreshToken(view_id)))
+            .emit(());
+
+        true
+    }
+
+    // Refreshes the access token of the CS view `view_id`, or forgets it if the view has no
+    // refresh token.
+    pub(crate) fn refresh_token(&mut self, view_id: ViewIndex) {
+        if let View::CS(view) = &mut self.views[view_id] {
+            if view.session.read().unwrap().refresh_token.is_none() {
+                view.resumed_after_refresh.clear();
+                view.session.write().unwrap().access_token = None;
+                self.console
+                    .log("The access token has expired, you have to connect again");
+            } else if view.refresh_task.is_none() {
+                view.refresh_task = Some(view.backend.refresh(view.refresh_callback.clone()));
+            }
+        }
+    }
+
+    // Uses the new access token of the CS view `view_id`, and runs again the commands which were
+    // rejected because the previous one had expired.
+    pub(crate) fn token_refreshed(&mut self, view_id: ViewIndex, res: RefreshResponse) {
+        if let View::CS(view) = &mut self.views[view_id] {
+            view.refresh_task = None;
+
+            {
+                let mut session = view.session.write().unwrap();
+
+                session.access_token = Some(res.access_token);
+
+                // The homeserver may rotate the refresh token as well
+                if res.refresh_token.is_some() {
+                    session.refresh_token = res.refresh_token;
+                }
+            }
+
+            self.console.log("Access token refreshed");
+
+            // Run again the requests rejected because the token had expired, e.g. to
+            // resume the synchronisation where it stopped
+            for cmd in view.resumed_after_refresh.drain(..) {
+                self.link.send_back(move |_: ()| Msg::BkCmd(cmd)).emit(());
+            }
+        }
+    }
+
+    // Forgets the tokens of the CS view `view_id` once its access token can't be refreshed, the
+    // user having to connect again.
+    pub(crate) fn refresh_failed(&mut self, view_id: ViewIndex, e: BackendError) {
+        let retried = self.report_failure(
+            Some(BkCommand::RefreshToken(view_id)),
+            "Could not refresh the access token, you have to connect again",
+            e,
+        );
+
+        if let View::CS(view) = &mut self.views[view_id] {
+            view.refresh_task = None;
+
+            if !retried {
+                let mut session = view.session.write().unwrap();
+                session.access_token = None;
+                session.refresh_token = None;
+                view.resumed_after_refresh.clear();
+            }
+        }
+    }
+}
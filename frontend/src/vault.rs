@@ -0,0 +1,256 @@
+use serde_derive::{Deserialize, Serialize};
+use stdweb::Once;
+use stdweb::Value;
+use yew::callback::Callback;
+
+// The number of iterations of PBKDF2 used to derive the encryption key from the passphrase
+const PBKDF2_ITERATIONS: u32 = 250_000;
+
+/// The API a set of credentials gives access to.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CredentialsKind {
+    ClientServer,
+    Admin,
+}
+
+/// The credentials allowing the application to access a server without asking the user to log
+/// in again.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerCredentials {
+    pub kind: CredentialsKind,
+    pub server_name: String,
+    pub username: String, // Empty for the admin API, whose tokens are entered directly
+    pub user_id: String,
+    pub access_token: String,
+    pub device_id: Option<String>,
+}
+
+/// The content of the vault, once it has been unlocked.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Credentials {
+    pub entries: Vec<ServerCredentials>,
+}
+
+impl Credentials {
+    /// Returns the credentials of `username` on `server_name` for the API `kind`.
+    pub fn find(
+        &self,
+        kind: CredentialsKind,
+        server_name: &str,
+        username: &str,
+    ) -> Option<&ServerCredentials> {
+        self.entries.iter().find(|entry| {
+            entry.kind == kind && entry.server_name == server_name && entry.username == username
+        })
+    }
+
+    /// Adds `credentials` to the vault, replacing the previous credentials of the same user on
+    /// the same server.
+    pub fn insert(&mut self, credentials: ServerCredentials) {
+        self.entries.retain(|entry| {
+            entry.kind != credentials.kind
+                || entry.server_name != credentials.server_name
+                || entry.username != credentials.username
+        });
+
+        self.entries.push(credentials);
+    }
+}
+
+/// Overwrites the bytes of `passphrase` before emptying it, so that it doesn't stay in the memory
+/// once the key has been derived from it.
+pub fn forget_passphrase(passphrase: &mut String) {
+    let mut bytes = std::mem::take(passphrase).into_bytes();
+
+    for byte in bytes.iter_mut() {
+        // Volatile writes so that the zeroing isn't optimised away as the bytes are dropped
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+}
+
+/// This service encrypts the credentials with a key derived from a passphrase of the user, with
+/// the WebCrypto API of the browser, so that they are never persisted in plaintext.
+///
+/// The key is derived with PBKDF2 (SHA-256) from the passphrase and a random salt, and the
+/// credentials are encrypted with AES-GCM. The sealed vault is a JSON object containing the
+/// salt, the IV and the ciphertext encoded in base64, which can then be saved with the
+/// `StorageService`.
+///
+/// Once the vault is unlocked, only the derived key is kept (as a non-extractable `CryptoKey`)
+/// to seal it again, the encoded passphrase being zeroed as soon as the key is derived.
+pub struct VaultService {
+    crypto: Value,   // The JS helpers shared by the sealing and the unsealing
+    unlocked: Value, // The key of the unlocked vault and its salt, both `null` while it is locked
+}
+
+impl VaultService {
+    pub fn new() -> Self {
+        let crypto = js! {
+            function to_base64(bytes) {
+                var array = new Uint8Array(bytes);
+                var binary = "";
+
+                // By chunks, as the engines limit the number of arguments of a call
+                for (var i = 0; i < array.length; i += 0x8000) {
+                    binary += String.fromCharCode.apply(null, array.subarray(i, i + 0x8000));
+                }
+
+                return btoa(binary);
+            }
+
+            function from_base64(text) {
+                return Uint8Array.from(atob(text), function (c) { return c.charCodeAt(0); });
+            }
+
+            function derive_key(passphrase, salt) {
+                var encoded = new TextEncoder().encode(passphrase);
+
+                return crypto.subtle
+                    .importKey("raw", encoded, "PBKDF2", false, ["deriveKey"])
+                    .then(
+                        function (base) {
+                            encoded.fill(0);
+
+                            return crypto.subtle.deriveKey(
+                                {
+                                    name: "PBKDF2",
+                                    salt: salt,
+                                    iterations: @{PBKDF2_ITERATIONS},
+                                    hash: "SHA-256"
+                                },
+                                base,
+                                { name: "AES-GCM", length: 256 },
+                                false,
+                                ["encrypt", "decrypt"]
+                            );
+                        },
+                        function (e) {
+                            encoded.fill(0);
+                            throw e;
+                        }
+                    );
+            }
+
+            return {
+                to_base64: to_base64,
+                from_base64: from_base64,
+                derive_key: derive_key
+            };
+        };
+        let unlocked = js! {
+            return { key: null, salt: null };
+        };
+
+        VaultService { crypto, unlocked }
+    }
+
+    /// Derives the key of a new, empty vault from `passphrase` and then calls `callback` with
+    /// whether it succeeded, the vault being unlocked if it did.
+    pub fn create(&self, passphrase: &str, callback: Callback<bool>) {
+        let callback = move |created: bool| callback.emit(created);
+
+        js! {
+            var helpers = @{&self.crypto};
+            var unlocked = @{&self.unlocked};
+            var callback = @{Once(callback)};
+
+            var salt = crypto.getRandomValues(new Uint8Array(16));
+
+            helpers.derive_key(@{passphrase}, salt)
+                .then(function (key) {
+                    unlocked.key = key;
+                    unlocked.salt = salt;
+                    callback(true);
+                })
+                .catch(function () {
+                    callback(false);
+                });
+        };
+    }
+
+    /// Encrypts `credentials` with the key of the unlocked vault and then calls `callback` with
+    /// the sealed vault, or with `None` if the vault is locked or the encryption failed.
+    pub fn seal(&self, credentials: &Credentials, callback: Callback<Option<String>>) {
+        let plaintext = serde_json::to_string(credentials).expect("Failed to serialize the vault");
+        let callback = move |sealed: Option<String>| callback.emit(sealed);
+
+        js! {
+            var helpers = @{&self.crypto};
+            var unlocked = @{&self.unlocked};
+            var plaintext = @{plaintext};
+            var callback = @{Once(callback)};
+
+            if (unlocked.key === null) {
+                callback(null);
+            } else {
+                var iv = crypto.getRandomValues(new Uint8Array(12));
+                var encoded = new TextEncoder().encode(plaintext);
+
+                crypto.subtle.encrypt({ name: "AES-GCM", iv: iv }, unlocked.key, encoded)
+                    .then(function (ciphertext) {
+                        callback(JSON.stringify({
+                            salt: helpers.to_base64(unlocked.salt),
+                            iv: helpers.to_base64(iv),
+                            ciphertext: helpers.to_base64(ciphertext)
+                        }));
+                    })
+                    .catch(function () {
+                        callback(null);
+                    });
+            }
+        };
+    }
+
+    /// Decrypts the vault `sealed` with `passphrase` and then calls `callback` with its content,
+    /// or with `None` if the passphrase is wrong or the vault is corrupted. The key is kept to
+    /// seal the vault again only if the decryption succeeded.
+    pub fn unseal(
+        &self,
+        passphrase: &str,
+        sealed: String,
+        callback: Callback<Option<Credentials>>,
+    ) {
+        let callback = move |plaintext: Option<String>| {
+            callback.emit(plaintext.and_then(|json| serde_json::from_str(&json).ok()))
+        };
+
+        js! {
+            var helpers = @{&self.crypto};
+            var unlocked = @{&self.unlocked};
+            var callback = @{Once(callback)};
+
+            try {
+                var sealed = JSON.parse(@{sealed});
+                var salt = helpers.from_base64(sealed.salt);
+                var iv = helpers.from_base64(sealed.iv);
+                var ciphertext = helpers.from_base64(sealed.ciphertext);
+
+                helpers.derive_key(@{passphrase}, salt)
+                    .then(function (key) {
+                        return crypto.subtle
+                            .decrypt({ name: "AES-GCM", iv: iv }, key, ciphertext)
+                            .then(function (plaintext) {
+                                unlocked.key = key;
+                                unlocked.salt = salt;
+                                callback(new TextDecoder().decode(plaintext));
+                            });
+                    })
+                    .catch(function () {
+                        callback(null);
+                    });
+            } catch (e) {
+                callback(null);
+            }
+        };
+    }
+
+    /// Forgets the key of the vault, which has to be unlocked with the passphrase again.
+    pub fn lock(&self) {
+        js! {
+            var unlocked = @{&self.unlocked};
+
+            unlocked.key = null;
+            unlocked.salt = null;
+        };
+    }
+}
@@ -0,0 +1,212 @@
+use stdweb::unstable::TryInto;
+use stdweb::web;
+use stdweb::web::IParentNode;
+use yew::{html, Html};
+
+use crate::vault::{forget_passphrase, Credentials, ServerCredentials, VaultService};
+use crate::{Model, Msg, UICommand, UIEvent};
+
+// The key under which the sealed vault is saved with the `StorageService`
+const VAULT_KEY: &str = "vault";
+
+/// The credentials vault, with the passphrase typed by the user to unlock or create it.
+pub struct VaultPanel {
+    pub service: VaultService,
+    pub passphrase: String,
+    pub pending: bool, // There is no vault yet, waiting for the user's confirmation to create one with the passphrase
+    pub credentials: Option<Credentials>, // The content of the vault, if it is unlocked
+}
+
+impl Default for VaultPanel {
+    fn default() -> Self {
+        VaultPanel {
+            service: VaultService::new(),
+            passphrase: String::new(),
+            pending: false,
+            credentials: None,
+        }
+    }
+}
+
+impl Model {
+    // Loads the sealed vault, to unseal it with the passphrase typed by the user.
+    pub(crate) fn unlock_vault(&mut self) {
+        self.storage.load(
+            VAULT_KEY,
+            self.link.send_back(move |sealed: Option<String>| {
+                Msg::UICmd(UICommand::VaultLoaded(sealed))
+            }),
+        );
+    }
+
+    // Unseals the vault `sealed` which was loaded, or asks the user to confirm the creation of a
+    // new one if there is none yet.
+    pub(crate) fn vault_loaded(&mut self, sealed: Option<String>) {
+        match sealed {
+            Some(sealed) => {
+                self.vault.service.unseal(
+                    &self.vault.passphrase,
+                    sealed,
+                    self.link
+                        .send_back(move |credentials: Option<Credentials>| {
+                            Msg::UICmd(UICommand::VaultUnlocked(credentials))
+                        }),
+                );
+                self.forget_vault_passphrase();
+            }
+            // There is no vault yet, the user has to confirm that a new one should be created
+            // with this passphrase, which may have been mistyped
+            None => self.vault.pending = true,
+        }
+    }
+
+    // Keeps the `credentials` of the vault which was unsealed, if the passphrase was right.
+    pub(crate) fn vault_unlocked(&mut self, credentials: Option<Credentials>) {
+        match credentials {
+            Some(credentials) => {
+                self.log_activity(format!(
+                    "Unlocked the credentials vault ({} servers)",
+                    credentials.entries.len()
+                ));
+                self.vault.credentials = Some(credentials);
+            }
+            None => self
+                .console
+                .log("Couldn't unlock the vault, the passphrase may be wrong"),
+        }
+    }
+
+    // Creates a new vault with the passphrase typed by the user, once they confirmed it.
+    pub(crate) fn create_vault(&mut self) {
+        if self.vault.pending {
+            self.vault.pending = false;
+            self.vault.service.create(
+                &self.vault.passphrase,
+                self.link
+                    .send_back(move |created: bool| Msg::UICmd(UICommand::VaultCreated(created))),
+            );
+            self.forget_vault_passphrase();
+        }
+    }
+
+    // Gives up the creation of a new vault.
+    pub(crate) fn cancel_vault(&mut self) {
+        self.vault.pending = false;
+        self.forget_vault_passphrase();
+    }
+
+    // Opens the new vault, if it could be created.
+    pub(crate) fn vault_created(&mut self, created: bool) {
+        if created {
+            self.vault.credentials = Some(Credentials::default());
+            self.log_activity("Opened a new credentials vault".to_string());
+        } else {
+            self.console.log("Couldn't create the vault");
+        }
+    }
+
+    // Puts the credentials of every view in the vault, and seals it to save it.
+    pub(crate) fn save_credentials(&mut self) {
+        match &mut self.vault.credentials {
+            Some(credentials) => {
+                for view in &self.views {
+                    if let Some(saved) = view.credentials() {
+                        credentials.insert(saved);
+                    }
+                }
+
+                self.vault.service.seal(
+                    credentials,
+                    self.link.send_back(move |sealed: Option<String>| {
+                        Msg::UICmd(UICommand::VaultSealed(sealed))
+                    }),
+                );
+            }
+            None => self.console.log("The vault is locked"),
+        }
+    }
+
+    // Saves the vault `sealed` with its new content, if it could be encrypted.
+    pub(crate) fn vault_sealed(&mut self, sealed: Option<String>) {
+        match sealed {
+            Some(sealed) => {
+                self.storage.store(VAULT_KEY, sealed);
+                self.log_activity("Saved the credentials in the vault".to_string());
+            }
+            None => self.console.log("Couldn't encrypt the vault"),
+        }
+    }
+
+    // Forgets the content of the vault and its key.
+    pub(crate) fn lock_vault(&mut self) {
+        self.vault.credentials = None;
+        self.vault.service.lock();
+        self.log_activity("Locked the credentials vault".to_string());
+    }
+
+    // Zeroes the passphrase of the vault once the key has been derived from it, and empties its
+    // input so that it can't be read back from the page.
+    fn forget_vault_passphrase(&mut self) {
+        forget_passphrase(&mut self.vault.passphrase);
+
+        if let Some(input) = web::document()
+            .query_selector("#vault-passphrase-input")
+            .ok()
+            .and_then(|input| input)
+        {
+            let input: web::html_element::InputElement = input.try_into().unwrap();
+            input.set_raw_value("");
+        }
+    }
+
+    pub(crate) fn display_vault(&self) -> Html<Self> {
+        match &self.vault.credentials {
+            Some(credentials) => {
+                let entry = |saved: &ServerCredentials| {
+                    html! {
+                        <li>{ format!("{:?}: {} {}", saved.kind, saved.server_name, saved.user_id) }</li>
+                    }
+                };
+
+                html! {
+                    <details class="vault",>
+                        <summary>{ "Credentials vault (unlocked)" }</summary>
+
+                        <ul>
+                            { for credentials.entries.iter().map(entry) }
+                        </ul>
+
+                        <button onclick=|_| Msg::UICmd(UICommand::SaveCredentials),>{ "Save the credentials of the views" }</button>
+                        <button onclick=|_| Msg::UICmd(UICommand::LockVault),>{ "Lock" }</button>
+                    </details>
+                }
+            }
+            None => {
+                html! {
+                    <details class="vault",>
+                        <summary>{ "Credentials vault (locked)" }</summary>
+
+                        <input id="vault-passphrase-input", type="password", placeholder="Passphrase", onchange=|e| Msg::UI(UIEvent::VaultPassphrase(e)),/>
+                        <button onclick=|_| Msg::UICmd(UICommand::UnlockVault),>{ "Unlock" }</button>
+
+                        { self.display_vault_confirmation() }
+                    </details>
+                }
+            }
+        }
+    }
+
+    pub(crate) fn display_vault_confirmation(&self) -> Html<Self> {
+        if !self.vault.pending {
+            return html! { <></> };
+        }
+
+        html! {
+            <p>
+                { "There is no credentials vault yet. Create a new one with this passphrase?" }
+                <button onclick=|_| Msg::UICmd(UICommand::CreateVault),>{ "Create" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::CancelVault),>{ "Cancel" }</button>
+            </p>
+        }
+    }
+}
@@ -0,0 +1,765 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use failure::Error;
+use yew::services::fetch::FetchTask;
+use yew::services::timeout::TimeoutTask;
+use yew::services::websocket::{WebSocketStatus, WebSocketTask};
+use yew::{Callback, ComponentLink};
+
+use crate::admin_backend::backend::{AdminBackend, EventReportsResponse, RoomStateResponse};
+use crate::admin_backend::session::Session as AdminSession;
+use crate::backend_error::BackendError;
+use crate::cs_backend::backend::{
+    CSBackend, ConnectionResponse, ContextResponse, FilterResponse, JoinedRooms, PublicRoom,
+    PublicRoomsResponse, RefreshResponse, RoomNames, SyncResponse,
+};
+use crate::cs_backend::session::Session as CSSession;
+use crate::dag_source::{DagSource, SourceEvents, SourceRequest};
+use crate::model::dag::RoomEvents;
+use crate::model::progress::BackfillProgress;
+use crate::pg_backend::backend::{EventsResponse, PostgresBackend};
+use crate::pg_backend::session::Session as PgSession;
+use crate::vault::{CredentialsKind, ServerCredentials};
+use crate::{BackendChoice, BkCommand, BkResponse, Model, Msg, ViewIndex};
+
+pub enum View {
+    CS(CSView),
+    Postgres(PgView),
+    Admin(AdminView),
+}
+
+impl View {
+    pub fn new(bk_type: BackendChoice, id: ViewIndex, link: &mut ComponentLink<Model>) -> View {
+        match bk_type {
+            BackendChoice::CS => View::CS(CSView::new(id, link)),
+            BackendChoice::Postgres => View::Postgres(PgView::new(id, link)),
+            BackendChoice::Admin => View::Admin(AdminView::new(id, link)),
+        }
+    }
+
+    pub fn get_id(&self) -> ViewIndex {
+        match self {
+            View::CS(v) => v.id,
+            View::Postgres(v) => v.id,
+            View::Admin(v) => v.id,
+        }
+    }
+
+    pub fn get_events_dag(&self) -> &Option<Arc<RwLock<RoomEvents>>> {
+        match self {
+            View::CS(v) => &v.events_dag,
+            View::Postgres(v) => &v.events_dag,
+            View::Admin(v) => &v.events_dag,
+        }
+    }
+
+    /// Returns the source the DAG of the view is fetched from, through which the requests which
+    /// don't depend on the kind of the view are made.
+    pub fn dag_source(&mut self) -> &mut dyn DagSource {
+        match self {
+            View::CS(v) => &mut v.backend,
+            View::Postgres(v) => &mut v.backend,
+            View::Admin(v) => &mut v.backend,
+        }
+    }
+
+    /// Returns the source of the DAG of the view along with the slot of the task of `request` and
+    /// the callback receiving its events. The CS views sync rather than fetch their initial and
+    /// later events, since the syncs also bring the read receipts of the room, and the single
+    /// events have slots of their own.
+    pub fn dag_fetch(
+        &mut self,
+        request: &SourceRequest,
+    ) -> Option<(
+        &mut dyn DagSource,
+        &mut Option<FetchTask>,
+        Callback<Result<SourceEvents, Error>>,
+    )> {
+        match (self, request) {
+            (View::CS(v), SourceRequest::Before(_)) => Some((
+                &mut v.backend,
+                &mut v.more_msg_task,
+                v.more_msg_callback.clone(),
+            )),
+            (View::CS(_), SourceRequest::Initial) | (View::CS(_), SourceRequest::After(_)) => None,
+            (View::Postgres(v), SourceRequest::Initial) => Some((
+                &mut v.backend,
+                &mut v.deepest_task,
+                v.deepest_callback.clone(),
+            )),
+            (View::Postgres(v), SourceRequest::Before(_)) => Some((
+                &mut v.backend,
+                &mut v.ancestors_task,
+                v.ancestors_callback.clone(),
+            )),
+            (View::Postgres(v), SourceRequest::After(_)) => Some((
+                &mut v.backend,
+                &mut v.descendants_task,
+                v.descendants_callback.clone(),
+            )),
+            (View::Admin(v), SourceRequest::Initial) => Some((
+                &mut v.backend,
+                &mut v.messages_task,
+                v.messages_callback.clone(),
+            )),
+            (View::Admin(v), SourceRequest::Before(_)) => Some((
+                &mut v.backend,
+                &mut v.more_msg_task,
+                v.more_msg_callback.clone(),
+            )),
+            (View::Admin(v), SourceRequest::After(_)) => Some((
+                &mut v.backend,
+                &mut v.new_msg_task,
+                v.new_msg_callback.clone(),
+            )),
+            (_, SourceRequest::Single(_)) => None,
+        }
+    }
+
+    /// Returns the lowest depth of the DAG of the view, if it has events.
+    pub fn reached_depth(&self) -> Option<i64> {
+        self.get_events_dag()
+            .as_ref()
+            .and_then(|dag| dag.read().unwrap().depth_bounds())
+            .map(|(min_depth, _)| min_depth)
+    }
+
+    /// Returns the slot of the task fetching the event jumped to which wasn't in the DAG of the
+    /// view.
+    pub fn single_event_task(&mut self) -> &mut Option<FetchTask> {
+        match self {
+            View::CS(v) => &mut v.single_event_task,
+            View::Postgres(v) => &mut v.single_event_task,
+            View::Admin(v) => &mut v.single_event_task,
+        }
+    }
+
+    pub fn set_events_dag(&mut self, events_dag: Option<Arc<RwLock<RoomEvents>>>) {
+        match self {
+            View::CS(v) => v.events_dag = events_dag,
+            View::Postgres(v) => v.events_dag = events_dag,
+            View::Admin(v) => v.events_dag = events_dag,
+        }
+    }
+
+    pub fn get_server_name(&self) -> String {
+        match self {
+            View::CS(v) => v.session.read().unwrap().server_name.clone(),
+            View::Postgres(v) => v.session.read().unwrap().server_name.clone(),
+            View::Admin(v) => v.session.read().unwrap().server_name.clone(),
+        }
+    }
+
+    /// Returns the credentials of the view which can be saved in the vault, if it has some. The
+    /// Postgres backend has none, since its database is configured on the backend side.
+    pub fn credentials(&self) -> Option<ServerCredentials> {
+        match self {
+            View::CS(v) => {
+                let session = v.session.read().unwrap();
+
+                session
+                    .access_token
+                    .as_ref()
+                    .map(|access_token| ServerCredentials {
+                        kind: CredentialsKind::ClientServer,
+                        server_name: session.server_name.clone(),
+                        username: session.username.clone(),
+                        user_id: session.user_id.clone(),
+                        access_token: access_token.clone(),
+                        device_id: session.device_id.clone(),
+                    })
+            }
+            View::Postgres(_) => None,
+            View::Admin(v) => {
+                let session = v.session.read().unwrap();
+
+                if session.access_token.is_empty() {
+                    None
+                } else {
+                    Some(ServerCredentials {
+                        kind: CredentialsKind::Admin,
+                        server_name: session.server_name.clone(),
+                        username: String::new(),
+                        user_id: String::new(),
+                        access_token: session.access_token.clone(),
+                        device_id: None,
+                    })
+                }
+            }
+        }
+    }
+
+    pub fn set_server_name(&self, server_name: String) {
+        match self {
+            View::CS(v) => {
+                let mut session = v.session.write().unwrap();
+
+                // The base URL of another server name has to be discovered again
+                session.server_name = server_name;
+                session.base_url = None;
+            }
+            View::Postgres(v) => v.session.write().unwrap().server_name = server_name,
+            View::Admin(v) => v.session.write().unwrap().server_name = server_name,
+        }
+    }
+
+    pub fn get_room_id(&self) -> String {
+        match self {
+            View::CS(v) => v.session.read().unwrap().room_id.clone(),
+            View::Postgres(v) => v.session.read().unwrap().room_id.clone(),
+            View::Admin(v) => v.session.read().unwrap().room_id.clone(),
+        }
+    }
+
+    pub fn set_room_id(&self, room_id: String) {
+        match self {
+            View::CS(v) => v.session.write().unwrap().room_id = room_id,
+            View::Postgres(v) => v.session.write().unwrap().room_id = room_id,
+            View::Admin(v) => v.session.write().unwrap().room_id = room_id,
+        }
+    }
+}
+
+// This contains every informations needed for the observation of a room from a given HS by using
+// the CS API.
+pub struct CSView {
+    pub id: ViewIndex,
+
+    pub connection_callback: Callback<Result<ConnectionResponse, Error>>,
+    pub connection_task: Option<FetchTask>,
+    pub discovery_task: Option<FetchTask>, // The `.well-known` discovery of the base URL of the homeserver
+
+    pub listing_rooms_callback: Callback<Result<JoinedRooms, Error>>,
+    pub listing_rooms_task: Option<FetchTask>,
+    pub joined_rooms: Vec<String>, // The rooms joined by the user, offered by the room picker
+
+    pub room_names_callback: Callback<Result<RoomNames, Error>>,
+    pub room_names_task: Option<FetchTask>,
+    pub room_names: RoomNames,
+
+    pub public_rooms_callback: Callback<Result<PublicRoomsResponse, Error>>,
+    pub public_rooms_task: Option<FetchTask>,
+    pub public_rooms: Vec<PublicRoom>, // The results of the last search of the public rooms directory
+
+    pub joining_room_callback: Callback<Result<(), Error>>,
+    pub joining_room_task: Option<FetchTask>,
+
+    pub filter_callback: Callback<Result<FilterResponse, Error>>,
+    pub filter_task: Option<FetchTask>,
+
+    pub sync_callback: Callback<Result<SyncResponse, Error>>,
+    pub sync_task: Option<FetchTask>,
+
+    pub refresh_callback: Callback<Result<RefreshResponse, Error>>,
+    pub refresh_task: Option<FetchTask>,
+    pub resumed_after_refresh: Vec<BkCommand>, // The commands whose access token had expired, run again once it is refreshed
+
+    pub more_msg_callback: Callback<Result<SourceEvents, Error>>,
+    pub more_msg_task: Option<FetchTask>,
+
+    pub state_callback: Callback<Result<ContextResponse, Error>>,
+    pub state_task: Option<FetchTask>,
+
+    pub gap_callback: Callback<Result<ContextResponse, Error>>,
+    pub gap_task: Option<FetchTask>,
+    pub gap_attempted: HashSet<String>, // IDs of the missing events which were already requested
+    pub gap_requested: Option<String>, // The ID of the missing event whose context is being fetched
+    pub gap_failures: HashMap<String, BackendError>, // The missing events whose request failed, with the error
+    pub filling_gaps: bool, // Whether every gaps are being filled, rather than a single region being revealed
+    pub backfill: Option<BackfillProgress>, // The progress of the filling of every gap, while it runs
+
+    pub predecessor_callback: Callback<Result<ContextResponse, Error>>,
+    pub predecessor_task: Option<FetchTask>,
+
+    pub room_version_callback: Callback<Result<String, Error>>,
+    pub room_version_task: Option<FetchTask>,
+
+    pub leaving_room_callback: Callback<Result<(), Error>>,
+    pub leaving_room_task: Option<FetchTask>,
+
+    pub disconnection_callback: Callback<Result<(), Error>>,
+    pub disconnection_task: Option<FetchTask>,
+
+    pub single_event_task: Option<FetchTask>, // The fetch of the event jumped to which wasn't in the DAG
+
+    pub session: Arc<RwLock<CSSession>>,
+    pub backend: CSBackend,
+    pub events_dag: Option<Arc<RwLock<RoomEvents>>>,
+}
+
+impl CSView {
+    /// Returns the number of missing events which are still to be requested to fill the gaps of
+    /// the DAG.
+    pub fn gaps_left(&self) -> usize {
+        match &self.events_dag {
+            Some(dag) => dag
+                .read()
+                .unwrap()
+                .backfill_targets(usize::MAX)
+                .iter()
+                .filter(|id| !self.gap_attempted.contains(*id))
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Returns the lowest depth of the DAG, if it has events.
+    pub fn reached_depth(&self) -> Option<i64> {
+        self.events_dag
+            .as_ref()
+            .and_then(|dag| dag.read().unwrap().depth_bounds())
+            .map(|(min_depth, _)| min_depth)
+    }
+
+    /// Returns the number of requests being made by the view to the homeserver, the long polling
+    /// of `/sync` included.
+    pub fn in_flight(&self) -> usize {
+        [
+            &self.connection_task,
+            &self.listing_rooms_task,
+            &self.room_names_task,
+            &self.public_rooms_task,
+            &self.joining_room_task,
+            &self.filter_task,
+            &self.sync_task,
+            &self.refresh_task,
+            &self.more_msg_task,
+            &self.state_task,
+            &self.gap_task,
+            &self.predecessor_task,
+            &self.room_version_task,
+            &self.leaving_room_task,
+            &self.disconnection_task,
+        ]
+        .iter()
+        .filter(|task| task.is_some())
+        .count()
+    }
+
+    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> CSView {
+        let session = Arc::new(RwLock::new(CSSession::empty()));
+
+        CSView {
+            id,
+
+            connection_callback: link.send_back(
+                move |response: Result<ConnectionResponse, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Connected(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::ConnectionFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            ),
+            connection_task: None,
+            discovery_task: None,
+
+            listing_rooms_callback: link.send_back(move |response: Result<JoinedRooms, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::RoomsList(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::ListingRoomsFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            listing_rooms_task: None,
+            joined_rooms: Vec::new(),
+
+            room_names_callback: link.send_back(move |response: Result<RoomNames, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::RoomNamesResolved(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::ResolvingRoomNamesFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            room_names_task: None,
+            room_names: RoomNames::new(),
+
+            public_rooms_callback: link.send_back(
+                move |response: Result<PublicRoomsResponse, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::PublicRoomsFound(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::SearchingPublicRoomsFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            ),
+            public_rooms_task: None,
+            public_rooms: Vec::new(),
+
+            joining_room_callback: link.send_back(
+                move |response: Result<(), Error>| match response {
+                    Ok(_) => Msg::BkRes(BkResponse::RoomJoined(id)),
+                    Err(e) => Msg::BkRes(BkResponse::JoiningRoomFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            ),
+            joining_room_task: None,
+
+            filter_callback: link.send_back(move |response: Result<FilterResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::FilterCreated(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::CreatingFilterFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            filter_task: None,
+
+            sync_callback: link.send_back(move |response: Result<SyncResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Synced(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::SyncFailed(id, BackendError::from_error(e))),
+                }
+            }),
+            sync_task: None,
+
+            refresh_callback: link.send_back(move |response: Result<RefreshResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::TokenRefreshed(id, res)),
+                    Err(e) => {
+                        Msg::BkRes(BkResponse::RefreshFailed(id, BackendError::from_error(e)))
+                    }
+                }
+            }),
+            refresh_task: None,
+            resumed_after_refresh: Vec::new(),
+
+            more_msg_callback: link.send_back(move |response: Result<SourceEvents, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::EventsBefore(id, res)),
+                    Err(e) => {
+                        Msg::BkRes(BkResponse::MoreMsgFailed(id, BackendError::from_error(e)))
+                    }
+                }
+            }),
+            more_msg_task: None,
+
+            state_callback: link.send_back(move |response: Result<ContextResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::StateFetched(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::FetchStateFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            state_task: None,
+
+            gap_callback: link.send_back(move |response: Result<ContextResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::GapFetched(id, res)),
+                    Err(e) => {
+                        Msg::BkRes(BkResponse::FillGapsFailed(id, BackendError::from_error(e)))
+                    }
+                }
+            }),
+            gap_task: None,
+            gap_attempted: HashSet::new(),
+            gap_requested: None,
+            gap_failures: HashMap::new(),
+            filling_gaps: false,
+            backfill: None,
+
+            predecessor_callback: link.send_back(
+                move |response: Result<ContextResponse, Error>| match response {
+                    Ok(res) => {
+                        let mut events = res.events_before;
+                        events.push(res.event);
+                        events.extend(res.events_after);
+
+                        Msg::BkRes(BkResponse::PredecessorEvents(
+                            id,
+                            SourceEvents::from_events(events),
+                        ))
+                    }
+                    Err(e) => Msg::BkRes(BkResponse::PredecessorFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            ),
+            predecessor_task: None,
+
+            room_version_callback: link.send_back(move |response: Result<String, Error>| {
+                match response {
+                    Ok(version) => Msg::BkRes(BkResponse::RoomVersionFetched(id, version)),
+                    Err(e) => Msg::BkRes(BkResponse::FetchingRoomVersionFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            room_version_task: None,
+
+            leaving_room_callback: link.send_back(
+                move |response: Result<(), Error>| match response {
+                    Ok(_) => Msg::BkRes(BkResponse::RoomLeft(id)),
+                    Err(e) => Msg::BkRes(BkResponse::LeavingRoomFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            ),
+            leaving_room_task: None,
+
+            disconnection_callback: link.send_back(
+                move |response: Result<(), Error>| match response {
+                    Ok(_) => Msg::BkRes(BkResponse::Disconnected(id)),
+                    Err(e) => Msg::BkRes(BkResponse::DisconnectionFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            ),
+            disconnection_task: None,
+
+            single_event_task: None,
+
+            session: session.clone(),
+            backend: CSBackend::with_session(session),
+            events_dag: None,
+        }
+    }
+}
+
+// This contains every informations needed for the observation of a room from a given HS by using
+// the PostgreSQL backend.
+pub struct PgView {
+    pub id: ViewIndex,
+
+    pub deepest_callback: Callback<Result<SourceEvents, Error>>,
+    pub deepest_task: Option<FetchTask>,
+
+    pub ancestors_callback: Callback<Result<SourceEvents, Error>>,
+    pub ancestors_task: Option<FetchTask>,
+
+    pub stop_callback: Callback<Result<(), Error>>,
+    pub stop_task: Option<FetchTask>,
+
+    pub descendants_callback: Callback<Result<SourceEvents, Error>>,
+    pub descendants_task: Option<FetchTask>,
+    pub descendants_timeout_task: Option<TimeoutTask>,
+
+    pub live_callback: Callback<Result<EventsResponse, Error>>,
+    pub live_notification: Callback<WebSocketStatus>,
+    pub live_task: Option<WebSocketTask>,
+    pub live: bool, // Whether the new events are pushed by the backend rather than polled for
+
+    pub predecessor_callback: Callback<Result<EventsResponse, Error>>,
+    pub predecessor_tasks: Vec<FetchTask>,
+
+    pub state_callback: Callback<Result<EventsResponse, Error>>,
+    pub state_task: Option<FetchTask>,
+
+    pub single_event_task: Option<FetchTask>, // The fetch of the event jumped to which wasn't in the DAG
+
+    pub session: Arc<RwLock<PgSession>>,
+    pub backend: PostgresBackend,
+    pub events_dag: Option<Arc<RwLock<RoomEvents>>>,
+}
+
+impl PgView {
+    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> PgView {
+        let session = Arc::new(RwLock::new(PgSession::empty()));
+
+        PgView {
+            id,
+
+            deepest_callback: link.send_back(move |response: Result<SourceEvents, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::DeepestEvents(id, res)),
+                    Err(e) => {
+                        Msg::BkRes(BkResponse::DeepestRqFailed(id, BackendError::from_error(e)))
+                    }
+                }
+            }),
+            deepest_task: None,
+
+            ancestors_callback: link.send_back(move |response: Result<SourceEvents, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::EventsBefore(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::AncestorsRqFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            ancestors_task: None,
+
+            descendants_callback: link.send_back(move |response: Result<SourceEvents, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Descendants(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::DescendantsRqFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            descendants_task: None,
+            descendants_timeout_task: None,
+
+            live_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::LiveEvents(id, SourceEvents::from(res))),
+                    Err(e) => Msg::BkRes(BkResponse::LiveEventsFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            live_notification: link.send_back(move |status: WebSocketStatus| match status {
+                WebSocketStatus::Opened => Msg::BkRes(BkResponse::LiveOpened(id)),
+                WebSocketStatus::Closed | WebSocketStatus::Error => {
+                    Msg::BkRes(BkResponse::LiveClosed(id))
+                }
+            }),
+            live_task: None,
+            live: false,
+
+            predecessor_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
+                match response {
+                    Ok(res) => {
+                        Msg::BkRes(BkResponse::PredecessorEvents(id, SourceEvents::from(res)))
+                    }
+                    Err(e) => Msg::BkRes(BkResponse::PredecessorFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            predecessor_tasks: Vec::new(),
+
+            state_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::State(id, res)),
+                    Err(e) => {
+                        Msg::BkRes(BkResponse::StateRqFailed(id, BackendError::from_error(e)))
+                    }
+                }
+            }),
+            state_task: None,
+
+            stop_callback: link.send_back(move |response: Result<(), Error>| match response {
+                Ok(_) => Msg::BkRes(BkResponse::Disconnected(id)),
+                Err(e) => Msg::BkRes(BkResponse::DisconnectionFailed(
+                    id,
+                    BackendError::from_error(e),
+                )),
+            }),
+            stop_task: None,
+
+            single_event_task: None,
+
+            session: session.clone(),
+            backend: PostgresBackend::with_session(session),
+            events_dag: None,
+        }
+    }
+}
+
+// This contains every informations needed for the observation of a room from a given HS by using
+// the admin API of Synapse.
+pub struct AdminView {
+    pub id: ViewIndex,
+
+    pub messages_callback: Callback<Result<SourceEvents, Error>>,
+    pub messages_task: Option<FetchTask>,
+
+    pub more_msg_callback: Callback<Result<SourceEvents, Error>>,
+    pub more_msg_task: Option<FetchTask>,
+
+    pub new_msg_callback: Callback<Result<SourceEvents, Error>>,
+    pub new_msg_task: Option<FetchTask>,
+    pub new_msg_timeout_task: Option<TimeoutTask>,
+
+    pub state_callback: Callback<Result<RoomStateResponse, Error>>,
+    pub state_task: Option<FetchTask>,
+
+    pub reports_callback: Callback<Result<EventReportsResponse, Error>>,
+    pub reports_task: Option<FetchTask>,
+
+    pub single_event_task: Option<FetchTask>, // The fetch of the event jumped to which wasn't in the DAG
+
+    pub session: Arc<RwLock<AdminSession>>,
+    pub backend: AdminBackend,
+    pub events_dag: Option<Arc<RwLock<RoomEvents>>>,
+}
+
+impl AdminView {
+    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> AdminView {
+        let session = Arc::new(RwLock::new(AdminSession::empty()));
+
+        AdminView {
+            id,
+
+            messages_callback: link.send_back(move |response: Result<SourceEvents, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::AdminMessages(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::AdminMessagesFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            messages_task: None,
+
+            more_msg_callback: link.send_back(move |response: Result<SourceEvents, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::EventsBefore(id, res)),
+                    Err(e) => {
+                        Msg::BkRes(BkResponse::MoreMsgFailed(id, BackendError::from_error(e)))
+                    }
+                }
+            }),
+            more_msg_task: None,
+
+            new_msg_callback: link.send_back(move |response: Result<SourceEvents, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::AdminNewMessages(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::AdminNewMessagesFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            new_msg_task: None,
+            new_msg_timeout_task: None,
+
+            state_callback: link.send_back(move |response: Result<RoomStateResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::AdminState(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::FetchStateFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                }
+            }),
+            state_task: None,
+
+            reports_callback: link.send_back(
+                move |response: Result<EventReportsResponse, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::EventReports(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::EventReportsFailed(
+                        id,
+                        BackendError::from_error(e),
+                    )),
+                },
+            ),
+            reports_task: None,
+
+            single_event_task: None,
+
+            session: session.clone(),
+            backend: AdminBackend::with_session(session),
+            events_dag: None,
+        }
+    }
+}
@@ -0,0 +1,874 @@
+use std::collections::{BTreeSet, HashSet};
+use std::sync::{Arc, RwLock};
+
+use stdweb::web;
+use stdweb::web::IParentNode;
+use stdweb::Value;
+
+use crate::model::dag::RoomEvents;
+use crate::model::event::Field;
+use crate::model::layout_cache::LayoutCache;
+use crate::model::layout_options::LayoutOptions;
+use crate::model::snapshot::Camera;
+use crate::model::theme::Theme;
+use crate::visjs::VisJsService;
+use crate::BackendChoice;
+
+/// How the DAGs of the different views are laid out on the page.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Layout {
+    /// Every DAGs are displayed side-by-side within a single vis.js network.
+    Single,
+    /// Each view has its own vis.js network, and the networks are displayed next to each other.
+    Split,
+}
+
+// The ID used for the nodes of the merged DAG in the main network.
+const MERGED_VIEW_ID: usize = 0;
+
+/// The number of edges around a pinned event within which the events are drawn at first.
+pub const DEFAULT_PIN_HOPS: usize = 3;
+
+/// An event pinned as the root of a view: only the events within `hops` edges of it, among its
+/// ancestors and its descendants, are drawn.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pin {
+    pub event_id: String,
+    pub hops: usize,
+}
+
+// The IDs of the elements of the page which are given to the vis.js networks when they are
+// initialised.
+struct Targets {
+    container_id: String,
+    targeted_view_input_id: String,
+    more_ev_btn_id: String,
+    selected_event_input_id: String,
+    display_body_btn_id: String,
+    ancestors_input_id: String,
+    ancestors_btn_id: String,
+    viewport_input_id: String,
+    viewport_btn_id: String,
+    reveal_btn_id: String,
+    shared_selection_btn_id: String,
+}
+
+/// This struct manages the vis.js networks displaying the DAGs of the views.
+///
+/// With the `Single` layout, it is a thin wrapper around a single `VisJsService`. With the `Split`
+/// layout, each view gets its own `VisJsService` rendered in a sub-container of the main one, and
+/// the pan/zoom and the selected depth of the networks are kept in sync so the same region of the
+/// DAG is visible for every servers at once. Since the level of a node is the depth of its event,
+/// synchronising the vertical position of the networks aligns their depths.
+///
+/// When the merge view is enabled, the main network only displays the union of the DAGs of every
+/// views (see `RoomEvents::merged`), which is rebuilt each time one of them changes.
+///
+/// Each view has its own layout options, which only apply to its network with the `Split` layout.
+/// The main network is laid out with the options of the first view.
+pub struct ViewsManager {
+    bk_type: Arc<RwLock<BackendChoice>>,
+    layout: Layout,
+    sampling_rate: Option<usize>,
+    lazy_margin: Option<i64>,
+    fog: bool, // Whether the regions of unknown history below the orphan events are drawn
+    targets: Option<Targets>,
+    main: VisJsService,
+    split: Vec<Option<VisJsService>>,
+    sync_state: Value, // The networks to synchronise, shared by their event handlers
+    dags: Vec<Option<Arc<RwLock<RoomEvents>>>>, // The DAGs displayed for each view
+    merge_view: bool,
+    merged_dag: Option<Arc<RwLock<RoomEvents>>>,
+    time_range: Option<(i64, i64)>, // Only the events created in this range are displayed
+    replay: Option<usize>, // Only this number of events are displayed, in the order they arrived
+    theme: Theme,
+    layout_options: Vec<LayoutOptions>, // The layout options of each view
+    fields: Vec<HashSet<Field>>,        // The fields of the events in the labels of each view
+    pins: Vec<Option<Pin>>,             // The event pinned as the root of each view, if any
+    layout_cache: Arc<RwLock<LayoutCache>>, // The positions of the nodes once laid out, shared by every networks
+}
+
+impl ViewsManager {
+    pub fn new(bk_type: Arc<RwLock<BackendChoice>>) -> Self {
+        let layout_cache = Arc::new(RwLock::new(LayoutCache::new()));
+
+        ViewsManager {
+            bk_type: bk_type.clone(),
+            layout: Layout::Single,
+            sampling_rate: None,
+            lazy_margin: None,
+            fog: false,
+            targets: None,
+            main: VisJsService::new(bk_type, layout_cache.clone()),
+            split: Vec::new(),
+            sync_state: new_sync_state(),
+            dags: Vec::new(),
+            merge_view: false,
+            merged_dag: None,
+            time_range: None,
+            replay: None,
+            theme: Theme::default(),
+            layout_options: Vec::new(),
+            fields: Vec::new(),
+            pins: Vec::new(),
+            layout_cache,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Changes the layout of the networks. The networks are recreated and the DAGs of the views
+    /// are displayed again.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+
+        self.redraw();
+    }
+
+    pub fn merge_view(&self) -> bool {
+        self.merge_view
+    }
+
+    /// Enables or disables the merge view, in which the union of the DAGs of every views is
+    /// displayed instead of the DAGs themselves.
+    pub fn set_merge_view(&mut self, merge_view: bool) {
+        self.merge_view = merge_view;
+
+        self.redraw();
+    }
+
+    /// Recreates the networks and displays the DAGs of the views again, e.g. after changing an
+    /// option which can't be applied to the nodes already in the networks.
+    pub fn redraw(&mut self) {
+        self.reset_networks();
+        self.redisplay();
+    }
+
+    /// Returns the DAG displayed in the merge view, if it is enabled.
+    pub fn merged_dag(&self) -> Option<&Arc<RwLock<RoomEvents>>> {
+        self.merged_dag.as_ref()
+    }
+
+    /// Only displays the events whose `origin_server_ts` is within `time_range`, or every events
+    /// if it is `None`. The filter also applies to the events added later.
+    pub fn set_time_range(&mut self, time_range: Option<(i64, i64)>) {
+        self.time_range = time_range;
+        self.apply_filters();
+    }
+
+    /// Only displays the first `replay` events of each DAG in the order in which they arrived (see
+    /// `RoomEvents::arrival_order`), or every events if it is `None`. Increasing it step by step
+    /// replays the arrival of the events. The time range filter still applies.
+    pub fn set_replay(&mut self, replay: Option<usize>) {
+        self.replay = replay;
+        self.apply_filters();
+    }
+
+    // Applies the time range filter and the replay to every networks.
+    fn apply_filters(&mut self) {
+        if !self.is_active() {
+            return;
+        }
+
+        if self.merge_view {
+            self.filter_merged_dag();
+        } else {
+            for view_id in 0..self.dags.len() {
+                self.filter_view(view_id);
+            }
+        }
+    }
+
+    /// Enables the sampling mode of every networks, see `VisJsService::set_sampling`.
+    pub fn set_sampling(&mut self, rate: Option<usize>) {
+        self.sampling_rate = rate;
+
+        self.main.set_sampling(rate);
+        for service in self.split.iter_mut().flatten() {
+            service.set_sampling(rate);
+        }
+    }
+
+    /// Enables the lazy rendering of every networks, see `VisJsService::set_lazy_rendering`. The
+    /// networks are recreated so that only the latest events stay in them.
+    pub fn set_lazy_rendering(&mut self, margin: Option<i64>) {
+        self.lazy_margin = margin;
+
+        self.redraw();
+    }
+
+    /// Enables or disables the frontier fog, see `VisJsService::show_fog`. It isn't drawn in the
+    /// merge view, since the merged DAG can't be backfilled.
+    pub fn set_fog(&mut self, fog: bool) {
+        self.fog = fog;
+
+        self.redraw();
+    }
+
+    /// Changes the theme of every networks, see `VisJsService::set_theme`. The networks are
+    /// recreated with its colours.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+
+        self.redraw();
+    }
+
+    /// Returns the layout options of the view `view_id`.
+    pub fn layout_options(&self, view_id: usize) -> LayoutOptions {
+        self.layout_options
+            .get(view_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the layout options of every views, e.g. to persist them.
+    pub fn all_layout_options(&self) -> &[LayoutOptions] {
+        &self.layout_options
+    }
+
+    /// Changes the layout options of the view `view_id`. The networks are recreated with them.
+    pub fn set_layout_options(&mut self, view_id: usize, layout_options: LayoutOptions) {
+        while self.layout_options.len() <= view_id {
+            self.layout_options.push(LayoutOptions::default());
+        }
+
+        self.layout_options[view_id] = layout_options;
+
+        self.redraw();
+    }
+
+    /// Replaces the layout options of every views, e.g. by the persisted ones.
+    pub fn set_all_layout_options(&mut self, layout_options: Vec<LayoutOptions>) {
+        self.layout_options = layout_options;
+
+        self.redraw();
+    }
+
+    /// Returns the fields of the events in the labels of the view `view_id`. The merge view uses
+    /// the ones of the first view.
+    pub fn fields(&self, view_id: usize) -> HashSet<Field> {
+        self.fields
+            .get(view_id)
+            .cloned()
+            .unwrap_or_else(default_fields)
+    }
+
+    /// Changes the fields of the events in the labels of the view `view_id` and updates the
+    /// labels of its nodes.
+    pub fn set_fields(&mut self, view_id: usize, fields: HashSet<Field>) {
+        while self.fields.len() <= view_id {
+            self.fields.push(default_fields());
+        }
+
+        self.fields[view_id] = fields;
+        self.push_fields();
+
+        if !self.is_active() {
+            return;
+        }
+
+        if let Some(events_dag) = self.dags.get(view_id).cloned().flatten() {
+            self.update_labels(events_dag, view_id);
+        }
+    }
+
+    /// Replaces the fields of the events in the labels of every views, e.g. by the ones of an
+    /// imported document. The labels of the nodes are updated.
+    pub fn set_all_fields(&mut self, fields: Vec<HashSet<Field>>) {
+        self.fields = fields;
+        self.push_fields();
+
+        if !self.is_active() {
+            return;
+        }
+
+        if self.merge_view {
+            self.refresh_merged_dag();
+        } else {
+            for (view_id, events_dag) in self.dags.clone().into_iter().enumerate() {
+                if let Some(events_dag) = events_dag {
+                    self.service_of(view_id).update_labels(events_dag, view_id);
+                }
+            }
+        }
+    }
+
+    // Gives the fields of every views to the networks.
+    fn push_fields(&mut self) {
+        self.main.set_fields(self.fields.clone());
+
+        for service in self.split.iter_mut().flatten() {
+            service.set_fields(self.fields.clone());
+        }
+    }
+
+    /// Returns the event pinned as the root of the view `view_id`, if any. The merge view uses
+    /// the one of the first view.
+    pub fn pin(&self, view_id: usize) -> Option<&Pin> {
+        self.pins.get(view_id).and_then(|pin| pin.as_ref())
+    }
+
+    /// Pins an event as the root of the view `view_id`, or unpins it if `pin` is `None`. The
+    /// networks are recreated with only the events around it.
+    pub fn set_pin(&mut self, view_id: usize, pin: Option<Pin>) {
+        while self.pins.len() <= view_id {
+            self.pins.push(None);
+        }
+
+        self.pins[view_id] = pin;
+
+        self.redraw();
+    }
+
+    /// Replaces the cached layouts by `layout_cache`, e.g. by the persisted ones. They are used
+    /// the next time a DAG is added to a network.
+    pub fn set_layout_cache(&mut self, layout_cache: LayoutCache) {
+        *self.layout_cache.write().unwrap() = layout_cache;
+    }
+
+    /// Returns the cached layouts serialized in JSON if a layout was added since this was last
+    /// called, so that they can be persisted.
+    pub fn unsaved_layout_cache(&mut self) -> Option<String> {
+        let mut layout_cache = self.layout_cache.write().unwrap();
+
+        if !layout_cache.take_unsaved() {
+            return None;
+        }
+
+        Some(serde_json::to_string(&*layout_cache).expect("Failed to serialize the layouts"))
+    }
+
+    /// Forgets every cached layout.
+    pub fn clear_layout_cache(&mut self) {
+        self.layout_cache.write().unwrap().clear();
+    }
+
+    /// Returns the number of cached layouts.
+    pub fn cached_layouts(&self) -> usize {
+        self.layout_cache.read().unwrap().len()
+    }
+
+    /// With the lazy rendering, only keeps the events within the margin of the viewport, which
+    /// spans from the level `min_level` to `max_level`, in every networks. Since the levels of
+    /// the split networks are kept aligned, the same window applies to all of them (when the
+    /// depths are compressed, each DAG maps it back to its own depths).
+    pub fn show_depth_window(&mut self, min_level: i64, max_level: i64) {
+        if self.lazy_margin.is_none() || !self.is_active() {
+            return;
+        }
+
+        if self.merge_view {
+            if let Some(merged_dag) = self.merged_dag.clone() {
+                self.main
+                    .show_depth_window(merged_dag, MERGED_VIEW_ID, min_level, max_level);
+                self.filter_merged_dag();
+            }
+        } else {
+            for (view_id, events_dag) in self.dags.clone().into_iter().enumerate() {
+                match events_dag {
+                    Some(events_dag) if events_dag.read().unwrap().cycle().is_none() => {
+                        self.service_of(view_id)
+                            .show_depth_window(events_dag, view_id, min_level, max_level);
+
+                        if self.is_filtered() {
+                            self.filter_view(view_id);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Saves the IDs of the elements used by the networks and creates the main network if only
+    /// one network is needed. Otherwise, the network of each view is created when its DAG is
+    /// added.
+    pub fn init(
+        &mut self,
+        container_id: &str,
+        targeted_view_input_id: &str,
+        more_ev_btn_id: &str,
+        selected_event_input_id: &str,
+        display_body_btn_id: &str,
+        ancestors_input_id: &str,
+        ancestors_btn_id: &str,
+        viewport_input_id: &str,
+        viewport_btn_id: &str,
+        reveal_btn_id: &str,
+        shared_selection_btn_id: &str,
+    ) {
+        let targets = Targets {
+            container_id: container_id.to_string(),
+            targeted_view_input_id: targeted_view_input_id.to_string(),
+            more_ev_btn_id: more_ev_btn_id.to_string(),
+            selected_event_input_id: selected_event_input_id.to_string(),
+            display_body_btn_id: display_body_btn_id.to_string(),
+            ancestors_input_id: ancestors_input_id.to_string(),
+            ancestors_btn_id: ancestors_btn_id.to_string(),
+            viewport_input_id: viewport_input_id.to_string(),
+            viewport_btn_id: viewport_btn_id.to_string(),
+            reveal_btn_id: reveal_btn_id.to_string(),
+            shared_selection_btn_id: shared_selection_btn_id.to_string(),
+        };
+
+        if self.uses_main_network() {
+            init_service(&mut self.main, container_id, &targets);
+        }
+
+        self.targets = Some(targets);
+    }
+
+    /// Adds a new `events_dag` for the view `view_id`.
+    pub fn add_dag(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        self.set_dag_of(view_id, Some(events_dag.clone()));
+
+        // A graph with a cycle is reported to the user instead of being drawn
+        if events_dag.read().unwrap().cycle().is_some() {
+            return;
+        }
+
+        if self.merge_view {
+            self.refresh_merged_dag();
+        } else {
+            self.service_of(view_id)
+                .add_dag(events_dag.clone(), view_id);
+
+            if self.fog {
+                self.service_of(view_id).show_fog(events_dag, view_id);
+            }
+
+            if self.is_filtered() {
+                self.filter_view(view_id);
+            }
+        }
+    }
+
+    /// Removes the DAG of the view `view_id`.
+    pub fn remove_dag(&mut self, view_id: usize) {
+        self.set_dag_of(view_id, None);
+
+        if self.merge_view {
+            self.refresh_merged_dag();
+        } else {
+            self.service_of(view_id).remove_dag(view_id);
+        }
+    }
+
+    /// Adds the new events of `events_dag` to the network of the view `view_id`.
+    pub fn update_dag(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        self.set_dag_of(view_id, Some(events_dag.clone()));
+
+        if events_dag.read().unwrap().cycle().is_some() {
+            return;
+        }
+
+        if self.merge_view {
+            self.refresh_merged_dag();
+        } else {
+            self.service_of(view_id)
+                .update_dag(events_dag.clone(), view_id);
+
+            if self.fog {
+                self.service_of(view_id).show_fog(events_dag, view_id);
+            }
+
+            if self.is_filtered() {
+                self.filter_view(view_id);
+            }
+        }
+    }
+
+    /// Updates the labels of the nodes of the view `view_id`.
+    pub fn update_labels(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        if self.merge_view {
+            self.refresh_merged_dag();
+        } else {
+            self.service_of(view_id).update_labels(events_dag, view_id);
+        }
+    }
+
+    /// Adds every events of the view `view_id` whose depth is between `min_depth` and `max_depth`
+    /// to its network, see `VisJsService::densify`.
+    pub fn densify(
+        &mut self,
+        events_dag: Arc<RwLock<RoomEvents>>,
+        view_id: usize,
+        min_depth: i64,
+        max_depth: i64,
+    ) {
+        if self.merge_view {
+            if let Some(merged_dag) = self.merged_dag.clone() {
+                self.main
+                    .densify(merged_dag, MERGED_VIEW_ID, min_depth, max_depth);
+            }
+        } else {
+            self.service_of(view_id)
+                .densify(events_dag, view_id, min_depth, max_depth);
+        }
+    }
+
+    /// Moves the camera so that it shows the events of the view `view_id` whose depth is between
+    /// `min_depth` and `max_depth`. With the `Split` layout, the other networks follow once the
+    /// animation is finished.
+    pub fn focus_depth_range(
+        &mut self,
+        events_dag: Arc<RwLock<RoomEvents>>,
+        view_id: usize,
+        min_depth: i64,
+        max_depth: i64,
+    ) {
+        if self.merge_view {
+            if let Some(merged_dag) = self.merged_dag.clone() {
+                self.main
+                    .focus_depth_range(merged_dag, MERGED_VIEW_ID, min_depth, max_depth);
+            }
+        } else {
+            self.service_of(view_id)
+                .focus_depth_range(events_dag, view_id, min_depth, max_depth);
+        }
+    }
+
+    /// Returns the IDs of the events whose nodes are currently selected in any network.
+    pub fn selected_events(&self) -> Vec<String> {
+        if self.uses_main_network() {
+            self.main.selected_events()
+        } else {
+            let events: BTreeSet<String> = self
+                .split
+                .iter()
+                .flatten()
+                .flat_map(|service| service.selected_events())
+                .collect();
+
+            events.into_iter().collect()
+        }
+    }
+
+    /// Selects the nodes of the events whose IDs are in `events` in every networks.
+    pub fn select_events(&self, events: &[String]) {
+        if self.uses_main_network() {
+            self.main.select_events(events);
+        } else {
+            for service in self.split.iter().flatten() {
+                service.select_events(events);
+            }
+        }
+    }
+
+    /// Selects the nodes of the event `event_id` in every network and centres them on it, so
+    /// that the same event can be compared across the servers.
+    pub fn focus_event(&self, event_id: &str) {
+        if self.uses_main_network() {
+            self.main.focus_event(event_id);
+        } else {
+            for service in self.split.iter().flatten() {
+                service.focus_event(event_id);
+            }
+        }
+    }
+
+    /// Returns where the camera of each network looks: the single network's, or those of the
+    /// split views in their order.
+    pub fn cameras(&self) -> Vec<Camera> {
+        if self.uses_main_network() {
+            self.main.camera().into_iter().collect()
+        } else {
+            self.split
+                .iter()
+                .flatten()
+                .filter_map(|service| service.camera())
+                .collect()
+        }
+    }
+
+    /// Moves the cameras of the networks, given in the order of `cameras`.
+    pub fn move_cameras(&self, cameras: &[Camera]) {
+        if self.uses_main_network() {
+            if let Some(camera) = cameras.first() {
+                self.main.move_camera(*camera);
+            }
+        } else {
+            for (service, camera) in self.split.iter().flatten().zip(cameras) {
+                service.move_camera(*camera);
+            }
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.targets.is_some()
+    }
+
+    fn uses_main_network(&self) -> bool {
+        self.merge_view || self.layout == Layout::Single
+    }
+
+    fn set_dag_of(&mut self, view_id: usize, events_dag: Option<Arc<RwLock<RoomEvents>>>) {
+        while self.dags.len() <= view_id {
+            self.dags.push(None);
+        }
+
+        self.dags[view_id] = events_dag;
+    }
+
+    // Removes every networks from the page and creates the main one again if it is needed.
+    fn reset_networks(&mut self) {
+        self.main = VisJsService::new(self.bk_type.clone(), self.layout_cache.clone());
+        self.main.set_sampling(self.sampling_rate);
+        self.main.set_lazy_rendering(self.lazy_margin);
+        self.main.set_theme(self.theme);
+        self.main.set_layout_options(self.layout_options(0));
+        self.main.set_fields(self.fields.clone());
+        self.main.set_pins(self.pins.clone());
+        self.split = Vec::new();
+        self.sync_state = new_sync_state();
+
+        let uses_main_network = self.uses_main_network();
+
+        if let Some(targets) = &self.targets {
+            let container = web::document()
+                .query_selector(&targets.container_id)
+                .expect("Couldn't get document element")
+                .expect("Couldn't get document element");
+
+            js! {
+                var container = @{container};
+
+                container.innerHTML = "";
+                container.classList.remove("split");
+            };
+
+            if uses_main_network {
+                init_service(&mut self.main, &targets.container_id, targets);
+            }
+        }
+    }
+
+    // Displays the DAGs of the views again after the networks were reset.
+    fn redisplay(&mut self) {
+        if !self.is_active() {
+            return;
+        }
+
+        if self.merge_view {
+            self.refresh_merged_dag();
+        } else {
+            for (view_id, events_dag) in self.dags.clone().into_iter().enumerate() {
+                if let Some(events_dag) = events_dag {
+                    self.add_dag(events_dag, view_id);
+                }
+            }
+        }
+    }
+
+    // Builds the union of the DAGs of the views again and displays it in the main network.
+    fn refresh_merged_dag(&mut self) {
+        let merged_dag = {
+            let guards: Vec<_> = self
+                .dags
+                .iter()
+                .flatten()
+                .map(|dag| dag.read().unwrap())
+                .collect();
+            let dags: Vec<&RoomEvents> = guards.iter().map(|dag| &**dag).collect();
+
+            RoomEvents::merged(&dags).map(|dag| Arc::new(RwLock::new(dag)))
+        };
+
+        match &merged_dag {
+            Some(merged_dag) if merged_dag.read().unwrap().cycle().is_none() => {
+                self.main.show_dag(merged_dag.clone(), MERGED_VIEW_ID)
+            }
+            _ => self.main.clear(),
+        }
+
+        self.merged_dag = merged_dag;
+
+        if self.is_filtered() {
+            self.filter_merged_dag();
+        }
+    }
+
+    // Tells whether some events are hidden by the time range filter or the replay.
+    fn is_filtered(&self) -> bool {
+        self.time_range.is_some() || self.replay.is_some()
+    }
+
+    // Returns the events of `events_dag` which are displayed given the time range filter and the
+    // replay, or `None` if every events are.
+    fn visible_events(&self, events_dag: &RoomEvents) -> Option<Vec<String>> {
+        let in_range = self
+            .time_range
+            .map(|(start, end)| events_dag.events_in_time_range(start, end));
+        let arrived = self.replay.map(|count| {
+            let mut order = events_dag.arrival_order();
+            order.truncate(count);
+
+            order
+        });
+
+        match (in_range, arrived) {
+            (Some(in_range), Some(arrived)) => {
+                let arrived: HashSet<String> = arrived.into_iter().collect();
+
+                Some(
+                    in_range
+                        .into_iter()
+                        .filter(|id| arrived.contains(id))
+                        .collect(),
+                )
+            }
+            (in_range, arrived) => in_range.or(arrived),
+        }
+    }
+
+    // Applies the time range filter and the replay to the network of the view `view_id`.
+    fn filter_view(&mut self, view_id: usize) {
+        let events_dag = match self.dags.get(view_id) {
+            Some(Some(events_dag)) => events_dag.clone(),
+            _ => return,
+        };
+        let visible = self.visible_events(&events_dag.read().unwrap());
+
+        self.service_of(view_id).filter_events(view_id, visible);
+    }
+
+    // Applies the time range filter and the replay to the merged DAG.
+    fn filter_merged_dag(&mut self) {
+        if let Some(merged_dag) = &self.merged_dag {
+            let visible = self.visible_events(&merged_dag.read().unwrap());
+
+            self.main.filter_events(MERGED_VIEW_ID, visible);
+        }
+    }
+
+    // Returns the service displaying the view `view_id`, creating its network if needed.
+    fn service_of(&mut self, view_id: usize) -> &mut VisJsService {
+        if self.uses_main_network() {
+            return &mut self.main;
+        }
+
+        while self.split.len() <= view_id {
+            self.split.push(None);
+        }
+
+        if self.split[view_id].is_none() {
+            let targets = self
+                .targets
+                .as_ref()
+                .expect("The networks weren't initialised");
+            let container = web::document()
+                .query_selector(&targets.container_id)
+                .expect("Couldn't get document element")
+                .expect("Couldn't get document element");
+            let sub_container_id = format!("{}-{}", targets.container_id, view_id);
+
+            js! {
+                var container = @{container};
+                var sub_container = document.createElement("div");
+
+                sub_container.id = @{sub_container_id.trim_start_matches('#')};
+                sub_container.className = "split-view";
+
+                container.classList.add("split");
+                container.appendChild(sub_container);
+            };
+
+            let mut service = VisJsService::new(self.bk_type.clone(), self.layout_cache.clone());
+            service.set_sampling(self.sampling_rate);
+            service.set_lazy_rendering(self.lazy_margin);
+            service.set_theme(self.theme);
+            service.set_layout_options(self.layout_options(view_id));
+            service.set_fields(self.fields.clone());
+            service.set_pins(self.pins.clone());
+            init_service(&mut service, &sub_container_id, targets);
+
+            js! {
+                var state = @{&self.sync_state};
+                var network = @{service.network()};
+
+                state.networks.push(network);
+
+                // The coordinate of `position` along the axis of the levels of `network`
+                function level_coordinate(network, position) {
+                    return network.horizontal ? position.x : position.y;
+                }
+
+                // Align the position along the axis of the levels and the scale of the other
+                // networks with the ones of `network`, keeping their own position on the other
+                // axis
+                function sync_others(c) {
+                    if (state.syncing) {
+                        return;
+                    }
+                    state.syncing = true;
+
+                    var scale = network.getScale();
+
+                    for (let other of state.networks) {
+                        if (other !== network) {
+                            var position = other.getViewPosition();
+
+                            if (other.horizontal) {
+                                position.x = c;
+                            } else {
+                                position.y = c;
+                            }
+
+                            other.moveTo({ position: position, scale: scale });
+                        }
+                    }
+
+                    state.syncing = false;
+                }
+
+                function sync_view() {
+                    sync_others(level_coordinate(network, network.getViewPosition()));
+                }
+
+                network.on("dragging", sync_view);
+                network.on("zoom", sync_view);
+                network.on("animationFinished", sync_view);
+
+                // Show the depth of the selected event on every servers
+                network.on("selectNode", function (ev) {
+                    var positions = network.getPositions(ev.nodes);
+
+                    sync_others(level_coordinate(network, positions[ev.nodes[0]]));
+                });
+            };
+
+            self.split[view_id] = Some(service);
+        }
+
+        self.split[view_id].as_mut().unwrap()
+    }
+}
+
+/// The fields of the events in the labels of a view until others are chosen for it.
+pub fn default_fields() -> HashSet<Field> {
+    [Field::EventID].iter().cloned().collect()
+}
+
+fn new_sync_state() -> Value {
+    js! {
+        return { networks: [], syncing: false };
+    }
+}
+
+fn init_service(service: &mut VisJsService, container_id: &str, targets: &Targets) {
+    service.init(
+        container_id,
+        &targets.targeted_view_input_id,
+        &targets.more_ev_btn_id,
+        &targets.selected_event_input_id,
+        &targets.display_body_btn_id,
+        &targets.ancestors_input_id,
+        &targets.ancestors_btn_id,
+        &targets.viewport_input_id,
+        &targets.viewport_btn_id,
+        &targets.reveal_btn_id,
+        &targets.shared_selection_btn_id,
+    );
+}
@@ -1,12 +1,21 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 use serde_derive::Serialize;
+use stdweb::unstable::TryInto;
 use stdweb::web;
 use stdweb::web::IParentNode;
 use stdweb::Value;
 
 use crate::model::dag::RoomEvents;
-use crate::model::dag::{DataSet, OrphanInfo};
+use crate::model::dag::{DataSet, FogRegion, OrphanInfo};
+use crate::model::event::Field;
+use crate::model::layout::{self, LayoutAlgorithm, LayoutGraph};
+use crate::model::layout_cache::{LayoutCache, Position};
+use crate::model::layout_options::LayoutOptions;
+use crate::model::snapshot::Camera;
+use crate::model::theme::Theme;
+use crate::views_manager::{self, Pin};
 use crate::BackendChoice;
 
 /// This struct contains the DAG displayed by the application.
@@ -18,6 +27,10 @@ use crate::BackendChoice;
 /// Each of `earliest_events`, `latest_events` and `orphan_events` variables contains a list of
 /// lists of the earliest/latest/orphan events' IDs currently displayed for each views. So
 /// `*_events[X]` corresponds with the view X.
+///
+/// When a DAG is added to an empty network and the layout of its nodes is in `layout_cache`, the
+/// hierarchical layout of vis.js is disabled and the nodes are placed at their cached positions.
+/// It is enabled again as soon as nodes without a cached position are added.
 pub struct VisJsService {
     lib: Option<Value>,
     network: Option<Value>,
@@ -26,6 +39,14 @@ pub struct VisJsService {
     earliest_events: Vec<Vec<String>>,
     latest_events: Vec<Vec<String>>,
     orphan_events: Vec<Vec<OrphanInfo>>,
+    sampling_rate: Option<usize>, // When set, only a sample of the events are initially displayed
+    lazy_margin: Option<i64>, // When set, only the events within this many depths of the viewport are displayed
+    theme: Theme, // Gives its colours to what isn't coloured by the DAGs (the labels, the edges and the fog)
+    layout_options: LayoutOptions,
+    fields: Vec<HashSet<Field>>, // The fields of the events in the labels of the nodes of each view
+    pins: Vec<Option<Pin>>,      // The event pinned as the root of each view, if any
+    layout_cache: Arc<RwLock<LayoutCache>>, // Shared by the networks of every views
+    fixed_layout: bool, // Whether the nodes were placed at cached positions rather than laid out
 }
 
 // This enables the serialization of the ID of a view, so it can be used within the `js!`
@@ -36,7 +57,10 @@ struct ViewId {
 }
 
 impl VisJsService {
-    pub fn new(bk_type: Arc<RwLock<BackendChoice>>) -> Self {
+    pub fn new(
+        bk_type: Arc<RwLock<BackendChoice>>,
+        layout_cache: Arc<RwLock<LayoutCache>>,
+    ) -> Self {
         let lib = js! {
             return vis;
         };
@@ -49,6 +73,90 @@ impl VisJsService {
             earliest_events: Vec::new(),
             latest_events: Vec::new(),
             orphan_events: Vec::new(),
+            sampling_rate: None,
+            lazy_margin: None,
+            theme: Theme::default(),
+            layout_options: LayoutOptions::default(),
+            fields: Vec::new(),
+            pins: Vec::new(),
+            layout_cache,
+            fixed_layout: false,
+        }
+    }
+
+    /// Enables the sampling mode, in which only about one event out of `rate` is displayed when
+    /// a DAG is added (in addition to the structurally important ones), or disables it if `rate`
+    /// is `None`.
+    pub fn set_sampling(&mut self, rate: Option<usize>) {
+        self.sampling_rate = rate;
+    }
+
+    /// Enables the lazy rendering, in which only the events within `margin` depths of the
+    /// viewport are kept in the network, or disables it if `margin` is `None`. See
+    /// `show_depth_window`.
+    pub fn set_lazy_rendering(&mut self, margin: Option<i64>) {
+        self.lazy_margin = margin;
+    }
+
+    /// Changes the theme of the network. It must be changed before the network is created.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Changes the direction and the spacing of the layout of the network. They must be changed
+    /// before the network is created.
+    pub fn set_layout_options(&mut self, layout_options: LayoutOptions) {
+        self.layout_options = layout_options;
+    }
+
+    /// Changes the fields of the events in the labels of the nodes of each view. The labels of
+    /// the nodes already in the network must then be updated, see `update_labels`.
+    pub fn set_fields(&mut self, fields: Vec<HashSet<Field>>) {
+        self.fields = fields;
+    }
+
+    /// Changes the event pinned as the root of each view. It must be changed before the DAGs are
+    /// added to the network.
+    pub fn set_pins(&mut self, pins: Vec<Option<Pin>>) {
+        self.pins = pins;
+    }
+
+    // Returns the event pinned as the root of the view `view_id`, if any.
+    fn pin_of(&self, view_id: usize) -> Option<&Pin> {
+        self.pins.get(view_id).and_then(|pin| pin.as_ref())
+    }
+
+    // Returns the fields of the events in the labels of the nodes of the view `view_id`.
+    fn fields_of(&self, view_id: usize) -> HashSet<Field> {
+        self.fields
+            .get(view_id)
+            .cloned()
+            .unwrap_or_else(views_manager::default_fields)
+    }
+
+    // Creates the data set of `events_dag` for the view `view_id`. If an event is pinned as its
+    // root, it only contains the events around it. With the lazy rendering, it only contains the
+    // latest events, since the viewport isn't known yet. Otherwise, it is sampled if the sampling
+    // mode is enabled.
+    fn data_set_of(&self, events_dag: &RoomEvents, view_id: usize) -> DataSet {
+        let fields = self.fields_of(view_id);
+
+        if let Some(pin) = self.pin_of(view_id) {
+            return events_dag.create_data_set_around(&pin.event_id, pin.hops, &fields);
+        }
+
+        match (self.lazy_margin, events_dag.depth_bounds()) {
+            (Some(margin), Some((_, max_depth))) => {
+                let max_level = events_dag.level_of(max_depth);
+                let (min_depth, max_depth) =
+                    events_dag.levels_to_depths(max_level - 2 * margin, max_level);
+
+                events_dag.create_data_set_range(min_depth, max_depth, &fields)
+            }
+            _ => match self.sampling_rate {
+                Some(rate) => events_dag.create_sampled_data_set(rate, &fields),
+                None => events_dag.create_data_set(&fields),
+            },
         }
     }
 
@@ -63,6 +171,10 @@ impl VisJsService {
         display_body_btn_id: &str,
         ancestors_input_id: &str,
         ancestors_btn_id: &str,
+        viewport_input_id: &str,
+        viewport_btn_id: &str,
+        reveal_btn_id: &str,
+        shared_selection_btn_id: &str,
     ) {
         let lib = self.lib.as_ref().expect("vis library object lost");
 
@@ -94,9 +206,26 @@ impl VisJsService {
             .query_selector(ancestors_btn_id)
             .expect("Couldn't get document element")
             .expect("Couldn't get document element");
+        let viewport_input = web::document()
+            .query_selector(viewport_input_id)
+            .expect("Couldn't get document element")
+            .expect("Couldn't get document element");
+        let viewport_btn = web::document()
+            .query_selector(viewport_btn_id)
+            .expect("Couldn't get document element")
+            .expect("Couldn't get document element");
+        let reveal_btn = web::document()
+            .query_selector(reveal_btn_id)
+            .expect("Couldn't get document element")
+            .expect("Couldn't get document element");
+        let shared_selection_btn = web::document()
+            .query_selector(shared_selection_btn_id)
+            .expect("Couldn't get document element")
+            .expect("Couldn't get document element");
 
         js_serializable!(DataSet);
         js_serializable!(OrphanInfo);
+        js_serializable!(FogRegion);
         js_serializable!(ViewId);
 
         self.data = Some(js! {
@@ -111,6 +240,9 @@ impl VisJsService {
             return data;
         });
 
+        let palette = self.theme.palette();
+        let direction = self.layout_options.direction;
+
         self.network = Some(js! {
             var vis = @{lib};
 
@@ -121,18 +253,28 @@ impl VisJsService {
                     randomSeed: undefined,
                     improvedLayout: true,
                     hierarchical: {
-                        enabled: true,
-                        levelSeparation: 250,
-                        nodeSpacing: 500,
+                        enabled: @{!self.uses_layered_layout()},
+                        direction: @{direction.name()},
+                        levelSeparation: @{self.layout_options.level_separation},
+                        nodeSpacing: @{self.layout_options.node_spacing},
                         treeSpacing: 300,
                         sortMethod: "directed"
                     }
                 },
                 nodes: {
                     shape: "box",
+                    font: { color: @{palette.label} },
                     widthConstraint: {
                         minimum: 200,
                         maximum: 300
+                    },
+                    // Only used when the nodes have a value, see `SizeMetric`
+                    scaling: {
+                        label: {
+                            enabled: true,
+                            min: 14,
+                            max: 40
+                        }
                     }
                 },
                 edges: {
@@ -140,14 +282,20 @@ impl VisJsService {
                     smooth: true
                 },
                 interaction: {
-                    dragNodes: false
+                    dragNodes: false,
+                    multiselect: true
                 },
                 physics: {
                     enabled: false
                 }
             };
 
-            var network = new vis.Network(@{container}, data, options);
+            var container = @{container};
+            var network = new vis.Network(container, data, options);
+
+            // The axis along which the levels follow each other, read when the networks are
+            // synchronised
+            network.horizontal = @{direction.is_horizontal()};
 
             function select_node() {
                 let id = network.getSelectedNodes()[0];
@@ -163,7 +311,7 @@ impl VisJsService {
                 if (id.includes("_more_of_")) {
                     let split_id = id.split("_");
                     let targeted_view_input = @{targeted_view_input.clone()};
-                    let id_input = @{ancestors_input};
+                    let id_input = @{ancestors_input.clone()};
 
                     let pref_patt = new RegExp("subdag_[0-9]+_more_of_");
 
@@ -171,6 +319,31 @@ impl VisJsService {
                     id_input.value = id.replace(pref_patt, "");
                     @{ancestors_btn}.click();
                 }
+
+                if (new RegExp("^subdag_[0-9]+_fog_").test(id)) {
+                    let split_id = id.split("_");
+                    let targeted_view_input = @{targeted_view_input.clone()};
+                    let id_input = @{ancestors_input};
+
+                    let pref_patt = new RegExp("subdag_[0-9]+_fog_");
+
+                    targeted_view_input.value = split_id[1];
+                    id_input.value = id.replace(pref_patt, "");
+                    @{reveal_btn}.click();
+                }
+
+                // Share the selection of a single event with the other views
+                if (network.getSelectedNodes().length == 1 && new RegExp("^subdag_[0-9]+_[$]").test(id)) {
+                    let split_id = id.split("_");
+                    let targeted_view_input = @{targeted_view_input.clone()};
+                    let id_input = @{selected_event_input.clone()};
+
+                    let pref_patt = new RegExp("subdag_[0-9]+_");
+
+                    targeted_view_input.value = split_id[1];
+                    id_input.value = id.replace(pref_patt, "");
+                    @{shared_selection_btn}.click();
+                }
             }
 
             network.on("selectNode", select_node);
@@ -194,6 +367,55 @@ impl VisJsService {
 
             network.on("doubleClick", display_json_body);
 
+            // Tell which levels are within the viewport once the user stops moving it. Since the
+            // levels are evenly spaced, they are deduced from the position of any event node of
+            // the network, along the axis of the layout.
+            var viewport_timeout = null;
+            var reversed = @{direction.is_reversed()};
+
+            function level_coordinate(position) {
+                return network.horizontal ? position.x : position.y;
+            }
+
+            function report_viewport() {
+                let anchors = data.nodes.get({
+                    filter: function (node) { return node.id.includes("_$"); }
+                });
+
+                if (anchors.length == 0) {
+                    return;
+                }
+
+                let anchor = anchors[0];
+                let anchor_c = level_coordinate(network.getPositions([anchor.id])[anchor.id]);
+                let separation = options.layout.hierarchical.levelSeparation;
+                let sign = reversed ? -1 : 1;
+
+                let start = level_coordinate(network.DOMtoCanvas({ x: 0, y: 0 }));
+                let end = level_coordinate(network.DOMtoCanvas({
+                    x: container.clientWidth,
+                    y: container.clientHeight
+                }));
+
+                let start_level = anchor.level + sign * (start - anchor_c) / separation;
+                let end_level = anchor.level + sign * (end - anchor_c) / separation;
+
+                let min_depth = Math.floor(Math.min(start_level, end_level));
+                let max_depth = Math.ceil(Math.max(start_level, end_level));
+
+                @{viewport_input}.value = min_depth + "," + max_depth;
+                @{viewport_btn}.click();
+            }
+
+            function viewport_moved() {
+                clearTimeout(viewport_timeout);
+                viewport_timeout = setTimeout(report_viewport, 200);
+            }
+
+            network.on("dragEnd", viewport_moved);
+            network.on("zoom", viewport_moved);
+            network.on("animationFinished", viewport_moved);
+
             return network;
         });
     }
@@ -203,9 +425,31 @@ impl VisJsService {
         let backend = *self.bk_type.read().unwrap();
         let events_dag = events_dag.read().unwrap();
 
+        let mut events = self.data_set_of(&events_dag, view_id);
+        let layout_key = events.layout_key();
+        let prefix = format!("subdag_{}_", view_id);
+        events.add_prefix(&prefix);
+
+        // The cached positions are relative to the other nodes of the network when they were
+        // computed, so they can only be used in an empty one. The layered layout isn't cached,
+        // since it is computed at once.
+        let alone = self.is_empty() && !self.uses_layered_layout();
+        let positions = if alone {
+            self.cached_positions(&layout_key, &prefix)
+        } else {
+            None
+        };
+        let laid_out = positions.is_none();
+
+        if laid_out {
+            self.release_layout();
+        } else {
+            self.set_hierarchical_layout(false);
+            self.fixed_layout = true;
+        }
+
+        let positions = serde_json::to_string(&positions).expect("Failed to serialize positions");
         let data = self.data.as_ref().expect("No data set found");
-        let mut events = events_dag.create_data_set();
-        events.add_prefix(&format!("subdag_{}_", view_id));
 
         while self.earliest_events.len() <= view_id {
             self.earliest_events.push(Vec::new());
@@ -224,11 +468,21 @@ impl VisJsService {
         let view_id = ViewId { id: view_id };
 
         match backend {
-            BackendChoice::CS => {
+            BackendChoice::CS | BackendChoice::Admin => {
                 self.data = Some(js! {
                     var view_id = @{view_id};
                     var data = @{data};
                     var events = @{events};
+                    var positions = JSON.parse(@{&positions});
+
+                    function place(node) {
+                        if (positions !== null && positions[node.id] !== undefined) {
+                            node.x = positions[node.id].x;
+                            node.y = positions[node.id].y;
+                        }
+
+                        return node;
+                    }
 
                     var min_depth = -1;
                     for (let n of events.nodes) {
@@ -237,15 +491,16 @@ impl VisJsService {
                         }
                     }
 
-                    data.nodes.add(events.nodes);
-                    data.edges.add(events.edges);
+                    data.nodes.add(events.nodes.map(place));
+                    // The edges have stable IDs, so an edge added again is updated in place
+                    data.edges.update(events.edges);
 
                     // Add the button to load more events
-                    data.nodes.add({
+                    data.nodes.add(place({
                         id: "subdag_" + view_id.id + "_more_ev",
                         label: "Load more events",
                         level: min_depth - 1
-                    });
+                    }));
                     for (let ev of @{&self.earliest_events[view_id.id]}) {
                         data.edges.add({
                             id: "subdag_" + view_id.id + "_" + ev + "_more_ev",
@@ -262,17 +517,28 @@ impl VisJsService {
                     var view_id = @{view_id};
                     var data = @{data};
                     var events = @{events};
+                    var positions = JSON.parse(@{&positions});
+
+                    function place(node) {
+                        if (positions !== null && positions[node.id] !== undefined) {
+                            node.x = positions[node.id].x;
+                            node.y = positions[node.id].y;
+                        }
 
-                    data.nodes.add(events.nodes);
-                    data.edges.add(events.edges);
+                        return node;
+                    }
+
+                    data.nodes.add(events.nodes.map(place));
+                    // The edges have stable IDs, so an edge added again is updated in place
+                    data.edges.update(events.edges);
 
                     // Add the buttons to load ancestors
                     for (let ev of @{&self.orphan_events[view_id.id]}) {
-                        data.nodes.add({
+                        data.nodes.add(place({
                             id: "subdag_" + view_id.id + "_more_of_" + ev.id,
                             label: "Load ancestors",
-                            level: ev.depth - 1
-                        });
+                            level: ev.level - 1
+                        }));
 
                         data.edges.add({
                             id: "subdag_" + view_id.id + "_" + ev.id + "_more_of",
@@ -285,6 +551,144 @@ impl VisJsService {
                 });
             }
         }
+
+        if alone && laid_out {
+            self.remember_layout(layout_key, &prefix);
+        }
+
+        self.apply_layered_layout();
+    }
+
+    // Whether the network has no node yet.
+    fn is_empty(&self) -> bool {
+        let data = match self.data.as_ref() {
+            Some(data) => data,
+            None => return true,
+        };
+
+        js! {
+            return @{data}.nodes.length == 0;
+        }
+        .try_into()
+        .unwrap_or(true)
+    }
+
+    // Returns the cached positions of the nodes of the layout `layout_key`, with the prefix of
+    // their view, if there are some for the current layout options.
+    fn cached_positions(
+        &self,
+        layout_key: &str,
+        prefix: &str,
+    ) -> Option<HashMap<String, Position>> {
+        let mut layout_cache = self.layout_cache.write().unwrap();
+        let positions = layout_cache.get(layout_key, self.layout_options)?;
+
+        Some(
+            positions
+                .iter()
+                .map(|(id, position)| (format!("{}{}", prefix, id), *position))
+                .collect(),
+        )
+    }
+
+    // Caches the positions of the nodes of the network, which only contains the view whose nodes
+    // have the prefix `prefix`, as the layout `layout_key`.
+    fn remember_layout(&self, layout_key: String, prefix: &str) {
+        let json: String = js! {
+            return JSON.stringify(@{&self.network}.getPositions());
+        }
+        .try_into()
+        .unwrap_or_default();
+
+        let positions: HashMap<String, Position> = match serde_json::from_str(&json) {
+            Ok(positions) => positions,
+            Err(_) => return,
+        };
+        let positions = positions
+            .into_iter()
+            .filter_map(|(id, position)| Some((id.strip_prefix(prefix)?.to_string(), position)))
+            .collect();
+
+        self.layout_cache
+            .write()
+            .unwrap()
+            .insert(layout_key, self.layout_options, positions);
+    }
+
+    // Enables or disables the hierarchical layout of the network. When it is disabled, the nodes
+    // stay where they are placed.
+    fn set_hierarchical_layout(&self, enabled: bool) {
+        js! {
+            // Disabling the hierarchical layout restores the physics vis.js had before, so it is
+            // kept disabled
+            @{&self.network}.setOptions({
+                layout: { hierarchical: { enabled: @{enabled} } },
+                physics: { enabled: false }
+            });
+        };
+    }
+
+    // Tells whether the nodes are placed by the layered layout rather than by vis.js.
+    fn uses_layered_layout(&self) -> bool {
+        self.layout_options.algorithm == LayoutAlgorithm::Layered
+    }
+
+    // Places every node of the network where the layered layout puts it, see
+    // `layout::layered_positions`, the nodes of each view being laid out next to the ones of the
+    // previous view. It must be called whenever nodes are added to the network, and does nothing
+    // when vis.js lays it out itself.
+    fn apply_layered_layout(&self) {
+        if !self.uses_layered_layout() {
+            return;
+        }
+
+        let data = match self.data.as_ref() {
+            Some(data) => data,
+            None => return,
+        };
+
+        let json: String = js! {
+            var data = @{data};
+
+            var nodes = data.nodes.get({
+                fields: ["id", "level"],
+                filter: function (node) {
+                    return node.level !== undefined;
+                }
+            }).map(function (node) {
+                return { id: node.id, level: node.level, group: parseInt(node.id.split("_")[1]) || 0 };
+            });
+            var edges = data.edges.get({ fields: ["from", "to"] });
+
+            return JSON.stringify({ nodes: nodes, edges: edges });
+        }
+        .try_into()
+        .unwrap_or_default();
+
+        let graph: LayoutGraph = match serde_json::from_str(&json) {
+            Ok(graph) => graph,
+            Err(_) => return,
+        };
+        let positions = layout::layered_positions(&graph, &self.layout_options);
+        let positions = serde_json::to_string(&positions).expect("Failed to serialize positions");
+
+        js! {
+            var positions = JSON.parse(@{positions});
+
+            @{data}.nodes.update(Object.keys(positions).map(function (id) {
+                return { id: id, x: positions[id].x, y: positions[id].y };
+            }));
+        };
+    }
+
+    // Lets vis.js lay out the network again if its nodes were placed at cached positions, before
+    // adding nodes which have none. The nodes stay where they are with the layered layout, which
+    // places the new ones itself.
+    fn release_layout(&mut self) {
+        if self.fixed_layout && !self.uses_layered_layout() {
+            self.set_hierarchical_layout(true);
+            self.fixed_layout = false;
+        }
     }
 
     /// Removes the DAG of the view `view_id`.
@@ -317,13 +721,132 @@ impl VisJsService {
         });
     }
 
+    /// Replaces the content of the network by the events of `events_dag`, with the prefix of the
+    /// view `view_id`. Unlike `add_dag`, no node is added for loading more events, since this is
+    /// used for DAGs which are built from the ones of the views.
+    pub fn show_dag(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        let mut events = self.data_set_of(&events_dag.read().unwrap(), view_id);
+        events.add_prefix(&format!("subdag_{}_", view_id));
+
+        self.clear();
+        self.release_layout();
+
+        let data = self.data.as_ref().expect("No data set found");
+
+        self.data = Some(js! {
+            var data = @{data};
+            var events = @{events};
+
+            data.nodes.add(events.nodes);
+            data.edges.update(events.edges);
+
+            return data;
+        });
+
+        self.apply_layered_layout();
+    }
+
+    /// Draws a fog region below each orphan event of `events_dag` in the view `view_id`, sized by
+    /// the estimated number of events missing there, replacing the previous ones. The fog takes
+    /// the place of the buttons to load the ancestors of the orphans, since clicking on a region
+    /// reveals it.
+    pub fn show_fog(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        let regions = events_dag.read().unwrap().fog_regions();
+
+        if !regions.is_empty() {
+            self.release_layout();
+        }
+
+        let data = self.data.as_ref().expect("No data set found");
+        let view_id = ViewId { id: view_id };
+        let palette = self.theme.palette();
+
+        self.data = Some(js! {
+            var view_id = @{view_id};
+            var data = @{data};
+            var regions = @{regions};
+            var fog = {
+                border: @{palette.fog.border},
+                background: @{palette.fog.background},
+                label: @{palette.fog_label}
+            };
+            var prefix = "subdag_" + view_id.id + "_";
+
+            data.nodes.remove(data.nodes.getIds({
+                filter: function (node) {
+                    return node.id.startsWith(prefix + "fog_")
+                        || node.id.startsWith(prefix + "more_of_");
+                }
+            }));
+            data.edges.remove(data.edges.getIds({
+                filter: function (edge) {
+                    return edge.id.startsWith(prefix) &&
+                        (edge.id.endsWith("_fog") || edge.id.endsWith("_more_of"));
+                }
+            }));
+
+            for (let region of regions) {
+                let size = Math.min(40 + 10 * region.estimate, 400);
+
+                data.nodes.add({
+                    id: prefix + "fog_" + region.orphan,
+                    label: "~" + region.estimate + " unknown events\nClick to reveal",
+                    title: "Missing: " + region.missing.join(", "),
+                    level: region.level - 1,
+                    color: {
+                        border: fog.border,
+                        background: fog.background
+                    },
+                    font: { color: fog.label },
+                    shapeProperties: { borderDashes: [4, 4] },
+                    widthConstraint: { minimum: size },
+                    heightConstraint: { minimum: size / 2 }
+                });
+
+                data.edges.add({
+                    id: prefix + region.orphan + "_fog",
+                    from: prefix + region.orphan,
+                    to: prefix + "fog_" + region.orphan,
+                    dashes: true,
+                    color: { color: fog.border }
+                });
+            }
+
+            return data;
+        });
+
+        self.apply_layered_layout();
+    }
+
+    /// Removes every nodes and edges from the network.
+    pub fn clear(&mut self) {
+        let data = self.data.as_ref().expect("No data set found");
+
+        self.data = Some(js! {
+            var data = @{data};
+
+            data.nodes.clear();
+            data.edges.clear();
+
+            return data;
+        });
+    }
+
     /// Updates the DAG of the view `view_id` so that each additional events in `events_dag`
     /// is added to the vis.js network.
     pub fn update_dag(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
         let events_dag = events_dag.read().unwrap();
         let backend = *self.bk_type.read().unwrap();
 
+        // The new events around the pinned event join it, the others stay out of the network
+        if self.pin_of(view_id).is_some() {
+            self.update_pinned_dag(&events_dag, view_id);
+            return;
+        }
+
         if self.earliest_events[view_id] != events_dag.earliest_events {
+            self.release_layout();
+
             let old_earliest_events = self.earliest_events[view_id].clone();
             let new_earliest_events = events_dag.earliest_events.clone();
             let old_orphan_events = self.orphan_events[view_id].clone();
@@ -332,14 +855,17 @@ impl VisJsService {
             let data = self.data.as_ref().expect("No data set found");
 
             let mut earlier_events = DataSet::new();
-            events_dag
-                .add_earlier_events_to_data_set(&mut earlier_events, old_earliest_events.clone());
+            events_dag.add_earlier_events_to_data_set(
+                &mut earlier_events,
+                old_earliest_events.clone(),
+                &self.fields_of(view_id),
+            );
             earlier_events.add_prefix(&format!("subdag_{}_", view_id));
 
             let view_id = ViewId { id: view_id };
 
             match backend {
-                BackendChoice::CS => {
+                BackendChoice::CS | BackendChoice::Admin => {
                     self.data = Some(js! {
                         var view_id = @{view_id};
                         var data = @{data};
@@ -352,8 +878,9 @@ impl VisJsService {
                             }
                         }
 
-                        data.nodes.add(ev.nodes);
-                        data.edges.add(ev.edges);
+                        // Updating rather than adding keeps the deltas idempotent when the streams overlap
+                        data.nodes.update(ev.nodes);
+                        data.edges.update(ev.edges);
 
                         // Update the position of the button to load more events
                         for (let ev of @{old_earliest_events}) {
@@ -382,8 +909,8 @@ impl VisJsService {
                         var data = @{data};
                         var ev = @{earlier_events};
 
-                        data.nodes.add(ev.nodes);
-                        data.edges.add(ev.edges);
+                        data.nodes.update(ev.nodes);
+                        data.edges.update(ev.edges);
 
                         for (let ev of @{old_orphan_events}) {
                             data.edges.remove("subdag_" + view_id.id + "_" + ev.id + "_more_of");
@@ -393,7 +920,7 @@ impl VisJsService {
                             data.nodes.add({
                                 id: "subdag_" + view_id.id + "_more_of_" + ev.id,
                                 label: "Load ancestors",
-                                level: ev.depth - 1
+                                level: ev.level - 1
                             });
 
                             data.edges.add({
@@ -413,24 +940,134 @@ impl VisJsService {
         }
 
         if self.latest_events[view_id] != events_dag.latest_events {
+            self.release_layout();
+
             let data = self.data.as_ref().expect("No data set found");
 
             let mut new_events = DataSet::new();
-            events_dag.add_new_events_to_data_set(&mut new_events, self.latest_events[0].clone());
+            events_dag.add_new_events_to_data_set(
+                &mut new_events,
+                self.latest_events[0].clone(),
+                &self.fields_of(view_id),
+            );
             new_events.add_prefix(&format!("subdag_{}_", view_id));
 
             self.data = Some(js! {
                 var data = @{data};
                 var ev = @{new_events};
 
-                data.nodes.add(ev.nodes);
-                data.edges.add(ev.edges);
+                data.nodes.update(ev.nodes);
+                data.edges.update(ev.edges);
 
                 return data;
             });
 
             self.latest_events[view_id] = events_dag.latest_events.clone();
         }
+
+        if events_dag.compresses_depths() {
+            self.relevel(&events_dag, view_id);
+        }
+
+        self.resolve_ghosts(view_id);
+        self.apply_layered_layout();
+    }
+
+    // Adds the events of `events_dag` which came within the reach of the event pinned as the
+    // root of the view `view_id` to the network.
+    fn update_pinned_dag(&mut self, events_dag: &RoomEvents, view_id: usize) {
+        if self.latest_events[view_id] == events_dag.latest_events
+            && self.earliest_events[view_id] == events_dag.earliest_events
+        {
+            return;
+        }
+
+        self.release_layout();
+
+        let mut events = self.data_set_of(events_dag, view_id);
+        events.add_prefix(&format!("subdag_{}_", view_id));
+
+        let data = self.data.as_ref().expect("No data set found");
+
+        self.data = Some(js! {
+            var data = @{data};
+            var ev = @{events};
+
+            data.nodes.update(ev.nodes);
+            data.edges.update(ev.edges);
+
+            return data;
+        });
+
+        self.earliest_events[view_id] = events_dag.earliest_events.clone();
+        self.latest_events[view_id] = events_dag.latest_events.clone();
+        self.orphan_events[view_id] = events_dag.orphan_events.clone();
+
+        self.resolve_ghosts(view_id);
+        self.apply_layered_layout();
+    }
+
+    // Moves the nodes of the view `view_id` already in the network to the levels of their events
+    // in `events_dag`, since adding events at new depths shifts the ranks of the depths above them
+    // when the depths are compressed.
+    fn relevel(&self, events_dag: &RoomEvents, view_id: usize) {
+        let data = match self.data.as_ref() {
+            Some(data) => data,
+            None => return,
+        };
+        let (ids, levels): (Vec<String>, Vec<i64>) = events_dag
+            .event_levels()
+            .into_iter()
+            .map(|(id, level)| (format!("subdag_{}_{}", view_id, id), level))
+            .unzip();
+        let levels: Vec<f64> = levels.into_iter().map(|level| level as f64).collect();
+
+        js! {
+            var data = @{data};
+            var ids = @{ids};
+            var levels = @{levels};
+
+            var moved = [];
+            for (let i = 0; i < ids.length; i++) {
+                let node = data.nodes.get(ids[i]);
+
+                if (node != null && node.level != levels[i]) {
+                    moved.push({ id: ids[i], level: levels[i] });
+                }
+            }
+
+            data.nodes.update(moved);
+        };
+    }
+
+    // Removes the ghost nodes of the view `view_id` whose event has now been received, along with
+    // the edges leading to them.
+    fn resolve_ghosts(&self, view_id: usize) {
+        let data = match self.data.as_ref() {
+            Some(data) => data,
+            None => return,
+        };
+        let view_id = ViewId { id: view_id };
+
+        js! {
+            var view_id = @{view_id};
+            var data = @{data};
+            var prefix = "subdag_" + view_id.id + "_";
+
+            var resolved = data.nodes.getIds({
+                filter: function (n) {
+                    return n.id.startsWith(prefix + "ghost_") &&
+                        data.nodes.get(prefix + n.id.substring(prefix.length + 6)) != null;
+                }
+            });
+
+            for (let ghost of resolved) {
+                data.edges.remove(data.edges.getIds({
+                    filter: function (e) { return e.to == ghost; }
+                }));
+            }
+            data.nodes.remove(resolved);
+        };
     }
 
     /// Updates the labels of the nodes corresponding to the events in `events_dag` in the view
@@ -438,9 +1075,9 @@ impl VisJsService {
     pub fn update_labels(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
         self.update_dag(events_dag.clone(), view_id);
 
-        let data = self.data.as_ref().expect("No data set found");
         let events_dag = events_dag.read().unwrap();
-        let mut new_data = events_dag.create_data_set();
+        let mut new_data = self.data_set_of(&events_dag, view_id);
+        let data = self.data.as_ref().expect("No data set found");
         new_data.add_prefix(&format!("subdag_{}_", view_id));
 
         self.data = Some(js! {
@@ -453,8 +1090,261 @@ impl VisJsService {
         });
     }
 
-    // TODO: maybe this will have to change
-    pub fn is_active(&self) -> bool {
-        self.network.is_some()
+    /// Adds every events of the view `view_id` whose depth is between `min_depth` and `max_depth`
+    /// to the network, in order to fill a region of a sampled DAG.
+    pub fn densify(
+        &mut self,
+        events_dag: Arc<RwLock<RoomEvents>>,
+        view_id: usize,
+        min_depth: i64,
+        max_depth: i64,
+    ) {
+        // The events away from the pinned event stay out of the network
+        if self.pin_of(view_id).is_some() {
+            return;
+        }
+
+        let mut events =
+            events_dag
+                .read()
+                .unwrap()
+                .densify(min_depth, max_depth, &self.fields_of(view_id));
+        events.add_prefix(&format!("subdag_{}_", view_id));
+
+        self.release_layout();
+
+        let data = self.data.as_ref().expect("No data set found");
+
+        self.data = Some(js! {
+            var data = @{data};
+            var events = @{events};
+
+            data.nodes.update(events.nodes);
+            data.edges.update(events.edges);
+
+            return data;
+        });
+
+        self.apply_layered_layout();
+    }
+
+    /// With the lazy rendering, makes the network of the view `view_id` only contain the events
+    /// whose level is within the margin of the viewport, which spans from the level `min_level`
+    /// to `max_level`. The nodes of the events leaving this window are evicted from the vis.js
+    /// data set, along with the edges between them and the other events.
+    pub fn show_depth_window(
+        &mut self,
+        events_dag: Arc<RwLock<RoomEvents>>,
+        view_id: usize,
+        min_level: i64,
+        max_level: i64,
+    ) {
+        let margin = match self.lazy_margin {
+            Some(margin) if self.pin_of(view_id).is_none() => margin,
+            _ => return,
+        };
+        let min_level = min_level - margin;
+        let max_level = max_level + margin;
+
+        let events_dag = events_dag.read().unwrap();
+        let (min_depth, max_depth) = events_dag.levels_to_depths(min_level, max_level);
+        let mut events =
+            events_dag.create_data_set_range(min_depth, max_depth, &self.fields_of(view_id));
+        events.add_prefix(&format!("subdag_{}_", view_id));
+
+        self.release_layout();
+
+        let data = self.data.as_ref().expect("No data set found");
+        let view_id = ViewId { id: view_id };
+
+        self.data = Some(js! {
+            var view_id = @{view_id};
+            var data = @{data};
+            var events = @{events};
+            var min_level = @{min_level as f64};
+            var max_level = @{max_level as f64};
+            var prefix = "subdag_" + view_id.id + "_$";
+
+            var evicted = new Set();
+            for (let node of data.nodes.get()) {
+                // The buttons for loading more events are always kept
+                if (node.id.startsWith(prefix) && (node.level < min_level || node.level > max_level)) {
+                    evicted.add(node.id);
+                }
+            }
+
+            var evicted_edges = data.edges.get({
+                filter: function (edge) {
+                    return (evicted.has(edge.from) && edge.to.startsWith(prefix))
+                        || (evicted.has(edge.to) && edge.from.startsWith(prefix));
+                }
+            });
+
+            data.edges.remove(evicted_edges.map(function (edge) { return edge.id; }));
+            data.nodes.remove(Array.from(evicted));
+
+            data.nodes.update(events.nodes);
+            data.edges.update(events.edges);
+
+            return data;
+        });
+
+        self.apply_layered_layout();
+    }
+
+    /// Hides the nodes of the events of the view `view_id` which are not in `visible`, or shows
+    /// every nodes if `visible` is `None`.
+    pub fn filter_events(&mut self, view_id: usize, visible: Option<Vec<String>>) {
+        let data = self.data.as_ref().expect("No data set found");
+        let view_id = ViewId { id: view_id };
+
+        self.data = Some(js! {
+            var view_id = @{view_id};
+            var data = @{data};
+            var visible = @{visible};
+            var prefix = "subdag_" + view_id.id + "_";
+
+            var visible_ids = null;
+            if (visible !== null) {
+                visible_ids = new Set(visible.map(function (id) { return prefix + id; }));
+            }
+
+            var updates = [];
+            for (let node of data.nodes.get()) {
+                // Only the nodes of actual events are hidden
+                if (node.id.startsWith(prefix + "$")) {
+                    updates.push({
+                        id: node.id,
+                        hidden: visible_ids !== null && !visible_ids.has(node.id)
+                    });
+                }
+            }
+            data.nodes.update(updates);
+
+            return data;
+        });
+    }
+
+    /// Moves the camera of the network so that it shows the events of the view `view_id` whose
+    /// depth is between `min_depth` and `max_depth`.
+    pub fn focus_depth_range(
+        &self,
+        events_dag: Arc<RwLock<RoomEvents>>,
+        view_id: usize,
+        min_depth: i64,
+        max_depth: i64,
+    ) {
+        let ids: Vec<String> = events_dag
+            .read()
+            .unwrap()
+            .events_in_depth_range(min_depth, max_depth)
+            .into_iter()
+            .map(|id| format!("subdag_{}_{}", view_id, id))
+            .collect();
+
+        js! {
+            var network = @{&self.network};
+            var ids = @{ids};
+
+            network.fit({
+                nodes: ids,
+                animation: true
+            });
+        };
+    }
+
+    /// Returns the IDs of the events whose nodes are currently selected in the network.
+    pub fn selected_events(&self) -> Vec<String> {
+        let ids = js! {
+            var network = @{&self.network};
+            var pref_patt = new RegExp("^subdag_[0-9]+_");
+
+            return network.getSelectedNodes()
+                .map(function (id) { return id.replace(pref_patt, ""); })
+                .filter(function (id) { return id.startsWith("$"); });
+        };
+
+        ids.try_into().unwrap_or_default()
+    }
+
+    /// Selects the nodes of the events whose IDs are in `events` in every views.
+    pub fn select_events(&self, events: &[String]) {
+        let ids: Vec<String> = (0..self.earliest_events.len())
+            .flat_map(|view_id| {
+                events
+                    .iter()
+                    .map(move |id| format!("subdag_{}_{}", view_id, id))
+            })
+            .collect();
+
+        js! {
+            var network = @{&self.network};
+            var data = @{&self.data};
+            var ids = @{ids};
+
+            network.selectNodes(ids.filter(function (id) {
+                return data.nodes.get(id) !== null;
+            }));
+        };
+    }
+
+    /// Selects the nodes of the event `event_id` in every views and moves the network to them:
+    /// it is centred on the node if there is only one, and zoomed to fit them otherwise.
+    pub fn focus_event(&self, event_id: &str) {
+        let ids: Vec<String> = (0..self.earliest_events.len())
+            .map(|view_id| format!("subdag_{}_{}", view_id, event_id))
+            .collect();
+
+        js! {
+            var network = @{&self.network};
+            var data = @{&self.data};
+            var ids = @{ids}.filter(function (id) {
+                return data.nodes.get(id) !== null;
+            });
+
+            network.selectNodes(ids);
+
+            if (ids.length == 1) {
+                network.focus(ids[0], { scale: network.getScale(), animation: true });
+            } else if (ids.length > 1) {
+                network.fit({ nodes: ids, animation: true });
+            }
+        };
+    }
+
+    /// Returns where the camera of the network looks, or `None` if there is no network yet.
+    pub fn camera(&self) -> Option<Camera> {
+        self.network.as_ref()?;
+
+        let camera: Vec<f64> = js! {
+            var network = @{&self.network};
+            var position = network.getViewPosition();
+
+            return [position.x, position.y, network.getScale()];
+        }
+        .try_into()
+        .ok()?;
+
+        match camera[..] {
+            [x, y, scale] => Some(Camera { x, y, scale }),
+            _ => None,
+        }
+    }
+
+    /// Moves the camera of the network to `camera`.
+    pub fn move_camera(&self, camera: Camera) {
+        let Camera { x, y, scale } = camera;
+
+        js! {
+            var network = @{&self.network};
+
+            if (network !== null) {
+                network.moveTo({ position: { x: @{x}, y: @{y} }, scale: @{scale} });
+            }
+        };
+    }
+
+    pub fn network(&self) -> &Option<Value> {
+        &self.network
     }
 }
@@ -0,0 +1,333 @@
+use stdweb::web;
+use yew::{html, Html};
+
+use crate::model::annotation::{Annotation, Annotations, Tag};
+use crate::model::event::format_timestamp;
+use crate::model::query::EventFilter;
+use crate::model::snapshot::{Snapshots, ViewSnapshot};
+use crate::model::workspace::{EventSet, SetOperation, Workspace};
+use crate::{Model, Msg, UICommand, UIEvent, ANNOTATIONS_KEY};
+
+/// The workspace of the user, with the values of the inputs of its panel.
+pub struct WorkspacePanel {
+    pub workspace: Workspace,
+    pub set_form: SetForm,
+    pub annotation_form: Annotation, // The note and the tag to pin to the selected events
+    pub query_form: EventFilter,     // The criteria of the events to select in bulk
+    pub query_expression: String, // The expression selecting events in bulk, for what the criteria can't express
+    pub snapshots: Snapshots,     // The named states of the views the user can switch between
+    pub snapshot_name: String,
+}
+
+impl Default for WorkspacePanel {
+    fn default() -> Self {
+        WorkspacePanel {
+            workspace: Workspace::new(),
+            set_form: SetForm::default(),
+            annotation_form: Annotation {
+                note: String::new(),
+                tag: Tag::default(),
+            },
+            query_form: EventFilter::default(),
+            query_expression: String::new(),
+            snapshots: Snapshots::new(),
+            snapshot_name: String::new(),
+        }
+    }
+}
+
+/// The values of the inputs used to build and combine the event sets of the workspace.
+#[derive(Default)]
+pub struct SetForm {
+    pub name: String,
+    pub lhs: String,
+    pub rhs: String,
+}
+
+impl Model {
+    // Saves the events selected in the DAG as an event set of the workspace, named after the
+    // set form.
+    pub(crate) fn save_selection_as_set(&mut self) {
+        if self.workspace_panel.set_form.name.is_empty() {
+            self.console.log("The event set needs a name");
+        } else if self.vis.is_active() {
+            let events: EventSet = self.vis.selected_events().into_iter().collect();
+            let description = format!(
+                "Saved {} selected events as the set {}",
+                events.len(),
+                self.workspace_panel.set_form.name
+            );
+
+            self.workspace_panel
+                .workspace
+                .add_event_set(&self.workspace_panel.set_form.name, events);
+            self.log_activity(description);
+        }
+    }
+
+    // Annotates the events selected in the DAG with the note and the tag of the annotation form.
+    pub(crate) fn annotate_selection(&mut self) {
+        if self.workspace_panel.annotation_form.note.is_empty() {
+            self.console.log("The annotation needs a note");
+        } else if self.vis.is_active() {
+            let selected = self.vis.selected_events();
+
+            for id in &selected {
+                self.workspace_panel
+                    .workspace
+                    .annotate(id, self.workspace_panel.annotation_form.clone());
+            }
+
+            self.apply_annotations();
+            self.log_activity(format!(
+                "Annotated {} events with \"{}\"",
+                selected.len(),
+                self.workspace_panel.annotation_form.note
+            ));
+        }
+    }
+
+    // Removes the annotation of the event `id` from the workspace.
+    pub(crate) fn remove_annotation(&mut self, id: String) {
+        if self
+            .workspace_panel
+            .workspace
+            .remove_annotation(&id)
+            .is_some()
+        {
+            self.apply_annotations();
+            self.log_activity(format!("Removed the annotation of {}", id));
+        }
+    }
+
+    // Puts the annotations saved in `json` in the workspace, if there were any.
+    pub(crate) fn load_annotations(&mut self, json: Option<String>) {
+        if let Some(json) = json {
+            match serde_json::from_str::<Annotations>(&json) {
+                Ok(annotations) => {
+                    self.workspace_panel.workspace.set_annotations(annotations);
+                    self.apply_annotations();
+                }
+                Err(e) => self
+                    .console
+                    .log(&format!("Couldn't load the annotations: {}", e)),
+            }
+        }
+    }
+
+    // Combines the two event sets of the set form with `op` into a new set.
+    pub(crate) fn combine_sets(&mut self, op: SetOperation) {
+        let form = &self.workspace_panel.set_form;
+
+        if form.name.is_empty() {
+            self.console.log("The resulting event set needs a name");
+        } else if self
+            .workspace_panel
+            .workspace
+            .combine(op, &form.lhs, &form.rhs, &form.name)
+            .is_none()
+        {
+            self.console.log("Unknown event set");
+        } else {
+            let description = format!(
+                "Combined the sets {} and {} with {:?} into {}",
+                form.lhs, form.rhs, op, form.name
+            );
+
+            self.log_activity(description);
+        }
+    }
+
+    // Selects the events of the set `name` in the DAG.
+    pub(crate) fn select_set(&mut self, name: String) {
+        if self.vis.is_active() {
+            if let Some(set) = self.workspace_panel.workspace.event_set(&name) {
+                let events: Vec<String> = set.iter().cloned().collect();
+
+                self.vis.select_events(&events);
+            }
+        }
+    }
+
+    // Removes the set `name` from the workspace.
+    pub(crate) fn remove_set(&mut self, name: String) {
+        if self
+            .workspace_panel
+            .workspace
+            .remove_event_set(&name)
+            .is_some()
+        {
+            self.log_activity(format!("Removed the set {}", name));
+        }
+    }
+
+    // Records `description` in the activity log of the workspace, with the current time.
+    pub(crate) fn log_activity(&mut self, description: String) {
+        let timestamp = format_timestamp(web::Date::now() as i64);
+
+        self.workspace_panel
+            .workspace
+            .log_activity(timestamp, description);
+    }
+
+    // Draws the annotations of the workspace on the DAGs of every views, and saves them.
+    pub(crate) fn apply_annotations(&mut self) {
+        for view in &self.views {
+            if let Some(events_dag) = view.get_events_dag() {
+                events_dag
+                    .write()
+                    .unwrap()
+                    .set_annotations(self.workspace_panel.workspace.annotations().clone());
+
+                if self.vis.is_active() {
+                    self.vis.update_labels(events_dag.clone(), view.get_id());
+                }
+            }
+        }
+
+        let json = serde_json::to_string(self.workspace_panel.workspace.annotations())
+            .expect("Failed to serialize the annotations");
+        self.storage.store(ANNOTATIONS_KEY, json);
+    }
+
+    pub(crate) fn display_activity_log(&self) -> Html<Self> {
+        html! {
+            <details class="activity-log",>
+                <summary>{ format!("Activity log: {} operations", self.workspace_panel.workspace.activity_log().len()) }</summary>
+
+                <textarea readonly=true, value=self.workspace_panel.workspace.activity_report(),/>
+            </details>
+        }
+    }
+
+    pub(crate) fn display_workspace(&self) -> Html<Self> {
+        let entry = |(name, count): (String, usize)| {
+            let select_name = name.clone();
+            let remove_name = name.clone();
+
+            html! {
+                <li>
+                    { format!("{} ({} events) ", name, count) }
+                    <button onclick=|_| Msg::UICmd(UICommand::SelectSet(select_name.clone())),>{ "Select" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::RemoveSet(remove_name.clone())),>{ "Remove" }</button>
+                </li>
+            }
+        };
+
+        html! {
+            <section class="workspace",>
+                <p>{ "Event sets:" }</p>
+
+                <ul>
+                    { for self.workspace_panel.workspace.event_sets().into_iter().map(entry) }
+                </ul>
+
+                <p>
+                    { "Set name: " }<input type="text", id="set-name-input", onchange=|e| Msg::UI(UIEvent::SetName(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::SaveSelectionAsSet),>{ "Save selection as set" }</button>
+                </p>
+
+                <p>
+                    <button onclick=|_| Msg::UICmd(UICommand::ExtractSelection),>{ "Open the selection in a new view" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::ExportSelection),>{ "Export the selection" }</button>
+                </p>
+
+                { self.display_query() }
+
+                { self.display_hidden_events() }
+
+                { self.display_annotations() }
+
+                { self.display_genealogy() }
+
+                { self.display_snapshots() }
+
+                <p>
+                    <input type="text", id="set-lhs-input", onchange=|e| Msg::UI(UIEvent::SetLhs(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::CombineSets(SetOperation::Union)),>{ "Union" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::CombineSets(SetOperation::Intersection)),>{ "Intersection" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::CombineSets(SetOperation::Difference)),>{ "Difference" }</button>
+                    <input type="text", id="set-rhs-input", onchange=|e| Msg::UI(UIEvent::SetRhs(e)),/>
+                </p>
+            </section>
+        }
+    }
+
+    pub(crate) fn display_annotations(&self) -> Html<Self> {
+        let id_shortener = self.id_shortener;
+        let entry = |(id, annotation): (&String, &Annotation)| {
+            let event_id = id.clone();
+            let removed = id.clone();
+
+            html! {
+                <li title=id, style=format!("border-left: 4px solid {}", annotation.tag.colour()),>
+                    <button onclick=|_| Msg::UICmd(UICommand::JumpToEvent(event_id.clone())),>{ id_shortener.shorten(id) }</button>
+                    { format!(" {} ", annotation.note) }
+                    <button onclick=|_| Msg::UICmd(UICommand::RemoveAnnotation(removed.clone())),>{ "Remove" }</button>
+                </li>
+            }
+        };
+        let tag = |tag: Tag| {
+            let id = format!("tag-{}", tag.name());
+
+            html! {
+                <>
+                    <input type="radio", id=&id, name="annotation-tag", checked=(self.workspace_panel.annotation_form.tag == tag), onclick=|_| Msg::UI(UIEvent::AnnotationTag(tag)),/>
+                    <label for=&id, style=format!("color: {}", tag.colour()),>{ tag.name() }</label>
+                </>
+            }
+        };
+
+        html! {
+            <details class="annotations",>
+                <summary>{ format!("Annotations ({})", self.workspace_panel.workspace.annotations().len()) }</summary>
+
+                <ul>
+                    { for self.workspace_panel.workspace.annotations().iter().map(entry) }
+                </ul>
+
+                <p>
+                    <input type="text", id="annotation-note", placeholder="Note", onchange=|e| Msg::UI(UIEvent::AnnotationNote(e)),/>
+                    { for Tag::ALL.iter().cloned().map(tag) }
+                    <button onclick=|_| Msg::UICmd(UICommand::AnnotateSelection),>{ "Annotate the selection" }</button>
+                </p>
+            </details>
+        }
+    }
+
+    pub(crate) fn display_snapshots(&self) -> Html<Self> {
+        let entry = |snapshot: &ViewSnapshot| {
+            let apply_name = snapshot.name.clone();
+            let remove_name = snapshot.name.clone();
+            let events: usize = snapshot
+                .views
+                .iter()
+                .map(|view| view.dag.events.len())
+                .sum();
+
+            html! {
+                <li>
+                    { format!("{} ({} views, {} events) ", snapshot.name, snapshot.views.len(), events) }
+                    <button onclick=|_| Msg::UICmd(UICommand::ApplySnapshot(apply_name.clone())),>{ "Switch to" }</button>
+                    <button onclick=|_| Msg::UICmd(UICommand::RemoveSnapshot(remove_name.clone())),>{ "Remove" }</button>
+                </li>
+            }
+        };
+
+        html! {
+            <section class="snapshots",>
+                <p>{ "Snapshots:" }</p>
+
+                <ul>
+                    { for self.workspace_panel.snapshots.iter().map(entry) }
+                </ul>
+
+                <p>
+                    <label for="snapshot-name",>{ "Snapshot name: " }</label>
+                    <input type="text", id="snapshot-name", placeholder="before", onchange=|e| Msg::UI(UIEvent::SnapshotName(e)),/>
+                    <button onclick=|_| Msg::UICmd(UICommand::TakeSnapshot),>{ "Take a snapshot" }</button>
+                </p>
+            </section>
+        }
+    }
+}